@@ -8,5 +8,6 @@ criterion_main! {
     benchmarks::serialize_block::serializing_block_group,
     benchmarks::misc::misc_group,
     benchmarks::tx_sign::tx_sign_group,
+    benchmarks::utxoset::utxoset_group,
     // benchmarks::int_to_buffer::int_to_buffer_group,
 }
@@ -4,3 +4,4 @@ pub mod misc;
 pub mod serialize_block;
 pub mod serialize_tx;
 pub mod tx_sign;
+pub mod utxoset;
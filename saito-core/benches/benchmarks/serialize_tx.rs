@@ -30,7 +30,7 @@ pub fn serialize_tx(c: &mut Criterion) {
     let buffer = tx.serialize_for_net();
     c.bench_function("deserializing tx with 0 slips and empty buffer", |b| {
         b.iter(|| {
-            black_box(Transaction::deserialize_from_net(&buffer));
+            black_box(Transaction::deserialize_from_net(&buffer).unwrap());
         });
     });
 
@@ -44,7 +44,7 @@ pub fn serialize_tx(c: &mut Criterion) {
     let buffer = tx.serialize_for_net();
     c.bench_function("deserializing tx with 0 slips and 1KB buffer", |b| {
         b.iter(|| {
-            black_box(Transaction::deserialize_from_net(&buffer));
+            black_box(Transaction::deserialize_from_net(&buffer).unwrap());
         });
     });
     let tx = generate_tx(0, 0, 10_000);
@@ -57,7 +57,7 @@ pub fn serialize_tx(c: &mut Criterion) {
     let buffer = tx.serialize_for_net();
     c.bench_function("deserializing tx with 0 slips and 10KB buffer", |b| {
         b.iter(|| {
-            black_box(Transaction::deserialize_from_net(&buffer));
+            black_box(Transaction::deserialize_from_net(&buffer).unwrap());
         });
     });
     let tx = generate_tx(0, 0, 100_000);
@@ -70,7 +70,7 @@ pub fn serialize_tx(c: &mut Criterion) {
     let buffer = tx.serialize_for_net();
     c.bench_function("deserializing tx with 0 slips and 100KB buffer", |b| {
         b.iter(|| {
-            black_box(Transaction::deserialize_from_net(&buffer));
+            black_box(Transaction::deserialize_from_net(&buffer).unwrap());
         });
     });
     let tx = generate_tx(0, 0, 1_000_000);
@@ -82,7 +82,7 @@ pub fn serialize_tx(c: &mut Criterion) {
     let buffer = tx.serialize_for_net();
     c.bench_function("deserializing tx with 0 slips and 1MB buffer", |b| {
         b.iter(|| {
-            black_box(Transaction::deserialize_from_net(&buffer));
+            black_box(Transaction::deserialize_from_net(&buffer).unwrap());
         });
     });
     let tx = generate_tx(0, 0, 10_000_000);
@@ -94,7 +94,7 @@ pub fn serialize_tx(c: &mut Criterion) {
     let buffer = tx.serialize_for_net();
     c.bench_function("deserializing tx with 0 slips and 10MB buffer", |b| {
         b.iter(|| {
-            black_box(Transaction::deserialize_from_net(&buffer));
+            black_box(Transaction::deserialize_from_net(&buffer).unwrap());
         });
     });
     let tx = generate_tx(0, 0, 100_000_000);
@@ -106,7 +106,7 @@ pub fn serialize_tx(c: &mut Criterion) {
     let buffer = tx.serialize_for_net();
     c.bench_function("deserializing tx with 0 slips and 100MB buffer", |b| {
         b.iter(|| {
-            black_box(Transaction::deserialize_from_net(&buffer));
+            black_box(Transaction::deserialize_from_net(&buffer).unwrap());
         });
     });
     let tx = generate_tx(1, 1, 0);
@@ -118,7 +118,7 @@ pub fn serialize_tx(c: &mut Criterion) {
     let buffer = tx.serialize_for_net();
     c.bench_function("deserializing tx with 1 slips and empty buffer", |b| {
         b.iter(|| {
-            black_box(Transaction::deserialize_from_net(&buffer));
+            black_box(Transaction::deserialize_from_net(&buffer).unwrap());
         });
     });
     let tx = generate_tx(10, 10, 0);
@@ -130,7 +130,7 @@ pub fn serialize_tx(c: &mut Criterion) {
     let buffer = tx.serialize_for_net();
     c.bench_function("deserializing tx with 10 slips and empty buffer", |b| {
         b.iter(|| {
-            black_box(Transaction::deserialize_from_net(&buffer));
+            black_box(Transaction::deserialize_from_net(&buffer).unwrap());
         });
     });
     let tx = generate_tx(100, 100, 0);
@@ -142,7 +142,7 @@ pub fn serialize_tx(c: &mut Criterion) {
     let buffer = tx.serialize_for_net();
     c.bench_function("deserializing tx with 100 slips and empty buffer", |b| {
         b.iter(|| {
-            black_box(Transaction::deserialize_from_net(&buffer));
+            black_box(Transaction::deserialize_from_net(&buffer).unwrap());
         });
     });
 }
@@ -0,0 +1,85 @@
+use ahash::AHashMap;
+use criterion::{black_box, criterion_group, BatchSize, Criterion};
+use rayon::prelude::*;
+use saito_core::common::defs::SaitoUTXOSetKey;
+use saito_core::core::data::utxoset::UtxoSet;
+
+const UTXOSET_SIZE: usize = 10_000_000;
+const BATCH_SIZE: usize = 100_000;
+
+fn generate_keys(count: usize) -> Vec<SaitoUTXOSetKey> {
+    (0..count)
+        .map(|_| {
+            let mut key = [0; 66];
+            key[0] = 2;
+            key[1..33].copy_from_slice(&rand::random::<[u8; 32]>());
+            key[33..41].copy_from_slice(&rand::random::<u64>().to_be_bytes());
+            key
+        })
+        .collect()
+}
+
+pub fn utxoset(c: &mut Criterion) {
+    let keys = generate_keys(UTXOSET_SIZE);
+    let batch = generate_keys(BATCH_SIZE);
+    let updates: Vec<(SaitoUTXOSetKey, bool)> = batch.iter().map(|key| (*key, true)).collect();
+
+    let mut map: AHashMap<SaitoUTXOSetKey, bool> = AHashMap::with_capacity(UTXOSET_SIZE);
+    map.extend(keys.iter().map(|key| (*key, true)));
+    let mut utxoset = UtxoSet::with_capacity(UTXOSET_SIZE);
+    utxoset.extend(keys.iter().map(|key| (*key, true)));
+
+    c.bench_function("reading 100K slips in parallel from 10M AHashMap", |b| {
+        b.iter(|| {
+            black_box(
+                keys[0..BATCH_SIZE]
+                    .par_iter()
+                    .all(|key| map.get(key).cloned().unwrap_or(false)),
+            )
+        })
+    });
+    c.bench_function("reading 100K slips in parallel from 10M UtxoSet", |b| {
+        b.iter(|| {
+            black_box(
+                keys[0..BATCH_SIZE]
+                    .par_iter()
+                    .all(|key| utxoset.get(key).cloned().unwrap_or(false)),
+            )
+        })
+    });
+
+    c.bench_function("adding 100K slips to 10M AHashMap", |b| {
+        b.iter_batched(
+            || updates.clone(),
+            |updates| {
+                for (key, spendable) in updates {
+                    map.insert(key, spendable);
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    c.bench_function("adding 100K slips to 10M UtxoSet", |b| {
+        b.iter_batched(
+            || updates.clone(),
+            |updates| utxoset.apply_updates(updates),
+            BatchSize::LargeInput,
+        )
+    });
+
+    c.bench_function("counting spendable slips of 10M AHashMap", |b| {
+        b.iter(|| black_box(map.iter().filter(|(_, spendable)| **spendable).count()))
+    });
+    c.bench_function("counting spendable slips of 10M UtxoSet", |b| {
+        b.iter(|| {
+            black_box(
+                utxoset
+                    .par_iter()
+                    .filter(|(_, spendable)| **spendable)
+                    .count(),
+            )
+        })
+    });
+}
+
+criterion_group!(utxoset_group, utxoset);
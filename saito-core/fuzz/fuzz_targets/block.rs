@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use saito_core::core::data::block::Block;
+use saito_core::core::data::storage::Storage;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(block) = Block::try_deserialize_from_net(data) {
+        assert!(Block::validate_net_buffer(data).is_ok());
+        let _ = block.transactions.len();
+    }
+    let _ = Storage::verify_block_file(data);
+});
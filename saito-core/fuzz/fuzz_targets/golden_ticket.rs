@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use saito_core::core::data::golden_ticket::GoldenTicket;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(golden_ticket) = GoldenTicket::try_deserialize_from_net(data) {
+        assert_eq!(golden_ticket.serialize_for_net(), data);
+    }
+});
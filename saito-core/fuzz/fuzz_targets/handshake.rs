@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use saito_core::core::data::msg::handshake::{HandshakeChallenge, HandshakeResponse};
+use saito_core::core::data::serialize::Serialize;
+
+fuzz_target!(|data: &[u8]| {
+    let buffer = data.to_vec();
+    let _ = HandshakeChallenge::deserialize(&buffer);
+    let _ = HandshakeResponse::deserialize(&buffer);
+});
@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use saito_core::core::data::msg::message::{Message, MessageHeader, MESSAGE_WIRE_FORMAT_VERSION};
+
+fuzz_target!(|data: (u8, &[u8])| {
+    let (message_type, payload) = data;
+    // a valid header is added to most inputs, so the payload deserializers are reached
+    let header = MessageHeader {
+        version: MESSAGE_WIRE_FORMAT_VERSION as u8,
+        flags: 0,
+        message_type,
+        request_id: 0,
+        payload_length: payload.len() as u32,
+    };
+    let mut buffer = header.serialize();
+    buffer.extend_from_slice(payload);
+    let _ = Message::deserialize(buffer);
+    let _ = Message::deserialize(payload.to_vec());
+});
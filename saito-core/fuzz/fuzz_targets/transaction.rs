@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use saito_core::core::data::transaction::Transaction;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(transaction) = Transaction::try_deserialize_from_net(data) {
+        // a valid buffer is read back as the same bytes
+        assert_eq!(transaction.serialize_for_net(), data);
+    }
+});
@@ -1,5 +1,6 @@
-use crate::common::defs::SaitoHash;
+use crate::common::defs::{SaitoHash, SaitoSignature, Timestamp};
 use crate::core::data;
+use crate::core::data::peer_requests::{PeerRequest, RequestId};
 
 #[derive(Debug)]
 pub enum NetworkEvent {
@@ -31,8 +32,25 @@ pub enum NetworkEvent {
         url: String,
     },
     BlockFetched {
+        /// id of the fetch request
+        request_id: RequestId,
         block_hash: SaitoHash,
         peer_index: u64,
         buffer: Vec<u8>,
+        /// serving node's signature over the response. None if the node didn't sign it
+        signature: Option<SaitoSignature>,
+    },
+    /// response to a request sent to the peer was received
+    PeerRequestCompleted {
+        request_id: RequestId,
+        peer_index: u64,
+        request: PeerRequest,
+        round_trip_time: Timestamp,
+    },
+    /// no response to a request sent to the peer was received within the timeout of the request type
+    PeerRequestTimedOut {
+        request_id: RequestId,
+        peer_index: u64,
+        request: PeerRequest,
     },
 }
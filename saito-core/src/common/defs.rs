@@ -1,8 +1,10 @@
 use std::cell::RefCell;
 use std::collections::VecDeque;
 
-use ahash::AHashMap;
-use tokio::sync::mpsc::Sender;
+use crate::common::lock_telemetry::{on_lock_released, LockTimer};
+use crate::common::runtime::Sender;
+
+pub use crate::core::data::utxoset::UtxoSet;
 
 pub type Currency = u128;
 pub type Timestamp = u64;
@@ -12,11 +14,12 @@ pub type SaitoPrivateKey = [u8; 32];
 pub type SaitoHash = [u8; 32];
 // pub type SlipUuid = [u8; 17];
 pub type SaitoUTXOSetKey = [u8; 66];
-pub type UtxoSet = AHashMap<SaitoUTXOSetKey, bool>;
 pub type PeerIndex = u64;
 pub type BlockId = u64;
 
 pub const BLOCK_FILE_EXTENSION: &str = ".sai";
+/// values are written to a file with this extension first and renamed once complete
+pub const TEMP_FILE_EXTENSION: &str = ".tmp";
 pub const STAT_BIN_COUNT: usize = 3;
 
 /// NOTE : Lock ordering is decided from how frequent the usage is for that resource. Please make sure to follow the order given below to avoid deadlocks
@@ -27,7 +30,8 @@ pub const STAT_BIN_COUNT: usize = 3;
 /// mempool
 /// peers
 /// wallet
-/// TODO : add a macro to check the lock ordering as a feature flag
+/// The ordering is asserted with the "locking-logs" feature. The "lock-telemetry" feature records wait and hold times
+/// of each lock and logs order violations without panicking (see common::lock_telemetry)
 ///
 
 pub const LOCK_ORDER_NETWORK_CONTROLLER: u8 = 1;
@@ -44,6 +48,7 @@ thread_local! {
 
 pub struct LockGuardWatcher {
     order: u8,
+    acquired_at: LockTimer,
 }
 
 impl Drop for LockGuardWatcher {
@@ -65,6 +70,15 @@ impl Drop for LockGuardWatcher {
                 self.order, r
             );
         });
+        #[cfg(all(feature = "lock-telemetry", not(feature = "locking-logs")))]
+        LOCK_ORDER.with(|v| {
+            let mut v = v.borrow_mut();
+            // the guard might not be the latest lock if the task moved between threads, so this doesn't assert
+            if let Some(index) = v.iter().rposition(|order| *order == self.order) {
+                v.remove(index);
+            }
+        });
+        on_lock_released(self.order, &self.acquired_at);
     }
 }
 
@@ -84,7 +98,14 @@ pub fn push_lock(order: u8) -> LockGuardWatcher {
         // println!("locking : {:?}", order);
         v.push_back(order);
     });
-    LockGuardWatcher { order }
+    #[cfg(all(feature = "lock-telemetry", not(feature = "locking-logs")))]
+    LOCK_ORDER.with(|v| {
+        v.borrow_mut().push_back(order);
+    });
+    LockGuardWatcher {
+        order,
+        acquired_at: LockTimer::start(),
+    }
 }
 
 #[macro_export]
@@ -97,7 +118,9 @@ macro_rules! lock_for_write {
             module_path!()
         );
 
+        let timer = $crate::common::lock_telemetry::LockTimer::start();
         let l = $lock.write().await;
+        $crate::common::lock_telemetry::on_lock_acquired($order, &timer, module_path!());
         let watcher = push_lock($order);
 
         #[cfg(feature = "locking-logs")]
@@ -121,7 +144,9 @@ macro_rules! lock_for_read {
             module_path!()
         );
 
+        let timer = $crate::common::lock_telemetry::LockTimer::start();
         let l = $lock.read().await;
+        $crate::common::lock_telemetry::on_lock_acquired($order, &timer, module_path!());
         let watcher = push_lock($order);
 
         #[cfg(feature = "locking-logs")]
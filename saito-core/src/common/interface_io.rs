@@ -100,6 +100,56 @@ pub trait InterfaceIO: Debug {
     /// ```
     async fn read_value(&self, key: String) -> Result<Vec<u8>, Error>;
 
+    /// Reads a part of the value with the given key, so large values can be streamed without
+    /// loading them into memory at once. Returns less than `length` bytes at the end of the value.
+    ///
+    /// The default implementation reads the whole value. Platforms with file access should override it.
+    async fn read_value_chunk(
+        &self,
+        key: String,
+        offset: u64,
+        length: u64,
+    ) -> Result<Vec<u8>, Error> {
+        let value = self.read_value(key).await?;
+        let start = (offset as usize).min(value.len());
+        let end = (offset.saturating_add(length) as usize).min(value.len());
+        Ok(value[start..end].to_vec())
+    }
+
+    /// Appends the chunk to the value with the given key. Creates the value if it doesn't exist.
+    ///
+    /// The default implementation rewrites the whole value. Platforms with file access should override it.
+    async fn append_value(&mut self, key: String, chunk: Vec<u8>) -> Result<(), Error> {
+        let mut value = vec![];
+        if self.is_existing_file(key.clone()).await {
+            value = self.read_value(key.clone()).await?;
+        }
+        value.extend(chunk);
+        self.write_value(key, value).await
+    }
+
+    /// Replaces the value with the key `to` with the value with the key `from`. Used to move a value written under a
+    /// temporary key to its final key once it's complete.
+    ///
+    /// The default implementation copies the value. Platforms with file access should override it with an atomic
+    /// rename.
+    async fn rename_value(&mut self, from: String, to: String) -> Result<(), Error> {
+        let value = self.read_value(from.clone()).await?;
+        self.write_value(to, value).await?;
+        self.remove_value(from).await
+    }
+
+    /// Removes the temporary files left in the block directory by writes interrupted by a crash. Returns the number
+    /// of removed files
+    async fn remove_temp_files(&mut self) -> Result<usize, Error> {
+        Ok(0)
+    }
+
+    /// Returns the size of the value with the given key in bytes
+    async fn get_value_size(&self, key: String) -> Result<u64, Error> {
+        Ok(self.read_value(key).await?.len() as u64)
+    }
+
     /// Loads the block path list from the persistent storage
     async fn load_block_file_list(&self) -> Result<Vec<String>, Error>;
     async fn is_existing_file(&self, key: String) -> bool;
@@ -1,5 +1,59 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::common::defs::Timestamp;
+
 /// Provides the current time in a implementation agnostic way into the core logic library. Since the core logic lib can be used on rust
 /// application as well as WASM, it needs to get the time via this trait implementation.  
 pub trait KeepTime {
     fn get_timestamp_in_ms(&self) -> u64;
 }
+
+/// Clock which only moves when it's advanced, so tests and analytics tools can replay the timing of a chain (e.g. burn
+/// fees and expiries) without sleeping. Clones share the same time, so a handle can be kept to advance the clock
+/// after a boxed clone is passed to a thread
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedClock {
+    timestamp: Arc<AtomicU64>,
+}
+
+impl SimulatedClock {
+    pub fn new(timestamp: Timestamp) -> SimulatedClock {
+        SimulatedClock {
+            timestamp: Arc::new(AtomicU64::new(timestamp)),
+        }
+    }
+
+    pub fn set_timestamp(&self, timestamp: Timestamp) {
+        self.timestamp.store(timestamp, Ordering::SeqCst);
+    }
+
+    /// moves the clock forward and returns the new time
+    pub fn advance(&self, duration_in_ms: Timestamp) -> Timestamp {
+        self.timestamp.fetch_add(duration_in_ms, Ordering::SeqCst) + duration_in_ms
+    }
+}
+
+impl KeepTime for SimulatedClock {
+    fn get_timestamp_in_ms(&self) -> u64 {
+        self.timestamp.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::keep_time::{KeepTime, SimulatedClock};
+
+    #[test]
+    fn simulated_clock_test() {
+        let clock = SimulatedClock::new(1_000);
+        let time_keeper: Box<dyn KeepTime + Send + Sync> = Box::new(clock.clone());
+        assert_eq!(time_keeper.get_timestamp_in_ms(), 1_000);
+
+        assert_eq!(clock.advance(500), 1_500);
+        assert_eq!(time_keeper.get_timestamp_in_ms(), 1_500);
+
+        clock.set_timestamp(10);
+        assert_eq!(time_keeper.get_timestamp_in_ms(), 10);
+    }
+}
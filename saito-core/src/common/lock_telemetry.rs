@@ -0,0 +1,299 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "lock-telemetry")]
+use tracing::warn;
+
+use crate::common::defs::{
+    LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_CONFIGS, LOCK_ORDER_MEMPOOL, LOCK_ORDER_NETWORK_CONTROLLER,
+    LOCK_ORDER_PEERS, LOCK_ORDER_SOCKETS, LOCK_ORDER_WALLET,
+};
+
+/// locks held longer than this are reported
+pub const LOCK_HOLD_WARNING_THRESHOLD_IN_US: u64 = 500_000;
+/// lock acquisitions waiting longer than this are reported
+pub const LOCK_WAIT_WARNING_THRESHOLD_IN_US: u64 = 1_000_000;
+/// lock order ids are 1 to 7. index 0 is unused
+const LOCK_ORDER_COUNT: usize = 8;
+
+pub fn get_lock_order_name(order: u8) -> &'static str {
+    match order {
+        LOCK_ORDER_NETWORK_CONTROLLER => "network_controller",
+        LOCK_ORDER_SOCKETS => "sockets",
+        LOCK_ORDER_CONFIGS => "configs",
+        LOCK_ORDER_BLOCKCHAIN => "blockchain",
+        LOCK_ORDER_MEMPOOL => "mempool",
+        LOCK_ORDER_PEERS => "peers",
+        LOCK_ORDER_WALLET => "wallet",
+        _ => "unknown",
+    }
+}
+
+#[derive(Debug)]
+struct LockStats {
+    acquisitions: AtomicU64,
+    total_wait_in_us: AtomicU64,
+    max_wait_in_us: AtomicU64,
+    total_hold_in_us: AtomicU64,
+    max_hold_in_us: AtomicU64,
+    long_waits: AtomicU64,
+    long_holds: AtomicU64,
+    order_violations: AtomicU64,
+}
+
+impl LockStats {
+    const EMPTY: LockStats = LockStats {
+        acquisitions: AtomicU64::new(0),
+        total_wait_in_us: AtomicU64::new(0),
+        max_wait_in_us: AtomicU64::new(0),
+        total_hold_in_us: AtomicU64::new(0),
+        max_hold_in_us: AtomicU64::new(0),
+        long_waits: AtomicU64::new(0),
+        long_holds: AtomicU64::new(0),
+        order_violations: AtomicU64::new(0),
+    };
+}
+
+/// Wait and hold times of a lock since the node started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LockStatsSnapshot {
+    pub order: u8,
+    pub acquisitions: u64,
+    pub avg_wait_in_us: u64,
+    pub max_wait_in_us: u64,
+    pub avg_hold_in_us: u64,
+    pub max_hold_in_us: u64,
+    pub long_waits: u64,
+    pub long_holds: u64,
+    pub order_violations: u64,
+}
+
+impl LockStatsSnapshot {
+    pub fn to_stat_line(&self) -> String {
+        format!(
+            "{} - acquired : {:?}, avg_wait_us : {:?}, max_wait_us : {:?}, avg_hold_us : {:?}, max_hold_us : {:?}, long_waits : {:?}, long_holds : {:?}, order_violations : {:?}",
+            format!(
+                "{:width$}",
+                format!("locks::{}", get_lock_order_name(self.order)),
+                width = 40
+            ),
+            self.acquisitions,
+            self.avg_wait_in_us,
+            self.max_wait_in_us,
+            self.avg_hold_in_us,
+            self.max_hold_in_us,
+            self.long_waits,
+            self.long_holds,
+            self.order_violations
+        )
+    }
+}
+
+/// Wait and hold times per lock order id. The node records into a single global instance when built with the
+/// `lock-telemetry` feature. Thresholds are checked as the times are recorded, so slow or misordered locks show up in
+/// the logs where they happen.
+#[derive(Debug)]
+pub struct LockTelemetry {
+    stats: [LockStats; LOCK_ORDER_COUNT],
+}
+
+impl LockTelemetry {
+    pub const fn new() -> LockTelemetry {
+        LockTelemetry {
+            stats: [LockStats::EMPTY; LOCK_ORDER_COUNT],
+        }
+    }
+
+    fn get_stats(&self, order: u8) -> Option<&LockStats> {
+        self.stats.get(order as usize)
+    }
+
+    /// Returns true if the wait was over the threshold
+    pub fn record_wait(&self, order: u8, wait_in_us: u64) -> bool {
+        let stats = match self.get_stats(order) {
+            Some(stats) => stats,
+            None => return false,
+        };
+        stats.acquisitions.fetch_add(1, Ordering::Relaxed);
+        stats
+            .total_wait_in_us
+            .fetch_add(wait_in_us, Ordering::Relaxed);
+        stats
+            .max_wait_in_us
+            .fetch_max(wait_in_us, Ordering::Relaxed);
+        if wait_in_us > LOCK_WAIT_WARNING_THRESHOLD_IN_US {
+            stats.long_waits.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+        false
+    }
+
+    /// Returns true if the lock was held over the threshold
+    pub fn record_hold(&self, order: u8, hold_in_us: u64) -> bool {
+        let stats = match self.get_stats(order) {
+            Some(stats) => stats,
+            None => return false,
+        };
+        stats
+            .total_hold_in_us
+            .fetch_add(hold_in_us, Ordering::Relaxed);
+        stats
+            .max_hold_in_us
+            .fetch_max(hold_in_us, Ordering::Relaxed);
+        if hold_in_us > LOCK_HOLD_WARNING_THRESHOLD_IN_US {
+            stats.long_holds.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+        false
+    }
+
+    /// Returns true if acquiring `order` while `held_order` is the latest held lock violates the lock ordering
+    pub fn check_order(&self, order: u8, held_order: Option<u8>) -> bool {
+        match held_order {
+            Some(held_order) if held_order >= order => {
+                if let Some(stats) = self.get_stats(order) {
+                    stats.order_violations.fetch_add(1, Ordering::Relaxed);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// stats of the locks which were acquired at least once
+    pub fn get_snapshots(&self) -> Vec<LockStatsSnapshot> {
+        self.stats
+            .iter()
+            .enumerate()
+            .filter_map(|(order, stats)| {
+                let acquisitions = stats.acquisitions.load(Ordering::Relaxed);
+                if acquisitions == 0 {
+                    return None;
+                }
+                Some(LockStatsSnapshot {
+                    order: order as u8,
+                    acquisitions,
+                    avg_wait_in_us: stats.total_wait_in_us.load(Ordering::Relaxed) / acquisitions,
+                    max_wait_in_us: stats.max_wait_in_us.load(Ordering::Relaxed),
+                    avg_hold_in_us: stats.total_hold_in_us.load(Ordering::Relaxed) / acquisitions,
+                    max_hold_in_us: stats.max_hold_in_us.load(Ordering::Relaxed),
+                    long_waits: stats.long_waits.load(Ordering::Relaxed),
+                    long_holds: stats.long_holds.load(Ordering::Relaxed),
+                    order_violations: stats.order_violations.load(Ordering::Relaxed),
+                })
+            })
+            .collect()
+    }
+}
+
+static LOCK_TELEMETRY: LockTelemetry = LockTelemetry::new();
+
+/// Stats of the node's locks. Empty unless built with the `lock-telemetry` feature
+pub fn get_lock_stats() -> Vec<LockStatsSnapshot> {
+    LOCK_TELEMETRY.get_snapshots()
+}
+
+/// Started before waiting for a lock. Doesn't read the clock unless the `lock-telemetry` feature is enabled, since
+/// `Instant` is not available on wasm
+#[derive(Debug)]
+pub struct LockTimer {
+    #[cfg(feature = "lock-telemetry")]
+    started_at: std::time::Instant,
+}
+
+impl LockTimer {
+    pub fn start() -> LockTimer {
+        LockTimer {
+            #[cfg(feature = "lock-telemetry")]
+            started_at: std::time::Instant::now(),
+        }
+    }
+}
+
+/// Called by `lock_for_read` and `lock_for_write` once the lock is acquired, before it's pushed to the lock order
+/// stack. The stack is per thread, so order violations of tasks which moved to another thread while holding a lock
+/// can be missed.
+#[allow(unused_variables)]
+pub fn on_lock_acquired(order: u8, wait_timer: &LockTimer, location: &'static str) {
+    #[cfg(feature = "lock-telemetry")]
+    {
+        let wait_in_us = wait_timer.started_at.elapsed().as_micros() as u64;
+        if LOCK_TELEMETRY.record_wait(order, wait_in_us) {
+            warn!(
+                "waited {:?} us for lock : {:?} in : {:?}",
+                wait_in_us,
+                get_lock_order_name(order),
+                location
+            );
+        }
+        let held_order =
+            crate::common::defs::LOCK_ORDER.with(|locks| locks.borrow().back().cloned());
+        if LOCK_TELEMETRY.check_order(order, held_order) {
+            warn!(
+                "lock order violation : {:?} acquired after : {:?} in : {:?}",
+                get_lock_order_name(order),
+                get_lock_order_name(held_order.unwrap()),
+                location
+            );
+        }
+    }
+}
+
+/// Called when the lock guard watcher is dropped
+#[allow(unused_variables)]
+pub fn on_lock_released(order: u8, hold_timer: &LockTimer) {
+    #[cfg(feature = "lock-telemetry")]
+    {
+        let hold_in_us = hold_timer.started_at.elapsed().as_micros() as u64;
+        if LOCK_TELEMETRY.record_hold(order, hold_in_us) {
+            warn!(
+                "lock : {:?} was held for {:?} us",
+                get_lock_order_name(order),
+                hold_in_us
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::defs::{LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_MEMPOOL, LOCK_ORDER_WALLET};
+    use crate::common::lock_telemetry::{
+        LockTelemetry, LOCK_HOLD_WARNING_THRESHOLD_IN_US, LOCK_WAIT_WARNING_THRESHOLD_IN_US,
+    };
+
+    #[test]
+    fn lock_telemetry_test() {
+        let telemetry = LockTelemetry::new();
+        assert!(telemetry.get_snapshots().is_empty());
+
+        assert!(!telemetry.record_wait(LOCK_ORDER_BLOCKCHAIN, 100));
+        assert!(!telemetry.record_hold(LOCK_ORDER_BLOCKCHAIN, 1000));
+        assert!(telemetry.record_wait(LOCK_ORDER_BLOCKCHAIN, LOCK_WAIT_WARNING_THRESHOLD_IN_US + 1));
+        assert!(telemetry.record_hold(LOCK_ORDER_BLOCKCHAIN, LOCK_HOLD_WARNING_THRESHOLD_IN_US + 1));
+        // unknown lock order ids are ignored
+        assert!(!telemetry.record_wait(100, LOCK_WAIT_WARNING_THRESHOLD_IN_US + 1));
+
+        assert!(!telemetry.check_order(LOCK_ORDER_MEMPOOL, None));
+        assert!(!telemetry.check_order(LOCK_ORDER_MEMPOOL, Some(LOCK_ORDER_BLOCKCHAIN)));
+        assert!(telemetry.check_order(LOCK_ORDER_BLOCKCHAIN, Some(LOCK_ORDER_WALLET)));
+        assert!(telemetry.check_order(LOCK_ORDER_BLOCKCHAIN, Some(LOCK_ORDER_BLOCKCHAIN)));
+
+        let snapshots = telemetry.get_snapshots();
+        assert_eq!(snapshots.len(), 1);
+        let snapshot = snapshots[0];
+        assert_eq!(snapshot.order, LOCK_ORDER_BLOCKCHAIN);
+        assert_eq!(snapshot.acquisitions, 2);
+        assert_eq!(
+            snapshot.max_wait_in_us,
+            LOCK_WAIT_WARNING_THRESHOLD_IN_US + 1
+        );
+        assert_eq!(
+            snapshot.avg_hold_in_us,
+            (LOCK_HOLD_WARNING_THRESHOLD_IN_US + 1001) / 2
+        );
+        assert_eq!(snapshot.long_waits, 1);
+        assert_eq!(snapshot.long_holds, 1);
+        assert_eq!(snapshot.order_violations, 2);
+        assert!(snapshot.to_stat_line().contains("locks::blockchain"));
+    }
+}
@@ -2,7 +2,11 @@ pub mod command;
 pub mod defs;
 pub mod interface_io;
 pub mod keep_time;
+pub mod lock_telemetry;
+pub mod network_lanes;
 pub mod process_event;
 pub mod run_task;
+pub mod runtime;
 pub mod test_io_handler;
 pub mod test_manager;
+pub mod validation_telemetry;
@@ -0,0 +1,214 @@
+use crate::common::command::NetworkEvent;
+use crate::common::runtime::{channel, Receiver, SendError, Sender};
+use crate::core::data::msg::message::{MESSAGE_HEADER_SIZE, MESSAGE_MAGIC};
+
+/// message types of the transaction traffic : transaction (6), application transaction (16), transaction batch (19),
+/// transaction batch result (20) and transaction rejection (26)
+const BULK_MESSAGE_TYPES: [u8; 5] = [6, 16, 19, 20, 26];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkEventLane {
+    /// handshakes, blocks, golden tickets, connection events and other control messages
+    Priority,
+    /// transactions received from the peers
+    Bulk,
+}
+
+impl NetworkEventLane {
+    /// Lane of the event. Incoming messages are classified by the message type in the header, without deserializing
+    /// the payload.
+    ///
+    /// Peer disconnections go to the bulk lane, so they are processed after all the messages received from the peer
+    /// before the disconnection.
+    pub fn of(event: &NetworkEvent) -> NetworkEventLane {
+        match event {
+            NetworkEvent::IncomingNetworkMessage { buffer, .. } => {
+                if buffer.len() >= MESSAGE_HEADER_SIZE
+                    && buffer[0..2] == MESSAGE_MAGIC
+                    && BULK_MESSAGE_TYPES.contains(&buffer[4])
+                {
+                    NetworkEventLane::Bulk
+                } else {
+                    NetworkEventLane::Priority
+                }
+            }
+            NetworkEvent::PeerDisconnected { .. } => NetworkEventLane::Bulk,
+            _ => NetworkEventLane::Priority,
+        }
+    }
+}
+
+/// Creates the bounded channels of the two lanes. Events of a lane are received in the order they were sent, so
+/// messages from a peer are processed in order within a lane, while the blocks and handshakes of the peer are
+/// processed ahead of its pending transactions
+pub fn network_event_lanes(
+    priority_lane_size: usize,
+    bulk_lane_size: usize,
+) -> (NetworkEventLaneSender, NetworkEventLaneReceiver) {
+    let (priority_sender, priority_receiver) = channel::<NetworkEvent>(priority_lane_size);
+    let (bulk_sender, bulk_receiver) = channel::<NetworkEvent>(bulk_lane_size);
+    (
+        NetworkEventLaneSender {
+            priority: priority_sender,
+            bulk: bulk_sender,
+            priority_event_count: 0,
+            bulk_event_count: 0,
+            max_priority_depth: 0,
+            max_bulk_depth: 0,
+        },
+        NetworkEventLaneReceiver {
+            priority: priority_receiver,
+            bulk: bulk_receiver,
+        },
+    )
+}
+
+#[derive(Debug)]
+pub struct NetworkEventLaneSender {
+    priority: Sender<NetworkEvent>,
+    bulk: Sender<NetworkEvent>,
+    pub priority_event_count: u64,
+    pub bulk_event_count: u64,
+    max_priority_depth: usize,
+    max_bulk_depth: usize,
+}
+
+impl NetworkEventLaneSender {
+    /// Waits if the lane of the event is full
+    pub async fn send(&mut self, event: NetworkEvent) -> Result<(), SendError<NetworkEvent>> {
+        match NetworkEventLane::of(&event) {
+            NetworkEventLane::Priority => {
+                self.priority.send(event).await?;
+                self.priority_event_count += 1;
+                self.max_priority_depth = self
+                    .max_priority_depth
+                    .max(self.get_depth(NetworkEventLane::Priority));
+            }
+            NetworkEventLane::Bulk => {
+                self.bulk.send(event).await?;
+                self.bulk_event_count += 1;
+                self.max_bulk_depth = self
+                    .max_bulk_depth
+                    .max(self.get_depth(NetworkEventLane::Bulk));
+            }
+        }
+        Ok(())
+    }
+
+    /// number of events waiting in the lane
+    pub fn get_depth(&self, lane: NetworkEventLane) -> usize {
+        let sender = match lane {
+            NetworkEventLane::Priority => &self.priority,
+            NetworkEventLane::Bulk => &self.bulk,
+        };
+        sender.max_capacity() - sender.capacity()
+    }
+
+    pub fn get_stats(&self) -> String {
+        format!(
+            "{} - priority : {:?} / {:?}, bulk : {:?} / {:?}, max_priority : {:?}, max_bulk : {:?}, priority_events : {:?}, bulk_events : {:?}",
+            format!("{:width$}", "network::event_lanes", width = 40),
+            self.get_depth(NetworkEventLane::Priority),
+            self.priority.max_capacity(),
+            self.get_depth(NetworkEventLane::Bulk),
+            self.bulk.max_capacity(),
+            self.max_priority_depth,
+            self.max_bulk_depth,
+            self.priority_event_count,
+            self.bulk_event_count
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct NetworkEventLaneReceiver {
+    priority: Receiver<NetworkEvent>,
+    bulk: Receiver<NetworkEvent>,
+}
+
+impl NetworkEventLaneReceiver {
+    /// Returns the next event from the priority lane, or from the bulk lane if the priority lane is empty
+    pub fn try_recv(&mut self) -> Option<NetworkEvent> {
+        if let Ok(event) = self.priority.try_recv() {
+            return Some(event);
+        }
+        self.bulk.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::command::NetworkEvent;
+    use crate::common::network_lanes::{network_event_lanes, NetworkEventLane};
+    use crate::core::data::msg::message::Message;
+    use crate::core::data::transaction::Transaction;
+
+    fn incoming(peer_index: u64, message: Message) -> NetworkEvent {
+        NetworkEvent::IncomingNetworkMessage {
+            peer_index,
+            buffer: message.serialize(),
+        }
+    }
+
+    fn get_peer_index(event: &NetworkEvent) -> u64 {
+        match event {
+            NetworkEvent::IncomingNetworkMessage { peer_index, .. }
+            | NetworkEvent::PeerDisconnected { peer_index } => *peer_index,
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn network_event_lanes_test() {
+        assert_eq!(
+            NetworkEventLane::of(&incoming(1, Message::Transaction(Transaction::default()))),
+            NetworkEventLane::Bulk
+        );
+        assert_eq!(
+            NetworkEventLane::of(&incoming(1, Message::Ping())),
+            NetworkEventLane::Priority
+        );
+        assert_eq!(
+            NetworkEventLane::of(&NetworkEvent::IncomingNetworkMessage {
+                peer_index: 1,
+                buffer: vec![6; 4],
+            }),
+            NetworkEventLane::Priority
+        );
+        assert_eq!(
+            NetworkEventLane::of(&NetworkEvent::PeerDisconnected { peer_index: 1 }),
+            NetworkEventLane::Bulk
+        );
+
+        let (mut sender, mut receiver) = network_event_lanes(10, 10);
+        sender
+            .send(incoming(1, Message::Transaction(Transaction::default())))
+            .await
+            .unwrap();
+        sender
+            .send(incoming(2, Message::Transaction(Transaction::default())))
+            .await
+            .unwrap();
+        sender.send(incoming(3, Message::Ping())).await.unwrap();
+        sender
+            .send(NetworkEvent::PeerDisconnected { peer_index: 1 })
+            .await
+            .unwrap();
+        sender.send(incoming(4, Message::Ping())).await.unwrap();
+        assert_eq!(sender.get_depth(NetworkEventLane::Priority), 2);
+        assert_eq!(sender.get_depth(NetworkEventLane::Bulk), 3);
+        assert_eq!(sender.priority_event_count, 2);
+        assert_eq!(sender.bulk_event_count, 3);
+
+        // priority events first, then the bulk events in the order they were sent
+        let mut peer_indices = vec![];
+        while let Some(event) = receiver.try_recv() {
+            peer_indices.push(get_peer_index(&event));
+        }
+        assert_eq!(peer_indices, vec![3, 4, 1, 2, 1]);
+        assert_eq!(sender.get_depth(NetworkEventLane::Bulk), 0);
+        assert!(sender
+            .get_stats()
+            .contains("max_priority : 2, max_bulk : 3"));
+    }
+}
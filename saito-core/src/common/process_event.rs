@@ -4,6 +4,16 @@ use async_trait::async_trait;
 
 use crate::common::command::NetworkEvent;
 use crate::common::defs::Timestamp;
+use crate::core::data::configuration::Configuration;
+
+/// Events sent by the node to all the event processing threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    /// thread should stop after calling `on_shutdown`
+    Shutdown,
+    /// configs were reloaded and `on_config_reload` should be called with the new values
+    ConfigReload,
+}
 
 /// Event Processing trait for the controllers. Handles both events from actions and timer
 #[async_trait]
@@ -56,4 +66,14 @@ where
     async fn on_init(&mut self);
 
     async fn on_stat_interval(&mut self, current_time: Timestamp);
+    /// Called once by the runner before the thread stops. No events are processed after this.
+    async fn on_shutdown(&mut self) {}
+    /// Called by the runner when the configs are reloaded at runtime, so the thread can apply the changed values.
+    ///
+    /// # Arguments
+    ///
+    /// * `configs`: reloaded configs
+    ///
+    /// returns: ()
+    async fn on_config_reload(&mut self, _configs: &(dyn Configuration + Send + Sync)) {}
 }
@@ -0,0 +1,136 @@
+//! Locks, channels and task helpers used by the core logic.
+//!
+//! With the default `tokio-runtime` feature these are the tokio types. With only the `wasm-runtime` feature, runtime independent
+//! implementations are used instead, so the core can be compiled to wasm32-unknown-unknown and driven by the browser's single
+//! threaded executor. Core code should import these from here instead of using tokio directly.
+
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(not(any(feature = "tokio-runtime", feature = "wasm-runtime")))]
+compile_error!("either `tokio-runtime` or `wasm-runtime` feature should be enabled for saito-core");
+
+#[cfg(feature = "tokio-runtime")]
+pub use tokio::sync::mpsc::error::SendError;
+#[cfg(feature = "tokio-runtime")]
+pub use tokio::sync::mpsc::{channel, Receiver, Sender};
+#[cfg(feature = "tokio-runtime")]
+pub use tokio::sync::{Notify, RwLock};
+
+#[cfg(all(feature = "wasm-runtime", not(feature = "tokio-runtime")))]
+pub use async_lock::RwLock;
+#[cfg(all(feature = "wasm-runtime", not(feature = "tokio-runtime")))]
+pub use wasm::{channel, Notify, Receiver, SendError, Sender};
+
+/// Runs the given future in the background
+#[cfg(feature = "tokio-runtime")]
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(future);
+}
+
+/// Runs the given future in the background
+#[cfg(all(feature = "wasm-runtime", not(feature = "tokio-runtime")))]
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+pub async fn sleep(duration: Duration) {
+    #[cfg(feature = "tokio-runtime")]
+    tokio::time::sleep(duration).await;
+
+    #[cfg(all(feature = "wasm-runtime", not(feature = "tokio-runtime")))]
+    gloo_timers::future::sleep(duration).await;
+}
+
+#[cfg(all(feature = "wasm-runtime", not(feature = "tokio-runtime")))]
+mod wasm {
+    use std::fmt::{Debug, Formatter};
+
+    use event_listener::{Event, EventListener};
+
+    /// Bounded channel with the same interface as tokio's mpsc channel
+    pub fn channel<T>(buffer: usize) -> (Sender<T>, Receiver<T>) {
+        let (sender, receiver) = async_channel::bounded(buffer);
+        (Sender { inner: sender }, Receiver { inner: receiver })
+    }
+
+    pub struct SendError<T>(pub T);
+
+    impl<T> Debug for SendError<T> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("SendError").finish_non_exhaustive()
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Sender<T> {
+        inner: async_channel::Sender<T>,
+    }
+
+    impl<T> Clone for Sender<T> {
+        fn clone(&self) -> Self {
+            Sender {
+                inner: self.inner.clone(),
+            }
+        }
+    }
+
+    impl<T> Sender<T> {
+        pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+            self.inner.send(value).await.map_err(|e| SendError(e.0))
+        }
+        /// remaining space in the channel
+        pub fn capacity(&self) -> usize {
+            self.max_capacity() - self.inner.len()
+        }
+        pub fn max_capacity(&self) -> usize {
+            self.inner.capacity().unwrap_or(usize::MAX)
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Receiver<T> {
+        inner: async_channel::Receiver<T>,
+    }
+
+    impl<T> Receiver<T> {
+        pub async fn recv(&mut self) -> Option<T> {
+            self.inner.recv().await.ok()
+        }
+        pub fn try_recv(&mut self) -> Result<T, async_channel::TryRecvError> {
+            self.inner.try_recv()
+        }
+    }
+
+    /// Notifies the waiting tasks, similar to tokio's Notify. Only `notify_waiters` is supported.
+    pub struct Notify {
+        event: Event,
+    }
+
+    impl Debug for Notify {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("Notify").finish()
+        }
+    }
+
+    impl Notify {
+        pub fn new() -> Notify {
+            Notify {
+                event: Event::new(),
+            }
+        }
+        /// listener is registered when this is called, so notifications after this call are not missed
+        pub fn notified(&self) -> EventListener {
+            self.event.listen()
+        }
+        pub fn notify_waiters(&self) {
+            self.event.notify(usize::MAX);
+        }
+    }
+}
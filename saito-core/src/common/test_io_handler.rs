@@ -83,22 +83,16 @@ pub mod test {
         }
 
         async fn read_value(&self, key: String) -> Result<Vec<u8>, Error> {
-            let result = File::open(key.clone()).await;
-            if result.is_err() {
-                error!(
-                    "error : path {:?} \r\n : {:?}",
-                    key.to_string(),
-                    result.err().unwrap()
-                );
-                todo!()
-            }
-            let mut file = result.unwrap();
+            let mut file = match File::open(key.clone()).await {
+                Ok(file) => file,
+                Err(error) => {
+                    error!("error : path {:?} \r\n : {:?}", key.to_string(), error);
+                    return Err(error);
+                }
+            };
             let mut encoded = Vec::<u8>::new();
 
-            let result = file.read_to_end(&mut encoded).await;
-            if result.is_err() {
-                todo!()
-            }
+            file.read_to_end(&mut encoded).await?;
             Ok(encoded)
         }
         async fn load_block_file_list(&self) -> Result<Vec<String>, Error> {
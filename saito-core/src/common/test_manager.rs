@@ -25,7 +25,6 @@ pub mod test {
     //
     use std::borrow::BorrowMut;
     use std::sync::Arc;
-    use std::time::{SystemTime, UNIX_EPOCH};
 
     use ahash::AHashMap;
     use tokio::sync::mpsc::{Receiver, Sender};
@@ -33,15 +32,17 @@ pub mod test {
     use tracing::{debug, info};
 
     use crate::common::defs::{
-        push_lock, Currency, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature, UtxoSet,
-        LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_MEMPOOL, LOCK_ORDER_WALLET,
+        push_lock, Currency, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature, Timestamp,
+        UtxoSet, LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_MEMPOOL, LOCK_ORDER_WALLET,
     };
+    use crate::common::keep_time::{KeepTime, SimulatedClock};
     use crate::common::test_io_handler::test::TestIOHandler;
     use crate::core::data::block::Block;
     use crate::core::data::blockchain::Blockchain;
     use crate::core::data::crypto::{generate_random_bytes, hash, verify_hash};
     use crate::core::data::golden_ticket::GoldenTicket;
     use crate::core::data::mempool::Mempool;
+    use crate::core::data::merkle::MerkleTreeBuilder;
     use crate::core::data::network::Network;
     use crate::core::data::peer_collection::PeerCollection;
     use crate::core::data::storage::Storage;
@@ -50,12 +51,9 @@ pub mod test {
     use crate::core::mining_thread::MiningEvent;
     use crate::{lock_for_read, lock_for_write};
 
-    pub fn create_timestamp() -> u64 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64
-    }
+    /// time of the simulated clock when a test starts, so the timestamps of the test chains don't depend on when the
+    /// tests are run
+    pub const TEST_START_TIMESTAMP: Timestamp = 1_700_000_000_000;
 
     pub struct TestManager {
         pub mempool_lock: Arc<RwLock<Mempool>>,
@@ -67,6 +65,7 @@ pub mod test {
         pub peers: Arc<RwLock<PeerCollection>>,
         pub sender_to_miner: Sender<MiningEvent>,
         pub receiver_in_miner: Receiver<MiningEvent>,
+        pub clock: SimulatedClock,
     }
 
     impl TestManager {
@@ -94,6 +93,7 @@ pub mod test {
                 storage: Storage::new(Box::new(TestIOHandler::new())),
                 sender_to_miner: sender_to_miner.clone(),
                 receiver_in_miner,
+                clock: SimulatedClock::new(TEST_START_TIMESTAMP),
             }
         }
 
@@ -179,7 +179,7 @@ pub mod test {
             let (blockchain, _blockchain_) =
                 lock_for_read!(self.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
 
-            let mut utxoset: UtxoSet = AHashMap::new();
+            let mut utxoset = UtxoSet::new();
             let latest_block_id = blockchain.get_latest_block_id();
 
             info!("---- check utxoset ");
@@ -468,8 +468,10 @@ pub mod test {
                 &public_key,
                 &private_key,
                 None,
+                &self.storage,
             )
-            .await;
+            .await
+            .unwrap();
             block.generate();
             block.sign(&private_key);
 
@@ -500,7 +502,7 @@ pub mod test {
         }
 
         pub async fn initialize(&mut self, vip_transactions: u64, vip_amount: Currency) {
-            let timestamp = create_timestamp();
+            let timestamp = self.clock.get_timestamp_in_ms();
             self.initialize_with_timestamp(vip_transactions, vip_amount, timestamp)
                 .await;
         }
@@ -519,6 +521,7 @@ pub mod test {
             //
             // initialize timestamp
             //
+            self.clock.set_timestamp(timestamp);
 
             //
             // reset data dirs
@@ -544,6 +547,7 @@ pub mod test {
             // create first block
             //
             let mut block = self.create_block([0; 32], timestamp, 0, 0, 0, false).await;
+            let mut merkle_tree_builder = MerkleTreeBuilder::from_transactions(&block.transactions);
 
             //
             // generate UTXO-carrying VIP transactions
@@ -552,11 +556,12 @@ pub mod test {
                 let mut tx = Transaction::create_vip_transaction(public_key, vip_amount);
                 tx.generate(&public_key, 0, 0);
                 tx.sign(&private_key);
+                merkle_tree_builder.push_transaction(&tx);
                 block.add_transaction(tx);
             }
 
-            // we have added VIP, so need to regenerate the merkle-root
-            block.merkle_root = block.generate_merkle_root();
+            // we have added VIP, so need to update the merkle-root
+            block.merkle_root = merkle_tree_builder.get_root_hash().unwrap_or([0; 32]);
             block.generate();
             block.sign(&private_key);
 
@@ -0,0 +1,276 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::span::EnteredSpan;
+use tracing::{trace_span, Span};
+
+/// Discrete stages of the block and transaction validation. The block stages run one after the other in
+/// `Block::validate`, the transaction stages run for each transaction in parallel within the `Transactions` stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationStage {
+    /// block signature against the creator's key
+    BlockSignature,
+    /// block totals, rebroadcasts and slip amounts
+    Amounts,
+    /// generating the consensus values of the block
+    ConsensusValues,
+    /// timestamp, utxoset commitment and treasuries against the previous block
+    ChainValues,
+    /// burn fee, difficulty and routing work
+    BurnFee,
+    /// golden ticket of the block against the previous block
+    GoldenTicket,
+    /// rebroadcasts of the ATR transactions
+    Atr,
+    MerkleRoot,
+    FeeTransaction,
+    /// all the transactions of the block
+    Transactions,
+    /// signatures and routing path of a transaction
+    TransactionSignatures,
+    /// inputs of a transaction against the utxoset
+    TransactionUtxo,
+}
+
+const VALIDATION_STAGES: [ValidationStage; 12] = [
+    ValidationStage::BlockSignature,
+    ValidationStage::Amounts,
+    ValidationStage::ConsensusValues,
+    ValidationStage::ChainValues,
+    ValidationStage::BurnFee,
+    ValidationStage::GoldenTicket,
+    ValidationStage::Atr,
+    ValidationStage::MerkleRoot,
+    ValidationStage::FeeTransaction,
+    ValidationStage::Transactions,
+    ValidationStage::TransactionSignatures,
+    ValidationStage::TransactionUtxo,
+];
+
+impl ValidationStage {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ValidationStage::BlockSignature => "block_signature",
+            ValidationStage::Amounts => "amounts",
+            ValidationStage::ConsensusValues => "consensus_values",
+            ValidationStage::ChainValues => "chain_values",
+            ValidationStage::BurnFee => "burn_fee",
+            ValidationStage::GoldenTicket => "golden_ticket",
+            ValidationStage::Atr => "atr",
+            ValidationStage::MerkleRoot => "merkle_root",
+            ValidationStage::FeeTransaction => "fee_transaction",
+            ValidationStage::Transactions => "transactions",
+            ValidationStage::TransactionSignatures => "transaction_signatures",
+            ValidationStage::TransactionUtxo => "transaction_utxo",
+        }
+    }
+
+    fn get_index(&self) -> usize {
+        *self as usize
+    }
+
+    /// Trace level span of the stage. Each stage has its own span name, so the stages show up as separate frames when
+    /// the spans are collected into a flamegraph. Disabled unless the `saito_core::validation` target is enabled at
+    /// the trace level
+    pub fn span(&self) -> Span {
+        match self {
+            ValidationStage::BlockSignature => {
+                trace_span!(target: "saito_core::validation", "block_signature")
+            }
+            ValidationStage::Amounts => trace_span!(target: "saito_core::validation", "amounts"),
+            ValidationStage::ConsensusValues => {
+                trace_span!(target: "saito_core::validation", "consensus_values")
+            }
+            ValidationStage::ChainValues => {
+                trace_span!(target: "saito_core::validation", "chain_values")
+            }
+            ValidationStage::BurnFee => trace_span!(target: "saito_core::validation", "burn_fee"),
+            ValidationStage::GoldenTicket => {
+                trace_span!(target: "saito_core::validation", "golden_ticket")
+            }
+            ValidationStage::Atr => trace_span!(target: "saito_core::validation", "atr"),
+            ValidationStage::MerkleRoot => {
+                trace_span!(target: "saito_core::validation", "merkle_root")
+            }
+            ValidationStage::FeeTransaction => {
+                trace_span!(target: "saito_core::validation", "fee_transaction")
+            }
+            ValidationStage::Transactions => {
+                trace_span!(target: "saito_core::validation", "transactions")
+            }
+            ValidationStage::TransactionSignatures => {
+                trace_span!(target: "saito_core::validation", "transaction_signatures")
+            }
+            ValidationStage::TransactionUtxo => {
+                trace_span!(target: "saito_core::validation", "transaction_utxo")
+            }
+        }
+    }
+
+    /// Enters the span of the stage and starts its timer. The time is recorded when the guard is dropped, including
+    /// when the validation returns early. The guard can't be held across an await since the entered span is bound to
+    /// the thread
+    pub fn start(&self) -> ValidationStageGuard {
+        ValidationStageGuard {
+            _timer: ValidationStageTimer::start(*self),
+            _span: self.span().entered(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ValidationStats {
+    runs: AtomicU64,
+    total_in_us: AtomicU64,
+    max_in_us: AtomicU64,
+}
+
+impl ValidationStats {
+    const EMPTY: ValidationStats = ValidationStats {
+        runs: AtomicU64::new(0),
+        total_in_us: AtomicU64::new(0),
+        max_in_us: AtomicU64::new(0),
+    };
+}
+
+/// Time spent in a validation stage since the node started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationStatsSnapshot {
+    pub stage: ValidationStage,
+    pub runs: u64,
+    pub total_in_us: u64,
+    pub avg_in_us: u64,
+    pub max_in_us: u64,
+}
+
+impl ValidationStatsSnapshot {
+    pub fn to_stat_line(&self) -> String {
+        format!(
+            "{} - runs : {:?}, total_ms : {:?}, avg_us : {:?}, max_us : {:?}",
+            format!(
+                "{:width$}",
+                format!("validation::{}", self.stage.get_name()),
+                width = 40
+            ),
+            self.runs,
+            self.total_in_us / 1000,
+            self.avg_in_us,
+            self.max_in_us
+        )
+    }
+}
+
+/// Run times per validation stage. The node records into a single global instance when built with the
+/// `validation-telemetry` feature. The transaction stages are recorded from the rayon threads validating the
+/// transactions, so their total can be larger than the time of the `transactions` stage.
+#[derive(Debug)]
+pub struct ValidationTelemetry {
+    stats: [ValidationStats; VALIDATION_STAGES.len()],
+}
+
+impl ValidationTelemetry {
+    pub const fn new() -> ValidationTelemetry {
+        ValidationTelemetry {
+            stats: [ValidationStats::EMPTY; VALIDATION_STAGES.len()],
+        }
+    }
+
+    pub fn record(&self, stage: ValidationStage, time_in_us: u64) {
+        let stats = &self.stats[stage.get_index()];
+        stats.runs.fetch_add(1, Ordering::Relaxed);
+        stats.total_in_us.fetch_add(time_in_us, Ordering::Relaxed);
+        stats.max_in_us.fetch_max(time_in_us, Ordering::Relaxed);
+    }
+
+    /// stats of the stages which ran at least once, in the validation order
+    pub fn get_snapshots(&self) -> Vec<ValidationStatsSnapshot> {
+        VALIDATION_STAGES
+            .iter()
+            .filter_map(|stage| {
+                let stats = &self.stats[stage.get_index()];
+                let runs = stats.runs.load(Ordering::Relaxed);
+                if runs == 0 {
+                    return None;
+                }
+                let total_in_us = stats.total_in_us.load(Ordering::Relaxed);
+                Some(ValidationStatsSnapshot {
+                    stage: *stage,
+                    runs,
+                    total_in_us,
+                    avg_in_us: total_in_us / runs,
+                    max_in_us: stats.max_in_us.load(Ordering::Relaxed),
+                })
+            })
+            .collect()
+    }
+}
+
+static VALIDATION_TELEMETRY: ValidationTelemetry = ValidationTelemetry::new();
+
+/// Stats of the validation stages. Empty unless built with the `validation-telemetry` feature
+pub fn get_validation_stats() -> Vec<ValidationStatsSnapshot> {
+    VALIDATION_TELEMETRY.get_snapshots()
+}
+
+/// Records the run time of a stage when dropped. Doesn't read the clock unless the `validation-telemetry` feature is
+/// enabled, since `Instant` is not available on wasm. Can be held across an await, unlike `ValidationStageGuard`
+#[derive(Debug)]
+pub struct ValidationStageTimer {
+    #[allow(dead_code)]
+    stage: ValidationStage,
+    #[cfg(feature = "validation-telemetry")]
+    started_at: std::time::Instant,
+}
+
+impl ValidationStageTimer {
+    pub fn start(stage: ValidationStage) -> ValidationStageTimer {
+        ValidationStageTimer {
+            stage,
+            #[cfg(feature = "validation-telemetry")]
+            started_at: std::time::Instant::now(),
+        }
+    }
+}
+
+impl Drop for ValidationStageTimer {
+    fn drop(&mut self) {
+        #[cfg(feature = "validation-telemetry")]
+        VALIDATION_TELEMETRY.record(self.stage, self.started_at.elapsed().as_micros() as u64);
+    }
+}
+
+/// Span and timer of a validation stage. Created by `ValidationStage::start`
+#[derive(Debug)]
+pub struct ValidationStageGuard {
+    _timer: ValidationStageTimer,
+    _span: EnteredSpan,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::validation_telemetry::{ValidationStage, ValidationTelemetry};
+
+    #[test]
+    fn validation_telemetry_test() {
+        let telemetry = ValidationTelemetry::new();
+        assert!(telemetry.get_snapshots().is_empty());
+
+        telemetry.record(ValidationStage::TransactionUtxo, 10);
+        telemetry.record(ValidationStage::TransactionUtxo, 30);
+        telemetry.record(ValidationStage::MerkleRoot, 2500);
+
+        // snapshots are in the validation order
+        let snapshots = telemetry.get_snapshots();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].stage, ValidationStage::MerkleRoot);
+        assert_eq!(snapshots[0].runs, 1);
+        assert_eq!(snapshots[1].stage, ValidationStage::TransactionUtxo);
+        assert_eq!(snapshots[1].runs, 2);
+        assert_eq!(snapshots[1].total_in_us, 40);
+        assert_eq!(snapshots[1].avg_in_us, 20);
+        assert_eq!(snapshots[1].max_in_us, 30);
+        assert!(snapshots[0]
+            .to_stat_line()
+            .contains("validation::merkle_root"));
+        assert!(snapshots[0].to_stat_line().contains("total_ms : 2,"));
+    }
+}
@@ -0,0 +1,147 @@
+use crate::common::defs::Currency;
+use crate::core::data::blockchain::MAX_TOKEN_SUPPLY;
+
+/// True if the amount can exist on the chain. No slip, transaction total or block total can be larger than the token
+/// supply, so larger amounts are the result of an overflow or a forged slip
+pub fn is_valid_amount(amount: Currency) -> bool {
+    amount <= MAX_TOKEN_SUPPLY
+}
+
+/// None if the sum overflows or is larger than the token supply
+pub fn checked_add_amounts(a: Currency, b: Currency) -> Option<Currency> {
+    a.checked_add(b).filter(|sum| is_valid_amount(*sum))
+}
+
+/// None if `b` is larger than `a`
+pub fn checked_sub_amounts(a: Currency, b: Currency) -> Option<Currency> {
+    a.checked_sub(b)
+}
+
+/// None if the sum overflows or is larger than the token supply at any point
+pub fn checked_sum_amounts<I>(amounts: I) -> Option<Currency>
+where
+    I: IntoIterator<Item = Currency>,
+{
+    amounts
+        .into_iter()
+        .try_fold(0, |sum: Currency, amount| checked_add_amounts(sum, amount))
+}
+
+/// floor(amount * numerator / denominator) in integers, so large amounts don't lose precision in a float. Saturates at
+/// `Currency::MAX` instead of wrapping
+pub fn multiply_ratio(amount: Currency, numerator: Currency, denominator: Currency) -> Currency {
+    assert_ne!(denominator, 0);
+    let whole = (amount / denominator).saturating_mul(numerator);
+    // remainder is less than the denominator, so this doesn't overflow for the small ratios used in the payouts
+    let fraction = (amount % denominator).saturating_mul(numerator) / denominator;
+    whole.saturating_add(fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::defs::Currency;
+    use crate::core::consensus::amounts::{
+        checked_add_amounts, checked_sub_amounts, checked_sum_amounts, is_valid_amount,
+        multiply_ratio,
+    };
+    use crate::core::data::blockchain::MAX_TOKEN_SUPPLY;
+
+    /// amounts around zero, the token supply and the integer limit, and random amounts in between
+    fn boundary_amounts() -> Vec<Currency> {
+        let mut amounts: Vec<Currency> = vec![];
+        for offset in 0..100 {
+            amounts.push(offset);
+            amounts.push(MAX_TOKEN_SUPPLY - offset);
+            amounts.push(MAX_TOKEN_SUPPLY + offset);
+            amounts.push(MAX_TOKEN_SUPPLY / 2 + offset);
+            amounts.push(Currency::MAX - offset);
+        }
+        amounts.extend((0..1000).map(|_| rand::random::<Currency>() % (MAX_TOKEN_SUPPLY + 1)));
+        amounts.extend((0..1000).map(|_| rand::random::<Currency>()));
+        amounts
+    }
+
+    #[test]
+    fn checked_amounts_test() {
+        assert!(is_valid_amount(MAX_TOKEN_SUPPLY));
+        assert!(!is_valid_amount(MAX_TOKEN_SUPPLY + 1));
+        assert_eq!(
+            checked_add_amounts(MAX_TOKEN_SUPPLY - 1, 1),
+            Some(MAX_TOKEN_SUPPLY)
+        );
+        assert_eq!(checked_add_amounts(MAX_TOKEN_SUPPLY, 1), None);
+        assert_eq!(checked_add_amounts(Currency::MAX, 1), None);
+        assert_eq!(checked_sub_amounts(1, 2), None);
+
+        let amounts = boundary_amounts();
+        for (index, a) in amounts.iter().enumerate() {
+            let b = amounts[(index * 7 + 3) % amounts.len()];
+            match checked_add_amounts(*a, b) {
+                Some(sum) => {
+                    assert!(is_valid_amount(sum));
+                    assert_eq!(sum - b, *a);
+                    assert_eq!(checked_sub_amounts(sum, b), Some(*a));
+                }
+                None => assert!(a.checked_add(b).map_or(true, |sum| sum > MAX_TOKEN_SUPPLY)),
+            }
+            match checked_sub_amounts(*a, b) {
+                Some(difference) => assert_eq!(difference + b, *a),
+                None => assert!(b > *a),
+            }
+        }
+    }
+
+    #[test]
+    fn checked_sum_amounts_test() {
+        assert_eq!(checked_sum_amounts(vec![]), Some(0));
+        assert_eq!(
+            checked_sum_amounts(vec![MAX_TOKEN_SUPPLY / 2, MAX_TOKEN_SUPPLY / 2]),
+            Some(MAX_TOKEN_SUPPLY)
+        );
+        assert_eq!(
+            checked_sum_amounts(vec![MAX_TOKEN_SUPPLY / 2, MAX_TOKEN_SUPPLY / 2, 1]),
+            None
+        );
+        // a sum which wraps around to a small value is not accepted
+        assert_eq!(
+            checked_sum_amounts(vec![Currency::MAX / 2 + 1, Currency::MAX / 2 + 1, 10]),
+            None
+        );
+
+        let amounts = boundary_amounts();
+        for window in amounts.windows(3) {
+            let expected = window
+                .iter()
+                .try_fold(0 as Currency, |sum, amount| sum.checked_add(*amount))
+                .filter(|sum| *sum <= MAX_TOKEN_SUPPLY);
+            let result = checked_sum_amounts(window.iter().cloned());
+            if window.iter().all(|amount| is_valid_amount(*amount)) {
+                assert_eq!(result, expected);
+            } else {
+                assert_eq!(result, None);
+            }
+        }
+    }
+
+    #[test]
+    fn multiply_ratio_test() {
+        assert_eq!(multiply_ratio(100, 124, 100), 124);
+        assert_eq!(multiply_ratio(101, 125, 100), 126);
+        assert_eq!(multiply_ratio(3, 1, 2), 1);
+        assert_eq!(multiply_ratio(Currency::MAX, 125, 100), Currency::MAX);
+        assert_eq!(multiply_ratio(Currency::MAX, 1, 2), Currency::MAX / 2);
+        // exact for amounts which don't fit in the mantissa of a float
+        assert_eq!(
+            multiply_ratio(MAX_TOKEN_SUPPLY + 3, 124, 100),
+            MAX_TOKEN_SUPPLY / 100 * 124 + 3
+        );
+
+        for amount in boundary_amounts() {
+            if amount <= MAX_TOKEN_SUPPLY {
+                assert_eq!(multiply_ratio(amount, 124, 100), amount * 124 / 100);
+                assert_eq!(multiply_ratio(amount, 125, 100), amount * 125 / 100);
+            }
+            assert!(multiply_ratio(amount, 99, 100) <= amount);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use ahash::AHashSet;
+
+use crate::common::defs::{Currency, SaitoHash, SaitoUTXOSetKey, UtxoSet};
+use crate::core::consensus::amounts::checked_add_amounts;
+use crate::core::data::block::Block;
+use crate::core::data::crypto::hash;
+use crate::core::data::slip::{Slip, SlipType};
+use crate::core::data::transaction::Transaction;
+
+/// fee charged for rebroadcasting a slip which falls off the genesis period
+pub const REBROADCAST_FEE: Currency = 200_000_000;
+/// part of the rebroadcast fee paid back to the slip as the staking subsidy
+pub const REBROADCAST_STAKING_SUBSIDY: Currency = 100_000_000;
+/// amount deducted from a rebroadcast slip. collected as a fee of the block which rebroadcasts it
+pub const REBROADCAST_NET_FEE: Currency = REBROADCAST_FEE - REBROADCAST_STAKING_SUBSIDY;
+
+/// Amount of the slip created when a slip with the given amount is rebroadcast. None for dust, i.e. slips which are
+/// not larger than the net fee. Dust isn't rebroadcast and is removed with the pruned block
+pub fn get_rebroadcast_amount(amount: Currency) -> Option<Currency> {
+    if amount <= REBROADCAST_NET_FEE {
+        return None;
+    }
+    Some(amount - REBROADCAST_NET_FEE)
+}
+
+/// Rebroadcasts of the slips of the block falling off the genesis period, and their totals which are committed to in
+/// the block
+#[derive(Debug, Default)]
+pub struct AtrRebroadcasts {
+    pub transactions: Vec<Transaction>,
+    pub total_slips: u64,
+    /// total amount of the slips before the fees
+    pub total_nolan: Currency,
+    /// fees of the rebroadcast slips and the dust
+    pub total_fees_nolan: Currency,
+    pub total_staking_payouts_nolan: Currency,
+    /// chained hash of the rebroadcast transactions in order
+    pub rebroadcast_hash: SaitoHash,
+}
+
+/// Creates the rebroadcast transactions for the unspent slips of the pruned block. Slips spent by the other
+/// transactions of the block being generated are skipped, so a slip is not spent twice in the same block. The pruned
+/// block has to be a full block.
+pub fn collect_rebroadcasts(
+    pruned_block: &Block,
+    utxoset: &UtxoSet,
+    spent_in_block: &AHashSet<SaitoUTXOSetKey>,
+) -> AtrRebroadcasts {
+    let mut rebroadcasts = AtrRebroadcasts::default();
+    for transaction in pruned_block.transactions.iter() {
+        for output in transaction.outputs.iter() {
+            // valid means spendable and non-zero
+            if output.amount == 0
+                || !output.validate(utxoset)
+                || spent_in_block.contains(&output.utxoset_key)
+            {
+                continue;
+            }
+            let rebroadcast_amount = match get_rebroadcast_amount(output.amount) {
+                Some(amount) => amount,
+                None => {
+                    rebroadcasts.total_fees_nolan =
+                        checked_add_amounts(rebroadcasts.total_fees_nolan, output.amount)
+                            .unwrap_or(Currency::MAX);
+                    continue;
+                }
+            };
+            rebroadcasts.total_slips += 1;
+            // amounts in the utxoset are within the token supply, so the totals of a valid chain don't overflow
+            rebroadcasts.total_nolan = checked_add_amounts(rebroadcasts.total_nolan, output.amount)
+                .unwrap_or(Currency::MAX);
+            rebroadcasts.total_fees_nolan =
+                checked_add_amounts(rebroadcasts.total_fees_nolan, REBROADCAST_FEE)
+                    .unwrap_or(Currency::MAX);
+            rebroadcasts.total_staking_payouts_nolan = checked_add_amounts(
+                rebroadcasts.total_staking_payouts_nolan,
+                REBROADCAST_STAKING_SUBSIDY,
+            )
+            .unwrap_or(Currency::MAX);
+
+            let rebroadcast_transaction = Transaction::create_rebroadcast_transaction(
+                transaction,
+                output,
+                rebroadcast_amount,
+            );
+
+            let mut vbytes: Vec<u8> = vec![];
+            vbytes.extend(&rebroadcasts.rebroadcast_hash);
+            vbytes.extend(&rebroadcast_transaction.serialize_for_signature());
+            rebroadcasts.rebroadcast_hash = hash(&vbytes);
+
+            rebroadcasts.transactions.push(rebroadcast_transaction);
+        }
+    }
+    rebroadcasts
+}
+
+/// Checks the slips of a rebroadcast transaction. It spends a single slip and creates a single ATR slip for the same
+/// key, with the net fee deducted. Whether the spent slip is unspent and belongs to the pruned block is checked with
+/// the rebroadcast hash of the block
+pub fn validate_rebroadcast_transaction(transaction: &Transaction) -> bool {
+    let (input, output): (&Slip, &Slip) = match (
+        transaction.inputs.as_slice(),
+        transaction.outputs.as_slice(),
+    ) {
+        ([input], [output]) => (input, output),
+        _ => return false,
+    };
+    output.slip_type == SlipType::ATR
+        && output.public_key == input.public_key
+        && get_rebroadcast_amount(input.amount) == Some(output.amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use ahash::AHashSet;
+
+    use crate::common::defs::{SaitoUTXOSetKey, UtxoSet};
+    use crate::core::consensus::atr::{
+        collect_rebroadcasts, get_rebroadcast_amount, validate_rebroadcast_transaction,
+        REBROADCAST_FEE, REBROADCAST_NET_FEE, REBROADCAST_STAKING_SUBSIDY,
+    };
+    use crate::core::data::block::Block;
+    use crate::core::data::slip::{Slip, SlipType};
+    use crate::core::data::transaction::{Transaction, TransactionType};
+
+    fn create_pruned_block(amounts: &[u128]) -> Block {
+        let mut block = Block::new();
+        block.id = 1;
+        let mut transaction = Transaction::default();
+        transaction.signature = [7; 64];
+        for amount in amounts {
+            let mut output = Slip::default();
+            output.public_key = [2; 33];
+            output.amount = *amount;
+            transaction.add_output(output);
+        }
+        transaction.generate(&[1; 33], 0, block.id);
+        block.transactions.push(transaction);
+        block
+    }
+
+    #[test]
+    fn rebroadcast_amount_test() {
+        assert_eq!(get_rebroadcast_amount(0), None);
+        assert_eq!(get_rebroadcast_amount(REBROADCAST_NET_FEE), None);
+        assert_eq!(get_rebroadcast_amount(REBROADCAST_NET_FEE + 1), Some(1));
+        assert_eq!(
+            get_rebroadcast_amount(REBROADCAST_FEE + 5),
+            Some(REBROADCAST_STAKING_SUBSIDY + 5)
+        );
+    }
+
+    #[test]
+    fn collect_rebroadcasts_test() {
+        let block = create_pruned_block(&[
+            REBROADCAST_FEE * 10,
+            REBROADCAST_NET_FEE,
+            REBROADCAST_FEE * 3,
+            REBROADCAST_FEE * 4,
+        ]);
+        let outputs = &block.transactions[0].outputs;
+        let mut utxoset = UtxoSet::default();
+        for output in outputs.iter() {
+            utxoset.insert(output.utxoset_key, true);
+        }
+        // already spent
+        utxoset.insert(outputs[2].utxoset_key, false);
+        // spent by another transaction in the block
+        let mut spent_in_block: AHashSet<SaitoUTXOSetKey> = Default::default();
+        spent_in_block.insert(outputs[3].utxoset_key);
+
+        let rebroadcasts = collect_rebroadcasts(&block, &utxoset, &spent_in_block);
+        assert_eq!(rebroadcasts.total_slips, 1);
+        assert_eq!(rebroadcasts.total_nolan, REBROADCAST_FEE * 10);
+        // the dust is collected with the fees
+        assert_eq!(
+            rebroadcasts.total_fees_nolan,
+            REBROADCAST_FEE + REBROADCAST_NET_FEE
+        );
+        assert_eq!(
+            rebroadcasts.total_staking_payouts_nolan,
+            REBROADCAST_STAKING_SUBSIDY
+        );
+        assert_ne!(rebroadcasts.rebroadcast_hash, [0; 32]);
+
+        let mut transaction = rebroadcasts.transactions[0].clone();
+        assert_eq!(transaction.transaction_type, TransactionType::ATR);
+        assert_eq!(transaction.signature, [7; 64]);
+        // the rebroadcast slip is spent, so it can't be spent again
+        assert_eq!(transaction.inputs[0].utxoset_key, outputs[0].utxoset_key);
+        assert_eq!(transaction.outputs[0].slip_type, SlipType::ATR);
+        assert_eq!(
+            transaction.outputs[0].amount,
+            REBROADCAST_FEE * 10 - REBROADCAST_NET_FEE
+        );
+        assert!(validate_rebroadcast_transaction(&transaction));
+
+        // the net fee is collected by the block
+        transaction.generate(&[1; 33], 0, 2);
+        assert_eq!(transaction.total_fees, REBROADCAST_NET_FEE);
+
+        transaction.outputs[0].amount += 1;
+        assert!(!validate_rebroadcast_transaction(&transaction));
+        transaction.outputs[0].amount -= 1;
+        transaction.outputs[0].public_key = [3; 33];
+        assert!(!validate_rebroadcast_transaction(&transaction));
+    }
+}
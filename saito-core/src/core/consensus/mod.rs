@@ -0,0 +1,3 @@
+pub mod amounts;
+pub mod atr;
+pub mod rewards;
@@ -0,0 +1,179 @@
+use crate::common::defs::Currency;
+use crate::core::consensus::amounts::multiply_ratio;
+use crate::core::data::block::BlockPayout;
+use crate::core::data::slip::{Slip, SlipType};
+use crate::core::data::transaction::{Transaction, TransactionType};
+
+/// fees over this percentage of the block's average income are capped
+pub const PAYOUT_CAP_THRESHOLD_PERCENT: Currency = 125;
+/// capped payouts are set to this percentage of the block's average income
+pub const PAYOUT_CAP_RATIO_PERCENT: Currency = 124;
+/// payouts up to this amount are never capped
+pub const MIN_CAPPED_PAYOUT: Currency = 50;
+
+/// Amount paid out for a block's fees once a golden ticket is found for it. Fees over 1.25 times the average income
+/// are capped to 1.24 times the average income, so a block creator can't inflate the payout by paying fees to itself.
+/// The capped amount is never more than the fees. Calculated in integers, since a float loses precision for the
+/// amounts over 2^53 nolan.
+pub fn calculate_block_payout(total_fees: Currency, avg_income: Currency) -> Currency {
+    if total_fees > multiply_ratio(avg_income, PAYOUT_CAP_THRESHOLD_PERCENT, 100)
+        && total_fees > MIN_CAPPED_PAYOUT
+    {
+        return multiply_ratio(avg_income, PAYOUT_CAP_RATIO_PERCENT, 100);
+    }
+    total_fees
+}
+
+/// Splits the payout of the previous block between the golden ticket miner and the winning router. Returns
+/// (miner payout, router payout). The router gets the odd nolan, so the shares always add up to the payout.
+pub fn split_miner_router_payout(payout: Currency) -> (Currency, Currency) {
+    let miner_payout = payout / 2;
+    (miner_payout, payout - miner_payout)
+}
+
+/// Splits the payout of an older block without a golden ticket between the staking treasury and the winning router.
+/// Returns (staking treasury share, router payout). The router gets the odd nolan.
+pub fn split_staker_router_payout(payout: Currency) -> (Currency, Currency) {
+    let staker_payout = payout / 2;
+    (staker_payout, payout - staker_payout)
+}
+
+/// Creates the fee transaction with the miner and router outputs of the payouts in order. Payouts to an empty key
+/// (e.g. the router of a block without routed fees) don't get an output.
+pub fn create_fee_transaction(payouts: &[BlockPayout]) -> Transaction {
+    let mut transaction = Transaction::default();
+    transaction.transaction_type = TransactionType::Fee;
+
+    let mut slip_index = 0;
+    for payout in payouts.iter() {
+        if payout.miner != [0; 33] {
+            let mut output = Slip::default();
+            output.public_key = payout.miner;
+            output.amount = payout.miner_payout;
+            output.slip_type = SlipType::MinerOutput;
+            output.slip_index = slip_index;
+            transaction.add_output(output);
+            slip_index += 1;
+        }
+        if payout.router != [0; 33] {
+            let mut output = Slip::default();
+            output.public_key = payout.router;
+            output.amount = payout.router_payout;
+            output.slip_type = SlipType::RouterOutput;
+            output.slip_index = slip_index;
+            transaction.add_output(output);
+            slip_index += 1;
+        }
+    }
+    transaction
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::defs::Currency;
+    use crate::core::consensus::rewards::{
+        calculate_block_payout, create_fee_transaction, split_miner_router_payout,
+        split_staker_router_payout, MIN_CAPPED_PAYOUT,
+    };
+    use crate::core::data::block::BlockPayout;
+    use crate::core::data::slip::SlipType;
+
+    /// small values exhaustively, then random values over the full range
+    fn sample_amounts() -> Vec<Currency> {
+        let mut amounts: Vec<Currency> = (0..2000).collect();
+        amounts.extend((0..2000).map(|_| rand::random::<u64>() as Currency));
+        amounts.extend((0..500).map(|_| rand::random::<Currency>()));
+        amounts.extend([u64::MAX as Currency, Currency::MAX - 1, Currency::MAX]);
+        amounts
+    }
+
+    #[test]
+    fn split_payout_test() {
+        for payout in sample_amounts() {
+            let (miner_payout, router_payout) = split_miner_router_payout(payout);
+            assert_eq!(miner_payout + router_payout, payout);
+            assert!(router_payout >= miner_payout);
+            assert!(router_payout - miner_payout <= 1);
+
+            let (staker_payout, router_payout) = split_staker_router_payout(payout);
+            assert_eq!(staker_payout + router_payout, payout);
+            assert!(router_payout - staker_payout <= 1);
+        }
+    }
+
+    #[test]
+    fn calculate_block_payout_test() {
+        assert_eq!(calculate_block_payout(125, 100), 125);
+        assert_eq!(calculate_block_payout(126, 100), 124);
+        assert_eq!(
+            calculate_block_payout(MIN_CAPPED_PAYOUT, 0),
+            MIN_CAPPED_PAYOUT
+        );
+        assert_eq!(calculate_block_payout(MIN_CAPPED_PAYOUT + 1, 0), 0);
+        // amounts which are not exact in a float
+        let avg_income: Currency = (1 << 60) + 1;
+        assert_eq!(
+            calculate_block_payout(avg_income * 2, avg_income),
+            avg_income * 124 / 100
+        );
+        assert_eq!(
+            calculate_block_payout(avg_income * 125 / 100, avg_income),
+            avg_income * 125 / 100
+        );
+
+        let fees = sample_amounts();
+        for (index, total_fees) in fees.iter().enumerate() {
+            let avg_income = fees[(index * 7 + 3) % fees.len()];
+            let payout = calculate_block_payout(*total_fees, avg_income);
+            assert!(
+                payout <= *total_fees,
+                "payout : {:?} is more than the fees : {:?} with avg income : {:?}",
+                payout,
+                total_fees,
+                avg_income
+            );
+            if *total_fees <= MIN_CAPPED_PAYOUT || *total_fees <= avg_income {
+                assert_eq!(payout, *total_fees);
+            }
+        }
+    }
+
+    #[test]
+    fn create_fee_transaction_test() {
+        let fees = sample_amounts();
+        let mut payouts = vec![];
+        let mut total_payout: Currency = 0;
+        // one payout per slip index, with amounts in the u64 range so the total doesn't overflow
+        for (index, total_fees) in fees.iter().skip(1900).take(200).enumerate() {
+            let payout = calculate_block_payout(*total_fees, fees[(index * 3) % fees.len()]);
+            let mut block_payout = BlockPayout::new();
+            block_payout.router = [2; 33];
+            if index == 0 {
+                let (miner_payout, router_payout) = split_miner_router_payout(payout);
+                block_payout.miner = [1; 33];
+                block_payout.miner_payout = miner_payout;
+                block_payout.router_payout = router_payout;
+                total_payout += payout;
+            } else {
+                let (staker_payout, router_payout) = split_staker_router_payout(payout);
+                block_payout.router_payout = router_payout;
+                block_payout.staking_treasury = staker_payout as i64;
+                total_payout += router_payout;
+            }
+            payouts.push(block_payout);
+        }
+        // fees of a block without routers are burnt
+        payouts.push(BlockPayout::new());
+
+        let transaction = create_fee_transaction(&payouts);
+        assert!(transaction.is_fee_transaction());
+        assert_eq!(transaction.outputs.len(), 201);
+        assert_eq!(transaction.outputs[0].slip_type, SlipType::MinerOutput);
+        assert_eq!(transaction.outputs[1].slip_type, SlipType::RouterOutput);
+        for (index, output) in transaction.outputs.iter().enumerate() {
+            assert_eq!(output.slip_index as usize, index);
+        }
+        let total: Currency = transaction.outputs.iter().map(|output| output.amount).sum();
+        assert_eq!(total, total_payout);
+    }
+}
@@ -1,45 +1,98 @@
 use std::ops::DerefMut;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use ahash::AHashMap;
 use async_trait::async_trait;
-use tokio::sync::mpsc::Sender;
-use tokio::sync::RwLock;
-use tracing::{debug, info, trace};
+use tracing::{debug, error, info, trace, warn};
 
 use crate::common::command::NetworkEvent;
 use crate::common::defs::{
-    push_lock, SaitoPublicKey, StatVariable, Timestamp, LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_MEMPOOL,
-    LOCK_ORDER_WALLET, STAT_BIN_COUNT,
+    push_lock, SaitoHash, SaitoPublicKey, SaitoSignature, StatVariable, Timestamp,
+    LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_MEMPOOL, LOCK_ORDER_PEERS, LOCK_ORDER_WALLET, STAT_BIN_COUNT,
 };
 use crate::common::keep_time::KeepTime;
+use crate::common::lock_telemetry::get_lock_stats;
 use crate::common::process_event::ProcessEvent;
+use crate::common::runtime::{RwLock, Sender};
+use crate::common::validation_telemetry::get_validation_stats;
+use crate::core::data::audit_log::{AuditEvent, AuditLog};
 use crate::core::data::block::Block;
+use crate::core::data::block_queue::BlockQueueStatus;
 use crate::core::data::blockchain::Blockchain;
+use crate::core::data::blockchain_snapshot::BlockchainSnapshot;
+use crate::core::data::chain_stats::BLOCK_TIME_AVERAGE_WINDOW;
+use crate::core::data::configuration::Configuration;
 use crate::core::data::crypto::hash;
 use crate::core::data::golden_ticket::GoldenTicket;
+use crate::core::data::issuance::Issuance;
+use crate::core::data::memory_report::MemoryReport;
 use crate::core::data::mempool::Mempool;
+use crate::core::data::msg::transaction_batch::{TransactionBatchResult, TransactionRejection};
 use crate::core::data::network::Network;
+use crate::core::data::peer_quorum::{ConnectionStatus, PeerQuorumConfig};
 use crate::core::data::storage::Storage;
-use crate::core::data::transaction::{Transaction, TransactionType};
-use crate::core::data::wallet::Wallet;
+use crate::core::data::transaction::{Transaction, TransactionRejectReason, TransactionType};
+use crate::core::data::transaction_index::{TransactionIndex, TRANSACTION_INDEX_FILE_PATH};
+use crate::core::data::transaction_receipts::TransactionReceipts;
+use crate::core::data::tx_generator::TransactionGenerator;
+use crate::core::data::utxo_report::{UtxoReport, UtxoReportRequest};
+use crate::core::data::wallet::{Wallet, WalletUpdate};
+use crate::core::data::webhook::Webhooks;
 use crate::core::mining_thread::MiningEvent;
 use crate::core::routing_thread::RoutingEvent;
+use crate::core::verification_thread::TransactionBatchSource;
 use crate::{lock_for_read, lock_for_write};
 
-pub const BLOCK_PRODUCING_TIMER: u64 = Duration::from_millis(100).as_millis() as u64;
-pub const SPAM_TX_PRODUCING_TIMER: u64 = Duration::from_millis(1_000).as_millis() as u64;
+/// block production is checked again after this time if the block couldn't be produced at the projected time
+pub const BLOCK_PRODUCTION_RETRY_PERIOD_IN_MS: Timestamp =
+    Duration::from_millis(1000).as_millis() as Timestamp;
+/// how often the wallet balance is checked against the utxoset
+const WALLET_CHECK_PERIOD_IN_MS: Timestamp = 60_000;
+/// golden ticket solutions tried per timer tick in dev mode. the search continues in the next tick if none is found
+const DEV_MODE_GOLDEN_TICKET_ATTEMPTS: u64 = 10_000;
+/// number of blocks added to the chain between the progress logs when reindexing
+const REINDEX_BATCH_SIZE: usize = 100;
 
 #[derive(Debug)]
 pub enum ConsensusEvent {
-    NewGoldenTicket { golden_ticket: GoldenTicket },
-    BlockFetched { peer_index: u64, block: Block },
-    NewTransaction { transaction: Transaction },
-    NewTransactions { transactions: Vec<Transaction> },
+    NewGoldenTicket {
+        golden_ticket: GoldenTicket,
+    },
+    BlockFetched {
+        peer_index: u64,
+        block: Block,
+    },
+    /// verified transaction and the index of the peer which sent it. None for transactions submitted by the node
+    NewTransaction {
+        transaction: Transaction,
+        peer_index: Option<u64>,
+    },
+    /// verified transactions of a batch. the mempool rejections are added to the result before it's sent to the
+    /// source
+    NewTransactionBatch {
+        transactions: Vec<Transaction>,
+        result: TransactionBatchResult,
+        source: TransactionBatchSource,
+    },
+    NewTransactions {
+        transactions: Vec<Transaction>,
+    },
+    RollbackChain {
+        block_id: u64,
+    },
+    ReindexChain,
+    GenerateUtxoReport {
+        request: UtxoReportRequest,
+    },
+    StartTxGeneration,
+    StopTxGeneration,
+    RescanWallet,
 }
 
 pub struct ConsensusStats {
     pub blocks_fetched: StatVariable,
+    pub rejected_blocks: StatVariable,
     pub blocks_created: StatVariable,
     pub received_tx: StatVariable,
     pub received_gts: StatVariable,
@@ -53,6 +106,11 @@ impl ConsensusStats {
                 STAT_BIN_COUNT,
                 sender.clone(),
             ),
+            rejected_blocks: StatVariable::new(
+                "consensus::rejected_blocks".to_string(),
+                STAT_BIN_COUNT,
+                sender.clone(),
+            ),
             blocks_created: StatVariable::new(
                 "consensus::blocks_created".to_string(),
                 STAT_BIN_COUNT,
@@ -78,20 +136,503 @@ pub struct ConsensusThread {
     pub blockchain: Arc<RwLock<Blockchain>>,
     pub wallet: Arc<RwLock<Wallet>>,
     pub generate_genesis_block: bool,
+    /// tokens issued in the genesis block created by this node. the test transactions are added to the genesis block
+    /// if not set
+    pub issuance: Option<Issuance>,
+    /// golden tickets are created by the consensus thread for the node's own blocks instead of the miner
+    pub dev_mode: bool,
+    /// golden tickets found by the node's miner are sent to the peers, for miner nodes which rely on upstream nodes
+    /// to produce the blocks
+    pub submit_golden_tickets: bool,
     pub sender_to_router: Sender<RoutingEvent>,
     pub sender_to_miner: Sender<MiningEvent>,
-    pub block_producing_timer: Timestamp,
-    pub tx_producing_timer: Timestamp,
-    pub create_test_tx: bool,
+    /// wallet updates are applied by the wallet updater task after the blockchain lock is released. if None, the
+    /// wallet is updated while the blockchain is updated
+    pub sender_to_wallet_updater: Option<Sender<Vec<WalletUpdate>>>,
+    /// projected time for producing the next block. None if it needs to be calculated again
+    pub next_block_production_time: Option<Timestamp>,
+    pub wallet_check_timer: Timestamp,
+    pub tx_generator: TransactionGenerator,
     pub time_keeper: Box<dyn KeepTime + Send + Sync>,
     pub network: Network,
     pub storage: Storage,
     pub stats: ConsensusStats,
-    pub txs_for_mempool: Vec<Transaction>,
+    /// transactions waiting to be added to the mempool, with the index of the peer which sent them
+    pub txs_for_mempool: Vec<(Transaction, Option<u64>)>,
     pub stat_sender: Sender<String>,
+    /// consensus decisions are recorded here. flushed to the storage on the timer
+    pub audit_log: AuditLog,
+    /// connected peers as seen by the routing thread
+    pub connection_status: Arc<Mutex<ConnectionStatus>>,
+    /// peers needed before producing blocks
+    pub block_production_quorum: PeerQuorumConfig,
+    /// status of the submitted transactions. queried by the http server
+    pub transaction_receipts: Arc<Mutex<TransactionReceipts>>,
+    /// fetched blocks waiting to be added. the routing thread stops fetching blocks while the queue is full
+    pub block_queue_status: Arc<Mutex<BlockQueueStatus>>,
+    /// view of the longest chain for the readers which shouldn't wait for the blockchain lock
+    pub blockchain_snapshot: Arc<Mutex<Arc<BlockchainSnapshot>>>,
+    /// new block, reorg and payment events are posted here
+    pub webhooks: Webhooks,
 }
 
 impl ConsensusThread {
+    /// Blocks are only bundled when the node is connected to the peers required by the quorum config, so an isolated
+    /// node doesn't produce a fork of its own
+    fn can_bundle_block(&self) -> bool {
+        if !self.block_production_quorum.is_enabled() {
+            return true;
+        }
+        match self
+            .connection_status
+            .lock()
+            .unwrap()
+            .check_quorum(&self.block_production_quorum)
+        {
+            Ok(()) => true,
+            Err(reason) => {
+                debug!("not producing blocks without peer quorum : {}", reason);
+                false
+            }
+        }
+    }
+
+    /// Loads the saved transaction index if the index is enabled. Blocks loaded from disk afterwards are
+    /// indexed again, so a missing or outdated file only makes the startup slower.
+    async fn load_transaction_index(&mut self) {
+        let (mut blockchain, _blockchain_) =
+            lock_for_write!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+        if blockchain.get_transaction_index().is_none()
+            || !self.storage.file_exists(TRANSACTION_INDEX_FILE_PATH).await
+        {
+            return;
+        }
+        match self.storage.read(TRANSACTION_INDEX_FILE_PATH).await {
+            Ok(buffer) => {
+                blockchain.set_transaction_index(TransactionIndex::deserialize_from_disk(&buffer));
+            }
+            Err(error) => {
+                warn!("loading transaction index failed : {:?}", error);
+            }
+        }
+    }
+
+    /// Creates a golden ticket for the latest block in dev mode, so a single node keeps producing blocks without the
+    /// miner
+    /// Adds the transactions received since the last tick to the mempool and routes them to peers. The block
+    /// production time is projected again since the routing work in the mempool has changed.
+    /// Adds golden tickets to the mempool and queues the other transactions to be added on the next tick
+    async fn queue_transaction(&mut self, transaction: Transaction, peer_index: Option<u64>) {
+        if let TransactionType::GoldenTicket = transaction.transaction_type {
+            let (mut mempool, _mempool_) = lock_for_write!(self.mempool, LOCK_ORDER_MEMPOOL);
+
+            self.stats.received_gts.increment();
+            let golden_ticket = GoldenTicket::deserialize_from_net(&transaction.message);
+            if mempool.add_golden_ticket(transaction).await {
+                self.audit_log.record(
+                    self.time_keeper.get_timestamp_in_ms(),
+                    AuditEvent::golden_ticket_accepted(&golden_ticket),
+                );
+            }
+            self.next_block_production_time = None;
+        } else {
+            self.txs_for_mempool.push((transaction, peer_index));
+        }
+    }
+
+    /// Returns the transactions which were not added. The peers which sent them are told why
+    async fn add_received_transactions_to_mempool(&mut self) -> Vec<TransactionRejection> {
+        let mut rejections = vec![];
+        let mut peer_rejections = vec![];
+        {
+            let (mut mempool, _mempool_) = lock_for_write!(self.mempool, LOCK_ORDER_MEMPOOL);
+            let mut results = Vec::with_capacity(self.txs_for_mempool.len());
+            for (tx, peer_index) in self.txs_for_mempool.iter() {
+                if let TransactionType::GoldenTicket = tx.transaction_type {
+                    unreachable!("golden tickets shouldn't be here");
+                } else {
+                    let result = mempool.add_transaction(tx.clone()).await;
+                    if let Some(reason) = result.get_reject_reason() {
+                        debug!(
+                            "transaction : {:?} not added to mempool : {:?}",
+                            hex::encode(tx.signature),
+                            reason
+                        );
+                        let rejection = TransactionRejection {
+                            signature: tx.signature,
+                            reason,
+                        };
+                        if let Some(peer_index) = peer_index {
+                            peer_rejections.push((*peer_index, rejection));
+                        }
+                        rejections.push(rejection);
+                    }
+                    results.push((tx.signature, result));
+                }
+            }
+            let time = self.time_keeper.get_timestamp_in_ms();
+            let mut receipts = self.transaction_receipts.lock().unwrap();
+            for (signature, result) in results {
+                receipts.on_transaction_added(signature, result, time);
+            }
+            // replaced transactions are not in the mempool anymore
+            receipts.drop_evicted_transactions(&mempool, time);
+        }
+        for (peer_index, rejection) in peer_rejections {
+            self.sender_to_router
+                .send(RoutingEvent::TransactionRejected {
+                    peer_index,
+                    rejection,
+                })
+                .await
+                .unwrap();
+        }
+        for (tx, _) in self.txs_for_mempool.drain(..) {
+            self.network.propagate_transaction(&tx).await;
+        }
+        self.next_block_production_time = None;
+        rejections
+    }
+
+    /// Adds the verified transactions of a batch to the mempool right away, so the result sent to the source includes
+    /// the mempool rejections
+    async fn add_transaction_batch_to_mempool(
+        &mut self,
+        transactions: Vec<Transaction>,
+        mut result: TransactionBatchResult,
+        source: TransactionBatchSource,
+    ) {
+        self.stats
+            .received_tx
+            .increment_by(transactions.len() as u64);
+        for transaction in transactions {
+            self.queue_transaction(transaction, None).await;
+        }
+        let rejections: AHashMap<SaitoSignature, TransactionRejectReason> = self
+            .add_received_transactions_to_mempool()
+            .await
+            .into_iter()
+            .map(|rejection| (rejection.signature, rejection.reason))
+            .collect();
+        for transaction_result in result.results.iter_mut() {
+            if transaction_result.rejection.is_none() {
+                transaction_result.rejection =
+                    rejections.get(&transaction_result.signature).copied();
+            }
+        }
+        match source {
+            TransactionBatchSource::Peer(peer_index) => {
+                self.sender_to_router
+                    .send(RoutingEvent::TransactionBatchVerified { peer_index, result })
+                    .await
+                    .unwrap();
+            }
+            TransactionBatchSource::Local(sender) => {
+                // the caller might not be waiting for the result anymore
+                let _ = sender.send(result).await;
+            }
+        }
+    }
+
+    /// Returns the time at which the routing work in the mempool satisfies the burnfee, so the mempool is not checked
+    /// at every tick. Calculated again when the mempool or the latest block changes.
+    async fn get_next_block_production_time(&mut self, current_time: Timestamp) -> Timestamp {
+        if let Some(time) = self.next_block_production_time {
+            return time;
+        }
+        let time;
+        {
+            let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            let (mempool, _mempool_) = lock_for_read!(self.mempool, LOCK_ORDER_MEMPOOL);
+            time = mempool
+                .get_projected_block_production_time(&blockchain)
+                .unwrap_or(current_time + BLOCK_PRODUCTION_RETRY_PERIOD_IN_MS);
+        }
+        trace!(
+            "next block production projected in : {:?} ms",
+            time.saturating_sub(current_time)
+        );
+        self.next_block_production_time = Some(time);
+        time
+    }
+
+    async fn add_dev_mode_golden_ticket(&mut self) {
+        let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+        let latest_block = match blockchain.get_latest_block() {
+            Some(block) => block,
+            None => return,
+        };
+        let (mut mempool, _mempool_) = lock_for_write!(self.mempool, LOCK_ORDER_MEMPOOL);
+        if mempool.golden_tickets.contains_key(&latest_block.hash) {
+            return;
+        }
+        let (wallet, _wallet_) = lock_for_read!(self.wallet, LOCK_ORDER_WALLET);
+        let golden_ticket = GoldenTicket::search(
+            latest_block.hash,
+            latest_block.difficulty,
+            wallet.public_key,
+            DEV_MODE_GOLDEN_TICKET_ATTEMPTS,
+        );
+        if let Some(golden_ticket) = golden_ticket {
+            debug!(
+                "dev mode golden ticket created for block : {:?} - {:?}",
+                latest_block.id,
+                hex::encode(latest_block.hash)
+            );
+            let audit_event = AuditEvent::golden_ticket_accepted(&golden_ticket);
+            let transaction = Wallet::create_golden_ticket_transaction(
+                golden_ticket,
+                &wallet.public_key,
+                &wallet.private_key,
+            )
+            .await;
+            if mempool.add_golden_ticket(transaction).await {
+                self.audit_log
+                    .record(self.time_keeper.get_timestamp_in_ms(), audit_event);
+            }
+        }
+    }
+
+    /// Publishes the number of blocks left in the mempool queue after adding the blocks to the blockchain
+    async fn update_block_queue_status(&self) {
+        let queued_block_count;
+        {
+            let (mempool, _mempool_) = lock_for_read!(self.mempool, LOCK_ORDER_MEMPOOL);
+            queued_block_count = mempool.blocks_queue.len();
+        }
+        self.block_queue_status
+            .lock()
+            .unwrap()
+            .set_queued_block_count(queued_block_count);
+    }
+
+    /// Publishes the longest chain after the last blockchain update. The snapshot is only copied if a reader still
+    /// holds the previous one
+    fn update_blockchain_snapshot(&self, blockchain: &Blockchain) {
+        let mut snapshot = self.blockchain_snapshot.lock().unwrap();
+        Arc::make_mut(&mut snapshot).update(blockchain);
+    }
+
+    /// Sends the wallet updates taken from the blockchain to the wallet updater. Should be called after the blockchain
+    /// lock is released, so waiting for a full queue doesn't block the threads reading the blockchain. Updates are
+    /// sent in the order they happened, so reorgs are applied to the wallet in the same order.
+    async fn send_wallet_updates(&mut self, updates: Vec<WalletUpdate>) {
+        if updates.is_empty() || self.sender_to_wallet_updater.is_none() {
+            return;
+        }
+        let count = updates.len();
+        let sender = self.sender_to_wallet_updater.as_ref().unwrap();
+        if sender.send(updates).await.is_err() {
+            error!(
+                "failed sending {:?} wallet updates to the wallet updater. rescanning the wallet",
+                count
+            );
+            self.rescan_wallet().await;
+        }
+    }
+
+    /// Applies the blocks added to and removed from the longest chain by the last blockchain update to the
+    /// transaction receipts and the webhooks
+    async fn process_longest_chain_updates(&self, blockchain: &mut Blockchain) {
+        let updates = blockchain.take_longest_chain_updates();
+        if updates.is_empty() {
+            return;
+        }
+        self.update_transaction_receipts(blockchain, &updates).await;
+        self.notify_webhooks(blockchain, &updates).await;
+    }
+
+    /// Pending transactions which were removed from the mempool by the update are dropped
+    async fn update_transaction_receipts(
+        &self,
+        blockchain: &Blockchain,
+        updates: &[(SaitoHash, bool)],
+    ) {
+        let time = self.time_keeper.get_timestamp_in_ms();
+        let (mempool, _mempool_) = lock_for_read!(self.mempool, LOCK_ORDER_MEMPOOL);
+        let mut receipts = self.transaction_receipts.lock().unwrap();
+        for (block_hash, longest_chain) in updates.iter() {
+            if let Some(block) = blockchain.get_block(block_hash) {
+                if *longest_chain {
+                    receipts.on_block_added_to_longest_chain(block, time);
+                } else {
+                    receipts.on_block_removed_from_longest_chain(block, time);
+                }
+            }
+        }
+        receipts.set_latest_block_id(blockchain.get_latest_block_id());
+        receipts.drop_evicted_transactions(&mempool, time);
+    }
+
+    async fn notify_webhooks(&self, blockchain: &Blockchain, updates: &[(SaitoHash, bool)]) {
+        if !self.webhooks.is_enabled() {
+            return;
+        }
+        let public_key = if self.webhooks.is_payment_notification_enabled() {
+            let (wallet, _wallet_) = lock_for_read!(self.wallet, LOCK_ORDER_WALLET);
+            Some(wallet.public_key)
+        } else {
+            None
+        };
+        let blocks: Vec<(&Block, bool)> = updates
+            .iter()
+            .filter_map(|(block_hash, longest_chain)| {
+                Some((blockchain.get_block(block_hash)?, *longest_chain))
+            })
+            .collect();
+        let events = self.webhooks.get_chain_events(&blocks, public_key.as_ref());
+        self.webhooks
+            .notify_all(self.time_keeper.get_timestamp_in_ms(), events);
+    }
+
+    /// Rebuilds the wallet slips from the longest chain. Needed after importing keys into the wallet
+    async fn rescan_wallet(&mut self) {
+        let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+        let (mut wallet, _wallet_) = lock_for_write!(self.wallet, LOCK_ORDER_WALLET);
+        if let Err(error) = wallet.rescan(&blockchain, &self.storage).await {
+            warn!("failed rescanning the wallet : {:?}", error);
+        }
+    }
+
+    /// Clears the chain state and adds the blocks from the block files again in id order. Used after the block files
+    /// were changed manually, e.g. copied from another node or forks deleted
+    async fn reindex_chain(&mut self) {
+        info!("reindexing the chain from the block files");
+        {
+            let (mut blockchain, _blockchain_) =
+                lock_for_write!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            blockchain.reset_chain_state();
+        }
+        let blocks = self.storage.load_blocks_for_reindex().await;
+        let total = blocks.len();
+        let mut reindexed = 0;
+        let mut blocks = blocks.into_iter();
+        while reindexed < total {
+            let wallet_updates;
+            {
+                let (mut mempool, _mempool_) = lock_for_write!(self.mempool, LOCK_ORDER_MEMPOOL);
+                for block in blocks.by_ref().take(REINDEX_BATCH_SIZE) {
+                    mempool.add_block(block);
+                    reindexed += 1;
+                }
+            }
+            {
+                let (mut blockchain, _blockchain_) =
+                    lock_for_write!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+                blockchain
+                    .add_blocks_from_mempool(
+                        self.mempool.clone(),
+                        &self.network,
+                        &mut self.storage,
+                        self.sender_to_miner.clone(),
+                    )
+                    .await;
+                wallet_updates = blockchain.take_wallet_updates();
+                self.process_longest_chain_updates(&mut blockchain).await;
+                self.update_blockchain_snapshot(&blockchain);
+                self.audit_log.record_all(
+                    self.time_keeper.get_timestamp_in_ms(),
+                    blockchain.take_audit_events(),
+                );
+            }
+            self.send_wallet_updates(wallet_updates).await;
+            info!(
+                "reindexed {:?} of {:?} block files ({:?}%)",
+                reindexed,
+                total,
+                reindexed * 100 / total
+            );
+        }
+        self.storage.clear_loaded_block_files();
+        self.rescan_wallet().await;
+        {
+            let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            info!(
+                "chain reindexed with {:?} blocks up to block : {:?} - {:?}",
+                blockchain.blocks.len(),
+                blockchain.get_latest_block_id(),
+                hex::encode(blockchain.get_latest_block_hash())
+            );
+        }
+        self.next_block_production_time = None;
+        self.sender_to_router
+            .send(RoutingEvent::BlockchainRolledBack)
+            .await
+            .unwrap();
+    }
+
+    /// Rescans the wallet if the balance doesn't match the unspent slips in the utxoset
+    async fn check_wallet_consistency(&mut self) {
+        {
+            let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            if !blockchain.wallet_tracking_enabled {
+                return;
+            }
+            let (wallet, _wallet_) = lock_for_read!(self.wallet, LOCK_ORDER_WALLET);
+            // the balance is checked after the wallet has applied the pending updates
+            if wallet.get_last_update_sequence() != blockchain.get_wallet_update_sequence()
+                || wallet.is_balance_consistent(&blockchain.utxoset)
+            {
+                return;
+            }
+        }
+        warn!("wallet balance is inconsistent with the blockchain. rescanning the wallet");
+        self.rescan_wallet().await;
+    }
+
+    /// Requests the blocks which were corrupted on disk from the fastest connected peer
+    async fn refetch_quarantined_blocks(&mut self) {
+        if !self.storage.has_quarantined_blocks() {
+            return;
+        }
+        let peer_index;
+        {
+            let (peers, _peers_) = lock_for_read!(self.network.peers, LOCK_ORDER_PEERS);
+            // latency is only recorded after the handshake, so these peers are ready to serve blocks
+            peer_index = peers
+                .get_peers_by_latency()
+                .first()
+                .map(|(peer_index, _)| *peer_index);
+        }
+        if peer_index.is_none() {
+            trace!("no peers available to fetch quarantined blocks");
+            return;
+        }
+        let peer_index = peer_index.unwrap();
+        for block_hash in self.storage.take_quarantined_blocks() {
+            info!(
+                "fetching quarantined block : {:?} from peer : {:?}",
+                hex::encode(block_hash),
+                peer_index
+            );
+            self.network
+                .process_incoming_block_hash(block_hash, peer_index, self.blockchain.clone())
+                .await;
+        }
+    }
+
+    async fn add_issuance_transaction(
+        issuance: &Issuance,
+        mempool: Arc<RwLock<Mempool>>,
+        wallet: Arc<RwLock<Wallet>>,
+        blockchain: Arc<RwLock<Blockchain>>,
+    ) {
+        let private_key;
+        {
+            let (wallet, _wallet_) = lock_for_read!(wallet, LOCK_ORDER_WALLET);
+            private_key = wallet.private_key;
+        }
+
+        let (blockchain, _blockchain_) = lock_for_read!(blockchain, LOCK_ORDER_BLOCKCHAIN);
+        let (mut mempool, _mempool_) = lock_for_write!(mempool, LOCK_ORDER_MEMPOOL);
+
+        let mut transaction = issuance.create_transaction();
+        transaction.sign(&private_key);
+        mempool
+            .add_transaction_if_validates(transaction, &blockchain)
+            .await;
+    }
+
     async fn generate_spammer_init_tx(
         mempool: Arc<RwLock<Mempool>>,
         wallet: Arc<RwLock<Wallet>>,
@@ -136,10 +677,11 @@ impl ConsensusThread {
             );
         }
     }
-    /// Test method to generate test transactions
+    /// Generates test transactions with the configured transaction generator and adds them to the mempool
     ///
     /// # Arguments
     ///
+    /// * `tx_generator`:
     /// * `mempool`:
     /// * `wallet`:
     /// * `blockchain`:
@@ -152,75 +694,36 @@ impl ConsensusThread {
     ///
     /// ```
     async fn generate_tx(
+        tx_generator: &mut TransactionGenerator,
         mempool: Arc<RwLock<Mempool>>,
         wallet: Arc<RwLock<Wallet>>,
         blockchain: Arc<RwLock<Blockchain>>,
     ) {
         info!("generating mock transactions");
 
-        let txs_to_generate = 10;
-        let bytes_per_tx = 1024;
-        let public_key;
-        let private_key;
-
-        {
-            let (wallet, _wallet_) = lock_for_read!(wallet, LOCK_ORDER_WALLET);
-            public_key = wallet.public_key;
-            private_key = wallet.private_key;
-        }
-
         let (blockchain, _blockchain_) = lock_for_read!(blockchain, LOCK_ORDER_BLOCKCHAIN);
         let (mut mempool, _mempool_) = lock_for_write!(mempool, LOCK_ORDER_MEMPOOL);
+        let (mut wallet, _wallet_) = lock_for_write!(wallet, LOCK_ORDER_WALLET);
 
-        let latest_block_id = blockchain.get_latest_block_id();
-
-        let spammer_public_key: SaitoPublicKey =
-            hex::decode("03145c7e7644ab277482ba8801a515b8f1b62bcd7e4834a33258f438cd7e223849")
-                .unwrap()
-                .try_into()
-                .unwrap();
-
-        {
-            if latest_block_id == 0 {
-                let mut vip_transaction =
-                    Transaction::create_vip_transaction(public_key, 50_000_000);
-                vip_transaction.sign(&private_key);
-
-                mempool
-                    .add_transaction_if_validates(vip_transaction, &blockchain)
-                    .await;
-
-                let mut vip_transaction =
-                    Transaction::create_vip_transaction(spammer_public_key, 50_000_000);
-                vip_transaction.sign(&private_key);
+        if blockchain.get_latest_block_id() == 0 {
+            // funding the node's wallet, so the generator has slips to spend
+            let mut vip_transaction =
+                Transaction::create_vip_transaction(wallet.public_key, 50_000_000);
+            vip_transaction.sign(&wallet.private_key);
 
-                mempool
-                    .add_transaction_if_validates(vip_transaction, &blockchain)
-                    .await;
-            }
+            mempool
+                .add_transaction_if_validates(vip_transaction, &blockchain)
+                .await;
         }
 
-        let (mut wallet, _wallet_) = lock_for_write!(wallet, LOCK_ORDER_WALLET);
-
-        for _i in 0..txs_to_generate {
-            let mut transaction;
-            transaction = Transaction::create(&mut wallet, public_key, 5000, 5000);
-            // TODO : generate a message buffer which can be converted back into JSON
-            transaction.message = (0..bytes_per_tx)
-                .into_iter()
-                .map(|_| rand::random::<u8>())
-                .collect();
-            transaction.generate(&public_key, 0, 0);
-            transaction.sign(&private_key);
-
-            transaction.add_hop(&private_key, &public_key, &public_key);
-            {
-                mempool
-                    .add_transaction_if_validates(transaction, &blockchain)
-                    .await;
-            }
+        let transactions = tx_generator.generate_transactions(&mut wallet);
+        let tx_count = transactions.len();
+        for transaction in transactions {
+            mempool
+                .add_transaction_if_validates(transaction, &blockchain)
+                .await;
         }
-        info!("generated transaction count: {:?}", txs_to_generate);
+        info!("generated transaction count: {:?}", tx_count);
     }
 }
 
@@ -236,13 +739,29 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
         let timestamp = self.time_keeper.get_timestamp_in_ms();
         let duration_value = duration.as_millis() as u64;
 
+        self.refetch_quarantined_blocks().await;
+        self.audit_log.flush(&mut self.storage).await;
+
         if self.generate_genesis_block {
-            Self::generate_spammer_init_tx(
-                self.mempool.clone(),
-                self.wallet.clone(),
-                self.blockchain.clone(),
-            )
-            .await;
+            match self.issuance.as_ref() {
+                Some(issuance) => {
+                    Self::add_issuance_transaction(
+                        issuance,
+                        self.mempool.clone(),
+                        self.wallet.clone(),
+                        self.blockchain.clone(),
+                    )
+                    .await;
+                }
+                None => {
+                    Self::generate_spammer_init_tx(
+                        self.mempool.clone(),
+                        self.wallet.clone(),
+                        self.blockchain.clone(),
+                    )
+                    .await;
+                }
+            }
 
             {
                 let (mut blockchain, _blockchain_) =
@@ -253,7 +772,7 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
                         lock_for_write!(self.mempool, LOCK_ORDER_MEMPOOL);
                     {
                         block = mempool
-                            .bundle_genesis_block(&mut blockchain, timestamp)
+                            .bundle_genesis_block(&mut blockchain, timestamp, &self.storage)
                             .await;
                     }
 
@@ -266,6 +785,9 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
                             &mut mempool,
                         )
                         .await;
+                    self.update_blockchain_snapshot(&blockchain);
+                    self.audit_log
+                        .record_all(timestamp, blockchain.take_audit_events());
                 }
             }
 
@@ -274,40 +796,48 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
         }
 
         // generate test transactions
-        if self.create_test_tx {
-            self.tx_producing_timer += duration_value;
-            if self.tx_producing_timer >= SPAM_TX_PRODUCING_TIMER {
-                // TODO : Remove this transaction generation once testing is done
-                ConsensusThread::generate_tx(
-                    self.mempool.clone(),
-                    self.wallet.clone(),
-                    self.blockchain.clone(),
-                )
-                .await;
+        if self.tx_generator.on_timer(duration_value) {
+            ConsensusThread::generate_tx(
+                &mut self.tx_generator,
+                self.mempool.clone(),
+                self.wallet.clone(),
+                self.blockchain.clone(),
+            )
+            .await;
+            self.next_block_production_time = None;
 
-                self.tx_producing_timer = 0;
-                work_done = true;
-            }
+            work_done = true;
+        }
+
+        self.wallet_check_timer += duration_value;
+        if self.wallet_check_timer >= WALLET_CHECK_PERIOD_IN_MS {
+            self.check_wallet_consistency().await;
+            self.wallet_check_timer = 0;
+        }
+
+        if !self.txs_for_mempool.is_empty() {
+            self.add_received_transactions_to_mempool().await;
+            work_done = true;
         }
 
         // generate blocks
-        self.block_producing_timer += duration_value;
-        if self.block_producing_timer >= BLOCK_PRODUCING_TIMER {
+        let mut wallet_updates = vec![];
+        if timestamp >= self.get_next_block_production_time(timestamp).await {
+            if self.dev_mode {
+                self.add_dev_mode_golden_ticket().await;
+            }
             let (mut blockchain, _blockchain_) =
                 lock_for_write!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
             let (mut mempool, _mempool_) = lock_for_write!(self.mempool, LOCK_ORDER_MEMPOOL);
 
-            if !self.txs_for_mempool.is_empty() {
-                for tx in self.txs_for_mempool.iter() {
-                    if let TransactionType::GoldenTicket = tx.transaction_type {
-                        unreachable!("golden tickets shouldn't be here");
-                    } else {
-                        mempool.add_transaction(tx.clone()).await;
-                    }
-                }
+            let expired = mempool.orphan_blocks.remove_expired(timestamp);
+            if expired > 0 {
+                debug!("removed {:?} expired orphan blocks", expired);
             }
+            mempool.creator_blacklist.update(timestamp);
 
-            self.block_producing_timer = 0;
+            // checked again later if the block can't be produced for other reasons (e.g. missing golden ticket)
+            self.next_block_production_time = Some(timestamp + BLOCK_PRODUCTION_RETRY_PERIOD_IN_MS);
 
             // trace!(
             //     "mempool size before bundling : {:?}",
@@ -325,9 +855,18 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
                 }
             }
 
-            let block = mempool
-                .bundle_block(blockchain.deref_mut(), timestamp, gt_result.clone())
-                .await;
+            let block = if self.can_bundle_block() {
+                mempool
+                    .bundle_block(
+                        blockchain.deref_mut(),
+                        timestamp,
+                        gt_result.clone(),
+                        &self.storage,
+                    )
+                    .await
+            } else {
+                None
+            };
             if block.is_some() {
                 let block = block.unwrap();
                 info!(
@@ -341,7 +880,7 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
                 );
 
                 mempool.add_block(block);
-                self.txs_for_mempool.clear();
+                self.next_block_production_time = None;
                 // dropping the lock here since blockchain needs the write lock to add blocks
                 drop(mempool);
                 self.stats.blocks_created.increment();
@@ -353,6 +892,14 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
                         self.sender_to_miner.clone(),
                     )
                     .await;
+                self.update_block_queue_status().await;
+                wallet_updates = blockchain.take_wallet_updates();
+                self.process_longest_chain_updates(&mut blockchain).await;
+                self.update_blockchain_snapshot(&blockchain);
+                self.audit_log.record_all(
+                    self.time_keeper.get_timestamp_in_ms(),
+                    blockchain.take_audit_events(),
+                );
 
                 if updated {
                     self.sender_to_router
@@ -365,10 +912,6 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
 
                 work_done = true;
             } else {
-                // route messages to peers
-                for tx in self.txs_for_mempool.drain(..) {
-                    self.network.propagate_transaction(&tx).await;
-                }
                 // route golden tickets to peers
                 if gt_result.is_some() && !gt_propagated {
                     self.network
@@ -386,6 +929,7 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
                 }
             }
         }
+        self.send_wallet_updates(wallet_updates).await;
 
         if work_done {
             return Some(());
@@ -410,6 +954,11 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
                     public_key = wallet.public_key;
                     private_key = wallet.private_key;
                 }
+                // tickets submitted by other miners are not sent back to the peers
+                if self.submit_golden_tickets && golden_ticket.public_key == public_key {
+                    self.network.submit_golden_ticket(&golden_ticket).await;
+                }
+                let audit_event = AuditEvent::golden_ticket_accepted(&golden_ticket);
                 let transaction = Wallet::create_golden_ticket_transaction(
                     golden_ticket,
                     &public_key,
@@ -417,10 +966,15 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
                 )
                 .await;
                 self.stats.received_gts.increment();
-                mempool.add_golden_ticket(transaction).await;
+                if mempool.add_golden_ticket(transaction).await {
+                    self.audit_log
+                        .record(self.time_keeper.get_timestamp_in_ms(), audit_event);
+                }
+                self.next_block_production_time = None;
                 Some(())
             }
-            ConsensusEvent::BlockFetched { block, .. } => {
+            ConsensusEvent::BlockFetched { peer_index, block } => {
+                self.block_queue_status.lock().unwrap().on_block_received();
                 let (mut blockchain, _blockchain_) =
                     lock_for_write!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
 
@@ -434,9 +988,52 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
                         );
                         return Some(());
                     }
-                    debug!("adding fetched block to mempool");
+                    if let Err(error) = blockchain.validate_received_block_timestamp(
+                        &block,
+                        self.time_keeper.get_timestamp_in_ms(),
+                    ) {
+                        warn!(
+                            "rejecting fetched block : {:?} : {:?}",
+                            hex::encode(block.hash),
+                            error
+                        );
+                        self.stats.rejected_blocks.increment();
+                        self.audit_log.record(
+                            self.time_keeper.get_timestamp_in_ms(),
+                            AuditEvent::block_rejected(
+                                block.id,
+                                &block.hash,
+                                &block.creator,
+                                format!("{:?}", error),
+                            ),
+                        );
+                        return Some(());
+                    }
                     let (mut mempool, _mempool_) =
                         lock_for_write!(self.mempool, LOCK_ORDER_MEMPOOL);
+                    if mempool.creator_blacklist.is_blacklisted(&block.creator) {
+                        debug!(
+                            "ignoring fetched block : {:?} from blacklisted creator : {:?}",
+                            hex::encode(block.hash),
+                            hex::encode(block.creator)
+                        );
+                        mempool.creator_blacklist.ignored_blocks += 1;
+                        self.audit_log.record(
+                            self.time_keeper.get_timestamp_in_ms(),
+                            AuditEvent::block_rejected(
+                                block.id,
+                                &block.hash,
+                                &block.creator,
+                                "blacklisted creator".to_string(),
+                            ),
+                        );
+                        self.sender_to_router
+                            .send(RoutingEvent::BlockFromBlacklistedCreator { peer_index })
+                            .await
+                            .unwrap();
+                        return Some(());
+                    }
+                    debug!("adding fetched block to mempool");
                     mempool.add_block(block);
                 }
                 self.stats.blocks_fetched.increment();
@@ -448,17 +1045,92 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
                         self.sender_to_miner.clone(),
                     )
                     .await;
+                self.update_block_queue_status().await;
+                let wallet_updates = blockchain.take_wallet_updates();
+                self.process_longest_chain_updates(&mut blockchain).await;
+                self.update_blockchain_snapshot(&blockchain);
+                self.audit_log.record_all(
+                    self.time_keeper.get_timestamp_in_ms(),
+                    blockchain.take_audit_events(),
+                );
 
                 if updated {
+                    self.next_block_production_time = None;
                     self.sender_to_router
                         .send(RoutingEvent::BlockchainUpdated)
                         .await
                         .unwrap();
                 }
+                drop(blockchain);
+                drop(_blockchain_);
+                self.send_wallet_updates(wallet_updates).await;
 
                 Some(())
             }
-            ConsensusEvent::NewTransaction { transaction } => {
+            ConsensusEvent::RollbackChain { block_id } => {
+                info!("rolling back the chain to block : {:?}", block_id);
+                let rolled_back;
+                let wallet_updates;
+                {
+                    let (mut blockchain, _blockchain_) =
+                        lock_for_write!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+
+                    rolled_back = blockchain
+                        .rollback_to_block_id(block_id, &self.storage)
+                        .await;
+                    wallet_updates = blockchain.take_wallet_updates();
+                    self.process_longest_chain_updates(&mut blockchain).await;
+                    self.update_blockchain_snapshot(&blockchain);
+                }
+                self.send_wallet_updates(wallet_updates).await;
+                if rolled_back {
+                    self.next_block_production_time = None;
+                    self.sender_to_router
+                        .send(RoutingEvent::BlockchainRolledBack)
+                        .await
+                        .unwrap();
+                }
+                Some(())
+            }
+            ConsensusEvent::ReindexChain => {
+                self.reindex_chain().await;
+                Some(())
+            }
+            ConsensusEvent::GenerateUtxoReport { request } => {
+                let report;
+                {
+                    let (blockchain, _blockchain_) =
+                        lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+                    report = UtxoReport::generate(
+                        &blockchain.utxoset,
+                        blockchain.get_latest_block_id(),
+                        request.dust_threshold,
+                    );
+                }
+                info!(
+                    "writing utxo report for block : {:?} with {:?} slips to : {:?}",
+                    report.block_id, report.slip_count, request.path
+                );
+                let buffer = report.serialize(request.format);
+                self.storage.write(buffer, request.path.as_str()).await;
+                Some(())
+            }
+            ConsensusEvent::StartTxGeneration => {
+                self.tx_generator.start();
+                Some(())
+            }
+            ConsensusEvent::StopTxGeneration => {
+                self.tx_generator.stop();
+                Some(())
+            }
+            ConsensusEvent::RescanWallet => {
+                self.rescan_wallet().await;
+                Some(())
+            }
+            ConsensusEvent::NewTransaction {
+                transaction,
+                peer_index,
+            } => {
                 self.stats.received_tx.increment();
 
                 trace!(
@@ -466,15 +1138,7 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
                     hex::encode(transaction.signature),
                     hex::encode(hash(&transaction.serialize_for_net()))
                 );
-                if let TransactionType::GoldenTicket = transaction.transaction_type {
-                    let (mut mempool, _mempool_) =
-                        lock_for_write!(self.mempool, LOCK_ORDER_MEMPOOL);
-
-                    self.stats.received_gts.increment();
-                    mempool.add_golden_ticket(transaction).await;
-                } else {
-                    self.txs_for_mempool.push(transaction);
-                }
+                self.queue_transaction(transaction, peer_index).await;
 
                 Some(())
             }
@@ -485,37 +1149,56 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
 
                 self.txs_for_mempool.reserve(transactions.len());
                 for transaction in transactions.drain(..) {
-                    if let TransactionType::GoldenTicket = transaction.transaction_type {
-                        let (mut mempool, _mempool_) =
-                            lock_for_write!(self.mempool, LOCK_ORDER_MEMPOOL);
-
-                        self.stats.received_gts.increment();
-                        mempool.add_golden_ticket(transaction).await;
-                    } else {
-                        self.txs_for_mempool.push(transaction);
-                    }
+                    self.queue_transaction(transaction, None).await;
                 }
                 Some(())
             }
+            ConsensusEvent::NewTransactionBatch {
+                transactions,
+                result,
+                source,
+            } => {
+                self.add_transaction_batch_to_mempool(transactions, result, source)
+                    .await;
+                Some(())
+            }
         };
     }
 
     async fn on_init(&mut self) {
         debug!("on_init");
+        self.load_transaction_index().await;
         self.storage
             .load_blocks_from_disk(self.mempool.clone())
             .await;
 
-        let (mut blockchain, _blockchain_) =
-            lock_for_write!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
-        blockchain
-            .add_blocks_from_mempool(
-                self.mempool.clone(),
-                &self.network,
-                &mut self.storage,
-                self.sender_to_miner.clone(),
-            )
-            .await;
+        let wallet_updates;
+        {
+            let (mut blockchain, _blockchain_) =
+                lock_for_write!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            blockchain.set_async_wallet_updates(self.sender_to_wallet_updater.is_some());
+            blockchain.set_audit_events_enabled(self.audit_log.is_enabled());
+            blockchain
+                .add_blocks_from_mempool(
+                    self.mempool.clone(),
+                    &self.network,
+                    &mut self.storage,
+                    self.sender_to_miner.clone(),
+                )
+                .await;
+            self.update_block_queue_status().await;
+            // invalid blocks from disk were quarantined. the chain stays at the last valid block until they are fetched
+            self.storage.clear_loaded_block_files();
+            // only transactions submitted after the startup are tracked
+            blockchain.set_longest_chain_updates_enabled(true);
+            wallet_updates = blockchain.take_wallet_updates();
+            self.update_blockchain_snapshot(&blockchain);
+            self.audit_log.record_all(
+                self.time_keeper.get_timestamp_in_ms(),
+                blockchain.take_audit_events(),
+            );
+        }
+        self.send_wallet_updates(wallet_updates).await;
     }
 
     async fn on_stat_interval(&mut self, current_time: Timestamp) {
@@ -523,6 +1206,10 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
             .blocks_fetched
             .calculate_stats(current_time)
             .await;
+        self.stats
+            .rejected_blocks
+            .calculate_stats(current_time)
+            .await;
         self.stats
             .blocks_created
             .calculate_stats(current_time)
@@ -557,12 +1244,163 @@ impl ProcessEvent<ConsensusEvent> for ConsensusThread {
             let (mempool, _mempool_) = lock_for_read!(self.mempool, LOCK_ORDER_MEMPOOL);
 
             let stat = format!(
-                "{} - blocks_queue : {:?}, transactions : {:?}",
+                "{} - blocks_queue : {:?}, transactions : {:?}, pending_slips : {:?}",
                 format!("{:width$}", "mempool:state", width = 40),
                 mempool.blocks_queue.len(),
                 mempool.transactions.len(),
+                mempool.get_pending_slip_count(),
+            );
+            self.stat_sender.send(stat).await.unwrap();
+
+            let stat = format!(
+                "{} - orphans : {:?}, expired : {:?}, evicted : {:?}",
+                format!("{:width$}", "mempool:orphan_blocks", width = 40),
+                mempool.orphan_blocks.len(),
+                mempool.orphan_blocks.expired_blocks,
+                mempool.orphan_blocks.evicted_blocks,
+            );
+            self.stat_sender.send(stat).await.unwrap();
+
+            let stat = format!(
+                "{} - blacklisted : {:?}, ignored_blocks : {:?}",
+                format!("{:width$}", "mempool:creator_blacklist", width = 40),
+                mempool.creator_blacklist.get_blacklisted_creators().len(),
+                mempool.creator_blacklist.ignored_blocks,
+            );
+            self.stat_sender.send(stat).await.unwrap();
+
+            if let Some(stats) = mempool.last_bundling_stats.as_ref() {
+                let stat = format!(
+                    "{} - included_txs : {:?}, included_bytes : {:?}, included_fees : {:?}, deferred_txs : {:?}, deferred_bytes : {:?}, max_block_size : {:?}",
+                    format!("{:width$}", "mempool:block_bundling", width = 40),
+                    stats.included_transactions,
+                    stats.included_bytes,
+                    stats.included_fees,
+                    stats.deferred_transactions,
+                    stats.deferred_bytes,
+                    mempool.get_max_block_size()
+                );
+                self.stat_sender.send(stat).await.unwrap();
+            }
+        }
+        {
+            let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            let (mempool, _mempool_) = lock_for_read!(self.mempool, LOCK_ORDER_MEMPOOL);
+            let (wallet, _wallet_) = lock_for_read!(self.wallet, LOCK_ORDER_WALLET);
+
+            let report = MemoryReport::generate(&blockchain, &mempool, &wallet);
+            let stat = format!(
+                "{} - utxoset : {:?}, blocks : {:?}, mempool_txs : {:?}, wallet_slips : {:?}, total : {:?}",
+                format!("{:width$}", "memory::estimated_bytes", width = 40),
+                report.utxoset.estimated_bytes,
+                report.blocks.estimated_bytes,
+                report.mempool_transactions.estimated_bytes,
+                report.wallet_slips.estimated_bytes,
+                report.get_total_bytes()
             );
             self.stat_sender.send(stat).await.unwrap();
         }
+        {
+            let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            let (mempool, _mempool_) = lock_for_read!(self.mempool, LOCK_ORDER_MEMPOOL);
+
+            if let Some(status) = mempool.get_block_production_status(&blockchain, current_time) {
+                let stat = format!(
+                    "{} - work_available : {:?}, work_needed : {:?}, time_since_last_block : {:?}, has_enough_work : {:?}",
+                    format!("{:width$}", "consensus::block_production", width = 40),
+                    status.work_available,
+                    status.work_needed,
+                    status.time_since_last_block,
+                    status.has_enough_work()
+                );
+                self.stat_sender.send(stat).await.unwrap();
+            }
+            let chain_stats = blockchain.get_chain_stats();
+            if let Some(last_interval) = chain_stats.get_latest_block_interval() {
+                let stat = format!(
+                    "{} - last_block_interval : {:?}, average_block_time : {:?}",
+                    format!("{:width$}", "blockchain::block_time", width = 40),
+                    last_interval,
+                    chain_stats
+                        .get_average_block_time(BLOCK_TIME_AVERAGE_WINDOW)
+                        .unwrap_or_default()
+                );
+                self.stat_sender.send(stat).await.unwrap();
+            }
+            let consensus_health = blockchain.get_consensus_health();
+            let sample = consensus_health.get_last_sample();
+            let stat = format!(
+                "{} - fork_rate : {:?}, block_interval_deviation : {:?}, healthy : {:?}, fork_rate_alerts : {:?}, block_interval_alerts : {:?}",
+                format!("{:width$}", "blockchain::consensus_health", width = 40),
+                sample.get_fork_rate(),
+                sample.block_interval_deviation,
+                consensus_health.is_healthy(),
+                consensus_health.fork_rate_alert_count,
+                consensus_health.block_interval_alert_count
+            );
+            self.stat_sender.send(stat).await.unwrap();
+        }
+        for lock_stats in get_lock_stats() {
+            self.stat_sender
+                .send(lock_stats.to_stat_line())
+                .await
+                .unwrap();
+        }
+        for validation_stats in get_validation_stats() {
+            self.stat_sender
+                .send(validation_stats.to_stat_line())
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn on_shutdown(&mut self) {
+        self.tx_generator.stop();
+        self.audit_log.flush(&mut self.storage).await;
+        let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+        info!(
+            "stopping consensus thread at block : {:?} - {:?}",
+            blockchain.get_latest_block_id(),
+            hex::encode(blockchain.get_latest_block_hash())
+        );
+        if let Some(transaction_index) = blockchain.get_transaction_index() {
+            info!(
+                "saving transaction index with {:?} transactions",
+                transaction_index.len()
+            );
+            self.storage
+                .write(
+                    transaction_index.serialize_for_disk(),
+                    TRANSACTION_INDEX_FILE_PATH,
+                )
+                .await;
+        }
+    }
+
+    async fn on_config_reload(&mut self, configs: &(dyn Configuration + Send + Sync)) {
+        let server_configs = configs.get_server_configs();
+        let (mut blockchain, _blockchain_) =
+            lock_for_write!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+        blockchain.set_full_block_cache_size(server_configs.full_block_cache_size as usize);
+        info!(
+            "full block cache size set to : {:?}",
+            server_configs.full_block_cache_size
+        );
+        blockchain.set_max_block_future_drift(server_configs.max_block_future_drift_in_ms);
+        blockchain.set_consensus_health_config(server_configs.consensus_health.clone());
+        self.audit_log.set_config(server_configs.audit_log.clone());
+        self.webhooks.set_config(server_configs.webhooks.clone());
+        blockchain.set_audit_events_enabled(self.audit_log.is_enabled());
+        info!("audit log enabled : {:?}", self.audit_log.is_enabled());
+        self.dev_mode = server_configs.dev_mode;
+        self.block_production_quorum = server_configs.block_production_quorum.clone();
+        self.next_block_production_time = None;
+        info!("dev mode : {:?}", self.dev_mode);
+
+        let (mut mempool, _mempool_) = lock_for_write!(self.mempool, LOCK_ORDER_MEMPOOL);
+        mempool.orphan_blocks.set_limits(
+            server_configs.max_orphan_blocks,
+            server_configs.orphan_block_expiry_in_ms,
+        );
     }
 }
@@ -0,0 +1,196 @@
+use ahash::AHashMap;
+use tracing::trace;
+
+use crate::common::defs::SaitoPublicKey;
+use crate::core::data::block::Block;
+
+/// marks a transaction message which starts with a nonce
+pub const NONCE_ENVELOPE_MAGIC: [u8; 4] = *b"SNCE";
+/// [magic - 4 bytes][nonce - 8 bytes]
+pub const NONCE_ENVELOPE_SIZE: usize = 12;
+
+/// Prepends the nonce envelope to the application message
+pub fn wrap_message_with_nonce(nonce: u64, message: &[u8]) -> Vec<u8> {
+    [
+        NONCE_ENVELOPE_MAGIC.as_slice(),
+        nonce.to_be_bytes().as_slice(),
+        message,
+    ]
+    .concat()
+}
+
+/// Returns the nonce if the message starts with a nonce envelope
+pub fn read_nonce(message: &[u8]) -> Option<u64> {
+    if message.len() < NONCE_ENVELOPE_SIZE || message[0..4] != NONCE_ENVELOPE_MAGIC {
+        return None;
+    }
+    Some(u64::from_be_bytes(
+        message[4..NONCE_ENVELOPE_SIZE].try_into().unwrap(),
+    ))
+}
+
+/// Returns the application message without the nonce envelope
+pub fn strip_nonce(message: &[u8]) -> &[u8] {
+    match read_nonce(message) {
+        Some(_) => &message[NONCE_ENVELOPE_SIZE..],
+        None => message,
+    }
+}
+
+/// Latest nonce of each sender in the longest chain. Only senders which used a nonce are tracked, transactions
+/// without one are not ordered.
+///
+/// Nonces are collected from the blocks in memory, so senders whose transactions are all in pruned blocks start again
+/// from 0 after a restart.
+#[derive(Debug, Default, Clone)]
+pub struct AccountNonces {
+    last_nonces: AHashMap<SaitoPublicKey, u64>,
+}
+
+impl AccountNonces {
+    pub fn new() -> AccountNonces {
+        AccountNonces {
+            last_nonces: Default::default(),
+        }
+    }
+
+    pub fn get_last_nonce(&self, public_key: &SaitoPublicKey) -> Option<u64> {
+        self.last_nonces.get(public_key).copied()
+    }
+
+    /// nonce expected from the sender's next transaction. the first nonce is 0
+    pub fn get_next_nonce(&self, public_key: &SaitoPublicKey) -> u64 {
+        self.get_last_nonce(public_key)
+            .map(|nonce| nonce + 1)
+            .unwrap_or(0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.last_nonces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.last_nonces.is_empty()
+    }
+
+    /// called when the block is added to the longest chain
+    pub fn on_block_added(&mut self, block: &Block) {
+        for (public_key, nonce) in block
+            .transactions
+            .iter()
+            .filter_map(|tx| Some((tx.get_nonce_sender()?, tx.get_nonce()?)))
+        {
+            let last_nonce = self.last_nonces.entry(public_key).or_insert(nonce);
+            if *last_nonce < nonce {
+                *last_nonce = nonce;
+            }
+        }
+    }
+
+    /// called when the block is removed from the longest chain. the sender's last nonce goes back to the one before
+    /// their first transaction in the block
+    pub fn on_block_removed(&mut self, block: &Block) {
+        let mut first_nonces: AHashMap<SaitoPublicKey, u64> = Default::default();
+        for (public_key, nonce) in block
+            .transactions
+            .iter()
+            .filter_map(|tx| Some((tx.get_nonce_sender()?, tx.get_nonce()?)))
+        {
+            let first_nonce = first_nonces.entry(public_key).or_insert(nonce);
+            if *first_nonce > nonce {
+                *first_nonce = nonce;
+            }
+        }
+        for (public_key, first_nonce) in first_nonces {
+            trace!(
+                "rolling back nonce of : {:?} to before : {:?}",
+                hex::encode(public_key),
+                first_nonce
+            );
+            if first_nonce == 0 {
+                self.last_nonces.remove(&public_key);
+            } else {
+                self.last_nonces.insert(public_key, first_nonce - 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::account_nonce::{
+        read_nonce, strip_nonce, wrap_message_with_nonce, AccountNonces,
+    };
+    use crate::core::data::block::Block;
+    use crate::core::data::slip::Slip;
+    use crate::core::data::transaction::{Transaction, TransactionType};
+
+    fn create_transaction(public_key: u8, nonce: Option<u64>) -> Transaction {
+        let mut tx = Transaction::default();
+        let mut input = Slip::default();
+        input.public_key = [public_key; 33];
+        tx.add_input(input);
+        tx.message = vec![1, 2, 3];
+        if let Some(nonce) = nonce {
+            tx.set_nonce(nonce);
+        }
+        tx
+    }
+
+    #[test]
+    fn nonce_envelope_test() {
+        let message = wrap_message_with_nonce(7, &[1, 2, 3]);
+        assert_eq!(message.len(), 15);
+        assert_eq!(read_nonce(&message), Some(7));
+        assert_eq!(strip_nonce(&message), &[1, 2, 3]);
+
+        assert_eq!(read_nonce(&[1, 2, 3]), None);
+        assert_eq!(strip_nonce(&[1, 2, 3]), &[1, 2, 3]);
+        assert_eq!(read_nonce(&message[0..11]), None);
+
+        let mut tx = create_transaction(1, Some(3));
+        assert_eq!(tx.get_nonce(), Some(3));
+        assert_eq!(tx.get_application_message(), &[1, 2, 3]);
+        tx.set_nonce(4);
+        assert_eq!(tx.get_nonce(), Some(4));
+        assert_eq!(tx.get_application_message(), &[1, 2, 3]);
+
+        tx.transaction_type = TransactionType::GoldenTicket;
+        assert_eq!(tx.get_nonce(), None);
+    }
+
+    #[test]
+    fn account_nonces_test() {
+        let mut nonces = AccountNonces::new();
+        assert_eq!(nonces.get_next_nonce(&[1; 33]), 0);
+
+        let mut block_1 = Block::new();
+        block_1.transactions = vec![
+            create_transaction(1, Some(1)),
+            create_transaction(1, Some(0)),
+            create_transaction(2, None),
+        ];
+        let mut block_2 = Block::new();
+        block_2.transactions = vec![
+            create_transaction(1, Some(2)),
+            create_transaction(3, Some(0)),
+        ];
+
+        nonces.on_block_added(&block_1);
+        assert_eq!(nonces.get_last_nonce(&[1; 33]), Some(1));
+        assert_eq!(nonces.get_next_nonce(&[1; 33]), 2);
+        assert_eq!(nonces.get_last_nonce(&[2; 33]), None);
+        assert_eq!(nonces.len(), 1);
+
+        nonces.on_block_added(&block_2);
+        assert_eq!(nonces.get_next_nonce(&[1; 33]), 3);
+        assert_eq!(nonces.get_next_nonce(&[3; 33]), 1);
+
+        nonces.on_block_removed(&block_2);
+        assert_eq!(nonces.get_next_nonce(&[1; 33]), 2);
+        assert_eq!(nonces.get_last_nonce(&[3; 33]), None);
+
+        nonces.on_block_removed(&block_1);
+        assert!(nonces.is_empty());
+    }
+}
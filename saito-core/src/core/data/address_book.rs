@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+
+use base58::{FromBase58, ToBase58};
+use tracing::{debug, warn};
+
+use crate::common::defs::{Currency, SaitoPublicKey, Timestamp};
+use crate::core::data::storage::Storage;
+
+pub const MAX_ADDRESS_NAME_LENGTH: usize = 64;
+pub const MAX_PAYMENT_REQUEST_MEMO_LENGTH: usize = 1024;
+pub const PAYMENT_REQUEST_PREFIX: &str = "saito:";
+/// [recipient - 33 bytes][amount - 16 bytes][expires at - 8 bytes][memo length - 4 bytes]
+pub const PAYMENT_REQUEST_HEADER_SIZE: usize = 61;
+
+/// Maps human readable names to public keys, so payments can be addressed by name
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AddressBook {
+    entries: BTreeMap<String, SaitoPublicKey>,
+}
+
+impl AddressBook {
+    pub fn new() -> AddressBook {
+        AddressBook {
+            entries: Default::default(),
+        }
+    }
+
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= MAX_ADDRESS_NAME_LENGTH
+            && !name.contains(|c: char| c.is_control() || c == ':')
+            && Self::parse_public_key(name).is_none()
+    }
+
+    /// Adds or replaces the entry with the given name. Names which could be confused with hex keys are rejected.
+    pub fn add(&mut self, name: &str, public_key: SaitoPublicKey) -> bool {
+        if !Self::is_valid_name(name) {
+            warn!("address name : {:?} is not valid", name);
+            return false;
+        }
+        self.entries.insert(name.to_string(), public_key);
+        true
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<SaitoPublicKey> {
+        self.entries.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SaitoPublicKey> {
+        self.entries.get(name)
+    }
+
+    /// Returns the names registered for the given key
+    pub fn get_names(&self, public_key: &SaitoPublicKey) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|(_, key)| *key == public_key)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    pub fn get_entries(&self) -> Vec<(String, SaitoPublicKey)> {
+        self.entries
+            .iter()
+            .map(|(name, key)| (name.clone(), *key))
+            .collect()
+    }
+
+    /// Resolves either a hex encoded public key or a name in the address book
+    pub fn resolve(&self, name_or_key: &str) -> Option<SaitoPublicKey> {
+        Self::parse_public_key(name_or_key).or_else(|| self.get(name_or_key).cloned())
+    }
+
+    fn parse_public_key(value: &str) -> Option<SaitoPublicKey> {
+        hex::decode(value)
+            .ok()
+            .and_then(|buffer| buffer.try_into().ok())
+    }
+
+    /// one entry per line as [name]:[hex public key]
+    pub fn serialize_for_disk(&self) -> Vec<u8> {
+        let mut buffer = String::new();
+        for (name, key) in self.entries.iter() {
+            buffer.push_str(name);
+            buffer.push(':');
+            buffer.push_str(hex::encode(key).as_str());
+            buffer.push('\n');
+        }
+        buffer.into_bytes()
+    }
+
+    pub fn deserialize_from_disk(bytes: &[u8]) -> AddressBook {
+        let mut address_book = AddressBook::new();
+        for line in String::from_utf8_lossy(bytes).lines() {
+            let entry = line.rsplit_once(':');
+            if entry.is_none() {
+                continue;
+            }
+            let (name, key) = entry.unwrap();
+            match Self::parse_public_key(key) {
+                Some(key) => {
+                    address_book.add(name, key);
+                }
+                None => {
+                    warn!("invalid address book entry : {:?}", line);
+                }
+            }
+        }
+        address_book
+    }
+
+    pub async fn load(&mut self, filename: &str, storage: &Storage) {
+        if !storage.file_exists(filename).await {
+            debug!("address book : {:?} not found", filename);
+            return;
+        }
+        let buffer = storage.read(filename).await.unwrap();
+        *self = AddressBook::deserialize_from_disk(&buffer);
+    }
+
+    pub async fn save(&self, filename: &str, storage: &mut Storage) {
+        storage.write(self.serialize_for_disk(), filename).await;
+    }
+}
+
+/// Request for a payment which can be shared between applications as a string
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaymentRequest {
+    pub recipient: SaitoPublicKey,
+    pub amount: Currency,
+    pub memo: String,
+    /// request is not valid after this time. 0 means it doesn't expire
+    pub expires_at: Timestamp,
+}
+
+impl PaymentRequest {
+    pub fn new(
+        recipient: SaitoPublicKey,
+        amount: Currency,
+        memo: String,
+        expires_at: Timestamp,
+    ) -> PaymentRequest {
+        PaymentRequest {
+            recipient,
+            amount,
+            memo,
+            expires_at,
+        }
+    }
+
+    pub fn is_expired(&self, current_time: Timestamp) -> bool {
+        self.expires_at != 0 && current_time > self.expires_at
+    }
+
+    /// [recipient - 33 bytes]
+    /// [amount - 16 bytes]
+    /// [expires at - 8 bytes]
+    /// [memo length - 4 bytes]
+    /// [memo - utf8]
+    pub fn serialize_for_net(&self) -> Vec<u8> {
+        let memo = self.memo.as_bytes();
+        [
+            self.recipient.as_slice(),
+            self.amount.to_be_bytes().as_slice(),
+            self.expires_at.to_be_bytes().as_slice(),
+            (memo.len() as u32).to_be_bytes().as_slice(),
+            memo,
+        ]
+        .concat()
+    }
+
+    pub fn deserialize_from_net(bytes: &[u8]) -> Result<PaymentRequest, Error> {
+        if bytes.len() < PAYMENT_REQUEST_HEADER_SIZE {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let recipient: SaitoPublicKey = bytes[0..33].try_into().unwrap();
+        let amount = Currency::from_be_bytes(bytes[33..49].try_into().unwrap());
+        let expires_at = Timestamp::from_be_bytes(bytes[49..57].try_into().unwrap());
+        let memo_length = u32::from_be_bytes(bytes[57..61].try_into().unwrap()) as usize;
+        if memo_length > MAX_PAYMENT_REQUEST_MEMO_LENGTH
+            || bytes.len() != PAYMENT_REQUEST_HEADER_SIZE + memo_length
+        {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let memo = String::from_utf8(bytes[PAYMENT_REQUEST_HEADER_SIZE..].to_vec())
+            .map_err(|_| Error::from(ErrorKind::InvalidData))?;
+
+        Ok(PaymentRequest {
+            recipient,
+            amount,
+            memo,
+            expires_at,
+        })
+    }
+
+    /// encodes the request as "saito:[base58 encoded request]"
+    pub fn encode(&self) -> String {
+        PAYMENT_REQUEST_PREFIX.to_string() + self.serialize_for_net().to_base58().as_str()
+    }
+
+    pub fn decode(value: &str) -> Result<PaymentRequest, Error> {
+        let encoded = value
+            .strip_prefix(PAYMENT_REQUEST_PREFIX)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidInput))?;
+        let buffer = encoded
+            .from_base58()
+            .map_err(|_| Error::from(ErrorKind::InvalidInput))?;
+        PaymentRequest::deserialize_from_net(&buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::address_book::{AddressBook, PaymentRequest};
+    use crate::core::data::crypto::generate_keys;
+
+    #[test]
+    fn address_book_test() {
+        let (key1, _) = generate_keys();
+        let (key2, _) = generate_keys();
+        let mut address_book = AddressBook::new();
+
+        assert!(address_book.add("alice", key1));
+        assert!(address_book.add("bob", key2));
+        assert!(address_book.add("alice's savings", key1));
+        assert!(!address_book.add("", key1));
+        assert!(!address_book.add("a:b", key1));
+        assert!(!address_book.add(hex::encode(key2).as_str(), key1));
+
+        assert_eq!(address_book.resolve("alice"), Some(key1));
+        assert_eq!(address_book.resolve(hex::encode(key2).as_str()), Some(key2));
+        assert_eq!(address_book.resolve("carol"), None);
+        assert_eq!(
+            address_book.get_names(&key1),
+            vec!["alice".to_string(), "alice's savings".to_string()]
+        );
+
+        let buffer = address_book.serialize_for_disk();
+        assert_eq!(AddressBook::deserialize_from_disk(&buffer), address_book);
+
+        assert_eq!(address_book.remove("bob"), Some(key2));
+        assert_eq!(address_book.resolve("bob"), None);
+    }
+
+    #[test]
+    fn payment_request_encode_decode_test() {
+        let (key, _) = generate_keys();
+        let request = PaymentRequest::new(key, 1_000_000, "coffee ☕".to_string(), 5000);
+
+        let encoded = request.encode();
+        assert!(encoded.starts_with("saito:"));
+        assert_eq!(PaymentRequest::decode(encoded.as_str()).unwrap(), request);
+
+        assert!(!request.is_expired(5000));
+        assert!(request.is_expired(5001));
+        assert!(!PaymentRequest::new(key, 1, "".to_string(), 0).is_expired(u64::MAX));
+
+        assert!(PaymentRequest::decode("bitcoin:abc").is_err());
+        assert!(PaymentRequest::decode("saito:0OIl").is_err());
+        let mut buffer = request.serialize_for_net();
+        buffer.pop();
+        assert!(PaymentRequest::deserialize_from_net(&buffer).is_err());
+    }
+}
@@ -0,0 +1,20 @@
+use std::fmt::Debug;
+use std::io::Error;
+
+use async_trait::async_trait;
+
+use crate::common::defs::{BlockId, SaitoHash};
+
+/// Receives the blocks purged from the blockchain after two genesis periods, before they are deleted from the local
+/// storage. Implementations can keep the historical blocks outside the node (e.g. in an object store) while the
+/// local storage stays bounded.
+#[async_trait]
+pub trait ArchiveSink: Debug + Send + Sync {
+    /// `buffer` is the full block serialized for the network. The block is deleted even if archiving fails.
+    async fn archive_block(
+        &self,
+        block_id: BlockId,
+        block_hash: SaitoHash,
+        buffer: Vec<u8>,
+    ) -> Result<(), Error>;
+}
@@ -0,0 +1,368 @@
+use std::io::Error;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info};
+
+use crate::common::defs::{BlockId, SaitoHash, SaitoPublicKey, Timestamp};
+use crate::core::data::golden_ticket::GoldenTicket;
+use crate::core::data::storage::Storage;
+
+/// Where the consensus decisions of the node are recorded. Rotated files get a `.1`, `.2`, ... suffix, `.1` being the
+/// latest
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(default)]
+pub struct AuditLogConfig {
+    pub enabled: bool,
+    pub file_path: String,
+    /// the file is rotated before it grows over this size
+    pub max_file_size_in_bytes: u64,
+    /// number of rotated files kept. older files are deleted
+    pub max_rotated_files: u64,
+}
+
+impl Default for AuditLogConfig {
+    fn default() -> Self {
+        AuditLogConfig {
+            enabled: false,
+            file_path: "./data/audit/audit.log".to_string(),
+            max_file_size_in_bytes: 10 * 1024 * 1024,
+            max_rotated_files: 5,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AddBlockOutcome {
+    AddedToLongestChain,
+    AddedToFork,
+    AlreadyExists,
+    /// added to the orphan pool until the previous block is added
+    WaitingForParent,
+    /// the chain with the block didn't validate, so the block was removed
+    FailedValidation,
+}
+
+/// A consensus decision of the node. Hashes and keys are hex encoded
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    AddBlock {
+        block_id: BlockId,
+        block_hash: String,
+        previous_block_hash: String,
+        creator: String,
+        outcome: AddBlockOutcome,
+    },
+    /// the longest chain moved to the fork ending with the block. blocks are listed from the tip to the shared
+    /// ancestor
+    Reorg {
+        block_id: BlockId,
+        block_hash: String,
+        unwound_blocks: Vec<String>,
+        wound_blocks: Vec<String>,
+    },
+    /// block received from a peer which was not added to the blockchain
+    BlockRejected {
+        block_id: BlockId,
+        block_hash: String,
+        creator: String,
+        reason: String,
+    },
+    GoldenTicketAccepted {
+        block_hash: String,
+        public_key: String,
+    },
+}
+
+impl AuditEvent {
+    pub fn add_block(
+        block_id: BlockId,
+        block_hash: &SaitoHash,
+        previous_block_hash: &SaitoHash,
+        creator: &SaitoPublicKey,
+        outcome: AddBlockOutcome,
+    ) -> AuditEvent {
+        AuditEvent::AddBlock {
+            block_id,
+            block_hash: hex::encode(block_hash),
+            previous_block_hash: hex::encode(previous_block_hash),
+            creator: hex::encode(creator),
+            outcome,
+        }
+    }
+
+    pub fn reorg(
+        block_id: BlockId,
+        block_hash: &SaitoHash,
+        unwound_blocks: &[SaitoHash],
+        wound_blocks: &[SaitoHash],
+    ) -> AuditEvent {
+        AuditEvent::Reorg {
+            block_id,
+            block_hash: hex::encode(block_hash),
+            unwound_blocks: unwound_blocks.iter().map(hex::encode).collect(),
+            wound_blocks: wound_blocks.iter().map(hex::encode).collect(),
+        }
+    }
+
+    pub fn block_rejected(
+        block_id: BlockId,
+        block_hash: &SaitoHash,
+        creator: &SaitoPublicKey,
+        reason: String,
+    ) -> AuditEvent {
+        AuditEvent::BlockRejected {
+            block_id,
+            block_hash: hex::encode(block_hash),
+            creator: hex::encode(creator),
+            reason,
+        }
+    }
+
+    pub fn golden_ticket_accepted(golden_ticket: &GoldenTicket) -> AuditEvent {
+        AuditEvent::GoldenTicketAccepted {
+            block_hash: hex::encode(golden_ticket.target),
+            public_key: hex::encode(golden_ticket.public_key),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct AuditRecord<'a> {
+    timestamp: Timestamp,
+    #[serde(flatten)]
+    event: &'a AuditEvent,
+}
+
+/// Append-only record of the consensus decisions (JSON lines), so the decisions of two diverged nodes can be compared.
+/// Events are buffered and appended to the file when flushed.
+#[derive(Debug)]
+pub struct AuditLog {
+    config: AuditLogConfig,
+    pending_lines: Vec<u8>,
+    // size of the current file. None until it's read from the storage
+    file_size: Option<u64>,
+}
+
+impl AuditLog {
+    pub fn new(config: AuditLogConfig) -> AuditLog {
+        AuditLog {
+            config,
+            pending_lines: vec![],
+            file_size: None,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn set_config(&mut self, config: AuditLogConfig) {
+        if config.file_path != self.config.file_path {
+            self.file_size = None;
+        }
+        if !config.enabled {
+            self.pending_lines.clear();
+        }
+        self.config = config;
+    }
+
+    pub fn record(&mut self, timestamp: Timestamp, event: AuditEvent) {
+        if !self.config.enabled {
+            return;
+        }
+        let record = AuditRecord {
+            timestamp,
+            event: &event,
+        };
+        match serde_json::to_vec(&record) {
+            Ok(line) => {
+                self.pending_lines.extend(line);
+                self.pending_lines.push(b'\n');
+            }
+            Err(error) => {
+                error!("failed serializing audit event : {:?} : {:?}", event, error);
+            }
+        }
+    }
+
+    pub fn record_all(&mut self, timestamp: Timestamp, events: Vec<AuditEvent>) {
+        for event in events {
+            self.record(timestamp, event);
+        }
+    }
+
+    /// Appends the recorded events to the file, rotating it first if it would grow over the max size
+    pub async fn flush(&mut self, storage: &mut Storage) {
+        if self.pending_lines.is_empty() {
+            return;
+        }
+        let file_path = self.config.file_path.clone();
+        let mut file_size = match self.file_size {
+            Some(file_size) => file_size,
+            None => {
+                if storage.file_exists(file_path.as_str()).await {
+                    storage
+                        .io_interface
+                        .get_value_size(file_path.clone())
+                        .await
+                        .unwrap_or(0)
+                } else {
+                    0
+                }
+            }
+        };
+        if file_size > 0
+            && file_size + self.pending_lines.len() as u64 > self.config.max_file_size_in_bytes
+        {
+            if let Err(error) = self.rotate(storage).await {
+                error!("failed rotating audit log : {:?} : {:?}", file_path, error);
+            }
+            file_size = 0;
+        }
+        let lines = std::mem::take(&mut self.pending_lines);
+        let length = lines.len() as u64;
+        match storage.io_interface.append_value(file_path, lines).await {
+            Ok(()) => {
+                self.file_size = Some(file_size + length);
+            }
+            Err(error) => {
+                error!("failed writing audit log : {:?}", error);
+                self.file_size = None;
+            }
+        }
+    }
+
+    fn get_rotated_file_path(&self, index: u64) -> String {
+        format!("{}.{}", self.config.file_path, index)
+    }
+
+    async fn rotate(&mut self, storage: &mut Storage) -> Result<(), Error> {
+        info!("rotating audit log : {:?}", self.config.file_path);
+        let max_rotated_files = self.config.max_rotated_files;
+        if max_rotated_files > 0 {
+            let oldest = self.get_rotated_file_path(max_rotated_files);
+            if storage.file_exists(oldest.as_str()).await {
+                storage.io_interface.remove_value(oldest).await?;
+            }
+        }
+        for index in (1..max_rotated_files).rev() {
+            let file_path = self.get_rotated_file_path(index);
+            if !storage.file_exists(file_path.as_str()).await {
+                continue;
+            }
+            Self::move_file(storage, file_path, self.get_rotated_file_path(index + 1)).await?;
+        }
+        if max_rotated_files > 0 {
+            Self::move_file(
+                storage,
+                self.config.file_path.clone(),
+                self.get_rotated_file_path(1),
+            )
+            .await?;
+        } else {
+            storage
+                .io_interface
+                .remove_value(self.config.file_path.clone())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn move_file(storage: &mut Storage, from: String, to: String) -> Result<(), Error> {
+        debug!("moving : {:?} to : {:?}", from, to);
+        let buffer = storage.io_interface.read_value(from.clone()).await?;
+        storage.io_interface.write_value(to, buffer).await?;
+        storage.io_interface.remove_value(from).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::test_io_handler::test::TestIOHandler;
+    use crate::core::data::audit_log::{AddBlockOutcome, AuditEvent, AuditLog, AuditLogConfig};
+    use crate::core::data::storage::Storage;
+
+    #[test]
+    fn audit_event_serialization_test() {
+        let mut audit_log = AuditLog::new(AuditLogConfig {
+            enabled: true,
+            ..Default::default()
+        });
+        audit_log.record(
+            10,
+            AuditEvent::add_block(
+                2,
+                &[1; 32],
+                &[0; 32],
+                &[2; 33],
+                AddBlockOutcome::AddedToLongestChain,
+            ),
+        );
+        audit_log.record(20, AuditEvent::reorg(3, &[3; 32], &[[4; 32]], &[[3; 32]]));
+        let lines: Vec<serde_json::Value> = String::from_utf8(audit_log.pending_lines.clone())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["timestamp"], 10);
+        assert_eq!(lines[0]["event"], "add_block");
+        assert_eq!(lines[0]["outcome"], "added_to_longest_chain");
+        assert_eq!(lines[0]["block_hash"], hex::encode([1; 32]));
+        assert_eq!(lines[1]["event"], "reorg");
+        assert_eq!(lines[1]["unwound_blocks"][0], hex::encode([4; 32]));
+
+        let mut audit_log = AuditLog::new(AuditLogConfig::default());
+        audit_log.record(
+            10,
+            AuditEvent::block_rejected(2, &[1; 32], &[2; 33], "".to_string()),
+        );
+        assert!(audit_log.pending_lines.is_empty());
+    }
+
+    #[tokio::test]
+    async fn audit_log_rotation_test() {
+        let file_path = "./data/test/audit/audit.log".to_string();
+        let mut storage = Storage::new(Box::new(TestIOHandler::new()));
+        for path in [
+            file_path.clone(),
+            file_path.clone() + ".1",
+            file_path.clone() + ".2",
+        ] {
+            if storage.file_exists(path.as_str()).await {
+                storage.io_interface.remove_value(path).await.unwrap();
+            }
+        }
+        let mut audit_log = AuditLog::new(AuditLogConfig {
+            enabled: true,
+            file_path: file_path.clone(),
+            max_file_size_in_bytes: 300,
+            max_rotated_files: 1,
+        });
+        for index in 0..10 {
+            audit_log.record(
+                index,
+                AuditEvent::block_rejected(index, &[1; 32], &[2; 33], "timestamp".to_string()),
+            );
+            audit_log.flush(&mut storage).await;
+        }
+        assert!(storage.file_exists(file_path.as_str()).await);
+        assert!(
+            storage
+                .file_exists((file_path.clone() + ".1").as_str())
+                .await
+        );
+        assert!(
+            !storage
+                .file_exists((file_path.clone() + ".2").as_str())
+                .await
+        );
+
+        let latest = String::from_utf8(storage.read(file_path.as_str()).await.unwrap()).unwrap();
+        assert!(latest.len() <= 300);
+        assert!(latest.ends_with("\"reason\":\"timestamp\"}\n"));
+        assert!(latest.contains("\"block_id\":9"));
+    }
+}
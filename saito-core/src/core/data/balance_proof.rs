@@ -0,0 +1,452 @@
+use std::io::{Error, ErrorKind};
+
+use ahash::AHashMap;
+use rayon::prelude::*;
+use tracing::{debug, warn};
+
+use crate::common::defs::{
+    BlockId, Currency, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature, SaitoUTXOSetKey,
+};
+use crate::core::data::block::{Block, BlockType, BLOCK_HEADER_SIZE};
+use crate::core::data::blockchain::Blockchain;
+use crate::core::data::crypto::{hash, sign, verify, verify_hash};
+use crate::core::data::merkle::{MerkleProofStep, MerkleTree, MERKLE_PROOF_STEP_SIZE};
+use crate::core::data::serialize::Serialize;
+use crate::core::data::slip::Slip;
+use crate::core::data::storage::Storage;
+use crate::core::data::transaction::Transaction;
+
+/// max number of slips proven in a single balance proof. the remaining slips are listed without proofs
+pub const MAX_BALANCE_PROOF_SLIPS: usize = 500;
+
+const UTXO_KEY_SIZE: usize = 66;
+
+/// Proof that a slip was created by a transaction included in a block. The lite client checks the block hash against
+/// the longest chain headers it has synced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlipProof {
+    /// header of the block which included the transaction
+    pub block: Block,
+    pub transaction: Transaction,
+    pub slip_index: u8,
+    /// path from the transaction to the merkle root of the block
+    pub merkle_proof: Vec<MerkleProofStep>,
+}
+
+impl SlipProof {
+    /// Returns None if the block doesn't have the transaction or the transaction doesn't have the slip
+    pub fn create(block: &Block, tx_ordinal: usize, slip_index: u8) -> Option<SlipProof> {
+        let transaction = block.transactions.get(tx_ordinal)?;
+        transaction.outputs.get(slip_index as usize)?;
+        let merkle_proof = MerkleTree::generate_proof(&block.transactions, tx_ordinal)?;
+        Some(SlipProof {
+            block: Block::deserialize_from_net(&block.serialize_for_net(BlockType::Header)),
+            transaction: transaction.clone(),
+            slip_index,
+            merkle_proof,
+        })
+    }
+
+    pub fn get_slip(&self) -> Option<&Slip> {
+        self.transaction.outputs.get(self.slip_index as usize)
+    }
+
+    /// hash of the block calculated from the header, so it doesn't depend on the hash sent by the full node
+    pub fn get_block_hash(&self) -> SaitoHash {
+        let pre_hash = hash(&self.block.serialize_for_signature());
+        hash(
+            &[
+                self.block.previous_block_hash.as_slice(),
+                pre_hash.as_slice(),
+            ]
+            .concat(),
+        )
+    }
+
+    /// Checks the block signature, the inclusion of the transaction in the block and that the slip was created in
+    /// the block
+    pub fn verify(&self) -> bool {
+        let pre_hash = hash(&self.block.serialize_for_signature());
+        if !verify_hash(&pre_hash, &self.block.signature, &self.block.creator) {
+            warn!(
+                "slip proof block : {:?} has an invalid signature",
+                self.block.id
+            );
+            return false;
+        }
+        let leaf = hash(&self.transaction.serialize_for_signature());
+        if !MerkleTree::verify_proof(&leaf, &self.merkle_proof, &self.block.merkle_root) {
+            warn!(
+                "transaction : {:?} is not in block : {:?}",
+                hex::encode(self.transaction.signature),
+                self.block.id
+            );
+            return false;
+        }
+        match self.get_slip() {
+            Some(slip) => slip.block_id == self.block.id,
+            None => false,
+        }
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let transaction = self.transaction.serialize_for_net();
+        [
+            [self.slip_index].as_slice(),
+            (self.merkle_proof.len() as u32).to_be_bytes().as_slice(),
+            self.merkle_proof
+                .iter()
+                .map(|step| [step.hash.as_slice(), [step.is_left as u8].as_slice()].concat())
+                .collect::<Vec<_>>()
+                .concat()
+                .as_slice(),
+            self.block.serialize_for_net(BlockType::Header).as_slice(),
+            (transaction.len() as u32).to_be_bytes().as_slice(),
+            transaction.as_slice(),
+        ]
+        .concat()
+    }
+
+    /// Reads a slip proof from the start of the buffer. Returns the proof and its serialized length
+    fn deserialize(buffer: &[u8]) -> Result<(SlipProof, usize), Error> {
+        if buffer.len() < 5 {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let slip_index = buffer[0];
+        let step_count = u32::from_be_bytes(buffer[1..5].try_into().unwrap()) as usize;
+        let mut offset = 5;
+        let steps = buffer
+            .get(offset..offset + step_count * MERKLE_PROOF_STEP_SIZE)
+            .ok_or(Error::from(ErrorKind::InvalidData))?;
+        let merkle_proof = steps
+            .chunks(MERKLE_PROOF_STEP_SIZE)
+            .map(|step| MerkleProofStep {
+                hash: step[0..32].try_into().unwrap(),
+                is_left: step[32] != 0,
+            })
+            .collect();
+        offset += steps.len();
+
+        let header = buffer
+            .get(offset..offset + BLOCK_HEADER_SIZE)
+            .ok_or(Error::from(ErrorKind::InvalidData))?;
+        if header[0..4] != [0; 4] {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let block = Block::deserialize_from_net(&header.to_vec());
+        offset += BLOCK_HEADER_SIZE;
+
+        let length = buffer
+            .get(offset..offset + 4)
+            .ok_or(Error::from(ErrorKind::InvalidData))?;
+        let length = u32::from_be_bytes(length.try_into().unwrap()) as usize;
+        offset += 4;
+        let transaction = buffer
+            .get(offset..offset + length)
+            .ok_or(Error::from(ErrorKind::InvalidData))?;
+        let transaction = Transaction::try_deserialize_from_net(transaction)?;
+        offset += length;
+
+        Ok((
+            SlipProof {
+                block,
+                transaction,
+                slip_index,
+                merkle_proof,
+            },
+            offset,
+        ))
+    }
+}
+
+/// Unspent slips of a public key in the longest chain of a full node, with the proofs of their creation. Lite clients
+/// can check the inclusion of the slips themselves. Whether they are still unspent can't be proven without the
+/// utxoset, so it is attested by the node's signature over the latest block and the slips.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceProof {
+    pub public_key: SaitoPublicKey,
+    pub latest_block_id: BlockId,
+    pub latest_block_hash: SaitoHash,
+    pub slip_proofs: Vec<SlipProof>,
+    /// unspent slips without a proof, since the creating transaction couldn't be loaded or the proof size limit was
+    /// reached
+    pub unproven_utxo_keys: Vec<SaitoUTXOSetKey>,
+    /// signed by the full node
+    pub signature: SaitoSignature,
+}
+
+impl BalanceProof {
+    /// Creates the proofs for the spendable slips of the public key. Transactions of pruned blocks are loaded from disk
+    pub async fn generate(
+        blockchain: &Blockchain,
+        storage: &Storage,
+        public_key: &SaitoPublicKey,
+    ) -> BalanceProof {
+        let mut utxo_keys: Vec<SaitoUTXOSetKey> = blockchain
+            .utxoset
+            .par_iter()
+            .filter(|(key, spendable)| **spendable && key[0..33] == *public_key)
+            .map(|(key, _)| *key)
+            .collect();
+        utxo_keys.sort_unstable();
+
+        let mut proof = BalanceProof {
+            public_key: *public_key,
+            latest_block_id: blockchain.get_latest_block_id(),
+            latest_block_hash: blockchain.get_latest_block_hash(),
+            slip_proofs: vec![],
+            unproven_utxo_keys: vec![],
+            signature: [0; 64],
+        };
+
+        // the oldest slips are proven first, since the keys are sorted by the block id after the public key
+        let mut keys_by_block: AHashMap<BlockId, Vec<SaitoUTXOSetKey>> = Default::default();
+        for (index, key) in utxo_keys.into_iter().enumerate() {
+            if index >= MAX_BALANCE_PROOF_SLIPS {
+                proof.unproven_utxo_keys.push(key);
+                continue;
+            }
+            let block_id = BlockId::from_be_bytes(key[33..41].try_into().unwrap());
+            keys_by_block.entry(block_id).or_default().push(key);
+        }
+
+        let mut block_ids: Vec<BlockId> = keys_by_block.keys().cloned().collect();
+        block_ids.sort_unstable();
+        for block_id in block_ids {
+            let keys = keys_by_block.remove(&block_id).unwrap();
+            let block_hash = blockchain
+                .blockring
+                .get_longest_chain_block_hash_by_block_id(block_id);
+            let block = match blockchain.get_full_block(&block_hash, storage).await {
+                Ok(block) => block,
+                Err(error) => {
+                    warn!(
+                        "loading block : {:?} for balance proof failed : {:?}",
+                        block_id, error
+                    );
+                    proof.unproven_utxo_keys.extend(keys);
+                    continue;
+                }
+            };
+            for key in keys {
+                let tx_ordinal = u64::from_be_bytes(key[41..49].try_into().unwrap());
+                let slip_proof =
+                    SlipProof::create(&block, tx_ordinal as usize, key[49]).filter(|slip_proof| {
+                        slip_proof
+                            .get_slip()
+                            .map_or(false, |slip| slip.get_utxoset_key() == key)
+                    });
+                match slip_proof {
+                    Some(slip_proof) => proof.slip_proofs.push(slip_proof),
+                    // rebroadcast slips keep the location of the original slip
+                    None => proof.unproven_utxo_keys.push(key),
+                }
+            }
+        }
+        proof.unproven_utxo_keys.sort_unstable();
+
+        debug!(
+            "balance proof generated for : {:?} with {:?} proven and {:?} unproven slips",
+            hex::encode(public_key),
+            proof.slip_proofs.len(),
+            proof.unproven_utxo_keys.len()
+        );
+        proof
+    }
+
+    /// total amount of the proven and unproven slips
+    pub fn get_balance(&self) -> Currency {
+        let proven: Currency = self
+            .slip_proofs
+            .iter()
+            .filter_map(|slip_proof| slip_proof.get_slip())
+            .map(|slip| slip.amount)
+            .sum();
+        let unproven: Currency = self
+            .unproven_utxo_keys
+            .iter()
+            .map(Slip::get_amount_from_utxoset_key)
+            .sum();
+        proven + unproven
+    }
+
+    fn get_hash_for_signature(&self) -> SaitoHash {
+        let slip_keys: Vec<SaitoUTXOSetKey> = self
+            .slip_proofs
+            .iter()
+            .filter_map(|slip_proof| slip_proof.get_slip())
+            .map(|slip| slip.get_utxoset_key())
+            .collect();
+        hash(
+            &[
+                self.public_key.as_slice(),
+                self.latest_block_id.to_be_bytes().as_slice(),
+                self.latest_block_hash.as_slice(),
+                slip_keys.concat().as_slice(),
+                self.unproven_utxo_keys.concat().as_slice(),
+            ]
+            .concat(),
+        )
+    }
+
+    pub fn sign(&mut self, private_key: &SaitoPrivateKey) {
+        self.signature = sign(&self.get_hash_for_signature(), private_key);
+    }
+
+    /// Checks the signature of the full node and the slip proofs. Block hashes of the slip proofs should be checked
+    /// against the longest chain separately.
+    pub fn verify(&self, node_public_key: &SaitoPublicKey) -> bool {
+        if !verify(
+            &self.get_hash_for_signature(),
+            &self.signature,
+            node_public_key,
+        ) {
+            warn!("balance proof has an invalid signature");
+            return false;
+        }
+        self.slip_proofs.iter().all(|slip_proof| {
+            slip_proof.verify()
+                && slip_proof
+                    .get_slip()
+                    .map_or(false, |slip| slip.public_key == self.public_key)
+        })
+    }
+}
+
+impl Serialize<Self> for BalanceProof {
+    fn serialize(&self) -> Vec<u8> {
+        [
+            self.public_key.as_slice(),
+            self.latest_block_id.to_be_bytes().as_slice(),
+            self.latest_block_hash.as_slice(),
+            self.signature.as_slice(),
+            (self.unproven_utxo_keys.len() as u32)
+                .to_be_bytes()
+                .as_slice(),
+            self.unproven_utxo_keys.concat().as_slice(),
+            (self.slip_proofs.len() as u32).to_be_bytes().as_slice(),
+            self.slip_proofs
+                .iter()
+                .map(|slip_proof| slip_proof.serialize())
+                .collect::<Vec<_>>()
+                .concat()
+                .as_slice(),
+        ]
+        .concat()
+    }
+
+    fn deserialize(buffer: &Vec<u8>) -> Result<Self, Error> {
+        if buffer.len() < 141 {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let unproven_count = u32::from_be_bytes(buffer[137..141].try_into().unwrap()) as usize;
+        let mut offset = 141;
+        let unproven_utxo_keys = buffer
+            .get(offset..offset + unproven_count * UTXO_KEY_SIZE)
+            .ok_or(Error::from(ErrorKind::InvalidData))?
+            .chunks(UTXO_KEY_SIZE)
+            .map(|key| key.try_into().unwrap())
+            .collect();
+        offset += unproven_count * UTXO_KEY_SIZE;
+
+        let proof_count = buffer
+            .get(offset..offset + 4)
+            .ok_or(Error::from(ErrorKind::InvalidData))?;
+        let proof_count = u32::from_be_bytes(proof_count.try_into().unwrap());
+        offset += 4;
+        let mut slip_proofs = vec![];
+        for _ in 0..proof_count {
+            let (slip_proof, length) = SlipProof::deserialize(&buffer[offset..])?;
+            slip_proofs.push(slip_proof);
+            offset += length;
+        }
+        if offset != buffer.len() {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        Ok(BalanceProof {
+            public_key: buffer[0..33].try_into().unwrap(),
+            latest_block_id: BlockId::from_be_bytes(buffer[33..41].try_into().unwrap()),
+            latest_block_hash: buffer[41..73].try_into().unwrap(),
+            signature: buffer[73..137].try_into().unwrap(),
+            slip_proofs,
+            unproven_utxo_keys,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::balance_proof::{BalanceProof, SlipProof};
+    use crate::core::data::block::Block;
+    use crate::core::data::crypto::generate_keys;
+    use crate::core::data::serialize::Serialize;
+    use crate::core::data::slip::Slip;
+    use crate::core::data::transaction::Transaction;
+
+    #[test]
+    fn balance_proof_test() {
+        let (creator, creator_private_key) = generate_keys();
+        let (node, node_private_key) = generate_keys();
+        let (receiver, _) = generate_keys();
+
+        let mut block = Block::new();
+        block.id = 5;
+        block.creator = creator;
+        for i in 0..3 {
+            let mut transaction = Transaction::default();
+            let mut output = Slip::default();
+            output.public_key = receiver;
+            output.amount = 1000 + i;
+            transaction.add_output(output);
+            transaction.timestamp = i as u64;
+            transaction.sign(&creator_private_key);
+            block.transactions.push(transaction);
+        }
+        block.generate();
+        block.merkle_root = block.generate_merkle_root();
+        block.sign(&creator_private_key);
+        block.generate_pre_hash();
+        block.generate_hash();
+
+        let slip_proof = SlipProof::create(&block, 1, 0).unwrap();
+        assert!(slip_proof.verify());
+        assert_eq!(slip_proof.get_block_hash(), block.hash);
+        assert_eq!(slip_proof.get_slip().unwrap().amount, 1001);
+        assert!(SlipProof::create(&block, 3, 0).is_none());
+        assert!(SlipProof::create(&block, 1, 1).is_none());
+
+        // transaction which doesn't match the merkle proof
+        let mut invalid = slip_proof.clone();
+        invalid.transaction = block.transactions[2].clone();
+        assert!(!invalid.verify());
+
+        let mut unproven_slip = Slip::default();
+        unproven_slip.public_key = receiver;
+        unproven_slip.amount = 500;
+        let mut proof = BalanceProof {
+            public_key: receiver,
+            latest_block_id: 10,
+            latest_block_hash: [1; 32],
+            slip_proofs: vec![slip_proof, SlipProof::create(&block, 2, 0).unwrap()],
+            unproven_utxo_keys: vec![unproven_slip.get_utxoset_key()],
+            signature: [0; 64],
+        };
+        proof.sign(&node_private_key);
+        assert!(proof.verify(&node));
+        assert!(!proof.verify(&creator));
+        assert_eq!(proof.get_balance(), 1001 + 1002 + 500);
+
+        let buffer = proof.serialize();
+        let deserialized = BalanceProof::deserialize(&buffer).unwrap();
+        assert!(deserialized.verify(&node));
+        assert_eq!(deserialized.serialize(), buffer);
+        assert_eq!(deserialized.get_balance(), proof.get_balance());
+        assert!(BalanceProof::deserialize(&buffer[..buffer.len() - 1].to_vec()).is_err());
+
+        // slips of another key
+        proof.public_key = creator;
+        proof.sign(&node_private_key);
+        assert!(!proof.verify(&node));
+    }
+}
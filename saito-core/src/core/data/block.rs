@@ -1,26 +1,43 @@
 use std::convert::TryInto;
+use std::io::{Error, ErrorKind};
 use std::ops::Rem;
 use std::{i128, mem};
 
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info, trace, warn, Instrument};
 
 use crate::common::defs::{
-    Currency, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature, SaitoUTXOSetKey, UtxoSet,
+    Currency, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature, SaitoUTXOSetKey,
+    Timestamp, UtxoSet,
 };
-use crate::core::data::blockchain::{Blockchain, GENESIS_PERIOD, MAX_STAKER_RECURSION};
+use crate::common::validation_telemetry::{ValidationStage, ValidationStageTimer};
+use crate::core::consensus::amounts::{checked_add_amounts, is_valid_amount};
+use crate::core::consensus::atr::collect_rebroadcasts;
+use crate::core::consensus::rewards::{
+    calculate_block_payout, create_fee_transaction, split_miner_router_payout,
+    split_staker_router_payout,
+};
+use crate::core::data::blockchain::{Blockchain, MAX_STAKER_RECURSION};
 use crate::core::data::burnfee::BurnFee;
 use crate::core::data::crypto::{hash, sign, verify_hash};
+use crate::core::data::format_version::{
+    get_format_envelope, read_format_envelope, CURRENT_FORMAT_VERSION, LEGACY_FORMAT_VERSION,
+};
 use crate::core::data::golden_ticket::GoldenTicket;
-use crate::core::data::hop::HOP_SIZE;
-use crate::core::data::merkle::MerkleTree;
-use crate::core::data::slip::{Slip, SlipType, SLIP_SIZE};
+use crate::core::data::merkle::{MerkleTree, MerkleTreeBuilder};
 use crate::core::data::storage::Storage;
-use crate::core::data::transaction::{Transaction, TransactionType, TRANSACTION_SIZE};
+use crate::core::data::transaction::{Transaction, TransactionType};
 
-pub const BLOCK_HEADER_SIZE: usize = 301;
+pub const BLOCK_HEADER_SIZE: usize = 333;
+/// header size of blocks in the legacy format version, which don't have the utxoset commitment at the end
+pub const LEGACY_BLOCK_HEADER_SIZE: usize = 301;
+/// blocks with an id divisible by this commit to the utxoset after their previous block
+pub const UTXOSET_COMMITMENT_INTERVAL: u64 = 10;
+/// first block which can commit to the utxoset. blocks before this were created without the commitment and are
+/// still read from the legacy block files, so their commitment stays empty
+pub const UTXOSET_COMMITMENT_ACTIVATION_BLOCK_ID: u64 = 1_000_000;
 
 //
 // object used when generating and validation transactions, containing the
@@ -155,6 +172,21 @@ pub enum BlockType {
     Full,
 }
 
+/// Reasons for rejecting a block by its timestamp. The burn fee depends on the time between blocks, so a block
+/// creator could lower the fee by dating the block too far ahead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockTimestampError {
+    NotAfterPreviousBlock {
+        timestamp: Timestamp,
+        previous_block_timestamp: Timestamp,
+    },
+    TooFarInFuture {
+        timestamp: Timestamp,
+        current_time: Timestamp,
+        max_drift: Timestamp,
+    },
+}
+
 #[serde_with::serde_as]
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Block {
@@ -175,6 +207,8 @@ pub struct Block {
     avg_variance: Currency,
     avg_atr_income: Currency,
     avg_atr_variance: Currency,
+    /// commitment to the utxoset after the previous block. zeros except in every `UTXOSET_COMMITMENT_INTERVAL`th block
+    pub(crate) utxoset_commitment: SaitoHash,
     /// Transactions
     pub transactions: Vec<Transaction>,
     /// Self-Calculated / Validated
@@ -215,6 +249,17 @@ pub struct Block {
     // the peer's connection ID who sent us this block
     #[serde(skip)]
     pub(crate) source_connection_id: Option<SaitoPublicKey>,
+    // result of the last validation, so the block is not validated again when the chain is rewound
+    #[serde(skip)]
+    validation_cache: Option<ValidationCacheEntry>,
+}
+
+/// Block validation depends on the chain up to the parent block. So a result can be reused as long as
+/// the chain tip is the same block as when the validation was done.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ValidationCacheEntry {
+    pub validated_against: SaitoHash,
+    pub result: bool,
 }
 
 impl Block {
@@ -235,6 +280,7 @@ impl Block {
             avg_variance: 0,
             avg_atr_income: 0,
             avg_atr_variance: 0,
+            utxoset_commitment: [0; 32],
             transactions: vec![],
             pre_hash: [0; 32],
             hash: [0; 32],
@@ -257,6 +303,7 @@ impl Block {
             slips_spent_this_block: AHashMap::new(),
             created_hashmap_of_slips_spent_this_block: false,
             source_connection_id: None,
+            validation_cache: None,
         }
     }
 
@@ -264,6 +311,74 @@ impl Block {
         self.transactions.push(tx);
     }
 
+    /// whether the block commits to the utxoset after its previous block
+    pub fn has_utxoset_commitment(&self) -> bool {
+        self.id >= UTXOSET_COMMITMENT_ACTIVATION_BLOCK_ID
+            && self.id % UTXOSET_COMMITMENT_INTERVAL == 0
+    }
+
+    /// Checks the commitment against the utxoset after the previous block. Only possible when the utxoset was
+    /// built from the whole chain, so it's checked only for blocks with a previous block
+    pub fn validate_utxoset_commitment(&self, utxoset: &UtxoSet) -> bool {
+        if !self.has_utxoset_commitment() {
+            return self.utxoset_commitment == [0; 32];
+        }
+        self.utxoset_commitment == utxoset.get_commitment()
+    }
+
+    /// Checks that the transaction totals don't overflow, and that the transactions spending slips don't output more
+    /// than their inputs in total. Fee, ATR, issuance and vip transactions create their outputs from the previous
+    /// blocks' fees, rebroadcast slips and the initial supply, so they are only checked for overflows
+    pub fn validate_amounts(&self) -> bool {
+        let mut total_in: Currency = 0;
+        let mut total_out: Currency = 0;
+        for transaction in self.transactions.iter() {
+            if !is_valid_amount(transaction.total_in) || !is_valid_amount(transaction.total_out) {
+                error!(
+                    "ERROR 802396: tx : {:?} in block : {:?} has amounts overflowing. total in : {:?} total out : {:?}",
+                    hex::encode(transaction.signature),
+                    self.id,
+                    transaction.total_in,
+                    transaction.total_out
+                );
+                return false;
+            }
+            if matches!(
+                transaction.transaction_type,
+                TransactionType::Fee
+                    | TransactionType::ATR
+                    | TransactionType::Issuance
+                    | TransactionType::Vip
+            ) {
+                continue;
+            }
+            match (
+                checked_add_amounts(total_in, transaction.total_in),
+                checked_add_amounts(total_out, transaction.total_out),
+            ) {
+                (Some(new_total_in), Some(new_total_out)) => {
+                    total_in = new_total_in;
+                    total_out = new_total_out;
+                }
+                _ => {
+                    error!(
+                        "ERROR 802397: total amounts of block : {:?} overflow",
+                        self.id
+                    );
+                    return false;
+                }
+            }
+        }
+        if total_out > total_in {
+            error!(
+                "ERROR 802398: block : {:?} outputs : {:?} which is more than its inputs : {:?}",
+                self.id, total_out, total_in
+            );
+            return false;
+        }
+        true
+    }
+
     //
     // returns valid block
     //
@@ -275,7 +390,8 @@ impl Block {
         public_key: &SaitoPublicKey,
         private_key: &SaitoPrivateKey,
         golden_ticket: Option<Transaction>,
-    ) -> Block {
+        storage: &Storage,
+    ) -> Result<Block, Error> {
         debug!(
             "Block::create : previous block hash : {:?}",
             hex::encode(previous_block_hash)
@@ -315,6 +431,10 @@ impl Block {
 
         block.creator = *public_key;
 
+        if block.has_utxoset_commitment() {
+            block.utxoset_commitment = blockchain.utxoset.get_commitment();
+        }
+
         if golden_ticket.is_some() {
             debug!("golden ticket found. adding to block.");
             block.transactions.push(golden_ticket.unwrap());
@@ -327,6 +447,27 @@ impl Block {
         // block.transactions = transactions.drain().collect();
         transactions.clear();
 
+        //
+        // handles are first-come-first-served, so registrations of handles
+        // owned by other keys are dropped instead of invalidating the block
+        //
+        let conflicting_registrations = blockchain
+            .get_handle_registry()
+            .find_conflicting_registrations(block.id, &block.transactions);
+        for index in conflicting_registrations.into_iter().rev() {
+            let transaction = block.transactions.remove(index);
+            debug!(
+                "dropping handle registration : {:?} since the handle is not available",
+                hex::encode(transaction.signature)
+            );
+        }
+
+        //
+        // the merkle tree is built with the bundled transactions here, so adding the ATR and
+        // fee transactions later only rehashes the right-most path of the tree
+        //
+        let mut merkle_tree_builder = MerkleTreeBuilder::from_transactions(&block.transactions);
+
         //
         // update slips_spent_this_block so that we have a record of
         // how many times input slips are spent in this block. we will
@@ -358,7 +499,21 @@ impl Block {
         //
         // contextual values
         //
-        let mut cv: ConsensusValues = block.generate_consensus_values(&blockchain).await;
+        let mut cv: ConsensusValues =
+            match block.generate_consensus_values(blockchain, storage).await {
+                Ok(cv) => cv,
+                Err(error) => {
+                    // transactions are given back so they can be bundled in the next block
+                    transactions.extend(
+                        block
+                            .transactions
+                            .drain(..)
+                            .filter(|tx| !tx.is_golden_ticket())
+                            .map(|tx| (tx.signature, tx)),
+                    );
+                    return Err(error);
+                }
+            };
 
         //
         // ATR transactions
@@ -371,6 +526,9 @@ impl Block {
             .enumerate()
             .all(|(index, tx)| tx.generate(&public_key, index as u64, block.id));
         if rlen > 0 {
+            for tx in cv.rebroadcasts.iter() {
+                merkle_tree_builder.push_transaction(tx);
+            }
             block.transactions.append(&mut cv.rebroadcasts);
         }
 
@@ -392,6 +550,7 @@ impl Block {
             //
             // and we add it to the block
             //
+            merkle_tree_builder.push_transaction(&fee_tx);
             block.add_transaction(fee_tx);
         }
 
@@ -448,7 +607,7 @@ impl Block {
         //
         // generate merkle root
         //
-        let block_merkle_root = block.generate_merkle_root();
+        let block_merkle_root = merkle_tree_builder.get_root_hash().unwrap_or([0; 32]);
         block.merkle_root = block_merkle_root;
 
         block.avg_income = cv.avg_income;
@@ -465,7 +624,7 @@ impl Block {
         // block.generate_hash();
         block.generate();
 
-        block
+        Ok(block)
     }
 
     //
@@ -490,9 +649,15 @@ impl Block {
     /// [staking_treasury - 8 bytes - u64]
     /// [burnfee - 8 bytes - u64]
     /// [difficulty - 8 bytes - u64]
+    /// [avg_income - 16 bytes - u128]
+    /// [avg_variance - 16 bytes - u128]
+    /// [avg_atr_income - 16 bytes - u128]
+    /// [avg_atr_variance - 16 bytes - u128]
+    /// [utxoset_commitment - 32 bytes - SHA 256 hash]
     /// [transaction][transaction][transaction]...
-    pub fn deserialize_from_net(bytes: &Vec<u8>) -> Block {
-        // TODO : return Option<Block> to support invalid buffers
+    ///
+    /// Panics if the buffer is malformed. Buffers from peers are read with `try_deserialize_from_net`
+    pub fn deserialize_from_net(bytes: &[u8]) -> Block {
         let transactions_len: u32 = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
         let id: u64 = u64::from_be_bytes(bytes[4..12].try_into().unwrap());
         let timestamp: u64 = u64::from_be_bytes(bytes[12..20].try_into().unwrap());
@@ -513,38 +678,17 @@ impl Block {
         let avg_atr_income: Currency = Currency::from_be_bytes(bytes[269..285].try_into().unwrap());
         let avg_atr_variance: Currency =
             Currency::from_be_bytes(bytes[285..301].try_into().unwrap());
+        let utxoset_commitment: SaitoHash = bytes[301..333].try_into().unwrap();
 
         let mut transactions = vec![];
         let mut start_of_transaction_data = BLOCK_HEADER_SIZE;
         for _n in 0..transactions_len {
-            let inputs_len: u32 = u32::from_be_bytes(
-                bytes[start_of_transaction_data..start_of_transaction_data + 4]
-                    .try_into()
-                    .unwrap(),
-            );
-            let outputs_len: u32 = u32::from_be_bytes(
-                bytes[start_of_transaction_data + 4..start_of_transaction_data + 8]
-                    .try_into()
-                    .unwrap(),
-            );
-            let message_len: usize = u32::from_be_bytes(
-                bytes[start_of_transaction_data + 8..start_of_transaction_data + 12]
-                    .try_into()
-                    .unwrap(),
-            ) as usize;
-            let path_len: usize = u32::from_be_bytes(
-                bytes[start_of_transaction_data + 12..start_of_transaction_data + 16]
-                    .try_into()
-                    .unwrap(),
-            ) as usize;
             let end_of_transaction_data = start_of_transaction_data
-                + TRANSACTION_SIZE
-                + ((inputs_len + outputs_len) as usize * SLIP_SIZE)
-                + message_len
-                + path_len as usize * HOP_SIZE;
+                + Transaction::get_serialized_len(&bytes[start_of_transaction_data..]);
             let transaction = Transaction::deserialize_from_net(
                 &bytes[start_of_transaction_data..end_of_transaction_data].to_vec(),
-            );
+            )
+            .unwrap();
             transactions.push(transaction);
             start_of_transaction_data = end_of_transaction_data;
         }
@@ -564,6 +708,7 @@ impl Block {
         block.avg_variance = avg_variance;
         block.avg_atr_income = avg_atr_income;
         block.avg_atr_variance = avg_atr_variance;
+        block.utxoset_commitment = utxoset_commitment;
         block.transactions = transactions.to_vec();
 
         debug!("block.deserialize tx length = {:?}", transactions_len);
@@ -573,6 +718,95 @@ impl Block {
 
         block
     }
+    /// Same as `deserialize_from_net`, but returns an error instead of panicking if the buffer is malformed
+    pub fn try_deserialize_from_net(bytes: &[u8]) -> Result<Block, Error> {
+        Block::validate_net_buffer(bytes)?;
+        Ok(Block::deserialize_from_net(bytes))
+    }
+
+    /// Reads a block serialized with the legacy 301 byte header. Panics if the buffer is malformed
+    pub fn deserialize_legacy_from_net(bytes: &[u8]) -> Block {
+        let buffer =
+            Block::upgrade_legacy_net_buffer(bytes).expect("legacy block header is too short");
+        Block::deserialize_from_net(&buffer)
+    }
+
+    /// Same as `deserialize_legacy_from_net`, but returns an error instead of panicking if the buffer is malformed
+    pub fn try_deserialize_legacy_from_net(bytes: &[u8]) -> Result<Block, Error> {
+        let buffer = Block::upgrade_legacy_net_buffer(bytes)?;
+        Block::try_deserialize_from_net(&buffer)
+    }
+
+    /// Converts a block serialized with the legacy header to the current layout by adding an empty utxoset
+    /// commitment. Legacy blocks never have a commitment, so their hash and signature stay the same
+    pub fn upgrade_legacy_net_buffer(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        if bytes.len() < LEGACY_BLOCK_HEADER_SIZE {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        Ok([
+            &bytes[..LEGACY_BLOCK_HEADER_SIZE],
+            [0; 32].as_slice(),
+            &bytes[LEGACY_BLOCK_HEADER_SIZE..],
+        ]
+        .concat())
+    }
+
+    /// Reads a block serialized in any supported format version. Buffers without a format envelope are read with the
+    /// legacy layout
+    pub fn try_deserialize_versioned(bytes: &[u8]) -> Result<Block, Error> {
+        let (version, buffer) = read_format_envelope(bytes)?;
+        match version {
+            LEGACY_FORMAT_VERSION => Block::try_deserialize_legacy_from_net(buffer),
+            CURRENT_FORMAT_VERSION => Block::try_deserialize_from_net(buffer),
+            _ => Err(Error::from(ErrorKind::InvalidData)),
+        }
+    }
+
+    /// Reads a block file which was checked with `Storage::verify_block_file`. Panics if the buffer is malformed
+    pub fn deserialize_from_disk(bytes: &[u8]) -> Block {
+        let (version, buffer) =
+            read_format_envelope(bytes).expect("block file format should be verified");
+        if version == LEGACY_FORMAT_VERSION {
+            return Block::deserialize_legacy_from_net(buffer);
+        }
+        Block::deserialize_from_net(buffer)
+    }
+
+    /// Checks that the buffer is exactly one serialized block and each of its transactions can be read by
+    /// `deserialize_from_net`
+    pub fn validate_net_buffer(bytes: &[u8]) -> Result<(), Error> {
+        if Block::try_get_serialized_len(bytes) != Some(bytes.len()) {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let mut start_of_transaction_data = BLOCK_HEADER_SIZE;
+        while start_of_transaction_data < bytes.len() {
+            let transaction_data = &bytes[start_of_transaction_data..];
+            let length = Transaction::try_get_serialized_len(transaction_data)
+                .ok_or_else(|| Error::from(ErrorKind::InvalidData))?;
+            Transaction::validate_net_buffer(&transaction_data[..length])?;
+            start_of_transaction_data += length;
+        }
+        Ok(())
+    }
+
+    /// Same as `validate_net_buffer` for a block serialized with the legacy header
+    pub fn validate_legacy_net_buffer(bytes: &[u8]) -> Result<(), Error> {
+        Block::validate_net_buffer(&Block::upgrade_legacy_net_buffer(bytes)?)
+    }
+
+    /// Returns the length of the serialized block by walking the transactions in the buffer. Returns None if the
+    /// buffer is too short, so truncated buffers can be detected without panicking in `deserialize_from_net`
+    pub fn try_get_serialized_len(bytes: &[u8]) -> Option<usize> {
+        if bytes.len() < BLOCK_HEADER_SIZE {
+            return None;
+        }
+        let transactions_len: u32 = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
+        let mut length = BLOCK_HEADER_SIZE;
+        for _ in 0..transactions_len {
+            length += Transaction::try_get_serialized_len(&bytes[length..])?;
+        }
+        Some(length)
+    }
     //
     // downgrade block
     //
@@ -649,7 +883,7 @@ impl Block {
         //
         if winning_tx.transaction_type == TransactionType::ATR {
             let tmptx = winning_tx.message.to_vec();
-            winning_tx_placeholder = Transaction::deserialize_from_net(&tmptx);
+            winning_tx_placeholder = Transaction::deserialize_from_net(&tmptx).unwrap();
             winning_tx = &winning_tx_placeholder;
         }
 
@@ -727,7 +961,8 @@ impl Block {
             let transaction = &mut self.transactions[i];
 
             cumulative_fees = transaction.generate_cumulative_fees(cumulative_fees);
-            total_work += transaction.total_work_for_me;
+            total_work = checked_add_amounts(total_work, transaction.total_work_for_me)
+                .unwrap_or(Currency::MAX);
 
             //
             // update slips_spent_this_block so that we have a record of
@@ -776,7 +1011,9 @@ impl Block {
 
                     for input in transaction.inputs.iter() {
                         self.total_rebroadcast_slips += 1;
-                        self.total_rebroadcast_nolan += input.amount;
+                        self.total_rebroadcast_nolan =
+                            checked_add_amounts(self.total_rebroadcast_nolan, input.amount)
+                                .unwrap_or(Currency::MAX);
                     }
                 }
                 _ => {}
@@ -832,21 +1069,33 @@ impl Block {
     }
 
     // generate dynamic consensus values
+    /// Calculates the values of this block which depend on the previous blocks. Returns an error if the block which
+    /// the ATR transactions are rebroadcast from was pruned and can't be loaded from disk
     #[tracing::instrument(level = "trace", skip_all)]
-    pub async fn generate_consensus_values(&self, blockchain: &Blockchain) -> ConsensusValues {
+    pub async fn generate_consensus_values(
+        &self,
+        blockchain: &Blockchain,
+        storage: &Storage,
+    ) -> Result<ConsensusValues, Error> {
         debug!("generate consensus values");
         let mut cv = ConsensusValues::new();
+        let genesis_period = blockchain.get_genesis_period();
 
         //
         // calculate total fees
         //
         let mut index: usize = 0;
         for transaction in &self.transactions {
-            if !transaction.is_fee_transaction() {
-                cv.total_fees += transaction.total_fees;
-            } else {
+            if transaction.is_fee_transaction() {
                 cv.ft_num += 1;
                 cv.ft_index = Some(index);
+            } else if !transaction.is_atr_transaction() {
+                // the rebroadcasts are added after the consensus values are generated for a new block, so their fees
+                // are left out in the validation as well
+                //
+                // overflowing fees are rejected with the amount checks in the validation
+                cv.total_fees = checked_add_amounts(cv.total_fees, transaction.total_fees)
+                    .unwrap_or(Currency::MAX);
             }
             if transaction.is_golden_ticket() {
                 cv.gt_num += 1;
@@ -862,73 +1111,47 @@ impl Block {
         //
         // calculate automatic transaction rebroadcasts / ATR / atr
         //
-        if self.id > GENESIS_PERIOD + 1 {
+        if self.id > genesis_period + 1 {
             let pruned_block_hash = blockchain
                 .blockring
-                .get_longest_chain_block_hash_by_block_id(self.id - GENESIS_PERIOD);
+                .get_longest_chain_block_hash_by_block_id(self.id - genesis_period);
 
             //
-            // generate metadata should have prepared us with a pre-prune block
-            // that contains all of the transactions and is ready to have its
-            // ATR rebroadcasts calculated.
+            // the block falling off the chain is usually pruned already, so its
+            // transactions are loaded from disk to calculate the ATR rebroadcasts
             //
-            if let Some(pruned_block) = blockchain.blocks.get(&pruned_block_hash) {
+            if blockchain.blocks.contains_key(&pruned_block_hash) {
+                let pruned_block = blockchain
+                    .get_full_block(&pruned_block_hash, storage)
+                    .await
+                    .map_err(|error| {
+                        error!(
+                            "cannot load block : {:?} to calculate the ATR transactions : {:?}",
+                            hex::encode(pruned_block_hash),
+                            error
+                        );
+                        error
+                    })?;
                 //
-                // identify all unspent transactions
+                // slips spent by the other transactions of this block are not
+                // rebroadcast, so they are not spent twice
                 //
-                for transaction in &pruned_block.transactions {
-                    for output in transaction.outputs.iter() {
-                        //
-                        // these need to be calculated dynamically based on the
-                        // value of the UTXO and the byte-size of the transaction
-                        //
-                        let rebroadcast_fee = 200_000_000;
-                        let staking_subsidy = 100_000_000;
-                        let utxo_adjustment = rebroadcast_fee - staking_subsidy;
-
-                        //
-                        // valid means spendable and non-zero
-                        //HACK
-                        if output.validate(&blockchain.utxoset) {
-                            if output.amount > utxo_adjustment {
-                                cv.total_rebroadcast_nolan += output.amount;
-                                cv.total_rebroadcast_fees_nolan += rebroadcast_fee;
-                                cv.total_rebroadcast_staking_payouts_nolan += staking_subsidy;
-                                cv.total_rebroadcast_slips += 1;
-
-                                //
-                                // create rebroadcast transaction
-                                //
-                                let rebroadcast_transaction =
-                                    Transaction::create_rebroadcast_transaction(
-                                        transaction,
-                                        output,
-                                        rebroadcast_fee,
-                                        staking_subsidy,
-                                    );
-
-                                //
-                                // update cryptographic hash of all ATRs
-                                //
-                                let mut vbytes: Vec<u8> = vec![];
-                                vbytes.extend(&cv.rebroadcast_hash);
-                                vbytes.extend(&rebroadcast_transaction.serialize_for_signature());
-                                cv.rebroadcast_hash = hash(&vbytes);
-
-                                cv.rebroadcasts.push(rebroadcast_transaction);
-                            } else {
-                                //
-                                // rebroadcast dust is either collected into the treasury or
-                                // distributed as a fee for the next block producer. for now
-                                // we will simply distribute it as a fee. we may need to
-                                // change this if the DUST becomes a significant enough amount
-                                // each block to reduce consensus security.
-                                //
-                                cv.total_rebroadcast_fees_nolan += output.amount;
-                            }
-                        }
-                    }
-                }
+                let spent_in_block: AHashSet<SaitoUTXOSetKey> = self
+                    .transactions
+                    .iter()
+                    .filter(|transaction| !transaction.is_atr_transaction())
+                    .flat_map(|transaction| transaction.inputs.iter())
+                    .map(|input| input.get_utxoset_key())
+                    .collect();
+                let rebroadcasts =
+                    collect_rebroadcasts(&pruned_block, &blockchain.utxoset, &spent_in_block);
+                cv.total_rebroadcast_slips = rebroadcasts.total_slips;
+                cv.total_rebroadcast_nolan = rebroadcasts.total_nolan;
+                cv.total_rebroadcast_fees_nolan = rebroadcasts.total_fees_nolan;
+                cv.total_rebroadcast_staking_payouts_nolan =
+                    rebroadcasts.total_staking_payouts_nolan;
+                cv.rebroadcast_hash = rebroadcasts.rebroadcast_hash;
+                cv.rebroadcasts = rebroadcasts.transactions;
             }
         }
 
@@ -943,14 +1166,14 @@ impl Block {
 
             if previous_block.avg_income > cv.total_fees {
                 let adjustment = (previous_block.avg_income as i128 - cv.total_fees as i128)
-                    / GENESIS_PERIOD as i128;
+                    / genesis_period as i128;
                 if adjustment > 0 {
                     cv.avg_income -= adjustment as Currency;
                 }
             }
             if previous_block.avg_income < cv.total_fees {
                 let adjustment = (cv.total_fees as i128 - previous_block.avg_income as i128)
-                    / GENESIS_PERIOD as i128;
+                    / genesis_period as i128;
                 if adjustment > 0 {
                     cv.avg_income += adjustment as Currency;
                 }
@@ -961,28 +1184,29 @@ impl Block {
             //
             if previous_block.avg_atr_income > cv.total_rebroadcast_nolan {
                 let adjustment = (previous_block.avg_atr_income - cv.total_rebroadcast_nolan)
-                    / GENESIS_PERIOD as Currency;
+                    / genesis_period as Currency;
                 if adjustment > 0 {
                     cv.avg_atr_income -= adjustment;
                 }
             }
             if previous_block.avg_atr_income < cv.total_rebroadcast_nolan {
                 let adjustment = (cv.total_rebroadcast_nolan - previous_block.avg_atr_income)
-                    / GENESIS_PERIOD as Currency;
+                    / genesis_period as Currency;
                 if adjustment > 0 {
                     cv.avg_atr_income += adjustment;
                 }
             }
 
-            let difficulty = previous_block.difficulty;
-            if previous_block.has_golden_ticket && cv.gt_num == 0 {
-                if difficulty > 0 {
-                    cv.expected_difficulty = previous_block.difficulty - 1;
-                }
-            } else if previous_block.has_golden_ticket && cv.gt_num > 0 {
-                cv.expected_difficulty = difficulty + 1;
-            } else {
-                cv.expected_difficulty = difficulty;
+            cv.expected_difficulty = blockchain.get_difficulty_policy().get_expected_difficulty(
+                previous_block.difficulty,
+                previous_block.has_golden_ticket,
+                cv.gt_num,
+            );
+            if cv.expected_difficulty != previous_block.difficulty {
+                debug!(
+                    "difficulty retargeted from {:?} to {:?} for block after : {:?}",
+                    previous_block.difficulty, cv.expected_difficulty, previous_block.id
+                );
             }
         } else {
             //
@@ -1013,15 +1237,10 @@ impl Block {
                 //
                 // limit previous block payout to avg income
                 //
-                let mut previous_block_payout = previous_block.total_fees;
-                if previous_block_payout > (previous_block.avg_income as f64 * 1.25) as Currency
-                    && previous_block_payout > 50
-                {
-                    previous_block_payout = (previous_block.avg_income as f64 * 1.24) as Currency;
-                }
-
-                let miner_payment = previous_block_payout / 2;
-                let router_payment = previous_block_payout - miner_payment;
+                let previous_block_payout =
+                    calculate_block_payout(previous_block.total_fees, previous_block.avg_income);
+                let (miner_payment, router_payment) =
+                    split_miner_router_payout(previous_block_payout);
 
                 //
                 // calculate miner and router payments
@@ -1081,17 +1300,12 @@ impl Block {
                             // be withheld for the staker treasury, which is what previous_staker_
                             // payment is measuring.
                             //
-                            let mut previous_staking_block_payout = staking_block.total_fees;
-                            if previous_staking_block_payout
-                                > (staking_block.avg_income as f64 * 1.25) as Currency
-                                && previous_staking_block_payout > 50
-                            {
-                                previous_staking_block_payout =
-                                    (staking_block.avg_income as f64 * 1.24) as Currency;
-                            }
-
-                            let sp = previous_staking_block_payout / 2;
-                            let rp = previous_staking_block_payout - sp;
+                            let previous_staking_block_payout = calculate_block_payout(
+                                staking_block.total_fees,
+                                staking_block.avg_income,
+                            );
+                            let (sp, rp) =
+                                split_staker_router_payout(previous_staking_block_payout);
 
                             let mut payout = BlockPayout::new();
                             payout.router = staking_block.find_winning_router(next_random_number);
@@ -1111,32 +1325,7 @@ impl Block {
             //
             // now create fee transaction using the block payout data
             //
-            let mut slip_index = 0;
-            let mut transaction = Transaction::default();
-            transaction.transaction_type = TransactionType::Fee;
-
-            for i in 0..cv.block_payout.len() {
-                if cv.block_payout[i].miner != [0; 33] {
-                    let mut output = Slip::default();
-                    output.public_key = cv.block_payout[i].miner;
-                    output.amount = cv.block_payout[i].miner_payout;
-                    output.slip_type = SlipType::MinerOutput;
-                    output.slip_index = slip_index;
-                    transaction.add_output(output.clone());
-                    slip_index += 1;
-                }
-                if cv.block_payout[i].router != [0; 33] {
-                    let mut output = Slip::default();
-                    output.public_key = cv.block_payout[i].router;
-                    output.amount = cv.block_payout[i].router_payout;
-                    output.slip_type = SlipType::RouterOutput;
-                    output.slip_index = slip_index;
-                    transaction.add_output(output.clone());
-                    slip_index += 1;
-                }
-            }
-
-            cv.fee_transaction = Some(transaction);
+            cv.fee_transaction = Some(create_fee_transaction(&cv.block_payout));
         }
 
         //
@@ -1181,7 +1370,7 @@ impl Block {
             }
         }
 
-        cv
+        Ok(cv)
     }
     #[tracing::instrument(level = "trace", skip_all)]
     pub fn generate_pre_hash(&mut self) {
@@ -1196,9 +1385,17 @@ impl Block {
             self.id,
             hex::encode(self.hash)
         );
-        for tx in &self.transactions {
-            tx.on_chain_reorganization(utxoset, longest_chain, self.id);
-        }
+        let updates: Vec<(SaitoUTXOSetKey, bool)> = self
+            .transactions
+            .iter()
+            .flat_map(|tx| tx.get_utxoset_updates(longest_chain))
+            .collect();
+        trace!(
+            "updating {:?} slips in utxoset for block : {:?}",
+            updates.len(),
+            self.id
+        );
+        utxoset.apply_updates(updates);
         self.in_longest_chain = longest_chain;
         true
     }
@@ -1230,6 +1427,13 @@ impl Block {
     // to avoid speed issues.
     #[tracing::instrument(level = "trace", skip_all)]
     pub fn serialize_for_signature(&self) -> Vec<u8> {
+        // blocks without a commitment are signed the same way as before the commitment was added. the commitment
+        // is never zero, so it can't be set in those blocks without invalidating the signature
+        let utxoset_commitment: &[u8] = if self.utxoset_commitment != [0; 32] {
+            self.utxoset_commitment.as_slice()
+        } else {
+            &[]
+        };
         [
             self.id.to_be_bytes().as_slice(),
             self.timestamp.to_be_bytes().as_slice(),
@@ -1244,6 +1448,7 @@ impl Block {
             self.avg_variance.to_be_bytes().as_slice(),
             self.avg_atr_income.to_be_bytes().as_slice(),
             self.avg_atr_variance.to_be_bytes().as_slice(),
+            utxoset_commitment,
         ]
         .concat()
     }
@@ -1264,6 +1469,7 @@ impl Block {
     /// [avg_variance - 8 bytes - u64]
     /// [avg_atr_income - 8 bytes - u64]
     /// [avg_atr_variance - 8 bytes - u64]
+    /// [utxoset_commitment - 32 bytes - SHA 256 hash]
     /// [transaction][transaction][transaction]...
     #[tracing::instrument(level = "trace", skip_all)]
     pub fn serialize_for_net(&self, block_type: BlockType) -> Vec<u8> {
@@ -1302,6 +1508,7 @@ impl Block {
             self.avg_variance.to_be_bytes().as_slice(),
             self.avg_atr_income.to_be_bytes().as_slice(),
             self.avg_atr_variance.to_be_bytes().as_slice(),
+            self.utxoset_commitment.as_slice(),
             tx_buf.as_slice(),
         ]
         .concat();
@@ -1309,6 +1516,61 @@ impl Block {
         buffer
     }
 
+    /// Serializes the block with the envelope of the given format version. Used for peers which negotiated an older
+    /// version in the handshake. Fails if the block can't be written in that version
+    pub fn serialize_for_net_with_version(
+        &self,
+        block_type: BlockType,
+        version: u8,
+    ) -> Result<Vec<u8>, Error> {
+        if version == LEGACY_FORMAT_VERSION {
+            return self.serialize_legacy_for_net(block_type);
+        }
+        let mut buffer = get_format_envelope(version);
+        buffer.extend(self.serialize_for_net(block_type));
+        Ok(buffer)
+    }
+
+    /// Serializes the block with the legacy 301 byte header, which doesn't have the utxoset commitment. Blocks with a
+    /// commitment can't be serialized this way, since the signature covers the commitment
+    pub fn serialize_legacy_for_net(&self, block_type: BlockType) -> Result<Vec<u8>, Error> {
+        if self.utxoset_commitment != [0; 32] {
+            warn!(
+                "utxoset commitment of block : {:?} can't be serialized in the legacy layout",
+                hex::encode(self.hash)
+            );
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+        let mut buffer = self.serialize_for_net(block_type);
+        buffer.drain(LEGACY_BLOCK_HEADER_SIZE..BLOCK_HEADER_SIZE);
+        Ok(buffer)
+    }
+
+    /// Serializes the full block for the block files in the current format version
+    pub fn serialize_for_disk(&self) -> Vec<u8> {
+        let mut buffer = get_format_envelope(CURRENT_FORMAT_VERSION);
+        buffer.extend(self.serialize_for_net(BlockType::Full));
+        buffer
+    }
+
+    /// Serializes the block header with the transaction count of the full block. Transactions can be
+    /// appended to this afterwards to get the same buffer as `serialize_for_net(BlockType::Full)`
+    pub fn serialize_header_for_full_block(&self) -> Vec<u8> {
+        let mut buffer = self.serialize_for_net(BlockType::Header);
+        buffer[0..4].copy_from_slice(&(self.transactions.len() as u32).to_be_bytes());
+        buffer
+    }
+
+    /// size of the full block when serialized
+    pub fn get_serialized_size(&self) -> usize {
+        BLOCK_HEADER_SIZE
+            + self
+                .transactions
+                .iter()
+                .map(|tx| tx.get_serialized_size())
+                .sum::<usize>()
+    }
+
     #[tracing::instrument(level = "trace", skip_all, fields(id = hex::encode(self.hash)))]
     pub async fn update_block_to_block_type(
         &mut self,
@@ -1360,10 +1622,20 @@ impl Block {
         // load the block if it exists on disk.
         //
         if block_type == BlockType::Full {
-            let mut new_block = storage
+            let mut new_block = match storage
                 .load_block_from_disk(storage.generate_block_filename(&self))
                 .await
-                .unwrap();
+            {
+                Ok(block) => block,
+                Err(error) => {
+                    warn!(
+                        "failed loading block : {:?} from disk : {:?}",
+                        hex::encode(self.hash),
+                        error
+                    );
+                    return false;
+                }
+            };
             let hash_for_signature = hash(&new_block.serialize_for_signature());
             new_block.pre_hash = hash_for_signature;
             let hash_for_hash = hash(&new_block.serialize_for_hash());
@@ -1385,7 +1657,96 @@ impl Block {
         false
     }
 
-    pub async fn validate(&self, blockchain: &Blockchain, utxoset: &UtxoSet) -> bool {
+    /// total fees paid by the transactions in the block. calculated when the block is generated
+    pub fn get_total_fees(&self) -> Currency {
+        self.total_fees
+    }
+
+    pub fn get_timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn get_previous_block_hash(&self) -> SaitoHash {
+        self.previous_block_hash
+    }
+
+    /// Checks the merkle root against the transactions. Hashes of the transactions are generated if not set
+    pub fn has_valid_merkle_root(&mut self) -> bool {
+        if self.transactions.is_empty() {
+            return true;
+        }
+        for tx in self.transactions.iter_mut() {
+            if tx.hash_for_signature.is_none() {
+                tx.generate_hash_for_signature();
+            }
+        }
+        self.merkle_root == self.generate_merkle_root()
+    }
+
+    /// Checks if the block is signed by the creator. Pre hash should be generated before calling this
+    pub fn has_valid_signature(&self) -> bool {
+        verify_hash(&self.pre_hash, &self.signature, &self.creator)
+    }
+
+    /// Returns the cached validation result if the block was validated on top of the given chain tip
+    pub fn get_cached_validation_result(&self, chain_tip: &SaitoHash) -> Option<bool> {
+        match self.validation_cache.as_ref() {
+            Some(entry) if entry.validated_against == *chain_tip => Some(entry.result),
+            _ => None,
+        }
+    }
+
+    pub fn set_cached_validation_result(&mut self, chain_tip: SaitoHash, result: bool) {
+        self.validation_cache = Some(ValidationCacheEntry {
+            validated_against: chain_tip,
+            result,
+        });
+    }
+
+    pub fn clear_validation_cache(&mut self) {
+        self.validation_cache = None;
+    }
+
+    /// Blocks must be created after their parent. Checked as part of the consensus rules since it doesn't depend on
+    /// the local clock.
+    pub fn validate_timestamp_against_previous_block(
+        &self,
+        previous_block_timestamp: Timestamp,
+    ) -> Result<(), BlockTimestampError> {
+        if self.timestamp <= previous_block_timestamp {
+            return Err(BlockTimestampError::NotAfterPreviousBlock {
+                timestamp: self.timestamp,
+                previous_block_timestamp,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks the block timestamp against the local clock. Only applied to the blocks received from peers, since the
+    /// result depends on when the block is checked.
+    pub fn validate_timestamp_against_current_time(
+        &self,
+        current_time: Timestamp,
+        max_drift: Timestamp,
+    ) -> Result<(), BlockTimestampError> {
+        if self.timestamp > current_time.saturating_add(max_drift) {
+            return Err(BlockTimestampError::TooFarInFuture {
+                timestamp: self.timestamp,
+                current_time,
+                max_drift,
+            });
+        }
+        Ok(())
+    }
+
+    /// Validates the block on top of the longest chain. Returns an error if the block can't be validated now, since the
+    /// blocks it depends on can't be loaded from disk. The block can still be valid in that case
+    pub async fn validate(
+        &self,
+        blockchain: &Blockchain,
+        utxoset: &UtxoSet,
+        storage: &Storage,
+    ) -> Result<bool, Error> {
         // TODO SYNC : Add the code to check whether this is the genesis block and skip validations
         //
         // no transactions? no thank you
@@ -1393,7 +1754,7 @@ impl Block {
         if self.transactions.is_empty() && self.id != 1 && !blockchain.blocks.is_empty() {
             // we check blockchain blocks to make sure #1 block can be created without transactions
             error!("ERROR 424342: block does not validate as it has no transactions",);
-            return false;
+            return Ok(false);
         }
 
         //
@@ -1404,9 +1765,27 @@ impl Block {
         // );
 
         // verify signed by creator
-        if !verify_hash(&self.pre_hash, &self.signature, &self.creator) {
-            error!("ERROR 582039: block is not signed by creator or signature does not validate",);
-            return false;
+        {
+            let _stage = ValidationStage::BlockSignature.start();
+            if !verify_hash(&self.pre_hash, &self.signature, &self.creator) {
+                error!(
+                    "ERROR 582039: block is not signed by creator or signature does not validate",
+                );
+                return Ok(false);
+            }
+        }
+
+        //
+        // amounts
+        //
+        // totals which overflow are set to the max value while generating the
+        // block's metadata, so they are rejected here instead of wrapping around.
+        //
+        {
+            let _stage = ValidationStage::Amounts.start();
+            if !self.validate_amounts() {
+                return Ok(false);
+            }
         }
 
         //
@@ -1422,32 +1801,47 @@ impl Block {
         // to validate it by checking the variables we can see in our block with what
         // they should be given this function.
         //
-        let cv = self.generate_consensus_values(blockchain).await;
+        let stage_timer = ValidationStageTimer::start(ValidationStage::ConsensusValues);
+        let cv = match self
+            .generate_consensus_values(blockchain, storage)
+            .instrument(ValidationStage::ConsensusValues.span())
+            .await
+        {
+            Ok(cv) => cv,
+            Err(error) => {
+                warn!(
+                    "block : {:?} can't be validated since its consensus values can't be calculated : {:?}",
+                    hex::encode(self.hash),
+                    error
+                );
+                return Err(error);
+            }
+        };
 
         if cv.avg_income != self.avg_income {
             error!(
                 "block is misreporting its average income. current : {:?} expected : {:?}",
                 self.avg_income, cv.avg_income
             );
-            return false;
+            return Ok(false);
         }
         if cv.avg_variance != self.avg_variance {
             error!(
                 "block is misreporting its average variance. current : {:?} expected : {:?}",
                 self.avg_variance, cv.avg_variance
             );
-            return false;
+            return Ok(false);
         }
         if cv.avg_atr_income != self.avg_atr_income {
             error!(
                 "block is mis-reporting its average atr income. current : {:?} expected : {:?}",
                 self.avg_atr_income, cv.avg_atr_income
             );
-            return false;
+            return Ok(false);
         }
         if cv.avg_atr_variance != self.avg_atr_variance {
             error!("block is mis-reporting its average atr variance");
-            return false;
+            return Ok(false);
         }
 
         //
@@ -1455,8 +1849,9 @@ impl Block {
         //
         if cv.it_num > 0 && self.id > 1 {
             error!("ERROR: blockchain contains issuance after block 1 in chain",);
-            return false;
+            return Ok(false);
         }
+        drop(stage_timer);
 
         //
         // Previous Block
@@ -1470,20 +1865,49 @@ impl Block {
         //
         if let Some(previous_block) = blockchain.blocks.get(&self.previous_block_hash) {
             if let BlockType::Ghost = previous_block.block_type {
-                return true;
+                return Ok(true);
+            }
+            let stage = ValidationStage::ChainValues.start();
+            //
+            // validate timestamp
+            //
+            if let Err(error) =
+                self.validate_timestamp_against_previous_block(previous_block.timestamp)
+            {
+                error!(
+                    "ERROR 381043: block timestamp does not validate : {:?}",
+                    error
+                );
+                return Ok(false);
+            }
+
+            //
+            // validate utxoset commitment
+            //
+            if !self.validate_utxoset_commitment(utxoset) {
+                error!(
+                    "ERROR 590321: utxoset commitment of block : {:?} does not validate. expected : {:?} actual : {:?}",
+                    self.id,
+                    hex::encode(utxoset.get_commitment()),
+                    hex::encode(self.utxoset_commitment)
+                );
+                return Ok(false);
             }
+
             //
             // validate treasury
             //
-            if self.treasury != previous_block.treasury + cv.nolan_falling_off_chain {
+            let expected_treasury =
+                checked_add_amounts(previous_block.treasury, cv.nolan_falling_off_chain);
+            if expected_treasury != Some(self.treasury) {
                 error!(
                     "ERROR 123243: treasury does not validate: expected : {:?} + {:?} = {:?} actual : {:?} found",
                     previous_block.treasury , cv.nolan_falling_off_chain,
-                    (previous_block.treasury + cv.nolan_falling_off_chain),
+                    expected_treasury,
                     self.treasury,
                     // tracing_tracker.time_since_last();
                 );
-                return false;
+                return Ok(false);
             }
 
             //
@@ -1499,7 +1923,16 @@ impl Block {
                     adjusted_staking_treasury = 0;
                 }
             } else {
-                adjusted_staking_treasury += cv.staking_treasury;
+                match checked_add_amounts(adjusted_staking_treasury, cv.staking_treasury) {
+                    Some(staking_treasury) => adjusted_staking_treasury = staking_treasury,
+                    None => {
+                        error!(
+                            "ERROR 820392: staking treasury : {:?} overflows with : {:?}",
+                            adjusted_staking_treasury, cv.staking_treasury
+                        );
+                        return Ok(false);
+                    }
+                }
             }
 
             if self.staking_treasury != adjusted_staking_treasury {
@@ -1510,12 +1943,14 @@ impl Block {
                 //     "ERROR: staking treasury does not validate: {} expected versus {} found",
                 //     adjusted_staking_treasury,
                 //     self.get_staking_treasury(),
-                return false;
+                return Ok(false);
             }
+            drop(stage);
 
             //
             // validate burn fee
             //
+            let stage = ValidationStage::BurnFee.start();
             let new_burnfee: Currency =
                 BurnFee::return_burnfee_for_block_produced_at_current_timestamp_in_nolan(
                     previous_block.burnfee,
@@ -1527,7 +1962,7 @@ impl Block {
                     "ERROR 182085: burn fee does not validate,current = {}, expected: {}",
                     self.burnfee, new_burnfee
                 );
-                return false;
+                return Ok(false);
             }
 
             // trace!(" ... burn fee in blk validated:  {:?}", create_timestamp());
@@ -1546,9 +1981,11 @@ impl Block {
                 );
             if self.total_work < amount_of_routing_work_needed {
                 error!("Error 510293: block lacking adequate routing work from creator. actual : {:?} expected : {:?}",self.total_work, amount_of_routing_work_needed);
-                return false;
+                return Ok(false);
             }
 
+            drop(stage);
+
             // trace!(" ... done routing work required: {:?}", create_timestamp());
 
             //
@@ -1565,6 +2002,7 @@ impl Block {
             // which was generated using this solution. If the solution is invalid
             // we find that out now, and it invalidates the block.
             //
+            let _stage = ValidationStage::GoldenTicket.start();
             if let Some(gt_index) = cv.gt_index {
                 let golden_ticket: GoldenTicket =
                     GoldenTicket::deserialize_from_net(&self.transactions[gt_index].message);
@@ -1596,7 +2034,7 @@ impl Block {
                         hex::encode(solution),
                         solution_num.leading_zeros()
                     );
-                    return false;
+                    return Ok(false);
                 }
             }
             // trace!(" ... golden ticket: (validated)  {:?}", create_timestamp());
@@ -1617,26 +2055,30 @@ impl Block {
         // which we counted in the generate_metadata() function, with the
         // expected number given the consensus values we calculated earlier.
         //
+        let stage = ValidationStage::Atr.start();
         if cv.total_rebroadcast_slips != self.total_rebroadcast_slips {
             error!("ERROR 624442: rebroadcast slips total incorrect");
-            return false;
+            return Ok(false);
         }
         if cv.total_rebroadcast_nolan != self.total_rebroadcast_nolan {
             error!("ERROR 294018: rebroadcast nolan amount incorrect");
-            return false;
+            return Ok(false);
         }
         if cv.rebroadcast_hash != self.rebroadcast_hash {
             error!("ERROR 123422: hash of rebroadcast transactions incorrect");
-            return false;
+            return Ok(false);
         }
+        drop(stage);
 
         //
         // validate merkle root
         //
+        let stage = ValidationStage::MerkleRoot.start();
         if self.merkle_root == [0; 32] && self.merkle_root != self.generate_merkle_root() {
             error!("merkle root is unset or is invalid false 1");
-            return false;
+            return Ok(false);
         }
+        drop(stage);
 
         // trace!(" ... block.validate: (cv-data)   {:?}", create_timestamp());
 
@@ -1648,6 +2090,7 @@ impl Block {
         // that stretches back into previous blocks and finds the winning nodes
         // that should collect payment.
         //
+        let stage = ValidationStage::FeeTransaction.start();
         if cv.ft_num > 0 {
             if let (Some(ft_index), Some(mut fee_transaction)) = (cv.ft_index, cv.fee_transaction) {
                 //
@@ -1657,7 +2100,7 @@ impl Block {
                     error!(
                         "ERROR 48203: block appears to have fee transaction without golden ticket"
                     );
-                    return false;
+                    return Ok(false);
                 }
 
                 //
@@ -1677,10 +2120,11 @@ impl Block {
                     );
                     info!("expected = {:?}", fee_transaction);
                     info!("actual   = {:?}", checked_tx);
-                    return false;
+                    return Ok(false);
                 }
             }
         }
+        drop(stage);
 
         //
         // validate difficulty
@@ -1700,7 +2144,7 @@ impl Block {
                 "ERROR 202392: difficulty is invalid {} vs {}",
                 cv.expected_difficulty, self.difficulty
             );
-            return false;
+            return Ok(false);
         }
 
         // trace!(" ... block.validate: (txs valid) {:?}", create_timestamp());
@@ -1726,11 +2170,56 @@ impl Block {
         // as to determine spendability.
         //
 
+        //
+        // multisig transactions
+        //
+        // the members of a multisig policy can produce differently signed copies
+        // of the same spend, which will have different signatures. since those
+        // are not caught as duplicates by their signatures, we make sure each
+        // spend is only included once in the block.
+        //
+        let mut multisig_hashes = AHashSet::new();
+        for tx in self.transactions.iter() {
+            if !tx.is_multisig_transaction() {
+                continue;
+            }
+            if let Some(hash_for_signature) = tx.hash_for_signature {
+                if !multisig_hashes.insert(hash_for_signature) {
+                    error!(
+                        "ERROR 391936: multisig tx : {:?} is included more than once",
+                        hex::encode(hash_for_signature)
+                    );
+                    return Ok(false);
+                }
+            }
+        }
+
+        //
+        // handle registrations
+        //
+        // a handle belongs to the first key registering it. a block cannot
+        // register a handle owned by another key, or the same handle for two
+        // different keys.
+        //
+        let conflicting_registrations = blockchain
+            .get_handle_registry()
+            .find_conflicting_registrations(self.id, &self.transactions);
+        if !conflicting_registrations.is_empty() {
+            error!(
+                "ERROR 391937: block : {:?} registers {:?} handles which are not available",
+                self.id,
+                conflicting_registrations.len()
+            );
+            return Ok(false);
+        }
+
+        let stage = ValidationStage::Transactions.start();
         let transactions_valid = self
             .transactions
             .par_iter()
             .with_min_len(100)
             .all(|tx| tx.validate(utxoset));
+        drop(stage);
 
         // let mut transactions_valid = true;
         // for tx in self.transactions.iter() {
@@ -1748,7 +2237,7 @@ impl Block {
             error!("ERROR 579128: Invalid transactions found, block validation failed");
         }
 
-        transactions_valid
+        Ok(transactions_valid)
     }
 }
 
@@ -1758,14 +2247,22 @@ mod tests {
     use futures::future::join_all;
     use hex::FromHex;
 
-    use crate::common::defs::{push_lock, SaitoHash, SaitoPublicKey, LOCK_ORDER_WALLET};
+    use crate::common::defs::{
+        push_lock, Currency, SaitoHash, SaitoPublicKey, UtxoSet, LOCK_ORDER_BLOCKCHAIN,
+        LOCK_ORDER_WALLET,
+    };
     use crate::common::test_manager::test::TestManager;
-    use crate::core::data::block::{Block, BlockType};
-    use crate::core::data::crypto::verify_hash;
+    use crate::core::data::block::{
+        Block, BlockTimestampError, BlockType, BLOCK_HEADER_SIZE, LEGACY_BLOCK_HEADER_SIZE,
+        UTXOSET_COMMITMENT_ACTIVATION_BLOCK_ID, UTXOSET_COMMITMENT_INTERVAL,
+    };
+    use crate::core::data::blockchain::MAX_TOKEN_SUPPLY;
+    use crate::core::data::crypto::{hash, verify_hash};
+    use crate::core::data::format_version::{CURRENT_FORMAT_VERSION, LEGACY_FORMAT_VERSION};
     use crate::core::data::slip::Slip;
     use crate::core::data::transaction::{Transaction, TransactionType};
     use crate::core::data::wallet::Wallet;
-    use crate::lock_for_read;
+    use crate::{lock_for_read, lock_for_write};
 
     #[test]
     fn block_new_test() {
@@ -1814,6 +2311,60 @@ mod tests {
         assert_eq!(block.hash, block.hash);
     }
 
+    #[test]
+    fn block_validation_cache_test() {
+        let mut block = Block::new();
+        assert_eq!(block.get_cached_validation_result(&[1; 32]), None);
+
+        block.set_cached_validation_result([1; 32], true);
+        assert_eq!(block.get_cached_validation_result(&[1; 32]), Some(true));
+        // result is not valid on top of another chain tip
+        assert_eq!(block.get_cached_validation_result(&[2; 32]), None);
+
+        block.set_cached_validation_result([2; 32], false);
+        assert_eq!(block.get_cached_validation_result(&[2; 32]), Some(false));
+        assert_eq!(block.get_cached_validation_result(&[1; 32]), None);
+
+        block.clear_validation_cache();
+        assert_eq!(block.get_cached_validation_result(&[2; 32]), None);
+    }
+
+    #[test]
+    fn block_timestamp_validation_test() {
+        let mut block = Block::new();
+        block.timestamp = 10_000;
+
+        assert_eq!(
+            block.validate_timestamp_against_previous_block(9_999),
+            Ok(())
+        );
+        assert_eq!(
+            block.validate_timestamp_against_previous_block(10_000),
+            Err(BlockTimestampError::NotAfterPreviousBlock {
+                timestamp: 10_000,
+                previous_block_timestamp: 10_000
+            })
+        );
+
+        assert_eq!(
+            block.validate_timestamp_against_current_time(9_000, 1_000),
+            Ok(())
+        );
+        assert_eq!(
+            block.validate_timestamp_against_current_time(8_999, 1_000),
+            Err(BlockTimestampError::TooFarInFuture {
+                timestamp: 10_000,
+                current_time: 8_999,
+                max_drift: 1_000
+            })
+        );
+        // blocks from the past are accepted, since they are received during the sync
+        assert_eq!(
+            block.validate_timestamp_against_current_time(50_000, 1_000),
+            Ok(())
+        );
+    }
+
     #[test]
     fn block_signature_test() {
         let mut block = Block::new();
@@ -1898,6 +2449,7 @@ mod tests {
         block.treasury = 1_000_000;
         block.burnfee = 2;
         block.difficulty = 3;
+        block.utxoset_commitment = [5; 32];
         block.transactions = vec![mock_tx, mock_tx2];
 
         let serialized_block = block.serialize_for_net(BlockType::Full);
@@ -1920,6 +2472,7 @@ mod tests {
         assert_eq!(deserialized_block.treasury, 1_000_000);
         assert_eq!(deserialized_block.burnfee, 2);
         assert_eq!(deserialized_block.difficulty, 3);
+        assert_eq!(deserialized_block.utxoset_commitment, [5; 32]);
 
         assert_eq!(
             deserialized_block_header.serialize_for_net(BlockType::Full),
@@ -1935,6 +2488,161 @@ mod tests {
         assert_eq!(deserialized_block_header.treasury, 1_000_000);
         assert_eq!(deserialized_block_header.burnfee, 2);
         assert_eq!(deserialized_block_header.difficulty, 3);
+        assert_eq!(deserialized_block_header.utxoset_commitment, [5; 32]);
+    }
+
+    #[test]
+    fn utxoset_commitment_test() {
+        let mut utxoset = UtxoSet::new();
+        utxoset.insert([1; 66], true);
+
+        let mut block = Block::new();
+        // blocks before the activation don't commit to the utxoset
+        block.id = UTXOSET_COMMITMENT_INTERVAL * 2;
+        assert!(!block.has_utxoset_commitment());
+        assert!(block.validate_utxoset_commitment(&utxoset));
+
+        block.id = UTXOSET_COMMITMENT_ACTIVATION_BLOCK_ID + UTXOSET_COMMITMENT_INTERVAL * 2;
+        assert!(block.has_utxoset_commitment());
+        assert!(!block.validate_utxoset_commitment(&utxoset));
+        block.utxoset_commitment = utxoset.get_commitment();
+        assert!(block.validate_utxoset_commitment(&utxoset));
+        utxoset.insert([2; 66], true);
+        assert!(!block.validate_utxoset_commitment(&utxoset));
+
+        let pre_hash = hash(&block.serialize_for_signature());
+        block.utxoset_commitment = utxoset.get_commitment();
+        assert_ne!(hash(&block.serialize_for_signature()), pre_hash);
+
+        block.id += 1;
+        assert!(!block.has_utxoset_commitment());
+        assert!(!block.validate_utxoset_commitment(&utxoset));
+        block.utxoset_commitment = [0; 32];
+        assert!(block.validate_utxoset_commitment(&utxoset));
+    }
+
+    #[test]
+    fn legacy_block_serialization_test() {
+        let wallet = Wallet::new();
+        let mut tx = Transaction::default();
+        tx.add_output(Slip::default());
+        tx.message = vec![1, 2, 3];
+
+        let mut block = Block::new();
+        block.id = 3;
+        block.creator = wallet.public_key;
+        block.transactions = vec![tx];
+        block.generate();
+        block.sign(&wallet.private_key);
+        block.generate_hash();
+
+        // blocks written before the utxoset commitment was added
+        let buffer = block.serialize_legacy_for_net(BlockType::Full).unwrap();
+        let current_buffer = block.serialize_for_net(BlockType::Full);
+        assert_eq!(
+            buffer.len() + BLOCK_HEADER_SIZE - LEGACY_BLOCK_HEADER_SIZE,
+            current_buffer.len()
+        );
+        assert_eq!(
+            buffer[..LEGACY_BLOCK_HEADER_SIZE],
+            current_buffer[..LEGACY_BLOCK_HEADER_SIZE]
+        );
+        assert_eq!(
+            buffer[LEGACY_BLOCK_HEADER_SIZE..],
+            current_buffer[BLOCK_HEADER_SIZE..]
+        );
+
+        let mut legacy_block = Block::try_deserialize_legacy_from_net(&buffer).unwrap();
+        assert_eq!(
+            legacy_block.serialize_for_net(BlockType::Full),
+            current_buffer
+        );
+        legacy_block.generate();
+        assert_eq!(legacy_block.hash, block.hash);
+        assert!(legacy_block.has_valid_signature());
+        assert_eq!(
+            Block::deserialize_legacy_from_net(&buffer).serialize_for_net(BlockType::Full),
+            current_buffer
+        );
+
+        // the legacy layout is not readable as the current layout
+        assert!(Block::try_deserialize_from_net(&buffer).is_err());
+        assert!(
+            Block::try_deserialize_legacy_from_net(&buffer[..LEGACY_BLOCK_HEADER_SIZE - 1])
+                .is_err()
+        );
+        assert!(Block::try_deserialize_legacy_from_net(&buffer[..buffer.len() - 1]).is_err());
+
+        // the signature covers the commitment, so blocks with a commitment can't be sent in the legacy layout
+        block.utxoset_commitment = [5; 32];
+        assert!(block.serialize_legacy_for_net(BlockType::Full).is_err());
+        assert!(block
+            .serialize_for_net_with_version(BlockType::Full, LEGACY_FORMAT_VERSION)
+            .is_err());
+        assert!(block
+            .serialize_for_net_with_version(BlockType::Full, CURRENT_FORMAT_VERSION)
+            .is_ok());
+    }
+
+    #[test]
+    fn block_amounts_validation_test() {
+        let create_transaction =
+            |transaction_type: TransactionType, inputs: &[Currency], outputs: &[Currency]| {
+                let mut transaction = Transaction::default();
+                transaction.transaction_type = transaction_type;
+                for amount in inputs {
+                    let mut slip = Slip::default();
+                    slip.amount = *amount;
+                    transaction.add_input(slip);
+                }
+                for amount in outputs {
+                    let mut slip = Slip::default();
+                    slip.amount = *amount;
+                    transaction.add_output(slip);
+                }
+                transaction.generate_total_fees(0, 10);
+                transaction
+            };
+
+        let mut block = Block::new();
+        block.id = 10;
+        block.transactions = vec![
+            create_transaction(TransactionType::Normal, &[1000], &[900]),
+            create_transaction(TransactionType::Normal, &[MAX_TOKEN_SUPPLY / 2], &[10]),
+            // payouts of the previous blocks' fees
+            create_transaction(TransactionType::Fee, &[], &[500]),
+        ];
+        assert!(block.validate_amounts());
+
+        // overflowing outputs are caught even if the other transactions pay enough fees
+        block.transactions.push(create_transaction(
+            TransactionType::Normal,
+            &[10],
+            &[Currency::MAX / 2 + 1, Currency::MAX / 2 + 1],
+        ));
+        assert!(!block.validate_amounts());
+        block.transactions.pop();
+
+        // transaction totals which are valid but overflow the supply together
+        block.transactions.push(create_transaction(
+            TransactionType::Normal,
+            &[MAX_TOKEN_SUPPLY / 2 + 1],
+            &[0],
+        ));
+        assert!(!block.validate_amounts());
+        block.transactions.pop();
+
+        // a transaction creating tokens
+        block.transactions = vec![create_transaction(TransactionType::Normal, &[100], &[101])];
+        assert!(!block.validate_amounts());
+
+        // issuance creates the supply
+        block.transactions = vec![create_transaction(
+            TransactionType::Issuance,
+            &[],
+            &[MAX_TOKEN_SUPPLY / 2, MAX_TOKEN_SUPPLY / 2],
+        )];
+        assert!(block.validate_amounts());
     }
 
     #[test]
@@ -1997,7 +2705,7 @@ mod tests {
         block.generate();
 
         // save to disk
-        t.storage.write_block_to_disk(&mut block).await;
+        t.storage.write_block_to_disk(&mut block).await.unwrap();
 
         assert_eq!(block.transactions.len(), 5);
         assert_eq!(block.block_type, BlockType::Full);
@@ -2021,4 +2729,133 @@ mod tests {
             block.serialize_for_net(BlockType::Full)
         );
     }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn atr_consensus_values_with_pruned_block_test() {
+        let mut t = TestManager::new();
+        t.initialize_with_timestamp(100, 1_000_000_000, 10_000_000)
+            .await;
+        let block1 = {
+            let (blockchain, _blockchain_) =
+                lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            blockchain.get_latest_block().unwrap().clone()
+        };
+        let mut block2 = t
+            .create_block(
+                block1.hash,
+                block1.timestamp + 120000,
+                3,
+                500_000_000,
+                0,
+                true,
+            )
+            .await;
+        block2.generate();
+        let block2_hash = block2.hash;
+        t.add_block(block2).await;
+
+        let (mut blockchain, _blockchain_) =
+            lock_for_write!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+        // block 2 is the block falling off the chain for block 3
+        blockchain.set_genesis_period(1);
+        let mut block3 = Block::new();
+        block3.id = 3;
+        block3.previous_block_hash = block2_hash;
+
+        let full_cv = block3
+            .generate_consensus_values(&blockchain, &t.storage)
+            .await
+            .unwrap();
+        assert!(full_cv.total_rebroadcast_slips > 0);
+
+        blockchain
+            .blocks
+            .get_mut(&block2_hash)
+            .unwrap()
+            .downgrade_block_to_block_type(BlockType::Pruned)
+            .await;
+        let cv = block3
+            .generate_consensus_values(&blockchain, &t.storage)
+            .await
+            .unwrap();
+        assert_eq!(cv.total_rebroadcast_slips, full_cv.total_rebroadcast_slips);
+        assert_eq!(cv.total_rebroadcast_nolan, full_cv.total_rebroadcast_nolan);
+        assert_eq!(cv.rebroadcast_hash, full_cv.rebroadcast_hash);
+        assert_eq!(
+            blockchain.get_block(&block2_hash).unwrap().block_type,
+            BlockType::Pruned
+        );
+
+        // the rebroadcasts can't be calculated without the block file
+        let filename = t
+            .storage
+            .generate_block_filename(blockchain.get_block(&block2_hash).unwrap());
+        t.storage.io_interface.remove_value(filename).await.unwrap();
+        assert!(block3
+            .generate_consensus_values(&blockchain, &t.storage)
+            .await
+            .is_err());
+        // block 3 can't be validated now, which doesn't make it invalid
+        let wallet = Wallet::new();
+        block3.creator = wallet.public_key;
+        block3.transactions.push(Transaction::default());
+        block3.generate();
+        block3.sign(&wallet.private_key);
+        block3.generate_hash();
+        assert!(block3
+            .validate(&blockchain, &blockchain.utxoset, &t.storage)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn legacy_block_before_utxoset_commitment_activation_test() {
+        let mut t = TestManager::new();
+        t.initialize_with_timestamp(100, 1_000_000_000, 10_000_000)
+            .await;
+
+        let mut legacy_block_hash = [0; 32];
+        for _i in 0..UTXOSET_COMMITMENT_INTERVAL - 1 {
+            let (parent_hash, parent_timestamp) = {
+                let (blockchain, _blockchain_) =
+                    lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+                let block = blockchain.get_latest_block().unwrap();
+                (block.hash, block.timestamp)
+            };
+            let mut block = t
+                .create_block(parent_hash, parent_timestamp + 120000, 0, 0, 0, true)
+                .await;
+            block.generate();
+
+            if block.id < UTXOSET_COMMITMENT_INTERVAL {
+                t.add_block(block).await;
+                continue;
+            }
+            // block 10 is read from a block file written before the utxoset commitment was added
+            assert_eq!(block.id, UTXOSET_COMMITMENT_INTERVAL);
+            assert!(!block.has_utxoset_commitment());
+            let filename = t.storage.generate_block_filename(&block);
+            let buffer = block.serialize_legacy_for_net(BlockType::Full).unwrap();
+            t.storage
+                .io_interface
+                .write_value(filename.clone(), buffer)
+                .await
+                .unwrap();
+            let mut legacy_block = t.storage.load_block_from_disk(filename).await.unwrap();
+            legacy_block.generate();
+            assert_eq!(legacy_block.hash, block.hash);
+            assert_eq!(legacy_block.utxoset_commitment, [0; 32]);
+            legacy_block_hash = legacy_block.hash;
+            t.add_block(legacy_block).await;
+        }
+
+        let (blockchain, _blockchain_) = lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+        assert_eq!(
+            blockchain.get_latest_block_id(),
+            UTXOSET_COMMITMENT_INTERVAL
+        );
+        assert_eq!(blockchain.get_latest_block_hash(), legacy_block_hash);
+    }
 }
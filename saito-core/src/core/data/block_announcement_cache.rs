@@ -0,0 +1,168 @@
+use ahash::HashMap;
+use tracing::trace;
+
+use crate::common::defs::{PeerIndex, SaitoHash, Timestamp};
+
+/// announcements of the same block received within this time are dropped as duplicates
+pub const BLOCK_ANNOUNCEMENT_CACHE_TTL_IN_MS: Timestamp = 60_000;
+/// max number of block hashes kept in the cache. oldest entries are removed first when the cache is full
+pub const BLOCK_ANNOUNCEMENT_CACHE_SIZE: usize = 10_000;
+
+#[derive(Debug)]
+struct SeenBlock {
+    first_seen_at: Timestamp,
+    /// peers which announced the block, in the order they were received
+    source_peers: Vec<PeerIndex>,
+}
+
+/// Recently seen block hashes announced by peers. Only the first announcement of a block is processed, the same
+/// block announced by other peers is dropped before reaching the sync state.
+#[derive(Debug)]
+pub struct BlockAnnouncementCache {
+    seen_blocks: HashMap<SaitoHash, SeenBlock>,
+    ttl_in_ms: Timestamp,
+    max_size: usize,
+    pub total_announcements: u64,
+    pub duplicate_announcements: u64,
+}
+
+impl BlockAnnouncementCache {
+    pub fn new(ttl_in_ms: Timestamp, max_size: usize) -> BlockAnnouncementCache {
+        BlockAnnouncementCache {
+            seen_blocks: Default::default(),
+            ttl_in_ms,
+            max_size,
+            total_announcements: 0,
+            duplicate_announcements: 0,
+        }
+    }
+
+    /// Records the announcement. Returns true if the block was not announced by any peer within the ttl.
+    pub fn on_announcement(
+        &mut self,
+        block_hash: SaitoHash,
+        peer_index: PeerIndex,
+        current_time: Timestamp,
+    ) -> bool {
+        self.total_announcements += 1;
+        if let Some(seen_block) = self.seen_blocks.get_mut(&block_hash) {
+            if current_time.saturating_sub(seen_block.first_seen_at) < self.ttl_in_ms {
+                self.duplicate_announcements += 1;
+                if !seen_block.source_peers.contains(&peer_index) {
+                    seen_block.source_peers.push(peer_index);
+                }
+                trace!(
+                    "dropping duplicate announcement of block : {:?} from peer : {:?}",
+                    hex::encode(block_hash),
+                    peer_index
+                );
+                return false;
+            }
+        }
+        if self.seen_blocks.len() >= self.max_size {
+            self.remove_expired(current_time);
+        }
+        if self.seen_blocks.len() >= self.max_size {
+            let oldest = self
+                .seen_blocks
+                .iter()
+                .min_by_key(|(_, seen_block)| seen_block.first_seen_at)
+                .map(|(hash, _)| *hash);
+            if let Some(oldest) = oldest {
+                self.seen_blocks.remove(&oldest);
+            }
+        }
+        self.seen_blocks.insert(
+            block_hash,
+            SeenBlock {
+                first_seen_at: current_time,
+                source_peers: vec![peer_index],
+            },
+        );
+        true
+    }
+
+    pub fn get_source_peers(&self, block_hash: &SaitoHash) -> Option<&Vec<PeerIndex>> {
+        self.seen_blocks
+            .get(block_hash)
+            .map(|seen_block| &seen_block.source_peers)
+    }
+
+    pub fn remove_expired(&mut self, current_time: Timestamp) {
+        let ttl_in_ms = self.ttl_in_ms;
+        self.seen_blocks.retain(|_, seen_block| {
+            current_time.saturating_sub(seen_block.first_seen_at) < ttl_in_ms
+        });
+    }
+
+    /// Forgets the blocks first announced by the peer, so they are processed again when announced by other peers
+    /// after the peer is disconnected
+    pub fn remove_peer(&mut self, peer_index: PeerIndex) {
+        self.seen_blocks.retain(|_, seen_block| {
+            if seen_block.source_peers.first() == Some(&peer_index) {
+                return false;
+            }
+            seen_block.source_peers.retain(|index| *index != peer_index);
+            true
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.seen_blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen_blocks.is_empty()
+    }
+
+    /// percentage of the announcements dropped as duplicates
+    pub fn get_hit_rate(&self) -> f64 {
+        if self.total_announcements == 0 {
+            return 0.0;
+        }
+        self.duplicate_announcements as f64 * 100.0 / self.total_announcements as f64
+    }
+
+    pub fn clear(&mut self) {
+        self.seen_blocks.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::block_announcement_cache::BlockAnnouncementCache;
+
+    #[test]
+    fn block_announcement_cache_test() {
+        let mut cache = BlockAnnouncementCache::new(1000, 3);
+
+        assert!(cache.on_announcement([1; 32], 1, 0));
+        assert!(!cache.on_announcement([1; 32], 2, 10));
+        assert!(!cache.on_announcement([1; 32], 2, 20));
+        assert_eq!(cache.get_source_peers(&[1; 32]), Some(&vec![1, 2]));
+        assert_eq!(cache.duplicate_announcements, 2);
+        assert_eq!(cache.total_announcements, 3);
+
+        // expired entries are processed again
+        assert!(cache.on_announcement([1; 32], 3, 1000));
+        assert_eq!(cache.get_source_peers(&[1; 32]), Some(&vec![3]));
+
+        assert!(cache.on_announcement([2; 32], 1, 1100));
+        assert!(cache.on_announcement([3; 32], 1, 1200));
+        // oldest entry is removed when the cache is full
+        assert!(cache.on_announcement([4; 32], 2, 1300));
+        assert_eq!(cache.len(), 3);
+        assert!(cache.get_source_peers(&[1; 32]).is_none());
+
+        // blocks first announced by a disconnected peer are forgotten
+        assert!(!cache.on_announcement([4; 32], 1, 1400));
+        cache.remove_peer(1);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get_source_peers(&[4; 32]), Some(&vec![2]));
+        assert!(cache.on_announcement([2; 32], 2, 1500));
+
+        cache.remove_expired(2400);
+        assert_eq!(cache.len(), 1);
+        assert!((cache.get_hit_rate() - 300.0 / 9.0).abs() < 0.001);
+    }
+}
@@ -0,0 +1,409 @@
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::common::defs::{BlockId, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature};
+use crate::core::data::block::{Block, BLOCK_HEADER_SIZE, LEGACY_BLOCK_HEADER_SIZE};
+use crate::core::data::crypto::{hash, sign, verify};
+use crate::core::data::format_version::{
+    read_format_envelope, CURRENT_FORMAT_VERSION, LEGACY_FORMAT_VERSION,
+};
+use crate::core::data::storage::Storage;
+
+/// fetched block responses larger than this are dropped without being deserialized
+pub const BLOCK_FETCH_MAX_SIZE_IN_BYTES: usize = 1024 * 1024 * 1024;
+/// http header carrying the serving node's signature over the block hash and the response length
+pub const BLOCK_FETCH_SIGNATURE_HEADER: &str = "saito-block-signature";
+/// max number of full blocks served for a single range request
+pub const BLOCK_RANGE_MAX_BLOCKS: u64 = 100;
+/// max number of block headers served for a single lite range request
+pub const BLOCK_RANGE_MAX_HEADERS: u64 = 10_000;
+/// [block id - 8 bytes][block hash - 32 bytes][payload length - 8 bytes][signature - 64 bytes]
+pub const BLOCK_RANGE_FRAME_HEADER_SIZE: usize = 112;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchedBlockError {
+    TooLarge,
+    InvalidStructure,
+    HashMismatch,
+    InvalidSignature,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockRangeError {
+    /// to_id is lower than from_id
+    InvalidRange,
+    /// more blocks than the serving node allows for a single request
+    TooManyBlocks,
+    /// the response ended in the middle of a frame
+    InvalidFrame,
+}
+
+/// Blocks of the longest chain from `from_id` to `to_id` (inclusive), requested with
+/// `/blocks?from_id=X&to_id=Y&lite=true|false`. Lite requests only get the block headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct BlockRangeRequest {
+    pub from_id: BlockId,
+    pub to_id: BlockId,
+    #[serde(default)]
+    pub lite: bool,
+}
+
+impl BlockRangeRequest {
+    pub fn validate(&self) -> Result<(), BlockRangeError> {
+        if self.to_id < self.from_id {
+            return Err(BlockRangeError::InvalidRange);
+        }
+        let max_blocks = if self.lite {
+            BLOCK_RANGE_MAX_HEADERS
+        } else {
+            BLOCK_RANGE_MAX_BLOCKS
+        };
+        if self.get_block_count() > max_blocks {
+            return Err(BlockRangeError::TooManyBlocks);
+        }
+        Ok(())
+    }
+
+    pub fn get_block_count(&self) -> u64 {
+        self.to_id.saturating_sub(self.from_id) + 1
+    }
+
+    /// path and query of the request, appended to the peer's base url
+    pub fn get_path(&self) -> String {
+        format!(
+            "blocks?from_id={}&to_id={}&lite={}",
+            self.from_id, self.to_id, self.lite
+        )
+    }
+}
+
+/// A block in the response of a range request. The payload is the block file (with the checksum) for full requests
+/// and the serialized header for lite requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockRangeFrame {
+    pub block_id: BlockId,
+    pub block_hash: SaitoHash,
+    /// signature of the serving node over the block hash and the payload length, same as single block fetches
+    pub signature: SaitoSignature,
+    pub payload: Vec<u8>,
+}
+
+impl BlockRangeFrame {
+    /// Header written before the payload of each block in the response stream, so the blocks can be read one by one
+    /// while the response is still streaming
+    pub fn serialize_frame_header(
+        block_id: BlockId,
+        block_hash: &SaitoHash,
+        payload_length: u64,
+        private_key: &SaitoPrivateKey,
+    ) -> Vec<u8> {
+        [
+            block_id.to_be_bytes().as_slice(),
+            block_hash.as_slice(),
+            payload_length.to_be_bytes().as_slice(),
+            sign_block_fetch_response(block_hash, payload_length, private_key).as_slice(),
+        ]
+        .concat()
+    }
+
+    /// Reads the frame at the start of the buffer. Returns the frame and the number of bytes read, or None if the
+    /// buffer doesn't contain the whole frame yet
+    pub fn deserialize(buffer: &[u8]) -> Option<(BlockRangeFrame, usize)> {
+        if buffer.len() < BLOCK_RANGE_FRAME_HEADER_SIZE {
+            return None;
+        }
+        let block_id = BlockId::from_be_bytes(buffer[0..8].try_into().unwrap());
+        let block_hash: SaitoHash = buffer[8..40].try_into().unwrap();
+        let length = u64::from_be_bytes(buffer[40..48].try_into().unwrap()) as usize;
+        let signature: SaitoSignature = buffer[48..112].try_into().unwrap();
+        if length > BLOCK_FETCH_MAX_SIZE_IN_BYTES
+            || buffer.len() < BLOCK_RANGE_FRAME_HEADER_SIZE + length
+        {
+            return None;
+        }
+        let end = BLOCK_RANGE_FRAME_HEADER_SIZE + length;
+        Some((
+            BlockRangeFrame {
+                block_id,
+                block_hash,
+                signature,
+                payload: buffer[BLOCK_RANGE_FRAME_HEADER_SIZE..end].to_vec(),
+            },
+            end,
+        ))
+    }
+
+    /// Reads all the frames of a range response. The serving node stops at the first block it doesn't have, so there
+    /// can be fewer frames than requested blocks
+    pub fn deserialize_all(buffer: &[u8]) -> Result<Vec<BlockRangeFrame>, BlockRangeError> {
+        let mut frames = vec![];
+        let mut start = 0;
+        while start < buffer.len() {
+            let (frame, length) = BlockRangeFrame::deserialize(&buffer[start..])
+                .ok_or(BlockRangeError::InvalidFrame)?;
+            frames.push(frame);
+            start += length;
+        }
+        Ok(frames)
+    }
+
+    /// Checks the frame the same way as a single block fetch. Headers of lite responses are only checked against the
+    /// block hash, since they are not stored as block files
+    pub fn validate(
+        &self,
+        lite: bool,
+        peer_public_key: Option<&SaitoPublicKey>,
+    ) -> Result<(), FetchedBlockError> {
+        if !lite {
+            return validate_fetched_block(
+                &self.block_hash,
+                &self.payload,
+                Some(&self.signature),
+                peer_public_key,
+            )
+            .map(|_| ());
+        }
+        let response_hash = get_response_hash(&self.block_hash, self.payload.len() as u64);
+        let valid = peer_public_key
+            .map(|public_key| verify(&response_hash, &self.signature, public_key))
+            .unwrap_or(false);
+        if !valid {
+            return Err(FetchedBlockError::InvalidSignature);
+        }
+        if self.payload.len() < BLOCK_HEADER_SIZE {
+            return Err(FetchedBlockError::InvalidStructure);
+        }
+        // lite frames have the block header in the current layout
+        if get_block_hash_from_header(&self.payload, CURRENT_FORMAT_VERSION) != self.block_hash {
+            return Err(FetchedBlockError::HashMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// Signed by the serving node, so a peer serving a block other than the requested one can be held accountable
+fn get_response_hash(block_hash: &SaitoHash, length: u64) -> SaitoHash {
+    hash(&[block_hash.as_slice(), length.to_be_bytes().as_slice()].concat())
+}
+
+pub fn sign_block_fetch_response(
+    block_hash: &SaitoHash,
+    length: u64,
+    private_key: &SaitoPrivateKey,
+) -> SaitoSignature {
+    sign(&get_response_hash(block_hash, length), private_key)
+}
+
+/// Calculates the block hash from the header only. The transactions are checked against the merkle root in the
+/// header when the block is validated. The buffer should have a header of the given format version
+fn get_block_hash_from_header(buffer: &[u8], version: u8) -> SaitoHash {
+    let header_size = if version == LEGACY_FORMAT_VERSION {
+        LEGACY_BLOCK_HEADER_SIZE
+    } else {
+        BLOCK_HEADER_SIZE
+    };
+    let mut header = buffer[..header_size].to_vec();
+    header[0..4].copy_from_slice(&0_u32.to_be_bytes());
+    let mut block = if version == LEGACY_FORMAT_VERSION {
+        Block::deserialize_legacy_from_net(&header)
+    } else {
+        Block::deserialize_from_net(&header)
+    };
+    block.generate_pre_hash();
+    block.generate_hash()
+}
+
+/// Checks the response of a block fetch before it's passed to the sync. Returns the length of the serialized block
+/// (with the format envelope, without the block file checksum) if the response contains the requested block.
+///
+/// The signature is only checked if the serving node sent one, since older nodes don't sign the responses. A signed
+/// response from a peer without a known public key is rejected, since the signature can't be verified.
+pub fn validate_fetched_block(
+    requested_hash: &SaitoHash,
+    buffer: &[u8],
+    signature: Option<&SaitoSignature>,
+    peer_public_key: Option<&SaitoPublicKey>,
+) -> Result<usize, FetchedBlockError> {
+    if buffer.len() > BLOCK_FETCH_MAX_SIZE_IN_BYTES {
+        warn!(
+            "fetched block : {:?} is too large : {:?}",
+            hex::encode(requested_hash),
+            buffer.len()
+        );
+        return Err(FetchedBlockError::TooLarge);
+    }
+    if let Some(signature) = signature {
+        let response_hash = get_response_hash(requested_hash, buffer.len() as u64);
+        let valid = peer_public_key
+            .map(|public_key| verify(&response_hash, signature, public_key))
+            .unwrap_or(false);
+        if !valid {
+            warn!(
+                "invalid signature for fetched block : {:?} from : {:?}",
+                hex::encode(requested_hash),
+                peer_public_key.map(hex::encode)
+            );
+            return Err(FetchedBlockError::InvalidSignature);
+        }
+    }
+    let length =
+        Storage::verify_block_file(buffer).map_err(|_| FetchedBlockError::InvalidStructure)?;
+    let (version, block_buffer) =
+        read_format_envelope(&buffer[..length]).map_err(|_| FetchedBlockError::InvalidStructure)?;
+    let block_hash = get_block_hash_from_header(block_buffer, version);
+    if block_hash != *requested_hash {
+        warn!(
+            "fetched block hash : {:?} doesn't match the requested hash : {:?}",
+            hex::encode(block_hash),
+            hex::encode(requested_hash)
+        );
+        return Err(FetchedBlockError::HashMismatch);
+    }
+    Ok(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::block::{Block, BlockType};
+    use crate::core::data::block_fetch::{
+        sign_block_fetch_response, validate_fetched_block, BlockRangeError, BlockRangeFrame,
+        BlockRangeRequest, FetchedBlockError, BLOCK_RANGE_MAX_BLOCKS,
+    };
+    use crate::core::data::crypto::generate_keys;
+
+    #[test]
+    fn validate_fetched_block_test() {
+        let (public_key, private_key) = generate_keys();
+        let (other_public_key, _) = generate_keys();
+
+        let mut block = Block::new();
+        block.id = 10;
+        block.timestamp = 1000;
+        block.generate_pre_hash();
+        block.generate_hash();
+        let buffer = block.serialize_for_disk();
+        let length = buffer.len() as u64;
+
+        let signature = sign_block_fetch_response(&block.hash, length, &private_key);
+        assert_eq!(
+            validate_fetched_block(&block.hash, &buffer, Some(&signature), Some(&public_key)),
+            Ok(buffer.len())
+        );
+        // unsigned responses from older nodes are accepted
+        assert_eq!(
+            validate_fetched_block(&block.hash, &buffer, None, Some(&public_key)),
+            Ok(buffer.len())
+        );
+        assert_eq!(
+            validate_fetched_block(
+                &block.hash,
+                &buffer,
+                Some(&signature),
+                Some(&other_public_key)
+            ),
+            Err(FetchedBlockError::InvalidSignature)
+        );
+        assert_eq!(
+            validate_fetched_block(&block.hash, &buffer, Some(&signature), None),
+            Err(FetchedBlockError::InvalidSignature)
+        );
+        assert_eq!(
+            validate_fetched_block(&[1; 32], &buffer, None, Some(&public_key)),
+            Err(FetchedBlockError::HashMismatch)
+        );
+        assert_eq!(
+            validate_fetched_block(&block.hash, &buffer[..100], None, Some(&public_key)),
+            Err(FetchedBlockError::InvalidStructure)
+        );
+
+        // block files written before the format envelope was added
+        let buffer = block.serialize_legacy_for_net(BlockType::Full).unwrap();
+        assert_eq!(
+            validate_fetched_block(&block.hash, &buffer, None, Some(&public_key)),
+            Ok(buffer.len())
+        );
+        assert_eq!(
+            validate_fetched_block(
+                &block.hash,
+                &block.serialize_for_net(BlockType::Full),
+                None,
+                Some(&public_key)
+            ),
+            Err(FetchedBlockError::InvalidStructure)
+        );
+    }
+
+    #[test]
+    fn block_range_test() {
+        let request = BlockRangeRequest {
+            from_id: 10,
+            to_id: 9,
+            lite: false,
+        };
+        assert_eq!(request.validate(), Err(BlockRangeError::InvalidRange));
+        let request = BlockRangeRequest {
+            from_id: 1,
+            to_id: BLOCK_RANGE_MAX_BLOCKS + 1,
+            lite: false,
+        };
+        assert_eq!(request.validate(), Err(BlockRangeError::TooManyBlocks));
+        let request = BlockRangeRequest {
+            lite: true,
+            ..request
+        };
+        assert_eq!(request.validate(), Ok(()));
+        assert_eq!(request.get_block_count(), BLOCK_RANGE_MAX_BLOCKS + 1);
+        assert_eq!(request.get_path(), "blocks?from_id=1&to_id=101&lite=true");
+
+        let (public_key, private_key) = generate_keys();
+        let mut buffer = vec![];
+        let mut blocks = vec![];
+        for id in 1..=3 {
+            let mut block = Block::new();
+            block.id = id;
+            block.timestamp = 1000 * id;
+            block.generate_pre_hash();
+            block.generate_hash();
+            let payload = block.serialize_for_disk();
+            buffer.extend(BlockRangeFrame::serialize_frame_header(
+                id,
+                &block.hash,
+                payload.len() as u64,
+                &private_key,
+            ));
+            buffer.extend(payload);
+            blocks.push(block);
+        }
+
+        let frames = BlockRangeFrame::deserialize_all(&buffer).unwrap();
+        assert_eq!(frames.len(), 3);
+        for (frame, block) in frames.iter().zip(blocks.iter()) {
+            assert_eq!(frame.block_id, block.id);
+            assert_eq!(frame.block_hash, block.hash);
+            assert_eq!(frame.validate(false, Some(&public_key)), Ok(()));
+            assert_eq!(
+                frame.validate(false, None),
+                Err(FetchedBlockError::InvalidSignature)
+            );
+
+            // lite frames have only the header
+            let payload = block.serialize_for_net(BlockType::Header);
+            let lite_frame = BlockRangeFrame {
+                block_id: block.id,
+                block_hash: block.hash,
+                signature: sign_block_fetch_response(
+                    &block.hash,
+                    payload.len() as u64,
+                    &private_key,
+                ),
+                payload,
+            };
+            assert_eq!(lite_frame.validate(true, Some(&public_key)), Ok(()));
+        }
+        assert_eq!(
+            BlockRangeFrame::deserialize_all(&buffer[..buffer.len() - 1]),
+            Err(BlockRangeError::InvalidFrame)
+        );
+        assert!(BlockRangeFrame::deserialize(&buffer[..50]).is_none());
+    }
+}
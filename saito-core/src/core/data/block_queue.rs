@@ -0,0 +1,151 @@
+/// max number of blocks waiting to be added to the blockchain before the routing thread stops fetching blocks
+pub const DEFAULT_BLOCK_QUEUE_LIMIT: usize = 100;
+
+/// Blocks which were fetched by the routing thread but not added to the blockchain yet. The routing thread counts the
+/// blocks it sends for verification, and the consensus thread counts the blocks it receives and the ones left in the
+/// mempool queue.
+///
+/// When the queue reaches the limit the routing thread stops fetching block bodies and only records the announced
+/// block hashes, until the consensus thread drains the queue to half of the limit.
+#[derive(Debug, Clone)]
+pub struct BlockQueueStatus {
+    limit: usize,
+    // sent for verification, but not received by the consensus thread yet
+    in_flight: usize,
+    // blocks in the mempool queue
+    queued: usize,
+    max_depth: usize,
+    paused: bool,
+    pub pause_count: u64,
+}
+
+impl BlockQueueStatus {
+    pub fn new(limit: usize) -> BlockQueueStatus {
+        assert_ne!(limit, 0);
+        BlockQueueStatus {
+            limit,
+            in_flight: 0,
+            queued: 0,
+            max_depth: 0,
+            paused: false,
+            pause_count: 0,
+        }
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        assert_ne!(limit, 0);
+        self.limit = limit;
+        self.update();
+    }
+
+    pub fn get_limit(&self) -> usize {
+        self.limit
+    }
+
+    /// called by the routing thread when fetched blocks are sent for verification
+    pub fn on_blocks_sent(&mut self, count: usize) {
+        self.in_flight += count;
+        self.update();
+    }
+
+    /// called by the consensus thread for each fetched block it receives
+    pub fn on_block_received(&mut self) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+        self.update();
+    }
+
+    /// called by the consensus thread after the blocks in the mempool queue are added to the blockchain
+    pub fn set_queued_block_count(&mut self, count: usize) {
+        self.queued = count;
+        self.update();
+    }
+
+    /// The blocks sent before the sync was restarted are dropped or already added, so they are not counted anymore
+    pub fn reset_in_flight(&mut self) {
+        self.in_flight = 0;
+        self.update();
+    }
+
+    pub fn get_depth(&self) -> usize {
+        self.in_flight + self.queued
+    }
+
+    pub fn get_max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// true if the routing thread should not fetch new blocks until the queue is drained
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn get_stats(&self) -> String {
+        format!(
+            "{} - depth : {:?}, in_flight : {:?}, queued : {:?}, max_depth : {:?}, limit : {:?}, paused : {:?}, pauses : {:?}",
+            format!("{:width$}", "routing::block_queue", width = 40),
+            self.get_depth(),
+            self.in_flight,
+            self.queued,
+            self.max_depth,
+            self.limit,
+            self.paused,
+            self.pause_count
+        )
+    }
+
+    fn update(&mut self) {
+        let depth = self.get_depth();
+        self.max_depth = self.max_depth.max(depth);
+        if !self.paused && depth >= self.limit {
+            self.paused = true;
+            self.pause_count += 1;
+        } else if self.paused && depth <= self.limit / 2 {
+            self.paused = false;
+        }
+    }
+}
+
+impl Default for BlockQueueStatus {
+    fn default() -> Self {
+        BlockQueueStatus::new(DEFAULT_BLOCK_QUEUE_LIMIT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::block_queue::BlockQueueStatus;
+
+    #[test]
+    fn block_queue_backpressure_test() {
+        let mut status = BlockQueueStatus::new(10);
+        status.on_blocks_sent(6);
+        assert!(!status.is_paused());
+        status.set_queued_block_count(4);
+        assert_eq!(status.get_depth(), 10);
+        assert!(status.is_paused());
+        assert_eq!(status.pause_count, 1);
+
+        // stays paused until drained to half of the limit
+        status.set_queued_block_count(0);
+        assert!(status.is_paused());
+        status.on_block_received();
+        assert_eq!(status.get_depth(), 5);
+        assert!(!status.is_paused());
+        assert_eq!(status.get_max_depth(), 10);
+
+        status.on_blocks_sent(5);
+        assert!(status.is_paused());
+        assert_eq!(status.pause_count, 2);
+        status.reset_in_flight();
+        assert_eq!(status.get_depth(), 0);
+        assert!(!status.is_paused());
+
+        // received blocks which were sent before the reset
+        status.on_block_received();
+        assert_eq!(status.get_depth(), 0);
+
+        status.on_blocks_sent(3);
+        status.set_limit(3);
+        assert!(status.is_paused());
+    }
+}
@@ -1,24 +1,39 @@
+use std::borrow::Cow;
 use std::collections::VecDeque;
-use std::io::Error;
+use std::io::{Error, ErrorKind};
 use std::sync::Arc;
 
 use ahash::AHashMap;
 use async_recursion::async_recursion;
 use rayon::prelude::*;
-use tokio::sync::mpsc::Sender;
-use tokio::sync::RwLock;
 use tracing::{debug, error, info, trace, warn};
 
 use crate::common::defs::{
-    push_lock, Currency, SaitoHash, UtxoSet, LOCK_ORDER_MEMPOOL, LOCK_ORDER_WALLET,
+    push_lock, Currency, SaitoHash, SaitoSignature, Timestamp, UtxoSet, LOCK_ORDER_MEMPOOL,
+    LOCK_ORDER_WALLET,
 };
-use crate::core::data::block::{Block, BlockType};
+use crate::common::runtime::{RwLock, Sender};
+use crate::core::data::account_nonce::AccountNonces;
+use crate::core::data::archive_sink::ArchiveSink;
+use crate::core::data::audit_log::{AddBlockOutcome, AuditEvent};
+use crate::core::data::block::{Block, BlockTimestampError, BlockType};
 use crate::core::data::blockring::BlockRing;
+use crate::core::data::chain_iterator::{ForkIter, LongestChainIter};
+use crate::core::data::chain_stats::{BlockChainStats, ChainStats, DEFAULT_CHAIN_STATS_RETENTION};
+use crate::core::data::consensus_health::{
+    ConsensusHealthAlert, ConsensusHealthConfig, ConsensusHealthMonitor, ConsensusHealthSample,
+    CONSENSUS_HEALTH_WINDOW,
+};
+use crate::core::data::difficulty::{ConsensusDifficultyPolicy, DifficultyPolicy};
+use crate::core::data::full_block_cache::FullBlockCache;
+use crate::core::data::handle_registry::HandleRegistry;
 use crate::core::data::mempool::Mempool;
 use crate::core::data::network::Network;
 use crate::core::data::storage::Storage;
+use crate::core::data::timestamp_index::{TimestampIndex, TIMESTAMP_INDEX_BUCKET_SIZE_IN_MS};
 use crate::core::data::transaction::{Transaction, TransactionType};
-use crate::core::data::wallet::Wallet;
+use crate::core::data::transaction_index::{TransactionIndex, TransactionLocation};
+use crate::core::data::wallet::{Wallet, WalletBlock, WalletUpdate, WalletUpdateType};
 use crate::core::mining_thread::MiningEvent;
 use crate::{lock_for_read, lock_for_write};
 
@@ -34,6 +49,8 @@ pub const MAX_TOKEN_SUPPLY: Currency = 10_000_000_000_000_000_000_000_000_000;
 pub const MIN_GOLDEN_TICKETS_NUMERATOR: u64 = 2;
 // minimum golden tickets required ( number of tickets / NUMBER_OF_PRECEDING_BLOCKS )
 pub const MIN_GOLDEN_TICKETS_DENOMINATOR: u64 = 6;
+// max time a received block's timestamp can be ahead of the local clock
+pub const DEFAULT_MAX_BLOCK_FUTURE_DRIFT_IN_MS: Timestamp = 120_000;
 
 pub fn bit_pack(top: u32, bottom: u32) -> u64 {
     ((top as u64) << 32) + (bottom as u64)
@@ -61,18 +78,80 @@ pub struct Blockchain {
     pub wallet_lock: Arc<RwLock<Wallet>>,
     pub genesis_block_id: u64,
     fork_id: SaitoHash,
+    // routing only nodes don't need to scan the blocks for wallet slips
+    pub wallet_tracking_enabled: bool,
+    // wallet updates are queued to be applied by another thread instead of locking the wallet while the chain is updated
+    async_wallet_updates: bool,
+    // queued wallet updates in the order they happened
+    wallet_updates: Vec<WalletUpdate>,
+    // sequence number of the last wallet update
+    wallet_update_sequence: u64,
+    // max number of full blocks kept in memory. 0 means no limit
+    full_block_cache_size: usize,
+    // hashes of the full blocks in memory by their last access
+    full_block_cache: FullBlockCache,
+    difficulty_policy: Box<dyn DifficultyPolicy>,
+    // block hashes indexed by block timestamps
+    timestamp_index: TimestampIndex,
+    // token supply and fee totals of the longest chain blocks
+    chain_stats: ChainStats,
+    // fork rate and block interval alerts of the latest blocks
+    consensus_health: ConsensusHealthMonitor,
+    // latest transaction nonce of each sender in the longest chain
+    account_nonces: AccountNonces,
+    // handles registered in the longest chain
+    handle_registry: HandleRegistry,
+    // transaction locations by signature. None if the index is disabled
+    transaction_index: Option<TransactionIndex>,
+    // receives the purged blocks before they are deleted from disk. None if the blocks are just deleted
+    archive_sink: Option<Box<dyn ArchiveSink>>,
+    // max time a received block's timestamp can be ahead of the local clock
+    max_block_future_drift_in_ms: Timestamp,
+    // add block outcomes and reorgs are only queued for the audit log if it's enabled
+    audit_events_enabled: bool,
+    // queued audit events in the order they happened
+    audit_events: Vec<AuditEvent>,
+    // longest chain changes are only queued if the transaction receipts are tracked
+    longest_chain_updates_enabled: bool,
+    // (block hash, added to the longest chain) in the order the chain was wound and unwound
+    longest_chain_updates: Vec<(SaitoHash, bool)>,
+    // blocks kept before pruning. set by the network preset
+    genesis_period: u64,
+    // set if the new chain couldn't be validated or wound since the blocks it depends on can't be loaded from disk
+    validation_deferred: bool,
 }
 
 impl Blockchain {
     #[allow(clippy::new_without_default)]
     pub fn new(wallet_lock: Arc<RwLock<Wallet>>) -> Self {
         Blockchain {
-            utxoset: AHashMap::with_capacity(10_000_000),
+            utxoset: UtxoSet::with_capacity(10_000_000),
             blockring: BlockRing::new(),
             blocks: AHashMap::new(),
             wallet_lock,
             genesis_block_id: 0,
             fork_id: [0; 32],
+            wallet_tracking_enabled: true,
+            async_wallet_updates: false,
+            wallet_updates: vec![],
+            wallet_update_sequence: 0,
+            full_block_cache_size: 0,
+            full_block_cache: Default::default(),
+            difficulty_policy: Box::new(ConsensusDifficultyPolicy),
+            timestamp_index: TimestampIndex::new(TIMESTAMP_INDEX_BUCKET_SIZE_IN_MS),
+            chain_stats: ChainStats::new(DEFAULT_CHAIN_STATS_RETENTION),
+            consensus_health: ConsensusHealthMonitor::new(ConsensusHealthConfig::default()),
+            account_nonces: AccountNonces::new(),
+            handle_registry: HandleRegistry::new(GENESIS_PERIOD),
+            transaction_index: None,
+            archive_sink: None,
+            max_block_future_drift_in_ms: DEFAULT_MAX_BLOCK_FUTURE_DRIFT_IN_MS,
+            audit_events_enabled: false,
+            audit_events: vec![],
+            longest_chain_updates_enabled: false,
+            longest_chain_updates: vec![],
+            genesis_period: GENESIS_PERIOD,
+            validation_deferred: false,
         }
     }
     pub fn init(&mut self) -> Result<(), Error> {
@@ -87,6 +166,324 @@ impl Blockchain {
         &self.fork_id
     }
 
+    /// Queues the wallet updates instead of applying them while the chain is updated. Queued updates are taken with
+    /// `take_wallet_updates` and applied with `Wallet::apply_update` in the same order.
+    pub fn set_async_wallet_updates(&mut self, enabled: bool) {
+        self.async_wallet_updates = enabled;
+    }
+
+    pub fn take_wallet_updates(&mut self) -> Vec<WalletUpdate> {
+        std::mem::take(&mut self.wallet_updates)
+    }
+
+    /// Queues the add block outcomes and reorgs for the audit log. Queued events are taken with `take_audit_events`
+    pub fn set_audit_events_enabled(&mut self, enabled: bool) {
+        self.audit_events_enabled = enabled;
+        if !enabled {
+            self.audit_events.clear();
+        }
+    }
+
+    pub fn take_audit_events(&mut self) -> Vec<AuditEvent> {
+        std::mem::take(&mut self.audit_events)
+    }
+
+    /// Queues the blocks added to and removed from the longest chain. Queued updates are taken with
+    /// `take_longest_chain_updates`
+    pub fn set_longest_chain_updates_enabled(&mut self, enabled: bool) {
+        self.longest_chain_updates_enabled = enabled;
+        if !enabled {
+            self.longest_chain_updates.clear();
+        }
+    }
+
+    pub fn take_longest_chain_updates(&mut self) -> Vec<(SaitoHash, bool)> {
+        std::mem::take(&mut self.longest_chain_updates)
+    }
+
+    fn record_longest_chain_update(&mut self, block_hash: SaitoHash, longest_chain: bool) {
+        if self.longest_chain_updates_enabled {
+            self.longest_chain_updates.push((block_hash, longest_chain));
+        }
+    }
+
+    fn record_audit_event(&mut self, event: AuditEvent) {
+        if self.audit_events_enabled {
+            self.audit_events.push(event);
+        }
+    }
+
+    pub fn get_wallet_update_sequence(&self) -> u64 {
+        self.wallet_update_sequence
+    }
+
+    async fn update_wallet(&mut self, block_hash: &SaitoHash, update_type: WalletUpdateType) {
+        //
+        // wallet update is optional, as core routing nodes will not want
+        // to do the work of scrolling through the block and updating their
+        // wallets. wallet processing can be more efficiently handled by
+        // lite-nodes.
+        //
+        if !self.wallet_tracking_enabled {
+            return;
+        }
+        let block = WalletBlock::new(self.blocks.get(block_hash).unwrap());
+        self.wallet_update_sequence += 1;
+        if self.async_wallet_updates {
+            self.wallet_updates.push(WalletUpdate {
+                sequence: self.wallet_update_sequence,
+                update_type,
+                block,
+            });
+            return;
+        }
+        let (mut wallet, _wallet_) = lock_for_write!(self.wallet_lock, LOCK_ORDER_WALLET);
+        wallet.apply_update(self.wallet_update_sequence, update_type, &block);
+    }
+
+    /// Returns the hashes of the blocks created within [start, end] ordered by their timestamps.
+    /// Includes blocks not in the longest chain.
+    pub fn get_blocks_in_time_range(&self, start: Timestamp, end: Timestamp) -> Vec<SaitoHash> {
+        self.timestamp_index.get_in_range(start, end)
+    }
+
+    /// latest transaction nonce of each sender in the longest chain
+    pub fn get_account_nonces(&self) -> &AccountNonces {
+        &self.account_nonces
+    }
+
+    /// handles registered in the longest chain and their owners
+    pub fn get_handle_registry(&self) -> &HandleRegistry {
+        &self.handle_registry
+    }
+
+    pub fn get_chain_stats(&self) -> &ChainStats {
+        &self.chain_stats
+    }
+
+    pub fn get_mut_chain_stats(&mut self) -> &mut ChainStats {
+        &mut self.chain_stats
+    }
+
+    /// Returns the token supply and fee totals as of the latest longest chain block
+    pub fn get_latest_chain_stats(&self) -> Option<&BlockChainStats> {
+        self.chain_stats.get_latest()
+    }
+
+    /// Returns the token supply and fee totals as of the longest chain block with the given id, if it's retained
+    pub fn get_chain_stats_by_block_id(&self, block_id: u64) -> Option<&BlockChainStats> {
+        self.chain_stats.get_by_block_id(block_id)
+    }
+
+    pub fn set_consensus_health_config(&mut self, config: ConsensusHealthConfig) {
+        self.consensus_health.set_config(config);
+    }
+
+    pub fn get_consensus_health(&self) -> &ConsensusHealthMonitor {
+        &self.consensus_health
+    }
+
+    /// Fork rate and block intervals of the latest longest chain blocks
+    pub fn get_consensus_health_sample(&self) -> ConsensusHealthSample {
+        let latest_block_id = self.get_latest_block_id();
+        let first_block_id = latest_block_id
+            .saturating_sub(CONSENSUS_HEALTH_WINDOW as u64 - 1)
+            .max(self.genesis_block_id)
+            .max(1);
+        let mut block_count = 0;
+        let mut fork_block_count = 0;
+        for block_id in first_block_id..=latest_block_id {
+            let count = self.blockring.get_block_count_at_block_id(block_id) as u64;
+            block_count += count.min(1);
+            fork_block_count += count.saturating_sub(1);
+        }
+        ConsensusHealthSample::new(
+            block_count,
+            fork_block_count,
+            &self
+                .chain_stats
+                .get_latest_block_intervals(CONSENSUS_HEALTH_WINDOW),
+        )
+    }
+
+    fn check_consensus_health(&mut self) {
+        let sample = self.get_consensus_health_sample();
+        for alert in self.consensus_health.check(sample) {
+            match alert {
+                ConsensusHealthAlert::HighForkRate { fork_rate } => {
+                    warn!(
+                        "HIGH FORK RATE : {:?} fork blocks per 100 blocks in the latest {:?} blocks. network might be split",
+                        fork_rate, sample.block_count
+                    );
+                }
+                ConsensusHealthAlert::IrregularBlockIntervals { deviation, average } => {
+                    warn!(
+                        "IRREGULAR BLOCK INTERVALS : deviation of {:?} ms from the average block time of {:?} ms. block timestamps might be manipulated",
+                        deviation, average
+                    );
+                }
+                ConsensusHealthAlert::ForkRateRecovered { fork_rate } => {
+                    info!("fork rate is back to : {:?} per 100 blocks", fork_rate);
+                }
+                ConsensusHealthAlert::BlockIntervalsRecovered { deviation } => {
+                    info!("block interval deviation is back to : {:?} ms", deviation);
+                }
+            }
+        }
+    }
+
+    /// Clears the blocks and the state built from them, keeping the configuration. The blocks should be added again
+    /// afterwards, e.g. when reindexing the chain from the block files
+    pub fn reset_chain_state(&mut self) {
+        info!(
+            "clearing the chain state of {:?} blocks up to block : {:?}",
+            self.blocks.len(),
+            self.get_latest_block_id()
+        );
+        self.utxoset.clear();
+        self.blockring = BlockRing::new();
+        self.blocks.clear();
+        self.genesis_block_id = 0;
+        self.fork_id = [0; 32];
+        // the wallet is rescanned after the blocks are added again
+        self.wallet_updates.clear();
+        self.longest_chain_updates.clear();
+        self.full_block_cache.clear();
+        self.timestamp_index = TimestampIndex::new(TIMESTAMP_INDEX_BUCKET_SIZE_IN_MS);
+        self.chain_stats = ChainStats::new(DEFAULT_CHAIN_STATS_RETENTION);
+        self.account_nonces = AccountNonces::new();
+        self.handle_registry = HandleRegistry::new(self.genesis_period);
+        if self.transaction_index.is_some() {
+            self.transaction_index = Some(TransactionIndex::new());
+        }
+    }
+
+    /// Enables the transaction index with the given entries. Blocks added afterwards are indexed as well.
+    pub fn set_transaction_index(&mut self, mut transaction_index: TransactionIndex) {
+        for block in self.blocks.values() {
+            if block.block_type == BlockType::Full {
+                transaction_index.add_block(block);
+            }
+        }
+        info!(
+            "transaction index enabled with {:?} transactions",
+            transaction_index.len()
+        );
+        self.transaction_index = Some(transaction_index);
+    }
+
+    pub fn get_transaction_index(&self) -> Option<&TransactionIndex> {
+        self.transaction_index.as_ref()
+    }
+
+    /// Returns the locations of the transaction with the given signature. Longest chain blocks are returned first.
+    /// Entries loaded from disk for blocks which are no longer in the blockchain are skipped.
+    pub fn get_transaction_locations(
+        &self,
+        signature: &SaitoSignature,
+    ) -> Vec<TransactionLocation> {
+        if self.transaction_index.is_none() {
+            return vec![];
+        }
+        let mut locations: Vec<(bool, TransactionLocation)> = self
+            .transaction_index
+            .as_ref()
+            .unwrap()
+            .get(signature)
+            .iter()
+            .filter_map(|location| {
+                let block = self.blocks.get(&location.block_hash)?;
+                Some((!block.in_longest_chain, *location))
+            })
+            .collect();
+        locations.sort_by_key(|(not_in_longest_chain, _)| *not_in_longest_chain);
+        locations
+            .into_iter()
+            .map(|(_, location)| location)
+            .collect()
+    }
+
+    /// Finds the transaction with the given signature using the transaction index. Transactions of pruned blocks
+    /// are loaded from disk.
+    pub async fn get_transaction(
+        &self,
+        signature: &SaitoSignature,
+        storage: &Storage,
+    ) -> Option<(TransactionLocation, Transaction)> {
+        let location = *self.get_transaction_locations(signature).first()?;
+        let block = self
+            .get_full_block(&location.block_hash, storage)
+            .await
+            .map_err(|error| {
+                warn!(
+                    "loading block : {:?} for transaction failed : {:?}",
+                    hex::encode(location.block_hash),
+                    error
+                );
+            })
+            .ok()?;
+        let tx = block.transactions.get(location.tx_ordinal as usize)?;
+        Some((location, tx.clone()))
+    }
+
+    /// Returns the data transactions in the longest chain between the given block ids (inclusive). Pruned blocks are
+    /// skipped since their transactions are not in memory.
+    pub fn get_data_transactions(
+        &self,
+        from_block_id: u64,
+        to_block_id: u64,
+    ) -> Vec<(TransactionLocation, &Transaction)> {
+        let mut transactions = vec![];
+        for block in self
+            .iter_longest_chain(from_block_id)
+            .take_while(|block| block.id <= to_block_id)
+        {
+            if block.block_type != BlockType::Full {
+                debug!(
+                    "skipping data transactions of pruned block : {:?}",
+                    hex::encode(block.hash)
+                );
+                continue;
+            }
+            transactions.extend(
+                block
+                    .transactions
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, tx)| tx.is_data_transaction())
+                    .map(|(index, tx)| {
+                        (
+                            TransactionLocation {
+                                block_hash: block.hash,
+                                block_id: block.id,
+                                tx_ordinal: index as u64,
+                            },
+                            tx,
+                        )
+                    }),
+            );
+        }
+        transactions
+    }
+
+    pub fn set_difficulty_policy(&mut self, difficulty_policy: Box<dyn DifficultyPolicy>) {
+        info!("using difficulty policy : {:?}", difficulty_policy);
+        self.difficulty_policy = difficulty_policy;
+        // cached validation results were calculated with the previous policy
+        for block in self.blocks.values_mut() {
+            block.clear_validation_cache();
+        }
+    }
+
+    pub fn set_archive_sink(&mut self, archive_sink: Box<dyn ArchiveSink>) {
+        info!("archiving purged blocks to : {:?}", archive_sink);
+        self.archive_sink = Some(archive_sink);
+    }
+
+    pub fn get_difficulty_policy(&self) -> &dyn DifficultyPolicy {
+        self.difficulty_policy.as_ref()
+    }
+
     #[tracing::instrument(level = "info", skip_all)]
     #[async_recursion]
     pub async fn add_block(
@@ -117,6 +514,17 @@ impl Blockchain {
         let block_id = block.id;
         let previous_block_hash = self.blockring.get_latest_block_hash();
         // let previous_block_hash = block.previous_block_hash;
+        let block_parent_hash = block.previous_block_hash;
+        let block_creator = block.creator;
+        let add_block_event = |outcome: AddBlockOutcome| {
+            AuditEvent::add_block(
+                block_id,
+                &block_hash,
+                &block_parent_hash,
+                &block_creator,
+                outcome,
+            )
+        };
 
         // sanity checks
         if self.blocks.contains_key(&block_hash) {
@@ -124,6 +532,7 @@ impl Blockchain {
                 "block already exists in blockchain {:?}. not adding",
                 &hex::encode(&block.hash)
             );
+            self.record_audit_event(add_block_event(AddBlockOutcome::AlreadyExists));
             return AddBlockResult::BlockAlreadyExists;
         }
 
@@ -145,10 +554,11 @@ impl Blockchain {
                 let block_hash = block.previous_block_hash;
                 let block_in_mempool_queue;
                 {
-                    block_in_mempool_queue = mempool
-                        .blocks_queue
-                        .par_iter()
-                        .any(|b| block_hash == b.hash);
+                    block_in_mempool_queue = mempool.orphan_blocks.contains(&block_hash)
+                        || mempool
+                            .blocks_queue
+                            .par_iter()
+                            .any(|b| block_hash == b.hash);
                 }
                 if !block_in_mempool_queue {
                     let result = network
@@ -171,11 +581,11 @@ impl Blockchain {
                     );
                 }
 
-                debug!("adding block : {:?} back to mempool so it can be processed again after the previous block : {:?} is added",
+                debug!("adding block : {:?} to the orphan pool so it can be processed again after the previous block : {:?} is added",
                                     hex::encode(block.hash),
                                     hex::encode(block.previous_block_hash));
-                // TODO : mempool can grow if an attacker keep sending blocks with non existing parents. need to fix. can use an expiry time perhaps?
-                mempool.add_block(block);
+                mempool.orphan_blocks.add(block);
+                self.record_audit_event(add_block_event(AddBlockOutcome::WaitingForParent));
                 return AddBlockResult::FailedButRetry;
             } else {
                 debug!(
@@ -242,12 +652,14 @@ impl Blockchain {
         // arrival if they do not exist.
 
         if !self.blocks.contains_key(&block_hash) {
+            self.timestamp_index.add(block.timestamp, block_hash);
             self.blocks.insert(block_hash, block);
         } else {
             error!(
                 "BLOCK IS ALREADY IN THE BLOCKCHAIN, WHY ARE WE ADDING IT????? {:?}",
                 block.hash
             );
+            self.record_audit_event(add_block_event(AddBlockOutcome::AlreadyExists));
             return AddBlockResult::BlockAlreadyExists;
         }
 
@@ -397,11 +809,21 @@ impl Blockchain {
             debug!("this is the longest chain");
             self.blocks.get_mut(&block_hash).unwrap().in_longest_chain = true;
 
+            self.validation_deferred = false;
             let does_new_chain_validate = self
                 .validate(new_chain.as_slice(), old_chain.as_slice(), storage)
                 .await;
 
             if does_new_chain_validate {
+                self.record_audit_event(add_block_event(AddBlockOutcome::AddedToLongestChain));
+                if !old_chain.is_empty() {
+                    self.record_audit_event(AuditEvent::reorg(
+                        block_id,
+                        &block_hash,
+                        &old_chain,
+                        &new_chain,
+                    ));
+                }
                 self.add_block_success(block_hash, network, storage, mempool)
                     .await;
 
@@ -423,11 +845,15 @@ impl Blockchain {
                     hex::encode(block_hash)
                 );
                 self.blocks.get_mut(&block_hash).unwrap().in_longest_chain = false;
-                self.add_block_failure(&block_hash, mempool).await;
+                self.record_audit_event(add_block_event(AddBlockOutcome::FailedValidation));
+                let validation_deferred = self.validation_deferred;
+                self.add_block_failure(&block_hash, mempool, storage, validation_deferred)
+                    .await;
                 AddBlockResult::FailedButRetry
             }
         } else {
             debug!("this is not the longest chain");
+            self.record_audit_event(add_block_event(AddBlockOutcome::AddedToFork));
             self.add_block_success(block_hash, network, storage, mempool)
                 .await;
             AddBlockResult::BlockAdded
@@ -453,11 +879,19 @@ impl Blockchain {
         //
         // save to disk
         //
+        let is_full_block;
         {
             let block = self.get_mut_block(&block_hash).unwrap();
+            is_full_block = block.block_type == BlockType::Full;
             if block.block_type != BlockType::Header {
-                // TODO : this will have an impact when the block sizes are getting large or there are many forks. need to handle this
-                storage.write_block_to_disk(block).await;
+                // if a block writer is running, this only queues the write. so propagation continues from memory
+                if let Err(error) = storage.write_block_to_disk(block).await {
+                    error!(
+                        "writing block : {:?} to disk failed : {:?}",
+                        hex::encode(block.hash),
+                        error
+                    );
+                }
             } else {
                 debug!(
                     "block : {:?} not written to disk as type : {:?}",
@@ -466,6 +900,10 @@ impl Blockchain {
                 );
             }
             network.propagate_block(block).await;
+            mempool.creator_blacklist.on_valid_block(&block.creator);
+        }
+        if let Some(transaction_index) = self.transaction_index.as_mut() {
+            transaction_index.add_block(self.blocks.get(&block_hash).unwrap());
         }
 
         //
@@ -483,6 +921,12 @@ impl Blockchain {
             let block = self.get_mut_block(&block_hash).unwrap();
             // we calling delete_tx after removing invalidated txs, to make sure routing work is calculated after removing all the txs
             mempool.delete_transactions(&block.transactions);
+            mempool.set_account_nonces(&self.account_nonces);
+        }
+
+        if is_full_block {
+            self.mark_full_block_access(block_hash);
+            self.enforce_full_block_cache_size(storage).await;
         }
 
         //
@@ -514,9 +958,9 @@ impl Blockchain {
         //
         // ensure pruning of next block OK will have the right CVs
         //
-        if self.get_latest_block_id() > GENESIS_PERIOD {
+        if self.get_latest_block_id() > self.genesis_period {
             let pruned_block_hash = self.blockring.get_longest_chain_block_hash_by_block_id(
-                self.get_latest_block_id() - GENESIS_PERIOD,
+                self.get_latest_block_id() - self.genesis_period,
             );
 
             assert_ne!(pruned_block_hash, [0; 32]);
@@ -540,16 +984,44 @@ impl Blockchain {
     }
 
     #[tracing::instrument(level = "info", skip_all)]
-    pub async fn add_block_failure(&mut self, block_hash: &SaitoHash, mempool: &mut Mempool) {
-        info!("add block failed : {:?}", hex::encode(block_hash));
+    pub async fn add_block_failure(
+        &mut self,
+        block_hash: &SaitoHash,
+        mempool: &mut Mempool,
+        storage: &mut Storage,
+        validation_deferred: bool,
+    ) {
+        info!(
+            "add block failed : {:?} validation deferred : {:?}",
+            hex::encode(block_hash),
+            validation_deferred
+        );
+
+        // only blocks loaded from disk at startup are quarantined. blocks which couldn't be validated
+        // because of the local disk state are not known to be invalid
+        if !validation_deferred {
+            storage.quarantine_invalid_block(block_hash).await;
+        }
 
         mempool.delete_block(block_hash);
         let mut block = self.blocks.remove(block_hash).unwrap();
+        self.timestamp_index.remove(block.timestamp, block_hash);
+        if let Some(transaction_index) = self.transaction_index.as_mut() {
+            transaction_index.remove_block(block_hash);
+        }
+
+        // blocks signed by another key don't count against the creator
+        if !validation_deferred
+            && block.creator != mempool.public_key
+            && block.has_valid_signature()
+        {
+            mempool.creator_blacklist.on_invalid_block(&block.creator);
+        }
 
         if block.creator == mempool.public_key {
             let transactions = &mut block.transactions;
             let prev_count = transactions.len();
-            let transactions: Vec<Transaction> = transactions
+            let mut transactions: Vec<Transaction> = transactions
                 .par_drain(..)
                 .with_min_len(10)
                 .filter(|tx| {
@@ -567,8 +1039,11 @@ impl Blockchain {
                 transactions.len(),
                 (prev_count - transactions.len())
             );
+            // transactions with nonces are only accepted in order
+            transactions.sort_by_key(|tx| tx.get_nonce());
+            mempool.set_account_nonces(&self.account_nonces);
             for tx in transactions {
-                mempool.transactions.insert(tx.signature, tx);
+                mempool.add_transaction(tx).await;
             }
             mempool.new_tx_added = true;
         }
@@ -786,22 +1261,180 @@ impl Blockchain {
         self.blockring.get_latest_block_id()
     }
 
+    /// 0 if there are no blocks
+    pub fn get_latest_block_timestamp(&self) -> Timestamp {
+        self.get_latest_block().map_or(0, |block| block.timestamp)
+    }
+
     pub fn get_block_sync(&self, block_hash: &SaitoHash) -> Option<&Block> {
         self.blocks.get(block_hash)
     }
 
     // #[tracing::instrument(level = "info", skip_all)]
+    /// Iterates the longest chain blocks from the given id up to the latest block
+    pub fn iter_longest_chain(&self, from_id: u64) -> LongestChainIter {
+        LongestChainIter::new(self, from_id, self.get_latest_block_id())
+    }
+
+    /// Iterates the blocks of the fork with the given tip back to the shared ancestor with the longest chain
+    pub fn iter_fork(&self, tip_hash: SaitoHash) -> ForkIter {
+        ForkIter::new(self, tip_hash)
+    }
+
+    /// Returns the block as it is in memory. Only the blocks in the full block cache have their transactions, others
+    /// are pruned. Callers reading the transactions should use `get_full_block` or `upgrade_block_to_full`
     pub fn get_block(&self, block_hash: &SaitoHash) -> Option<&Block> {
         //
 
         self.blocks.get(block_hash)
     }
 
+    /// Returns the block with its transactions. Blocks which are not full in memory are loaded from disk without
+    /// keeping them in memory, so this works with a read lock. Fails if the block is not in the blockchain or its
+    /// file can't be loaded
+    pub async fn get_full_block(
+        &self,
+        block_hash: &SaitoHash,
+        storage: &Storage,
+    ) -> Result<Cow<'_, Block>, Error> {
+        let block = self
+            .blocks
+            .get(block_hash)
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        if block.block_type == BlockType::Full {
+            return Ok(Cow::Borrowed(block));
+        }
+        let mut full_block = storage
+            .load_block_from_disk(storage.generate_block_filename(block))
+            .await?;
+        full_block.generate();
+        if full_block.hash != *block_hash {
+            warn!(
+                "block file of : {:?} contains block : {:?}",
+                hex::encode(block_hash),
+                hex::encode(full_block.hash)
+            );
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        Ok(Cow::Owned(full_block))
+    }
+
     pub fn get_mut_block(&mut self, block_hash: &SaitoHash) -> Option<&mut Block> {
         //
         self.blocks.get_mut(block_hash)
     }
 
+    /// Sets the max number of full blocks to keep in memory. 0 disables the limit.
+    /// Blocks within the pruning depth are needed in full, so the limit cannot go below that.
+    pub fn set_full_block_cache_size(&mut self, size: usize) {
+        let min_size = PRUNE_AFTER_BLOCKS as usize;
+        if size != 0 && size < min_size {
+            warn!(
+                "full block cache size : {:?} is too small. using : {:?}",
+                size, min_size
+            );
+            self.full_block_cache_size = min_size;
+            return;
+        }
+        self.full_block_cache_size = size;
+    }
+
+    /// Sets the number of blocks kept before pruning. Should be set before any block is added, since the
+    /// consensus values of the blocks depend on it
+    pub fn set_genesis_period(&mut self, genesis_period: u64) {
+        assert!(genesis_period > 0 && genesis_period <= GENESIS_PERIOD);
+        info!("using genesis period : {:?}", genesis_period);
+        self.genesis_period = genesis_period;
+        self.handle_registry = HandleRegistry::new(genesis_period);
+    }
+
+    pub fn get_genesis_period(&self) -> u64 {
+        self.genesis_period
+    }
+
+    /// Sets how far ahead of the local clock a received block can be dated. 0 uses the default.
+    pub fn set_max_block_future_drift(&mut self, drift_in_ms: Timestamp) {
+        if drift_in_ms == 0 {
+            self.max_block_future_drift_in_ms = DEFAULT_MAX_BLOCK_FUTURE_DRIFT_IN_MS;
+            return;
+        }
+        self.max_block_future_drift_in_ms = drift_in_ms;
+    }
+
+    /// Checks the timestamp of a block received from a peer against the local clock, and against its parent if the
+    /// parent is already in the blockchain.
+    pub fn validate_received_block_timestamp(
+        &self,
+        block: &Block,
+        current_time: Timestamp,
+    ) -> Result<(), BlockTimestampError> {
+        block.validate_timestamp_against_current_time(
+            current_time,
+            self.max_block_future_drift_in_ms,
+        )?;
+        if let Some(previous_block) = self.blocks.get(&block.previous_block_hash) {
+            block.validate_timestamp_against_previous_block(previous_block.timestamp)?;
+        }
+        Ok(())
+    }
+
+    fn mark_full_block_access(&mut self, block_hash: SaitoHash) {
+        if self.full_block_cache_size == 0 {
+            return;
+        }
+        self.full_block_cache.touch(block_hash);
+    }
+
+    /// Loads the block's transactions from disk if the block is not already full in memory, and keeps it in the full
+    /// block cache. Returns false if the block is not found or its file can't be loaded
+    pub async fn upgrade_block_to_full(
+        &mut self,
+        block_hash: &SaitoHash,
+        storage: &Storage,
+    ) -> bool {
+        let block = self.blocks.get_mut(block_hash);
+        if block.is_none() {
+            warn!("block : {:?} not found to upgrade", hex::encode(block_hash));
+            return false;
+        }
+        let block = block.unwrap();
+        if block.block_type == BlockType::Full {
+            return true;
+        }
+        if !block
+            .upgrade_block_to_block_type(BlockType::Full, storage)
+            .await
+        {
+            return false;
+        }
+        self.mark_full_block_access(*block_hash);
+        true
+    }
+
+    /// Downgrades the least recently accessed full blocks until we are within the cache size
+    async fn enforce_full_block_cache_size(&mut self, storage: &Storage) {
+        if self.full_block_cache_size == 0 {
+            return;
+        }
+        while self.full_block_cache.len() > self.full_block_cache_size {
+            let block_hash = self.full_block_cache.pop_least_recent().unwrap();
+            if let Some(block) = self.blocks.get_mut(&block_hash) {
+                if block.block_type != BlockType::Full {
+                    continue;
+                }
+                // transactions are reloaded from disk on next access, so the block needs to be written first
+                storage
+                    .wait_for_block_write(storage.generate_block_filename(block).as_str())
+                    .await;
+                trace!(
+                    "downgrading block : {:?} to free memory",
+                    hex::encode(block_hash)
+                );
+                block.downgrade_block_to_block_type(BlockType::Pruned).await;
+            }
+        }
+    }
+
     pub fn is_block_indexed(&self, block_hash: SaitoHash) -> bool {
         if self.blocks.contains_key(&block_hash) {
             return true;
@@ -1010,14 +1643,9 @@ impl Blockchain {
         //
         let block_hash = new_chain.get(current_wind_index).unwrap();
 
+        let is_full_block = self.upgrade_block_to_full(block_hash, storage).await;
         {
-            let block = self.get_mut_block(block_hash).unwrap();
-
-            block
-                .upgrade_block_to_block_type(BlockType::Full, storage)
-                .await;
-
-            let latest_block_id = block.id;
+            let latest_block_id = self.get_block(block_hash).unwrap().id;
 
             //
             // ensure previous blocks that may be needed to calculate the staking
@@ -1032,45 +1660,44 @@ impl Blockchain {
                 let previous_block_hash =
                     self.blockring.get_longest_chain_block_hash_by_block_id(bid);
                 if self.is_block_indexed(previous_block_hash) {
-                    let block = self.get_mut_block(&previous_block_hash).unwrap();
-                    block
-                        .upgrade_block_to_block_type(BlockType::Full, storage)
+                    self.upgrade_block_to_full(&previous_block_hash, storage)
                         .await;
                 }
             }
         }
 
+        if !is_full_block {
+            error!(
+                "transactions of block : {:?} can't be loaded for winding",
+                hex::encode(block_hash)
+            );
+            self.validation_deferred = true;
+        }
+        let does_block_validate =
+            is_full_block && self.validate_block_with_cache(block_hash, storage).await;
         let block = self.blocks.get(block_hash).unwrap();
-        assert_eq!(block.block_type, BlockType::Full);
-
-        let does_block_validate = block.validate(self, &self.utxoset).await;
 
         if does_block_validate {
             // blockring update
             self.blockring
                 .on_chain_reorganization(block.id, block.hash, true);
 
-            //
-            // TODO - wallet update should be optional, as core routing nodes
-            // will not want to do the work of scrolling through the block and
-            // updating their wallets by default. wallet processing can be
-            // more efficiently handled by lite-nodes.
-            //
-            {
-                // trace!(" ... wallet processing start:    {}", create_timestamp());
-                let (mut wallet, _wallet_) = lock_for_write!(self.wallet_lock, LOCK_ORDER_WALLET);
-
-                wallet.on_chain_reorganization(block, true);
-
-                // trace!(" ... wallet processing stop:     {}", create_timestamp());
-            }
             let block_id = block.id;
             drop(block);
+            self.update_wallet(block_hash, WalletUpdateType::AddBlock)
+                .await;
             // utxoset update
             {
                 let block = self.blocks.get_mut(block_hash).unwrap();
                 block.on_chain_reorganization(&mut self.utxoset, true);
             }
+            self.chain_stats
+                .on_block_added(self.blocks.get(block_hash).unwrap(), &self.utxoset);
+            self.account_nonces
+                .on_block_added(self.blocks.get(block_hash).unwrap());
+            self.handle_registry
+                .on_block_added(self.blocks.get(block_hash).unwrap());
+            self.record_longest_chain_update(*block_hash, true);
 
             self.on_chain_reorganization(block_id, true, storage).await;
 
@@ -1169,6 +1796,44 @@ impl Blockchain {
         }
     }
 
+    /// Validates the block against the current chain tip. Result is cached in the block, so if the same
+    /// block is wound again on top of the same tip (e.g. while rewinding after a failed reorg) the
+    /// signature and merkle checks are not repeated. If the block can't be validated now it's not cached
+    /// and `validation_deferred` is set instead.
+    async fn validate_block_with_cache(
+        &mut self,
+        block_hash: &SaitoHash,
+        storage: &Storage,
+    ) -> bool {
+        let chain_tip = self.blockring.get_latest_block_hash();
+        let block = self.blocks.get(block_hash).unwrap();
+        if let Some(result) = block.get_cached_validation_result(&chain_tip) {
+            debug!(
+                "using cached validation result : {:?} for block : {:?}",
+                result,
+                hex::encode(block_hash)
+            );
+            return result;
+        }
+        let result = match block.validate(self, &self.utxoset, storage).await {
+            Ok(result) => result,
+            Err(error) => {
+                warn!(
+                    "block : {:?} can't be validated now : {:?}",
+                    hex::encode(block_hash),
+                    error
+                );
+                self.validation_deferred = true;
+                return false;
+            }
+        };
+        self.blocks
+            .get_mut(block_hash)
+            .unwrap()
+            .set_cached_validation_result(chain_tip, result);
+        result
+    }
+
     //
     // when new_chain and old_chain are generated the block_hashes are pushed
     // to their vectors from tip-to-shared-ancestors. if the shared ancestors
@@ -1196,32 +1861,61 @@ impl Blockchain {
         storage: &Storage,
     ) -> bool {
         let block_id;
+        // the utxoset can't be restored without the transactions of the block
+        if !self
+            .upgrade_block_to_full(&old_chain[current_unwind_index], storage)
+            .await
+        {
+            error!(
+                "transactions of block : {:?} can't be loaded for unwinding",
+                hex::encode(old_chain[current_unwind_index])
+            );
+            self.validation_deferred = true;
+            if current_unwind_index == 0 {
+                // nothing was unwound yet
+                return false;
+            }
+            //
+            // rewind the blocks we have already unwound. they are at the
+            // beginning of the vector, so winding starts from the last of them
+            //
+            //   [4] [3] | [2] [1]
+            //
+            let unwound_chain = &old_chain[..current_unwind_index];
+            let res = self
+                .wind_chain(unwound_chain, &[], unwound_chain.len() - 1, true, storage)
+                .await;
+            return res;
+        }
         {
             let block = self
                 .blocks
                 .get_mut(&old_chain[current_unwind_index])
                 .unwrap();
-            block
-                .upgrade_block_to_block_type(BlockType::Full, storage)
-                .await;
             block_id = block.id;
 
             // utxoset update
             block.on_chain_reorganization(&mut self.utxoset, false);
+            self.chain_stats.on_block_removed(block);
+            self.account_nonces.on_block_removed(block);
+            self.handle_registry.on_block_removed(block);
 
             // blockring update
             self.blockring
                 .on_chain_reorganization(block.id, block.hash, false);
-
-            // wallet update
-            {
-                let (mut wallet, _wallet_) = lock_for_write!(self.wallet_lock, LOCK_ORDER_WALLET);
-
-                wallet.on_chain_reorganization(&block, false);
-            }
         }
+        self.update_wallet(
+            &old_chain[current_unwind_index],
+            WalletUpdateType::RemoveBlock,
+        )
+        .await;
+        self.record_longest_chain_update(old_chain[current_unwind_index], false);
         self.on_chain_reorganization(block_id, false, storage).await;
         if current_unwind_index == old_chain.len() - 1 {
+            if new_chain.is_empty() {
+                // nothing to wind when rolling back the chain
+                return !wind_failure;
+            }
             //
             // start winding new chain
             //
@@ -1294,7 +1988,7 @@ impl Blockchain {
             self.set_fork_id(fork_id);
         }
 
-        self.downgrade_blockchain_data().await;
+        self.downgrade_blockchain_data(storage).await;
     }
 
     #[tracing::instrument(level = "info", skip_all)]
@@ -1310,18 +2004,19 @@ impl Blockchain {
         // update the genesis period when that is the case.
         //
         let latest_block_id = self.get_latest_block_id();
-        if latest_block_id >= ((GENESIS_PERIOD * 2) + 1) {
+        if latest_block_id >= ((self.genesis_period * 2) + 1) {
             //
             // prune blocks
             //
-            let purge_bid = latest_block_id - (GENESIS_PERIOD * 2);
-            self.genesis_block_id = latest_block_id - GENESIS_PERIOD;
+            let purge_bid = latest_block_id - (self.genesis_period * 2);
+            self.genesis_block_id = latest_block_id - self.genesis_period;
 
             //
             // in either case, we are OK to throw out everything below the
             // lowest_block_id that we have found. we use the purge_id to
             // handle purges.
             if purge_bid > 0 {
+                self.archive_blocks(purge_bid, storage).await;
                 self.delete_blocks(purge_bid, storage).await;
             }
         }
@@ -1330,6 +2025,42 @@ impl Blockchain {
         // self.downgrade_blockchain_data().await;
     }
 
+    /// Sends the blocks at the given block id to the archive sink. Pruned blocks are loaded from disk.
+    async fn archive_blocks(&mut self, block_id: u64, storage: &Storage) {
+        if self.archive_sink.is_none() {
+            return;
+        }
+        let block_hashes = self.blockring.get_block_hashes_at_block_id(block_id);
+        let archive_sink = self.archive_sink.as_ref().unwrap();
+        for block_hash in block_hashes {
+            if !self.blocks.contains_key(&block_hash) {
+                continue;
+            }
+            let buffer = match self.get_full_block(&block_hash, storage).await {
+                Ok(block) => block.serialize_for_net(BlockType::Full),
+                Err(error) => {
+                    warn!(
+                        "cannot archive block : {:?} since loading it failed : {:?}",
+                        hex::encode(block_hash),
+                        error
+                    );
+                    continue;
+                }
+            };
+            if let Err(error) = archive_sink
+                .archive_block(block_id, block_hash, buffer)
+                .await
+            {
+                warn!(
+                    "archiving block : {:?}-{:?} failed : {:?}",
+                    block_id,
+                    hex::encode(block_hash),
+                    error
+                );
+            }
+        }
+    }
+
     //
     // deletes all blocks at a single block_id
     //
@@ -1367,20 +2098,17 @@ impl Blockchain {
         storage: &Storage,
     ) {
         //
+        // remove slips from wallet
+        //
+        self.update_wallet(&delete_block_hash, WalletUpdateType::DeleteBlock)
+            .await;
+        //
         // ask block to delete itself / utxo-wise
         //
         {
             let pblock = self.blocks.get(&delete_block_hash).unwrap();
             let pblock_filename = storage.generate_block_filename(pblock);
 
-            //
-            // remove slips from wallet
-            //
-            {
-                let (mut wallet, _wallet_) = lock_for_write!(self.wallet_lock, LOCK_ORDER_WALLET);
-
-                wallet.delete_block(pblock);
-            }
             //
             // removes utxoset data
             //
@@ -1398,21 +2126,85 @@ impl Blockchain {
         self.blockring
             .delete_block(delete_block_id, delete_block_hash);
 
+        self.full_block_cache.remove(&delete_block_hash);
+
         //
         // remove from block index
         //
-        if self.blocks.contains_key(&delete_block_hash) {
-            self.blocks.remove_entry(&delete_block_hash);
+        if let Some(block) = self.blocks.remove(&delete_block_hash) {
+            self.timestamp_index
+                .remove(block.timestamp, &delete_block_hash);
+        }
+        if let Some(transaction_index) = self.transaction_index.as_mut() {
+            transaction_index.remove_block(&delete_block_hash);
         }
     }
 
+    /// Unwinds the longest chain back to the given block id and removes the blocks above it from memory and disk.
+    /// Returns false if the chain cannot be rolled back to the given block id.
     #[tracing::instrument(level = "info", skip_all)]
-    pub async fn downgrade_blockchain_data(&mut self) {
-        trace!("downgrading blockchain data");
-        //
-        // downgrade blocks still on the chain
-        //
-        if PRUNE_AFTER_BLOCKS > self.get_latest_block_id() {
+    pub async fn rollback_to_block_id(&mut self, block_id: u64, storage: &Storage) -> bool {
+        let latest_block_id = self.get_latest_block_id();
+        if block_id >= latest_block_id || block_id < self.genesis_block_id {
+            warn!(
+                "cannot rollback to block : {:?} since latest block id : {:?} and genesis block id : {:?}",
+                block_id, latest_block_id, self.genesis_block_id
+            );
+            return false;
+        }
+        info!(
+            "rolling back the chain from block : {:?} to block : {:?}",
+            latest_block_id, block_id
+        );
+
+        //
+        // old chain is ordered from the tip to the target block as in add_block
+        //
+        let mut old_chain: Vec<SaitoHash> = vec![];
+        for id in (block_id + 1..=latest_block_id).rev() {
+            let block_hash = self.blockring.get_longest_chain_block_hash_by_block_id(id);
+            if block_hash == [0; 32] || !self.blocks.contains_key(&block_hash) {
+                error!("longest chain block not found for block id : {:?}", id);
+                return false;
+            }
+            old_chain.push(block_hash);
+        }
+
+        if !self.unwind_chain(&[], &old_chain, 0, false, storage).await {
+            error!("unwinding the chain to block : {:?} failed", block_id);
+            return false;
+        }
+        for block_hash in old_chain.iter() {
+            if let Some(block) = self.blocks.get_mut(block_hash) {
+                block.in_longest_chain = false;
+            }
+        }
+
+        //
+        // remove all the blocks (including forks) above the target block
+        //
+        for id in block_id + 1..=latest_block_id {
+            self.delete_blocks(id, storage).await;
+        }
+
+        let fork_id = self.generate_fork_id(block_id);
+        self.set_fork_id(fork_id);
+
+        info!(
+            "chain rolled back to block : {:?} - {:?}",
+            self.get_latest_block_id(),
+            hex::encode(self.get_latest_block_hash())
+        );
+        true
+    }
+
+    #[tracing::instrument(level = "info", skip_all)]
+    pub async fn downgrade_blockchain_data(&mut self, storage: &Storage) {
+        trace!("downgrading blockchain data");
+        //
+        // downgrade blocks still on the chain
+        //
+        if PRUNE_AFTER_BLOCKS > self.get_latest_block_id() {
             return;
         }
         let prune_blocks_at_block_id = self.get_latest_block_id() - PRUNE_AFTER_BLOCKS;
@@ -1435,11 +2227,16 @@ impl Blockchain {
             {
                 let block = self.get_mut_block(&hash);
                 if let Some(block) = block {
+                    // pruned transactions are reloaded from disk, so the block write has to be completed first
+                    storage
+                        .wait_for_block_write(storage.generate_block_filename(block).as_str())
+                        .await;
                     block.downgrade_block_to_block_type(BlockType::Pruned).await;
                 } else {
                     warn!("block : {:?} not found to downgrade", hex::encode(hash));
                 }
             }
+            self.full_block_cache.remove(&hash);
         }
     }
     pub async fn add_blocks_from_mempool(
@@ -1454,6 +2251,12 @@ impl Blockchain {
         let (mut mempool, _mempool_) = lock_for_write!(mempool, LOCK_ORDER_MEMPOOL);
 
         blocks = mempool.blocks_queue.drain(..).collect();
+        // parents of these orphans were added directly without going through the queue
+        blocks.extend(
+            mempool
+                .orphan_blocks
+                .take_resolved(|hash| self.blocks.contains_key(hash)),
+        );
         blocks.make_contiguous().sort_by(|a, b| a.id.cmp(&b.id));
 
         debug!("blocks to add : {:?}", blocks.len());
@@ -1468,16 +2271,24 @@ impl Blockchain {
                     &mut mempool,
                 )
                 .await;
-            if !blockchain_updated {
-                if let AddBlockResult::BlockAdded = result {
-                    blockchain_updated = true;
+            if let AddBlockResult::BlockAdded = result {
+                blockchain_updated = true;
+                let children = mempool
+                    .orphan_blocks
+                    .take_resolved(|hash| self.blocks.contains_key(hash));
+                for child in children.into_iter().rev() {
+                    blocks.push_front(child);
                 }
             }
         }
+        if blockchain_updated {
+            self.check_consensus_health();
+        }
 
         debug!(
-            "added blocks to blockchain. added back : {:?}",
-            mempool.blocks_queue.len()
+            "added blocks to blockchain. added back : {:?}, orphans : {:?}",
+            mempool.blocks_queue.len(),
+            mempool.orphan_blocks.len()
         );
         blockchain_updated
     }
@@ -1489,10 +2300,15 @@ mod tests {
 
     use tokio::sync::RwLock;
 
-    use crate::common::defs::{push_lock, LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_WALLET};
+    use crate::common::defs::{push_lock, SaitoHash, LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_WALLET};
     use crate::common::test_manager::test;
     use crate::common::test_manager::test::TestManager;
-    use crate::core::data::blockchain::{bit_pack, bit_unpack, Blockchain};
+    use crate::core::data::archive_sink::ArchiveSink;
+    use crate::core::data::audit_log::{AddBlockOutcome, AuditEvent};
+    use crate::core::data::block::{Block, BlockTimestampError, BlockType};
+    use crate::core::data::blockchain::{
+        bit_pack, bit_unpack, Blockchain, DEFAULT_MAX_BLOCK_FUTURE_DRIFT_IN_MS, PRUNE_AFTER_BLOCKS,
+    };
     use crate::core::data::wallet::Wallet;
     use crate::{lock_for_read, lock_for_write};
 
@@ -2491,6 +3307,80 @@ mod tests {
     }
 
     /// Loading blocks into a blockchain which were created from another blockchain instance
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn audit_events_for_longest_chain_reorg_test() {
+        let mut t = TestManager::new();
+        t.initialize(100, 1_000_000_000).await;
+        let (block1_hash, ts) = {
+            let (mut blockchain, _blockchain_) =
+                lock_for_write!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            blockchain.set_audit_events_enabled(true);
+            let block1 = blockchain.get_latest_block().unwrap();
+            (block1.hash, block1.timestamp)
+        };
+
+        // 1 - 2 - 3
+        //      \
+        //       3_2 - 4_2
+        let mut chain = vec![block1_hash];
+        for i in 1..3 {
+            let mut block = t
+                .create_block(chain[i - 1], ts + 120000 * i as u64, 0, 0, 0, true)
+                .await;
+            block.generate();
+            chain.push(block.hash);
+            t.add_block(block).await;
+        }
+        let mut fork = vec![chain[1]];
+        for i in 1..3 {
+            let mut block = t
+                .create_block(
+                    fork[i - 1],
+                    ts + 120000 * (i + 1) as u64 + 1000,
+                    0,
+                    0,
+                    0,
+                    true,
+                )
+                .await;
+            block.generate();
+            fork.push(block.hash);
+            t.add_block(block).await;
+        }
+
+        let (mut blockchain, _blockchain_) =
+            lock_for_write!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+        assert_eq!(blockchain.get_latest_block_hash(), fork[2]);
+        let events = blockchain.take_audit_events();
+        assert_eq!(events.len(), 5);
+        assert_eq!(
+            events[2],
+            AuditEvent::add_block(
+                3,
+                &fork[1],
+                &chain[1],
+                &blockchain.get_block(&fork[1]).unwrap().creator,
+                AddBlockOutcome::AddedToFork
+            )
+        );
+        assert_eq!(
+            events[3],
+            AuditEvent::add_block(
+                4,
+                &fork[2],
+                &fork[1],
+                &blockchain.get_block(&fork[2]).unwrap().creator,
+                AddBlockOutcome::AddedToLongestChain
+            )
+        );
+        assert_eq!(
+            events[4],
+            AuditEvent::reorg(4, &fork[2], &[chain[2]], &[fork[2], fork[1]])
+        );
+        assert!(blockchain.take_audit_events().is_empty());
+    }
+
     #[tokio::test]
     #[serial_test::serial]
     async fn load_blocks_from_another_blockchain_test() {
@@ -2634,4 +3524,457 @@ mod tests {
             assert_eq!(fork_id[4..], [0; 28]);
         }
     }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn rollback_to_block_id_test() {
+        let mut t = TestManager::new();
+        let mut block1;
+        let mut block1_hash;
+        let mut ts;
+        let mut block_hashes = vec![];
+
+        t.initialize_with_timestamp(100, 1_000_000_000, 10_000_000)
+            .await;
+
+        for _i in 0..5 {
+            {
+                let (blockchain, _blockchain_) =
+                    lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+
+                block1 = blockchain.get_latest_block().unwrap();
+                block1_hash = block1.hash;
+                ts = block1.timestamp;
+            }
+
+            let mut block = t
+                .create_block(block1_hash, ts + 120000, 0, 0, 0, true)
+                .await;
+            block.generate();
+            block_hashes.push(block.hash);
+
+            t.add_block(block).await;
+
+            let _result = t.receiver_in_miner.try_recv();
+        }
+
+        let rollback_hash;
+        {
+            let (mut blockchain, _blockchain_) =
+                lock_for_write!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+
+            assert_eq!(blockchain.get_latest_block_id(), 6);
+            rollback_hash = blockchain
+                .blockring
+                .get_longest_chain_block_hash_by_block_id(3);
+
+            assert!(!blockchain.rollback_to_block_id(6, &t.storage).await);
+            assert!(blockchain.rollback_to_block_id(3, &t.storage).await);
+
+            assert_eq!(blockchain.get_latest_block_id(), 3);
+            assert_eq!(blockchain.get_latest_block_hash(), rollback_hash);
+            for block_hash in block_hashes.iter().skip(2) {
+                assert!(!blockchain.is_block_indexed(*block_hash));
+            }
+        }
+
+        t.check_utxoset().await;
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn reindex_chain_test() {
+        let mut t = TestManager::new();
+        let mut block1;
+        let mut block1_hash;
+        let mut ts;
+
+        t.initialize_with_timestamp(100, 1_000_000_000, 10_000_000)
+            .await;
+
+        for _i in 0..3 {
+            {
+                let (blockchain, _blockchain_) =
+                    lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+
+                block1 = blockchain.get_latest_block().unwrap();
+                block1_hash = block1.hash;
+                ts = block1.timestamp;
+            }
+
+            let mut block = t
+                .create_block(block1_hash, ts + 120000, 0, 0, 0, true)
+                .await;
+            block.generate();
+            t.add_block(block).await;
+
+            let _result = t.receiver_in_miner.try_recv();
+        }
+
+        let latest_block_hash;
+        {
+            let (mut blockchain, _blockchain_) =
+                lock_for_write!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            latest_block_hash = blockchain.get_latest_block_hash();
+
+            blockchain.reset_chain_state();
+            assert_eq!(blockchain.get_latest_block_id(), 0);
+            assert!(blockchain.blocks.is_empty());
+            assert!(blockchain.utxoset.is_empty());
+        }
+
+        let blocks = t.storage.load_blocks_for_reindex().await;
+        assert_eq!(blocks.len(), 4);
+        for block in blocks {
+            t.add_block(block).await;
+        }
+        t.storage.clear_loaded_block_files();
+
+        {
+            let (blockchain, _blockchain_) =
+                lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            assert_eq!(blockchain.get_latest_block_id(), 4);
+            assert_eq!(blockchain.get_latest_block_hash(), latest_block_hash);
+        }
+        t.check_utxoset().await;
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn full_block_cache_size_test() {
+        let mut t = TestManager::new();
+        let mut block1;
+        let mut block1_hash;
+        let mut ts;
+
+        t.initialize_with_timestamp(100, 1_000_000_000, 10_000_000)
+            .await;
+        {
+            let (mut blockchain, _blockchain_) =
+                lock_for_write!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            blockchain.set_full_block_cache_size(1);
+            assert_eq!(
+                blockchain.full_block_cache_size,
+                PRUNE_AFTER_BLOCKS as usize
+            );
+        }
+
+        for i in 0..10 {
+            if i == 8 {
+                // reload old pruned blocks to memory
+                let (mut blockchain, _blockchain_) =
+                    lock_for_write!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+                for id in 1..=3 {
+                    let block_hash = blockchain
+                        .blockring
+                        .get_longest_chain_block_hash_by_block_id(id);
+                    blockchain
+                        .upgrade_block_to_full(&block_hash, &t.storage)
+                        .await;
+                    assert_eq!(
+                        blockchain.get_block(&block_hash).unwrap().block_type,
+                        BlockType::Full
+                    );
+                }
+            }
+            {
+                let (blockchain, _blockchain_) =
+                    lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+
+                block1 = blockchain.get_latest_block().unwrap();
+                block1_hash = block1.hash;
+                ts = block1.timestamp;
+            }
+
+            let mut block = t
+                .create_block(block1_hash, ts + 120000, 0, 0, 0, true)
+                .await;
+            block.generate();
+
+            t.add_block(block).await;
+
+            let _result = t.receiver_in_miner.try_recv();
+        }
+
+        let (blockchain, _blockchain_) = lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+        let full_block_count = blockchain
+            .blocks
+            .values()
+            .filter(|block| block.block_type == BlockType::Full)
+            .count();
+        assert!(full_block_count <= PRUNE_AFTER_BLOCKS as usize);
+        assert!(blockchain.full_block_cache.len() <= PRUNE_AFTER_BLOCKS as usize);
+
+        // evicted blocks are loaded from disk on access, without changing the cache
+        let block_hash = (1..=blockchain.get_latest_block_id())
+            .map(|id| {
+                blockchain
+                    .blockring
+                    .get_longest_chain_block_hash_by_block_id(id)
+            })
+            .find(|hash| blockchain.get_block(hash).unwrap().block_type != BlockType::Full)
+            .unwrap();
+        let block = blockchain.get_block(&block_hash).unwrap();
+        assert!(block.transactions.is_empty());
+        let full_block = blockchain
+            .get_full_block(&block_hash, &t.storage)
+            .await
+            .unwrap();
+        assert_eq!(full_block.hash, block_hash);
+        assert_eq!(full_block.block_type, BlockType::Full);
+        assert!(!full_block.transactions.is_empty());
+        assert_ne!(
+            blockchain.get_block(&block_hash).unwrap().block_type,
+            BlockType::Full
+        );
+        assert!(blockchain
+            .get_full_block(&[1; 32], &t.storage)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn block_timestamp_validation_test() {
+        let mut t = TestManager::new();
+        let latest_block_hash;
+        let ts;
+
+        t.initialize_with_timestamp(100, 1_000_000_000, 10_000_000)
+            .await;
+        {
+            let (blockchain, _blockchain_) =
+                lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            let latest_block = blockchain.get_latest_block().unwrap();
+            latest_block_hash = latest_block.hash;
+            ts = latest_block.timestamp;
+        }
+
+        // dated at the same time as the previous block
+        let mut block = t.create_block(latest_block_hash, ts, 0, 0, 0, true).await;
+        block.generate();
+        {
+            let (blockchain, _blockchain_) =
+                lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            assert_eq!(
+                blockchain.validate_received_block_timestamp(&block, ts),
+                Err(BlockTimestampError::NotAfterPreviousBlock {
+                    timestamp: ts,
+                    previous_block_timestamp: ts
+                })
+            );
+        }
+        t.add_block(block).await;
+        {
+            let (blockchain, _blockchain_) =
+                lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            assert_eq!(blockchain.get_latest_block_hash(), latest_block_hash);
+        }
+
+        // dated too far ahead of the local clock
+        let mut block = t
+            .create_block(latest_block_hash, ts + 600_000, 0, 0, 0, true)
+            .await;
+        block.generate();
+        {
+            let (mut blockchain, _blockchain_) =
+                lock_for_write!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            assert_eq!(
+                blockchain.validate_received_block_timestamp(&block, ts),
+                Err(BlockTimestampError::TooFarInFuture {
+                    timestamp: ts + 600_000,
+                    current_time: ts,
+                    max_drift: DEFAULT_MAX_BLOCK_FUTURE_DRIFT_IN_MS
+                })
+            );
+            assert_eq!(
+                blockchain.validate_received_block_timestamp(&block, ts + 500_000),
+                Ok(())
+            );
+            blockchain.set_max_block_future_drift(600_000);
+            assert_eq!(
+                blockchain.validate_received_block_timestamp(&block, ts),
+                Ok(())
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn get_blocks_in_time_range_test() {
+        let mut t = TestManager::new();
+        let mut block1;
+        let mut block1_hash;
+        let mut ts;
+        let mut blocks = vec![];
+
+        t.initialize_with_timestamp(100, 1_000_000_000, 10_000_000)
+            .await;
+
+        for _i in 0..4 {
+            {
+                let (blockchain, _blockchain_) =
+                    lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+
+                block1 = blockchain.get_latest_block().unwrap();
+                block1_hash = block1.hash;
+                ts = block1.timestamp;
+            }
+
+            let mut block = t
+                .create_block(block1_hash, ts + 120000, 0, 0, 0, true)
+                .await;
+            block.generate();
+            blocks.push((block.hash, block.timestamp));
+
+            t.add_block(block).await;
+
+            let _result = t.receiver_in_miner.try_recv();
+        }
+
+        {
+            let (mut blockchain, _blockchain_) =
+                lock_for_write!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+
+            assert_eq!(
+                blockchain.get_blocks_in_time_range(blocks[1].1, blocks[2].1),
+                vec![blocks[1].0, blocks[2].0]
+            );
+            assert_eq!(
+                blockchain.get_blocks_in_time_range(blocks[3].1, blocks[3].1 + 1000),
+                vec![blocks[3].0]
+            );
+            assert!(blockchain
+                .get_blocks_in_time_range(blocks[3].1 + 1, blocks[3].1 + 1000)
+                .is_empty());
+
+            // deleted blocks are removed from the index
+            assert!(blockchain.rollback_to_block_id(3, &t.storage).await);
+            assert_eq!(
+                blockchain.get_blocks_in_time_range(blocks[0].1, blocks[3].1),
+                vec![blocks[0].0, blocks[1].0]
+            );
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct TestArchiveSink {
+        blocks: Arc<std::sync::Mutex<Vec<(u64, SaitoHash, Vec<u8>)>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ArchiveSink for TestArchiveSink {
+        async fn archive_block(
+            &self,
+            block_id: u64,
+            block_hash: SaitoHash,
+            buffer: Vec<u8>,
+        ) -> Result<(), std::io::Error> {
+            self.blocks
+                .lock()
+                .unwrap()
+                .push((block_id, block_hash, buffer));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn archive_blocks_test() {
+        let mut t = TestManager::new();
+        t.initialize(100, 1_000_000_000).await;
+
+        let (mut blockchain, _blockchain_) =
+            lock_for_write!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+        let block_hash = blockchain.get_latest_block_hash();
+
+        // nothing happens without a sink
+        blockchain.archive_blocks(1, &t.storage).await;
+
+        let sink = TestArchiveSink::default();
+        let archived = sink.blocks.clone();
+        blockchain.set_archive_sink(Box::new(sink));
+        blockchain.archive_blocks(1, &t.storage).await;
+        blockchain
+            .get_mut_block(&block_hash)
+            .unwrap()
+            .downgrade_block_to_block_type(BlockType::Pruned)
+            .await;
+        blockchain.archive_blocks(1, &t.storage).await;
+
+        let archived = archived.lock().unwrap();
+        assert_eq!(archived.len(), 2);
+        assert_eq!(archived[0].0, 1);
+        assert_eq!(archived[0].1, block_hash);
+        // pruned blocks are loaded from disk
+        assert_eq!(archived[1].2, archived[0].2);
+        assert_eq!(
+            Block::deserialize_from_net(&archived[0].2)
+                .transactions
+                .len(),
+            100
+        );
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn unwind_chain_with_missing_block_file_test() {
+        let mut t = TestManager::new();
+        t.initialize_with_timestamp(100, 1_000_000_000, 10_000_000)
+            .await;
+        let (block1_hash, ts) = {
+            let (blockchain, _blockchain_) =
+                lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            let block1 = blockchain.get_latest_block().unwrap();
+            (block1.hash, block1.timestamp)
+        };
+
+        // 1 - 2 - 3 - 4
+        //      \
+        //       3_2 - 4_2 - 5_2
+        let mut chain = vec![block1_hash];
+        for i in 1..4 {
+            let mut block = t
+                .create_block(chain[i - 1], ts + 120000 * i as u64, 0, 0, 0, true)
+                .await;
+            block.generate();
+            chain.push(block.hash);
+            t.add_block(block).await;
+        }
+        let block4_hash = chain[3];
+
+        {
+            let (mut blockchain, _blockchain_) =
+                lock_for_write!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            assert_eq!(blockchain.get_latest_block_hash(), block4_hash);
+            let block3 = blockchain.get_mut_block(&chain[2]).unwrap();
+            block3
+                .downgrade_block_to_block_type(BlockType::Pruned)
+                .await;
+            let filename = t.storage.generate_block_filename(block3);
+            assert!(t.storage.delete_block_from_disk(filename).await);
+        }
+
+        let mut fork = vec![chain[1]];
+        for i in 1..4 {
+            let mut block = t
+                .create_block(
+                    fork[i - 1],
+                    ts + 120000 * (i + 1) as u64 + 1000,
+                    0,
+                    0,
+                    0,
+                    true,
+                )
+                .await;
+            block.generate();
+            fork.push(block.hash);
+            t.add_block(block).await;
+        }
+
+        // block 3 can't be unwound, so block 4 is wound back and the reorg is aborted
+        let (blockchain, _blockchain_) = lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+        assert_eq!(blockchain.get_latest_block_hash(), block4_hash);
+        assert!(blockchain.get_block(&fork[3]).is_none());
+    }
 }
@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use ahash::AHashMap;
+use tracing::trace;
+
+use crate::common::defs::{
+    push_lock, BlockId, Currency, SaitoHash, SaitoPublicKey, Timestamp, LOCK_ORDER_BLOCKCHAIN,
+};
+use crate::common::runtime::RwLock;
+use crate::core::data::block::Block;
+use crate::core::data::blockchain::Blockchain;
+use crate::lock_for_read;
+
+/// Header fields of a longest chain block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockSummary {
+    pub id: BlockId,
+    pub hash: SaitoHash,
+    pub previous_block_hash: SaitoHash,
+    pub timestamp: Timestamp,
+    pub creator: SaitoPublicKey,
+    pub difficulty: u64,
+    pub burnfee: Currency,
+    pub has_golden_ticket: bool,
+}
+
+impl BlockSummary {
+    pub fn new(block: &Block) -> BlockSummary {
+        BlockSummary {
+            id: block.id,
+            hash: block.hash,
+            previous_block_hash: block.previous_block_hash,
+            timestamp: block.timestamp,
+            creator: block.creator,
+            difficulty: block.difficulty,
+            burnfee: block.burnfee,
+            has_golden_ticket: block.has_golden_ticket,
+        }
+    }
+}
+
+/// Read-only view of the longest chain, published by the consensus thread after each blockchain update. Readers
+/// (http endpoints, status files etc.) clone the `Arc` of the latest snapshot instead of taking the blockchain lock, so
+/// they don't wait for or delay the block processing.
+///
+/// Only the block headers are kept. Full blocks are copied out of the blockchain with `read_block`, which holds the
+/// read lock only while copying.
+#[derive(Debug, Clone, Default)]
+pub struct BlockchainSnapshot {
+    pub latest_block_id: BlockId,
+    pub latest_block_hash: SaitoHash,
+    pub genesis_block_id: BlockId,
+    pub fork_id: SaitoHash,
+    /// incremented each time the snapshot is updated
+    pub version: u64,
+    // longest chain blocks by block id
+    longest_chain: BTreeMap<BlockId, Arc<BlockSummary>>,
+    // block ids of the longest chain blocks by block hash
+    block_ids: AHashMap<SaitoHash, BlockId>,
+}
+
+impl BlockchainSnapshot {
+    pub fn new() -> BlockchainSnapshot {
+        Default::default()
+    }
+
+    pub fn get_block_summary(&self, block_hash: &SaitoHash) -> Option<&BlockSummary> {
+        let block_id = self.block_ids.get(block_hash)?;
+        self.get_block_summary_by_id(*block_id)
+    }
+
+    pub fn get_block_summary_by_id(&self, block_id: BlockId) -> Option<&BlockSummary> {
+        self.longest_chain
+            .get(&block_id)
+            .map(|summary| summary.as_ref())
+    }
+
+    pub fn get_block_hash_by_id(&self, block_id: BlockId) -> Option<SaitoHash> {
+        self.get_block_summary_by_id(block_id)
+            .map(|summary| summary.hash)
+    }
+
+    pub fn is_in_longest_chain(&self, block_hash: &SaitoHash) -> bool {
+        self.block_ids.contains_key(block_hash)
+    }
+
+    /// Longest chain blocks in id order, from the given id up to the latest block
+    pub fn iter_longest_chain(
+        &self,
+        from_id: BlockId,
+    ) -> impl DoubleEndedIterator<Item = &BlockSummary> {
+        self.longest_chain
+            .range(from_id..)
+            .map(|(_, summary)| summary.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.longest_chain.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.longest_chain.is_empty()
+    }
+
+    /// Updates the snapshot to the current longest chain. Blocks are read from the latest block back to the first
+    /// block which is already in the snapshot, so only the blocks added since the last update (or replaced by a
+    /// reorg) are read.
+    pub fn update(&mut self, blockchain: &Blockchain) {
+        let latest_block_id = blockchain.get_latest_block_id();
+
+        // blocks after the latest block were rolled back
+        for (_, summary) in self.longest_chain.split_off(&(latest_block_id + 1)) {
+            self.block_ids.remove(&summary.hash);
+        }
+
+        let mut block_id = latest_block_id;
+        let mut updated_count = 0;
+        while block_id > 0 {
+            let block_hash = blockchain
+                .blockring
+                .get_longest_chain_block_hash_by_block_id(block_id);
+            if self.get_block_hash_by_id(block_id) == Some(block_hash) {
+                break;
+            }
+            let block = match blockchain.get_block(&block_hash) {
+                Some(block) => block,
+                None => break,
+            };
+            if let Some(previous) = self
+                .longest_chain
+                .insert(block_id, Arc::new(BlockSummary::new(block)))
+            {
+                self.block_ids.remove(&previous.hash);
+            }
+            self.block_ids.insert(block_hash, block_id);
+            updated_count += 1;
+            block_id -= 1;
+        }
+
+        // blocks before the genesis block are purged from the blockchain
+        let retained = self.longest_chain.split_off(&blockchain.genesis_block_id);
+        for (_, summary) in std::mem::replace(&mut self.longest_chain, retained) {
+            self.block_ids.remove(&summary.hash);
+        }
+
+        self.latest_block_id = latest_block_id;
+        self.latest_block_hash = blockchain.get_latest_block_hash();
+        self.genesis_block_id = blockchain.genesis_block_id;
+        self.fork_id = *blockchain.get_fork_id();
+        self.version += 1;
+        trace!(
+            "blockchain snapshot : {:?} updated with {:?} blocks. latest block : {:?}",
+            self.version,
+            updated_count,
+            latest_block_id
+        );
+    }
+}
+
+/// Copies data out of a block in the blockchain. The read lock is only held while `read` runs, so readers copy what
+/// they need instead of holding the lock while using it. Returns None if the block is not in memory
+pub async fn read_block<R>(
+    blockchain: &RwLock<Blockchain>,
+    block_hash: &SaitoHash,
+    read: impl FnOnce(&Block) -> R,
+) -> Option<R> {
+    let (blockchain, _blockchain_) = lock_for_read!(blockchain, LOCK_ORDER_BLOCKCHAIN);
+    blockchain.get_block(block_hash).map(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::common::defs::{push_lock, LOCK_ORDER_BLOCKCHAIN};
+    use crate::common::test_manager::test::TestManager;
+    use crate::core::data::blockchain_snapshot::{read_block, BlockchainSnapshot};
+    use crate::lock_for_read;
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn blockchain_snapshot_test() {
+        let mut t = TestManager::new();
+        t.initialize_with_timestamp(100, 1_000_000_000, 10_000_000)
+            .await;
+
+        let mut snapshot = Arc::new(BlockchainSnapshot::new());
+        let mut block_hashes = vec![];
+        for _i in 0..3 {
+            let (latest_block_hash, timestamp) = {
+                let (blockchain, _blockchain_) =
+                    lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+                let block = blockchain.get_latest_block().unwrap();
+                (block.hash, block.timestamp)
+            };
+            let mut block = t
+                .create_block(latest_block_hash, timestamp + 120000, 0, 0, 0, true)
+                .await;
+            block.generate();
+            block_hashes.push(block.hash);
+            t.add_block(block).await;
+            let _result = t.receiver_in_miner.try_recv();
+
+            let (blockchain, _blockchain_) =
+                lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            Arc::make_mut(&mut snapshot).update(&blockchain);
+        }
+        assert_eq!(snapshot.latest_block_id, 4);
+        assert_eq!(snapshot.latest_block_hash, block_hashes[2]);
+        assert_eq!(snapshot.len(), 4);
+        assert_eq!(snapshot.version, 3);
+        assert_eq!(snapshot.get_block_summary(&block_hashes[1]).unwrap().id, 3);
+        let ids: Vec<u64> = snapshot
+            .iter_longest_chain(2)
+            .map(|block| block.id)
+            .collect();
+        assert_eq!(ids, vec![2, 3, 4]);
+
+        // readers keep the snapshot they cloned while the chain is updated
+        let old_snapshot = snapshot.clone();
+
+        // longer fork from block 2 replaces blocks 3 and 4
+        let mut parent_hash = block_hashes[0];
+        let mut fork_hashes = vec![];
+        for _i in 0..3 {
+            let timestamp = {
+                let (blockchain, _blockchain_) =
+                    lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+                blockchain.get_block(&parent_hash).unwrap().timestamp
+            };
+            let mut block = t
+                .create_block(parent_hash, timestamp + 130000, 0, 0, 0, true)
+                .await;
+            block.generate();
+            parent_hash = block.hash;
+            fork_hashes.push(block.hash);
+            t.add_block(block).await;
+            let _result = t.receiver_in_miner.try_recv();
+        }
+        {
+            let (blockchain, _blockchain_) =
+                lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            assert_eq!(blockchain.get_latest_block_hash(), fork_hashes[2]);
+            Arc::make_mut(&mut snapshot).update(&blockchain);
+        }
+        assert_eq!(snapshot.latest_block_id, 5);
+        assert_eq!(snapshot.get_block_hash_by_id(2), Some(block_hashes[0]));
+        assert_eq!(snapshot.get_block_hash_by_id(3), Some(fork_hashes[0]));
+        assert_eq!(snapshot.get_block_hash_by_id(5), Some(fork_hashes[2]));
+        assert!(!snapshot.is_in_longest_chain(&block_hashes[1]));
+        assert!(!snapshot.is_in_longest_chain(&block_hashes[2]));
+        assert_eq!(
+            snapshot
+                .get_block_summary_by_id(4)
+                .unwrap()
+                .previous_block_hash,
+            fork_hashes[0]
+        );
+
+        assert_eq!(old_snapshot.latest_block_id, 4);
+        assert_eq!(old_snapshot.get_block_hash_by_id(3), Some(block_hashes[1]));
+
+        let block_id = read_block(&t.blockchain_lock, &fork_hashes[1], |block| block.id).await;
+        assert_eq!(block_id, Some(4));
+        assert!(read_block(&t.blockchain_lock, &[1; 32], |block| block.id)
+            .await
+            .is_none());
+    }
+}
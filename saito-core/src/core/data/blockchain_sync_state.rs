@@ -1,15 +1,18 @@
-use std::cmp::min;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 
-use ahash::HashMap;
+use ahash::{AHashSet, HashMap};
 use tracing::{debug, trace};
 
-use crate::common::defs::{BlockId, PeerIndex, SaitoHash};
+use crate::common::defs::{BlockId, PeerIndex, SaitoHash, Timestamp};
+
+/// block fetches not completed within this time are requested again
+pub const BLOCK_FETCH_TIMEOUT_IN_MS: Timestamp = 30_000;
 
 #[derive(Debug)]
 enum BlockStatus {
     Queued,
-    Fetching,
+    /// time the fetch request was sent
+    Fetching(Timestamp),
     Fetched,
 }
 
@@ -19,6 +22,13 @@ pub struct BlockchainSyncState {
     /// since we are maintaining this state in routing thread and adding to blockchain in other thread, we need to keep a ceiling value for allowed block ids
     block_ceiling: BlockId,
     batch_size: usize,
+    /// max number of blocks requested from a single peer at once
+    max_in_flight_per_peer: usize,
+    fetch_timeout_in_ms: Timestamp,
+    /// fetched blocks waiting for the blocks with lower ids, by block id
+    fetched_blocks: BTreeMap<BlockId, Vec<(PeerIndex, Vec<u8>)>>,
+    /// fetched blocks which were not requested through the sync state
+    unordered_fetched_blocks: Vec<(PeerIndex, Vec<u8>)>,
 }
 
 impl BlockchainSyncState {
@@ -28,6 +38,10 @@ impl BlockchainSyncState {
             blocks_to_fetch: Default::default(),
             block_ceiling: batch_size as BlockId,
             batch_size,
+            max_in_flight_per_peer: batch_size,
+            fetch_timeout_in_ms: BLOCK_FETCH_TIMEOUT_IN_MS,
+            fetched_blocks: Default::default(),
+            unordered_fetched_blocks: vec![],
         }
     }
     pub(crate) fn build_peer_block_picture(&mut self) {
@@ -93,18 +107,13 @@ impl BlockchainSyncState {
         self.blocks_to_fetch.retain(|_, vec| !vec.is_empty());
     }
 
-    pub fn request_blocks_from_waitlist(&mut self) -> HashMap<PeerIndex, Vec<SaitoHash>> {
-        debug!("requesting blocks from waiting list");
-        let mut result: HashMap<u64, Vec<SaitoHash>> = Default::default();
-
-        // for each peer check if we can fetch block
-        for (peer_index, hashes) in self.blocks_to_fetch.iter_mut() {
+    /// Returns the queued hashes of each peer which are within the batch size and below the block ceiling
+    fn get_fetchable_hashes(&self) -> Vec<(PeerIndex, Vec<(SaitoHash, BlockId)>)> {
+        let mut result = vec![];
+        for (peer_index, hashes) in self.blocks_to_fetch.iter() {
+            let mut queued = vec![];
             // check if we have blocks to fetch within our batch size
-            for i in 0..min(hashes.len(), self.batch_size) {
-                // TODO : same block can be fetched from multiple peers as of now. need to define the expected behaviour
-                let (hash, status, block_id) = hashes
-                    .get_mut(i)
-                    .expect("entry should exist since we are checking the length");
+            for (hash, status, block_id) in hashes.iter().take(self.batch_size) {
                 if *block_id > self.block_ceiling {
                     debug!(
                         "block : {:?} - {:?} is above the ceiling : {:?}",
@@ -115,13 +124,7 @@ impl BlockchainSyncState {
                     break;
                 }
                 if let BlockStatus::Queued = status {
-                    debug!(
-                        "block : {:?} : {:?} to be fetched from peer : {:?}",
-                        block_id,
-                        hex::encode(*hash),
-                        peer_index
-                    );
-                    result.entry(*peer_index).or_default().push(*hash);
+                    queued.push((*hash, *block_id));
                 } else {
                     debug!(
                         "block {:?} - {:?} status = {:?}",
@@ -131,11 +134,239 @@ impl BlockchainSyncState {
                     );
                 }
             }
+            if !queued.is_empty() {
+                result.push((*peer_index, queued));
+            }
+        }
+        result
+    }
+
+    /// Selects the blocks to be fetched from each peer. Blocks are assigned in block id order to the peer with the
+    /// lowest latency which has the block and hasn't reached the in-flight limit, so a deep sync is pipelined across
+    /// multiple peers. Peers without a latency measurement are used last.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer_latencies`: measured round trip times of the peers in ms
+    ///
+    /// returns: HashMap<PeerIndex, Vec<SaitoHash>>
+    pub fn request_blocks_from_waitlist(
+        &mut self,
+        peer_latencies: &HashMap<PeerIndex, Timestamp>,
+    ) -> HashMap<PeerIndex, Vec<SaitoHash>> {
+        debug!("requesting blocks from waiting list");
+        let mut result: HashMap<PeerIndex, Vec<SaitoHash>> = Default::default();
+
+        // blocks already being fetched from a peer are not requested again from others
+        let mut fetching_hashes: AHashSet<SaitoHash> = Default::default();
+        let mut in_flight: HashMap<PeerIndex, usize> = Default::default();
+        for (peer_index, hashes) in self.blocks_to_fetch.iter() {
+            for (hash, status, _) in hashes.iter() {
+                if let BlockStatus::Fetching(_) = status {
+                    fetching_hashes.insert(*hash);
+                    *in_flight.entry(*peer_index).or_default() += 1;
+                }
+            }
+        }
+
+        let mut candidates: BTreeMap<(BlockId, SaitoHash), Vec<(Timestamp, PeerIndex)>> =
+            Default::default();
+        for (peer_index, hashes) in self.get_fetchable_hashes() {
+            let latency = peer_latencies
+                .get(&peer_index)
+                .cloned()
+                .unwrap_or(Timestamp::MAX);
+            for (hash, block_id) in hashes {
+                if fetching_hashes.contains(&hash) {
+                    trace!(
+                        "block : {:?} is already being fetched from another peer",
+                        hex::encode(hash)
+                    );
+                    continue;
+                }
+                candidates
+                    .entry((block_id, hash))
+                    .or_default()
+                    .push((latency, peer_index));
+            }
+        }
+
+        for ((block_id, hash), mut peers) in candidates {
+            peers.sort();
+            let selected_peer = peers.iter().find_map(|(_, peer_index)| {
+                let count = in_flight.entry(*peer_index).or_default();
+                if *count >= self.max_in_flight_per_peer {
+                    return None;
+                }
+                *count += 1;
+                Some(*peer_index)
+            });
+            match selected_peer {
+                Some(peer_index) => {
+                    debug!(
+                        "block : {:?} - {:?} to be fetched from peer : {:?}",
+                        block_id,
+                        hex::encode(hash),
+                        peer_index
+                    );
+                    result.entry(peer_index).or_default().push(hash);
+                }
+                None => {
+                    trace!(
+                        "all peers with block : {:?} - {:?} reached the in-flight limit",
+                        block_id,
+                        hex::encode(hash)
+                    );
+                }
+            }
         }
 
         result
     }
-    pub fn mark_as_fetching(&mut self, entries: Vec<(PeerIndex, SaitoHash)>) {
+
+    /// Fetch requests which didn't complete within the timeout are queued again, so they can be fetched from
+    /// another peer. Returns the peers of the stalled requests.
+    pub fn requeue_stalled_fetches(&mut self, current_time: Timestamp) -> Vec<PeerIndex> {
+        let mut stalled_peers = vec![];
+        for (peer_index, hashes) in self.blocks_to_fetch.iter_mut() {
+            for (hash, status, block_id) in hashes.iter_mut() {
+                if let BlockStatus::Fetching(started_at) = status {
+                    if current_time.saturating_sub(*started_at) < self.fetch_timeout_in_ms {
+                        continue;
+                    }
+                    debug!(
+                        "fetching block : {:?} - {:?} from peer : {:?} timed out",
+                        block_id,
+                        hex::encode(*hash),
+                        peer_index
+                    );
+                    *status = BlockStatus::Queued;
+                    stalled_peers.push(*peer_index);
+                }
+            }
+        }
+        stalled_peers.sort();
+        stalled_peers.dedup();
+        stalled_peers
+    }
+
+    /// Queues a block being fetched from the peer again, e.g. after the fetch request timed out. Returns false if the
+    /// block was not being fetched from the peer
+    pub fn requeue_fetch(&mut self, peer_index: PeerIndex, block_hash: SaitoHash) -> bool {
+        let status = self
+            .blocks_to_fetch
+            .get_mut(&peer_index)
+            .and_then(|hashes| hashes.iter_mut().find(|(hash, _, _)| *hash == block_hash))
+            .map(|(_, status, _)| status);
+        match status {
+            Some(status) if matches!(status, BlockStatus::Fetching(_)) => {
+                *status = BlockStatus::Queued;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Keeps a fetched block until the blocks with lower ids are fetched, so they are sent for verification in
+    /// order even when they arrive out of order from multiple peers. Blocks which were not requested by the sync
+    /// are released immediately.
+    pub fn add_fetched_block(
+        &mut self,
+        peer_index: PeerIndex,
+        block_hash: SaitoHash,
+        buffer: Vec<u8>,
+    ) {
+        let block_id = self.blocks_to_fetch.get(&peer_index).and_then(|hashes| {
+            hashes
+                .iter()
+                .find(|(hash, _, _)| *hash == block_hash)
+                .map(|(_, _, block_id)| *block_id)
+        });
+        match block_id {
+            Some(block_id) => {
+                self.fetched_blocks
+                    .entry(block_id)
+                    .or_default()
+                    .push((peer_index, buffer));
+            }
+            None => {
+                self.unordered_fetched_blocks.push((peer_index, buffer));
+            }
+        }
+    }
+
+    /// Returns the fetched blocks which don't have any lower block ids left to be fetched, in block id order
+    pub fn get_blocks_ready_for_verification(&mut self) -> Vec<(PeerIndex, Vec<u8>)> {
+        let lowest_pending_id = self
+            .blocks_to_fetch
+            .values()
+            .flatten()
+            .filter(|(_, status, _)| !matches!(status, BlockStatus::Fetched))
+            .map(|(_, _, block_id)| *block_id)
+            .min();
+        let mut result = std::mem::take(&mut self.unordered_fetched_blocks);
+        let remaining = match lowest_pending_id {
+            Some(block_id) => self.fetched_blocks.split_off(&block_id),
+            None => Default::default(),
+        };
+        let ready = std::mem::replace(&mut self.fetched_blocks, remaining);
+        for (block_id, blocks) in ready {
+            trace!(
+                "releasing {:?} fetched blocks with id : {:?}",
+                blocks.len(),
+                block_id
+            );
+            result.extend(blocks);
+        }
+        result
+    }
+
+    pub fn get_fetched_block_count(&self) -> usize {
+        self.fetched_blocks
+            .values()
+            .map(|blocks| blocks.len())
+            .sum()
+    }
+
+    /// Number of blocks announced by the peers which are not passed to the verification yet. 0 when the node has all
+    /// the blocks it knows about
+    pub fn get_pending_block_count(&self) -> usize {
+        let mut hashes: AHashSet<SaitoHash> = self
+            .received_block_picture
+            .values()
+            .flatten()
+            .map(|(_, hash)| *hash)
+            .collect();
+        hashes.extend(
+            self.blocks_to_fetch
+                .values()
+                .flatten()
+                .filter(|(_, status, _)| !matches!(status, BlockStatus::Fetched))
+                .map(|(hash, _, _)| *hash),
+        );
+        hashes.len() + self.get_fetched_block_count() + self.unordered_fetched_blocks.len()
+    }
+
+    /// Removes the blocks queued for the peer. used when the peer is disconnected
+    pub fn remove_peer(&mut self, peer_index: PeerIndex) {
+        debug!("removing sync state for peer : {:?}", peer_index);
+        self.received_block_picture.remove(&peer_index);
+        self.blocks_to_fetch.remove(&peer_index);
+    }
+
+    pub fn set_max_in_flight_per_peer(&mut self, limit: usize) {
+        self.max_in_flight_per_peer = limit;
+    }
+
+    pub fn set_fetch_timeout(&mut self, timeout_in_ms: Timestamp) {
+        self.fetch_timeout_in_ms = timeout_in_ms;
+    }
+
+    pub fn mark_as_fetching(
+        &mut self,
+        entries: Vec<(PeerIndex, SaitoHash)>,
+        current_time: Timestamp,
+    ) {
         debug!("marking as fetching : {:?}", entries.len());
         for (peer_index, hash) in entries.iter() {
             let res = self.blocks_to_fetch.get_mut(peer_index);
@@ -145,14 +376,20 @@ impl BlockchainSyncState {
             let res = res.unwrap();
             for (block_hash, status, _) in res {
                 if hash.eq(block_hash) {
-                    *status = BlockStatus::Fetching;
+                    *status = BlockStatus::Fetching(current_time);
                     debug!("block : {:?} marked as fetching", hex::encode(block_hash));
                     break;
                 }
             }
         }
     }
-    pub fn mark_as_fetched(&mut self, peer_index: PeerIndex, hash: SaitoHash) {
+    /// Marks the block as fetched. Returns the time taken to fetch the block from the peer if it was requested by us
+    pub fn mark_as_fetched(
+        &mut self,
+        peer_index: PeerIndex,
+        hash: SaitoHash,
+        current_time: Timestamp,
+    ) -> Option<Timestamp> {
         let res = self.blocks_to_fetch.get_mut(&peer_index);
         if res.is_none() {
             debug!(
@@ -160,11 +397,15 @@ impl BlockchainSyncState {
                 hex::encode(hash),
                 peer_index
             );
-            return;
+            return None;
         }
         let res = res.unwrap();
+        let mut fetch_time = None;
         for (block_hash, status, _) in res {
             if hash.eq(block_hash) {
+                if let BlockStatus::Fetching(started_at) = status {
+                    fetch_time = Some(current_time.saturating_sub(*started_at));
+                }
                 *status = BlockStatus::Fetched;
                 debug!(
                     "block : {:?} marked as fetched from peer : {:?}",
@@ -174,7 +415,22 @@ impl BlockchainSyncState {
                 break;
             }
         }
-        self.clean_fetched(peer_index);
+        // the block doesn't need to be fetched from the other peers
+        for (other_peer, hashes) in self.blocks_to_fetch.iter_mut() {
+            if *other_peer == peer_index {
+                continue;
+            }
+            for (block_hash, status, _) in hashes.iter_mut() {
+                if hash.eq(block_hash) {
+                    *status = BlockStatus::Fetched;
+                }
+            }
+        }
+        let peers: Vec<PeerIndex> = self.blocks_to_fetch.keys().cloned().collect();
+        for peer_index in peers {
+            self.clean_fetched(peer_index);
+        }
+        fetch_time
     }
     fn clean_fetched(&mut self, peer_index: PeerIndex) {
         debug!("cleaning fetched : {:?}", peer_index);
@@ -260,7 +516,7 @@ impl BlockchainSyncState {
             }
             let fetching_count = vec
                 .iter()
-                .filter(|(_, status, _)| matches!(status, BlockStatus::Fetching))
+                .filter(|(_, status, _)| matches!(status, BlockStatus::Fetching(_)))
                 .count();
             let stat = format!(
                 "{} - peer : {:?} first: {:?} fetching_count : {:?} ordered_till : {:?} waiting_to_order : {:?}",
@@ -274,13 +530,22 @@ impl BlockchainSyncState {
             stats.push(stat);
         }
         let stat = format!(
-            "{} - block_ceiling : {:?}",
+            "{} - block_ceiling : {:?} buffered_blocks : {:?}",
             format!("{:width$}", "routing:sync_state", width = 40),
-            self.block_ceiling
+            self.block_ceiling,
+            self.get_fetched_block_count()
         );
         stats.push(stat);
         stats
     }
+    /// clears the sync progress. used when the blockchain is rolled back and has to be synced again
+    pub fn reset(&mut self) {
+        self.received_block_picture.clear();
+        self.blocks_to_fetch.clear();
+        self.fetched_blocks.clear();
+        self.unordered_fetched_blocks.clear();
+        self.block_ceiling = self.batch_size as BlockId;
+    }
     pub fn set_latest_blockchain_id(&mut self, id: BlockId) {
         // TODO : batch size should be larger than the fork length diff which can change the current fork.
         // otherwise we won't fetch the blocks for new longest fork until current fork adds new blocks
@@ -294,7 +559,9 @@ impl BlockchainSyncState {
 
 #[cfg(test)]
 mod tests {
-    use crate::common::defs::BlockId;
+    use ahash::HashMap;
+
+    use crate::common::defs::{BlockId, PeerIndex, Timestamp};
     use crate::core::data::blockchain_sync_state::BlockchainSyncState;
 
     #[test]
@@ -308,7 +575,7 @@ mod tests {
         state.add_entry([201; 32], 201, 1);
 
         state.build_peer_block_picture();
-        let mut result = state.request_blocks_from_waitlist();
+        let mut result = state.request_blocks_from_waitlist(&Default::default());
         assert_eq!(result.len(), 1);
         let vec = result.get_mut(&1);
         assert!(vec.is_some());
@@ -319,9 +586,9 @@ mod tests {
             assert_eq!(*entry, [(i + 1) as u8; 32]);
         }
         let vec = vec![(1, [2; 32]), (1, [5; 32])];
-        state.mark_as_fetching(vec);
+        state.mark_as_fetching(vec, 0);
         state.build_peer_block_picture();
-        let mut result = state.request_blocks_from_waitlist();
+        let mut result = state.request_blocks_from_waitlist(&Default::default());
         assert_eq!(result.len(), 1);
         let vec = result.get_mut(&1);
         assert!(vec.is_some());
@@ -334,7 +601,7 @@ mod tests {
 
         state.build_peer_block_picture();
         state.set_latest_blockchain_id(30);
-        let mut result = state.request_blocks_from_waitlist();
+        let mut result = state.request_blocks_from_waitlist(&Default::default());
         assert_eq!(result.len(), 1);
         let vec = result.get_mut(&1);
         assert!(vec.is_some());
@@ -342,6 +609,25 @@ mod tests {
         assert_eq!(vec.len(), state.batch_size - 1);
     }
 
+    #[test]
+    fn pending_block_count_test() {
+        let mut state = BlockchainSyncState::new(10);
+        assert_eq!(state.get_pending_block_count(), 0);
+        state.add_entry([1; 32], 1, 1);
+        state.add_entry([2; 32], 2, 1);
+        // same block announced by another peer
+        state.add_entry([1; 32], 1, 2);
+        assert_eq!(state.get_pending_block_count(), 2);
+
+        state.build_peer_block_picture();
+        assert_eq!(state.get_pending_block_count(), 2);
+        state.mark_as_fetching(vec![(1, [1; 32])], 0);
+        state.mark_as_fetched(1, [1; 32], 10);
+        assert_eq!(state.get_pending_block_count(), 1);
+        state.remove_entry([2; 32], 1);
+        assert_eq!(state.get_pending_block_count(), 0);
+    }
+
     #[test]
     fn fetch_count_test() {
         let mut state = BlockchainSyncState::new(3);
@@ -352,7 +638,7 @@ mod tests {
         state.add_entry([200; 32], 200, 1);
 
         state.build_peer_block_picture();
-        let mut result = state.request_blocks_from_waitlist();
+        let mut result = state.request_blocks_from_waitlist(&Default::default());
         assert_eq!(result.len(), 1);
         let vec = result.get_mut(&1);
         assert!(vec.is_some());
@@ -364,19 +650,19 @@ mod tests {
             assert_eq!(*entry, [(i + 1) as u8; 32]);
         }
         let vec = vec![(1, [1; 32]), (1, [2; 32]), (1, [3; 32])];
-        state.mark_as_fetching(vec);
+        state.mark_as_fetching(vec, 0);
         state.build_peer_block_picture();
-        let result = state.request_blocks_from_waitlist();
+        let result = state.request_blocks_from_waitlist(&Default::default());
         assert_eq!(result.len(), 0);
         state.remove_entry([1; 32], 1);
         state.remove_entry([3; 32], 1);
         state.build_peer_block_picture();
-        let result = state.request_blocks_from_waitlist();
+        let result = state.request_blocks_from_waitlist(&Default::default());
         assert_eq!(result.len(), 0);
 
         state.set_latest_blockchain_id(1);
         state.build_peer_block_picture();
-        let mut result = state.request_blocks_from_waitlist();
+        let mut result = state.request_blocks_from_waitlist(&Default::default());
         assert_eq!(result.len(), 1);
         let vec = result.get_mut(&1).unwrap();
         assert_eq!(vec.len(), 1);
@@ -384,7 +670,7 @@ mod tests {
         state.remove_entry([2; 32], 1);
         state.set_latest_blockchain_id(3);
         state.build_peer_block_picture();
-        let mut result = state.request_blocks_from_waitlist();
+        let mut result = state.request_blocks_from_waitlist(&Default::default());
         assert_eq!(result.len(), 1);
         let vec = result.get_mut(&1).unwrap();
         assert_eq!(vec.len(), 3);
@@ -401,7 +687,7 @@ mod tests {
         }
 
         state.build_peer_block_picture();
-        let mut result = state.request_blocks_from_waitlist();
+        let mut result = state.request_blocks_from_waitlist(&Default::default());
         assert_eq!(result.len(), 1);
         let vec = result.get_mut(&1);
         assert!(vec.is_some());
@@ -425,16 +711,16 @@ mod tests {
             assert_eq!(*entry, [(value + 100) as u8; 32]);
             fetching.push((1, [(value + 100) as u8; 32]));
         }
-        state.mark_as_fetching(fetching);
+        state.mark_as_fetching(fetching, 0);
         state.build_peer_block_picture();
-        let result = state.request_blocks_from_waitlist();
+        let result = state.request_blocks_from_waitlist(&Default::default());
         assert_eq!(result.len(), 0);
 
         state.remove_entry([1; 32], 1);
         state.remove_entry([5; 32], 1);
         state.remove_entry([106; 32], 1);
         state.build_peer_block_picture();
-        let mut result = state.request_blocks_from_waitlist();
+        let mut result = state.request_blocks_from_waitlist(&Default::default());
         assert_eq!(result.len(), 1);
         let vec = result.get_mut(&1).unwrap();
         assert_eq!(vec.len(), 3);
@@ -442,4 +728,139 @@ mod tests {
         assert!(vec.contains(&[108; 32]));
         assert!(vec.contains(&[9; 32]));
     }
+
+    #[test]
+    fn fetch_from_lowest_latency_peer_test() {
+        let mut state = BlockchainSyncState::new(10);
+        for i in 0..5 {
+            state.add_entry([(i + 1) as u8; 32], (i + 1) as BlockId, 1);
+            state.add_entry([(i + 1) as u8; 32], (i + 1) as BlockId, 2);
+        }
+        state.add_entry([6; 32], 6, 1);
+
+        let mut latencies: HashMap<PeerIndex, Timestamp> = Default::default();
+        latencies.insert(1, 300);
+        latencies.insert(2, 100);
+
+        state.build_peer_block_picture();
+        let result = state.request_blocks_from_waitlist(&latencies);
+        assert_eq!(result.len(), 2);
+        assert_eq!(
+            result.get(&2).unwrap(),
+            &vec![[1; 32], [2; 32], [3; 32], [4; 32], [5; 32]]
+        );
+        // only peer 1 has this block
+        assert_eq!(result.get(&1).unwrap(), &vec![[6; 32]]);
+
+        state.mark_as_fetching(vec![(2, [1; 32]), (2, [2; 32])], 1000);
+        // blocks being fetched from peer 2 are not requested from peer 1 even if peer 2 is slower now
+        latencies.insert(2, 500);
+        let result = state.request_blocks_from_waitlist(&latencies);
+        let vec = result.get(&1).unwrap();
+        assert_eq!(vec, &vec![[3; 32], [4; 32], [5; 32], [6; 32]]);
+
+        assert_eq!(state.mark_as_fetched(2, [1; 32], 1250), Some(250));
+        assert_eq!(state.mark_as_fetched(3, [1; 32], 1250), None);
+        state.remove_entry([1; 32], 1);
+
+        // without latency measurements the peer with the lower index is used
+        let result = state.request_blocks_from_waitlist(&Default::default());
+        assert_eq!(result.len(), 1);
+        assert_eq!(
+            result.get(&1).unwrap(),
+            &vec![[3; 32], [4; 32], [5; 32], [6; 32]]
+        );
+    }
+
+    #[test]
+    fn pipelined_fetch_across_peers_test() {
+        let mut state = BlockchainSyncState::new(10);
+        state.set_max_in_flight_per_peer(2);
+        for i in 1..=5 {
+            state.add_entry([i as u8; 32], i as BlockId, 1);
+            state.add_entry([i as u8; 32], i as BlockId, 2);
+        }
+        let mut latencies: HashMap<PeerIndex, Timestamp> = Default::default();
+        latencies.insert(1, 100);
+        latencies.insert(2, 200);
+
+        state.build_peer_block_picture();
+        let result = state.request_blocks_from_waitlist(&latencies);
+        assert_eq!(result.get(&1).unwrap(), &vec![[1; 32], [2; 32]]);
+        assert_eq!(result.get(&2).unwrap(), &vec![[3; 32], [4; 32]]);
+        state.mark_as_fetching(
+            vec![(1, [1; 32]), (1, [2; 32]), (2, [3; 32]), (2, [4; 32])],
+            1000,
+        );
+
+        // both peers are at the limit
+        let result = state.request_blocks_from_waitlist(&latencies);
+        assert!(result.is_empty());
+
+        // blocks received out of order are held until the lower ids are received
+        state.add_fetched_block(2, [3; 32], vec![3]);
+        state.mark_as_fetched(2, [3; 32], 1100);
+        assert!(state.get_blocks_ready_for_verification().is_empty());
+        assert_eq!(state.get_fetched_block_count(), 1);
+
+        state.add_fetched_block(1, [1; 32], vec![1]);
+        state.mark_as_fetched(1, [1; 32], 1100);
+        assert_eq!(
+            state.get_blocks_ready_for_verification(),
+            vec![(1, vec![1])]
+        );
+
+        state.add_fetched_block(1, [2; 32], vec![2]);
+        state.mark_as_fetched(1, [2; 32], 1100);
+        assert_eq!(
+            state.get_blocks_ready_for_verification(),
+            vec![(1, vec![2]), (2, vec![3])]
+        );
+
+        // peer 1 has capacity again
+        let result = state.request_blocks_from_waitlist(&latencies);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result.get(&1).unwrap(), &vec![[5; 32]]);
+
+        // blocks not requested through the sync state are released right away
+        state.add_fetched_block(3, [9; 32], vec![9]);
+        assert_eq!(
+            state.get_blocks_ready_for_verification(),
+            vec![(3, vec![9])]
+        );
+    }
+
+    #[test]
+    fn requeue_stalled_fetches_test() {
+        let mut state = BlockchainSyncState::new(10);
+        state.set_fetch_timeout(1000);
+        state.add_entry([1; 32], 1, 1);
+        state.add_entry([1; 32], 1, 2);
+        state.add_entry([2; 32], 2, 2);
+        let mut latencies: HashMap<PeerIndex, Timestamp> = Default::default();
+        latencies.insert(1, 100);
+        latencies.insert(2, 200);
+
+        state.build_peer_block_picture();
+        let result = state.request_blocks_from_waitlist(&latencies);
+        assert_eq!(result.get(&1).unwrap(), &vec![[1; 32]]);
+        state.mark_as_fetching(vec![(1, [1; 32]), (2, [2; 32])], 1000);
+
+        assert!(state.requeue_stalled_fetches(1999).is_empty());
+        assert!(state.requeue_fetch(1, [1; 32]));
+        assert!(!state.requeue_fetch(1, [1; 32]));
+        assert_eq!(state.requeue_stalled_fetches(2000), vec![2]);
+        state.mark_as_fetching(vec![(1, [1; 32])], 1000);
+        assert_eq!(state.requeue_stalled_fetches(2000), vec![1]);
+
+        // peer 1 is slower now, so the block is fetched from peer 2
+        latencies.insert(1, 1000);
+        let result = state.request_blocks_from_waitlist(&latencies);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result.get(&2).unwrap(), &vec![[1; 32], [2; 32]]);
+
+        state.remove_peer(2);
+        let result = state.request_blocks_from_waitlist(&latencies);
+        assert_eq!(result.get(&1).unwrap(), &vec![[1; 32]]);
+    }
 }
@@ -56,6 +56,16 @@ impl BlockRing {
         self.ring[(insert_pos as usize)].contains_block_hash(block_hash)
     }
 
+    /// number of blocks with the given id, including the blocks not in the longest chain
+    pub fn get_block_count_at_block_id(&self, block_id: u64) -> usize {
+        let insert_pos = block_id % RING_BUFFER_LENGTH;
+        self.ring[(insert_pos as usize)]
+            .block_ids
+            .iter()
+            .filter(|id| **id == block_id)
+            .count()
+    }
+
     #[tracing::instrument(level = "info", skip_all)]
     pub fn get_latest_block_hash(&self) -> SaitoHash {
         match self.lc_pos {
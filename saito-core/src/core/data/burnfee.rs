@@ -60,6 +60,33 @@ impl BurnFee {
         (work_needed_float * 100_000_000.0).round() as Currency
     }
 
+    /// Returns the earliest timestamp at which the given routing work is enough to produce a block on top of the
+    /// previous block. This is the inverse of `return_routing_work_needed_to_produce_block_in_nolan`. Work needed
+    /// falls to zero after two heartbeats, so the result is never later than that.
+    ///
+    /// * `burn_fee_previous_block` - burn fee of the previous block
+    /// * `routing_work_available` - routing work of the transactions to bundle
+    /// * `previous_block_timestamp` - timestamp of previous block
+    ///
+    pub fn return_timestamp_when_routing_work_is_sufficient(
+        burn_fee_previous_block: Currency,
+        routing_work_available: Currency,
+        previous_block_timestamp_in_ms: Timestamp,
+    ) -> Timestamp {
+        let max_elapsed_time = 2 * HEARTBEAT;
+        let elapsed_time = if burn_fee_previous_block == 0 {
+            1
+        } else if routing_work_available == 0 {
+            max_elapsed_time
+        } else {
+            // rounded up so the work needed at the returned time is not more than the available work
+            let elapsed_time = burn_fee_previous_block / routing_work_available
+                + Currency::from(burn_fee_previous_block % routing_work_available != 0);
+            elapsed_time.clamp(1, max_elapsed_time as Currency) as Timestamp
+        };
+        previous_block_timestamp_in_ms + elapsed_time
+    }
+
     /// Returns an adjusted burnfee based on the start value provided
     /// and the difference between the current block timestamp and the
     /// previous block timestamp
@@ -120,6 +147,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn burnfee_return_timestamp_when_work_is_sufficient_test() {
+        let previous_block_timestamp = 1_000_000;
+        let burn_fee = 100_000_000;
+        for work_available in [1, 30_000, 333_333, 1_000_000, 100_000_000, 500_000_000] {
+            let timestamp = BurnFee::return_timestamp_when_routing_work_is_sufficient(
+                burn_fee,
+                work_available,
+                previous_block_timestamp,
+            );
+            assert!(timestamp > previous_block_timestamp);
+            assert!(timestamp <= previous_block_timestamp + 2 * HEARTBEAT);
+            assert!(
+                BurnFee::return_routing_work_needed_to_produce_block_in_nolan(
+                    burn_fee,
+                    timestamp,
+                    previous_block_timestamp
+                ) <= work_available
+            );
+            if timestamp > previous_block_timestamp + 1 {
+                assert!(
+                    BurnFee::return_routing_work_needed_to_produce_block_in_nolan(
+                        burn_fee,
+                        timestamp - 2,
+                        previous_block_timestamp
+                    ) > work_available
+                );
+            }
+        }
+        assert_eq!(
+            BurnFee::return_timestamp_when_routing_work_is_sufficient(burn_fee, 0, 0),
+            2 * HEARTBEAT
+        );
+        assert_eq!(
+            BurnFee::return_timestamp_when_routing_work_is_sufficient(0, 0, 10),
+            11
+        );
+    }
+
     #[test]
     fn burnfee_burn_fee_adjustment_test() {
         // if the difference in timestamps is equal to HEARTBEAT, our start value should not change
@@ -0,0 +1,261 @@
+use std::fmt::Write;
+
+use crate::common::defs::{BlockId, Currency};
+use crate::core::data::block::Block;
+use crate::core::data::slip::Slip;
+
+/// version of the exported columns. increased when a column is added, removed or changes meaning
+pub const CHAIN_EXPORT_SCHEMA_VERSION: u32 = 1;
+
+pub const BLOCKS_FILE_NAME: &str = "blocks.csv";
+pub const TRANSACTIONS_FILE_NAME: &str = "transactions.csv";
+pub const SLIPS_FILE_NAME: &str = "slips.csv";
+pub const HOPS_FILE_NAME: &str = "hops.csv";
+pub const MANIFEST_FILE_NAME: &str = "manifest.csv";
+
+/// Blocks, transactions, slips and routing hops of a block range as csv tables, one row per item. Rows of the
+/// transactions, slips and hops reference their block with the block id and their transaction with its index in the
+/// block, so the tables can be joined without parsing the block files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainExport {
+    pub from_block_id: BlockId,
+    pub to_block_id: BlockId,
+    pub blocks: String,
+    pub transactions: String,
+    pub slips: String,
+    pub hops: String,
+    pub block_count: u64,
+    pub transaction_count: u64,
+    pub slip_count: u64,
+    pub hop_count: u64,
+}
+
+impl ChainExport {
+    /// `blocks` are consecutive longest chain blocks, oldest first. Blocks should be generated before calling this,
+    /// since the fees of the transactions are set when generating.
+    pub fn generate(blocks: &[Block]) -> ChainExport {
+        let mut export = ChainExport {
+            from_block_id: blocks.first().map(|block| block.id).unwrap_or_default(),
+            to_block_id: blocks.last().map(|block| block.id).unwrap_or_default(),
+            ..Default::default()
+        };
+        writeln!(
+            export.blocks,
+            "block_id,hash,previous_block_hash,timestamp,creator,burnfee,difficulty,treasury,staking_treasury,\
+            transaction_count,total_fees,total_work"
+        )
+        .unwrap();
+        writeln!(
+            export.transactions,
+            "block_id,transaction_index,signature,transaction_type,timestamp,input_count,output_count,total_in,\
+            total_out,total_fees,message_size,hop_count"
+        )
+        .unwrap();
+        writeln!(
+            export.slips,
+            "block_id,transaction_index,is_input,slip_index,public_key,amount,slip_type,source_block_id,\
+            source_tx_ordinal"
+        )
+        .unwrap();
+        writeln!(
+            export.hops,
+            "block_id,transaction_index,hop_index,from,to,signature"
+        )
+        .unwrap();
+
+        for block in blocks.iter() {
+            export.add_block(block);
+        }
+        export
+    }
+
+    fn add_block(&mut self, block: &Block) {
+        let total_fees: Currency = block
+            .transactions
+            .iter()
+            .map(|transaction| transaction.total_fees)
+            .sum();
+        writeln!(
+            self.blocks,
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            block.id,
+            hex::encode(block.hash),
+            hex::encode(block.previous_block_hash),
+            block.timestamp,
+            hex::encode(block.creator),
+            block.burnfee,
+            block.difficulty,
+            block.treasury,
+            block.staking_treasury,
+            block.transactions.len(),
+            total_fees,
+            block.total_work
+        )
+        .unwrap();
+        self.block_count += 1;
+
+        for (index, transaction) in block.transactions.iter().enumerate() {
+            let path = transaction.get_path();
+            writeln!(
+                self.transactions,
+                "{},{},{},{:?},{},{},{},{},{},{},{},{}",
+                block.id,
+                index,
+                hex::encode(transaction.signature),
+                transaction.transaction_type,
+                transaction.timestamp,
+                transaction.inputs.len(),
+                transaction.outputs.len(),
+                transaction.total_in,
+                transaction.total_out,
+                transaction.total_fees,
+                transaction.message.len(),
+                path.len()
+            )
+            .unwrap();
+            self.transaction_count += 1;
+
+            for input in transaction.inputs.iter() {
+                self.add_slip(block.id, index, true, input);
+            }
+            for output in transaction.outputs.iter() {
+                self.add_slip(block.id, index, false, output);
+            }
+            for (hop_index, hop) in path.iter().enumerate() {
+                writeln!(
+                    self.hops,
+                    "{},{},{},{},{},{}",
+                    block.id,
+                    index,
+                    hop_index,
+                    hex::encode(hop.from),
+                    hex::encode(hop.to),
+                    hex::encode(hop.sig)
+                )
+                .unwrap();
+                self.hop_count += 1;
+            }
+        }
+    }
+
+    /// block id and tx ordinal of an input point to the transaction which created the spent slip
+    fn add_slip(
+        &mut self,
+        block_id: BlockId,
+        transaction_index: usize,
+        is_input: bool,
+        slip: &Slip,
+    ) {
+        writeln!(
+            self.slips,
+            "{},{},{},{},{},{},{:?},{},{}",
+            block_id,
+            transaction_index,
+            is_input,
+            slip.slip_index,
+            hex::encode(slip.public_key),
+            slip.amount,
+            slip.slip_type,
+            slip.block_id,
+            slip.tx_ordinal
+        )
+        .unwrap();
+        self.slip_count += 1;
+    }
+
+    /// schema version, block range and the row counts of the tables
+    pub fn get_manifest(&self) -> String {
+        let mut csv = String::new();
+        writeln!(csv, "metric,value").unwrap();
+        writeln!(csv, "schema_version,{}", CHAIN_EXPORT_SCHEMA_VERSION).unwrap();
+        writeln!(csv, "from_block_id,{}", self.from_block_id).unwrap();
+        writeln!(csv, "to_block_id,{}", self.to_block_id).unwrap();
+        writeln!(csv, "block_count,{}", self.block_count).unwrap();
+        writeln!(csv, "transaction_count,{}", self.transaction_count).unwrap();
+        writeln!(csv, "slip_count,{}", self.slip_count).unwrap();
+        writeln!(csv, "hop_count,{}", self.hop_count).unwrap();
+        csv
+    }
+
+    /// file names and contents of the tables and the manifest
+    pub fn get_files(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (BLOCKS_FILE_NAME, self.blocks.clone()),
+            (TRANSACTIONS_FILE_NAME, self.transactions.clone()),
+            (SLIPS_FILE_NAME, self.slips.clone()),
+            (HOPS_FILE_NAME, self.hops.clone()),
+            (MANIFEST_FILE_NAME, self.get_manifest()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::block::Block;
+    use crate::core::data::chain_export::{ChainExport, MANIFEST_FILE_NAME};
+    use crate::core::data::crypto::generate_keys;
+    use crate::core::data::slip::Slip;
+    use crate::core::data::transaction::Transaction;
+
+    #[test]
+    fn chain_export_test() {
+        let (sender, sender_private_key) = generate_keys();
+        let (router, _) = generate_keys();
+
+        let mut transaction = Transaction::default();
+        let mut input = Slip::default();
+        input.public_key = sender;
+        input.amount = 1000;
+        input.block_id = 1;
+        transaction.add_input(input);
+        let mut output = Slip::default();
+        output.public_key = router;
+        output.amount = 900;
+        transaction.add_output(output);
+        transaction.message = vec![1; 10];
+        transaction.sign(&sender_private_key);
+        transaction.add_hop(&sender_private_key, &sender, &router);
+
+        let mut block_1 = Block::new();
+        block_1.id = 1;
+        let mut block_2 = Block::new();
+        block_2.id = 2;
+        block_2.transactions = vec![transaction];
+        block_2.generate();
+
+        let export = ChainExport::generate(&[block_1, block_2]);
+        assert_eq!(export.from_block_id, 1);
+        assert_eq!(export.to_block_id, 2);
+        assert_eq!(export.block_count, 2);
+        assert_eq!(export.transaction_count, 1);
+        assert_eq!(export.slip_count, 2);
+        assert_eq!(export.hop_count, 1);
+
+        assert_eq!(export.blocks.lines().count(), 3);
+        assert!(export
+            .transactions
+            .lines()
+            .nth(1)
+            .unwrap()
+            .ends_with(",Normal,0,1,1,1000,900,100,10,1"));
+        assert!(export.slips.contains(&format!(
+            "2,0,true,0,{},1000,Normal,1,0\n",
+            hex::encode(sender)
+        )));
+        assert!(export.hops.contains(&format!(
+            "2,0,0,{},{},",
+            hex::encode(sender),
+            hex::encode(router)
+        )));
+
+        let files = export.get_files();
+        assert_eq!(files.len(), 5);
+        let manifest = &files
+            .iter()
+            .find(|(name, _)| *name == MANIFEST_FILE_NAME)
+            .unwrap()
+            .1;
+        assert!(manifest.contains("schema_version,1\n"));
+        assert!(manifest.contains("slip_count,2\n"));
+    }
+}
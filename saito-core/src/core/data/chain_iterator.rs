@@ -0,0 +1,197 @@
+use crate::common::defs::{BlockId, SaitoHash};
+use crate::core::data::block::Block;
+use crate::core::data::blockchain::Blockchain;
+
+/// Longest chain blocks in id order. Created with `Blockchain::iter_longest_chain`.
+///
+/// Ids without a block in memory (e.g. before the genesis block of a node which synced from a snapshot) are skipped.
+/// The range is fixed when the iterator is created.
+pub struct LongestChainIter<'a> {
+    blockchain: &'a Blockchain,
+    next_id: BlockId,
+    last_id: BlockId,
+}
+
+impl<'a> LongestChainIter<'a> {
+    pub(crate) fn new(blockchain: &'a Blockchain, from_id: BlockId, to_id: BlockId) -> Self {
+        LongestChainIter {
+            blockchain,
+            // block ids start from 1
+            next_id: from_id.max(1),
+            last_id: to_id,
+        }
+    }
+
+    fn get_block(&self, block_id: BlockId) -> Option<&'a Block> {
+        let block_hash = self
+            .blockchain
+            .blockring
+            .get_longest_chain_block_hash_by_block_id(block_id);
+        if block_hash == [0; 32] {
+            return None;
+        }
+        self.blockchain.blocks.get(&block_hash)
+    }
+}
+
+impl<'a> Iterator for LongestChainIter<'a> {
+    type Item = &'a Block;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next_id <= self.last_id {
+            let block_id = self.next_id;
+            self.next_id += 1;
+            if let Some(block) = self.get_block(block_id) {
+                return Some(block);
+            }
+        }
+        None
+    }
+}
+
+impl<'a> DoubleEndedIterator for LongestChainIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while self.next_id <= self.last_id {
+            let block_id = self.last_id;
+            self.last_id -= 1;
+            if let Some(block) = self.get_block(block_id) {
+                return Some(block);
+            }
+        }
+        None
+    }
+}
+
+/// Blocks of a fork from its tip back to the shared ancestor with the longest chain, newest first. The ancestor
+/// itself is not yielded. Created with `Blockchain::iter_fork`.
+///
+/// Blocks of the longest chain yield nothing. Iteration stops early at a block which is not in memory, in which case
+/// `get_shared_ancestor` returns None.
+pub struct ForkIter<'a> {
+    blockchain: &'a Blockchain,
+    next_hash: SaitoHash,
+    shared_ancestor: Option<SaitoHash>,
+}
+
+impl<'a> ForkIter<'a> {
+    pub(crate) fn new(blockchain: &'a Blockchain, tip_hash: SaitoHash) -> Self {
+        ForkIter {
+            blockchain,
+            next_hash: tip_hash,
+            shared_ancestor: None,
+        }
+    }
+
+    /// hash of the longest chain block the fork branched off from. set once the iteration reaches it
+    pub fn get_shared_ancestor(&self) -> Option<SaitoHash> {
+        self.shared_ancestor
+    }
+}
+
+impl<'a> Iterator for ForkIter<'a> {
+    type Item = &'a Block;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_hash == [0; 32] {
+            return None;
+        }
+        let block = match self.blockchain.blocks.get(&self.next_hash) {
+            Some(block) => block,
+            None => {
+                self.next_hash = [0; 32];
+                return None;
+            }
+        };
+        if block.in_longest_chain {
+            self.shared_ancestor = Some(block.hash);
+            self.next_hash = [0; 32];
+            return None;
+        }
+        self.next_hash = block.previous_block_hash;
+        Some(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::defs::{push_lock, LOCK_ORDER_BLOCKCHAIN};
+    use crate::common::test_manager::test::TestManager;
+    use crate::lock_for_read;
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn chain_iterator_test() {
+        let mut t = TestManager::new();
+        t.initialize_with_timestamp(100, 1_000_000_000, 10_000_000)
+            .await;
+
+        let mut block_hashes = vec![];
+        for _i in 0..3 {
+            let (latest_block_hash, timestamp) = {
+                let (blockchain, _blockchain_) =
+                    lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+                let block = blockchain.get_latest_block().unwrap();
+                (block.hash, block.timestamp)
+            };
+            let mut block = t
+                .create_block(latest_block_hash, timestamp + 120000, 0, 0, 0, true)
+                .await;
+            block.generate();
+            block_hashes.push(block.hash);
+            t.add_block(block).await;
+            let _result = t.receiver_in_miner.try_recv();
+        }
+
+        // fork of the same length from block 2 doesn't replace the longest chain
+        let fork_parent_timestamp = {
+            let (blockchain, _blockchain_) =
+                lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+            blockchain.get_block(&block_hashes[0]).unwrap().timestamp
+        };
+        let mut fork_block = t
+            .create_block(
+                block_hashes[0],
+                fork_parent_timestamp + 130000,
+                0,
+                0,
+                0,
+                true,
+            )
+            .await;
+        fork_block.generate();
+        let fork_block_hash = fork_block.hash;
+        t.add_block(fork_block).await;
+
+        let (blockchain, _blockchain_) = lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+        assert_eq!(blockchain.get_latest_block_id(), 4);
+
+        let ids: Vec<u64> = blockchain
+            .iter_longest_chain(0)
+            .map(|block| block.id)
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+        let ids: Vec<u64> = blockchain
+            .iter_longest_chain(3)
+            .rev()
+            .map(|block| block.id)
+            .collect();
+        assert_eq!(ids, vec![4, 3]);
+        assert_eq!(blockchain.iter_longest_chain(5).count(), 0);
+
+        let mut fork = blockchain.iter_fork(fork_block_hash);
+        assert_eq!(fork.next().unwrap().hash, fork_block_hash);
+        assert!(fork.next().is_none());
+        assert_eq!(fork.get_shared_ancestor(), Some(block_hashes[0]));
+
+        let mut fork = blockchain.iter_fork(blockchain.get_latest_block_hash());
+        assert!(fork.next().is_none());
+        assert_eq!(
+            fork.get_shared_ancestor(),
+            Some(blockchain.get_latest_block_hash())
+        );
+
+        let mut fork = blockchain.iter_fork([1; 32]);
+        assert!(fork.next().is_none());
+        assert_eq!(fork.get_shared_ancestor(), None);
+    }
+}
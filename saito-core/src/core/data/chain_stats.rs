@@ -0,0 +1,316 @@
+use std::collections::VecDeque;
+
+use rayon::prelude::*;
+use tracing::{debug, trace};
+
+use crate::common::defs::{BlockId, Currency, SaitoHash, Timestamp, UtxoSet};
+use crate::core::data::block::Block;
+use crate::core::data::slip::Slip;
+use crate::core::data::transaction::TransactionType;
+
+/// number of longest chain blocks to keep the stats for
+pub const DEFAULT_CHAIN_STATS_RETENTION: usize = 10_000;
+/// number of latest block intervals used for the rolling average block time
+pub const BLOCK_TIME_AVERAGE_WINDOW: usize = 100;
+
+/// Token supply and fee totals of the longest chain, as of a single block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockChainStats {
+    pub block_id: BlockId,
+    pub block_hash: SaitoHash,
+    pub timestamp: Timestamp,
+    /// tokens in spendable slips
+    pub circulating_supply: Currency,
+    pub treasury: Currency,
+    pub staking_treasury: Currency,
+    /// fees paid by the transactions in this block
+    pub block_fees: Currency,
+    /// fees paid out to routers and stakers by this block's fee transaction
+    pub block_payouts: Currency,
+    /// fees paid since the first tracked block
+    pub total_fees_paid: Currency,
+    /// payouts since the first tracked block
+    pub total_payouts: Currency,
+}
+
+impl BlockChainStats {
+    /// circulating supply including the tokens held by the treasuries
+    pub fn get_total_supply(&self) -> Currency {
+        self.circulating_supply + self.treasury + self.staking_treasury
+    }
+}
+
+/// Accumulates the token supply and fee totals as blocks are added to or removed from the longest chain.
+/// Stats are calculated incrementally from the previous block, so they are only recalculated from the utxoset
+/// when there is no previous entry (e.g. when the node starts).
+#[derive(Debug)]
+pub struct ChainStats {
+    // longest chain blocks, oldest first
+    history: VecDeque<BlockChainStats>,
+    // 0 means no limit
+    retention: usize,
+}
+
+impl ChainStats {
+    pub fn new(retention: usize) -> ChainStats {
+        ChainStats {
+            history: Default::default(),
+            retention,
+        }
+    }
+
+    pub fn set_retention(&mut self, retention: usize) {
+        self.retention = retention;
+        self.apply_retention();
+    }
+
+    fn apply_retention(&mut self) {
+        if self.retention == 0 {
+            return;
+        }
+        while self.history.len() > self.retention {
+            self.history.pop_front();
+        }
+    }
+
+    /// Returns the (outputs, inputs, payouts) of the block. Inputs of the fee transaction are taken from the
+    /// staking treasury, so they are not removed from the circulating supply.
+    fn get_block_totals(block: &Block) -> (Currency, Currency, Currency) {
+        let mut outputs: Currency = 0;
+        let mut inputs: Currency = 0;
+        let mut payouts: Currency = 0;
+        for tx in block.transactions.iter() {
+            let tx_outputs: Currency = tx.outputs.iter().map(|slip| slip.amount).sum();
+            if tx.transaction_type == TransactionType::Fee {
+                payouts += tx_outputs;
+            } else {
+                inputs += tx.inputs.iter().map(|slip| slip.amount).sum::<Currency>();
+            }
+            outputs += tx_outputs;
+        }
+        (outputs, inputs, payouts)
+    }
+
+    /// Should be called after the block is added to the longest chain and the utxoset is updated
+    pub fn on_block_added(&mut self, block: &Block, utxoset: &UtxoSet) {
+        let (outputs, inputs, payouts) = Self::get_block_totals(block);
+        let previous = self
+            .history
+            .back()
+            .filter(|stats| stats.block_hash == block.previous_block_hash);
+
+        let stats = match previous {
+            Some(previous) => BlockChainStats {
+                block_id: block.id,
+                block_hash: block.hash,
+                timestamp: block.timestamp,
+                circulating_supply: (previous.circulating_supply + outputs).saturating_sub(inputs),
+                treasury: block.treasury,
+                staking_treasury: block.staking_treasury,
+                block_fees: block.get_total_fees(),
+                block_payouts: payouts,
+                total_fees_paid: previous.total_fees_paid + block.get_total_fees(),
+                total_payouts: previous.total_payouts + payouts,
+            },
+            None => {
+                debug!(
+                    "calculating chain stats from utxoset for block : {:?} - {:?}",
+                    block.id,
+                    hex::encode(block.hash)
+                );
+                self.history.clear();
+                BlockChainStats {
+                    block_id: block.id,
+                    block_hash: block.hash,
+                    timestamp: block.timestamp,
+                    circulating_supply: Self::get_utxoset_supply(utxoset),
+                    treasury: block.treasury,
+                    staking_treasury: block.staking_treasury,
+                    block_fees: block.get_total_fees(),
+                    block_payouts: payouts,
+                    total_fees_paid: block.get_total_fees(),
+                    total_payouts: payouts,
+                }
+            }
+        };
+        trace!("chain stats updated : {:?}", stats);
+        self.history.push_back(stats);
+        self.apply_retention();
+    }
+
+    /// Should be called when the block is removed from the longest chain
+    pub fn on_block_removed(&mut self, block: &Block) {
+        match self.history.back() {
+            Some(stats) if stats.block_hash == block.hash => {
+                self.history.pop_back();
+            }
+            _ => {
+                // stats will be recalculated from the utxoset when the next block is added
+                debug!(
+                    "block : {:?} is not the latest in chain stats. clearing the history",
+                    hex::encode(block.hash)
+                );
+                self.history.clear();
+            }
+        }
+    }
+
+    fn get_utxoset_supply(utxoset: &UtxoSet) -> Currency {
+        utxoset
+            .par_iter()
+            .filter(|(_, spendable)| **spendable)
+            .map(|(key, _)| Slip::get_amount_from_utxoset_key(key))
+            .sum()
+    }
+
+    pub fn get_latest(&self) -> Option<&BlockChainStats> {
+        self.history.back()
+    }
+
+    pub fn get_by_block_id(&self, block_id: BlockId) -> Option<&BlockChainStats> {
+        let first = self.history.front()?;
+        if block_id < first.block_id {
+            return None;
+        }
+        self.history
+            .get((block_id - first.block_id) as usize)
+            .filter(|stats| stats.block_id == block_id)
+    }
+
+    /// time between the latest block and its parent
+    pub fn get_latest_block_interval(&self) -> Option<Timestamp> {
+        let mut blocks = self.history.iter().rev();
+        let latest = blocks.next()?;
+        let previous = blocks.next()?;
+        Some(latest.timestamp.saturating_sub(previous.timestamp))
+    }
+
+    /// Rolling average of the intervals between the latest `window` blocks. Fewer intervals are used if not
+    /// enough blocks are retained
+    pub fn get_average_block_time(&self, window: usize) -> Option<Timestamp> {
+        let intervals = window.min(self.history.len().saturating_sub(1));
+        if intervals == 0 {
+            return None;
+        }
+        let latest = self.history.back()?;
+        let first = self.history.get(self.history.len() - 1 - intervals)?;
+        Some(latest.timestamp.saturating_sub(first.timestamp) / intervals as Timestamp)
+    }
+
+    /// Intervals between the latest `window` + 1 blocks, oldest first. Fewer intervals are returned if not enough
+    /// blocks are retained
+    pub fn get_latest_block_intervals(&self, window: usize) -> Vec<Timestamp> {
+        let skip = self.history.len().saturating_sub(window + 1);
+        let timestamps: Vec<Timestamp> = self
+            .history
+            .iter()
+            .skip(skip)
+            .map(|stats| stats.timestamp)
+            .collect();
+        timestamps
+            .windows(2)
+            .map(|pair| pair[1].saturating_sub(pair[0]))
+            .collect()
+    }
+
+    /// Returns the stats of the retained blocks, oldest first
+    pub fn get_history(&self) -> impl Iterator<Item = &BlockChainStats> {
+        self.history.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::defs::UtxoSet;
+    use crate::core::data::block::Block;
+    use crate::core::data::chain_stats::ChainStats;
+    use crate::core::data::slip::Slip;
+    use crate::core::data::transaction::{Transaction, TransactionType};
+
+    fn create_block(id: u64, previous_block_hash: [u8; 32], txs: Vec<Transaction>) -> Block {
+        let mut block = Block::new();
+        block.id = id;
+        block.hash = [id as u8; 32];
+        block.previous_block_hash = previous_block_hash;
+        block.treasury = 100 * id as u128;
+        block.timestamp = 1000 * id * id;
+        block.transactions = txs;
+        block
+    }
+
+    fn create_tx(inputs: Vec<u128>, outputs: Vec<u128>) -> Transaction {
+        let mut tx = Transaction::default();
+        for amount in inputs {
+            let mut slip = Slip::default();
+            slip.amount = amount;
+            tx.add_input(slip);
+        }
+        for amount in outputs {
+            let mut slip = Slip::default();
+            slip.amount = amount;
+            tx.add_output(slip);
+        }
+        tx
+    }
+
+    #[test]
+    fn chain_stats_test() {
+        let mut utxoset = UtxoSet::new();
+        let mut slip = Slip::default();
+        slip.amount = 1000;
+        slip.generate_utxoset_key();
+        utxoset.insert(slip.utxoset_key, true);
+        slip.amount = 500;
+        slip.generate_utxoset_key();
+        utxoset.insert(slip.utxoset_key, false);
+
+        let mut stats = ChainStats::new(2);
+        let block1 = create_block(1, [0; 32], vec![create_tx(vec![], vec![1000])]);
+        stats.on_block_added(&block1, &utxoset);
+        assert_eq!(stats.get_latest().unwrap().circulating_supply, 1000);
+        assert_eq!(stats.get_latest().unwrap().get_total_supply(), 1100);
+
+        let mut fee_tx = create_tx(vec![50], vec![30]);
+        fee_tx.transaction_type = TransactionType::Fee;
+        let block2 = create_block(
+            2,
+            block1.hash,
+            vec![create_tx(vec![400], vec![390]), fee_tx],
+        );
+        stats.on_block_added(&block2, &utxoset);
+        let latest = stats.get_latest().unwrap();
+        assert_eq!(latest.circulating_supply, 1020);
+        assert_eq!(latest.block_payouts, 30);
+        assert_eq!(latest.total_payouts, 30);
+        assert_eq!(stats.get_by_block_id(1).unwrap().block_hash, block1.hash);
+
+        let block3 = create_block(3, block2.hash, vec![create_tx(vec![20], vec![20])]);
+        stats.on_block_added(&block3, &utxoset);
+        assert_eq!(stats.len(), 2);
+        assert!(stats.get_by_block_id(1).is_none());
+        assert_eq!(stats.get_by_block_id(3).unwrap().circulating_supply, 1020);
+        assert_eq!(stats.get_latest_block_interval(), Some(5000));
+        assert_eq!(stats.get_average_block_time(1), Some(5000));
+        // only 1 interval is retained
+        assert_eq!(stats.get_average_block_time(10), Some(5000));
+
+        stats.on_block_removed(&block3);
+        assert_eq!(stats.get_latest().unwrap().block_id, 2);
+
+        // a block not following the latest entry is recalculated from the utxoset
+        let block4 = create_block(4, [9; 32], vec![]);
+        stats.on_block_added(&block4, &utxoset);
+        assert_eq!(stats.len(), 1);
+        assert!(stats.get_latest_block_interval().is_none());
+        assert!(stats.get_average_block_time(10).is_none());
+        assert_eq!(stats.get_latest().unwrap().circulating_supply, 1000);
+    }
+}
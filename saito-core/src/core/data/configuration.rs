@@ -1,31 +1,331 @@
 use serde::Deserialize;
 
+use crate::core::data::audit_log::AuditLogConfig;
+use crate::core::data::consensus_health::ConsensusHealthConfig;
+use crate::core::data::network_preset::NetworkPreset;
+use crate::core::data::peer_quorum::PeerQuorumConfig;
+use crate::core::data::trusted_sync::TrustedSyncConfig;
+use crate::core::data::tx_generator::TxGeneratorConfig;
+use crate::core::data::webhook::WebhookConfig;
+
 #[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct PeerConfig {
     pub host: String,
     pub port: u16,
     pub protocol: String,
     pub synctype: String,
+    /// websocket scheme ("ws" or "wss") to connect with. derived from the protocol if not set
+    #[serde(default)]
+    pub scheme: Option<String>,
+    /// expected public key of the peer (hex). pinned after the first handshake if not set. handshakes with another
+    /// key are rejected unless the peer presents a key rotation proof
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+impl PeerConfig {
+    pub fn get_websocket_scheme(&self) -> &str {
+        match self.scheme.as_deref() {
+            Some(scheme) => scheme,
+            None if self.protocol == "https" => "wss",
+            None => "ws",
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Endpoint {
     pub host: String,
+    /// port of the network preset if not set
+    #[serde(default)]
     pub port: u16,
     pub protocol: String,
 }
 
+impl Endpoint {
+    /// Address the node is reachable at. Named in the handshake challenges sent to the peers
+    pub fn get_address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// Defines which parts of the node's work are enabled.
+///
+/// A router only validates and relays blocks and transactions. It doesn't scan blocks for wallet slips,
+/// mine golden tickets or produce transactions, which keeps the per block cost low for relay nodes.
+///
+/// A full node also tracks its wallet, produces transactions and bundles blocks, but doesn't mine golden tickets.
+///
+/// A miner is a full node which mines golden tickets and submits them to its peers, so they are paid out in blocks
+/// produced by the upstream nodes.
+#[derive(Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeRole {
+    Router,
+    Full,
+    Miner,
+}
+
+impl Default for NodeRole {
+    fn default() -> Self {
+        NodeRole::Full
+    }
+}
+
+impl NodeRole {
+    pub fn is_wallet_tracking_enabled(&self) -> bool {
+        *self != NodeRole::Router
+    }
+    pub fn is_mining_enabled(&self) -> bool {
+        *self == NodeRole::Miner
+    }
+    pub fn is_tx_production_enabled(&self) -> bool {
+        *self != NodeRole::Router
+    }
+    pub fn is_golden_ticket_submission_enabled(&self) -> bool {
+        *self == NodeRole::Miner
+    }
+}
+
+/// Difficulty adjustment used when creating and validating blocks. Anything other than `consensus`
+/// is only meant for private test networks, which otherwise can't find golden tickets fast enough.
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum DifficultyConfig {
+    Consensus,
+    Fixed {
+        difficulty: u64,
+    },
+    Rapid {
+        min_difficulty: u64,
+        max_difficulty: u64,
+    },
+}
+
+/// Certificates used to serve peer connections and block fetches over TLS
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct TlsConfig {
+    /// PEM encoded certificate chain of the server
+    pub cert_path: String,
+    /// PEM encoded private key of the server
+    pub key_path: String,
+    /// PEM encoded certificates trusted for outgoing connections in addition to the system roots.
+    /// used when peers have self signed certificates
+    #[serde(default)]
+    pub ca_cert_path: Option<String>,
+}
+
+/// Limits for the peer connections handled by the network controller. Statically configured peers are always
+/// connected and are not evicted
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(default)]
+pub struct PeerSlotsConfig {
+    pub max_outbound_peers: usize,
+    pub max_inbound_peers: usize,
+}
+
+impl Default for PeerSlotsConfig {
+    fn default() -> Self {
+        PeerSlotsConfig {
+            max_outbound_peers: 8,
+            max_inbound_peers: 117,
+        }
+    }
+}
+
+/// Hostnames resolving to the addresses of public nodes. The resolved nodes are connected when there are free outbound
+/// slots and stored on disk, so they can be connected again after a restart
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(default)]
+pub struct DnsSeedConfig {
+    pub hosts: Vec<String>,
+    /// port of the nodes behind the seeds
+    pub port: u16,
+    pub protocol: String,
+    /// how often the seeds are resolved again
+    pub resolve_interval_in_ms: u64,
+}
+
+impl Default for DnsSeedConfig {
+    fn default() -> Self {
+        DnsSeedConfig {
+            hosts: vec![],
+            port: 12101,
+            protocol: "http".to_string(),
+            resolve_interval_in_ms: 10 * 60 * 1000,
+        }
+    }
+}
+
+/// Batching and queue limits of the transaction verification threads
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(default)]
+pub struct VerificationConfig {
+    /// max number of transactions a thread takes from its queue at once. the signatures of a batch are verified in
+    /// parallel, since secp256k1 ecdsa signatures can't be batch verified
+    pub batch_size: u64,
+    /// max number of requests waiting for each thread. requests are sent to the thread with the shortest queue
+    pub queue_size: u64,
+}
+
+impl Default for VerificationConfig {
+    fn default() -> Self {
+        VerificationConfig {
+            batch_size: 10_000,
+            queue_size: 1_000_000,
+        }
+    }
+}
+
+impl Default for DifficultyConfig {
+    fn default() -> Self {
+        DifficultyConfig::Consensus
+    }
+}
+
+/// network id of the nodes which don't set one in the config
+pub const DEFAULT_NETWORK_ID: &str = "mainnet";
+/// max length of the network id in bytes
+pub const MAX_NETWORK_ID_LENGTH: usize = 64;
+
+fn default_network_id() -> String {
+    DEFAULT_NETWORK_ID.to_string()
+}
+
+fn default_verification_threads() -> u16 {
+    std::thread::available_parallelism()
+        .map(|cores| cores.get().min(4) as u16)
+        .unwrap_or(1)
+}
+
+fn default_channel_size() -> u64 {
+    1000
+}
+
+fn default_stat_timer_in_ms() -> u64 {
+    5000
+}
+
+fn default_thread_sleep_time_in_ms() -> u64 {
+    10
+}
+
+fn default_block_fetch_batch_size() -> u64 {
+    10
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Server {
     pub host: String,
+    /// port of the network preset if not set
+    #[serde(default)]
     pub port: u16,
     pub protocol: String,
     pub endpoint: Endpoint,
+    /// preset with the network id, ports, seeds, genesis period and data directory of a named network. values set
+    /// in the config take precedence over the preset. the node runs with the mainnet parameters if not set
+    #[serde(default)]
+    pub network: Option<NetworkPreset>,
+    /// network the node belongs to (e.g. "mainnet", "testnet" or a custom id). peers with another network id are
+    /// rejected in the handshake
+    #[serde(default = "default_network_id")]
+    pub network_id: String,
+    /// number of transaction verification threads. defaults to the number of cores, up to 4
+    #[serde(default = "default_verification_threads")]
     pub verification_threads: u16,
+    /// batch and queue sizes of the verification threads. read when the node starts
+    #[serde(default)]
+    pub verification: VerificationConfig,
+    #[serde(default = "default_channel_size")]
     pub channel_size: u64,
+    #[serde(default = "default_stat_timer_in_ms")]
     pub stat_timer_in_ms: u64,
+    #[serde(default = "default_thread_sleep_time_in_ms")]
     pub thread_sleep_time_in_ms: u64,
+    #[serde(default = "default_block_fetch_batch_size")]
     pub block_fetch_batch_size: u64,
+    /// max number of blocks requested from a single peer at once during sync. 0 means the batch size is used
+    #[serde(default)]
+    pub block_fetch_limit_per_peer: u64,
+    /// max number of fetched blocks waiting to be added to the blockchain. block bodies are not fetched while the
+    /// queue is full. 0 means the default of 100
+    #[serde(default)]
+    pub block_queue_limit: u64,
+    #[serde(default)]
+    pub node_role: NodeRole,
+    /// max number of full blocks kept in memory. others are loaded from disk when needed. 0 means no limit
+    #[serde(default)]
+    pub full_block_cache_size: u64,
+    #[serde(default)]
+    pub difficulty: DifficultyConfig,
+    /// alerts if the longest chain tip doesn't change within this time. 0 disables the check
+    #[serde(default)]
+    pub stale_chain_threshold_in_ms: u64,
+    /// reconnects to static peers and requests the blockchain again when the chain is stale
+    #[serde(default)]
+    pub reconnect_on_stale_chain: bool,
+    /// test transaction generation within the node
+    #[serde(default)]
+    pub tx_generator: TxGeneratorConfig,
+    /// indexes the transactions by signature, so they can be looked up without scanning the blocks
+    #[serde(default)]
+    pub transaction_index: bool,
+    /// serves websocket and http connections over TLS if set
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// inbound and outbound connection limits
+    #[serde(default)]
+    pub peer_slots: PeerSlotsConfig,
+    /// blocks purged after two genesis periods are copied to this directory before they are deleted if set
+    #[serde(default)]
+    pub archive_dir: Option<String>,
+    /// creates golden tickets for the node's own blocks, so a single local node can produce a chain without the
+    /// miner or peers. only meant for application development
+    #[serde(default)]
+    pub dev_mode: bool,
+    /// max time a received block's timestamp can be ahead of the local clock. 0 means the default of 2 minutes
+    #[serde(default)]
+    pub max_block_future_drift_in_ms: u64,
+    /// syncing from a utxoset snapshot of a trusted node instead of the genesis block
+    #[serde(default)]
+    pub trusted_sync: TrustedSyncConfig,
+    /// max number of blocks kept while waiting for their parent. 0 means the default of 100
+    #[serde(default)]
+    pub max_orphan_blocks: u64,
+    /// orphan blocks older than this are dropped. 0 means the default of 10 minutes
+    #[serde(default)]
+    pub orphan_block_expiry_in_ms: u64,
+    /// serialized size limit of the blocks bundled by the node. transactions paying the most per byte are included
+    /// first and the rest are left for the next block. 0 means the default of 10 MB
+    #[serde(default)]
+    pub max_block_size: u64,
+    /// advertises support for compressed messages in the handshake. large messages to peers which also support it
+    /// are compressed
+    #[serde(default)]
+    pub message_compression: bool,
+    /// peers found by resolving dns seeds in addition to the static peers
+    #[serde(default)]
+    pub dns_seeds: DnsSeedConfig,
+    /// thresholds of the fork rate and block interval alerts
+    #[serde(default)]
+    pub consensus_health: ConsensusHealthConfig,
+    /// json lines record of the added and rejected blocks, reorgs and accepted golden tickets
+    #[serde(default)]
+    pub audit_log: AuditLogConfig,
+    /// urls which receive the new block, reorg, stalled node and payment events
+    #[serde(default)]
+    pub webhooks: WebhookConfig,
+    /// peers which need to be connected before the node produces blocks
+    #[serde(default)]
+    pub block_production_quorum: PeerQuorumConfig,
+    /// proof (hex) of moving from the node's previous key to the current wallet key, signed with the previous key.
+    /// sent in handshakes so peers which pinned the previous key accept the new key
+    #[serde(default)]
+    pub key_rotation_proof: Option<String>,
+    /// file with the public keys and amounts issued in the genesis block, when the node creates the genesis block of
+    /// a new network. test transactions are issued if not set
+    #[serde(default)]
+    pub issuance_file: Option<String>,
 }
 
 pub trait Configuration {
@@ -0,0 +1,443 @@
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::common::defs::{Currency, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature};
+use crate::core::data::block::{Block, BlockType};
+use crate::core::data::crypto::{generate_keypair_from_private_key, hash, sign, verify};
+use crate::core::data::golden_ticket::GoldenTicket;
+use crate::core::data::msg::handshake::HandshakeChallenge;
+use crate::core::data::serialize::Serialize as SaitoSerialize;
+use crate::core::data::slip::Slip;
+use crate::core::data::transaction::{Transaction, TransactionType};
+
+/// version of the test vector format. should be incremented when the vectors or the expected fields change
+pub const TEST_VECTOR_VERSION: u32 = 1;
+/// private key used to generate the canonical vectors, so every implementation gets the same signatures
+const TEST_VECTOR_PRIVATE_KEY: &str =
+    "854702489d49c7fb2334005b903580c7a48fe81121ff16ee6d1a528ad32f235d";
+const TEST_VECTOR_TIMESTAMP: u64 = 1_650_000_000_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorType {
+    /// input : message. expected : hash
+    Hash,
+    /// input : message. expected : signature
+    Signature,
+    /// input : transaction serialized for net. expected : hash_for_signature, signature
+    Transaction,
+    /// input : full block serialized for net. expected : merkle_root, pre_hash, hash, signature
+    Block,
+    /// input : golden ticket serialized for net. expected : solution_hash
+    GoldenTicket,
+    /// input : serialized challenge. expected : response_signature
+    HandshakeChallenge,
+}
+
+/// A single test vector. Binary values are hex encoded
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TestVector {
+    pub name: String,
+    pub vector_type: VectorType,
+    pub input: String,
+    pub expected: BTreeMap<String, String>,
+}
+
+/// Canonical test vectors for checking other implementations (or other versions of this one) against the
+/// consensus rules of this node.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TestVectorSet {
+    pub version: u32,
+    pub public_key: String,
+    pub private_key: String,
+    pub vectors: Vec<TestVector>,
+}
+
+impl TestVectorSet {
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(|error| {
+            warn!("failed serializing test vectors : {:?}", error);
+            Error::from(ErrorKind::InvalidData)
+        })
+    }
+
+    pub fn from_json(json: &str) -> Result<TestVectorSet, Error> {
+        serde_json::from_str(json).map_err(|error| {
+            warn!("failed parsing test vectors : {:?}", error);
+            Error::from(ErrorKind::InvalidData)
+        })
+    }
+}
+
+fn create_vector(
+    name: &str,
+    vector_type: VectorType,
+    input: &[u8],
+    expected: Vec<(&str, &[u8])>,
+) -> TestVector {
+    TestVector {
+        name: name.to_string(),
+        vector_type,
+        input: hex::encode(input),
+        expected: expected
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), hex::encode(value)))
+            .collect(),
+    }
+}
+
+fn create_transaction(
+    public_key: &SaitoPublicKey,
+    private_key: &SaitoPrivateKey,
+    amount: Currency,
+    message: &[u8],
+) -> Transaction {
+    let mut tx = Transaction::default();
+    tx.timestamp = TEST_VECTOR_TIMESTAMP;
+    tx.transaction_type = TransactionType::Normal;
+    tx.message = message.to_vec();
+
+    let mut input = Slip::default();
+    input.public_key = *public_key;
+    input.amount = amount;
+    input.block_id = 1;
+    input.tx_ordinal = 1;
+    tx.add_input(input);
+
+    let mut output = Slip::default();
+    output.public_key = *public_key;
+    output.amount = amount - 1;
+    tx.add_output(output);
+
+    tx.sign(private_key);
+    tx
+}
+
+/// Generates the canonical test vectors. All the values are fixed, so the output is the same on every run
+pub fn generate_test_vectors() -> TestVectorSet {
+    let private_key: SaitoPrivateKey = hex::decode(TEST_VECTOR_PRIVATE_KEY)
+        .unwrap()
+        .try_into()
+        .unwrap();
+    let (public_key, private_key) = generate_keypair_from_private_key(&private_key);
+    let mut vectors = vec![];
+
+    for (name, message) in [
+        ("hash_empty", b"".as_slice()),
+        ("hash_message", b"saito test vector".as_slice()),
+    ] {
+        vectors.push(create_vector(
+            name,
+            VectorType::Hash,
+            message,
+            vec![("hash", hash(message).as_slice())],
+        ));
+    }
+
+    let message = b"saito test vector";
+    vectors.push(create_vector(
+        "signature_message",
+        VectorType::Signature,
+        message,
+        vec![("signature", sign(message, &private_key).as_slice())],
+    ));
+
+    let tx = create_transaction(&public_key, &private_key, 1_000, b"test transaction");
+    vectors.push(create_vector(
+        "transaction_normal",
+        VectorType::Transaction,
+        &tx.serialize_for_net(),
+        vec![
+            (
+                "hash_for_signature",
+                tx.hash_for_signature.unwrap().as_slice(),
+            ),
+            ("signature", tx.signature.as_slice()),
+        ],
+    ));
+
+    let mut block = Block::new();
+    block.id = 2;
+    block.timestamp = TEST_VECTOR_TIMESTAMP;
+    block.previous_block_hash = hash(b"previous block");
+    block.creator = public_key;
+    block.treasury = 1_000_000;
+    block.staking_treasury = 500;
+    block.burnfee = 50_000;
+    block.difficulty = 1;
+    block.block_type = BlockType::Full;
+    block.transactions = vec![
+        create_transaction(&public_key, &private_key, 1_000, b"first"),
+        create_transaction(&public_key, &private_key, 2_000, b"second"),
+    ];
+    block.merkle_root = block.generate_merkle_root();
+    block.sign(&private_key);
+    block.generate_pre_hash();
+    block.generate_hash();
+    vectors.push(create_vector(
+        "block_with_transactions",
+        VectorType::Block,
+        &block.serialize_for_net(BlockType::Full),
+        vec![
+            ("merkle_root", block.merkle_root.as_slice()),
+            ("pre_hash", block.pre_hash.as_slice()),
+            ("hash", block.hash.as_slice()),
+            ("signature", block.signature.as_slice()),
+        ],
+    ));
+
+    let golden_ticket = GoldenTicket::new(block.hash, hash(b"random"), public_key);
+    let buffer = golden_ticket.serialize_for_net();
+    vectors.push(create_vector(
+        "golden_ticket",
+        VectorType::GoldenTicket,
+        &buffer,
+        vec![("solution_hash", hash(&buffer).as_slice())],
+    ));
+
+    let challenge = HandshakeChallenge {
+        challenge: hash(b"challenge"),
+        issued_at: TEST_VECTOR_TIMESTAMP,
+        public_key,
+        peer_address: "127.0.0.1:12101".to_string(),
+    };
+    let payload = HandshakeChallenge::get_payload_to_sign(
+        &challenge.challenge,
+        challenge.issued_at,
+        &challenge.public_key,
+        &challenge.peer_address,
+    );
+    vectors.push(create_vector(
+        "handshake_challenge",
+        VectorType::HandshakeChallenge,
+        &challenge.serialize(),
+        vec![(
+            "response_signature",
+            sign(&payload, &private_key).as_slice(),
+        )],
+    ));
+
+    TestVectorSet {
+        version: TEST_VECTOR_VERSION,
+        public_key: hex::encode(public_key),
+        private_key: hex::encode(private_key),
+        vectors,
+    }
+}
+
+fn decode<const N: usize>(value: &str) -> Result<[u8; N], String> {
+    let bytes = hex::decode(value).map_err(|error| format!("invalid hex : {:?}", error))?;
+    bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| format!("expected {} bytes, found {}", N, bytes.len()))
+}
+
+fn get_expected<const N: usize>(vector: &TestVector, key: &str) -> Result<[u8; N], String> {
+    let value = vector
+        .expected
+        .get(key)
+        .ok_or_else(|| format!("expected value : {} not found", key))?;
+    decode(value).map_err(|error| format!("{} : {}", key, error))
+}
+
+fn check<const N: usize>(vector: &TestVector, key: &str, actual: &[u8; N]) -> Result<(), String> {
+    let expected: [u8; N] = get_expected(vector, key)?;
+    if expected != *actual {
+        return Err(format!(
+            "{} mismatch. expected : {} actual : {}",
+            key,
+            hex::encode(expected),
+            hex::encode(actual)
+        ));
+    }
+    Ok(())
+}
+
+fn check_roundtrip(input: &[u8], serialized: &[u8]) -> Result<(), String> {
+    if input != serialized {
+        return Err("serialized buffer doesn't match the input".to_string());
+    }
+    Ok(())
+}
+
+/// Checks the signature against the key pair of the set. Signatures are deterministic, so they are
+/// also regenerated and compared
+fn check_signature(
+    vector: &TestVector,
+    key: &str,
+    message: &[u8],
+    public_key: &SaitoPublicKey,
+    private_key: &SaitoPrivateKey,
+) -> Result<(), String> {
+    let signature: SaitoSignature = get_expected(vector, key)?;
+    if !verify(message, &signature, public_key) {
+        return Err(format!("{} verification failed", key));
+    }
+    check(vector, key, &sign(message, private_key))
+}
+
+fn validate_vector(
+    vector: &TestVector,
+    public_key: &SaitoPublicKey,
+    private_key: &SaitoPrivateKey,
+) -> Result<(), String> {
+    let input =
+        hex::decode(&vector.input).map_err(|error| format!("invalid input hex : {:?}", error))?;
+    match vector.vector_type {
+        VectorType::Hash => check(vector, "hash", &hash(&input)),
+        VectorType::Signature => {
+            check_signature(vector, "signature", &input, public_key, private_key)
+        }
+        VectorType::Transaction => {
+            if Transaction::try_get_serialized_len(&input) != Some(input.len()) {
+                return Err("invalid transaction buffer length".to_string());
+            }
+            let mut tx = Transaction::deserialize_from_net(&input)
+                .map_err(|error| format!("invalid transaction buffer : {:?}", error))?;
+            check_roundtrip(&input, &tx.serialize_for_net())?;
+            tx.generate_hash_for_signature();
+            check(
+                vector,
+                "hash_for_signature",
+                &tx.hash_for_signature.unwrap(),
+            )?;
+            check(vector, "signature", &tx.signature)?;
+            check_signature(
+                vector,
+                "signature",
+                &tx.serialize_for_signature(),
+                public_key,
+                private_key,
+            )
+        }
+        VectorType::Block => {
+            if Block::try_get_serialized_len(&input) != Some(input.len()) {
+                return Err("invalid block buffer length".to_string());
+            }
+            let mut block = Block::deserialize_from_net(&input);
+            check_roundtrip(&input, &block.serialize_for_net(BlockType::Full))?;
+            for tx in block.transactions.iter_mut() {
+                tx.generate_hash_for_signature();
+            }
+            check(vector, "merkle_root", &block.generate_merkle_root())?;
+            check(vector, "merkle_root", &block.merkle_root)?;
+            block.generate_pre_hash();
+            check(vector, "pre_hash", &block.pre_hash)?;
+            let block_hash: SaitoHash = block.generate_hash();
+            check(vector, "hash", &block_hash)?;
+            check(vector, "signature", &block.signature)?;
+            check_signature(
+                vector,
+                "signature",
+                &block.serialize_for_signature(),
+                &block.creator,
+                private_key,
+            )
+        }
+        VectorType::GoldenTicket => {
+            if input.len() != 97 {
+                return Err("invalid golden ticket buffer length".to_string());
+            }
+            let golden_ticket = GoldenTicket::deserialize_from_net(&input);
+            let buffer = golden_ticket.serialize_for_net();
+            check_roundtrip(&input, &buffer)?;
+            check(vector, "solution_hash", &hash(&buffer))
+        }
+        VectorType::HandshakeChallenge => {
+            let challenge = HandshakeChallenge::deserialize(&input)
+                .map_err(|_| "invalid handshake challenge buffer".to_string())?;
+            check_roundtrip(&input, &challenge.serialize())?;
+            let payload = HandshakeChallenge::get_payload_to_sign(
+                &challenge.challenge,
+                challenge.issued_at,
+                &challenge.public_key,
+                &challenge.peer_address,
+            );
+            check_signature(
+                vector,
+                "response_signature",
+                &payload,
+                public_key,
+                private_key,
+            )
+        }
+    }
+}
+
+/// Validates the implementation against the given vectors. Returns the failures as "<vector name> : <reason>"
+pub fn validate_test_vectors(set: &TestVectorSet) -> Vec<String> {
+    if set.version != TEST_VECTOR_VERSION {
+        return vec![format!(
+            "unsupported test vector version : {}. expected : {}",
+            set.version, TEST_VECTOR_VERSION
+        )];
+    }
+    let keys = decode::<33>(&set.public_key).and_then(|public_key| {
+        decode::<32>(&set.private_key).map(|private_key| (public_key, private_key))
+    });
+    let (public_key, private_key) = match keys {
+        Ok(keys) => keys,
+        Err(error) => {
+            return vec![format!("invalid key pair : {}", error)];
+        }
+    };
+
+    let mut failures = vec![];
+    for vector in set.vectors.iter() {
+        match validate_vector(vector, &public_key, &private_key) {
+            Ok(()) => {
+                debug!("test vector : {:?} passed", vector.name);
+            }
+            Err(error) => {
+                warn!("test vector : {:?} failed : {}", vector.name, error);
+                failures.push(format!("{} : {}", vector.name, error));
+            }
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::conformance::{
+        generate_test_vectors, validate_test_vectors, TestVectorSet, VectorType,
+    };
+
+    #[test]
+    fn conformance_test_vectors_test() {
+        let set = generate_test_vectors();
+        assert_eq!(set, generate_test_vectors());
+        assert_eq!(set.vectors.len(), 7);
+
+        let loaded = TestVectorSet::from_json(&set.to_json().unwrap()).unwrap();
+        assert_eq!(loaded, set);
+        assert!(validate_test_vectors(&loaded).is_empty());
+
+        let mut tampered = loaded.clone();
+        let block_vector = tampered
+            .vectors
+            .iter_mut()
+            .find(|vector| vector.vector_type == VectorType::Block)
+            .unwrap();
+        block_vector
+            .expected
+            .insert("hash".to_string(), hex::encode([1u8; 32]));
+        let transaction_vector = tampered
+            .vectors
+            .iter_mut()
+            .find(|vector| vector.vector_type == VectorType::Transaction)
+            .unwrap();
+        // changing the timestamp invalidates the signature
+        let mut input = hex::decode(&transaction_vector.input).unwrap();
+        input[87] ^= 1;
+        transaction_vector.input = hex::encode(input);
+
+        let failures = validate_test_vectors(&tampered);
+        assert_eq!(failures.len(), 2);
+        assert!(failures[0].starts_with("transaction_normal"));
+        assert!(failures[1].starts_with("block_with_transactions : hash mismatch"));
+    }
+}
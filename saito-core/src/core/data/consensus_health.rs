@@ -0,0 +1,235 @@
+use serde::Deserialize;
+
+use crate::common::defs::Timestamp;
+
+/// number of latest longest chain blocks the fork rate and the block intervals are calculated for
+pub const CONSENSUS_HEALTH_WINDOW: usize = 100;
+/// checks are skipped until the chain has this many blocks, so a node which just started doesn't raise alerts
+pub const CONSENSUS_HEALTH_MIN_BLOCKS: u64 = 10;
+
+/// Thresholds of the consensus health alerts. 0 disables the check
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(default)]
+pub struct ConsensusHealthConfig {
+    /// max number of fork blocks per 100 longest chain blocks
+    pub max_fork_rate: u64,
+    /// max standard deviation of the intervals between the longest chain blocks
+    pub max_block_interval_deviation_in_ms: Timestamp,
+}
+
+impl Default for ConsensusHealthConfig {
+    fn default() -> Self {
+        ConsensusHealthConfig {
+            max_fork_rate: 10,
+            max_block_interval_deviation_in_ms: 60_000,
+        }
+    }
+}
+
+/// Fork and block interval stats of the latest longest chain blocks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConsensusHealthSample {
+    /// longest chain blocks in the window
+    pub block_count: u64,
+    /// blocks not in the longest chain with the ids in the window
+    pub fork_block_count: u64,
+    pub average_block_interval: Timestamp,
+    pub block_interval_deviation: Timestamp,
+}
+
+impl ConsensusHealthSample {
+    /// `intervals` are the times between the consecutive longest chain blocks in the window
+    pub fn new(
+        block_count: u64,
+        fork_block_count: u64,
+        intervals: &[Timestamp],
+    ) -> ConsensusHealthSample {
+        let mut sample = ConsensusHealthSample {
+            block_count,
+            fork_block_count,
+            ..Default::default()
+        };
+        if intervals.is_empty() {
+            return sample;
+        }
+        let count = intervals.len() as f64;
+        let average = intervals
+            .iter()
+            .map(|interval| *interval as f64)
+            .sum::<f64>()
+            / count;
+        let variance = intervals
+            .iter()
+            .map(|interval| (*interval as f64 - average).powi(2))
+            .sum::<f64>()
+            / count;
+        sample.average_block_interval = average as Timestamp;
+        sample.block_interval_deviation = variance.sqrt() as Timestamp;
+        sample
+    }
+
+    /// fork blocks per 100 longest chain blocks
+    pub fn get_fork_rate(&self) -> u64 {
+        if self.block_count == 0 {
+            return 0;
+        }
+        self.fork_block_count * 100 / self.block_count
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsensusHealthAlert {
+    /// many competing blocks for the same ids. the network might be split
+    HighForkRate { fork_rate: u64 },
+    /// block times are irregular. block timestamps might be manipulated or the block production is stalling
+    IrregularBlockIntervals {
+        deviation: Timestamp,
+        average: Timestamp,
+    },
+    /// fork rate is back under the threshold
+    ForkRateRecovered { fork_rate: u64 },
+    /// block interval deviation is back under the threshold
+    BlockIntervalsRecovered { deviation: Timestamp },
+}
+
+/// Checks the fork rate and the block interval deviation of the latest blocks against the thresholds. An alert is
+/// returned when a threshold is breached and a recovery when the value is back under the threshold, so the alerts are
+/// not repeated for every block while the chain is unhealthy.
+#[derive(Debug)]
+pub struct ConsensusHealthMonitor {
+    config: ConsensusHealthConfig,
+    high_fork_rate: bool,
+    irregular_block_intervals: bool,
+    last_sample: ConsensusHealthSample,
+    pub fork_rate_alert_count: u64,
+    pub block_interval_alert_count: u64,
+}
+
+impl ConsensusHealthMonitor {
+    pub fn new(config: ConsensusHealthConfig) -> ConsensusHealthMonitor {
+        ConsensusHealthMonitor {
+            config,
+            high_fork_rate: false,
+            irregular_block_intervals: false,
+            last_sample: Default::default(),
+            fork_rate_alert_count: 0,
+            block_interval_alert_count: 0,
+        }
+    }
+
+    pub fn set_config(&mut self, config: ConsensusHealthConfig) {
+        self.config = config;
+    }
+
+    pub fn get_last_sample(&self) -> &ConsensusHealthSample {
+        &self.last_sample
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        !self.high_fork_rate && !self.irregular_block_intervals
+    }
+
+    pub fn check(&mut self, sample: ConsensusHealthSample) -> Vec<ConsensusHealthAlert> {
+        self.last_sample = sample;
+        let mut alerts = vec![];
+        if sample.block_count < CONSENSUS_HEALTH_MIN_BLOCKS {
+            return alerts;
+        }
+
+        let fork_rate = sample.get_fork_rate();
+        let high_fork_rate =
+            self.config.max_fork_rate != 0 && fork_rate > self.config.max_fork_rate;
+        if high_fork_rate && !self.high_fork_rate {
+            self.fork_rate_alert_count += 1;
+            alerts.push(ConsensusHealthAlert::HighForkRate { fork_rate });
+        } else if !high_fork_rate && self.high_fork_rate {
+            alerts.push(ConsensusHealthAlert::ForkRateRecovered { fork_rate });
+        }
+        self.high_fork_rate = high_fork_rate;
+
+        let deviation = sample.block_interval_deviation;
+        let irregular_block_intervals = self.config.max_block_interval_deviation_in_ms != 0
+            && deviation > self.config.max_block_interval_deviation_in_ms;
+        if irregular_block_intervals && !self.irregular_block_intervals {
+            self.block_interval_alert_count += 1;
+            alerts.push(ConsensusHealthAlert::IrregularBlockIntervals {
+                deviation,
+                average: sample.average_block_interval,
+            });
+        } else if !irregular_block_intervals && self.irregular_block_intervals {
+            alerts.push(ConsensusHealthAlert::BlockIntervalsRecovered { deviation });
+        }
+        self.irregular_block_intervals = irregular_block_intervals;
+
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::consensus_health::{
+        ConsensusHealthAlert, ConsensusHealthConfig, ConsensusHealthMonitor, ConsensusHealthSample,
+    };
+
+    #[test]
+    fn consensus_health_sample_test() {
+        let sample = ConsensusHealthSample::new(50, 5, &[1000, 3000, 1000, 3000]);
+        assert_eq!(sample.get_fork_rate(), 10);
+        assert_eq!(sample.average_block_interval, 2000);
+        assert_eq!(sample.block_interval_deviation, 1000);
+
+        let sample = ConsensusHealthSample::new(0, 0, &[]);
+        assert_eq!(sample.get_fork_rate(), 0);
+        assert_eq!(sample.block_interval_deviation, 0);
+    }
+
+    #[test]
+    fn consensus_health_monitor_test() {
+        let mut monitor = ConsensusHealthMonitor::new(ConsensusHealthConfig {
+            max_fork_rate: 10,
+            max_block_interval_deviation_in_ms: 5000,
+        });
+        let irregular_intervals = [1000, 21000, 1000, 21000];
+
+        // not enough blocks yet
+        assert!(monitor
+            .check(ConsensusHealthSample::new(5, 5, &irregular_intervals))
+            .is_empty());
+
+        assert_eq!(
+            monitor.check(ConsensusHealthSample::new(20, 4, &[5000, 5000])),
+            vec![ConsensusHealthAlert::HighForkRate { fork_rate: 20 }]
+        );
+        // not repeated while the fork rate stays high
+        assert!(monitor
+            .check(ConsensusHealthSample::new(20, 3, &[5000, 5000]))
+            .is_empty());
+        assert!(!monitor.is_healthy());
+
+        assert_eq!(
+            monitor.check(ConsensusHealthSample::new(20, 1, &irregular_intervals)),
+            vec![
+                ConsensusHealthAlert::ForkRateRecovered { fork_rate: 5 },
+                ConsensusHealthAlert::IrregularBlockIntervals {
+                    deviation: 10000,
+                    average: 11000
+                }
+            ]
+        );
+        assert_eq!(
+            monitor.check(ConsensusHealthSample::new(20, 1, &[5000, 5000])),
+            vec![ConsensusHealthAlert::BlockIntervalsRecovered { deviation: 0 }]
+        );
+        assert!(monitor.is_healthy());
+        assert_eq!(monitor.fork_rate_alert_count, 1);
+        assert_eq!(monitor.block_interval_alert_count, 1);
+
+        monitor.set_config(ConsensusHealthConfig {
+            max_fork_rate: 0,
+            max_block_interval_deviation_in_ms: 0,
+        });
+        assert!(monitor
+            .check(ConsensusHealthSample::new(20, 20, &irregular_intervals))
+            .is_empty());
+    }
+}
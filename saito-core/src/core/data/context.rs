@@ -1,12 +1,16 @@
 use std::io::Error;
-use std::sync::Arc;
-
-use tokio::sync::RwLock;
+use std::sync::{Arc, Mutex};
 
 use crate::common::run_task::RunTask;
+use crate::common::runtime::RwLock;
+use crate::core::data::block_queue::BlockQueueStatus;
 use crate::core::data::blockchain::Blockchain;
+use crate::core::data::blockchain_snapshot::BlockchainSnapshot;
 use crate::core::data::configuration::Configuration;
 use crate::core::data::mempool::Mempool;
+use crate::core::data::mining_status::MiningStatus;
+use crate::core::data::peer_quorum::ConnectionStatus;
+use crate::core::data::transaction_receipts::TransactionReceipts;
 use crate::core::data::wallet::Wallet;
 
 #[derive(Clone)]
@@ -15,6 +19,17 @@ pub struct Context {
     pub mempool: Arc<RwLock<Mempool>>,
     pub wallet: Arc<RwLock<Wallet>>,
     pub configuration: Arc<RwLock<Box<dyn Configuration + Send + Sync>>>,
+    /// published by the mining thread. only held to copy or update the status
+    pub mining_status: Arc<Mutex<MiningStatus>>,
+    /// updated by the routing thread when peers connect or disconnect
+    pub connection_status: Arc<Mutex<ConnectionStatus>>,
+    /// updated by the consensus thread as transactions are added to the mempool and the chain
+    pub transaction_receipts: Arc<Mutex<TransactionReceipts>>,
+    /// fetched blocks waiting to be added. updated by the routing and consensus threads
+    pub block_queue_status: Arc<Mutex<BlockQueueStatus>>,
+    /// latest view of the longest chain, published by the consensus thread. readers clone the inner `Arc` so they
+    /// don't need the blockchain lock
+    pub blockchain_snapshot: Arc<Mutex<Arc<BlockchainSnapshot>>>,
 }
 
 impl Context {
@@ -31,6 +46,11 @@ impl Context {
             mempool: Arc::new(RwLock::new(Mempool::new(public_key, private_key))),
             wallet: wallet.clone(),
             configuration: configs,
+            mining_status: Arc::new(Mutex::new(MiningStatus::new())),
+            connection_status: Arc::new(Mutex::new(ConnectionStatus::new())),
+            transaction_receipts: Arc::new(Mutex::new(TransactionReceipts::default())),
+            block_queue_status: Arc::new(Mutex::new(BlockQueueStatus::default())),
+            blockchain_snapshot: Arc::new(Mutex::new(Arc::new(BlockchainSnapshot::new()))),
         }
     }
     pub async fn init(&self, _task_runner: &dyn RunTask) -> Result<(), Error> {
@@ -0,0 +1,109 @@
+use ahash::AHashMap;
+use tracing::{info, warn};
+
+use crate::common::defs::{SaitoPublicKey, Timestamp};
+
+/// number of invalid blocks in a row after which a creator is blacklisted
+pub const INVALID_BLOCK_THRESHOLD: u32 = 3;
+/// blacklisted creators are removed from the list after this time
+pub const CREATOR_BLACKLIST_DURATION_IN_MS: Timestamp = 60 * 60 * 1000;
+
+/// Creators of blocks which failed validation repeatedly. Blocks of blacklisted creators are ignored when they are
+/// fetched, so a single key can't keep the node busy validating spam forks.
+///
+/// Only blocks with a valid creator signature are counted, so a creator can't be blacklisted with blocks signed by
+/// another key. The count of a creator is reset when one of its blocks is added to the longest chain.
+#[derive(Debug)]
+pub struct CreatorBlacklist {
+    /// invalid blocks in a row by each creator
+    failures: AHashMap<SaitoPublicKey, u32>,
+    /// blacklisted creators with the time they were blacklisted. None until the time is set by `update`
+    blacklisted: AHashMap<SaitoPublicKey, Option<Timestamp>>,
+    /// blocks of blacklisted creators which were ignored
+    pub ignored_blocks: u64,
+}
+
+impl CreatorBlacklist {
+    pub fn new() -> CreatorBlacklist {
+        CreatorBlacklist {
+            failures: Default::default(),
+            blacklisted: Default::default(),
+            ignored_blocks: 0,
+        }
+    }
+
+    pub fn on_invalid_block(&mut self, creator: &SaitoPublicKey) {
+        let count = self.failures.entry(*creator).or_default();
+        *count += 1;
+        if *count >= INVALID_BLOCK_THRESHOLD {
+            warn!(
+                "blacklisting block creator : {:?} after {:?} invalid blocks",
+                hex::encode(creator),
+                count
+            );
+            self.failures.remove(creator);
+            self.blacklisted.insert(*creator, None);
+        }
+    }
+
+    pub fn on_valid_block(&mut self, creator: &SaitoPublicKey) {
+        self.failures.remove(creator);
+    }
+
+    /// Sets the blacklisting time of the new entries and removes the expired ones
+    pub fn update(&mut self, current_time: Timestamp) {
+        self.blacklisted.retain(|creator, blacklisted_at| {
+            let time = *blacklisted_at.get_or_insert(current_time);
+            if current_time.saturating_sub(time) < CREATOR_BLACKLIST_DURATION_IN_MS {
+                return true;
+            }
+            info!(
+                "removing block creator : {:?} from the blacklist",
+                hex::encode(creator)
+            );
+            false
+        });
+    }
+
+    pub fn is_blacklisted(&self, creator: &SaitoPublicKey) -> bool {
+        self.blacklisted.contains_key(creator)
+    }
+
+    pub fn get_blacklisted_creators(&self) -> Vec<SaitoPublicKey> {
+        self.blacklisted.keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::creator_blacklist::{
+        CreatorBlacklist, CREATOR_BLACKLIST_DURATION_IN_MS, INVALID_BLOCK_THRESHOLD,
+    };
+
+    #[test]
+    fn creator_blacklist_test() {
+        let mut blacklist = CreatorBlacklist::new();
+        let creator = [1; 33];
+
+        for _ in 1..INVALID_BLOCK_THRESHOLD {
+            blacklist.on_invalid_block(&creator);
+        }
+        // valid block resets the count
+        blacklist.on_valid_block(&creator);
+        blacklist.on_invalid_block(&creator);
+        assert!(!blacklist.is_blacklisted(&creator));
+
+        for _ in 1..INVALID_BLOCK_THRESHOLD {
+            blacklist.on_invalid_block(&creator);
+        }
+        assert!(blacklist.is_blacklisted(&creator));
+        assert!(!blacklist.is_blacklisted(&[2; 33]));
+        assert_eq!(blacklist.get_blacklisted_creators(), vec![creator]);
+
+        blacklist.update(1000);
+        blacklist.update(1000 + CREATOR_BLACKLIST_DURATION_IN_MS - 1);
+        assert!(blacklist.is_blacklisted(&creator));
+        blacklist.update(1000 + CREATOR_BLACKLIST_DURATION_IN_MS);
+        assert!(!blacklist.is_blacklisted(&creator));
+    }
+}
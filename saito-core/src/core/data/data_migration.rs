@@ -0,0 +1,469 @@
+use std::io::{Error, ErrorKind};
+
+use tracing::{info, warn};
+
+use crate::common::defs::TEMP_FILE_EXTENSION;
+use crate::common::interface_io::InterfaceIO;
+use crate::core::data::block::Block;
+use crate::core::data::crypto::hash;
+use crate::core::data::format_version::{
+    get_format_envelope, read_format_envelope, CURRENT_FORMAT_VERSION, LEGACY_FORMAT_VERSION,
+};
+use crate::core::data::storage::Storage;
+use crate::core::data::transaction_index::{
+    TRANSACTION_INDEX_ENTRY_SIZE, TRANSACTION_INDEX_FILE_PATH,
+};
+
+pub const DATA_MANIFEST_FILE_PATH: &str = "./data/manifest";
+/// data directories written before the manifest was added
+pub const LEGACY_DATA_VERSION: u32 = 0;
+/// layout written by this node. a migration is added with a new version whenever the layout of the block, wallet or
+/// index files changes
+pub const CURRENT_DATA_VERSION: u32 = 2;
+
+/// Changes to the data directory, applied in order at startup. The manifest records the version after the last applied
+/// migration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataMigration {
+    /// rewrites the block files without the format envelope or the checksum footer in the current format
+    BlockFileFormat,
+    /// removes a transaction index file with a partial entry. the index is rebuilt from the blocks at startup
+    TransactionIndexFile,
+}
+
+const DATA_MIGRATIONS: [DataMigration; 2] = [
+    DataMigration::BlockFileFormat,
+    DataMigration::TransactionIndexFile,
+];
+
+impl DataMigration {
+    /// version of the data directory after applying this migration
+    pub fn get_target_version(&self) -> u32 {
+        match self {
+            DataMigration::BlockFileFormat => 1,
+            DataMigration::TransactionIndexFile => 2,
+        }
+    }
+
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            DataMigration::BlockFileFormat => "rewrite block files in the current format",
+            DataMigration::TransactionIndexFile => "remove invalid transaction index file",
+        }
+    }
+
+    /// Returns a line per changed file. Nothing is written in a dry run
+    async fn run(
+        &self,
+        io_interface: &mut (dyn InterfaceIO + Send + Sync),
+        dry_run: bool,
+    ) -> Result<Vec<String>, Error> {
+        match self {
+            DataMigration::BlockFileFormat => migrate_block_files(io_interface, dry_run).await,
+            DataMigration::TransactionIndexFile => {
+                migrate_transaction_index_file(io_interface, dry_run).await
+            }
+        }
+    }
+}
+
+/// Version of the data directory, kept in the data directory so a node can tell which migrations are already applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataManifest {
+    pub version: u32,
+    /// node version which wrote the manifest. only for troubleshooting
+    pub node_version: String,
+}
+
+impl DataManifest {
+    pub fn new(version: u32) -> DataManifest {
+        DataManifest {
+            version,
+            node_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// one [key]=[value] entry per line
+    pub fn serialize_for_disk(&self) -> Vec<u8> {
+        format!(
+            "version={}\nnode_version={}\n",
+            self.version, self.node_version
+        )
+        .into_bytes()
+    }
+
+    pub fn deserialize_from_disk(bytes: &[u8]) -> Result<DataManifest, Error> {
+        let mut version = None;
+        let mut node_version = String::new();
+        for line in String::from_utf8_lossy(bytes).lines() {
+            match line.split_once('=') {
+                Some(("version", value)) => {
+                    version = Some(
+                        value
+                            .trim()
+                            .parse::<u32>()
+                            .map_err(|_| Error::from(ErrorKind::InvalidData))?,
+                    );
+                }
+                Some(("node_version", value)) => node_version = value.trim().to_string(),
+                _ => warn!("unknown data manifest entry : {:?}", line),
+            }
+        }
+        match version {
+            Some(version) => Ok(DataManifest {
+                version,
+                node_version,
+            }),
+            None => {
+                warn!("data manifest doesn't have a version");
+                Err(Error::from(ErrorKind::InvalidData))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataMigrationReport {
+    pub from_version: u32,
+    pub to_version: u32,
+    pub dry_run: bool,
+    /// applied (or pending in a dry run) migrations with the changed files
+    pub migrations: Vec<(DataMigration, Vec<String>)>,
+}
+
+impl DataMigrationReport {
+    pub fn get_change_count(&self) -> usize {
+        self.migrations
+            .iter()
+            .map(|(_, changes)| changes.len())
+            .sum()
+    }
+
+    pub fn get_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "data version : {} -> {}{}",
+            self.from_version,
+            self.to_version,
+            if self.dry_run { " (dry run)" } else { "" }
+        )];
+        for (migration, changes) in self.migrations.iter() {
+            lines.push(format!(
+                "migration {} : {} : {} changes",
+                migration.get_target_version(),
+                migration.get_description(),
+                changes.len()
+            ));
+            lines.extend(changes.iter().map(|change| format!("  {}", change)));
+        }
+        lines
+    }
+}
+
+/// Reads the manifest. A data directory without a manifest is of the legacy version
+pub async fn load_data_manifest(
+    io_interface: &(dyn InterfaceIO + Send + Sync),
+) -> Result<DataManifest, Error> {
+    if !io_interface
+        .is_existing_file(DATA_MANIFEST_FILE_PATH.to_string())
+        .await
+    {
+        return Ok(DataManifest::new(LEGACY_DATA_VERSION));
+    }
+    let buffer = io_interface
+        .read_value(DATA_MANIFEST_FILE_PATH.to_string())
+        .await?;
+    DataManifest::deserialize_from_disk(&buffer)
+}
+
+/// Applies the migrations newer than the version in the manifest and writes the manifest after each one, so an
+/// interrupted run continues from the failed migration. A dry run only reports the changes.
+///
+/// Fails if the data directory was written by a newer node, since this node can't read its layout
+pub async fn run_data_migrations(
+    io_interface: &mut (dyn InterfaceIO + Send + Sync),
+    dry_run: bool,
+) -> Result<DataMigrationReport, Error> {
+    let manifest = load_data_manifest(io_interface).await?;
+    if manifest.version > CURRENT_DATA_VERSION {
+        warn!(
+            "data directory version : {:?} written by node : {:?} is newer than the supported version : {:?}",
+            manifest.version, manifest.node_version, CURRENT_DATA_VERSION
+        );
+        return Err(Error::from(ErrorKind::Unsupported));
+    }
+    let mut report = DataMigrationReport {
+        from_version: manifest.version,
+        to_version: CURRENT_DATA_VERSION,
+        dry_run,
+        migrations: vec![],
+    };
+    for migration in DATA_MIGRATIONS
+        .iter()
+        .filter(|migration| migration.get_target_version() > manifest.version)
+    {
+        info!(
+            "running data migration {:?} : {:?}. dry run : {:?}",
+            migration.get_target_version(),
+            migration.get_description(),
+            dry_run
+        );
+        let changes = migration.run(io_interface, dry_run).await?;
+        if !dry_run {
+            io_interface
+                .write_value(
+                    DATA_MANIFEST_FILE_PATH.to_string(),
+                    DataManifest::new(migration.get_target_version()).serialize_for_disk(),
+                )
+                .await?;
+        }
+        report.migrations.push((*migration, changes));
+    }
+    Ok(report)
+}
+
+/// Returns the block file in the current format, or None if it's already in the current format. The checksum of a
+/// file without a footer is calculated from its contents, so the structure of the block is verified first. Legacy
+/// files get the utxoset commitment added to the block header
+fn migrate_block_file_buffer(buffer: &[u8]) -> Result<Option<(u8, Vec<u8>)>, Error> {
+    let length = Storage::verify_block_file(buffer)?;
+    let (version, block_buffer) = read_format_envelope(&buffer[..length])?;
+    if version == CURRENT_FORMAT_VERSION && length < buffer.len() {
+        return Ok(None);
+    }
+    let mut migrated = get_format_envelope(CURRENT_FORMAT_VERSION);
+    if version == LEGACY_FORMAT_VERSION {
+        migrated.extend(Block::upgrade_legacy_net_buffer(block_buffer)?);
+    } else {
+        migrated.extend_from_slice(block_buffer);
+    }
+    let checksum = hash(&migrated);
+    migrated.extend(Storage::create_block_file_footer(&checksum));
+    Ok(Some((version, migrated)))
+}
+
+async fn migrate_block_files(
+    io_interface: &mut (dyn InterfaceIO + Send + Sync),
+    dry_run: bool,
+) -> Result<Vec<String>, Error> {
+    let block_dir = io_interface.get_block_dir();
+    let mut file_names = match io_interface.load_block_file_list().await {
+        Ok(file_names) => file_names,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(error),
+    };
+    file_names.sort();
+
+    let mut changes = vec![];
+    for file_name in file_names {
+        let path = block_dir.clone() + file_name.as_str();
+        let buffer = io_interface.read_value(path.clone()).await?;
+        match migrate_block_file_buffer(&buffer) {
+            Ok(None) => {}
+            Ok(Some((version, migrated))) => {
+                changes.push(format!(
+                    "{} : format version {} -> {}",
+                    file_name, version, CURRENT_FORMAT_VERSION
+                ));
+                if !dry_run {
+                    // replaced with a rename, so an interrupted migration doesn't leave a partial block file
+                    let temp_path = path.clone() + TEMP_FILE_EXTENSION;
+                    io_interface
+                        .write_value(temp_path.clone(), migrated)
+                        .await?;
+                    io_interface.rename_value(temp_path, path).await?;
+                }
+            }
+            Err(_) => {
+                // quarantined when the blocks are loaded
+                changes.push(format!("{} : corrupted. skipped", file_name));
+            }
+        }
+    }
+    Ok(changes)
+}
+
+fn is_valid_transaction_index_size(size: u64) -> bool {
+    size % TRANSACTION_INDEX_ENTRY_SIZE as u64 == 0
+}
+
+async fn migrate_transaction_index_file(
+    io_interface: &mut (dyn InterfaceIO + Send + Sync),
+    dry_run: bool,
+) -> Result<Vec<String>, Error> {
+    let path = TRANSACTION_INDEX_FILE_PATH.to_string();
+    if !io_interface.is_existing_file(path.clone()).await {
+        return Ok(vec![]);
+    }
+    let size = io_interface.get_value_size(path.clone()).await?;
+    if is_valid_transaction_index_size(size) {
+        return Ok(vec![]);
+    }
+    if !dry_run {
+        io_interface.remove_value(path.clone()).await?;
+    }
+    Ok(vec![format!(
+        "{} : invalid size {}. removed",
+        TRANSACTION_INDEX_FILE_PATH, size
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::test_manager::test::TestManager;
+    use crate::core::data::block::{Block, BlockType, BLOCK_HEADER_SIZE, LEGACY_BLOCK_HEADER_SIZE};
+    use crate::core::data::data_migration::{
+        is_valid_transaction_index_size, migrate_block_file_buffer, migrate_block_files,
+        DataManifest, DataMigration, DataMigrationReport, CURRENT_DATA_VERSION, DATA_MIGRATIONS,
+    };
+    use crate::core::data::format_version::{
+        get_format_envelope, CURRENT_FORMAT_VERSION, LEGACY_FORMAT_VERSION,
+    };
+    use crate::core::data::storage::{Storage, BLOCK_FILE_FOOTER_SIZE};
+
+    #[test]
+    fn data_manifest_test() {
+        let manifest = DataManifest::new(CURRENT_DATA_VERSION);
+        assert_eq!(
+            DataManifest::deserialize_from_disk(&manifest.serialize_for_disk()).unwrap(),
+            manifest
+        );
+        assert_eq!(
+            DataManifest::deserialize_from_disk(b"version=1\n")
+                .unwrap()
+                .version,
+            1
+        );
+        assert!(DataManifest::deserialize_from_disk(b"node_version=1.0\n").is_err());
+        assert!(DataManifest::deserialize_from_disk(b"version=a\n").is_err());
+
+        // migrations are ordered and the last one brings the data to the current version
+        for pair in DATA_MIGRATIONS.windows(2) {
+            assert!(pair[0].get_target_version() < pair[1].get_target_version());
+        }
+        assert_eq!(
+            DATA_MIGRATIONS.last().unwrap().get_target_version(),
+            CURRENT_DATA_VERSION
+        );
+
+        let report = DataMigrationReport {
+            from_version: 0,
+            to_version: 2,
+            dry_run: true,
+            migrations: vec![
+                (
+                    DataMigration::BlockFileFormat,
+                    vec!["1-aa.sai : format version 0 -> 1".to_string()],
+                ),
+                (DataMigration::TransactionIndexFile, vec![]),
+            ],
+        };
+        assert_eq!(report.get_change_count(), 1);
+        assert_eq!(
+            report.get_lines(),
+            vec![
+                "data version : 0 -> 2 (dry run)",
+                "migration 1 : rewrite block files in the current format : 1 changes",
+                "  1-aa.sai : format version 0 -> 1",
+                "migration 2 : remove invalid transaction index file : 0 changes",
+            ]
+        );
+    }
+
+    #[test]
+    fn migrate_block_file_test() {
+        let mut block = Block::new();
+        block.id = 5;
+        block.generate();
+        let legacy_buffer = block
+            .serialize_for_net_with_version(BlockType::Full, LEGACY_FORMAT_VERSION)
+            .unwrap();
+
+        let (version, migrated) = migrate_block_file_buffer(&legacy_buffer).unwrap().unwrap();
+        assert_eq!(version, LEGACY_FORMAT_VERSION);
+        assert!(migrated.starts_with(&get_format_envelope(CURRENT_FORMAT_VERSION)));
+        // the header gets the utxoset commitment
+        assert_eq!(
+            migrated.len(),
+            get_format_envelope(CURRENT_FORMAT_VERSION).len()
+                + legacy_buffer.len()
+                + BLOCK_HEADER_SIZE
+                - LEGACY_BLOCK_HEADER_SIZE
+                + BLOCK_FILE_FOOTER_SIZE
+        );
+        assert_eq!(
+            Storage::verify_block_file(&migrated).unwrap(),
+            migrated.len() - BLOCK_FILE_FOOTER_SIZE
+        );
+        let mut loaded =
+            Block::deserialize_from_disk(&migrated[..migrated.len() - BLOCK_FILE_FOOTER_SIZE]);
+        loaded.generate();
+        assert_eq!(loaded.hash, block.hash);
+
+        // files in the current format are not rewritten
+        assert!(migrate_block_file_buffer(&migrated).unwrap().is_none());
+        // a file in the current format without a checksum gets one
+        let (version, with_footer) =
+            migrate_block_file_buffer(&migrated[..migrated.len() - BLOCK_FILE_FOOTER_SIZE])
+                .unwrap()
+                .unwrap();
+        assert_eq!(version, CURRENT_FORMAT_VERSION);
+        assert_eq!(with_footer, migrated);
+
+        assert!(migrate_block_file_buffer(&legacy_buffer[..legacy_buffer.len() - 1]).is_err());
+
+        assert!(is_valid_transaction_index_size(0));
+        assert!(is_valid_transaction_index_size(224));
+        assert!(!is_valid_transaction_index_size(223));
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn migrate_legacy_block_files_test() {
+        let mut t = TestManager::new();
+        t.initialize(100, 100_000_000).await;
+
+        let mut block = Block::new();
+        block.id = 5;
+        block.timestamp = 1000;
+        block.generate();
+        let path = t.storage.generate_block_filename(&block);
+        let file_name = path
+            .trim_start_matches(t.storage.io_interface.get_block_dir().as_str())
+            .to_string();
+        let legacy_buffer = block.serialize_legacy_for_net(BlockType::Full).unwrap();
+        t.storage
+            .io_interface
+            .write_value(path.clone(), legacy_buffer.clone())
+            .await
+            .unwrap();
+
+        // legacy files are reported as migrated in a dry run instead of as corrupted
+        let changes = migrate_block_files(t.storage.io_interface.as_mut(), true)
+            .await
+            .unwrap();
+        assert_eq!(
+            changes,
+            vec![file_name.clone() + " : format version 0 -> 1"]
+        );
+        assert_eq!(
+            t.storage
+                .io_interface
+                .read_value(path.clone())
+                .await
+                .unwrap(),
+            legacy_buffer
+        );
+
+        let changes = migrate_block_files(t.storage.io_interface.as_mut(), false)
+            .await
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+        let mut loaded = t.storage.load_block_from_disk(path).await.unwrap();
+        loaded.generate();
+        assert_eq!(loaded.hash, block.hash);
+        assert!(migrate_block_files(t.storage.io_interface.as_mut(), true)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt::Debug;
+
+use crate::core::data::configuration::DifficultyConfig;
+
+/// Decides the golden ticket difficulty of a block based on its parent.
+///
+/// Used both when creating and validating blocks, so all the nodes of a network must run the same policy.
+pub trait DifficultyPolicy: Debug + Send + Sync {
+    fn get_expected_difficulty(
+        &self,
+        previous_difficulty: u64,
+        previous_has_golden_ticket: bool,
+        gt_num: u8,
+    ) -> u64;
+}
+
+/// Consensus rules of the main network. Difficulty only changes if the previous block had a golden ticket.
+/// It increases if this block also has one and decreases otherwise.
+#[derive(Debug, Default)]
+pub struct ConsensusDifficultyPolicy;
+
+impl DifficultyPolicy for ConsensusDifficultyPolicy {
+    fn get_expected_difficulty(
+        &self,
+        previous_difficulty: u64,
+        previous_has_golden_ticket: bool,
+        gt_num: u8,
+    ) -> u64 {
+        if previous_has_golden_ticket && gt_num == 0 {
+            if previous_difficulty > 0 {
+                previous_difficulty - 1
+            } else {
+                // kept from the original consensus values default
+                1
+            }
+        } else if previous_has_golden_ticket && gt_num > 0 {
+            previous_difficulty + 1
+        } else {
+            previous_difficulty
+        }
+    }
+}
+
+/// Keeps the difficulty constant. Intended for test networks only.
+#[derive(Debug)]
+pub struct FixedDifficultyPolicy {
+    pub difficulty: u64,
+}
+
+impl DifficultyPolicy for FixedDifficultyPolicy {
+    fn get_expected_difficulty(&self, _: u64, _: bool, _: u8) -> u64 {
+        self.difficulty
+    }
+}
+
+/// Adjusts the difficulty on every block within the given bounds, so test networks can reach a
+/// difficulty where golden tickets are found quickly. Intended for test networks only.
+#[derive(Debug)]
+pub struct RapidDifficultyPolicy {
+    pub min_difficulty: u64,
+    pub max_difficulty: u64,
+}
+
+impl DifficultyPolicy for RapidDifficultyPolicy {
+    fn get_expected_difficulty(
+        &self,
+        previous_difficulty: u64,
+        _previous_has_golden_ticket: bool,
+        gt_num: u8,
+    ) -> u64 {
+        let difficulty = if gt_num > 0 {
+            previous_difficulty.saturating_add(1)
+        } else {
+            previous_difficulty.saturating_sub(1)
+        };
+        difficulty.clamp(
+            self.min_difficulty,
+            self.max_difficulty.max(self.min_difficulty),
+        )
+    }
+}
+
+pub fn create_difficulty_policy(config: &DifficultyConfig) -> Box<dyn DifficultyPolicy> {
+    match config {
+        DifficultyConfig::Consensus => Box::new(ConsensusDifficultyPolicy),
+        DifficultyConfig::Fixed { difficulty } => Box::new(FixedDifficultyPolicy {
+            difficulty: *difficulty,
+        }),
+        DifficultyConfig::Rapid {
+            min_difficulty,
+            max_difficulty,
+        } => Box::new(RapidDifficultyPolicy {
+            min_difficulty: *min_difficulty,
+            max_difficulty: *max_difficulty,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consensus_policy_test() {
+        let policy = ConsensusDifficultyPolicy;
+        assert_eq!(policy.get_expected_difficulty(5, false, 0), 5);
+        assert_eq!(policy.get_expected_difficulty(5, false, 1), 5);
+        assert_eq!(policy.get_expected_difficulty(5, true, 0), 4);
+        assert_eq!(policy.get_expected_difficulty(5, true, 1), 6);
+        assert_eq!(policy.get_expected_difficulty(0, true, 0), 1);
+    }
+
+    #[test]
+    fn test_network_policies_test() {
+        let policy = create_difficulty_policy(&DifficultyConfig::Fixed { difficulty: 2 });
+        assert_eq!(policy.get_expected_difficulty(5, true, 1), 2);
+        assert_eq!(policy.get_expected_difficulty(0, false, 0), 2);
+
+        let policy = create_difficulty_policy(&DifficultyConfig::Rapid {
+            min_difficulty: 1,
+            max_difficulty: 3,
+        });
+        assert_eq!(policy.get_expected_difficulty(2, false, 1), 3);
+        assert_eq!(policy.get_expected_difficulty(3, true, 1), 3);
+        assert_eq!(policy.get_expected_difficulty(2, true, 0), 1);
+        assert_eq!(policy.get_expected_difficulty(1, false, 0), 1);
+        // difficulty outside the bounds is moved back within them
+        assert_eq!(policy.get_expected_difficulty(10, false, 0), 3);
+    }
+}
@@ -0,0 +1,246 @@
+use std::fmt::Write;
+
+use ahash::AHashSet;
+
+use crate::common::defs::{BlockId, SaitoHash};
+use crate::core::data::block::Block;
+use crate::core::data::blockchain_snapshot::BlockSummary;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForkGraphFormat {
+    /// graphviz dot
+    Dot,
+    Json,
+}
+
+impl ForkGraphFormat {
+    /// json is used for ".json" files, dot otherwise
+    pub fn from_path(path: &str) -> ForkGraphFormat {
+        if path.to_lowercase().ends_with(".json") {
+            ForkGraphFormat::Json
+        } else {
+            ForkGraphFormat::Dot
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForkGraphBlock {
+    pub summary: BlockSummary,
+    pub in_longest_chain: bool,
+}
+
+/// Blocks in a range linked to their previous blocks, including the forks which are not pruned yet. Written as a
+/// graphviz dot or json file to see the fork structure when debugging reorgs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForkGraph {
+    pub from_block_id: BlockId,
+    pub to_block_id: BlockId,
+    /// ordered by the block id, then the block hash
+    pub blocks: Vec<ForkGraphBlock>,
+}
+
+impl ForkGraph {
+    /// `blocks` should be generated before calling this, since the golden tickets are found while generating.
+    /// `longest_chain` has the hashes of the longest chain blocks
+    pub fn generate(blocks: &[Block], longest_chain: &AHashSet<SaitoHash>) -> ForkGraph {
+        let mut blocks: Vec<ForkGraphBlock> = blocks
+            .iter()
+            .map(|block| ForkGraphBlock {
+                summary: BlockSummary::new(block),
+                in_longest_chain: longest_chain.contains(&block.hash),
+            })
+            .collect();
+        blocks.sort_by(|a, b| (a.summary.id, a.summary.hash).cmp(&(b.summary.id, b.summary.hash)));
+        ForkGraph {
+            from_block_id: blocks
+                .first()
+                .map(|block| block.summary.id)
+                .unwrap_or_default(),
+            to_block_id: blocks
+                .last()
+                .map(|block| block.summary.id)
+                .unwrap_or_default(),
+            blocks,
+        }
+    }
+
+    /// number of blocks which are not in the longest chain
+    pub fn get_fork_block_count(&self) -> usize {
+        self.blocks
+            .iter()
+            .filter(|block| !block.in_longest_chain)
+            .count()
+    }
+
+    pub fn serialize(&self, format: ForkGraphFormat) -> Vec<u8> {
+        match format {
+            ForkGraphFormat::Dot => self.to_dot(),
+            ForkGraphFormat::Json => self.to_json(),
+        }
+        .into_bytes()
+    }
+
+    /// Longest chain blocks are filled and blocks with a golden ticket have a double border. Edges are only drawn to
+    /// the previous blocks in the range
+    pub fn to_dot(&self) -> String {
+        let hashes: AHashSet<SaitoHash> =
+            self.blocks.iter().map(|block| block.summary.hash).collect();
+        let mut dot = String::new();
+        writeln!(dot, "digraph saito_forks {{").unwrap();
+        writeln!(dot, "  rankdir=LR;").unwrap();
+        writeln!(dot, "  node [shape=box, fontname=\"monospace\"];").unwrap();
+        for block in self.blocks.iter() {
+            let summary = &block.summary;
+            writeln!(
+                dot,
+                "  \"{}\" [label=\"{} : {}\\ncreator : {}\\nburnfee : {}\", style=\"{}\", peripheries={}];",
+                hex::encode(summary.hash),
+                summary.id,
+                &hex::encode(summary.hash)[..16],
+                &hex::encode(summary.creator)[..16],
+                summary.burnfee,
+                if block.in_longest_chain {
+                    "filled"
+                } else {
+                    "dashed"
+                },
+                if summary.has_golden_ticket { 2 } else { 1 }
+            )
+            .unwrap();
+        }
+        for block in self.blocks.iter() {
+            let summary = &block.summary;
+            if hashes.contains(&summary.previous_block_hash) {
+                writeln!(
+                    dot,
+                    "  \"{}\" -> \"{}\";",
+                    hex::encode(summary.previous_block_hash),
+                    hex::encode(summary.hash)
+                )
+                .unwrap();
+            }
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+    /// amounts are written as strings since they can exceed the integer range of json parsers
+    pub fn to_json(&self) -> String {
+        let blocks: Vec<String> = self
+            .blocks
+            .iter()
+            .map(|block| {
+                let summary = &block.summary;
+                format!(
+                    "{{\"id\":{},\"hash\":\"{}\",\"previous_block_hash\":\"{}\",\"timestamp\":{},\
+                    \"creator\":\"{}\",\"burnfee\":\"{}\",\"difficulty\":{},\"has_golden_ticket\":{},\
+                    \"in_longest_chain\":{}}}",
+                    summary.id,
+                    hex::encode(summary.hash),
+                    hex::encode(summary.previous_block_hash),
+                    summary.timestamp,
+                    hex::encode(summary.creator),
+                    summary.burnfee,
+                    summary.difficulty,
+                    summary.has_golden_ticket,
+                    block.in_longest_chain
+                )
+            })
+            .collect();
+        format!(
+            "{{\"from_block_id\":{},\"to_block_id\":{},\"blocks\":[{}]}}",
+            self.from_block_id,
+            self.to_block_id,
+            blocks.join(",")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ahash::AHashSet;
+
+    use crate::core::data::block::Block;
+    use crate::core::data::fork_graph::{ForkGraph, ForkGraphFormat};
+
+    fn create_block(id: u64, previous_block_hash: [u8; 32], timestamp: u64) -> Block {
+        let mut block = Block::new();
+        block.id = id;
+        block.previous_block_hash = previous_block_hash;
+        block.timestamp = timestamp;
+        block.burnfee = 1000 * id;
+        block.generate();
+        block
+    }
+
+    #[test]
+    fn fork_graph_test() {
+        assert_eq!(
+            ForkGraphFormat::from_path("forks.JSON"),
+            ForkGraphFormat::Json
+        );
+        assert_eq!(
+            ForkGraphFormat::from_path("forks.dot"),
+            ForkGraphFormat::Dot
+        );
+
+        let block_1 = create_block(1, [0; 32], 1000);
+        let block_2 = create_block(2, block_1.hash, 2000);
+        let block_3 = create_block(3, block_2.hash, 3000);
+        // fork from block 1 which lost to block 2
+        let fork_2 = create_block(2, block_1.hash, 2500);
+        let longest_chain: AHashSet<[u8; 32]> = [block_1.hash, block_2.hash, block_3.hash]
+            .into_iter()
+            .collect();
+
+        let graph = ForkGraph::generate(
+            &[
+                block_3.clone(),
+                fork_2.clone(),
+                block_2.clone(),
+                block_1.clone(),
+            ],
+            &longest_chain,
+        );
+        assert_eq!(graph.from_block_id, 1);
+        assert_eq!(graph.to_block_id, 3);
+        assert_eq!(graph.blocks.len(), 4);
+        assert_eq!(graph.blocks[0].summary.hash, block_1.hash);
+        assert_eq!(graph.blocks[3].summary.hash, block_3.hash);
+        assert_eq!(graph.get_fork_block_count(), 1);
+        let fork = graph
+            .blocks
+            .iter()
+            .find(|block| block.summary.hash == fork_2.hash)
+            .unwrap();
+        assert!(!fork.in_longest_chain);
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph saito_forks {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains(&format!(
+            "\"{}\" -> \"{}\";",
+            hex::encode(block_1.hash),
+            hex::encode(fork_2.hash)
+        )));
+        assert!(dot.contains(&format!(
+            "\"{}\" -> \"{}\";",
+            hex::encode(block_2.hash),
+            hex::encode(block_3.hash)
+        )));
+        // block 1's parent is not in the range
+        assert_eq!(dot.matches(" -> ").count(), 3);
+        assert_eq!(dot.matches("style=\"dashed\"").count(), 1);
+
+        let json = graph.to_json();
+        assert!(json.starts_with("{\"from_block_id\":1,\"to_block_id\":3,\"blocks\":[{\"id\":1,"));
+        assert!(json.contains(&format!(
+            "\"hash\":\"{}\",\"previous_block_hash\":\"{}\",\"timestamp\":2500,",
+            hex::encode(fork_2.hash),
+            hex::encode(block_1.hash)
+        )));
+        assert!(json.contains("\"burnfee\":\"2000\""));
+        assert_eq!(json.matches("\"in_longest_chain\":false").count(), 1);
+    }
+}
@@ -0,0 +1,97 @@
+use std::io::{Error, ErrorKind};
+
+use tracing::warn;
+
+/// Layout of the blocks and transactions written before the formats were versioned. These buffers don't have an
+/// envelope, and the block header doesn't have the utxoset commitment
+pub const LEGACY_FORMAT_VERSION: u8 = 0;
+/// version written by this node. transactions have the legacy layout inside the envelope, while the block header
+/// has the utxoset commitment added at the end
+pub const CURRENT_FORMAT_VERSION: u8 = 1;
+/// oldest version this node can read
+pub const MIN_SUPPORTED_FORMAT_VERSION: u8 = LEGACY_FORMAT_VERSION;
+/// first bytes of a versioned buffer. a legacy block or transaction can't start with these, since it would have
+/// billions of transactions or inputs
+pub const FORMAT_ENVELOPE_MAGIC: [u8; 2] = [0xFF, 0x56];
+/// magic (2) + version (1)
+pub const FORMAT_ENVELOPE_SIZE: usize = 3;
+
+pub fn is_supported_format_version(version: u8) -> bool {
+    (MIN_SUPPORTED_FORMAT_VERSION..=CURRENT_FORMAT_VERSION).contains(&version)
+}
+
+/// Version used with a peer which advertised the given version in the handshake. Peers without the versioned
+/// handshake can't connect, so the legacy format is only read from the block files written before it
+pub fn negotiate_format_version(peer_version: u8) -> u8 {
+    peer_version.min(CURRENT_FORMAT_VERSION)
+}
+
+/// Envelope written before the serialized block or transaction. Empty for the legacy version
+pub fn get_format_envelope(version: u8) -> Vec<u8> {
+    if version == LEGACY_FORMAT_VERSION {
+        return vec![];
+    }
+    [FORMAT_ENVELOPE_MAGIC.as_slice(), &[version]].concat()
+}
+
+/// Returns the format version of the buffer and the serialized block or transaction inside the envelope. Buffers
+/// without an envelope are read as the legacy version
+pub fn read_format_envelope(buffer: &[u8]) -> Result<(u8, &[u8]), Error> {
+    if buffer.len() < FORMAT_ENVELOPE_SIZE || buffer[0..2] != FORMAT_ENVELOPE_MAGIC {
+        return Ok((LEGACY_FORMAT_VERSION, buffer));
+    }
+    let version = buffer[2];
+    if !is_supported_format_version(version) {
+        warn!(
+            "format version : {:?} is not supported. supported versions : {:?} to {:?}",
+            version, MIN_SUPPORTED_FORMAT_VERSION, CURRENT_FORMAT_VERSION
+        );
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+    Ok((version, &buffer[FORMAT_ENVELOPE_SIZE..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::format_version::{
+        get_format_envelope, negotiate_format_version, read_format_envelope,
+        CURRENT_FORMAT_VERSION, FORMAT_ENVELOPE_MAGIC, LEGACY_FORMAT_VERSION,
+    };
+
+    #[test]
+    fn format_envelope_test() {
+        let payload = vec![0, 0, 0, 2, 1, 2, 3];
+
+        let buffer = [get_format_envelope(CURRENT_FORMAT_VERSION), payload.clone()].concat();
+        let (version, result) = read_format_envelope(&buffer).unwrap();
+        assert_eq!(version, CURRENT_FORMAT_VERSION);
+        assert_eq!(result, payload.as_slice());
+
+        // buffers written before the envelope was added
+        assert!(get_format_envelope(LEGACY_FORMAT_VERSION).is_empty());
+        let (version, result) = read_format_envelope(&payload).unwrap();
+        assert_eq!(version, LEGACY_FORMAT_VERSION);
+        assert_eq!(result, payload.as_slice());
+
+        let buffer = [
+            FORMAT_ENVELOPE_MAGIC.as_slice(),
+            &[CURRENT_FORMAT_VERSION + 1],
+            &payload,
+        ]
+        .concat();
+        assert!(read_format_envelope(&buffer).is_err());
+    }
+
+    #[test]
+    fn negotiate_format_version_test() {
+        assert_eq!(
+            negotiate_format_version(LEGACY_FORMAT_VERSION),
+            LEGACY_FORMAT_VERSION
+        );
+        assert_eq!(
+            negotiate_format_version(CURRENT_FORMAT_VERSION),
+            CURRENT_FORMAT_VERSION
+        );
+        assert_eq!(negotiate_format_version(u8::MAX), CURRENT_FORMAT_VERSION);
+    }
+}
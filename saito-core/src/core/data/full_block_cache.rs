@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+use ahash::AHashMap;
+
+use crate::common::defs::SaitoHash;
+
+/// Tracks the full blocks kept in memory in the order they were last accessed, so the least recently
+/// accessed blocks can be downgraded first. Accessing, removing and evicting a block are O(log n).
+#[derive(Debug, Default)]
+pub struct FullBlockCache {
+    // block hashes by access sequence, least recently accessed first
+    access_order: BTreeMap<u64, SaitoHash>,
+    // access sequence of each block hash
+    access_sequences: AHashMap<SaitoHash, u64>,
+    next_sequence: u64,
+}
+
+impl FullBlockCache {
+    /// Marks the block as the most recently accessed one
+    pub fn touch(&mut self, block_hash: SaitoHash) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        if let Some(previous) = self.access_sequences.insert(block_hash, sequence) {
+            self.access_order.remove(&previous);
+        }
+        self.access_order.insert(sequence, block_hash);
+    }
+
+    pub fn remove(&mut self, block_hash: &SaitoHash) {
+        if let Some(sequence) = self.access_sequences.remove(block_hash) {
+            self.access_order.remove(&sequence);
+        }
+    }
+
+    /// Removes and returns the least recently accessed block hash
+    pub fn pop_least_recent(&mut self) -> Option<SaitoHash> {
+        let sequence = *self.access_order.keys().next()?;
+        let block_hash = self.access_order.remove(&sequence).unwrap();
+        self.access_sequences.remove(&block_hash);
+        Some(block_hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.access_order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.access_order.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.access_order.clear();
+        self.access_sequences.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_block_cache_test() {
+        let mut cache = FullBlockCache::default();
+        assert_eq!(cache.pop_least_recent(), None);
+
+        cache.touch([1; 32]);
+        cache.touch([2; 32]);
+        cache.touch([3; 32]);
+        cache.touch([1; 32]);
+        assert_eq!(cache.len(), 3);
+
+        cache.remove(&[3; 32]);
+        cache.remove(&[3; 32]);
+        assert_eq!(cache.len(), 2);
+
+        assert_eq!(cache.pop_least_recent(), Some([2; 32]));
+        assert_eq!(cache.pop_least_recent(), Some([1; 32]));
+        assert_eq!(cache.pop_least_recent(), None);
+        assert!(cache.is_empty());
+
+        cache.touch([4; 32]);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
@@ -1,9 +1,13 @@
 use std::convert::TryInto;
+use std::io::{Error, ErrorKind};
 
 use serde::{Deserialize, Serialize};
 
 use crate::common::defs::{SaitoHash, SaitoPublicKey};
-use crate::core::data::crypto::hash;
+use crate::core::data::crypto::{generate_random_bytes, hash};
+
+/// target (32) + random (32) + public key (33)
+pub const GOLDEN_TICKET_SIZE: usize = 97;
 
 #[serde_with::serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -33,14 +37,22 @@ impl GoldenTicket {
         GoldenTicket::new(previous_block_hash, random_bytes, public_key)
     }
 
-    pub fn deserialize_from_net(bytes: &Vec<u8>) -> GoldenTicket {
-        assert_eq!(bytes.len(), 97);
+    pub fn deserialize_from_net(bytes: &[u8]) -> GoldenTicket {
+        assert_eq!(bytes.len(), GOLDEN_TICKET_SIZE);
         let target: SaitoHash = bytes[0..32].try_into().unwrap();
         let random: SaitoHash = bytes[32..64].try_into().unwrap();
         let public_key: SaitoPublicKey = bytes[64..97].try_into().unwrap();
         GoldenTicket::new(target, random, public_key)
     }
 
+    /// Same as `deserialize_from_net`, but returns an error instead of panicking for buffers from peers
+    pub fn try_deserialize_from_net(bytes: &[u8]) -> Result<GoldenTicket, Error> {
+        if bytes.len() != GOLDEN_TICKET_SIZE {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        Ok(GoldenTicket::deserialize_from_net(bytes))
+    }
+
     pub fn serialize_for_net(&self) -> Vec<u8> {
         let vbytes: Vec<u8> = [
             self.target.as_slice(),
@@ -51,6 +63,19 @@ impl GoldenTicket {
         vbytes
     }
 
+    /// Tries random numbers until a golden ticket valid for the difficulty is found. Returns None if none of the
+    /// attempts are valid, so the caller can continue later without blocking for high difficulties
+    pub fn search(
+        target: SaitoHash,
+        difficulty: u64,
+        public_key: SaitoPublicKey,
+        max_attempts: u64,
+    ) -> Option<GoldenTicket> {
+        (0..max_attempts)
+            .map(|_| GoldenTicket::create(target, hash(&generate_random_bytes(32)), public_key))
+            .find(|gt| gt.validate(difficulty))
+    }
+
     // #[tracing::instrument(level = "trace", skip_all)]
     pub fn validate(&self, difficulty: u64) -> bool {
         let solution_hash = hash(&self.serialize_for_net());
@@ -114,6 +139,19 @@ mod tests {
         assert_eq!(gt.validate(0), true);
         assert_eq!(gt.validate(256), false);
     }
+    #[test]
+    fn golden_ticket_search_test() {
+        let wallet = Wallet::new();
+        let target = hash(&generate_random_bytes(32));
+
+        let gt = GoldenTicket::search(target, 4, wallet.public_key, 10_000).unwrap();
+        assert!(gt.validate(4));
+        assert_eq!(gt.target, target);
+        assert_eq!(gt.public_key, wallet.public_key);
+
+        assert!(GoldenTicket::search(target, 256, wallet.public_key, 10).is_none());
+    }
+
     #[test]
     fn gt_against_slr() {
         let buffer = hex::decode("844702489d49c7fb2334005b903580c7a48fe81121ff16ee6d1a528ad32f235e03bf1a4714cfc7ae33d3f6e860c23191ddea07bcb1bfa6c85bc124151ad8d4ce03cb14a56ddc769932baba62c22773aaf6d26d799b548c8b8f654fb92d25ce7610").unwrap();
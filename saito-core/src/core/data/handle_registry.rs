@@ -0,0 +1,288 @@
+use ahash::{AHashMap, AHashSet};
+use tracing::{debug, trace};
+
+use crate::common::defs::{BlockId, Currency, SaitoPublicKey};
+use crate::core::data::block::Block;
+use crate::core::data::transaction::Transaction;
+
+pub const MIN_HANDLE_LENGTH: usize = 3;
+pub const MAX_HANDLE_LENGTH: usize = 32;
+/// minimum fee of a handle registration, so handles can't be reserved in bulk for free
+pub const HANDLE_REGISTRATION_FEE: Currency = 100_000;
+
+/// Handles are lowercase ascii letters, digits, '-' and '_', and start with a letter or a digit
+pub fn is_valid_handle(handle: &str) -> bool {
+    handle.len() >= MIN_HANDLE_LENGTH
+        && handle.len() <= MAX_HANDLE_LENGTH
+        && handle
+            .chars()
+            .next()
+            .map(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+            .unwrap_or(false)
+        && handle
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// Handles registered in the longest chain. A handle belongs to the first key registering it, until a genesis period
+/// passes without the owner registering it again. Other keys can register it after that.
+///
+/// Registrations are collected from the blocks in memory, so the registry is rebuilt from the chain after a restart.
+#[derive(Debug, Clone)]
+pub struct HandleRegistry {
+    // accepted registrations of each handle in the longest chain, oldest first. the last one is the current owner
+    registrations: AHashMap<String, Vec<(BlockId, SaitoPublicKey)>>,
+    registration_period: BlockId,
+    latest_block_id: BlockId,
+}
+
+impl HandleRegistry {
+    pub fn new(registration_period: BlockId) -> HandleRegistry {
+        assert_ne!(registration_period, 0);
+        HandleRegistry {
+            registrations: Default::default(),
+            registration_period,
+            latest_block_id: 0,
+        }
+    }
+
+    /// Returns the owner of the handle at the latest block
+    pub fn resolve(&self, handle: &str) -> Option<SaitoPublicKey> {
+        self.get_owner_at(handle, self.latest_block_id)
+    }
+
+    /// Returns the block id at which the handle is released if the owner doesn't register it again
+    pub fn get_expiry_block_id(&self, handle: &str) -> Option<BlockId> {
+        self.resolve(handle)?;
+        let (block_id, _) = self.registrations.get(handle)?.last()?;
+        Some(block_id + self.registration_period)
+    }
+
+    /// Returns the handles owned by the key at the latest block
+    pub fn get_handles(&self, public_key: &SaitoPublicKey) -> Vec<String> {
+        let mut handles: Vec<String> = self
+            .registrations
+            .keys()
+            .filter(|handle| self.resolve(handle).as_ref() == Some(public_key))
+            .cloned()
+            .collect();
+        handles.sort();
+        handles
+    }
+
+    /// number of handles with an owner at the latest block
+    pub fn len(&self) -> usize {
+        self.registrations
+            .keys()
+            .filter(|handle| self.resolve(handle).is_some())
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get_owner_at(&self, handle: &str, block_id: BlockId) -> Option<SaitoPublicKey> {
+        let (registered_at, owner) = self.registrations.get(handle)?.last()?;
+        if registered_at + self.registration_period <= block_id {
+            return None;
+        }
+        Some(*owner)
+    }
+
+    /// Checks if the key can register the handle in the block with the given id. Owners can register their handles
+    /// again to keep them
+    pub fn can_register(&self, handle: &str, owner: &SaitoPublicKey, block_id: BlockId) -> bool {
+        match self.get_owner_at(handle, block_id) {
+            Some(current_owner) => current_owner == *owner,
+            None => true,
+        }
+    }
+
+    /// Returns the indices of the registrations which can't be included in the block with the given id. These
+    /// register a handle owned by another key, or a handle registered by another key earlier in the same block.
+    pub fn find_conflicting_registrations(
+        &self,
+        block_id: BlockId,
+        transactions: &[Transaction],
+    ) -> Vec<usize> {
+        let mut registered_in_block: AHashMap<&str, SaitoPublicKey> = Default::default();
+        let mut conflicts = vec![];
+        for (index, transaction) in transactions.iter().enumerate() {
+            let (handle, owner) = match transaction.get_handle_registration() {
+                Some(registration) => registration,
+                None => continue,
+            };
+            let available = match registered_in_block.get(handle) {
+                Some(registered_owner) => *registered_owner == owner,
+                None => self.can_register(handle, &owner, block_id),
+            };
+            if !available {
+                debug!(
+                    "handle : {:?} is not available for : {:?} in block : {:?}",
+                    handle,
+                    hex::encode(owner),
+                    block_id
+                );
+                conflicts.push(index);
+                continue;
+            }
+            registered_in_block.insert(handle, owner);
+        }
+        conflicts
+    }
+
+    /// called when the block is added to the longest chain
+    pub fn on_block_added(&mut self, block: &Block) {
+        self.latest_block_id = block.id;
+        for (handle, owner) in block
+            .transactions
+            .iter()
+            .filter_map(|tx| tx.get_handle_registration())
+        {
+            if !self.can_register(handle, &owner, block.id) {
+                continue;
+            }
+            trace!(
+                "handle : {:?} registered by : {:?} in block : {:?}",
+                handle,
+                hex::encode(owner),
+                block.id
+            );
+            let registrations = self.registrations.entry(handle.to_string()).or_default();
+            if registrations.last() != Some(&(block.id, owner)) {
+                registrations.push((block.id, owner));
+            }
+        }
+        if block.id % self.registration_period == 0 {
+            self.remove_expired(block.id);
+        }
+    }
+
+    /// called when the block is removed from the longest chain. handles go back to their owners before the block
+    pub fn on_block_removed(&mut self, block: &Block) {
+        let handles: AHashSet<&str> = block
+            .transactions
+            .iter()
+            .filter_map(|tx| tx.get_handle_registration())
+            .map(|(handle, _)| handle)
+            .collect();
+        for handle in handles {
+            if let Some(registrations) = self.registrations.get_mut(handle) {
+                registrations.retain(|(block_id, _)| *block_id != block.id);
+                if registrations.is_empty() {
+                    self.registrations.remove(handle);
+                }
+            }
+        }
+        self.latest_block_id = block.id.saturating_sub(1);
+    }
+
+    /// Removes the handles released more than a genesis period ago. Blocks older than that are not rolled back, so
+    /// these registrations are not needed anymore
+    fn remove_expired(&mut self, current_block_id: BlockId) {
+        let period = self.registration_period;
+        self.registrations.retain(|_, registrations| {
+            registrations
+                .last()
+                .map(|(block_id, _)| block_id + 2 * period > current_block_id)
+                .unwrap_or(false)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::block::Block;
+    use crate::core::data::handle_registry::{is_valid_handle, HandleRegistry};
+    use crate::core::data::slip::Slip;
+    use crate::core::data::transaction::{Transaction, TransactionType};
+
+    fn create_registration(handle: &str, public_key: u8) -> Transaction {
+        let mut tx = Transaction::default();
+        tx.transaction_type = TransactionType::HandleRegistration;
+        let mut input = Slip::default();
+        input.public_key = [public_key; 33];
+        tx.add_input(input);
+        tx.message = handle.as_bytes().to_vec();
+        tx
+    }
+
+    fn create_block(id: u64, transactions: Vec<Transaction>) -> Block {
+        let mut block = Block::new();
+        block.id = id;
+        block.transactions = transactions;
+        block
+    }
+
+    #[test]
+    fn is_valid_handle_test() {
+        assert!(is_valid_handle("alice"));
+        assert!(is_valid_handle("bob_2-x"));
+        assert!(is_valid_handle("123"));
+        assert!(!is_valid_handle("al"));
+        assert!(!is_valid_handle("Alice"));
+        assert!(!is_valid_handle("_alice"));
+        assert!(!is_valid_handle("alice bob"));
+        assert!(!is_valid_handle("alicé"));
+        assert!(!is_valid_handle(&"a".repeat(33)));
+    }
+
+    #[test]
+    fn handle_registry_test() {
+        let mut registry = HandleRegistry::new(10);
+        assert!(registry.resolve("alice").is_none());
+
+        // first registration in the block wins
+        let block_1 = create_block(
+            1,
+            vec![
+                create_registration("alice", 1),
+                create_registration("alice", 2),
+                create_registration("bob", 2),
+                create_registration("x", 3),
+            ],
+        );
+        assert_eq!(
+            registry.find_conflicting_registrations(1, &block_1.transactions),
+            vec![1]
+        );
+        registry.on_block_added(&block_1);
+        assert_eq!(registry.resolve("alice"), Some([1; 33]));
+        assert_eq!(registry.resolve("bob"), Some([2; 33]));
+        assert_eq!(registry.get_handles(&[2; 33]), vec!["bob".to_string()]);
+        assert_eq!(registry.get_expiry_block_id("alice"), Some(11));
+        assert_eq!(registry.len(), 2);
+
+        // owner renews the handle
+        let block_2 = create_block(5, vec![create_registration("alice", 1)]);
+        assert!(registry
+            .find_conflicting_registrations(5, &block_2.transactions)
+            .is_empty());
+        registry.on_block_added(&block_2);
+        assert_eq!(registry.get_expiry_block_id("alice"), Some(15));
+
+        // bob's handle is released after the registration period
+        let block_3 = create_block(
+            11,
+            vec![
+                create_registration("bob", 3),
+                create_registration("alice", 3),
+            ],
+        );
+        assert_eq!(
+            registry.find_conflicting_registrations(11, &block_3.transactions),
+            vec![1]
+        );
+        registry.on_block_added(&block_3);
+        assert_eq!(registry.resolve("bob"), Some([3; 33]));
+        assert_eq!(registry.resolve("alice"), Some([1; 33]));
+
+        registry.on_block_removed(&block_3);
+        assert_eq!(registry.resolve("bob"), Some([2; 33]));
+        registry.on_block_removed(&block_2);
+        assert_eq!(registry.get_expiry_block_id("alice"), Some(11));
+        registry.on_block_removed(&block_1);
+        assert!(registry.is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+use tracing::info;
+
+use crate::common::defs::{Currency, SaitoPublicKey};
+use crate::core::consensus::amounts::{checked_sum_amounts, is_valid_amount};
+use crate::core::data::blockchain::MAX_TOKEN_SUPPLY;
+use crate::core::data::crypto::PublicKey;
+use crate::core::data::slip::{Slip, SlipType};
+use crate::core::data::transaction::{Transaction, TransactionType};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssuanceError {
+    /// line number (starting from 1) which is not a public key and an amount
+    InvalidLine {
+        line: usize,
+    },
+    InvalidPublicKey {
+        line: usize,
+    },
+    /// amounts should be greater than 0
+    InvalidAmount {
+        line: usize,
+    },
+    NoSlips,
+    /// total of the amounts is larger than the token supply
+    ExceedsTokenSupply,
+}
+
+/// Token distribution of a new network, issued to the keys in the genesis block.
+///
+/// Read from a text file with a hex encoded public key and an amount in nolan on each line, separated by whitespace.
+/// Empty lines and lines starting with `#` are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issuance {
+    pub slips: Vec<(SaitoPublicKey, Currency)>,
+}
+
+impl Issuance {
+    pub fn parse(content: &str) -> Result<Issuance, IssuanceError> {
+        let mut slips = vec![];
+        for (index, line) in content.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let (public_key, amount) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(public_key), Some(amount), None) => (public_key, amount),
+                _ => return Err(IssuanceError::InvalidLine { line: line_number }),
+            };
+            let public_key: SaitoPublicKey = hex::decode(public_key)
+                .ok()
+                .and_then(|key| key.try_into().ok())
+                .filter(|key: &SaitoPublicKey| PublicKey::from_slice(key).is_ok())
+                .ok_or(IssuanceError::InvalidPublicKey { line: line_number })?;
+            let amount: Currency = amount
+                .parse()
+                .ok()
+                .filter(|amount| *amount > 0 && is_valid_amount(*amount))
+                .ok_or(IssuanceError::InvalidAmount { line: line_number })?;
+            slips.push((public_key, amount));
+        }
+        let issuance = Issuance { slips };
+        if issuance.slips.is_empty() {
+            return Err(IssuanceError::NoSlips);
+        }
+        issuance
+            .get_total()
+            .ok_or(IssuanceError::ExceedsTokenSupply)?;
+        Ok(issuance)
+    }
+
+    /// None if the total is larger than the token supply
+    pub fn get_total(&self) -> Option<Currency> {
+        checked_sum_amounts(self.slips.iter().map(|(_, amount)| *amount))
+    }
+
+    /// Issuance transaction of the genesis block. Needs to be signed by the node creating the block
+    pub fn create_transaction(&self) -> Transaction {
+        let total = self.get_total().unwrap_or(Currency::MAX);
+        info!(
+            "issuing : {:?} nolan to {:?} keys. {:?} nolan of the token supply is not issued",
+            total,
+            self.slips.len(),
+            MAX_TOKEN_SUPPLY.saturating_sub(total)
+        );
+        let mut transaction = Transaction::default();
+        transaction.transaction_type = TransactionType::Issuance;
+        for (public_key, amount) in self.slips.iter() {
+            let mut output = Slip::default();
+            output.public_key = *public_key;
+            output.amount = *amount;
+            output.slip_type = SlipType::Normal;
+            transaction.add_output(output);
+        }
+        transaction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::blockchain::MAX_TOKEN_SUPPLY;
+    use crate::core::data::issuance::{Issuance, IssuanceError};
+    use crate::core::data::transaction::TransactionType;
+    use crate::core::data::wallet::Wallet;
+
+    #[test]
+    fn parse_issuance_test() {
+        let key_1 = hex::encode(Wallet::new().public_key);
+        let key_2 = hex::encode(Wallet::new().public_key);
+
+        let content = format!(
+            "# early supporters\n{} 100000000\n\n  {}\t{}\n",
+            key_1,
+            key_2,
+            MAX_TOKEN_SUPPLY - 100_000_000
+        );
+        let issuance = Issuance::parse(&content).unwrap();
+        assert_eq!(issuance.slips.len(), 2);
+        assert_eq!(issuance.get_total(), Some(MAX_TOKEN_SUPPLY));
+
+        let transaction = issuance.create_transaction();
+        assert_eq!(transaction.transaction_type, TransactionType::Issuance);
+        assert!(transaction.inputs.is_empty());
+        assert_eq!(hex::encode(transaction.outputs[1].public_key), key_2);
+        assert_eq!(transaction.outputs[0].amount, 100_000_000);
+
+        // one nolan over the supply
+        let content = format!(
+            "{} 100000001\n{} {}",
+            key_1,
+            key_2,
+            MAX_TOKEN_SUPPLY - 100_000_000
+        );
+        assert_eq!(
+            Issuance::parse(&content),
+            Err(IssuanceError::ExceedsTokenSupply)
+        );
+
+        assert_eq!(
+            Issuance::parse(&format!("{}\n{} 100", key_1, key_2)),
+            Err(IssuanceError::InvalidLine { line: 1 })
+        );
+        assert_eq!(
+            Issuance::parse(&format!("{} 100\n{} 0", key_1, key_2)),
+            Err(IssuanceError::InvalidAmount { line: 2 })
+        );
+        // not a point on the curve
+        assert_eq!(
+            Issuance::parse(&format!("{} 100", hex::encode([9; 33]))),
+            Err(IssuanceError::InvalidPublicKey { line: 1 })
+        );
+        assert_eq!(
+            Issuance::parse("# nothing issued\n"),
+            Err(IssuanceError::NoSlips)
+        );
+    }
+}
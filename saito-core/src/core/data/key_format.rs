@@ -0,0 +1,213 @@
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+use base58::{FromBase58, ToBase58};
+use secp256k1::hashes::{sha256d, Hash};
+use tracing::warn;
+
+use crate::common::defs::{SaitoPrivateKey, SaitoPublicKey};
+use crate::core::data::crypto::{generate_keypair_from_private_key, sign, verify, SecretKey};
+
+/// version byte of the WIF encoding (bitcoin mainnet)
+const WIF_VERSION: u8 = 0x80;
+/// saito public keys are compressed, so the keys are always exported with the compression flag
+const WIF_COMPRESSED_FLAG: u8 = 0x01;
+const WIF_CHECKSUM_SIZE: usize = 4;
+
+/// Human readable encodings of a private key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    Hex,
+    /// base58check encoded with the WIF version byte and the compression flag
+    Wif,
+    /// 24 word BIP39 mnemonic with the private key as the entropy. only available with the `mnemonic` feature
+    Mnemonic,
+}
+
+impl FromStr for KeyFormat {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "hex" => Ok(KeyFormat::Hex),
+            "wif" => Ok(KeyFormat::Wif),
+            "mnemonic" => Ok(KeyFormat::Mnemonic),
+            _ => {
+                warn!("unknown key format : {:?}", value);
+                Err(Error::from(ErrorKind::InvalidInput))
+            }
+        }
+    }
+}
+
+pub fn export_private_key(
+    private_key: &SaitoPrivateKey,
+    format: KeyFormat,
+) -> Result<String, Error> {
+    match format {
+        KeyFormat::Hex => Ok(hex::encode(private_key)),
+        KeyFormat::Wif => {
+            let mut buffer = Vec::with_capacity(1 + 32 + 1 + WIF_CHECKSUM_SIZE);
+            buffer.push(WIF_VERSION);
+            buffer.extend_from_slice(private_key);
+            buffer.push(WIF_COMPRESSED_FLAG);
+            let checksum = sha256d::Hash::hash(&buffer).into_inner();
+            buffer.extend_from_slice(&checksum[0..WIF_CHECKSUM_SIZE]);
+            Ok(buffer.to_base58())
+        }
+        KeyFormat::Mnemonic => export_mnemonic(private_key),
+    }
+}
+
+/// Decodes the private key and derives the public key from it. The keypair is checked by signing a message, so
+/// the returned keys can be used as a wallet directly.
+pub fn import_private_key(
+    encoded: &str,
+    format: KeyFormat,
+) -> Result<(SaitoPublicKey, SaitoPrivateKey), Error> {
+    let encoded = encoded.trim();
+    let private_key: SaitoPrivateKey = match format {
+        KeyFormat::Hex => hex::decode(encoded)
+            .map_err(|error| {
+                warn!("failed decoding hex private key : {:?}", error);
+                Error::from(ErrorKind::InvalidData)
+            })?
+            .try_into()
+            .map_err(|_| {
+                warn!("hex private key should be 32 bytes");
+                Error::from(ErrorKind::InvalidData)
+            })?,
+        KeyFormat::Wif => import_wif(encoded)?,
+        KeyFormat::Mnemonic => import_mnemonic(encoded)?,
+    };
+    if SecretKey::from_slice(&private_key).is_err() {
+        warn!("private key is not a valid secp256k1 key");
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+    let (public_key, private_key) = generate_keypair_from_private_key(&private_key);
+    let message = public_key.to_vec();
+    if !verify(&message, &sign(&message, &private_key), &public_key) {
+        warn!("derived public key doesn't verify signatures of the private key");
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+    Ok((public_key, private_key))
+}
+
+fn import_wif(encoded: &str) -> Result<SaitoPrivateKey, Error> {
+    let buffer = encoded.from_base58().map_err(|error| {
+        warn!("failed decoding wif private key : {:?}", error);
+        Error::from(ErrorKind::InvalidData)
+    })?;
+    if buffer.len() != 1 + 32 + 1 + WIF_CHECKSUM_SIZE
+        || buffer[0] != WIF_VERSION
+        || buffer[33] != WIF_COMPRESSED_FLAG
+    {
+        warn!("wif private key is not a compressed mainnet key");
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+    let (payload, checksum) = buffer.split_at(buffer.len() - WIF_CHECKSUM_SIZE);
+    if sha256d::Hash::hash(payload).into_inner()[0..WIF_CHECKSUM_SIZE] != *checksum {
+        warn!("wif private key checksum doesn't match");
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+    Ok(payload[1..33].try_into().unwrap())
+}
+
+#[cfg(feature = "mnemonic")]
+fn export_mnemonic(private_key: &SaitoPrivateKey) -> Result<String, Error> {
+    let mnemonic = bip39::Mnemonic::from_entropy(private_key).map_err(|error| {
+        warn!("failed creating mnemonic : {:?}", error);
+        Error::from(ErrorKind::InvalidData)
+    })?;
+    Ok(mnemonic.to_string())
+}
+
+#[cfg(feature = "mnemonic")]
+fn import_mnemonic(encoded: &str) -> Result<SaitoPrivateKey, Error> {
+    let words: Vec<String> = encoded
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect();
+    let mnemonic = bip39::Mnemonic::parse_normalized(&words.join(" ")).map_err(|error| {
+        warn!("failed parsing mnemonic : {:?}", error);
+        Error::from(ErrorKind::InvalidData)
+    })?;
+    mnemonic.to_entropy().try_into().map_err(|_| {
+        warn!("mnemonic should have 24 words to encode a private key");
+        Error::from(ErrorKind::InvalidData)
+    })
+}
+
+#[cfg(not(feature = "mnemonic"))]
+fn export_mnemonic(_private_key: &SaitoPrivateKey) -> Result<String, Error> {
+    warn!("mnemonic keys are not supported without the mnemonic feature");
+    Err(Error::from(ErrorKind::Unsupported))
+}
+
+#[cfg(not(feature = "mnemonic"))]
+fn import_mnemonic(_encoded: &str) -> Result<SaitoPrivateKey, Error> {
+    warn!("mnemonic keys are not supported without the mnemonic feature");
+    Err(Error::from(ErrorKind::Unsupported))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::crypto::generate_keys;
+    use crate::core::data::key_format::{export_private_key, import_private_key, KeyFormat};
+
+    #[test]
+    fn key_format_test() {
+        let (public_key, private_key) = generate_keys();
+        for format in [KeyFormat::Hex, KeyFormat::Wif] {
+            let encoded = export_private_key(&private_key, format).unwrap();
+            assert_eq!(
+                import_private_key(&encoded, format).unwrap(),
+                (public_key, private_key)
+            );
+        }
+
+        let private_key: [u8; 32] =
+            hex::decode("0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let wif = "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617";
+        assert_eq!(
+            export_private_key(&private_key, KeyFormat::Wif).unwrap(),
+            wif
+        );
+        assert_eq!(
+            import_private_key(wif, KeyFormat::Wif).unwrap().1,
+            private_key
+        );
+
+        // checksum mismatch
+        assert!(import_private_key(
+            "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98618",
+            KeyFormat::Wif
+        )
+        .is_err());
+        assert!(import_private_key("abcd", KeyFormat::Hex).is_err());
+        // zero is not a valid secp256k1 key
+        assert!(import_private_key(&hex::encode([0; 32]), KeyFormat::Hex).is_err());
+        assert_eq!("WIF".parse::<KeyFormat>().unwrap(), KeyFormat::Wif);
+    }
+
+    #[cfg(feature = "mnemonic")]
+    #[test]
+    fn mnemonic_key_format_test() {
+        let private_key = [0x7f; 32];
+        let mnemonic = "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth title";
+        assert_eq!(
+            export_private_key(&private_key, KeyFormat::Mnemonic).unwrap(),
+            mnemonic
+        );
+        assert_eq!(
+            import_private_key(&mnemonic.to_uppercase(), KeyFormat::Mnemonic)
+                .unwrap()
+                .1,
+            private_key
+        );
+        assert!(import_private_key("legal winner thank year", KeyFormat::Mnemonic).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::VecDeque;
+use std::hash::Hash;
+
+use ahash::AHashSet;
+
+/// max number of block hashes remembered for each peer
+pub const MAX_KNOWN_BLOCKS_PER_PEER: usize = 1_000;
+/// max number of transaction signatures remembered for each peer
+pub const MAX_KNOWN_TRANSACTIONS_PER_PEER: usize = 5_000;
+
+/// Blocks or transactions which a peer already has, either because the peer announced them or because we sent them.
+/// Oldest entries are removed first when the inventory is full, so the memory used per peer is bounded.
+#[derive(Debug, Clone)]
+pub struct KnownInventory<T: Hash + Eq + Copy> {
+    items: AHashSet<T>,
+    insertion_order: VecDeque<T>,
+    max_size: usize,
+}
+
+impl<T: Hash + Eq + Copy> KnownInventory<T> {
+    pub fn new(max_size: usize) -> KnownInventory<T> {
+        assert_ne!(max_size, 0);
+        KnownInventory {
+            items: Default::default(),
+            insertion_order: Default::default(),
+            max_size,
+        }
+    }
+
+    /// Returns true if the item was not known before
+    pub fn insert(&mut self, item: T) -> bool {
+        if !self.items.insert(item) {
+            return false;
+        }
+        self.insertion_order.push_back(item);
+        if self.insertion_order.len() > self.max_size {
+            if let Some(oldest) = self.insertion_order.pop_front() {
+                self.items.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    pub fn contains(&self, item: &T) -> bool {
+        self.items.contains(item)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::known_inventory::KnownInventory;
+
+    #[test]
+    fn known_inventory_test() {
+        let mut inventory = KnownInventory::new(3);
+        assert!(inventory.is_empty());
+
+        assert!(inventory.insert([1; 32]));
+        assert!(!inventory.insert([1; 32]));
+        assert!(inventory.insert([2; 32]));
+        assert!(inventory.insert([3; 32]));
+        assert_eq!(inventory.len(), 3);
+
+        // oldest entry is removed when full
+        assert!(inventory.insert([4; 32]));
+        assert_eq!(inventory.len(), 3);
+        assert!(!inventory.contains(&[1; 32]));
+        assert!(inventory.contains(&[2; 32]));
+        assert!(inventory.contains(&[4; 32]));
+
+        assert!(inventory.insert([1; 32]));
+        assert!(!inventory.contains(&[2; 32]));
+    }
+}
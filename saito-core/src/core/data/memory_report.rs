@@ -0,0 +1,150 @@
+use std::mem::size_of;
+
+use crate::common::defs::{SaitoSignature, SaitoUTXOSetKey};
+use crate::core::data::block::Block;
+use crate::core::data::blockchain::Blockchain;
+use crate::core::data::hop::Hop;
+use crate::core::data::mempool::Mempool;
+use crate::core::data::slip::Slip;
+use crate::core::data::transaction::Transaction;
+use crate::core::data::wallet::{Wallet, WalletSlip};
+
+/// Estimated size of a hash map entry. Includes the control byte of the table, but not the unused capacity.
+fn get_map_entry_size<K, V>() -> usize {
+    size_of::<K>() + size_of::<V>() + 1
+}
+
+/// Heap data of a transaction is estimated from the vector lengths. Multisig data is not included since it's rare.
+fn get_transaction_size(transaction: &Transaction) -> usize {
+    size_of::<Transaction>()
+        + (transaction.inputs.len() + transaction.outputs.len()) * size_of::<Slip>()
+        + transaction.message.len()
+        + transaction.get_path().len() * size_of::<Hop>()
+}
+
+fn get_block_size(block: &Block) -> usize {
+    size_of::<Block>()
+        + block
+            .transactions
+            .iter()
+            .map(get_transaction_size)
+            .sum::<usize>()
+        + block.slips_spent_this_block.len() * get_map_entry_size::<SaitoUTXOSetKey, u64>()
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub count: u64,
+    pub estimated_bytes: u64,
+}
+
+impl MemoryUsage {
+    fn new(count: usize, estimated_bytes: usize) -> MemoryUsage {
+        MemoryUsage {
+            count: count as u64,
+            estimated_bytes: estimated_bytes as u64,
+        }
+    }
+}
+
+/// Approximate heap usage of the largest in-memory collections. The sizes are estimated from the element counts and
+/// the vector lengths, so allocator overhead and unused capacity are not included.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub utxoset: MemoryUsage,
+    pub blocks: MemoryUsage,
+    pub mempool_transactions: MemoryUsage,
+    pub wallet_slips: MemoryUsage,
+}
+
+impl MemoryReport {
+    pub fn generate(blockchain: &Blockchain, mempool: &Mempool, wallet: &Wallet) -> MemoryReport {
+        MemoryReport {
+            utxoset: MemoryUsage::new(
+                blockchain.utxoset.len(),
+                blockchain.utxoset.len() * get_map_entry_size::<SaitoUTXOSetKey, bool>(),
+            ),
+            blocks: MemoryUsage::new(
+                blockchain.blocks.len(),
+                blockchain.blocks.values().map(get_block_size).sum(),
+            ),
+            mempool_transactions: MemoryUsage::new(
+                mempool.transactions.len(),
+                mempool
+                    .transactions
+                    .values()
+                    .map(|transaction| {
+                        size_of::<SaitoSignature>() + 1 + get_transaction_size(transaction)
+                    })
+                    .sum(),
+            ),
+            wallet_slips: MemoryUsage::new(
+                wallet.slips.len(),
+                wallet.slips.len() * get_map_entry_size::<SaitoUTXOSetKey, WalletSlip>(),
+            ),
+        }
+    }
+
+    pub fn get_total_bytes(&self) -> u64 {
+        self.utxoset.estimated_bytes
+            + self.blocks.estimated_bytes
+            + self.mempool_transactions.estimated_bytes
+            + self.wallet_slips.estimated_bytes
+    }
+
+    pub fn to_json(&self) -> String {
+        let usage_to_json = |usage: &MemoryUsage| {
+            format!(
+                "{{\"count\":{},\"estimated_bytes\":{}}}",
+                usage.count, usage.estimated_bytes
+            )
+        };
+        format!(
+            "{{\"utxoset\":{},\"blocks\":{},\"mempool_transactions\":{},\"wallet_slips\":{},\"total_bytes\":{}}}",
+            usage_to_json(&self.utxoset),
+            usage_to_json(&self.blocks),
+            usage_to_json(&self.mempool_transactions),
+            usage_to_json(&self.wallet_slips),
+            self.get_total_bytes()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::defs::{
+        push_lock, LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_MEMPOOL, LOCK_ORDER_WALLET,
+    };
+    use crate::common::test_manager::test::TestManager;
+    use crate::core::data::memory_report::MemoryReport;
+    use crate::lock_for_read;
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn memory_report_test() {
+        let mut t = TestManager::new();
+        t.initialize(100, 1_000_000_000).await;
+
+        let (blockchain, _blockchain_) = lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+        let (mempool, _mempool_) = lock_for_read!(t.mempool_lock, LOCK_ORDER_MEMPOOL);
+        let (wallet, _wallet_) = lock_for_read!(t.wallet_lock, LOCK_ORDER_WALLET);
+        let report = MemoryReport::generate(&blockchain, &mempool, &wallet);
+
+        assert_eq!(report.utxoset.count, blockchain.utxoset.len() as u64);
+        assert_eq!(report.blocks.count, blockchain.blocks.len() as u64);
+        assert!(report.blocks.estimated_bytes > 0);
+        assert_eq!(
+            report.mempool_transactions.count,
+            mempool.transactions.len() as u64
+        );
+        assert_eq!(report.wallet_slips.count, wallet.slips.len() as u64);
+        assert_eq!(
+            report.get_total_bytes(),
+            report.utxoset.estimated_bytes
+                + report.blocks.estimated_bytes
+                + report.mempool_transactions.estimated_bytes
+                + report.wallet_slips.estimated_bytes
+        );
+        assert!(report.to_json().starts_with("{\"utxoset\":{\"count\":"));
+    }
+}
@@ -1,17 +1,32 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::time::Duration;
 
 use ahash::AHashMap;
 use rayon::prelude::*;
-use tracing::{debug, info, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 
-use crate::common::defs::{Currency, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature};
-use crate::core::data::block::Block;
+use crate::common::defs::{
+    Currency, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature, SaitoUTXOSetKey,
+    Timestamp,
+};
+use crate::common::runtime;
+use crate::core::data::account_nonce::AccountNonces;
+use crate::core::data::block::{Block, BLOCK_HEADER_SIZE};
 use crate::core::data::blockchain::Blockchain;
 use crate::core::data::burnfee::BurnFee;
+use crate::core::data::creator_blacklist::CreatorBlacklist;
 use crate::core::data::crypto::hash;
 use crate::core::data::golden_ticket::GoldenTicket;
-use crate::core::data::transaction::{Transaction, TransactionType};
+use crate::core::data::orphan_block_pool::{
+    OrphanBlockPool, DEFAULT_MAX_ORPHAN_BLOCKS, DEFAULT_ORPHAN_BLOCK_EXPIRY_IN_MS,
+};
+use crate::core::data::storage::Storage;
+use crate::core::data::transaction::{Transaction, TransactionRejectReason, TransactionType};
+
+/// maximum total payload size of the data transactions waiting in the mempool
+pub const MAX_DATA_BYTES_IN_MEMPOOL: usize = 10_000_000;
+/// size limit of the bundled blocks if not configured
+pub const DEFAULT_MAX_BLOCK_SIZE: usize = 10_000_000;
 
 //
 // In addition to responding to global broadcast messages, the
@@ -25,6 +40,59 @@ pub enum MempoolMessage {
     LocalNewBlock,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddTransactionResult {
+    Added,
+    /// added after removing the given number of conflicting transactions which paid lower fees in total
+    Replaced(usize),
+    Exists,
+    /// spends slips which are already spent by pending transactions paying equal or higher fees
+    Conflicting,
+    /// data transaction which doesn't fit in the mempool
+    Rejected,
+    /// nonce is already used by the sender or leaves a gap after the sender's latest nonce
+    InvalidNonce,
+}
+
+impl AddTransactionResult {
+    /// Why the transaction was not added. None if it was added or is already in the mempool
+    pub fn get_reject_reason(&self) -> Option<TransactionRejectReason> {
+        match self {
+            AddTransactionResult::Added
+            | AddTransactionResult::Replaced(_)
+            | AddTransactionResult::Exists => None,
+            AddTransactionResult::Conflicting => Some(TransactionRejectReason::DoubleSpend),
+            AddTransactionResult::Rejected => Some(TransactionRejectReason::MempoolFull),
+            AddTransactionResult::InvalidNonce => Some(TransactionRejectReason::InvalidNonce),
+        }
+    }
+}
+
+/// Routing work in the mempool compared to the work needed to produce the next block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockProductionStatus {
+    pub work_available: Currency,
+    pub work_needed: Currency,
+    pub time_since_last_block: Timestamp,
+}
+
+impl BlockProductionStatus {
+    pub fn has_enough_work(&self) -> bool {
+        self.work_available >= self.work_needed
+    }
+}
+
+/// Transactions selected from the mempool for the last bundled block
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockBundlingStats {
+    pub included_transactions: usize,
+    pub included_bytes: usize,
+    pub included_fees: Currency,
+    /// transactions left in the mempool for the next block since they didn't fit in the size limit
+    pub deferred_transactions: usize,
+    pub deferred_bytes: usize,
+}
+
 /// The `Mempool` holds unprocessed blocks and transactions and is in control of
 /// discerning when the node is allowed to create a block. It bundles the block and
 /// sends it to the `Blockchain` to be added to the longest-chain. New `Block`s
@@ -33,11 +101,26 @@ pub enum MempoolMessage {
 #[derive(Debug)]
 pub struct Mempool {
     pub blocks_queue: VecDeque<Block>,
+    /// blocks waiting for their parent block
+    pub orphan_blocks: OrphanBlockPool,
+    /// creators whose blocks repeatedly failed validation
+    pub creator_blacklist: CreatorBlacklist,
     pub transactions: AHashMap<SaitoSignature, Transaction>,
     pub golden_tickets: AHashMap<SaitoHash, (Transaction, bool)>,
+    // slips spent by the pending transactions -> signature of the spending transaction
+    pending_slips: AHashMap<SaitoUTXOSetKey, SaitoSignature>,
     // vector so we just copy it over
     routing_work_in_mempool: Currency,
+    // total payload size of the data transactions
+    data_bytes_in_mempool: usize,
+    // latest nonces in the longest chain. copied from the blockchain when blocks are added
+    account_nonces: AccountNonces,
+    // nonce -> signature of the pending transactions with a nonce, by sender
+    pending_nonces: AHashMap<SaitoPublicKey, BTreeMap<u64, SaitoSignature>>,
     pub new_tx_added: bool,
+    // serialized size limit of the bundled blocks
+    max_block_size: usize,
+    pub last_bundling_stats: Option<BlockBundlingStats>,
     pub(crate) public_key: SaitoPublicKey,
     private_key: SaitoPrivateKey,
 }
@@ -47,15 +130,39 @@ impl Mempool {
     pub fn new(public_key: SaitoPublicKey, private_key: SaitoPrivateKey) -> Self {
         Mempool {
             blocks_queue: VecDeque::new(),
+            orphan_blocks: OrphanBlockPool::new(
+                DEFAULT_MAX_ORPHAN_BLOCKS,
+                DEFAULT_ORPHAN_BLOCK_EXPIRY_IN_MS,
+            ),
+            creator_blacklist: CreatorBlacklist::new(),
             transactions: Default::default(),
             golden_tickets: Default::default(),
+            pending_slips: Default::default(),
             routing_work_in_mempool: 0,
+            data_bytes_in_mempool: 0,
+            account_nonces: AccountNonces::new(),
+            pending_nonces: Default::default(),
             new_tx_added: false,
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+            last_bundling_stats: None,
             public_key,
             private_key,
         }
     }
 
+    /// 0 means the default limit
+    pub fn set_max_block_size(&mut self, max_block_size: u64) {
+        self.max_block_size = if max_block_size == 0 {
+            DEFAULT_MAX_BLOCK_SIZE
+        } else {
+            max_block_size as usize
+        };
+    }
+
+    pub fn get_max_block_size(&self) -> usize {
+        self.max_block_size
+    }
+
     #[tracing::instrument(level = "info", skip_all)]
     pub fn add_block(&mut self, block: Block) {
         debug!("mempool add block : {:?}", hex::encode(block.hash));
@@ -71,7 +178,8 @@ impl Mempool {
         }
     }
     #[tracing::instrument(level = "info", skip_all)]
-    pub async fn add_golden_ticket(&mut self, golden_ticket: Transaction) {
+    /// Returns false if there's already a golden ticket for the same block
+    pub async fn add_golden_ticket(&mut self, golden_ticket: Transaction) -> bool {
         let gt = GoldenTicket::deserialize_from_net(&golden_ticket.message);
         info!(
             "adding golden ticket : {:?} target : {:?} public_key : {:?}",
@@ -85,12 +193,13 @@ impl Mempool {
                 "similar golden ticket already exists : {:?}",
                 hex::encode(gt.target)
             );
-            return;
+            return false;
         }
         self.golden_tickets
             .insert(gt.target, (golden_ticket, false));
 
         info!("golden ticket added to mempool");
+        true
     }
     #[tracing::instrument(level = "info", skip_all)]
     pub async fn add_transaction_if_validates(
@@ -113,8 +222,11 @@ impl Mempool {
             );
         }
     }
+    /// Adds the transaction if it doesn't spend the same slips as a pending transaction. Conflicting transactions
+    /// are replaced if the new transaction pays higher fees than all of them, since only one of them can be
+    /// included in a block.
     #[tracing::instrument(level = "info", skip_all)]
-    pub async fn add_transaction(&mut self, transaction: Transaction) {
+    pub async fn add_transaction(&mut self, transaction: Transaction) -> AddTransactionResult {
         trace!(
             "add_transaction {:?} : type = {:?}",
             hex::encode(transaction.hash_for_signature.unwrap()),
@@ -133,19 +245,198 @@ impl Mempool {
         //
         // transaction.generate(&self.public_key, 0, 0);
 
-        if !self.transactions.contains_key(&transaction.signature) {
-            self.routing_work_in_mempool += transaction.total_work_for_me;
+        if self.transactions.contains_key(&transaction.signature) {
+            return AddTransactionResult::Exists;
+        }
+        if let TransactionType::GoldenTicket = transaction.transaction_type {
+            panic!("golden tickets should be in gt collection");
+        }
+        if transaction.is_data_transaction()
+            && self.data_bytes_in_mempool + transaction.message.len() > MAX_DATA_BYTES_IN_MEMPOOL
+        {
             debug!(
-                "routing work available in mempool : {:?} after adding work : {:?} from tx with fees : {:?}",
-                self.routing_work_in_mempool, transaction.total_work_for_me, transaction.total_fees
+                "data transaction : {:?} with payload size : {:?} rejected. data in mempool : {:?}",
+                hex::encode(transaction.signature),
+                transaction.message.len(),
+                self.data_bytes_in_mempool
             );
-            if let TransactionType::GoldenTicket = transaction.transaction_type {
-                panic!("golden tickets should be in gt collection");
-            } else {
-                self.transactions.insert(transaction.signature, transaction);
-                self.new_tx_added = true;
+            return AddTransactionResult::Rejected;
+        }
+
+        let mut conflicting_txs: Vec<SaitoSignature> = Self::get_spent_slip_keys(&transaction)
+            .filter_map(|key| self.pending_slips.get(&key).copied())
+            .collect();
+        conflicting_txs.sort();
+        conflicting_txs.dedup();
+
+        if let Some(sender) = transaction.get_nonce_sender() {
+            let nonce = transaction.get_nonce().unwrap();
+            // a pending transaction of the sender can be replaced with one using the same nonce, since its later
+            // transactions are removed along with it
+            let expected_nonce = conflicting_txs
+                .iter()
+                .filter_map(|signature| self.transactions.get(signature))
+                .filter(|tx| tx.get_nonce_sender() == Some(sender))
+                .filter_map(|tx| tx.get_nonce())
+                .min()
+                .unwrap_or_else(|| self.get_next_nonce(&sender));
+            if nonce != expected_nonce {
+                debug!(
+                    "transaction : {:?} from : {:?} with nonce : {:?} rejected. expected nonce : {:?}",
+                    hex::encode(transaction.signature),
+                    hex::encode(sender),
+                    nonce,
+                    expected_nonce
+                );
+                return AddTransactionResult::InvalidNonce;
+            }
+        }
+
+        let mut result = AddTransactionResult::Added;
+        if !conflicting_txs.is_empty() {
+            let conflicting_fees: Currency = conflicting_txs
+                .iter()
+                .filter_map(|signature| self.transactions.get(signature))
+                .map(|tx| tx.total_fees)
+                .sum();
+            if transaction.total_fees <= conflicting_fees {
+                debug!(
+                    "transaction : {:?} with fees : {:?} conflicts with {:?} pending transactions with fees : {:?}",
+                    hex::encode(transaction.signature),
+                    transaction.total_fees,
+                    conflicting_txs.len(),
+                    conflicting_fees
+                );
+                return AddTransactionResult::Conflicting;
+            }
+            debug!(
+                "transaction : {:?} with fees : {:?} replaces {:?} pending transactions with fees : {:?}",
+                hex::encode(transaction.signature),
+                transaction.total_fees,
+                conflicting_txs.len(),
+                conflicting_fees
+            );
+            for signature in conflicting_txs.iter() {
+                self.remove_transaction(signature);
+            }
+            result = AddTransactionResult::Replaced(conflicting_txs.len());
+        }
+
+        self.routing_work_in_mempool += transaction.total_work_for_me;
+        debug!(
+            "routing work available in mempool : {:?} after adding work : {:?} from tx with fees : {:?}",
+            self.routing_work_in_mempool, transaction.total_work_for_me, transaction.total_fees
+        );
+        for key in Self::get_spent_slip_keys(&transaction) {
+            self.pending_slips.insert(key, transaction.signature);
+        }
+        if transaction.is_data_transaction() {
+            self.data_bytes_in_mempool += transaction.message.len();
+        }
+        self.add_pending_nonce(&transaction);
+        self.transactions.insert(transaction.signature, transaction);
+        self.new_tx_added = true;
+        result
+    }
+
+    /// Nonce expected from the sender's next transaction, after their pending transactions
+    pub fn get_next_nonce(&self, public_key: &SaitoPublicKey) -> u64 {
+        self.pending_nonces
+            .get(public_key)
+            .and_then(|nonces| nonces.keys().next_back())
+            .map(|nonce| nonce + 1)
+            .unwrap_or_else(|| self.account_nonces.get_next_nonce(public_key))
+    }
+
+    /// Updates the latest nonces of the longest chain and removes the pending transactions which don't follow them
+    /// anymore (e.g. the nonce was used by another transaction in the block, or the chain was reorganized)
+    pub fn set_account_nonces(&mut self, account_nonces: &AccountNonces) {
+        self.account_nonces = account_nonces.clone();
+        let mut invalid_txs = vec![];
+        for (public_key, nonces) in self.pending_nonces.iter() {
+            let mut expected_nonce = self.account_nonces.get_next_nonce(public_key);
+            for (nonce, signature) in nonces.iter() {
+                if *nonce != expected_nonce {
+                    // later transactions are removed with this one
+                    invalid_txs.push(*signature);
+                    break;
+                }
+                expected_nonce += 1;
+            }
+        }
+        for signature in invalid_txs {
+            if self.remove_transaction(&signature).is_some() {
+                debug!(
+                    "removed transaction : {:?} from mempool since its nonce is invalid",
+                    hex::encode(signature)
+                );
+            }
+        }
+    }
+
+    fn add_pending_nonce(&mut self, transaction: &Transaction) {
+        if let Some(sender) = transaction.get_nonce_sender() {
+            self.pending_nonces
+                .entry(sender)
+                .or_default()
+                .insert(transaction.get_nonce().unwrap(), transaction.signature);
+        }
+    }
+
+    /// utxoset keys of the slips spent by the transaction. zero amount inputs are ignored since they don't spend
+    /// a slip
+    fn get_spent_slip_keys(
+        transaction: &Transaction,
+    ) -> impl Iterator<Item = SaitoUTXOSetKey> + '_ {
+        transaction
+            .inputs
+            .iter()
+            .filter(|slip| slip.amount > 0)
+            .map(|slip| slip.get_utxoset_key())
+    }
+
+    fn remove_transaction(&mut self, signature: &SaitoSignature) -> Option<Transaction> {
+        let transaction = self.transactions.remove(signature)?;
+        self.routing_work_in_mempool = self
+            .routing_work_in_mempool
+            .saturating_sub(transaction.total_work_for_me);
+        if transaction.is_data_transaction() {
+            self.data_bytes_in_mempool = self
+                .data_bytes_in_mempool
+                .saturating_sub(transaction.message.len());
+        }
+        for key in Self::get_spent_slip_keys(&transaction) {
+            if self.pending_slips.get(&key) == Some(signature) {
+                self.pending_slips.remove(&key);
+            }
+        }
+        if let Some(sender) = transaction.get_nonce_sender() {
+            let nonce = transaction.get_nonce().unwrap();
+            let mut later_nonces = BTreeMap::new();
+            if let Some(nonces) = self.pending_nonces.get_mut(&sender) {
+                later_nonces = nonces.split_off(&nonce);
+                if nonces.is_empty() {
+                    self.pending_nonces.remove(&sender);
+                }
+            }
+            // later transactions of the sender can't be included without this one
+            for (later_nonce, later_signature) in later_nonces {
+                if later_nonce != nonce {
+                    self.remove_transaction(&later_signature);
+                }
             }
         }
+        Some(transaction)
+    }
+
+    /// number of slips spent by the pending transactions
+    pub fn get_pending_slip_count(&self) -> usize {
+        self.pending_slips.len()
+    }
+
+    /// total payload size of the pending data transactions
+    pub fn get_data_bytes_in_mempool(&self) -> usize {
+        self.data_bytes_in_mempool
     }
 
     #[tracing::instrument(level = "info", skip_all)]
@@ -154,14 +445,25 @@ impl Mempool {
         blockchain: &mut Blockchain,
         current_timestamp: u64,
         gt_tx: Option<Transaction>,
+        storage: &Storage,
     ) -> Option<Block> {
         let mempool_work = self
             .can_bundle_block(blockchain, current_timestamp, &gt_tx)
             .await?;
+
+        let reserved_bytes = BLOCK_HEADER_SIZE
+            + gt_tx
+                .as_ref()
+                .map(|tx| tx.get_serialized_size())
+                .unwrap_or_default();
+        let (mut transactions, stats) = self.take_transactions_for_block(reserved_bytes);
         info!(
-            "bundling block with {:?} txs with work : {:?}",
-            self.transactions.len(),
-            mempool_work
+            "bundling block with {:?} txs ({:?} bytes) with work : {:?}. {:?} txs ({:?} bytes) left for the next block",
+            stats.included_transactions,
+            stats.included_bytes,
+            mempool_work,
+            stats.deferred_transactions,
+            stats.deferred_bytes
         );
 
         let previous_block_hash: SaitoHash;
@@ -169,33 +471,140 @@ impl Mempool {
             previous_block_hash = blockchain.get_latest_block_hash();
         }
 
-        let mut block = Block::create(
-            &mut self.transactions,
+        let result = Block::create(
+            &mut transactions,
             previous_block_hash,
             blockchain,
             current_timestamp,
             &self.public_key,
             &self.private_key,
             gt_tx,
+            storage,
         )
         .await;
+        let mut block = match result {
+            Ok(block) => block,
+            Err(error) => {
+                error!("failed creating block : {:?}", error);
+                self.transactions.extend(transactions);
+                self.recalculate_pending_transactions();
+                return None;
+            }
+        };
         block.generate();
         info!(
             "block generated with work : {:?} and burnfee : {:?}",
             block.total_work, block.burnfee
         );
         // assert_eq!(block.total_work, mempool_work);
-        self.new_tx_added = false;
-        self.routing_work_in_mempool = 0;
+        // deferred transactions can be bundled in the next block without waiting for new transactions
+        self.new_tx_added = !self.transactions.is_empty();
+        self.recalculate_pending_transactions();
+        self.last_bundling_stats = Some(stats);
 
         Some(block)
     }
 
+    /// Takes the transactions for the next block out of the mempool, in the order of their fee per byte, until the
+    /// block reaches the size limit. The rest are left for the next block. A transaction with a nonce is only taken
+    /// after the sender's previous transaction, so the sender's later transactions stay valid.
+    ///
+    /// `reserved_bytes` are used by the block header and the golden ticket. ATR and fee transactions added by
+    /// `Block::create` are not counted.
+    fn take_transactions_for_block(
+        &mut self,
+        reserved_bytes: usize,
+    ) -> (AHashMap<SaitoSignature, Transaction>, BlockBundlingStats) {
+        // transactions which don't fit in an empty block would be deferred forever
+        let max_transaction_size = self.max_block_size.saturating_sub(BLOCK_HEADER_SIZE);
+        let oversized: Vec<SaitoSignature> = self
+            .transactions
+            .values()
+            .filter(|tx| tx.get_serialized_size() > max_transaction_size)
+            .map(|tx| tx.signature)
+            .collect();
+        for signature in oversized {
+            if self.remove_transaction(&signature).is_some() {
+                debug!(
+                    "removed transaction : {:?} from mempool since it's larger than the block size limit : {:?}",
+                    hex::encode(signature),
+                    self.max_block_size
+                );
+            }
+        }
+
+        let mut candidates: Vec<(SaitoSignature, usize, Currency)> = self
+            .transactions
+            .values()
+            .map(|tx| (tx.signature, tx.get_serialized_size(), tx.total_fees))
+            .collect();
+        // fees are multiplied by the other transaction's size instead of dividing by their own size, so small fee
+        // differences are not rounded away. signatures break the ties to keep the order deterministic
+        candidates.sort_by(
+            |(signature_a, size_a, fees_a), (signature_b, size_b, fees_b)| {
+                (*fees_b as u128 * *size_a as u128)
+                    .cmp(&(*fees_a as u128 * *size_b as u128))
+                    .then(signature_a.cmp(signature_b))
+            },
+        );
+
+        let mut available_bytes = self.max_block_size.saturating_sub(reserved_bytes);
+        let mut selected = vec![];
+        // next nonce which can be included for each sender
+        let mut next_nonces: AHashMap<SaitoPublicKey, u64> = Default::default();
+        // transactions waiting for the sender's previous transaction to be included
+        let mut waiting: AHashMap<(SaitoPublicKey, u64), (SaitoSignature, usize)> =
+            Default::default();
+        for (signature, size, _) in candidates {
+            let mut next = Some((signature, size));
+            while let Some((signature, size)) = next.take() {
+                let transaction = &self.transactions[&signature];
+                if let Some(sender) = transaction.get_nonce_sender() {
+                    let nonce = transaction.get_nonce().unwrap();
+                    let next_nonce = *next_nonces
+                        .entry(sender)
+                        .or_insert_with(|| self.account_nonces.get_next_nonce(&sender));
+                    if nonce != next_nonce {
+                        waiting.insert((sender, nonce), (signature, size));
+                        break;
+                    }
+                    if size > available_bytes {
+                        break;
+                    }
+                    next_nonces.insert(sender, nonce + 1);
+                    next = waiting.remove(&(sender, nonce + 1));
+                } else if size > available_bytes {
+                    break;
+                }
+                available_bytes -= size;
+                selected.push(signature);
+            }
+        }
+
+        let mut stats = BlockBundlingStats::default();
+        let mut transactions = AHashMap::with_capacity(selected.len());
+        for signature in selected {
+            let transaction = self.transactions.remove(&signature).unwrap();
+            stats.included_transactions += 1;
+            stats.included_bytes += transaction.get_serialized_size();
+            stats.included_fees += transaction.total_fees;
+            transactions.insert(signature, transaction);
+        }
+        stats.deferred_transactions = self.transactions.len();
+        stats.deferred_bytes = self
+            .transactions
+            .values()
+            .map(|tx| tx.get_serialized_size())
+            .sum();
+        (transactions, stats)
+    }
+
     #[tracing::instrument(level = "info", skip_all)]
     pub async fn bundle_genesis_block(
         &mut self,
         blockchain: &mut Blockchain,
         current_timestamp: u64,
+        storage: &Storage,
     ) -> Block {
         debug!("bundling genesis block...");
 
@@ -207,11 +616,15 @@ impl Mempool {
             &self.public_key,
             &self.private_key,
             None,
+            storage,
         )
-        .await;
+        .await
+        .expect("genesis block doesn't have ATR transactions to load");
         block.generate();
         self.new_tx_added = false;
         self.routing_work_in_mempool = 0;
+        self.data_bytes_in_mempool = 0;
+        self.pending_slips.clear();
 
         block
     }
@@ -232,7 +645,7 @@ impl Mempool {
 
         if blockchain.blocks.is_empty() {
             warn!("Not generating #1 block. Waiting for blocks from peers");
-            tokio::time::sleep(Duration::from_secs(1)).await;
+            runtime::sleep(Duration::from_secs(1)).await;
             return None;
         }
         if !self.blocks_queue.is_empty() {
@@ -248,29 +661,21 @@ impl Mempool {
             return None;
         }
 
-        if let Some(previous_block) = blockchain.get_latest_block() {
-            let work_available = self.get_routing_work_available();
-            let work_needed = BurnFee::return_routing_work_needed_to_produce_block_in_nolan(
-                previous_block.burnfee,
-                current_timestamp,
-                previous_block.timestamp,
-            );
-            let time_elapsed = current_timestamp - previous_block.timestamp;
-
-            let result = work_available >= work_needed;
+        if let Some(status) = self.get_block_production_status(blockchain, current_timestamp) {
+            let result = status.has_enough_work();
             if result {
                 info!(
-                "last ts: {:?}, this ts: {:?}, work available: {:?}, work needed: {:?}, time_elapsed : {:?} can_bundle : {:?}",
-                previous_block.timestamp, current_timestamp, work_available, work_needed, time_elapsed, true
+                "this ts: {:?}, work available: {:?}, work needed: {:?}, time_elapsed : {:?} can_bundle : {:?}",
+                current_timestamp, status.work_available, status.work_needed, status.time_since_last_block, true
                 );
             } else {
                 debug!(
-                "last ts: {:?}, this ts: {:?}, work available: {:?}, work needed: {:?}, time_elapsed : {:?} can_bundle : {:?}",
-                previous_block.timestamp, current_timestamp, work_available, work_needed, time_elapsed, false
+                "this ts: {:?}, work available: {:?}, work needed: {:?}, time_elapsed : {:?} can_bundle : {:?}",
+                current_timestamp, status.work_available, status.work_needed, status.time_since_last_block, false
                 );
             }
             if result {
-                return Some(work_available);
+                return Some(status.work_available);
             }
             None
         } else {
@@ -278,6 +683,42 @@ impl Mempool {
         }
     }
 
+    /// Calculates the routing work needed to produce a block on top of the latest block at the given time. Returns
+    /// None if there are no blocks in the blockchain.
+    pub fn get_block_production_status(
+        &self,
+        blockchain: &Blockchain,
+        current_timestamp: Timestamp,
+    ) -> Option<BlockProductionStatus> {
+        let previous_block = blockchain.get_latest_block()?;
+        Some(BlockProductionStatus {
+            work_available: self.get_routing_work_available(),
+            work_needed: BurnFee::return_routing_work_needed_to_produce_block_in_nolan(
+                previous_block.burnfee,
+                current_timestamp,
+                previous_block.timestamp,
+            ),
+            time_since_last_block: current_timestamp.saturating_sub(previous_block.timestamp),
+        })
+    }
+
+    /// Projects the time at which the routing work in the mempool will be enough to produce the next block. Returns
+    /// None if there are no new transactions to bundle or no previous block.
+    pub fn get_projected_block_production_time(
+        &self,
+        blockchain: &Blockchain,
+    ) -> Option<Timestamp> {
+        if self.transactions.is_empty() || !self.new_tx_added {
+            return None;
+        }
+        let previous_block = blockchain.get_latest_block()?;
+        Some(BurnFee::return_timestamp_when_routing_work_is_sufficient(
+            previous_block.burnfee,
+            self.get_routing_work_available(),
+            previous_block.timestamp,
+        ))
+    }
+
     #[tracing::instrument(level = "info", skip_all)]
     pub fn delete_block(&mut self, block_hash: &SaitoHash) {
         debug!(
@@ -289,22 +730,59 @@ impl Mempool {
         // self.blocks_queue.retain(|block| !block.hash.eq(block_hash));
     }
 
+    /// Removes the transactions included in a block, along with the pending transactions spending the same slips
     #[tracing::instrument(level = "info", skip_all)]
     pub fn delete_transactions(&mut self, transactions: &Vec<Transaction>) {
+        let mut conflicting_txs = vec![];
         for transaction in transactions {
             if let TransactionType::GoldenTicket = transaction.transaction_type {
                 let gt = GoldenTicket::deserialize_from_net(&transaction.message);
                 self.golden_tickets.remove(&gt.target);
             } else {
                 self.transactions.remove(&transaction.signature);
+                conflicting_txs.extend(
+                    Self::get_spent_slip_keys(transaction)
+                        .filter_map(|key| self.pending_slips.get(&key).copied())
+                        .filter(|signature| *signature != transaction.signature),
+                );
+            }
+        }
+        for signature in conflicting_txs {
+            if self.transactions.remove(&signature).is_some() {
+                debug!(
+                    "removed transaction : {:?} from mempool since its slips are already spent",
+                    hex::encode(signature)
+                );
             }
         }
 
+        self.recalculate_pending_transactions();
+    }
+
+    /// Calculates the routing work, spent slips and nonces of the remaining transactions again after transactions
+    /// are removed
+    fn recalculate_pending_transactions(&mut self) {
         self.routing_work_in_mempool = 0;
+        self.data_bytes_in_mempool = 0;
+        self.pending_slips.clear();
+        self.pending_nonces.clear();
 
-        // add routing work from remaining tx
+        // add routing work and spent slips from remaining tx. transactions can be removed from outside too, so
+        // these are recalculated here
         for (_, transaction) in &self.transactions {
             self.routing_work_in_mempool += transaction.total_work_for_me;
+            if transaction.is_data_transaction() {
+                self.data_bytes_in_mempool += transaction.message.len();
+            }
+            for key in Self::get_spent_slip_keys(transaction) {
+                self.pending_slips.insert(key, transaction.signature);
+            }
+            if let Some(sender) = transaction.get_nonce_sender() {
+                self.pending_nonces
+                    .entry(sender)
+                    .or_default()
+                    .insert(transaction.get_nonce().unwrap(), transaction.signature);
+            }
         }
     }
 
@@ -325,8 +803,10 @@ mod tests {
     use crate::common::defs::{
         push_lock, LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_MEMPOOL, LOCK_ORDER_WALLET,
     };
-    use crate::common::test_manager::test::{create_timestamp, TestManager};
+    use crate::common::keep_time::KeepTime;
+    use crate::common::test_manager::test::TestManager;
     use crate::core::data::burnfee::HEARTBEAT;
+    use crate::core::data::slip::Slip;
     use crate::core::data::wallet::Wallet;
     use crate::{lock_for_read, lock_for_write};
 
@@ -346,6 +826,242 @@ mod tests {
         assert_eq!(Some(block), mempool.blocks_queue.pop_front())
     }
 
+    fn create_transaction(signature: u8, fees: Currency, input_slips: &[u64]) -> Transaction {
+        let mut tx = Transaction::default();
+        for block_id in input_slips {
+            let mut slip = Slip::default();
+            slip.amount = 100;
+            slip.block_id = *block_id;
+            tx.add_input(slip);
+        }
+        tx.signature = [signature; 64];
+        tx.total_fees = fees;
+        tx.generate_hash_for_signature();
+        tx
+    }
+
+    #[tokio::test]
+    async fn mempool_conflicting_transactions_test() {
+        let mut mempool = Mempool::new([0; 33], [0; 32]);
+
+        let result = mempool
+            .add_transaction(create_transaction(1, 10, &[1]))
+            .await;
+        assert_eq!(result, AddTransactionResult::Added);
+        let result = mempool
+            .add_transaction(create_transaction(1, 10, &[1]))
+            .await;
+        assert_eq!(result, AddTransactionResult::Exists);
+        let result = mempool
+            .add_transaction(create_transaction(2, 10, &[1]))
+            .await;
+        assert_eq!(result, AddTransactionResult::Conflicting);
+        assert_eq!(
+            result.get_reject_reason(),
+            Some(TransactionRejectReason::DoubleSpend)
+        );
+        assert_eq!(mempool.transactions.len(), 1);
+
+        // higher fees replace the conflicting transaction
+        let result = mempool
+            .add_transaction(create_transaction(3, 20, &[1, 2]))
+            .await;
+        assert_eq!(result, AddTransactionResult::Replaced(1));
+        assert_eq!(result.get_reject_reason(), None);
+        assert!(!mempool.transactions.contains_key(&[1; 64]));
+        let result = mempool
+            .add_transaction(create_transaction(4, 10, &[3]))
+            .await;
+        assert_eq!(result, AddTransactionResult::Added);
+        assert_eq!(mempool.get_pending_slip_count(), 3);
+
+        // transaction in a block spending a slip of a pending transaction
+        mempool.delete_transactions(&vec![create_transaction(5, 10, &[2])]);
+        assert_eq!(mempool.transactions.len(), 1);
+        assert!(mempool.transactions.contains_key(&[4; 64]));
+        assert_eq!(mempool.get_pending_slip_count(), 1);
+
+        let result = mempool
+            .add_transaction(create_transaction(1, 10, &[1]))
+            .await;
+        assert_eq!(result, AddTransactionResult::Added);
+    }
+
+    #[tokio::test]
+    async fn mempool_data_transactions_test() {
+        let mut mempool = Mempool::new([0; 33], [0; 32]);
+
+        let mut tx = create_transaction(1, 10, &[1]);
+        tx.transaction_type = TransactionType::Data;
+        tx.message = vec![1; MAX_DATA_BYTES_IN_MEMPOOL - 10];
+        let result = mempool.add_transaction(tx).await;
+        assert_eq!(result, AddTransactionResult::Added);
+        assert_eq!(
+            mempool.get_data_bytes_in_mempool(),
+            MAX_DATA_BYTES_IN_MEMPOOL - 10
+        );
+
+        let mut tx = create_transaction(2, 10, &[2]);
+        tx.transaction_type = TransactionType::Data;
+        tx.message = vec![1; 20];
+        let result = mempool.add_transaction(tx).await;
+        assert_eq!(result, AddTransactionResult::Rejected);
+
+        // normal transactions are not limited by the data in mempool
+        let mut tx = create_transaction(3, 10, &[3]);
+        tx.message = vec![1; 20];
+        let result = mempool.add_transaction(tx).await;
+        assert_eq!(result, AddTransactionResult::Added);
+
+        mempool.delete_transactions(&vec![create_transaction(1, 10, &[1])]);
+        assert_eq!(mempool.get_data_bytes_in_mempool(), 0);
+    }
+
+    fn create_transaction_with_nonce(
+        signature: u8,
+        fees: Currency,
+        input_slips: &[u64],
+        nonce: u64,
+    ) -> Transaction {
+        let mut tx = create_transaction(signature, fees, input_slips);
+        tx.set_nonce(nonce);
+        tx
+    }
+
+    #[tokio::test]
+    async fn mempool_transaction_nonces_test() {
+        let mut mempool = Mempool::new([0; 33], [0; 32]);
+        assert_eq!(mempool.get_next_nonce(&[0; 33]), 0);
+
+        // gaps and duplicates are rejected
+        let result = mempool
+            .add_transaction(create_transaction_with_nonce(1, 10, &[1], 1))
+            .await;
+        assert_eq!(result, AddTransactionResult::InvalidNonce);
+        let result = mempool
+            .add_transaction(create_transaction_with_nonce(1, 10, &[1], 0))
+            .await;
+        assert_eq!(result, AddTransactionResult::Added);
+        let result = mempool
+            .add_transaction(create_transaction_with_nonce(2, 10, &[2], 0))
+            .await;
+        assert_eq!(result, AddTransactionResult::InvalidNonce);
+        let result = mempool
+            .add_transaction(create_transaction_with_nonce(2, 10, &[2], 1))
+            .await;
+        assert_eq!(result, AddTransactionResult::Added);
+        let result = mempool
+            .add_transaction(create_transaction_with_nonce(3, 10, &[3], 2))
+            .await;
+        assert_eq!(result, AddTransactionResult::Added);
+        assert_eq!(mempool.get_next_nonce(&[0; 33]), 3);
+
+        // transactions without a nonce are not ordered
+        let result = mempool
+            .add_transaction(create_transaction(4, 10, &[4]))
+            .await;
+        assert_eq!(result, AddTransactionResult::Added);
+
+        // replacing a nonce removes the later transactions of the sender
+        let result = mempool
+            .add_transaction(create_transaction_with_nonce(5, 20, &[2], 1))
+            .await;
+        assert_eq!(result, AddTransactionResult::Replaced(1));
+        assert_eq!(mempool.transactions.len(), 3);
+        assert!(!mempool.transactions.contains_key(&[3; 64]));
+        assert_eq!(mempool.get_next_nonce(&[0; 33]), 2);
+
+        // nonce 0 and 1 were included in a block by another node
+        let mut block = Block::new();
+        block.transactions = vec![
+            create_transaction_with_nonce(6, 10, &[6], 0),
+            create_transaction_with_nonce(7, 10, &[7], 1),
+        ];
+        let mut account_nonces = AccountNonces::new();
+        account_nonces.on_block_added(&block);
+        mempool.delete_transactions(&block.transactions);
+        mempool.set_account_nonces(&account_nonces);
+        assert_eq!(mempool.transactions.len(), 1);
+        assert!(mempool.transactions.contains_key(&[4; 64]));
+        assert_eq!(mempool.get_next_nonce(&[0; 33]), 2);
+    }
+
+    #[tokio::test]
+    async fn mempool_block_size_limit_test() {
+        let mut mempool = Mempool::new([0; 33], [0; 32]);
+        let small_tx = create_transaction(1, 100, &[1]);
+        let large_tx = create_transaction(2, 100, &[2, 3, 4]);
+        let cheap_tx = create_transaction(3, 10, &[5]);
+        let small_size = small_tx.get_serialized_size();
+        let large_size = large_tx.get_serialized_size();
+        assert!(large_size > small_size);
+        mempool.add_transaction(small_tx).await;
+        mempool.add_transaction(large_tx).await;
+        mempool.add_transaction(cheap_tx).await;
+
+        // large transaction pays more per byte than the cheap one, but doesn't fit after the small one
+        mempool.set_max_block_size((BLOCK_HEADER_SIZE + 2 * small_size) as u64);
+        let (transactions, stats) = mempool.take_transactions_for_block(BLOCK_HEADER_SIZE);
+        assert_eq!(transactions.len(), 2);
+        assert!(transactions.contains_key(&[1; 64]));
+        assert!(transactions.contains_key(&[3; 64]));
+        assert_eq!(
+            stats,
+            BlockBundlingStats {
+                included_transactions: 2,
+                included_bytes: 2 * small_size,
+                included_fees: 110,
+                deferred_transactions: 1,
+                deferred_bytes: large_size,
+            }
+        );
+        assert!(mempool.transactions.contains_key(&[2; 64]));
+
+        // reserved bytes are not used for the transactions
+        let (transactions, stats) =
+            mempool.take_transactions_for_block(BLOCK_HEADER_SIZE + small_size + 1);
+        assert!(transactions.is_empty());
+        assert_eq!(stats.deferred_transactions, 1);
+
+        mempool.set_max_block_size(0);
+        assert_eq!(mempool.get_max_block_size(), DEFAULT_MAX_BLOCK_SIZE);
+        let (transactions, _) = mempool.take_transactions_for_block(BLOCK_HEADER_SIZE);
+        assert_eq!(transactions.len(), 1);
+        assert!(mempool.transactions.is_empty());
+
+        // transactions which can't fit in any block are removed
+        mempool
+            .add_transaction(create_transaction(4, 100, &[6]))
+            .await;
+        mempool.set_max_block_size((BLOCK_HEADER_SIZE + small_size - 1) as u64);
+        let (transactions, stats) = mempool.take_transactions_for_block(BLOCK_HEADER_SIZE);
+        assert!(transactions.is_empty());
+        assert_eq!(stats.deferred_transactions, 0);
+        assert!(mempool.transactions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn mempool_block_size_limit_with_nonces_test() {
+        let mut mempool = Mempool::new([0; 33], [0; 32]);
+        let first_tx = create_transaction_with_nonce(1, 10, &[1], 0);
+        let second_tx = create_transaction_with_nonce(2, 1000, &[2], 1);
+        let size = first_tx.get_serialized_size();
+        assert_eq!(second_tx.get_serialized_size(), size);
+        mempool.add_transaction(first_tx).await;
+        mempool.add_transaction(second_tx).await;
+
+        // the second transaction pays more, but can't be included without the first one
+        mempool.set_max_block_size((BLOCK_HEADER_SIZE + size) as u64);
+        let (transactions, stats) = mempool.take_transactions_for_block(BLOCK_HEADER_SIZE);
+        assert_eq!(transactions.len(), 1);
+        assert!(transactions.contains_key(&[1; 64]));
+        assert_eq!(stats.deferred_transactions, 1);
+
+        mempool.recalculate_pending_transactions();
+        assert_eq!(mempool.get_pending_slip_count(), 1);
+        assert_eq!(mempool.get_next_nonce(&[0; 33]), 2);
+    }
+
     #[tokio::test]
     #[serial_test::serial]
     async fn mempool_bundle_blocks_test() {
@@ -354,11 +1070,13 @@ mod tests {
         let blockchain_lock: Arc<RwLock<Blockchain>>;
         let public_key: SaitoPublicKey;
         let private_key: SaitoPrivateKey;
+        let ts;
 
         {
             let mut t = TestManager::new();
             t.initialize(100, 720_000).await;
             t.wait_for_mining_event().await;
+            ts = t.clock.get_timestamp_in_ms();
 
             wallet_lock = t.get_wallet_lock();
             mempool_lock = t.get_mempool_lock();
@@ -372,7 +1090,6 @@ mod tests {
             private_key = wallet.private_key;
         }
 
-        let ts = create_timestamp();
         let _next_block_timestamp = ts + (HEARTBEAT * 2);
 
         let (blockchain, _blockchain_) = lock_for_read!(blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
@@ -417,4 +1134,33 @@ mod tests {
             .await
             .is_some());
     }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn block_production_with_simulated_clock_test() {
+        let mut t = TestManager::new();
+        t.initialize(100, 720_000).await;
+
+        let (blockchain, _blockchain_) = lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+        let (mempool, _mempool_) = lock_for_read!(t.mempool_lock, LOCK_ORDER_MEMPOOL);
+
+        // the first block was created at the current time of the clock
+        let status = mempool
+            .get_block_production_status(&blockchain, t.clock.get_timestamp_in_ms())
+            .unwrap();
+        assert_eq!(status.time_since_last_block, 0);
+        assert!(!status.has_enough_work());
+
+        let status = mempool
+            .get_block_production_status(&blockchain, t.clock.advance(HEARTBEAT))
+            .unwrap();
+        assert_eq!(status.time_since_last_block, HEARTBEAT);
+
+        // routing work needed drops to zero after 2 heartbeats
+        let status = mempool
+            .get_block_production_status(&blockchain, t.clock.advance(HEARTBEAT))
+            .unwrap();
+        assert_eq!(status.work_needed, 0);
+        assert!(status.has_enough_work());
+    }
 }
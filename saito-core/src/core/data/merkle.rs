@@ -47,6 +47,16 @@ pub struct MerkleTree {
     root: Box<MerkleTreeNode>,
 }
 
+/// Sibling hash on the path from a transaction to the merkle root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MerkleProofStep {
+    pub hash: SaitoHash,
+    /// sibling is hashed before the current node
+    pub is_left: bool,
+}
+
+pub const MERKLE_PROOF_STEP_SIZE: usize = 33;
+
 impl MerkleTree {
     pub fn len(&self) -> usize {
         self.root.count
@@ -131,6 +141,59 @@ impl MerkleTree {
         }));
     }
 
+    /// Sibling hashes from the transaction at `index` up to the root. A node without a sibling is carried to the next
+    /// level as is, so there is no step for that level.
+    pub fn generate_proof(
+        transactions: &[Transaction],
+        index: usize,
+    ) -> Option<Vec<MerkleProofStep>> {
+        if index >= transactions.len() {
+            return None;
+        }
+        let mut hashes: Vec<SaitoHash> = transactions
+            .iter()
+            .map(|transaction| {
+                transaction
+                    .hash_for_signature
+                    .unwrap_or_else(|| hash(&transaction.serialize_for_signature()))
+            })
+            .collect();
+        let mut index = index;
+        let mut proof = vec![];
+        while hashes.len() > 1 {
+            let sibling = index ^ 1;
+            if sibling < hashes.len() {
+                proof.push(MerkleProofStep {
+                    hash: hashes[sibling],
+                    is_left: sibling < index,
+                });
+            }
+            hashes = hashes
+                .chunks(2)
+                .map(|pair| {
+                    if pair.len() == 2 {
+                        hash(&[pair[0].as_slice(), pair[1].as_slice()].concat())
+                    } else {
+                        pair[0]
+                    }
+                })
+                .collect();
+            index /= 2;
+        }
+        Some(proof)
+    }
+
+    pub fn verify_proof(leaf: &SaitoHash, proof: &[MerkleProofStep], root: &SaitoHash) -> bool {
+        let computed = proof.iter().fold(*leaf, |current, step| {
+            if step.is_left {
+                hash(&[step.hash.as_slice(), current.as_slice()].concat())
+            } else {
+                hash(&[current.as_slice(), step.hash.as_slice()].concat())
+            }
+        });
+        computed == *root
+    }
+
     pub fn traverse(&self, mode: TraverseMode, read_func: impl Fn(&MerkleTreeNode)) {
         MerkleTree::traverse_node(&mode, &self.root, &read_func);
     }
@@ -261,9 +324,127 @@ impl MerkleTree {
     }
 }
 
+/// Levels are hashed in parallel only when building from more leaves than this
+const PARALLEL_BUILD_THRESHOLD: usize = 1_000;
+
+/// Merkle root built level by level from the transaction hashes, giving the same root as `MerkleTree::generate`.
+/// Appending or replacing a transaction only rehashes the path from its leaf to the root, so a block can be bundled
+/// without rebuilding the whole tree for every added transaction.
+#[derive(Debug, Clone, Default)]
+pub struct MerkleTreeBuilder {
+    // levels[0] are the leaves, the last level has the root
+    levels: Vec<Vec<SaitoHash>>,
+}
+
+impl MerkleTreeBuilder {
+    pub fn new() -> MerkleTreeBuilder {
+        Default::default()
+    }
+
+    pub fn from_transactions(transactions: &[Transaction]) -> MerkleTreeBuilder {
+        let leaves = transactions
+            .iter()
+            .map(MerkleTreeBuilder::get_leaf_hash)
+            .collect();
+        MerkleTreeBuilder::from_leaves(leaves)
+    }
+
+    pub fn from_leaves(leaves: Vec<SaitoHash>) -> MerkleTreeBuilder {
+        if leaves.is_empty() {
+            return MerkleTreeBuilder::new();
+        }
+        let mut levels = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let nodes = levels.last().unwrap();
+            let next = if nodes.len() > PARALLEL_BUILD_THRESHOLD {
+                nodes
+                    .par_chunks(2)
+                    .map(MerkleTreeBuilder::hash_pair)
+                    .collect()
+            } else {
+                nodes.chunks(2).map(MerkleTreeBuilder::hash_pair).collect()
+            };
+            levels.push(next);
+        }
+        MerkleTreeBuilder { levels }
+    }
+
+    fn get_leaf_hash(transaction: &Transaction) -> SaitoHash {
+        transaction
+            .hash_for_signature
+            .unwrap_or_else(|| hash(&transaction.serialize_for_signature()))
+    }
+
+    // a node without a sibling is carried to the next level as is
+    fn hash_pair(pair: &[SaitoHash]) -> SaitoHash {
+        if pair.len() == 2 {
+            hash(&[pair[0].as_slice(), pair[1].as_slice()].concat())
+        } else {
+            pair[0]
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.first().map_or(0, |leaves| leaves.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// None if there are no transactions
+    pub fn get_root_hash(&self) -> Option<SaitoHash> {
+        self.levels.last().map(|root| root[0])
+    }
+
+    pub fn push_transaction(&mut self, transaction: &Transaction) {
+        self.push(MerkleTreeBuilder::get_leaf_hash(transaction));
+    }
+
+    pub fn push(&mut self, leaf: SaitoHash) {
+        if self.levels.is_empty() {
+            self.levels.push(vec![]);
+        }
+        self.levels[0].push(leaf);
+        self.update_path(self.levels[0].len() - 1);
+    }
+
+    /// Replaces the leaf at `index`, e.g. after the transaction is signed again. Returns false if out of range
+    pub fn update(&mut self, index: usize, leaf: SaitoHash) -> bool {
+        if index >= self.len() {
+            return false;
+        }
+        self.levels[0][index] = leaf;
+        self.update_path(index);
+        true
+    }
+
+    fn update_path(&mut self, index: usize) {
+        let mut index = index;
+        let mut level = 0;
+        while self.levels[level].len() > 1 {
+            let start = index - index % 2;
+            let end = (start + 2).min(self.levels[level].len());
+            let parent = MerkleTreeBuilder::hash_pair(&self.levels[level][start..end]);
+            index /= 2;
+            level += 1;
+            if self.levels.len() == level {
+                self.levels.push(vec![]);
+            }
+            let nodes = &mut self.levels[level];
+            if index == nodes.len() {
+                nodes.push(parent);
+            } else {
+                nodes[index] = parent;
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::core::data::merkle::MerkleTree;
+    use crate::core::data::crypto::hash;
+    use crate::core::data::merkle::{MerkleTree, MerkleTreeBuilder, PARALLEL_BUILD_THRESHOLD};
     use crate::core::data::transaction::Transaction;
     use crate::core::data::wallet::Wallet;
 
@@ -349,4 +530,76 @@ mod tests {
         //     print!("{}, ", hex::encode(node.hash.unwrap()))
         // });
     }
+
+    #[test]
+    fn merkle_proof_test() {
+        let wallet = Wallet::new();
+
+        for count in 1..8 {
+            let mut transactions = vec![];
+            for i in 0..count {
+                let mut transaction = Transaction::default();
+                transaction.timestamp = i;
+                transaction.sign(&wallet.private_key);
+                transactions.push(transaction);
+            }
+            let root = MerkleTree::generate(&transactions).unwrap().get_root_hash();
+
+            for (index, transaction) in transactions.iter().enumerate() {
+                let leaf = transaction.hash_for_signature.unwrap();
+                let proof = MerkleTree::generate_proof(&transactions, index).unwrap();
+                assert!(MerkleTree::verify_proof(&leaf, &proof, &root));
+                assert!(!MerkleTree::verify_proof(&[1; 32], &proof, &root));
+            }
+            assert!(MerkleTree::generate_proof(&transactions, count as usize).is_none());
+        }
+    }
+
+    #[test]
+    fn merkle_tree_builder_test() {
+        let wallet = Wallet::new();
+        let mut builder = MerkleTreeBuilder::new();
+        assert!(builder.is_empty());
+        assert_eq!(builder.get_root_hash(), None);
+
+        let mut transactions = vec![];
+        for i in 0..20 {
+            let mut transaction = Transaction::default();
+            transaction.timestamp = i;
+            transaction.sign(&wallet.private_key);
+            builder.push_transaction(&transaction);
+            transactions.push(transaction);
+
+            let root = MerkleTree::generate(&transactions).unwrap().get_root_hash();
+            assert_eq!(builder.len(), transactions.len());
+            assert_eq!(builder.get_root_hash(), Some(root));
+            assert_eq!(
+                MerkleTreeBuilder::from_transactions(&transactions).get_root_hash(),
+                Some(root)
+            );
+        }
+
+        for index in [0, 7, 19] {
+            transactions[index].timestamp = 100;
+            transactions[index].sign(&wallet.private_key);
+            assert!(builder.update(index, transactions[index].hash_for_signature.unwrap()));
+            let root = MerkleTree::generate(&transactions).unwrap().get_root_hash();
+            assert_eq!(builder.get_root_hash(), Some(root));
+        }
+        assert!(!builder.update(20, [0; 32]));
+    }
+
+    #[test]
+    fn merkle_tree_builder_parallel_test() {
+        let leaves: Vec<[u8; 32]> = (0..PARALLEL_BUILD_THRESHOLD as u32 * 3 + 1)
+            .map(|index| hash(&index.to_be_bytes()))
+            .collect();
+        let mut sequential = MerkleTreeBuilder::new();
+        for leaf in leaves.iter() {
+            sequential.push(*leaf);
+        }
+        let builder = MerkleTreeBuilder::from_leaves(leaves);
+        assert_eq!(builder.len(), sequential.len());
+        assert_eq!(builder.get_root_hash(), sequential.get_root_hash());
+    }
 }
@@ -0,0 +1,76 @@
+use crate::common::defs::{SaitoHash, Timestamp};
+
+/// State of the node's miner, published by the mining thread so it can be queried without parsing the logs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MiningStatus {
+    pub mining_enabled: bool,
+    pub miner_active: bool,
+    /// hash of the block the miner is solving
+    pub target: SaitoHash,
+    pub difficulty: u64,
+    pub latest_block_hash: SaitoHash,
+    pub mined_golden_tickets: u64,
+    pub total_hashes: u64,
+    /// hashes per second since the previous sample
+    pub hashrate: u64,
+    // time and total hashes of the previous hashrate sample
+    sampled_at: Timestamp,
+    sampled_hashes: u64,
+}
+
+impl MiningStatus {
+    pub fn new() -> MiningStatus {
+        Default::default()
+    }
+
+    /// Updates the hashrate with the hashes done since the previous sample
+    pub fn sample_hashrate(&mut self, total_hashes: u64, current_time: Timestamp) {
+        self.total_hashes = total_hashes;
+        if self.sampled_at != 0 && current_time > self.sampled_at {
+            self.hashrate = total_hashes.saturating_sub(self.sampled_hashes) * 1000
+                / (current_time - self.sampled_at);
+        }
+        self.sampled_at = current_time;
+        self.sampled_hashes = total_hashes;
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "mining_enabled": self.mining_enabled,
+            "miner_active": self.miner_active,
+            "target": hex::encode(self.target),
+            "difficulty": self.difficulty,
+            "latest_block_hash": hex::encode(self.latest_block_hash),
+            "mined_golden_tickets": self.mined_golden_tickets,
+            "total_hashes": self.total_hashes,
+            "hashrate": self.hashrate,
+        })
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::mining_status::MiningStatus;
+
+    #[test]
+    fn mining_status_test() {
+        let mut status = MiningStatus::new();
+        status.sample_hashrate(100, 1_000);
+        assert_eq!(status.hashrate, 0);
+        status.sample_hashrate(600, 3_000);
+        assert_eq!(status.hashrate, 250);
+        assert_eq!(status.total_hashes, 600);
+        // clock didn't move
+        status.sample_hashrate(700, 3_000);
+        assert_eq!(status.hashrate, 250);
+
+        status.target = [1; 32];
+        status.difficulty = 5;
+        let json: serde_json::Value = serde_json::from_str(status.to_json().as_str()).unwrap();
+        assert_eq!(json["target"], hex::encode([1; 32]));
+        assert_eq!(json["difficulty"], 5);
+        assert_eq!(json["hashrate"], 250);
+        assert_eq!(json["total_hashes"], 700);
+    }
+}
@@ -1,22 +1,66 @@
+pub mod account_nonce;
+pub mod address_book;
+pub mod archive_sink;
+pub mod audit_log;
+pub mod balance_proof;
 pub mod block;
+pub mod block_announcement_cache;
+pub mod block_fetch;
+pub mod block_queue;
 pub mod blockchain;
+pub mod blockchain_snapshot;
 pub mod blockchain_sync_state;
 pub mod blockring;
 pub mod burnfee;
+pub mod chain_export;
+pub mod chain_iterator;
+pub mod chain_stats;
 pub mod configuration;
+pub mod conformance;
+pub mod consensus_health;
 pub mod context;
+pub mod creator_blacklist;
 pub mod crypto;
+pub mod data_migration;
+pub mod difficulty;
+pub mod fork_graph;
+pub mod format_version;
+pub mod full_block_cache;
 pub mod golden_ticket;
-pub mod hop;
+pub mod handle_registry;
+pub(crate) mod hop;
+pub mod issuance;
+pub mod key_format;
+pub mod known_inventory;
+pub mod memory_report;
 pub mod mempool;
 pub mod merkle;
+pub mod mining_status;
 pub mod msg;
+pub mod multisig;
 pub mod network;
+pub mod network_preset;
+pub mod orphan_block_pool;
 pub mod peer;
 pub mod peer_collection;
-pub mod ringitem;
-pub mod serialize;
+pub mod peer_events;
+pub mod peer_identity;
+pub mod peer_message_stats;
+pub mod peer_quorum;
+pub mod peer_requests;
+pub(crate) mod ringitem;
+pub mod routing_report;
+pub(crate) mod serialize;
 pub mod slip;
+pub mod stale_chain_watchdog;
 pub mod storage;
+pub mod timestamp_index;
 pub mod transaction;
+pub mod transaction_index;
+pub mod transaction_receipts;
+pub mod trusted_sync;
+pub mod tx_generator;
+pub mod utxo_report;
+pub mod utxoset;
 pub mod wallet;
+pub mod webhook;
@@ -0,0 +1,59 @@
+use std::io::{Error, ErrorKind};
+
+use crate::common::defs::SaitoHash;
+use crate::core::data::serialize::Serialize;
+
+/// Sent in reply to a fork id request, with the fork id of the peer's longest chain
+#[derive(Debug)]
+pub struct ForkIdResponse {
+    pub(crate) latest_block_id: u64,
+    pub(crate) fork_id: SaitoHash,
+}
+
+impl Serialize<Self> for ForkIdResponse {
+    fn serialize(&self) -> Vec<u8> {
+        [
+            self.latest_block_id.to_be_bytes().as_slice(),
+            self.fork_id.as_slice(),
+        ]
+        .concat()
+    }
+
+    fn deserialize(buffer: &Vec<u8>) -> Result<Self, Error> {
+        if buffer.len() != 40 {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        Ok(ForkIdResponse {
+            latest_block_id: u64::from_be_bytes(buffer[0..8].to_vec().try_into().unwrap()),
+            fork_id: buffer[8..40].to_vec().try_into().unwrap(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::crypto::generate_random_bytes;
+    use crate::core::data::msg::fork_id::ForkIdResponse;
+    use crate::core::data::serialize::Serialize;
+
+    #[test]
+    fn test_serialize_fork_id_response() {
+        let response = ForkIdResponse {
+            latest_block_id: 25,
+            fork_id: generate_random_bytes(32).try_into().unwrap(),
+        };
+        let buffer = response.serialize();
+        assert_eq!(buffer.len(), 40);
+        let new_response = ForkIdResponse::deserialize(&buffer);
+        assert!(new_response.is_ok());
+        let new_response = new_response.unwrap();
+        assert_eq!(response.latest_block_id, new_response.latest_block_id);
+        assert_eq!(response.fork_id, new_response.fork_id);
+    }
+
+    #[test]
+    fn test_deserialize_invalid_buffer() {
+        let result = ForkIdResponse::deserialize(&vec![0; 39]);
+        assert!(result.is_err());
+    }
+}
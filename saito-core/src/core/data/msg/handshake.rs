@@ -2,12 +2,46 @@ use std::io::{Error, ErrorKind};
 
 use tracing::warn;
 
-use crate::common::defs::{SaitoHash, SaitoPublicKey, SaitoSignature};
+use crate::common::defs::{SaitoHash, SaitoPublicKey, SaitoSignature, Timestamp};
+use crate::core::data::peer_identity::{KeyRotationProof, KEY_ROTATION_PROOF_SIZE};
 use crate::core::data::serialize::Serialize;
 
+/// max age of a handshake challenge. responses to older challenges are rejected
+pub const HANDSHAKE_CHALLENGE_EXPIRY_TIME: Timestamp = 60_000;
+
 #[derive(Debug)]
 pub struct HandshakeChallenge {
     pub challenge: SaitoHash,
+    pub issued_at: Timestamp,
+    /// public key of the challenger
+    pub public_key: SaitoPublicKey,
+    /// address the challenger is reachable at (host:port of its endpoint). the responder only answers if it matches
+    /// the address it connected to
+    pub peer_address: String,
+}
+
+impl HandshakeChallenge {
+    /// Payload signed by the responder. Includes the issue time, the challenger's public key and the challenger's
+    /// address named in the challenge, so a captured response cannot be replayed to another node, through another
+    /// address or after the challenge expires.
+    pub fn get_payload_to_sign(
+        challenge: &SaitoHash,
+        issued_at: Timestamp,
+        challenger_public_key: &SaitoPublicKey,
+        peer_address: &str,
+    ) -> Vec<u8> {
+        [
+            challenge.as_slice(),
+            issued_at.to_be_bytes().as_slice(),
+            challenger_public_key.as_slice(),
+            peer_address.as_bytes(),
+        ]
+        .concat()
+    }
+
+    pub fn is_expired(issued_at: Timestamp, current_time: Timestamp) -> bool {
+        current_time.saturating_sub(issued_at) > HANDSHAKE_CHALLENGE_EXPIRY_TIME
+    }
 }
 
 // TODO : can we drop other 2 structs and only use this ? need to confirm with more fields being added
@@ -16,8 +50,21 @@ pub struct HandshakeResponse {
     pub public_key: SaitoPublicKey,
     pub signature: SaitoSignature,
     pub is_lite: u64,
+    /// the sender accepts compressed messages
+    pub supports_compression: bool,
+    /// latest block and transaction format version the sender can read
+    pub format_version: u8,
     pub block_fetch_url: String,
+    /// network the sender belongs to. peers from other networks are rejected
+    pub network_id: String,
+    /// proof of the sender moving from its previous key to the current key. lets peers which pinned the previous key
+    /// accept the new key
+    pub key_rotation_proof: Option<KeyRotationProof>,
+    /// new challenge for the peer
     pub challenge: SaitoHash,
+    pub challenge_issued_at: Timestamp,
+    /// address of the sender named in the new challenge
+    pub challenge_peer_address: String,
 }
 
 // #[derive(Debug)]
@@ -30,22 +77,44 @@ pub struct HandshakeResponse {
 
 impl Serialize<Self> for HandshakeChallenge {
     fn serialize(&self) -> Vec<u8> {
-        let buffer = [self.challenge.to_vec()].concat();
-        return buffer;
+        [
+            self.challenge.as_slice(),
+            self.issued_at.to_be_bytes().as_slice(),
+            self.public_key.as_slice(),
+            (self.peer_address.len() as u32).to_be_bytes().as_slice(),
+            self.peer_address.as_bytes(),
+        ]
+        .concat()
     }
     fn deserialize(buffer: &Vec<u8>) -> Result<Self, Error> {
-        if buffer.len() < 32 {
+        if buffer.len() < 77 {
             warn!(
                 "Deserializing Handshake Challenge, buffer size is :{:?}",
                 buffer.len()
             );
             return Err(Error::from(ErrorKind::InvalidData));
         }
+        let address_length = u32::from_be_bytes(buffer[73..77].try_into().unwrap()) as usize;
+        if buffer.len() - 77 < address_length {
+            warn!(
+                "Deserializing Handshake Challenge, buffer size is :{:?} for address length : {:?}",
+                buffer.len(),
+                address_length
+            );
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let result = String::from_utf8(buffer[77..77 + address_length].to_vec());
+        if result.is_err() {
+            warn!("failed decoding peer address. {:?}", result.err().unwrap());
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
 
-        let mut challenge = HandshakeChallenge { challenge: [0; 32] };
-        challenge.challenge = buffer[0..32].to_vec().try_into().unwrap();
-
-        return Ok(challenge);
+        Ok(HandshakeChallenge {
+            challenge: buffer[0..32].to_vec().try_into().unwrap(),
+            issued_at: Timestamp::from_be_bytes(buffer[32..40].try_into().unwrap()),
+            public_key: buffer[40..73].to_vec().try_into().unwrap(),
+            peer_address: result.unwrap(),
+        })
     }
 }
 
@@ -55,14 +124,26 @@ impl Serialize<Self> for HandshakeResponse {
             self.public_key.to_vec(),
             self.signature.to_vec(),
             self.challenge.to_vec(),
+            self.challenge_issued_at.to_be_bytes().to_vec(),
             self.is_lite.to_be_bytes().to_vec(),
+            vec![self.supports_compression as u8, self.format_version],
             (self.block_fetch_url.len() as u32).to_be_bytes().to_vec(),
             self.block_fetch_url.as_bytes().to_vec(),
+            (self.network_id.len() as u32).to_be_bytes().to_vec(),
+            self.network_id.as_bytes().to_vec(),
+            (self.challenge_peer_address.len() as u32)
+                .to_be_bytes()
+                .to_vec(),
+            self.challenge_peer_address.as_bytes().to_vec(),
+            match &self.key_rotation_proof {
+                Some(proof) => [vec![1], proof.serialize()].concat(),
+                None => vec![0],
+            },
         ]
         .concat()
     }
     fn deserialize(buffer: &Vec<u8>) -> Result<Self, Error> {
-        if buffer.len() < 141 {
+        if buffer.len() < 160 {
             warn!(
                 "Deserializing Handshake Response, buffer size is :{:?}",
                 buffer.len()
@@ -74,24 +155,87 @@ impl Serialize<Self> for HandshakeResponse {
             public_key: buffer[0..33].to_vec().try_into().unwrap(),
             signature: buffer[33..97].to_vec().try_into().unwrap(),
             challenge: buffer[97..129].to_vec().try_into().unwrap(),
-            is_lite: u64::from_be_bytes(buffer[129..137].try_into().unwrap()),
+            challenge_issued_at: Timestamp::from_be_bytes(buffer[129..137].try_into().unwrap()),
+            challenge_peer_address: "".to_string(),
+            is_lite: u64::from_be_bytes(buffer[137..145].try_into().unwrap()),
+            supports_compression: buffer[145] != 0,
+            format_version: buffer[146],
             block_fetch_url: "".to_string(),
+            network_id: "".to_string(),
+            key_rotation_proof: None,
         };
 
-        let url_length = u32::from_be_bytes(buffer[137..141].try_into().unwrap());
+        let url_length = u32::from_be_bytes(buffer[147..151].try_into().unwrap()) as usize;
+        // url is followed by the network id length. lengths are compared without adding to the untrusted length,
+        // so it can't overflow in 32 bit targets
+        if buffer.len() - 160 < url_length {
+            warn!(
+                "Deserializing Handshake Response, buffer size is :{:?} for url length : {:?}",
+                buffer.len(),
+                url_length
+            );
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let result = String::from_utf8(buffer[151..151 + url_length].to_vec());
+        if result.is_err() {
+            warn!(
+                "failed decoding block fetch url. {:?}",
+                result.err().unwrap()
+            );
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        response.block_fetch_url = result.unwrap();
 
-        if url_length > 0 {
-            let result =
-                String::from_utf8(buffer[141..141 as usize + url_length as usize].to_vec());
-            if result.is_err() {
+        let start = 155 + url_length;
+        let network_id_length =
+            u32::from_be_bytes(buffer[start - 4..start].try_into().unwrap()) as usize;
+        // network id is followed by the peer address length and the key rotation proof flag
+        if buffer.len() - start - 5 < network_id_length {
+            warn!(
+                "Deserializing Handshake Response, buffer size is :{:?} for network id length : {:?}",
+                buffer.len(),
+                network_id_length
+            );
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let result = String::from_utf8(buffer[start..start + network_id_length].to_vec());
+        if result.is_err() {
+            warn!("failed decoding network id. {:?}", result.err().unwrap());
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        response.network_id = result.unwrap();
+
+        let start = start + network_id_length + 4;
+        let address_length =
+            u32::from_be_bytes(buffer[start - 4..start].try_into().unwrap()) as usize;
+        // peer address is followed by the key rotation proof flag
+        if buffer.len() - start - 1 < address_length {
+            warn!(
+                "Deserializing Handshake Response, buffer size is :{:?} for address length : {:?}",
+                buffer.len(),
+                address_length
+            );
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let result = String::from_utf8(buffer[start..start + address_length].to_vec());
+        if result.is_err() {
+            warn!("failed decoding peer address. {:?}", result.err().unwrap());
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        response.challenge_peer_address = result.unwrap();
+
+        let start = start + address_length;
+        if buffer[start] != 0 {
+            if buffer.len() - start - 1 < KEY_ROTATION_PROOF_SIZE {
                 warn!(
-                    "failed decoding block fetch url. {:?}",
-                    result.err().unwrap()
+                    "Deserializing Handshake Response, buffer size is :{:?} for key rotation proof",
+                    buffer.len()
                 );
                 return Err(Error::from(ErrorKind::InvalidData));
             }
-
-            response.block_fetch_url = result.unwrap();
+            response.key_rotation_proof = Some(KeyRotationProof::deserialize(
+                &buffer[start + 1..start + 1 + KEY_ROTATION_PROOF_SIZE],
+            )?);
         }
 
         Ok(response)
@@ -115,7 +259,12 @@ impl Serialize<Self> for HandshakeResponse {
 
 #[cfg(test)]
 mod tests {
-    use crate::core::data::msg::handshake::{HandshakeChallenge, HandshakeResponse};
+    use crate::core::data::crypto::generate_keys;
+    use crate::core::data::format_version::CURRENT_FORMAT_VERSION;
+    use crate::core::data::msg::handshake::{
+        HandshakeChallenge, HandshakeResponse, HANDSHAKE_CHALLENGE_EXPIRY_TIME,
+    };
+    use crate::core::data::peer_identity::KeyRotationProof;
     use crate::core::data::serialize::Serialize;
 
     #[test]
@@ -128,31 +277,65 @@ mod tests {
             crypto.generate_keypair(&mut secp256k1::rand::thread_rng());
         let challenge = HandshakeChallenge {
             challenge: rand::random(),
+            issued_at: 1000,
+            public_key: public_key_1.serialize(),
+            peer_address: "localhost:12101".to_string(),
         };
         let buffer = challenge.serialize();
-        assert_eq!(buffer.len(), 32);
+        assert_eq!(buffer.len(), 92);
         let challenge2 = HandshakeChallenge::deserialize(&buffer).expect("deserialization failed");
         assert_eq!(challenge.challenge, challenge2.challenge);
+        assert_eq!(challenge.issued_at, challenge2.issued_at);
+        assert_eq!(challenge.public_key, challenge2.public_key);
+        assert_eq!(challenge.peer_address, challenge2.peer_address);
+        assert!(HandshakeChallenge::deserialize(&buffer[0..91].to_vec()).is_err());
 
         let signature = crypto.sign_ecdsa(
             &secp256k1::Message::from_slice(&challenge.challenge).unwrap(),
             &secret_key_2,
         );
-        let response = HandshakeResponse {
+        let mut response = HandshakeResponse {
             public_key: public_key_2.serialize(),
             signature: signature.serialize_compact(),
             challenge: rand::random(),
+            challenge_issued_at: 2000,
+            challenge_peer_address: "127.0.0.1:12101".to_string(),
             is_lite: 0,
+            supports_compression: true,
+            format_version: CURRENT_FORMAT_VERSION,
             block_fetch_url: "http://url/test2".to_string(),
+            network_id: "testnet".to_string(),
+            key_rotation_proof: None,
         };
         let buffer = response.serialize();
-        assert_eq!(buffer.len(), 157);
+        assert_eq!(buffer.len(), 198);
         let response2 = HandshakeResponse::deserialize(&buffer).expect("deserialization failed");
         assert_eq!(response.challenge, response2.challenge);
+        assert_eq!(response.challenge_issued_at, response2.challenge_issued_at);
         assert_eq!(response.public_key, response2.public_key);
         assert_eq!(response.block_fetch_url, response2.block_fetch_url);
+        assert_eq!(response.network_id, response2.network_id);
+        assert_eq!(
+            response.challenge_peer_address,
+            response2.challenge_peer_address
+        );
+        assert!(response2.supports_compression);
+        assert_eq!(response2.format_version, CURRENT_FORMAT_VERSION);
 
         assert_eq!(response.signature, response2.signature);
+        assert!(HandshakeResponse::deserialize(&buffer[0..161].to_vec()).is_err());
+        assert!(HandshakeResponse::deserialize(&buffer[0..197].to_vec()).is_err());
+        assert!(response2.key_rotation_proof.is_none());
+
+        let (old_public_key, old_private_key) = generate_keys();
+        let proof = KeyRotationProof::new(old_public_key, &old_private_key, response.public_key);
+        response.key_rotation_proof = Some(proof.clone());
+        let buffer = response.serialize();
+        assert_eq!(buffer.len(), 328);
+        let response2 = HandshakeResponse::deserialize(&buffer).expect("deserialization failed");
+        assert_eq!(response2.key_rotation_proof, Some(proof));
+        assert_eq!(response2.network_id, response.network_id);
+        assert!(HandshakeResponse::deserialize(&buffer[0..327].to_vec()).is_err());
         // let response = HandshakeCompletion {
         //     signature: signature.serialize_compact(),
         // };
@@ -161,4 +344,19 @@ mod tests {
         // let response2 = HandshakeCompletion::deserialize(&buffer).expect("deserialization failed");
         // assert_eq!(response.signature, response2.signature);
     }
+
+    #[test]
+    fn challenge_expiry_test() {
+        assert!(!HandshakeChallenge::is_expired(1000, 1000));
+        assert!(!HandshakeChallenge::is_expired(
+            1000,
+            1000 + HANDSHAKE_CHALLENGE_EXPIRY_TIME
+        ));
+        assert!(HandshakeChallenge::is_expired(
+            1000,
+            1001 + HANDSHAKE_CHALLENGE_EXPIRY_TIME
+        ));
+        // challenges from the future are not considered expired here
+        assert!(!HandshakeChallenge::is_expired(2000, 1000));
+    }
 }
@@ -2,12 +2,124 @@ use std::io::{Error, ErrorKind};
 
 use tracing::{trace, warn};
 
-use crate::common::defs::SaitoHash;
+use crate::common::defs::{SaitoHash, SaitoPublicKey};
+use crate::core::data::balance_proof::BalanceProof;
 use crate::core::data::block::{Block, BlockType};
+use crate::core::data::format_version::CURRENT_FORMAT_VERSION;
+use crate::core::data::golden_ticket::GoldenTicket;
 use crate::core::data::msg::block_request::BlockchainRequest;
+use crate::core::data::msg::fork_id::ForkIdResponse;
 use crate::core::data::msg::handshake::{HandshakeChallenge, HandshakeResponse};
+use crate::core::data::msg::transaction_batch::{
+    TransactionBatch, TransactionBatchResult, TransactionRejection,
+};
 use crate::core::data::serialize::Serialize;
 use crate::core::data::transaction::Transaction;
+use crate::core::data::trusted_sync::UtxoSnapshot;
+
+/// version of the message encoding. should be incremented when a message type is added or a payload format changes.
+/// blocks and transactions have their own format versions, advertised in the handshake. only peers with this version
+/// are accepted
+pub const MESSAGE_WIRE_FORMAT_VERSION: u32 = 10;
+/// first bytes of each message. used to reject buffers which are not saito messages
+pub const MESSAGE_MAGIC: [u8; 2] = [0x53, 0x41];
+/// magic (2) + version (1) + flags (1) + message type (1) + request id (4) + payload length (4)
+pub const MESSAGE_HEADER_SIZE: usize = 13;
+/// payloads smaller than this are not compressed since the saving doesn't cover the compression cost
+pub const MESSAGE_COMPRESSION_THRESHOLD: usize = 1024;
+/// max size of a decompressed payload. larger payloads are rejected before decompressing
+pub const MAX_DECOMPRESSED_PAYLOAD_SIZE: usize = 64 * 1024 * 1024;
+
+/// payload is compressed with lz4 and prefixed with the uncompressed length
+const MESSAGE_FLAG_COMPRESSED: u8 = 0b0000_0001;
+
+/// Frame header of a serialized message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageHeader {
+    pub version: u8,
+    pub flags: u8,
+    pub message_type: u8,
+    pub request_id: u32,
+    pub payload_length: u32,
+}
+
+impl MessageHeader {
+    pub fn is_compressed(&self) -> bool {
+        self.flags & MESSAGE_FLAG_COMPRESSED != 0
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            MESSAGE_MAGIC.as_slice(),
+            &[self.version, self.flags, self.message_type],
+            self.request_id.to_be_bytes().as_slice(),
+            self.payload_length.to_be_bytes().as_slice(),
+        ]
+        .concat()
+    }
+
+    /// Reads the header and checks that the buffer has the magic, a supported version and exactly the payload length
+    pub fn deserialize(buffer: &[u8]) -> Result<MessageHeader, Error> {
+        if buffer.len() < MESSAGE_HEADER_SIZE {
+            warn!("message buffer size : {:?} is too small", buffer.len());
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        if buffer[0..2] != MESSAGE_MAGIC {
+            warn!("message magic : {:?} not valid", hex::encode(&buffer[0..2]));
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let header = MessageHeader {
+            version: buffer[2],
+            flags: buffer[3],
+            message_type: buffer[4],
+            request_id: u32::from_be_bytes(buffer[5..9].try_into().unwrap()),
+            payload_length: u32::from_be_bytes(buffer[9..13].try_into().unwrap()),
+        };
+        if header.version as u32 != MESSAGE_WIRE_FORMAT_VERSION {
+            warn!(
+                "message version : {:?} not supported. expected : {:?}",
+                header.version, MESSAGE_WIRE_FORMAT_VERSION
+            );
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        if buffer.len() - MESSAGE_HEADER_SIZE != header.payload_length as usize {
+            warn!(
+                "message payload length : {:?} doesn't match the buffer size : {:?}",
+                header.payload_length,
+                buffer.len()
+            );
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        Ok(header)
+    }
+}
+
+fn compress_payload(payload: &[u8]) -> Vec<u8> {
+    [
+        (payload.len() as u32).to_be_bytes().as_slice(),
+        lz4_flex::compress(payload).as_slice(),
+    ]
+    .concat()
+}
+
+fn decompress_payload(buffer: &[u8]) -> Result<Vec<u8>, Error> {
+    if buffer.len() < 4 {
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+    let length = u32::from_be_bytes(buffer[0..4].try_into().unwrap()) as usize;
+    if length > MAX_DECOMPRESSED_PAYLOAD_SIZE {
+        warn!("decompressed payload size : {:?} is too large", length);
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+    let payload = lz4_flex::decompress(&buffer[4..], length).map_err(|error| {
+        warn!("failed decompressing message payload : {:?}", error);
+        Error::from(ErrorKind::InvalidData)
+    })?;
+    if payload.len() != length {
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+    Ok(payload)
+}
 
 #[derive(Debug)]
 pub enum Message {
@@ -26,22 +138,66 @@ pub enum Message {
     Result(),
     Error(),
     ApplicationTransaction(Vec<u8>),
+    ForkIdRequest(),
+    ForkIdResponse(ForkIdResponse),
+    TransactionBatch(TransactionBatch),
+    TransactionBatchResult(TransactionBatchResult),
+    TrustedSyncRequest(),
+    TrustedSyncResponse(UtxoSnapshot),
+    BalanceProofRequest(SaitoPublicKey),
+    BalanceProofResponse(BalanceProof),
+    /// golden ticket found by a miner node which doesn't produce blocks
+    GoldenTicket(GoldenTicket),
+    /// single transaction sent by the peer which was not added to the mempool
+    TransactionRejected(TransactionRejection),
 }
 
 impl Message {
+    /// Serializes the message without compression. Used for peers which didn't negotiate compression
     pub fn serialize(&self) -> Vec<u8> {
-        let message_type: u8 = self.get_type_value();
-        let request_id: u32 = 0;
-        let mut buffer: Vec<u8> = vec![];
-        buffer.extend(&message_type.to_be_bytes());
-        buffer.extend(&request_id.to_be_bytes());
-        buffer.append(&mut match self {
+        self.serialize_with_compression(false)
+    }
+
+    /// Serializes the message and compresses the payload if `compress` is set and the compressed payload is smaller
+    pub fn serialize_with_compression(&self, compress: bool) -> Vec<u8> {
+        self.serialize_for_peer(compress, CURRENT_FORMAT_VERSION)
+            .expect("messages can always be serialized in the current format version")
+    }
+
+    /// Serializes the message with the blocks and transactions in the format version negotiated with the peer. Fails
+    /// if a block can't be written in that version
+    pub fn serialize_for_peer(&self, compress: bool, format_version: u8) -> Result<Vec<u8>, Error> {
+        let mut payload = self.serialize_payload(format_version)?;
+        let mut flags = 0;
+        if compress && payload.len() >= MESSAGE_COMPRESSION_THRESHOLD {
+            let compressed = compress_payload(&payload);
+            if compressed.len() < payload.len() {
+                payload = compressed;
+                flags |= MESSAGE_FLAG_COMPRESSED;
+            }
+        }
+        let header = MessageHeader {
+            version: MESSAGE_WIRE_FORMAT_VERSION as u8,
+            flags,
+            message_type: self.get_type_value(),
+            request_id: 0,
+            payload_length: payload.len() as u32,
+        };
+        let mut buffer = header.serialize();
+        buffer.append(&mut payload);
+        Ok(buffer)
+    }
+
+    fn serialize_payload(&self, format_version: u8) -> Result<Vec<u8>, Error> {
+        let payload = match self {
             Message::HandshakeChallenge(data) => data.serialize(),
             Message::HandshakeResponse(data) => data.serialize(),
             Message::ApplicationMessage(data) => data.clone(),
             Message::ApplicationTransaction(data) => data.clone(),
-            Message::Block(data) => data.serialize_for_net(BlockType::Full),
-            Message::Transaction(data) => data.serialize_for_net(),
+            Message::Block(data) => {
+                data.serialize_for_net_with_version(BlockType::Full, format_version)?
+            }
+            Message::Transaction(data) => data.serialize_for_net_with_version(format_version),
             Message::BlockchainRequest(data) => data.serialize(),
             Message::BlockHeaderHash(block_hash, block_id) => {
                 [block_hash.as_slice(), block_id.to_be_bytes().as_slice()].concat()
@@ -49,17 +205,34 @@ impl Message {
             Message::Ping() => {
                 vec![]
             }
+            Message::ForkIdRequest() => {
+                vec![]
+            }
+            Message::ForkIdResponse(data) => data.serialize(),
+            Message::TransactionBatch(data) => data.serialize(),
+            Message::TransactionBatchResult(data) => data.serialize(),
+            Message::TrustedSyncRequest() => {
+                vec![]
+            }
+            Message::TrustedSyncResponse(data) => data.serialize(),
+            Message::BalanceProofRequest(public_key) => public_key.to_vec(),
+            Message::BalanceProofResponse(data) => data.serialize(),
+            Message::GoldenTicket(golden_ticket) => golden_ticket.serialize_for_net(),
+            Message::TransactionRejected(data) => data.serialize(),
             _ => {
                 todo!()
             }
-        });
-
-        return buffer;
+        };
+        Ok(payload)
     }
     pub fn deserialize(buffer: Vec<u8>) -> Result<Message, Error> {
-        let message_type: u8 = u8::from_be_bytes(buffer[0..1].try_into().unwrap());
-        let _request_id: u32 = u32::from_be_bytes(buffer[1..5].try_into().unwrap());
-        let buffer = buffer[5..].to_vec();
+        let header = MessageHeader::deserialize(&buffer)?;
+        let message_type = header.message_type;
+        let buffer = if header.is_compressed() {
+            decompress_payload(&buffer[MESSAGE_HEADER_SIZE..])?
+        } else {
+            buffer[MESSAGE_HEADER_SIZE..].to_vec()
+        };
 
         trace!("buffer size = {:?}", buffer.len());
 
@@ -76,11 +249,11 @@ impl Message {
 
             4 => Ok(Message::ApplicationMessage(buffer)),
             5 => {
-                let block = Block::deserialize_from_net(&buffer);
+                let block = Block::try_deserialize_versioned(&buffer)?;
                 Ok(Message::Block(block))
             }
             6 => {
-                let tx = Transaction::deserialize_from_net(&buffer);
+                let tx = Transaction::try_deserialize_versioned(&buffer)?;
                 Ok(Message::Transaction(tx))
             }
             7 => {
@@ -88,9 +261,11 @@ impl Message {
                 Ok(Message::BlockchainRequest(result))
             }
             8 => {
-                assert_eq!(buffer.len(), 40);
-                let block_hash = buffer[0..32].to_vec().try_into().unwrap();
-                let block_id = u64::from_be_bytes(buffer[32..40].to_vec().try_into().unwrap());
+                if buffer.len() != 40 {
+                    return Err(Error::from(ErrorKind::InvalidData));
+                }
+                let block_hash = buffer[0..32].try_into().unwrap();
+                let block_id = u64::from_be_bytes(buffer[32..40].try_into().unwrap());
                 Ok(Message::BlockHeaderHash(block_hash, block_id))
             }
             9 => Ok(Message::Ping()),
@@ -101,6 +276,42 @@ impl Message {
             14 => Ok(Message::Result()),
             15 => Ok(Message::Error()),
             16 => Ok(Message::ApplicationTransaction(buffer)),
+            17 => Ok(Message::ForkIdRequest()),
+            18 => {
+                let result = ForkIdResponse::deserialize(&buffer)?;
+                Ok(Message::ForkIdResponse(result))
+            }
+            19 => {
+                let result = TransactionBatch::deserialize(&buffer)?;
+                Ok(Message::TransactionBatch(result))
+            }
+            20 => {
+                let result = TransactionBatchResult::deserialize(&buffer)?;
+                Ok(Message::TransactionBatchResult(result))
+            }
+            21 => Ok(Message::TrustedSyncRequest()),
+            22 => {
+                let result = UtxoSnapshot::deserialize(&buffer)?;
+                Ok(Message::TrustedSyncResponse(result))
+            }
+            23 => {
+                if buffer.len() != 33 {
+                    return Err(Error::from(ErrorKind::InvalidData));
+                }
+                Ok(Message::BalanceProofRequest(buffer.try_into().unwrap()))
+            }
+            24 => {
+                let result = BalanceProof::deserialize(&buffer)?;
+                Ok(Message::BalanceProofResponse(result))
+            }
+            25 => {
+                let golden_ticket = GoldenTicket::try_deserialize_from_net(&buffer)?;
+                Ok(Message::GoldenTicket(golden_ticket))
+            }
+            26 => {
+                let result = TransactionRejection::deserialize(&buffer)?;
+                Ok(Message::TransactionRejected(result))
+            }
             _ => {
                 warn!("message type : {:?} not valid", message_type);
                 Err(Error::from(ErrorKind::InvalidData))
@@ -124,6 +335,309 @@ impl Message {
             Message::Result() => 14,
             Message::Error() => 15,
             Message::ApplicationTransaction(_) => 16,
+            Message::ForkIdRequest() => 17,
+            Message::ForkIdResponse(_) => 18,
+            Message::TransactionBatch(_) => 19,
+            Message::TransactionBatchResult(_) => 20,
+            Message::TrustedSyncRequest() => 21,
+            Message::TrustedSyncResponse(_) => 22,
+            Message::BalanceProofRequest(_) => 23,
+            Message::BalanceProofResponse(_) => 24,
+            Message::GoldenTicket(_) => 25,
+            Message::TransactionRejected(_) => 26,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::block::{Block, BlockType};
+    use crate::core::data::crypto::generate_keys;
+    use crate::core::data::format_version::{
+        CURRENT_FORMAT_VERSION, FORMAT_ENVELOPE_MAGIC, LEGACY_FORMAT_VERSION,
+    };
+    use crate::core::data::golden_ticket::GoldenTicket;
+    use crate::core::data::msg::handshake::HandshakeResponse;
+    use crate::core::data::msg::message::{
+        Message, MessageHeader, MESSAGE_HEADER_SIZE, MESSAGE_WIRE_FORMAT_VERSION,
+    };
+    use crate::core::data::multisig::{MultisigData, MultisigPolicy};
+    use crate::core::data::serialize::Serialize;
+    use crate::core::data::slip::Slip;
+    use crate::core::data::transaction::{Transaction, TransactionType};
+
+    fn frame(message_type: u8, payload: &[u8]) -> Vec<u8> {
+        let mut buffer = MessageHeader {
+            version: MESSAGE_WIRE_FORMAT_VERSION as u8,
+            flags: 0,
+            message_type,
+            request_id: 0,
+            payload_length: payload.len() as u32,
+        }
+        .serialize();
+        buffer.extend_from_slice(payload);
+        buffer
+    }
+
+    fn create_transaction() -> Transaction {
+        let mut tx = Transaction::default();
+        tx.add_input(Slip::default());
+        tx.add_output(Slip::default());
+        tx.message = vec![1, 2, 3];
+        tx
+    }
+
+    #[test]
+    fn malformed_payloads_test() {
+        let tx = create_transaction();
+        let mut block = Block::new();
+        block.id = 2;
+        block.transactions = vec![tx.clone(), tx.clone()];
+        let handshake = HandshakeResponse {
+            public_key: [2; 33],
+            signature: [3; 64],
+            challenge: [4; 32],
+            challenge_issued_at: 0,
+            challenge_peer_address: "127.0.0.1:12101".to_string(),
+            is_lite: 0,
+            supports_compression: false,
+            format_version: CURRENT_FORMAT_VERSION,
+            block_fetch_url: "http://url".to_string(),
+            network_id: "testnet".to_string(),
+            key_rotation_proof: None,
+        };
+        let payloads: Vec<(u8, Vec<u8>)> = vec![
+            (2, handshake.serialize()),
+            (5, block.serialize_legacy_for_net(BlockType::Full).unwrap()),
+            (
+                5,
+                block
+                    .serialize_for_net_with_version(BlockType::Full, CURRENT_FORMAT_VERSION)
+                    .unwrap(),
+            ),
+            (6, tx.serialize_for_net()),
+            (6, tx.serialize_for_net_with_version(CURRENT_FORMAT_VERSION)),
+            (
+                25,
+                GoldenTicket::new([1; 32], [2; 32], [3; 33]).serialize_for_net(),
+            ),
+        ];
+        for (message_type, payload) in payloads {
+            assert!(Message::deserialize(frame(message_type, &payload)).is_ok());
+            // truncated payloads and corrupted bytes return errors (or other values) without panicking
+            for length in 0..payload.len() {
+                assert!(
+                    Message::deserialize(frame(message_type, &payload[..length])).is_err(),
+                    "type : {:?} length : {:?}",
+                    message_type,
+                    length
+                );
+            }
+            for index in 0..payload.len() {
+                let mut corrupted = payload.clone();
+                corrupted[index] = 0xff;
+                let _ = Message::deserialize(frame(message_type, &corrupted));
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_transaction_buffer_test() {
+        let tx = create_transaction();
+        let buffer = tx.serialize_for_net();
+        let result = Transaction::try_deserialize_from_net(&buffer).unwrap();
+        assert_eq!(result.serialize_for_net(), buffer);
+
+        // unknown transaction type
+        let mut invalid = buffer.clone();
+        invalid[92] = 0xff;
+        assert!(Transaction::try_deserialize_from_net(&invalid).is_err());
+
+        // unknown slip type of the output
+        let mut invalid = buffer.clone();
+        invalid[93 + 67 * 2 - 1] = 0xff;
+        assert!(Transaction::try_deserialize_from_net(&invalid).is_err());
+
+        // input count which doesn't fit the buffer
+        let mut invalid = buffer.clone();
+        invalid[0..4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(Transaction::try_deserialize_from_net(&invalid).is_err());
+
+        // golden ticket transaction without a golden ticket in the message
+        let mut golden_ticket_tx = create_transaction();
+        golden_ticket_tx.transaction_type = TransactionType::GoldenTicket;
+        assert!(
+            Transaction::try_deserialize_from_net(&golden_ticket_tx.serialize_for_net()).is_err()
+        );
+        golden_ticket_tx.message = GoldenTicket::new([1; 32], [2; 32], [3; 33]).serialize_for_net();
+        assert!(
+            Transaction::try_deserialize_from_net(&golden_ticket_tx.serialize_for_net()).is_ok()
+        );
+
+        // multisig transaction cut inside the multisig section
+        let mut multisig_tx = create_transaction();
+        multisig_tx.transaction_type = TransactionType::Multisig;
+        let policy = MultisigPolicy::new(1, vec![generate_keys().0, generate_keys().0]).unwrap();
+        multisig_tx.multisig = Some(MultisigData::new(policy));
+        let buffer = multisig_tx.serialize_for_net();
+        assert!(Transaction::try_deserialize_from_net(&buffer).is_ok());
+        let start_of_multisig = buffer.len() - (2 + 2 * 33 + 1);
+        for length in [start_of_multisig, start_of_multisig + 1, buffer.len() - 1] {
+            assert!(Transaction::try_deserialize_from_net(&buffer[..length]).is_err());
+            assert!(Transaction::deserialize_from_net(&buffer[..length]).is_err());
+        }
+        // key count which doesn't fit the buffer
+        let mut invalid = buffer.clone();
+        invalid[start_of_multisig + 1] = 0xff;
+        assert!(Transaction::try_deserialize_from_net(&invalid).is_err());
+        assert!(Transaction::deserialize_from_net(&invalid).is_err());
+
+        let mut block = Block::new();
+        block.transactions = vec![tx];
+        let mut buffer = block.serialize_for_net(BlockType::Full);
+        assert!(Block::try_deserialize_from_net(&buffer).is_ok());
+        let length = buffer.len();
+        buffer[length - 1 - 3 - 67] = 0xff;
+        assert!(Block::try_deserialize_from_net(&buffer).is_err());
+    }
+
+    #[test]
+    fn message_framing_test() {
+        let buffer = Message::BlockHeaderHash([1; 32], 10).serialize();
+        assert_eq!(buffer.len(), MESSAGE_HEADER_SIZE + 40);
+        let header = MessageHeader::deserialize(&buffer).unwrap();
+        assert_eq!(header.version as u32, MESSAGE_WIRE_FORMAT_VERSION);
+        assert_eq!(header.message_type, 8);
+        assert_eq!(header.payload_length, 40);
+        assert!(!header.is_compressed());
+        match Message::deserialize(buffer.clone()).unwrap() {
+            Message::BlockHeaderHash(block_hash, block_id) => {
+                assert_eq!(block_hash, [1; 32]);
+                assert_eq!(block_id, 10);
+            }
+            _ => panic!("unexpected message type"),
+        }
+
+        // truncated buffer, wrong magic and unsupported version
+        assert!(Message::deserialize(buffer[..buffer.len() - 1].to_vec()).is_err());
+        assert!(Message::deserialize(buffer[..5].to_vec()).is_err());
+        let mut invalid = buffer.clone();
+        invalid[0] = 0;
+        assert!(Message::deserialize(invalid).is_err());
+        let mut invalid = buffer.clone();
+        invalid[2] = 0;
+        assert!(Message::deserialize(invalid).is_err());
+        let mut invalid = buffer.clone();
+        invalid[2] = MESSAGE_WIRE_FORMAT_VERSION as u8 + 1;
+        assert!(Message::deserialize(invalid).is_err());
+        // older peers don't have the handshake layout of this version
+        let mut invalid = buffer.clone();
+        invalid[2] = MESSAGE_WIRE_FORMAT_VERSION as u8 - 1;
+        assert!(Message::deserialize(invalid).is_err());
+    }
+
+    #[test]
+    fn balance_proof_request_test() {
+        let buffer = Message::BalanceProofRequest([2; 33]).serialize();
+        assert_eq!(
+            MessageHeader::deserialize(&buffer).unwrap().message_type,
+            23
+        );
+        match Message::deserialize(buffer).unwrap() {
+            Message::BalanceProofRequest(public_key) => assert_eq!(public_key, [2; 33]),
+            _ => panic!("unexpected message type"),
         }
+        let mut invalid = Message::BalanceProofRequest([2; 33]).serialize();
+        invalid.push(0);
+        invalid[12] += 1;
+        assert!(Message::deserialize(invalid).is_err());
+    }
+
+    #[test]
+    fn golden_ticket_message_test() {
+        let golden_ticket = GoldenTicket::new([1; 32], [2; 32], [3; 33]);
+        let buffer = Message::GoldenTicket(golden_ticket).serialize();
+        match Message::deserialize(buffer).unwrap() {
+            Message::GoldenTicket(golden_ticket) => {
+                assert_eq!(golden_ticket.target, [1; 32]);
+                assert_eq!(golden_ticket.public_key, [3; 33]);
+            }
+            _ => panic!("unexpected message type"),
+        }
+        let mut invalid =
+            Message::GoldenTicket(GoldenTicket::new([1; 32], [2; 32], [3; 33])).serialize();
+        invalid.truncate(invalid.len() - 1);
+        invalid[12] -= 1;
+        assert!(Message::deserialize(invalid).is_err());
+    }
+
+    #[test]
+    fn format_version_test() {
+        let tx = create_transaction();
+        let mut block = Block::new();
+        block.id = 2;
+        block.transactions = vec![tx.clone()];
+
+        for version in [LEGACY_FORMAT_VERSION, CURRENT_FORMAT_VERSION] {
+            let buffer = Message::Block(block.clone())
+                .serialize_for_peer(false, version)
+                .unwrap();
+            let payload = &buffer[MESSAGE_HEADER_SIZE..];
+            assert_eq!(
+                payload.starts_with(&FORMAT_ENVELOPE_MAGIC),
+                version != LEGACY_FORMAT_VERSION
+            );
+            match Message::deserialize(buffer).unwrap() {
+                Message::Block(result) => {
+                    assert_eq!(result.id, 2);
+                    assert_eq!(result.transactions.len(), 1);
+                    assert_eq!(
+                        result.serialize_for_net(BlockType::Full),
+                        block.serialize_for_net(BlockType::Full)
+                    );
+                }
+                _ => panic!("unexpected message type"),
+            }
+
+            let buffer = Message::Transaction(tx.clone())
+                .serialize_for_peer(false, version)
+                .unwrap();
+            match Message::deserialize(buffer).unwrap() {
+                Message::Transaction(result) => {
+                    assert_eq!(result.serialize_for_net(), tx.serialize_for_net())
+                }
+                _ => panic!("unexpected message type"),
+            }
+        }
+
+        // versions newer than this node can read
+        let mut buffer = Message::Transaction(tx)
+            .serialize_for_peer(false, CURRENT_FORMAT_VERSION)
+            .unwrap();
+        buffer[MESSAGE_HEADER_SIZE + 2] = CURRENT_FORMAT_VERSION + 1;
+        assert!(Message::deserialize(buffer).is_err());
+    }
+
+    #[test]
+    fn message_compression_test() {
+        let payload = vec![7; 10_000];
+        let message = Message::ApplicationMessage(payload.clone());
+        let buffer = message.serialize_with_compression(true);
+        assert!(buffer.len() < payload.len());
+        assert!(MessageHeader::deserialize(&buffer).unwrap().is_compressed());
+        match Message::deserialize(buffer).unwrap() {
+            Message::ApplicationMessage(data) => assert_eq!(data, payload),
+            _ => panic!("unexpected message type"),
+        }
+
+        // small payloads are not compressed
+        let buffer = Message::ApplicationMessage(vec![7; 100]).serialize_with_compression(true);
+        assert!(!MessageHeader::deserialize(&buffer).unwrap().is_compressed());
+
+        // decompressed size over the limit
+        let mut buffer = message.serialize_with_compression(true);
+        buffer[MESSAGE_HEADER_SIZE..MESSAGE_HEADER_SIZE + 4]
+            .copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(Message::deserialize(buffer).is_err());
     }
 }
@@ -1,3 +1,5 @@
 pub mod block_request;
+pub mod fork_id;
 pub mod handshake;
 pub mod message;
+pub mod transaction_batch;
@@ -0,0 +1,235 @@
+use std::io::{Error, ErrorKind};
+
+use num_traits::FromPrimitive;
+
+use crate::common::defs::SaitoSignature;
+use crate::core::data::serialize::Serialize;
+use crate::core::data::transaction::TransactionRejectReason;
+
+/// max number of transactions accepted in a single batch
+pub const TRANSACTION_BATCH_MAX_SIZE: usize = 10_000;
+
+const TRANSACTION_RESULT_SIZE: usize = 65;
+
+/// Serialized transactions submitted together. Each transaction gets its own result, so a malformed transaction
+/// doesn't reject the whole batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionBatch {
+    /// chosen by the sender to match the result with the batch
+    pub batch_id: u32,
+    pub transactions: Vec<Vec<u8>>,
+}
+
+/// Result of a single transaction in a batch. `signature` is zero if the transaction couldn't be deserialized
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionResult {
+    pub signature: SaitoSignature,
+    /// None if the transaction is valid and was added to the mempool
+    pub rejection: Option<TransactionRejectReason>,
+}
+
+/// Sent back to the peer which submitted a single transaction, when the transaction is not added to the mempool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionRejection {
+    pub signature: SaitoSignature,
+    pub reason: TransactionRejectReason,
+}
+
+/// Results of a batch in the same order as the submitted transactions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionBatchResult {
+    pub batch_id: u32,
+    pub results: Vec<TransactionResult>,
+}
+
+impl TransactionBatchResult {
+    pub fn get_accepted_count(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|result| result.rejection.is_none())
+            .count()
+    }
+}
+
+impl Serialize<Self> for TransactionBatch {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        buffer.extend(self.batch_id.to_be_bytes());
+        buffer.extend((self.transactions.len() as u32).to_be_bytes());
+        for transaction in self.transactions.iter() {
+            buffer.extend((transaction.len() as u32).to_be_bytes());
+            buffer.extend(transaction);
+        }
+        buffer
+    }
+
+    fn deserialize(buffer: &Vec<u8>) -> Result<Self, Error> {
+        if buffer.len() < 8 {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let batch_id = u32::from_be_bytes(buffer[0..4].try_into().unwrap());
+        let count = u32::from_be_bytes(buffer[4..8].try_into().unwrap()) as usize;
+        if count > TRANSACTION_BATCH_MAX_SIZE {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let mut transactions = Vec::with_capacity(count);
+        let mut index = 8;
+        for _ in 0..count {
+            if buffer.len() < index + 4 {
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+            let length = u32::from_be_bytes(buffer[index..index + 4].try_into().unwrap()) as usize;
+            index += 4;
+            if buffer.len() < index + length {
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+            transactions.push(buffer[index..index + length].to_vec());
+            index += length;
+        }
+        if index != buffer.len() {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        Ok(TransactionBatch {
+            batch_id,
+            transactions,
+        })
+    }
+}
+
+impl Serialize<Self> for TransactionBatchResult {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        buffer.extend(self.batch_id.to_be_bytes());
+        buffer.extend((self.results.len() as u32).to_be_bytes());
+        for result in self.results.iter() {
+            buffer.push(result.rejection.map(|reason| reason as u8).unwrap_or(0));
+            buffer.extend(result.signature);
+        }
+        buffer
+    }
+
+    fn deserialize(buffer: &Vec<u8>) -> Result<Self, Error> {
+        if buffer.len() < 8 {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let batch_id = u32::from_be_bytes(buffer[0..4].try_into().unwrap());
+        let count = u32::from_be_bytes(buffer[4..8].try_into().unwrap()) as usize;
+        if count > TRANSACTION_BATCH_MAX_SIZE || buffer.len() != 8 + count * TRANSACTION_RESULT_SIZE
+        {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let mut results = Vec::with_capacity(count);
+        for chunk in buffer[8..].chunks_exact(TRANSACTION_RESULT_SIZE) {
+            let rejection = match chunk[0] {
+                0 => None,
+                value => Some(
+                    TransactionRejectReason::from_u8(value)
+                        .ok_or_else(|| Error::from(ErrorKind::InvalidData))?,
+                ),
+            };
+            results.push(TransactionResult {
+                signature: chunk[1..].try_into().unwrap(),
+                rejection,
+            });
+        }
+        Ok(TransactionBatchResult { batch_id, results })
+    }
+}
+
+impl Serialize<Self> for TransactionRejection {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(TRANSACTION_RESULT_SIZE);
+        buffer.push(self.reason as u8);
+        buffer.extend(self.signature);
+        buffer
+    }
+
+    fn deserialize(buffer: &Vec<u8>) -> Result<Self, Error> {
+        if buffer.len() != TRANSACTION_RESULT_SIZE {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let reason = TransactionRejectReason::from_u8(buffer[0])
+            .ok_or_else(|| Error::from(ErrorKind::InvalidData))?;
+        Ok(TransactionRejection {
+            signature: buffer[1..].try_into().unwrap(),
+            reason,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::msg::transaction_batch::{
+        TransactionBatch, TransactionBatchResult, TransactionRejection, TransactionResult,
+    };
+    use crate::core::data::serialize::Serialize;
+    use crate::core::data::transaction::TransactionRejectReason;
+
+    #[test]
+    fn transaction_batch_serialize_test() {
+        let batch = TransactionBatch {
+            batch_id: 12,
+            transactions: vec![vec![1, 2, 3], vec![], vec![4; 100]],
+        };
+        let buffer = batch.serialize();
+        assert_eq!(TransactionBatch::deserialize(&buffer).unwrap(), batch);
+
+        assert!(TransactionBatch::deserialize(&buffer[..buffer.len() - 1].to_vec()).is_err());
+        let mut buffer = buffer;
+        buffer.push(0);
+        assert!(TransactionBatch::deserialize(&buffer).is_err());
+        assert!(TransactionBatch::deserialize(&vec![0; 7]).is_err());
+    }
+
+    #[test]
+    fn transaction_batch_result_serialize_test() {
+        let result = TransactionBatchResult {
+            batch_id: 12,
+            results: vec![
+                TransactionResult {
+                    signature: [1; 64],
+                    rejection: None,
+                },
+                TransactionResult {
+                    signature: [2; 64],
+                    rejection: Some(TransactionRejectReason::InvalidInputs),
+                },
+                TransactionResult {
+                    signature: [0; 64],
+                    rejection: Some(TransactionRejectReason::InvalidFormat),
+                },
+            ],
+        };
+        assert_eq!(result.get_accepted_count(), 1);
+        let buffer = result.serialize();
+        assert_eq!(buffer.len(), 8 + 3 * 65);
+        assert_eq!(
+            TransactionBatchResult::deserialize(&buffer).unwrap(),
+            result
+        );
+
+        let mut buffer = buffer;
+        buffer[8 + 65] = 200;
+        assert!(TransactionBatchResult::deserialize(&buffer).is_err());
+    }
+
+    #[test]
+    fn transaction_rejection_serialize_test() {
+        let rejection = TransactionRejection {
+            signature: [3; 64],
+            reason: TransactionRejectReason::DoubleSpend,
+        };
+        let buffer = rejection.serialize();
+        assert_eq!(buffer.len(), 65);
+        assert_eq!(buffer[0], 13);
+        assert_eq!(
+            TransactionRejection::deserialize(&buffer).unwrap(),
+            rejection
+        );
+
+        assert!(TransactionRejection::deserialize(&buffer[1..].to_vec()).is_err());
+        let mut buffer = buffer;
+        buffer[0] = 0;
+        assert!(TransactionRejection::deserialize(&buffer).is_err());
+    }
+}
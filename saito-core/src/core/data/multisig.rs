@@ -0,0 +1,303 @@
+use std::io::{Error, ErrorKind};
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::defs::{Currency, SaitoHash, SaitoPublicKey, SaitoSignature};
+use crate::core::data::crypto::{hash, verify_hash};
+use crate::core::data::slip::Slip;
+
+/// compressed secp256k1 keys start with 0x02 or 0x03, so an address with this prefix
+/// can never be controlled by a single private key
+pub const MULTISIG_ADDRESS_PREFIX: u8 = 0xff;
+pub const MAX_MULTISIG_PARTICIPANTS: usize = 16;
+pub const MULTISIG_SIGNATURE_SIZE: usize = 65;
+
+/// m-of-n spending policy. Slips sent to the address of the policy can only be spent by
+/// a multisig transaction which carries the policy and `threshold` valid signatures.
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct MultisigPolicy {
+    pub threshold: u8,
+    #[serde_as(as = "Vec<[_; 33]>")]
+    pub public_keys: Vec<SaitoPublicKey>,
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct MultisigSignature {
+    /// index of the signer's key in the policy
+    pub key_index: u8,
+    #[serde_as(as = "[_; 64]")]
+    pub signature: SaitoSignature,
+}
+
+/// multisig section of a transaction. included only in multisig transactions
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct MultisigData {
+    pub policy: MultisigPolicy,
+    pub signatures: Vec<MultisigSignature>,
+}
+
+pub fn is_multisig_address(public_key: &SaitoPublicKey) -> bool {
+    public_key[0] == MULTISIG_ADDRESS_PREFIX
+}
+
+impl MultisigPolicy {
+    pub fn new(threshold: u8, public_keys: Vec<SaitoPublicKey>) -> Option<MultisigPolicy> {
+        let policy = MultisigPolicy {
+            threshold,
+            public_keys,
+        };
+        if !policy.is_valid() {
+            return None;
+        }
+        Some(policy)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        if self.threshold == 0
+            || self.public_keys.is_empty()
+            || self.public_keys.len() > MAX_MULTISIG_PARTICIPANTS
+            || self.threshold as usize > self.public_keys.len()
+        {
+            return false;
+        }
+        for (index, key) in self.public_keys.iter().enumerate() {
+            if is_multisig_address(key) || self.public_keys[index + 1..].contains(key) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// address which holds the funds controlled by this policy
+    pub fn get_address(&self) -> SaitoPublicKey {
+        let hash: SaitoHash = hash(&self.serialize_for_net());
+        let mut address: SaitoPublicKey = [0; 33];
+        address[0] = MULTISIG_ADDRESS_PREFIX;
+        address[1..].copy_from_slice(&hash);
+        address
+    }
+
+    pub fn get_key_index(&self, public_key: &SaitoPublicKey) -> Option<u8> {
+        self.public_keys
+            .iter()
+            .position(|key| key == public_key)
+            .map(|index| index as u8)
+    }
+
+    pub fn create_output_slip(&self, amount: Currency) -> Slip {
+        let mut slip = Slip::default();
+        slip.public_key = self.get_address();
+        slip.amount = amount;
+        slip
+    }
+
+    /// [threshold - 1 byte]
+    /// [key count - 1 byte]
+    /// [key][key][key]...
+    pub fn serialize_for_net(&self) -> Vec<u8> {
+        let mut buffer = vec![self.threshold, self.public_keys.len() as u8];
+        for key in self.public_keys.iter() {
+            buffer.extend(key);
+        }
+        buffer
+    }
+}
+
+impl MultisigData {
+    pub fn new(policy: MultisigPolicy) -> MultisigData {
+        MultisigData {
+            policy,
+            signatures: vec![],
+        }
+    }
+
+    /// adds the signature of the given policy member. keeps the signatures ordered by key index.
+    pub fn add_signature(&mut self, key_index: u8, signature: SaitoSignature) -> bool {
+        if key_index as usize >= self.policy.public_keys.len() {
+            return false;
+        }
+        if self.signatures.iter().any(|sig| sig.key_index == key_index) {
+            return false;
+        }
+        self.signatures.push(MultisigSignature {
+            key_index,
+            signature,
+        });
+        self.signatures.sort_by_key(|sig| sig.key_index);
+        true
+    }
+
+    pub fn has_signature(&self, key_index: u8) -> bool {
+        self.signatures.iter().any(|sig| sig.key_index == key_index)
+    }
+
+    pub fn is_fully_signed(&self) -> bool {
+        self.signatures.len() >= self.policy.threshold as usize
+    }
+
+    /// checks the policy and that at least `threshold` distinct members signed the hash
+    pub fn validate(&self, hash_for_signature: &SaitoHash) -> bool {
+        if !self.policy.is_valid() {
+            return false;
+        }
+        if !self.is_fully_signed() {
+            return false;
+        }
+        for (index, sig) in self.signatures.iter().enumerate() {
+            if index > 0 && self.signatures[index - 1].key_index >= sig.key_index {
+                // signatures should be unique and ordered
+                return false;
+            }
+            match self.policy.public_keys.get(sig.key_index as usize) {
+                Some(public_key) => {
+                    if !verify_hash(hash_for_signature, &sig.signature, public_key) {
+                        return false;
+                    }
+                }
+                None => {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// [policy]
+    /// [signature count - 1 byte]
+    /// [key index - 1 byte][signature - 64 bytes]...
+    pub fn serialize_for_net(&self) -> Vec<u8> {
+        let mut buffer = self.policy.serialize_for_net();
+        buffer.push(self.signatures.len() as u8);
+        for sig in self.signatures.iter() {
+            buffer.push(sig.key_index);
+            buffer.extend(sig.signature);
+        }
+        buffer
+    }
+
+    /// Reads the multisig section at the start of the buffer. Returns an error if the buffer is shorter than the
+    /// section
+    pub fn deserialize_from_net(bytes: &[u8]) -> Result<MultisigData, Error> {
+        let length = MultisigData::get_serialized_len(bytes)?;
+        if bytes.len() < length {
+            return Err(Error::from(ErrorKind::UnexpectedEof));
+        }
+        let threshold = bytes[0];
+        let key_count = bytes[1] as usize;
+        let mut public_keys = Vec::with_capacity(key_count);
+        for n in 0..key_count {
+            let start = 2 + n * 33;
+            let key: SaitoPublicKey = bytes[start..start + 33].try_into().unwrap();
+            public_keys.push(key);
+        }
+        let start_of_signatures = 2 + key_count * 33;
+        let signature_count = bytes[start_of_signatures] as usize;
+        let mut signatures = Vec::with_capacity(signature_count);
+        for n in 0..signature_count {
+            let start = start_of_signatures + 1 + n * MULTISIG_SIGNATURE_SIZE;
+            signatures.push(MultisigSignature {
+                key_index: bytes[start],
+                signature: bytes[start + 1..start + MULTISIG_SIGNATURE_SIZE]
+                    .try_into()
+                    .unwrap(),
+            });
+        }
+        Ok(MultisigData {
+            policy: MultisigPolicy {
+                threshold,
+                public_keys,
+            },
+            signatures,
+        })
+    }
+
+    /// length of the serialized multisig section starting at the beginning of the buffer. Returns an error if the
+    /// buffer ends before the signature count
+    pub fn get_serialized_len(bytes: &[u8]) -> Result<usize, Error> {
+        let key_count = *bytes
+            .get(1)
+            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))? as usize;
+        let start_of_signatures = 2 + key_count * 33;
+        let signature_count = *bytes
+            .get(start_of_signatures)
+            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?
+            as usize;
+        Ok(start_of_signatures + 1 + signature_count * MULTISIG_SIGNATURE_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::crypto::{generate_keys, sign};
+
+    use super::*;
+
+    #[test]
+    fn policy_validation_test() {
+        let (key1, _) = generate_keys();
+        let (key2, _) = generate_keys();
+
+        assert!(MultisigPolicy::new(1, vec![key1, key2]).is_some());
+        assert!(MultisigPolicy::new(2, vec![key1, key2]).is_some());
+        assert!(MultisigPolicy::new(0, vec![key1, key2]).is_none());
+        assert!(MultisigPolicy::new(3, vec![key1, key2]).is_none());
+        assert!(MultisigPolicy::new(1, vec![key1, key1]).is_none());
+        assert!(MultisigPolicy::new(1, vec![]).is_none());
+
+        let policy = MultisigPolicy::new(2, vec![key1, key2]).unwrap();
+        let address = policy.get_address();
+        assert!(is_multisig_address(&address));
+        assert!(!is_multisig_address(&key1));
+        assert!(MultisigPolicy::new(1, vec![key1, address]).is_none());
+
+        // address depends on the threshold as well as the keys
+        let other_policy = MultisigPolicy::new(1, vec![key1, key2]).unwrap();
+        assert_ne!(other_policy.get_address(), address);
+    }
+
+    #[test]
+    fn multisig_data_serialize_and_validate_test() {
+        let keys: Vec<_> = (0..3).map(|_| generate_keys()).collect();
+        let policy =
+            MultisigPolicy::new(2, keys.iter().map(|(public_key, _)| *public_key).collect())
+                .unwrap();
+        let buffer = [1, 2, 3];
+        let hash_for_signature = hash(&buffer);
+
+        let mut data = MultisigData::new(policy);
+        assert!(!data.validate(&hash_for_signature));
+
+        assert!(data.add_signature(2, sign(&buffer, &keys[2].1)));
+        assert!(!data.add_signature(2, sign(&buffer, &keys[2].1)));
+        assert!(!data.add_signature(3, sign(&buffer, &keys[0].1)));
+        assert!(!data.is_fully_signed());
+        assert!(!data.validate(&hash_for_signature));
+
+        assert!(data.add_signature(0, sign(&buffer, &keys[0].1)));
+        assert!(data.is_fully_signed());
+        assert_eq!(data.signatures[0].key_index, 0);
+        assert!(data.validate(&hash_for_signature));
+
+        let serialized = data.serialize_for_net();
+        assert_eq!(
+            serialized.len(),
+            MultisigData::get_serialized_len(&serialized).unwrap()
+        );
+        let deserialized = MultisigData::deserialize_from_net(&serialized).unwrap();
+        assert_eq!(deserialized, data);
+
+        // truncated sections are rejected
+        for length in [0, 1, 2, serialized.len() - 1] {
+            assert!(MultisigData::deserialize_from_net(&serialized[..length]).is_err());
+        }
+        assert!(MultisigData::get_serialized_len(&serialized[..2]).is_err());
+
+        // signature from the wrong member
+        let mut data = MultisigData::new(deserialized.policy.clone());
+        data.add_signature(0, sign(&buffer, &keys[1].1));
+        data.add_signature(1, sign(&buffer, &keys[1].1));
+        assert!(!data.validate(&hash_for_signature));
+    }
+}
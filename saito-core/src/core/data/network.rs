@@ -2,22 +2,25 @@ use std::fmt::Debug;
 use std::io::Error;
 use std::sync::Arc;
 
-use tokio::sync::RwLock;
 use tracing::{debug, info, trace, warn};
 
 use crate::common::defs::{
-    push_lock, PeerIndex, SaitoHash, SaitoPublicKey, LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_CONFIGS,
-    LOCK_ORDER_PEERS, LOCK_ORDER_WALLET,
+    push_lock, PeerIndex, SaitoHash, SaitoPublicKey, SaitoSignature, Timestamp,
+    LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_CONFIGS, LOCK_ORDER_PEERS, LOCK_ORDER_WALLET,
 };
 use crate::common::interface_io::InterfaceIO;
+use crate::common::runtime::RwLock;
 use crate::core::data::block::Block;
 use crate::core::data::blockchain::Blockchain;
 use crate::core::data::configuration::{Configuration, PeerConfig};
+use crate::core::data::golden_ticket::GoldenTicket;
 use crate::core::data::msg::block_request::BlockchainRequest;
+use crate::core::data::msg::fork_id::ForkIdResponse;
 use crate::core::data::msg::handshake::{HandshakeChallenge, HandshakeResponse};
 use crate::core::data::msg::message::Message;
 use crate::core::data::peer::Peer;
 use crate::core::data::peer_collection::PeerCollection;
+use crate::core::data::peer_identity::{PeerKeyCheck, PeerKeyPins, PEER_KEYS_FILE_PATH};
 use crate::core::data::transaction::Transaction;
 use crate::core::data::wallet::Wallet;
 use crate::{lock_for_read, lock_for_write};
@@ -29,6 +32,8 @@ pub struct Network {
     pub io_interface: Box<dyn InterfaceIO + Send + Sync>,
     static_peer_configs: Vec<PeerConfig>,
     pub wallet: Arc<RwLock<Wallet>>,
+    // public keys expected from the configured static peers
+    peer_key_pins: PeerKeyPins,
 }
 
 impl Network {
@@ -42,6 +47,7 @@ impl Network {
             io_interface: io_handler,
             static_peer_configs: Default::default(),
             wallet,
+            peer_key_pins: Default::default(),
         }
     }
     pub async fn propagate_block(&self, block: &Block) {
@@ -51,20 +57,23 @@ impl Network {
         // finding block sender to avoid resending the block to that node
 
         {
-            let (peers, _peers_) = lock_for_read!(self.peers, LOCK_ORDER_PEERS);
-            for (index, peer) in peers.index_to_peers.iter() {
-                if peer.public_key.is_none() {
+            let (mut peers, _peers_) = lock_for_write!(self.peers, LOCK_ORDER_PEERS);
+            let source_peer = block
+                .source_connection_id
+                .and_then(|address| peers.address_to_peers.get(&address).cloned());
+            for (index, peer) in peers.index_to_peers.iter_mut() {
+                if peer.public_key.is_none() || source_peer == Some(*index) {
                     excluded_peers.push(*index);
                     continue;
                 }
-                if block.source_connection_id.is_some() {
-                    let peer = peers
-                        .address_to_peers
-                        .get(&block.source_connection_id.unwrap());
-                    if peer.is_some() {
-                        excluded_peers.push(*peer.unwrap());
-                        continue;
-                    }
+                // peers which announced the block or were already sent it
+                if !peer.mark_block_as_known(block.hash) {
+                    trace!(
+                        "skipping block : {:?} for peer : {:?} since it's already known",
+                        hex::encode(block.hash),
+                        index
+                    );
+                    excluded_peers.push(*index);
                 }
             }
         }
@@ -85,30 +94,95 @@ impl Network {
 
         // TODO : return if tx is not valid
 
-        let (peers, _peers_) = lock_for_read!(self.peers, LOCK_ORDER_PEERS);
+        let (mut peers, _peers_) = lock_for_write!(self.peers, LOCK_ORDER_PEERS);
 
         let (wallet, _wallet_) = lock_for_read!(self.wallet, LOCK_ORDER_WALLET);
-        for (index, peer) in peers.index_to_peers.iter() {
+        for (index, peer) in peers.index_to_peers.iter_mut() {
             if peer.public_key.is_none() {
                 continue;
             }
             if transaction.is_in_path(peer.public_key.as_ref().unwrap()) {
                 continue;
             }
+            // the peer sent us the transaction or it was already sent to the peer
+            if !peer.mark_transaction_as_known(transaction.signature) {
+                continue;
+            }
             let mut transaction = transaction.clone();
             transaction.add_hop(
                 &wallet.private_key,
                 &wallet.public_key,
                 peer.public_key.as_ref().unwrap(),
             );
-            let message = Message::Transaction(transaction);
+            // data transactions are relayed separately so peers can handle application payloads
+            let message = if transaction.is_data_transaction() {
+                Message::ApplicationTransaction(transaction.serialize_for_net())
+            } else {
+                Message::Transaction(transaction)
+            };
+            let buffer = match peer.serialize_message(&message) {
+                Ok(buffer) => buffer,
+                Err(error) => {
+                    warn!(
+                        "transaction : {:?} can't be sent to peer : {:?} : {:?}",
+                        hex::encode(transaction.signature),
+                        index,
+                        error
+                    );
+                    continue;
+                }
+            };
             self.io_interface
-                .send_message(*index, message.serialize())
+                .send_message(*index, buffer)
                 .await
                 .unwrap();
         }
     }
 
+    /// Sends the message to a single peer. Compressed if the peer negotiated compression
+    /// Sends a golden ticket found by this node's miner to the peers which completed the handshake, so upstream nodes
+    /// can include it in their blocks
+    pub async fn submit_golden_ticket(&self, golden_ticket: &GoldenTicket) {
+        let peer_indices: Vec<PeerIndex> = {
+            let (peers, _peers_) = lock_for_read!(self.peers, LOCK_ORDER_PEERS);
+            peers
+                .index_to_peers
+                .iter()
+                .filter(|(_, peer)| peer.public_key.is_some())
+                .map(|(index, _)| *index)
+                .collect()
+        };
+        debug!(
+            "submitting golden ticket for block : {:?} to {:?} peers",
+            hex::encode(golden_ticket.target),
+            peer_indices.len()
+        );
+        let message = Message::GoldenTicket(golden_ticket.clone());
+        for peer_index in peer_indices {
+            if let Err(error) = self.send_message_to_peer(peer_index, &message).await {
+                warn!(
+                    "failed submitting golden ticket to peer : {:?} : {:?}",
+                    peer_index, error
+                );
+            }
+        }
+    }
+
+    pub async fn send_message_to_peer(
+        &self,
+        peer_index: PeerIndex,
+        message: &Message,
+    ) -> Result<(), Error> {
+        let buffer = {
+            let (peers, _peers_) = lock_for_read!(self.peers, LOCK_ORDER_PEERS);
+            match peers.index_to_peers.get(&peer_index) {
+                Some(peer) => peer.serialize_message(message)?,
+                None => message.serialize(),
+            }
+        };
+        self.io_interface.send_message(peer_index, buffer).await
+    }
+
     pub async fn fetch_missing_block(
         &self,
         block_hash: SaitoHash,
@@ -177,9 +251,20 @@ impl Network {
             todo!("Handle the unknown peer disconnect");
         }
     }
-    pub async fn handle_new_peer(&mut self, peer_data: Option<PeerConfig>, peer_index: u64) {
+    pub async fn handle_new_peer(
+        &mut self,
+        peer_data: Option<PeerConfig>,
+        peer_index: u64,
+        configs: Arc<RwLock<Box<dyn Configuration + Send + Sync>>>,
+        current_time: Timestamp,
+    ) {
         // TODO : if an incoming peer is same as static peer, handle the scenario
         debug!("handing new peer : {:?}", peer_index);
+        let endpoint_address;
+        {
+            let (configs, _configs_) = lock_for_read!(configs, LOCK_ORDER_CONFIGS);
+            endpoint_address = configs.get_server_configs().endpoint.get_address();
+        }
         let (mut peers, _peers_) = lock_for_write!(self.peers, LOCK_ORDER_PEERS);
 
         let mut peer = Peer::new(peer_index);
@@ -187,7 +272,19 @@ impl Network {
 
         if peer.static_peer_config.is_none() {
             // if we don't have peer data it means this is an incoming connection. so we initiate the handshake
-            peer.initiate_handshake(&self.io_interface).await.unwrap();
+            let public_key;
+            {
+                let (wallet, _wallet_) = lock_for_read!(self.wallet, LOCK_ORDER_WALLET);
+                public_key = wallet.public_key;
+            }
+            peer.initiate_handshake(
+                &self.io_interface,
+                public_key,
+                endpoint_address,
+                current_time,
+            )
+            .await
+            .unwrap();
         } else {
             info!(
                 "removing static peer config : {:?}",
@@ -200,13 +297,15 @@ impl Network {
         info!("new peer added : {:?}", peer_index);
         peers.index_to_peers.insert(peer_index, peer);
     }
+    /// Returns the reason if the handshake is rejected
     pub async fn handle_handshake_challenge(
         &self,
         peer_index: u64,
         challenge: HandshakeChallenge,
         wallet: Arc<RwLock<Wallet>>,
         configs: Arc<RwLock<Box<dyn Configuration + Send + Sync>>>,
-    ) {
+        current_time: Timestamp,
+    ) -> Result<(), String> {
         let (mut peers, _peers_) = lock_for_write!(self.peers, LOCK_ORDER_PEERS);
 
         let peer = peers.index_to_peers.get_mut(&peer_index);
@@ -214,19 +313,40 @@ impl Network {
             todo!()
         }
         let peer = peer.unwrap();
-        peer.handle_handshake_challenge(challenge, &self.io_interface, wallet.clone(), configs)
-            .await
-            .unwrap();
+        let result = peer
+            .handle_handshake_challenge(
+                challenge,
+                &self.io_interface,
+                wallet.clone(),
+                configs,
+                current_time,
+            )
+            .await;
+        if let Err(error) = result {
+            warn!(
+                "rejected handshake challenge from peer : {:?}. {:?}",
+                peer_index, error
+            );
+            return Err(format!("{:?}", error));
+        }
+        Ok(())
     }
+    /// Returns the reason if the handshake is rejected
     pub async fn handle_handshake_response(
-        &self,
+        &mut self,
         peer_index: u64,
         response: HandshakeResponse,
         wallet: Arc<RwLock<Wallet>>,
         blockchain: Arc<RwLock<Blockchain>>,
         configs: Arc<RwLock<Box<dyn Configuration + Send + Sync>>>,
-    ) {
+        current_time: Timestamp,
+    ) -> Result<(), String> {
         debug!("received handshake response");
+        let peer_configs;
+        {
+            let (configs, _configs_) = lock_for_read!(configs, LOCK_ORDER_CONFIGS);
+            peer_configs = configs.get_peer_configs().clone();
+        }
         let (mut peers, _peers_) = lock_for_write!(self.peers, LOCK_ORDER_PEERS);
 
         let peer = peers.index_to_peers.get_mut(&peer_index);
@@ -235,14 +355,61 @@ impl Network {
             todo!()
         }
         let peer = peer.unwrap();
-        peer.handle_handshake_response(
-            response,
-            &self.io_interface,
-            wallet.clone(),
-            configs.clone(),
-        )
-        .await
-        .unwrap();
+
+        // keys are only pinned for the peers in the config. dns seeds can resolve to different nodes
+        let pinned_peer_config = peer
+            .static_peer_config
+            .clone()
+            .filter(|config| peer_configs.contains(config));
+        if let Some(config) = pinned_peer_config.as_ref() {
+            match self.peer_key_pins.check(
+                config,
+                &response.public_key,
+                response.key_rotation_proof.as_ref(),
+            ) {
+                Ok(PeerKeyCheck::Matches) => {}
+                Ok(PeerKeyCheck::Unknown) => {
+                    info!(
+                        "pinning key : {:?} for peer : {:?}",
+                        hex::encode(response.public_key),
+                        PeerKeyPins::get_peer_address(config)
+                    );
+                }
+                Ok(PeerKeyCheck::Rotated) => {
+                    info!(
+                        "peer : {:?} rotated its key to : {:?}",
+                        PeerKeyPins::get_peer_address(config),
+                        hex::encode(response.public_key)
+                    );
+                }
+                Err(error) => {
+                    warn!(
+                        "rejected handshake response from peer : {:?} at : {:?}. {:?}",
+                        peer_index,
+                        PeerKeyPins::get_peer_address(config),
+                        error
+                    );
+                    return Err(format!("{:?}", error));
+                }
+            }
+        }
+
+        let result = peer
+            .handle_handshake_response(
+                response,
+                &self.io_interface,
+                wallet.clone(),
+                configs.clone(),
+                current_time,
+            )
+            .await;
+        if let Err(error) = result {
+            warn!(
+                "rejected handshake response from peer : {:?}. {:?}",
+                peer_index, error
+            );
+            return Err(format!("{:?}", error));
+        }
         if peer.public_key.is_some() {
             debug!(
                 "peer : {:?} handshake successful for peer : {:?}",
@@ -251,7 +418,97 @@ impl Network {
             );
             let public_key = peer.public_key.clone().unwrap();
             peers.address_to_peers.insert(public_key, peer_index);
-            // start block syncing here
+            if let Some(config) = pinned_peer_config.as_ref() {
+                if self.peer_key_pins.pin(config, public_key) {
+                    let buffer = self.peer_key_pins.serialize_for_disk();
+                    if let Err(error) = self
+                        .io_interface
+                        .write_value(PEER_KEYS_FILE_PATH.to_string(), buffer)
+                        .await
+                    {
+                        warn!("failed saving pinned peer keys. {:?}", error);
+                    }
+                }
+            }
+            // start block syncing here. fork ids are exchanged first to find the shared ancestor
+            self.request_fork_id_from_peer(peer_index).await;
+        }
+        Ok(())
+    }
+
+    pub async fn request_fork_id_from_peer(&self, peer_index: u64) {
+        debug!("requesting fork id from peer : {:?}", peer_index);
+        let buffer = Message::ForkIdRequest().serialize();
+        self.io_interface
+            .send_message(peer_index, buffer)
+            .await
+            .unwrap();
+    }
+
+    /// Finds the last shared ancestor with the peer's longest chain using its fork id and requests the
+    /// blocks after that. Skips the request if the peer doesn't have any blocks beyond the shared ancestor.
+    pub async fn handle_fork_id_response(
+        &self,
+        peer_index: u64,
+        response: ForkIdResponse,
+        blockchain: Arc<RwLock<Blockchain>>,
+    ) {
+        debug!(
+            "received fork id : {:?} at block : {:?} from peer : {:?}",
+            hex::encode(response.fork_id),
+            response.latest_block_id,
+            peer_index
+        );
+        let request;
+        {
+            let (blockchain, _blockchain_) = lock_for_read!(blockchain, LOCK_ORDER_BLOCKCHAIN);
+
+            let last_shared_ancestor = blockchain
+                .generate_last_shared_ancestor(response.latest_block_id, response.fork_id);
+            debug!(
+                "last shared ancestor with peer : {:?} = {:?}",
+                peer_index, last_shared_ancestor
+            );
+            if response.latest_block_id <= last_shared_ancestor {
+                debug!(
+                    "peer : {:?} doesn't have new blocks after : {:?}",
+                    peer_index, last_shared_ancestor
+                );
+                return;
+            }
+
+            request = BlockchainRequest {
+                latest_block_id: last_shared_ancestor,
+                latest_block_hash: blockchain
+                    .blockring
+                    .get_longest_chain_block_hash_by_block_id(last_shared_ancestor),
+                fork_id: blockchain.generate_fork_id(last_shared_ancestor),
+            };
+        }
+        info!(
+            "requesting blockchain from peer : {:?} after block : {:?}",
+            peer_index, request.latest_block_id
+        );
+        let buffer = Message::BlockchainRequest(request).serialize();
+        self.io_interface
+            .send_message(peer_index, buffer)
+            .await
+            .unwrap();
+    }
+
+    pub async fn request_blockchain_from_all_peers(&self, blockchain: Arc<RwLock<Blockchain>>) {
+        let peer_indices: Vec<u64>;
+        {
+            let (peers, _peers_) = lock_for_read!(self.peers, LOCK_ORDER_PEERS);
+
+            peer_indices = peers
+                .index_to_peers
+                .iter()
+                .filter(|(_, peer)| peer.public_key.is_some())
+                .map(|(index, _)| *index)
+                .collect();
+        }
+        for peer_index in peer_indices {
             self.request_blockchain_from_peer(peer_index, blockchain.clone())
                 .await;
         }
@@ -282,6 +539,24 @@ impl Network {
             .await
             .unwrap();
     }
+    /// Records the blocks and transactions received from the peer, so they are not propagated back to it
+    pub async fn on_inventory_received(
+        &self,
+        peer_index: PeerIndex,
+        block_hashes: &[SaitoHash],
+        signatures: &[SaitoSignature],
+    ) {
+        let (mut peers, _peers_) = lock_for_write!(self.peers, LOCK_ORDER_PEERS);
+        if let Some(peer) = peers.index_to_peers.get_mut(&peer_index) {
+            for block_hash in block_hashes {
+                peer.mark_block_as_known(*block_hash);
+            }
+            for signature in signatures {
+                peer.mark_transaction_as_known(*signature);
+            }
+        }
+    }
+
     pub async fn process_incoming_block_hash(
         &self,
         block_hash: SaitoHash,
@@ -314,6 +589,30 @@ impl Network {
         Some(())
     }
 
+    /// Loads the public keys pinned for the static peers in the previous runs
+    pub async fn load_peer_key_pins(&mut self) {
+        if !self
+            .io_interface
+            .is_existing_file(PEER_KEYS_FILE_PATH.to_string())
+            .await
+        {
+            return;
+        }
+        match self
+            .io_interface
+            .read_value(PEER_KEYS_FILE_PATH.to_string())
+            .await
+        {
+            Ok(buffer) => {
+                self.peer_key_pins = PeerKeyPins::deserialize_from_disk(&buffer);
+                info!("loaded {:?} pinned peer keys", self.peer_key_pins.len());
+            }
+            Err(error) => {
+                warn!("failed loading pinned peer keys. {:?}", error);
+            }
+        }
+    }
+
     pub async fn initialize_static_peers(
         &mut self,
         configs: Arc<RwLock<Box<dyn Configuration + Send + Sync>>>,
@@ -0,0 +1,194 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::common::defs::BlockId;
+use crate::core::data::blockchain::GENESIS_PERIOD;
+use crate::core::data::configuration::{DifficultyConfig, Server, DEFAULT_NETWORK_ID};
+
+/// Named network with its bundled parameters. Selected with `server.network` in the config or `--network` in the
+/// command line
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkPreset {
+    #[default]
+    Mainnet,
+    Testnet,
+    /// single node networks for application development
+    Devnet,
+}
+
+/// Parameters of a network preset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkParams {
+    pub network_id: &'static str,
+    /// server and endpoint port used if not set in the config
+    pub port: u16,
+    /// dns seeds resolving to the public nodes of the network
+    pub dns_seeds: &'static [&'static str],
+    /// number of blocks kept in the chain before they are pruned. can't be longer than the mainnet period, which
+    /// sets the size of the blockring
+    pub genesis_period: BlockId,
+    pub difficulty: DifficultyConfig,
+    /// prefix of the data paths. mainnet keeps the directory of the nodes which ran before the presets
+    pub data_dir: &'static str,
+}
+
+impl NetworkPreset {
+    pub const ALL: [NetworkPreset; 3] = [
+        NetworkPreset::Mainnet,
+        NetworkPreset::Testnet,
+        NetworkPreset::Devnet,
+    ];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            NetworkPreset::Mainnet => "mainnet",
+            NetworkPreset::Testnet => "testnet",
+            NetworkPreset::Devnet => "devnet",
+        }
+    }
+
+    pub fn get_params(&self) -> NetworkParams {
+        match self {
+            NetworkPreset::Mainnet => NetworkParams {
+                network_id: DEFAULT_NETWORK_ID,
+                port: 12101,
+                dns_seeds: &[],
+                genesis_period: GENESIS_PERIOD,
+                difficulty: DifficultyConfig::Consensus,
+                data_dir: "./data/",
+            },
+            NetworkPreset::Testnet => NetworkParams {
+                network_id: "testnet",
+                port: 12201,
+                dns_seeds: &[],
+                genesis_period: GENESIS_PERIOD,
+                difficulty: DifficultyConfig::Consensus,
+                data_dir: "./data/testnet/",
+            },
+            NetworkPreset::Devnet => NetworkParams {
+                network_id: "devnet",
+                port: 12301,
+                dns_seeds: &[],
+                // short enough to see blocks being pruned and rebroadcast while testing
+                genesis_period: 1_000,
+                difficulty: DifficultyConfig::Fixed { difficulty: 1 },
+                data_dir: "./data/devnet/",
+            },
+        }
+    }
+
+    /// Fills the values of the server config which are left at their defaults with the parameters of the network.
+    /// Values set in the config are kept, so a private network can reuse a preset with another network id or port
+    pub fn apply(&self, server: &mut Server) {
+        let params = self.get_params();
+        server.network = Some(*self);
+        if server.network_id == DEFAULT_NETWORK_ID {
+            server.network_id = params.network_id.to_string();
+        }
+        if server.port == 0 {
+            server.port = params.port;
+        }
+        if server.endpoint.port == 0 {
+            server.endpoint.port = params.port;
+        }
+        if server.dns_seeds.hosts.is_empty() && !params.dns_seeds.is_empty() {
+            server.dns_seeds.hosts = params
+                .dns_seeds
+                .iter()
+                .map(|host| host.to_string())
+                .collect();
+            server.dns_seeds.port = params.port;
+        }
+        if server.difficulty == DifficultyConfig::Consensus {
+            server.difficulty = params.difficulty;
+        }
+    }
+}
+
+impl Display for NetworkPreset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.get_name())
+    }
+}
+
+impl FromStr for NetworkPreset {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        NetworkPreset::ALL
+            .iter()
+            .find(|preset| preset.get_name() == name)
+            .cloned()
+            .ok_or_else(|| {
+                format!(
+                    "unknown network : {}. expected one of : mainnet, testnet, devnet",
+                    name
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::core::data::blockchain::GENESIS_PERIOD;
+    use crate::core::data::configuration::{
+        DifficultyConfig, Endpoint, Server, DEFAULT_NETWORK_ID,
+    };
+    use crate::core::data::network_preset::NetworkPreset;
+
+    fn create_server() -> Server {
+        serde_json::from_str::<Server>(
+            r#"{ "host": "localhost", "protocol": "http",
+                 "endpoint": { "host": "localhost", "port": 0, "protocol": "http" } }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn network_preset_test() {
+        assert_eq!(
+            NetworkPreset::from_str("testnet"),
+            Ok(NetworkPreset::Testnet)
+        );
+        assert!(NetworkPreset::from_str("Testnet").is_err());
+        for preset in NetworkPreset::ALL {
+            let params = preset.get_params();
+            assert!(params.genesis_period > 0 && params.genesis_period <= GENESIS_PERIOD);
+            assert!(params.data_dir.ends_with('/'));
+        }
+
+        let mut server = create_server();
+        assert_eq!(server.network, None);
+        NetworkPreset::Devnet.apply(&mut server);
+        assert_eq!(server.network, Some(NetworkPreset::Devnet));
+        assert_eq!(server.network_id, "devnet");
+        assert_eq!(server.port, 12301);
+        assert_eq!(server.endpoint.port, 12301);
+        assert_eq!(server.difficulty, DifficultyConfig::Fixed { difficulty: 1 });
+
+        // values set in the config are kept
+        let mut server = create_server();
+        server.network_id = "private".to_string();
+        server.port = 15000;
+        server.endpoint = Endpoint {
+            host: "localhost".to_string(),
+            port: 443,
+            protocol: "https".to_string(),
+        };
+        NetworkPreset::Testnet.apply(&mut server);
+        assert_eq!(server.network_id, "private");
+        assert_eq!(server.port, 15000);
+        assert_eq!(server.endpoint.port, 443);
+        assert_eq!(server.difficulty, DifficultyConfig::Consensus);
+
+        let mut server = create_server();
+        NetworkPreset::Mainnet.apply(&mut server);
+        assert_eq!(server.network_id, DEFAULT_NETWORK_ID);
+        assert_eq!(server.port, 12101);
+    }
+}
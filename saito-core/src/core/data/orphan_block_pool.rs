@@ -0,0 +1,182 @@
+use ahash::AHashMap;
+use tracing::debug;
+
+use crate::common::defs::{SaitoHash, Timestamp};
+use crate::core::data::block::Block;
+
+/// max number of orphan blocks kept when the size is not configured
+pub const DEFAULT_MAX_ORPHAN_BLOCKS: usize = 100;
+/// orphan blocks older than this (by the block timestamp) are removed when the expiry is not configured
+pub const DEFAULT_ORPHAN_BLOCK_EXPIRY_IN_MS: Timestamp = 10 * 60 * 1000;
+
+/// Blocks received before their parent. They are kept until the parent is added to the blockchain or they expire.
+///
+/// When the pool is full, the block with the oldest timestamp is evicted first and the lower block id (less work
+/// claimed on top of it) is evicted first among blocks with the same timestamp. Blocks with timestamps too far in the
+/// future are rejected before reaching the pool, so the timestamps can't be used to keep a block in the pool.
+#[derive(Debug)]
+pub struct OrphanBlockPool {
+    blocks: AHashMap<SaitoHash, Block>,
+    max_size: usize,
+    expiry_in_ms: Timestamp,
+    pub expired_blocks: u64,
+    pub evicted_blocks: u64,
+}
+
+impl OrphanBlockPool {
+    pub fn new(max_size: usize, expiry_in_ms: Timestamp) -> OrphanBlockPool {
+        OrphanBlockPool {
+            blocks: Default::default(),
+            max_size,
+            expiry_in_ms,
+            expired_blocks: 0,
+            evicted_blocks: 0,
+        }
+    }
+
+    /// sets the limits from the configs. 0 uses the default value. existing blocks over the new size are evicted
+    pub fn set_limits(&mut self, max_size: u64, expiry_in_ms: Timestamp) {
+        self.max_size = if max_size == 0 {
+            DEFAULT_MAX_ORPHAN_BLOCKS
+        } else {
+            max_size as usize
+        };
+        self.expiry_in_ms = if expiry_in_ms == 0 {
+            DEFAULT_ORPHAN_BLOCK_EXPIRY_IN_MS
+        } else {
+            expiry_in_ms
+        };
+        while self.blocks.len() > self.max_size {
+            self.evict();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn contains(&self, block_hash: &SaitoHash) -> bool {
+        self.blocks.contains_key(block_hash)
+    }
+
+    fn get_eviction_key(block: &Block) -> (Timestamp, u64) {
+        (block.timestamp, block.id)
+    }
+
+    fn evict(&mut self) {
+        let hash = self
+            .blocks
+            .values()
+            .min_by_key(|block| Self::get_eviction_key(block))
+            .map(|block| block.hash);
+        if let Some(hash) = hash {
+            debug!("evicting orphan block : {:?}", hex::encode(hash));
+            self.blocks.remove(&hash);
+            self.evicted_blocks += 1;
+        }
+    }
+
+    /// Adds the block to the pool. Returns false if the block is already in the pool or if the pool is full and the
+    /// block would be the first one to be evicted.
+    pub fn add(&mut self, block: Block) -> bool {
+        if self.blocks.contains_key(&block.hash) {
+            return false;
+        }
+        if self.blocks.len() >= self.max_size {
+            let lowest = self.blocks.values().map(Self::get_eviction_key).min();
+            if self.max_size == 0
+                || lowest.map_or(false, |key| Self::get_eviction_key(&block) < key)
+            {
+                debug!(
+                    "orphan pool is full. dropping block : {:?}",
+                    hex::encode(block.hash)
+                );
+                self.evicted_blocks += 1;
+                return false;
+            }
+            self.evict();
+        }
+        self.blocks.insert(block.hash, block);
+        true
+    }
+
+    /// Removes and returns the blocks whose parent is known, sorted by the block id so parents are added first
+    pub fn take_resolved<F: Fn(&SaitoHash) -> bool>(&mut self, is_known_block: F) -> Vec<Block> {
+        let hashes: Vec<SaitoHash> = self
+            .blocks
+            .values()
+            .filter(|block| is_known_block(&block.previous_block_hash))
+            .map(|block| block.hash)
+            .collect();
+        let mut blocks: Vec<Block> = hashes
+            .iter()
+            .filter_map(|hash| self.blocks.remove(hash))
+            .collect();
+        blocks.sort_by_key(|block| block.id);
+        blocks
+    }
+
+    /// removes the blocks which are older than the expiry time. returns the number of removed blocks
+    pub fn remove_expired(&mut self, current_time: Timestamp) -> usize {
+        let count = self.blocks.len();
+        let expiry_in_ms = self.expiry_in_ms;
+        self.blocks
+            .retain(|_, block| block.timestamp.saturating_add(expiry_in_ms) >= current_time);
+        let removed = count - self.blocks.len();
+        self.expired_blocks += removed as u64;
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::defs::SaitoHash;
+    use crate::core::data::block::Block;
+    use crate::core::data::orphan_block_pool::OrphanBlockPool;
+
+    fn create_block(hash: u8, previous_block_hash: u8, id: u64, timestamp: u64) -> Block {
+        let mut block = Block::new();
+        block.hash = [hash; 32];
+        block.previous_block_hash = [previous_block_hash; 32];
+        block.id = id;
+        block.timestamp = timestamp;
+        block
+    }
+
+    #[test]
+    fn orphan_block_pool_test() {
+        let mut pool = OrphanBlockPool::new(3, 1000);
+
+        assert!(pool.add(create_block(2, 1, 2, 200)));
+        assert!(!pool.add(create_block(2, 1, 2, 200)));
+        assert!(pool.add(create_block(3, 2, 3, 300)));
+        assert!(pool.add(create_block(5, 4, 5, 500)));
+        assert_eq!(pool.len(), 3);
+
+        // older than all the blocks in the full pool
+        assert!(!pool.add(create_block(9, 8, 1, 100)));
+        assert_eq!(pool.evicted_blocks, 1);
+        // evicts the block with the oldest timestamp
+        assert!(pool.add(create_block(6, 5, 6, 600)));
+        assert!(!pool.contains(&[2; 32]));
+        assert_eq!(pool.evicted_blocks, 2);
+
+        let known: Vec<SaitoHash> = vec![[4; 32]];
+        let blocks = pool.take_resolved(|hash| known.contains(hash));
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].hash, [5; 32]);
+        assert_eq!(pool.len(), 2);
+
+        assert_eq!(pool.remove_expired(1300), 0);
+        assert_eq!(pool.remove_expired(1301), 1);
+        assert!(pool.contains(&[6; 32]));
+        assert_eq!(pool.expired_blocks, 1);
+
+        pool.set_limits(0, 0);
+        assert_eq!(pool.remove_expired(1601), 0);
+    }
+}
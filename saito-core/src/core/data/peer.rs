@@ -1,21 +1,53 @@
-use std::io::Error;
+use std::io::{Error, ErrorKind};
 use std::sync::Arc;
 
-use tokio::sync::RwLock;
+use ahash::AHashSet;
 use tracing::{info, warn};
 
 use crate::common::defs::{
-    push_lock, SaitoHash, SaitoPublicKey, LOCK_ORDER_CONFIGS, LOCK_ORDER_WALLET,
+    push_lock, SaitoHash, SaitoPublicKey, SaitoSignature, Timestamp, LOCK_ORDER_CONFIGS,
+    LOCK_ORDER_WALLET,
 };
 use crate::common::interface_io::InterfaceIO;
+use crate::common::runtime::RwLock;
 use crate::core::data;
 use crate::core::data::configuration::Configuration;
 use crate::core::data::crypto::{generate_random_bytes, sign, verify};
+use crate::core::data::format_version::{negotiate_format_version, CURRENT_FORMAT_VERSION};
+use crate::core::data::known_inventory::{
+    KnownInventory, MAX_KNOWN_BLOCKS_PER_PEER, MAX_KNOWN_TRANSACTIONS_PER_PEER,
+};
 use crate::core::data::msg::handshake::{HandshakeChallenge, HandshakeResponse};
 use crate::core::data::msg::message::Message;
+use crate::core::data::peer_identity::{KeyRotationProof, PeerKeyPins};
 use crate::core::data::wallet::Wallet;
 use crate::lock_for_read;
 
+/// weight of the existing latency value against a new sample when smoothing the peer latency
+const LATENCY_SMOOTHING_FACTOR: Timestamp = 8;
+
+/// Reads the key rotation proof from the config. The proof is only sent if it's valid for the wallet's key
+fn load_key_rotation_proof(
+    value: Option<&str>,
+    public_key: &SaitoPublicKey,
+) -> Option<KeyRotationProof> {
+    let proof = match KeyRotationProof::from_hex(value?) {
+        Ok(proof) => proof,
+        Err(_) => {
+            warn!("failed decoding the key rotation proof in the config");
+            return None;
+        }
+    };
+    if proof.new_public_key != *public_key || !proof.verify() {
+        warn!(
+            "key rotation proof in the config is not valid for the wallet key : {:?}",
+            hex::encode(public_key)
+        );
+        return None;
+    }
+    Some(proof)
+}
+
 #[derive(Debug, Clone)]
 pub struct Peer {
     pub index: u64,
@@ -24,6 +56,19 @@ pub struct Peer {
     // if this is None(), it means an incoming connection. else a connection which we started from the data from config file
     pub static_peer_config: Option<data::configuration::PeerConfig>,
     pub challenge_for_peer: Option<SaitoHash>,
+    pub challenge_issued_at: Timestamp,
+    // challenges from this peer which we already signed. each challenge is answered only once
+    answered_challenges: AHashSet<SaitoHash>,
+    // smoothed round trip time to the peer in ms. None until the first measurement
+    latency: Option<Timestamp>,
+    // both sides advertised compression support in the handshake
+    compression_enabled: bool,
+    // version of the block and transaction formats sent to the peer. lowered in the handshake if the peer only reads an
+    // older version
+    format_version: u8,
+    // blocks and transactions the peer already has. these are not propagated to the peer again
+    known_blocks: KnownInventory<SaitoHash>,
+    known_transactions: KnownInventory<SaitoSignature>,
 }
 
 impl Peer {
@@ -34,18 +79,117 @@ impl Peer {
             block_fetch_url: "".to_string(),
             static_peer_config: None,
             challenge_for_peer: None,
+            challenge_issued_at: 0,
+            answered_challenges: Default::default(),
+            latency: None,
+            compression_enabled: false,
+            format_version: CURRENT_FORMAT_VERSION,
+            known_blocks: KnownInventory::new(MAX_KNOWN_BLOCKS_PER_PEER),
+            known_transactions: KnownInventory::new(MAX_KNOWN_TRANSACTIONS_PER_PEER),
         }
     }
+
+    pub fn is_compression_enabled(&self) -> bool {
+        self.compression_enabled
+    }
+
+    pub fn get_format_version(&self) -> u8 {
+        self.format_version
+    }
+
+    /// Serializes the message for this peer. Large messages are compressed if the peer supports it, and blocks and
+    /// transactions are written in the format version negotiated with the peer. Fails if the message can't be written
+    /// in that version, in which case it's not sent to the peer
+    pub fn serialize_message(&self, message: &Message) -> Result<Vec<u8>, Error> {
+        message.serialize_for_peer(self.compression_enabled, self.format_version)
+    }
+
+    /// Records a round trip time measured with the peer (handshakes or block fetches). Kept as a moving average so
+    /// a single slow response doesn't change the peer's ranking much.
+    pub fn record_latency(&mut self, round_trip_time: Timestamp) {
+        let latency = match self.latency {
+            Some(latency) => {
+                (latency * (LATENCY_SMOOTHING_FACTOR - 1) + round_trip_time)
+                    / LATENCY_SMOOTHING_FACTOR
+            }
+            None => round_trip_time,
+        };
+        self.latency = Some(latency);
+    }
+
+    pub fn get_latency(&self) -> Option<Timestamp> {
+        self.latency
+    }
+
+    /// Records a block announced by the peer or sent to it. Returns true if the peer didn't know the block before
+    pub fn mark_block_as_known(&mut self, block_hash: SaitoHash) -> bool {
+        self.known_blocks.insert(block_hash)
+    }
+
+    /// Records a transaction received from the peer or sent to it. Returns true if the peer didn't know the
+    /// transaction before
+    pub fn mark_transaction_as_known(&mut self, signature: SaitoSignature) -> bool {
+        self.known_transactions.insert(signature)
+    }
+
+    /// creates a new challenge for the peer. any previous challenge is dropped
+    fn generate_challenge(&mut self, current_time: Timestamp) -> SaitoHash {
+        let challenge: SaitoHash = generate_random_bytes(32).try_into().unwrap();
+        self.challenge_for_peer = Some(challenge);
+        self.challenge_issued_at = current_time;
+        challenge
+    }
+
+    /// Checks the peer's challenge before signing it. Rejects reused challenges and challenges issued outside the
+    /// expiry window. Issue time is checked in both directions to allow some clock difference between the nodes.
+    fn accept_challenge(
+        &mut self,
+        challenge: &SaitoHash,
+        issued_at: Timestamp,
+        current_time: Timestamp,
+    ) -> bool {
+        if HandshakeChallenge::is_expired(issued_at, current_time)
+            || HandshakeChallenge::is_expired(current_time, issued_at)
+        {
+            warn!(
+                "handshake challenge from peer : {:?} is expired. issued at : {:?} current time : {:?}",
+                self.index, issued_at, current_time
+            );
+            return false;
+        }
+        if !self.answered_challenges.insert(*challenge) {
+            warn!(
+                "handshake challenge : {:?} from peer : {:?} is already answered",
+                hex::encode(challenge),
+                self.index
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Address we connected to the peer with. None for incoming connections since the peer's address is not known
+    pub fn get_peer_address(&self) -> Option<String> {
+        self.static_peer_config
+            .as_ref()
+            .map(PeerKeyPins::get_peer_address)
+    }
+
     pub async fn initiate_handshake(
         &mut self,
         io_handler: &Box<dyn InterfaceIO + Send + Sync>,
+        public_key: SaitoPublicKey,
+        endpoint_address: String,
+        current_time: Timestamp,
     ) -> Result<(), Error> {
         info!("initiating handshake : {:?}", self.index);
 
         let challenge = HandshakeChallenge {
-            challenge: generate_random_bytes(32).try_into().unwrap(),
+            challenge: self.generate_challenge(current_time),
+            issued_at: current_time,
+            public_key,
+            peer_address: endpoint_address,
         };
-        self.challenge_for_peer = Some(challenge.challenge);
         let message = Message::HandshakeChallenge(challenge);
         io_handler
             .send_message(self.index, message.serialize())
@@ -61,25 +205,62 @@ impl Peer {
         io_handler: &Box<dyn InterfaceIO + Send + Sync>,
         wallet: Arc<RwLock<Wallet>>,
         configs: Arc<RwLock<Box<dyn Configuration + Send + Sync>>>,
+        current_time: Timestamp,
     ) -> Result<(), Error> {
         info!("handling handshake challenge : {:?}", self.index,);
+        // we only know the address of the peers we connected to. the signature covers the address named in the
+        // challenge, so the challenger can check we connected to the address it's reachable at
+        if let Some(peer_address) = self.get_peer_address() {
+            if peer_address != challenge.peer_address {
+                warn!(
+                    "handshake challenge from peer : {:?} names address : {:?}, but we connected to : {:?}",
+                    self.index, challenge.peer_address, peer_address
+                );
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+        }
+        if !self.accept_challenge(&challenge.challenge, challenge.issued_at, current_time) {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
         let block_fetch_url;
+        let supports_compression;
+        let network_id;
+        let key_rotation_proof;
+        let endpoint_address;
         {
             let (configs, _configs_) = lock_for_read!(configs, LOCK_ORDER_CONFIGS);
 
             block_fetch_url = configs.get_block_fetch_url();
+            supports_compression = configs.get_server_configs().message_compression;
+            network_id = configs.get_server_configs().network_id.clone();
+            key_rotation_proof = configs.get_server_configs().key_rotation_proof.clone();
+            endpoint_address = configs.get_server_configs().endpoint.get_address();
         }
 
         let (wallet, _wallet_) = lock_for_read!(wallet, LOCK_ORDER_WALLET);
+        let payload = HandshakeChallenge::get_payload_to_sign(
+            &challenge.challenge,
+            challenge.issued_at,
+            &challenge.public_key,
+            &challenge.peer_address,
+        );
         let response = HandshakeResponse {
             public_key: wallet.public_key,
-            signature: sign(challenge.challenge.as_slice(), &wallet.private_key),
-            challenge: generate_random_bytes(32).try_into().unwrap(),
+            signature: sign(payload.as_slice(), &wallet.private_key),
+            challenge: self.generate_challenge(current_time),
+            challenge_issued_at: current_time,
+            challenge_peer_address: endpoint_address,
             is_lite: 0,
+            supports_compression,
+            format_version: CURRENT_FORMAT_VERSION,
             block_fetch_url,
+            network_id,
+            key_rotation_proof: load_key_rotation_proof(
+                key_rotation_proof.as_deref(),
+                &wallet.public_key,
+            ),
         };
 
-        self.challenge_for_peer = Some(response.challenge);
         io_handler
             .send_message(self.index, Message::HandshakeResponse(response).serialize())
             .await
@@ -94,68 +275,131 @@ impl Peer {
         io_handler: &Box<dyn InterfaceIO + Send + Sync>,
         wallet: Arc<RwLock<Wallet>>,
         configs: Arc<RwLock<Box<dyn Configuration + Send + Sync>>>,
+        current_time: Timestamp,
     ) -> Result<(), Error> {
         info!(
             "handling handshake response :{:?} with address : {:?}",
             self.index,
             hex::encode(response.public_key)
         );
-        if self.challenge_for_peer.is_none() {
+        // challenge is removed here so it can only be used once
+        let sent_challenge = self.challenge_for_peer.take();
+        if sent_challenge.is_none() {
             warn!(
-                "we don't have a challenge to verify for peer : {:?}",
+                "we don't have a challenge to verify for peer : {:?}. challenge might be already used",
                 self.index
             );
-            // TODO : handle the scenario.
-            todo!()
+            return Err(Error::from(ErrorKind::InvalidData));
         }
-        // TODO : validate block fetch URL
-        let sent_challenge = self.challenge_for_peer.unwrap();
-        let result = verify(&sent_challenge, &response.signature, &response.public_key);
-        if !result {
+        let sent_challenge = sent_challenge.unwrap();
+        if HandshakeChallenge::is_expired(self.challenge_issued_at, current_time) {
             warn!(
-                "handshake failed. signature is not valid. sig : {:?} challenge : {:?} key : {:?}",
-                hex::encode(sent_challenge),
-                hex::encode(response.signature),
-                hex::encode(response.public_key)
+                "handshake challenge for peer : {:?} is expired. issued at : {:?} current time : {:?}",
+                self.index, self.challenge_issued_at, current_time
             );
-            todo!()
+            return Err(Error::from(ErrorKind::InvalidData));
         }
 
-        let (wallet, _wallet_) = lock_for_read!(wallet, LOCK_ORDER_WALLET);
-
         let block_fetch_url;
+        let supports_compression;
+        let network_id;
+        let key_rotation_proof;
+        let endpoint_address;
         {
             let (configs, _configs_) = lock_for_read!(configs, LOCK_ORDER_CONFIGS);
 
             block_fetch_url = configs.get_block_fetch_url();
+            supports_compression = configs.get_server_configs().message_compression;
+            network_id = configs.get_server_configs().network_id.clone();
+            key_rotation_proof = configs.get_server_configs().key_rotation_proof.clone();
+            endpoint_address = configs.get_server_configs().endpoint.get_address();
+        }
+        if response.network_id != network_id {
+            warn!(
+                "handshake failed. peer : {:?} with key : {:?} is on network : {:?}, expected : {:?}",
+                self.index,
+                hex::encode(response.public_key),
+                response.network_id,
+                network_id
+            );
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+
+        let (wallet, _wallet_) = lock_for_read!(wallet, LOCK_ORDER_WALLET);
+
+        // TODO : validate block fetch URL
+        // our challenges name our endpoint address
+        let payload = HandshakeChallenge::get_payload_to_sign(
+            &sent_challenge,
+            self.challenge_issued_at,
+            &wallet.public_key,
+            &endpoint_address,
+        );
+        let result = verify(&payload, &response.signature, &response.public_key);
+        if !result {
+            warn!(
+                "handshake failed. signature is not valid. sig : {:?} challenge : {:?} key : {:?}",
+                hex::encode(response.signature),
+                hex::encode(sent_challenge),
+                hex::encode(response.public_key)
+            );
+            return Err(Error::from(ErrorKind::InvalidData));
         }
-        self.challenge_for_peer = None;
-        self.public_key = Some(response.public_key);
-        self.block_fetch_url = response.block_fetch_url;
 
         if self.static_peer_config.is_none() {
             // this is only called in initiator's side.
             // [1. A:challenge -> 2. B:response -> 3. A : response|B verified -> 4. B: A verified]
             // we only need to send a response for response is in above stage 3 (meaning the challenger).
-
-            let response = HandshakeResponse {
+            if !self.accept_challenge(
+                &response.challenge,
+                response.challenge_issued_at,
+                current_time,
+            ) {
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+            // the peer connected to us, so we don't know its address to compare with the named address
+            let payload = HandshakeChallenge::get_payload_to_sign(
+                &response.challenge,
+                response.challenge_issued_at,
+                &response.public_key,
+                &response.challenge_peer_address,
+            );
+            let our_response = HandshakeResponse {
                 public_key: wallet.public_key.clone(),
-                signature: sign(&response.challenge, &wallet.private_key),
+                signature: sign(payload.as_slice(), &wallet.private_key),
                 is_lite: 0,
+                supports_compression,
+                format_version: CURRENT_FORMAT_VERSION,
                 block_fetch_url: block_fetch_url.to_string(),
+                network_id,
+                key_rotation_proof: load_key_rotation_proof(
+                    key_rotation_proof.as_deref(),
+                    &wallet.public_key,
+                ),
                 challenge: generate_random_bytes(32).try_into().unwrap(),
+                challenge_issued_at: current_time,
+                challenge_peer_address: endpoint_address,
             };
             io_handler
-                .send_message(self.index, Message::HandshakeResponse(response).serialize())
+                .send_message(
+                    self.index,
+                    Message::HandshakeResponse(our_response).serialize(),
+                )
                 .await
                 .unwrap();
             info!("handshake response sent for peer: {:?}", self.index);
         } else {
             info!(
                 "handshake completed for peer : {:?}",
-                hex::encode(self.public_key.as_ref().unwrap())
+                hex::encode(response.public_key)
             );
         }
+        self.public_key = Some(response.public_key);
+        self.block_fetch_url = response.block_fetch_url;
+        self.compression_enabled = supports_compression && response.supports_compression;
+        self.format_version = negotiate_format_version(response.format_version);
+        // our challenge was answered, so the time since it was issued is a full round trip
+        self.record_latency(current_time.saturating_sub(self.challenge_issued_at));
 
         Ok(())
     }
@@ -180,6 +424,8 @@ impl Peer {
 
 #[cfg(test)]
 mod tests {
+    use crate::core::data::configuration::PeerConfig;
+    use crate::core::data::msg::handshake::HANDSHAKE_CHALLENGE_EXPIRY_TIME;
     use crate::core::data::peer::Peer;
 
     #[test]
@@ -191,5 +437,69 @@ mod tests {
         assert_eq!(peer.block_fetch_url, "".to_string());
         assert_eq!(peer.static_peer_config, None);
         assert_eq!(peer.challenge_for_peer, None);
+        assert_eq!(peer.get_latency(), None);
+        assert_eq!(peer.get_peer_address(), None);
+    }
+
+    #[test]
+    fn peer_address_test() {
+        let mut peer = Peer::new(1);
+        peer.static_peer_config = Some(PeerConfig {
+            host: "10.0.0.1".to_string(),
+            port: 12101,
+            protocol: "http".to_string(),
+            synctype: "full".to_string(),
+            scheme: None,
+            public_key: None,
+        });
+
+        // challenges from the peer must name this address
+        assert_eq!(peer.get_peer_address(), Some("10.0.0.1:12101".to_string()));
+    }
+
+    #[test]
+    fn record_latency_test() {
+        let mut peer = Peer::new(1);
+
+        peer.record_latency(100);
+        assert_eq!(peer.get_latency(), Some(100));
+        peer.record_latency(900);
+        assert_eq!(peer.get_latency(), Some(200));
+        peer.record_latency(200);
+        assert_eq!(peer.get_latency(), Some(200));
+    }
+
+    #[test]
+    fn known_inventory_test() {
+        let mut peer = Peer::new(1);
+
+        assert!(peer.mark_block_as_known([1; 32]));
+        assert!(!peer.mark_block_as_known([1; 32]));
+        // blocks and transactions are tracked separately
+        assert!(peer.mark_transaction_as_known([1; 64]));
+        assert!(!peer.mark_transaction_as_known([1; 64]));
+        assert!(peer.mark_transaction_as_known([2; 64]));
+    }
+
+    #[test]
+    fn accept_challenge_test() {
+        let mut peer = Peer::new(1);
+        let current_time = 10 * HANDSHAKE_CHALLENGE_EXPIRY_TIME;
+
+        assert!(peer.accept_challenge(&[1; 32], current_time - 1000, current_time));
+        // same challenge cannot be answered twice
+        assert!(!peer.accept_challenge(&[1; 32], current_time - 1000, current_time));
+
+        assert!(!peer.accept_challenge(
+            &[2; 32],
+            current_time - HANDSHAKE_CHALLENGE_EXPIRY_TIME - 1,
+            current_time
+        ));
+        assert!(!peer.accept_challenge(
+            &[3; 32],
+            current_time + HANDSHAKE_CHALLENGE_EXPIRY_TIME + 1,
+            current_time
+        ));
+        assert!(peer.accept_challenge(&[3; 32], current_time + 1000, current_time));
     }
 }
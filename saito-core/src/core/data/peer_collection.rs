@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::common::defs::SaitoPublicKey;
+use crate::common::defs::{SaitoPublicKey, Timestamp};
 use crate::core::data::peer::Peer;
 
 #[derive(Debug, Clone)]
@@ -31,4 +31,67 @@ impl PeerCollection {
     pub fn find_peer_by_index(&self, peer_index: u64) -> Option<&Peer> {
         return self.index_to_peers.get(&peer_index);
     }
+
+    pub fn record_latency(&mut self, peer_index: u64, round_trip_time: Timestamp) {
+        if let Some(peer) = self.index_to_peers.get_mut(&peer_index) {
+            peer.record_latency(round_trip_time);
+        }
+    }
+
+    pub fn get_peer_latency(&self, peer_index: u64) -> Option<Timestamp> {
+        self.find_peer_by_index(peer_index)
+            .and_then(|peer| peer.get_latency())
+    }
+
+    /// Returns the peers with a latency measurement, ordered from the lowest latency
+    pub fn get_peers_by_latency(&self) -> Vec<(u64, Timestamp)> {
+        let mut peers: Vec<(u64, Timestamp)> = self
+            .index_to_peers
+            .iter()
+            .filter_map(|(index, peer)| peer.get_latency().map(|latency| (*index, latency)))
+            .collect();
+        peers.sort_by(|(index_a, latency_a), (index_b, latency_b)| {
+            latency_a.cmp(latency_b).then(index_a.cmp(index_b))
+        });
+        peers
+    }
+
+    /// Returns the peer with the lowest latency among the given peers. Peers without a measurement are only
+    /// selected if none of the peers has one.
+    pub fn find_fastest_peer(&self, peer_indices: &[u64]) -> Option<u64> {
+        peer_indices.iter().cloned().min_by_key(|index| {
+            (
+                self.get_peer_latency(*index).unwrap_or(Timestamp::MAX),
+                *index,
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::peer::Peer;
+    use crate::core::data::peer_collection::PeerCollection;
+
+    #[test]
+    fn peers_by_latency_test() {
+        let mut peers = PeerCollection::new();
+        for index in 1..=4 {
+            peers.index_to_peers.insert(index, Peer::new(index));
+        }
+        peers.record_latency(1, 300);
+        peers.record_latency(2, 100);
+        peers.record_latency(3, 200);
+        peers.record_latency(10, 50);
+
+        assert_eq!(peers.get_peer_latency(2), Some(100));
+        assert_eq!(peers.get_peer_latency(4), None);
+        assert_eq!(
+            peers.get_peers_by_latency(),
+            vec![(2, 100), (3, 200), (1, 300)]
+        );
+        assert_eq!(peers.find_fastest_peer(&[1, 3, 4]), Some(3));
+        assert_eq!(peers.find_fastest_peer(&[4, 5]), Some(4));
+        assert_eq!(peers.find_fastest_peer(&[]), None);
+    }
 }
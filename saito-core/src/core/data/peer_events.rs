@@ -0,0 +1,169 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use tracing::debug;
+
+use crate::common::defs::{PeerIndex, SaitoPublicKey};
+
+/// Lifecycle of a peer connection as seen by the routing thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeerEvent {
+    /// `address` (host:port) is set for the connections made by this node. None for the incoming connections
+    Connected {
+        peer_index: PeerIndex,
+        address: Option<String>,
+    },
+    HandshakeCompleted {
+        peer_index: PeerIndex,
+        public_key: SaitoPublicKey,
+    },
+    /// the peer's handshake failed, e.g. its key changed without a rotation proof or it's on another network. the
+    /// peer is not used until it connects again
+    HandshakeRejected {
+        peer_index: PeerIndex,
+        reason: String,
+    },
+    /// `public_key` is None if the handshake wasn't completed
+    Disconnected {
+        peer_index: PeerIndex,
+        public_key: Option<SaitoPublicKey>,
+    },
+}
+
+impl PeerEvent {
+    pub fn get_peer_index(&self) -> PeerIndex {
+        match self {
+            PeerEvent::Connected { peer_index, .. }
+            | PeerEvent::HandshakeCompleted { peer_index, .. }
+            | PeerEvent::HandshakeRejected { peer_index, .. }
+            | PeerEvent::Disconnected { peer_index, .. } => *peer_index,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        match self {
+            PeerEvent::Connected {
+                peer_index,
+                address,
+            } => serde_json::json!({
+                "event": "connected",
+                "peer_index": peer_index,
+                "address": address,
+            }),
+            PeerEvent::HandshakeCompleted {
+                peer_index,
+                public_key,
+            } => serde_json::json!({
+                "event": "handshake_completed",
+                "peer_index": peer_index,
+                "public_key": hex::encode(public_key),
+            }),
+            PeerEvent::HandshakeRejected { peer_index, reason } => serde_json::json!({
+                "event": "handshake_rejected",
+                "peer_index": peer_index,
+                "reason": reason,
+            }),
+            PeerEvent::Disconnected {
+                peer_index,
+                public_key,
+            } => serde_json::json!({
+                "event": "disconnected",
+                "peer_index": peer_index,
+                "public_key": public_key.map(hex::encode),
+            }),
+        }
+        .to_string()
+    }
+}
+
+/// Receives the peer events. Called from the routing thread, so implementations shouldn't block
+pub trait PeerEventListener: Debug + Send + Sync {
+    fn on_peer_event(&self, event: &PeerEvent);
+}
+
+/// Listeners added by the application embedding the node
+#[derive(Debug, Clone, Default)]
+pub struct PeerEventListeners {
+    listeners: Vec<Arc<dyn PeerEventListener>>,
+}
+
+impl PeerEventListeners {
+    pub fn new() -> PeerEventListeners {
+        Default::default()
+    }
+
+    pub fn add_listener(&mut self, listener: Arc<dyn PeerEventListener>) {
+        self.listeners.push(listener);
+    }
+
+    pub fn notify(&self, event: PeerEvent) {
+        debug!("peer event : {:?}", event);
+        for listener in self.listeners.iter() {
+            listener.on_peer_event(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::core::data::peer_events::{PeerEvent, PeerEventListener, PeerEventListeners};
+
+    #[derive(Debug, Default)]
+    struct RecordingListener {
+        events: Mutex<Vec<PeerEvent>>,
+    }
+
+    impl PeerEventListener for RecordingListener {
+        fn on_peer_event(&self, event: &PeerEvent) {
+            self.events.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn peer_event_listeners_test() {
+        let listener_1 = Arc::new(RecordingListener::default());
+        let listener_2 = Arc::new(RecordingListener::default());
+        let mut listeners = PeerEventListeners::new();
+        // nothing to notify yet
+        listeners.notify(PeerEvent::Disconnected {
+            peer_index: 1,
+            public_key: None,
+        });
+        listeners.add_listener(listener_1.clone());
+        listeners.add_listener(listener_2.clone());
+
+        listeners.notify(PeerEvent::Connected {
+            peer_index: 2,
+            address: Some("127.0.0.1:12101".to_string()),
+        });
+        listeners.notify(PeerEvent::HandshakeCompleted {
+            peer_index: 2,
+            public_key: [3; 33],
+        });
+
+        let events = listener_2.events.lock().unwrap().clone();
+        assert_eq!(events, listener_1.events.lock().unwrap().clone());
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].get_peer_index(), 2);
+        let json: serde_json::Value = serde_json::from_str(&events[1].to_json()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "event": "handshake_completed",
+                "peer_index": 2,
+                "public_key": hex::encode([3; 33]),
+            })
+        );
+        let json: serde_json::Value = serde_json::from_str(
+            &PeerEvent::Disconnected {
+                peer_index: 2,
+                public_key: None,
+            }
+            .to_json(),
+        )
+        .unwrap();
+        assert_eq!(json["public_key"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+
+use tracing::warn;
+
+use crate::common::defs::{SaitoPrivateKey, SaitoPublicKey, SaitoSignature};
+use crate::core::data::configuration::PeerConfig;
+use crate::core::data::crypto::{sign, verify};
+
+pub const PEER_KEYS_FILE_PATH: &str = "./data/peer_keys";
+/// [old public key - 33 bytes][new public key - 33 bytes][signature - 64 bytes]
+pub const KEY_ROTATION_PROOF_SIZE: usize = 130;
+const KEY_ROTATION_DOMAIN: &[u8] = b"saito-key-rotation";
+
+/// Proof that a node moved from one key to another, signed with the old key. Peers which pinned the old key accept
+/// the new key in the handshake if the proof is presented
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyRotationProof {
+    pub old_public_key: SaitoPublicKey,
+    pub new_public_key: SaitoPublicKey,
+    pub signature: SaitoSignature,
+}
+
+impl KeyRotationProof {
+    pub fn new(
+        old_public_key: SaitoPublicKey,
+        old_private_key: &SaitoPrivateKey,
+        new_public_key: SaitoPublicKey,
+    ) -> KeyRotationProof {
+        let payload = Self::get_payload_to_sign(&old_public_key, &new_public_key);
+        KeyRotationProof {
+            old_public_key,
+            new_public_key,
+            signature: sign(payload.as_slice(), old_private_key),
+        }
+    }
+
+    fn get_payload_to_sign(
+        old_public_key: &SaitoPublicKey,
+        new_public_key: &SaitoPublicKey,
+    ) -> Vec<u8> {
+        [
+            KEY_ROTATION_DOMAIN,
+            old_public_key.as_slice(),
+            new_public_key.as_slice(),
+        ]
+        .concat()
+    }
+
+    pub fn verify(&self) -> bool {
+        let payload = Self::get_payload_to_sign(&self.old_public_key, &self.new_public_key);
+        verify(payload.as_slice(), &self.signature, &self.old_public_key)
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            self.old_public_key.as_slice(),
+            self.new_public_key.as_slice(),
+            self.signature.as_slice(),
+        ]
+        .concat()
+    }
+
+    pub fn deserialize(buffer: &[u8]) -> Result<KeyRotationProof, Error> {
+        if buffer.len() != KEY_ROTATION_PROOF_SIZE {
+            warn!(
+                "invalid key rotation proof length : {:?}, expected : {:?}",
+                buffer.len(),
+                KEY_ROTATION_PROOF_SIZE
+            );
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        Ok(KeyRotationProof {
+            old_public_key: buffer[0..33].try_into().unwrap(),
+            new_public_key: buffer[33..66].try_into().unwrap(),
+            signature: buffer[66..130].try_into().unwrap(),
+        })
+    }
+
+    /// Reads a hex encoded proof, as it's given in the config file
+    pub fn from_hex(value: &str) -> Result<KeyRotationProof, Error> {
+        let buffer = hex::decode(value).map_err(|_| Error::from(ErrorKind::InvalidData))?;
+        Self::deserialize(&buffer)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerKeyCheck {
+    /// no key is expected from the peer yet. the key is pinned after the handshake
+    Unknown,
+    Matches,
+    /// the peer presented a valid proof for moving from the expected key to its current key
+    Rotated,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeerKeyError {
+    /// the key is different from the expected key and the peer didn't present a proof for the change
+    KeyChanged {
+        expected: SaitoPublicKey,
+        received: SaitoPublicKey,
+    },
+    InvalidRotationProof,
+}
+
+/// Public keys expected from the static peers by their addresses. A key is pinned when the handshake with the peer
+/// completes for the first time, and handshakes with another key are rejected afterwards unless the peer presents a
+/// `KeyRotationProof` signed with the pinned key. So a node taking over the peer's address (e.g. with a hijacked dns
+/// record) can't pass as the peer.
+///
+/// A `public_key` set in the peer config takes precedence over the pinned key.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeerKeyPins {
+    pins: BTreeMap<String, SaitoPublicKey>,
+}
+
+impl PeerKeyPins {
+    pub fn new() -> PeerKeyPins {
+        Default::default()
+    }
+
+    pub fn get_peer_address(config: &PeerConfig) -> String {
+        format!("{}:{}", config.host, config.port)
+    }
+
+    pub fn get_pinned_key(&self, config: &PeerConfig) -> Option<&SaitoPublicKey> {
+        self.pins.get(&Self::get_peer_address(config))
+    }
+
+    /// Key expected in the next handshake with the peer
+    pub fn get_expected_key(&self, config: &PeerConfig) -> Option<SaitoPublicKey> {
+        if let Some(public_key) = config.public_key.as_ref() {
+            match parse_public_key(public_key) {
+                Some(public_key) => return Some(public_key),
+                None => warn!(
+                    "invalid public key : {:?} in the config of peer : {:?}",
+                    public_key,
+                    Self::get_peer_address(config)
+                ),
+            }
+        }
+        self.get_pinned_key(config).cloned()
+    }
+
+    /// Checks the key received in the handshake with the peer against the expected key
+    pub fn check(
+        &self,
+        config: &PeerConfig,
+        public_key: &SaitoPublicKey,
+        rotation_proof: Option<&KeyRotationProof>,
+    ) -> Result<PeerKeyCheck, PeerKeyError> {
+        let expected = match self.get_expected_key(config) {
+            Some(expected) => expected,
+            None => return Ok(PeerKeyCheck::Unknown),
+        };
+        if expected == *public_key {
+            return Ok(PeerKeyCheck::Matches);
+        }
+        match rotation_proof {
+            Some(proof)
+                if proof.old_public_key == expected && proof.new_public_key == *public_key =>
+            {
+                if proof.verify() {
+                    Ok(PeerKeyCheck::Rotated)
+                } else {
+                    Err(PeerKeyError::InvalidRotationProof)
+                }
+            }
+            _ => Err(PeerKeyError::KeyChanged {
+                expected,
+                received: *public_key,
+            }),
+        }
+    }
+
+    /// Returns true if the pinned key of the peer changed
+    pub fn pin(&mut self, config: &PeerConfig, public_key: SaitoPublicKey) -> bool {
+        self.pins.insert(Self::get_peer_address(config), public_key) != Some(public_key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+    }
+
+    /// one entry per line as [host]:[port]:[hex public key]
+    pub fn serialize_for_disk(&self) -> Vec<u8> {
+        let mut buffer = String::new();
+        for (address, public_key) in self.pins.iter() {
+            buffer.push_str(address);
+            buffer.push(':');
+            buffer.push_str(hex::encode(public_key).as_str());
+            buffer.push('\n');
+        }
+        buffer.into_bytes()
+    }
+
+    pub fn deserialize_from_disk(bytes: &[u8]) -> PeerKeyPins {
+        let mut pins = PeerKeyPins::new();
+        for line in String::from_utf8_lossy(bytes).lines() {
+            match line
+                .rsplit_once(':')
+                .and_then(|(address, key)| Some((address, parse_public_key(key)?)))
+            {
+                Some((address, public_key)) => {
+                    pins.pins.insert(address.to_string(), public_key);
+                }
+                None => warn!("invalid peer key entry : {:?}", line),
+            }
+        }
+        pins
+    }
+}
+
+fn parse_public_key(value: &str) -> Option<SaitoPublicKey> {
+    hex::decode(value)
+        .ok()
+        .and_then(|buffer| buffer.try_into().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::configuration::PeerConfig;
+    use crate::core::data::crypto::generate_keys;
+    use crate::core::data::peer_identity::{
+        KeyRotationProof, PeerKeyCheck, PeerKeyError, PeerKeyPins,
+    };
+
+    fn create_peer_config(public_key: Option<String>) -> PeerConfig {
+        PeerConfig {
+            host: "10.0.0.1".to_string(),
+            port: 12101,
+            protocol: "http".to_string(),
+            synctype: "full".to_string(),
+            scheme: None,
+            public_key,
+        }
+    }
+
+    #[test]
+    fn key_rotation_proof_test() {
+        let (old_public_key, old_private_key) = generate_keys();
+        let (new_public_key, new_private_key) = generate_keys();
+
+        let proof = KeyRotationProof::new(old_public_key, &old_private_key, new_public_key);
+        assert!(proof.verify());
+        let buffer = proof.serialize();
+        assert_eq!(KeyRotationProof::deserialize(&buffer).unwrap(), proof);
+        assert_eq!(
+            KeyRotationProof::from_hex(hex::encode(&buffer).as_str()).unwrap(),
+            proof
+        );
+        assert!(KeyRotationProof::deserialize(&buffer[..129]).is_err());
+
+        // signed with the new key instead of the old key
+        let proof = KeyRotationProof::new(old_public_key, &new_private_key, new_public_key);
+        assert!(!proof.verify());
+    }
+
+    #[test]
+    fn peer_key_pins_test() {
+        let (public_key, private_key) = generate_keys();
+        let (new_public_key, new_private_key) = generate_keys();
+        let (other_public_key, _) = generate_keys();
+        let config = create_peer_config(None);
+
+        let mut pins = PeerKeyPins::new();
+        assert_eq!(
+            pins.check(&config, &public_key, None),
+            Ok(PeerKeyCheck::Unknown)
+        );
+        assert!(pins.pin(&config, public_key));
+        assert!(!pins.pin(&config, public_key));
+        assert_eq!(
+            pins.check(&config, &public_key, None),
+            Ok(PeerKeyCheck::Matches)
+        );
+        assert_eq!(
+            pins.check(&config, &other_public_key, None),
+            Err(PeerKeyError::KeyChanged {
+                expected: public_key,
+                received: other_public_key,
+            })
+        );
+
+        let proof = KeyRotationProof::new(public_key, &private_key, new_public_key);
+        assert_eq!(
+            pins.check(&config, &new_public_key, Some(&proof)),
+            Ok(PeerKeyCheck::Rotated)
+        );
+        // proof for another key doesn't allow the change
+        assert!(pins
+            .check(&config, &other_public_key, Some(&proof))
+            .is_err());
+        let invalid_proof = KeyRotationProof::new(public_key, &new_private_key, new_public_key);
+        assert_eq!(
+            pins.check(&config, &new_public_key, Some(&invalid_proof)),
+            Err(PeerKeyError::InvalidRotationProof)
+        );
+
+        // key in the config takes precedence over the pinned key
+        let config_with_key = create_peer_config(Some(hex::encode(other_public_key)));
+        assert_eq!(
+            pins.check(&config_with_key, &other_public_key, None),
+            Ok(PeerKeyCheck::Matches)
+        );
+
+        let pins2 = PeerKeyPins::deserialize_from_disk(&pins.serialize_for_disk());
+        assert_eq!(pins2, pins);
+        assert_eq!(pins2.get_pinned_key(&config), Some(&public_key));
+    }
+}
@@ -0,0 +1,206 @@
+use ahash::HashMap;
+
+use crate::common::defs::PeerIndex;
+use crate::core::data::msg::message::Message;
+
+const MESSAGE_CATEGORY_COUNT: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageCategory {
+    Handshake = 0,
+    Block = 1,
+    Transaction = 2,
+    Ping = 3,
+    /// blocks fetched from the peer's block fetch url
+    Fetch = 4,
+    Other = 5,
+}
+
+impl MessageCategory {
+    pub const ALL: [MessageCategory; MESSAGE_CATEGORY_COUNT] = [
+        MessageCategory::Handshake,
+        MessageCategory::Block,
+        MessageCategory::Transaction,
+        MessageCategory::Ping,
+        MessageCategory::Fetch,
+        MessageCategory::Other,
+    ];
+
+    pub fn from_message(message: &Message) -> MessageCategory {
+        match message {
+            Message::HandshakeChallenge(_) | Message::HandshakeResponse(_) => {
+                MessageCategory::Handshake
+            }
+            Message::Block(_) | Message::BlockHeaderHash(_, _) | Message::BlockchainRequest(_) => {
+                MessageCategory::Block
+            }
+            Message::Transaction(_)
+            | Message::ApplicationTransaction(_)
+            | Message::TransactionBatch(_)
+            | Message::TransactionBatchResult(_)
+            | Message::TransactionRejected(_)
+            | Message::GoldenTicket(_) => MessageCategory::Transaction,
+            Message::Ping() => MessageCategory::Ping,
+            _ => MessageCategory::Other,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            MessageCategory::Handshake => "handshake",
+            MessageCategory::Block => "block",
+            MessageCategory::Transaction => "tx",
+            MessageCategory::Ping => "ping",
+            MessageCategory::Fetch => "fetch",
+            MessageCategory::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MessageCounter {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct PeerMessageCounters {
+    counters: [MessageCounter; MESSAGE_CATEGORY_COUNT],
+    /// messages from the peer which couldn't be deserialized
+    pub invalid_messages: u64,
+    /// blocks announced by the peer which were created by blacklisted block creators
+    pub blacklisted_blocks: u64,
+}
+
+impl PeerMessageCounters {
+    pub fn get(&self, category: MessageCategory) -> MessageCounter {
+        self.counters[category as usize]
+    }
+
+    pub fn get_total(&self) -> MessageCounter {
+        self.counters
+            .iter()
+            .fold(MessageCounter::default(), |total, counter| MessageCounter {
+                count: total.count + counter.count,
+                bytes: total.bytes + counter.bytes,
+            })
+    }
+}
+
+/// Incoming traffic from each connected peer, grouped by the message type. Counters of a peer are removed when the
+/// peer disconnects, so a reconnected peer starts from zero.
+#[derive(Debug, Default)]
+pub struct PeerMessageStats {
+    peers: HashMap<PeerIndex, PeerMessageCounters>,
+}
+
+impl PeerMessageStats {
+    pub fn new() -> PeerMessageStats {
+        PeerMessageStats {
+            peers: Default::default(),
+        }
+    }
+
+    pub fn on_message_received(
+        &mut self,
+        peer_index: PeerIndex,
+        category: MessageCategory,
+        size: usize,
+    ) {
+        let counter = &mut self.peers.entry(peer_index).or_default().counters[category as usize];
+        counter.count += 1;
+        counter.bytes += size as u64;
+    }
+
+    pub fn on_invalid_message(&mut self, peer_index: PeerIndex) {
+        self.peers.entry(peer_index).or_default().invalid_messages += 1;
+    }
+
+    pub fn on_blacklisted_block(&mut self, peer_index: PeerIndex) {
+        self.peers.entry(peer_index).or_default().blacklisted_blocks += 1;
+    }
+
+    pub fn get_peer_counters(&self, peer_index: PeerIndex) -> Option<&PeerMessageCounters> {
+        self.peers.get(&peer_index)
+    }
+
+    pub fn remove_peer(&mut self, peer_index: PeerIndex) {
+        self.peers.remove(&peer_index);
+    }
+
+    pub fn get_stats(&self) -> Vec<String> {
+        let mut peer_indices: Vec<&PeerIndex> = self.peers.keys().collect();
+        peer_indices.sort();
+        peer_indices
+            .into_iter()
+            .map(|peer_index| {
+                let counters = &self.peers[peer_index];
+                let categories = MessageCategory::ALL
+                    .iter()
+                    .map(|category| {
+                        let counter = counters.get(*category);
+                        format!(
+                            "{} : {:?} ({:?} bytes)",
+                            category.get_name(),
+                            counter.count,
+                            counter.bytes
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                format!(
+                    "{} - peer : {:?} {}, invalid : {:?}, blacklisted_blocks : {:?}",
+                    format!("{:width$}", "routing::peer_messages", width = 40),
+                    peer_index,
+                    categories,
+                    counters.invalid_messages,
+                    counters.blacklisted_blocks
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::msg::message::Message;
+    use crate::core::data::peer_message_stats::{MessageCategory, PeerMessageStats};
+
+    #[test]
+    fn peer_message_stats_test() {
+        let mut stats = PeerMessageStats::new();
+        assert_eq!(
+            MessageCategory::from_message(&Message::Ping()),
+            MessageCategory::Ping
+        );
+        assert_eq!(
+            MessageCategory::from_message(&Message::ForkIdRequest()),
+            MessageCategory::Other
+        );
+
+        stats.on_message_received(1, MessageCategory::Ping, 5);
+        stats.on_message_received(1, MessageCategory::Ping, 5);
+        stats.on_message_received(1, MessageCategory::Fetch, 1000);
+        stats.on_message_received(2, MessageCategory::Block, 45);
+        stats.on_invalid_message(2);
+        stats.on_blacklisted_block(2);
+
+        let counters = stats.get_peer_counters(1).unwrap();
+        assert_eq!(counters.get(MessageCategory::Ping).count, 2);
+        assert_eq!(counters.get(MessageCategory::Ping).bytes, 10);
+        assert_eq!(counters.get(MessageCategory::Block).count, 0);
+        assert_eq!(counters.get_total().count, 3);
+        assert_eq!(counters.get_total().bytes, 1010);
+        assert_eq!(stats.get_peer_counters(2).unwrap().invalid_messages, 1);
+        assert_eq!(stats.get_peer_counters(2).unwrap().blacklisted_blocks, 1);
+
+        let lines = stats.get_stats();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("peer : 1 handshake : 0 (0 bytes)"));
+        assert!(lines[0].contains("fetch : 1 (1000 bytes)"));
+
+        stats.remove_peer(1);
+        assert!(stats.get_peer_counters(1).is_none());
+        assert_eq!(stats.get_stats().len(), 1);
+    }
+}
@@ -0,0 +1,168 @@
+use ahash::AHashMap;
+use serde::Deserialize;
+
+use crate::common::defs::SaitoPublicKey;
+use crate::core::data::configuration::PeerConfig;
+
+/// Peers the node needs to be connected to before producing blocks, so a temporarily isolated node doesn't build a
+/// fork which gets orphaned when it reconnects. Only peers which completed the handshake are counted
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct PeerQuorumConfig {
+    /// 0 disables the check
+    pub min_peers: u64,
+    /// peers which need to be connected, as hex encoded public keys or "host:port" of static peers
+    pub required_peers: Vec<String>,
+}
+
+impl PeerQuorumConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.min_peers > 0 || !self.required_peers.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct ConnectedPeer {
+    // "host:port" of the static peer. None for incoming connections
+    address: Option<String>,
+    // set when the handshake is completed
+    public_key: Option<SaitoPublicKey>,
+}
+
+/// Connected peers and the sync progress as seen by the routing thread. Shared with the consensus thread, so it can
+/// check the quorum without locking the peer collection
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionStatus {
+    peers: AHashMap<u64, ConnectedPeer>,
+    pending_block_count: usize,
+}
+
+impl ConnectionStatus {
+    pub fn new() -> ConnectionStatus {
+        Default::default()
+    }
+
+    pub fn on_peer_connected(&mut self, peer_index: u64, static_peer_config: Option<&PeerConfig>) {
+        self.peers.insert(
+            peer_index,
+            ConnectedPeer {
+                address: static_peer_config
+                    .map(|config| format!("{}:{}", config.host, config.port)),
+                public_key: None,
+            },
+        );
+    }
+
+    pub fn on_handshake_completed(&mut self, peer_index: u64, public_key: SaitoPublicKey) {
+        if let Some(peer) = self.peers.get_mut(&peer_index) {
+            peer.public_key = Some(public_key);
+        }
+    }
+
+    pub fn on_peer_disconnected(&mut self, peer_index: u64) {
+        self.peers.remove(&peer_index);
+    }
+
+    /// number of peers which completed the handshake
+    pub fn get_connected_peer_count(&self) -> u64 {
+        self.peers
+            .values()
+            .filter(|peer| peer.public_key.is_some())
+            .count() as u64
+    }
+
+    pub fn set_pending_block_count(&mut self, count: usize) {
+        self.pending_block_count = count;
+    }
+
+    /// number of blocks announced by the peers which are not added to the blockchain yet
+    pub fn get_pending_block_count(&self) -> usize {
+        self.pending_block_count
+    }
+
+    fn is_connected(&self, required_peer: &str) -> bool {
+        self.peers.values().any(|peer| {
+            peer.public_key.is_some()
+                && (peer.address.as_deref() == Some(required_peer)
+                    || peer
+                        .public_key
+                        .map(|key| hex::encode(key) == required_peer.to_lowercase())
+                        .unwrap_or(false))
+        })
+    }
+
+    /// Returns the reason if the connected peers don't meet the quorum
+    pub fn check_quorum(&self, config: &PeerQuorumConfig) -> Result<(), String> {
+        let connected = self.get_connected_peer_count();
+        if connected < config.min_peers {
+            return Err(format!(
+                "connected to {:?} peers, need {:?}",
+                connected, config.min_peers
+            ));
+        }
+        if let Some(missing) = config
+            .required_peers
+            .iter()
+            .find(|peer| !self.is_connected(peer.as_str()))
+        {
+            return Err(format!("required peer : {:?} is not connected", missing));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::configuration::PeerConfig;
+    use crate::core::data::peer_quorum::{ConnectionStatus, PeerQuorumConfig};
+
+    #[test]
+    fn peer_quorum_test() {
+        let mut status = ConnectionStatus::new();
+        assert!(status.check_quorum(&PeerQuorumConfig::default()).is_ok());
+
+        let config = PeerQuorumConfig {
+            min_peers: 2,
+            required_peers: vec!["10.0.0.1:12101".to_string(), hex::encode([3; 33])],
+        };
+        let static_peer = PeerConfig {
+            host: "10.0.0.1".to_string(),
+            port: 12101,
+            protocol: "http".to_string(),
+            synctype: "full".to_string(),
+            scheme: None,
+            public_key: None,
+        };
+        status.on_peer_connected(1, Some(&static_peer));
+        status.on_peer_connected(2, None);
+        // handshakes are not completed yet
+        assert_eq!(status.get_connected_peer_count(), 0);
+        assert!(status.check_quorum(&config).is_err());
+
+        status.on_handshake_completed(1, [1; 33]);
+        status.on_handshake_completed(2, [2; 33]);
+        assert_eq!(status.get_connected_peer_count(), 2);
+        assert_eq!(
+            status.check_quorum(&config),
+            Err(format!(
+                "required peer : {:?} is not connected",
+                hex::encode([3; 33])
+            ))
+        );
+
+        status.on_peer_connected(3, None);
+        status.on_handshake_completed(3, [3; 33]);
+        assert!(status.check_quorum(&config).is_ok());
+
+        status.on_peer_disconnected(1);
+        assert_eq!(
+            status.check_quorum(&config),
+            Err("required peer : \"10.0.0.1:12101\" is not connected".to_string())
+        );
+        status.on_peer_disconnected(3);
+        assert_eq!(
+            status.check_quorum(&config),
+            Err("connected to 1 peers, need 2".to_string())
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+
+use tracing::{debug, trace};
+
+use crate::common::defs::{PeerIndex, SaitoHash, Timestamp};
+use crate::core::data::blockchain_sync_state::BLOCK_FETCH_TIMEOUT_IN_MS;
+use crate::core::data::msg::message::{MESSAGE_HEADER_SIZE, MESSAGE_MAGIC};
+
+/// ids are unique for the lifetime of the node, so a late response can't be matched to a newer request
+pub type RequestId = u64;
+
+pub const FORK_ID_REQUEST_TIMEOUT_IN_MS: Timestamp = 10_000;
+pub const PING_TIMEOUT_IN_MS: Timestamp = 10_000;
+
+/// message types of the fork id request (17) and response (18)
+const FORK_ID_REQUEST_MESSAGE_TYPE: u8 = 17;
+const FORK_ID_RESPONSE_MESSAGE_TYPE: u8 = 18;
+
+/// Requests sent to a peer which expect a response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerRequest {
+    BlockFetch { block_hash: SaitoHash },
+    ForkId,
+    Ping,
+}
+
+impl PeerRequest {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            PeerRequest::BlockFetch { .. } => "block_fetch",
+            PeerRequest::ForkId => "fork_id",
+            PeerRequest::Ping => "ping",
+        }
+    }
+
+    pub fn get_timeout_in_ms(&self) -> Timestamp {
+        match self {
+            PeerRequest::BlockFetch { .. } => BLOCK_FETCH_TIMEOUT_IN_MS,
+            PeerRequest::ForkId => FORK_ID_REQUEST_TIMEOUT_IN_MS,
+            PeerRequest::Ping => PING_TIMEOUT_IN_MS,
+        }
+    }
+
+    /// Request sent with the outgoing message buffer. Only the message type in the header is read
+    pub fn from_request_message(buffer: &[u8]) -> Option<PeerRequest> {
+        match get_message_type(buffer)? {
+            FORK_ID_REQUEST_MESSAGE_TYPE => Some(PeerRequest::ForkId),
+            _ => None,
+        }
+    }
+
+    /// Request answered by the incoming message buffer. Only the message type in the header is read
+    pub fn from_response_message(buffer: &[u8]) -> Option<PeerRequest> {
+        match get_message_type(buffer)? {
+            FORK_ID_RESPONSE_MESSAGE_TYPE => Some(PeerRequest::ForkId),
+            _ => None,
+        }
+    }
+}
+
+fn get_message_type(buffer: &[u8]) -> Option<u8> {
+    if buffer.len() < MESSAGE_HEADER_SIZE || buffer[0..2] != MESSAGE_MAGIC {
+        return None;
+    }
+    Some(buffer[4])
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingPeerRequest {
+    pub peer_index: PeerIndex,
+    pub request: PeerRequest,
+    pub sent_at: Timestamp,
+}
+
+impl PendingPeerRequest {
+    pub fn get_round_trip_time(&self, current_time: Timestamp) -> Timestamp {
+        current_time.saturating_sub(self.sent_at)
+    }
+}
+
+/// Requests sent to the peers which are waiting for a response. Each request times out separately with the timeout of
+/// its type. Responses carrying the request id are matched by the id. Responses without one (fork ids) are matched to
+/// the oldest pending request of the same type to the peer, since peers answer the requests in order
+#[derive(Debug, Default)]
+pub struct PendingPeerRequests {
+    // ordered by the id, so the requests are ordered by the time they were sent
+    requests: BTreeMap<RequestId, PendingPeerRequest>,
+    pub completed_requests: u64,
+    pub timed_out_requests: u64,
+}
+
+impl PendingPeerRequests {
+    pub fn new() -> PendingPeerRequests {
+        Default::default()
+    }
+
+    pub fn add(
+        &mut self,
+        request_id: RequestId,
+        peer_index: PeerIndex,
+        request: PeerRequest,
+        current_time: Timestamp,
+    ) {
+        trace!(
+            "request : {:?} - {:?} sent to peer : {:?}",
+            request_id,
+            request.get_name(),
+            peer_index
+        );
+        self.requests.insert(
+            request_id,
+            PendingPeerRequest {
+                peer_index,
+                request,
+                sent_at: current_time,
+            },
+        );
+    }
+
+    /// Removes the request answered by the response. None if the request already timed out or was never sent
+    pub fn complete(&mut self, request_id: RequestId) -> Option<PendingPeerRequest> {
+        let request = self.requests.remove(&request_id)?;
+        self.completed_requests += 1;
+        Some(request)
+    }
+
+    /// Removes the oldest pending request of the type to the peer
+    pub fn complete_oldest(
+        &mut self,
+        peer_index: PeerIndex,
+        request: PeerRequest,
+    ) -> Option<(RequestId, PendingPeerRequest)> {
+        let request_id = self
+            .requests
+            .iter()
+            .find(|(_, pending)| pending.peer_index == peer_index && pending.request == request)
+            .map(|(request_id, _)| *request_id)?;
+        self.complete(request_id)
+            .map(|pending| (request_id, pending))
+    }
+
+    /// Drops a request without a response, e.g. a block fetch which was not started
+    pub fn cancel(&mut self, request_id: RequestId) {
+        self.requests.remove(&request_id);
+    }
+
+    /// Requests to a disconnected peer are dropped without timing out, since the disconnection is handled separately
+    pub fn remove_peer(&mut self, peer_index: PeerIndex) {
+        self.requests
+            .retain(|_, pending| pending.peer_index != peer_index);
+    }
+
+    /// Removes and returns the requests which were not answered within their timeout
+    pub fn take_timed_out(
+        &mut self,
+        current_time: Timestamp,
+    ) -> Vec<(RequestId, PendingPeerRequest)> {
+        let timed_out: Vec<RequestId> = self
+            .requests
+            .iter()
+            .filter(|(_, pending)| {
+                pending.get_round_trip_time(current_time) >= pending.request.get_timeout_in_ms()
+            })
+            .map(|(request_id, _)| *request_id)
+            .collect();
+        let mut result = vec![];
+        for request_id in timed_out {
+            let pending = self.requests.remove(&request_id).unwrap();
+            debug!(
+                "request : {:?} - {:?} to peer : {:?} timed out",
+                request_id,
+                pending.request.get_name(),
+                pending.peer_index
+            );
+            result.push((request_id, pending));
+        }
+        self.timed_out_requests += result.len() as u64;
+        result
+    }
+
+    pub fn get_pending_count(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn get_stats(&self) -> String {
+        format!(
+            "{} - pending : {:?}, completed : {:?}, timed_out : {:?}",
+            format!("{:width$}", "network::peer_requests", width = 40),
+            self.requests.len(),
+            self.completed_requests,
+            self.timed_out_requests
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::msg::message::Message;
+    use crate::core::data::peer_requests::{
+        PeerRequest, PendingPeerRequests, FORK_ID_REQUEST_TIMEOUT_IN_MS, PING_TIMEOUT_IN_MS,
+    };
+
+    #[test]
+    fn pending_peer_requests_test() {
+        assert_eq!(
+            PeerRequest::from_request_message(&Message::ForkIdRequest().serialize()),
+            Some(PeerRequest::ForkId)
+        );
+        assert_eq!(
+            PeerRequest::from_request_message(&Message::Ping().serialize()),
+            None
+        );
+        assert_eq!(PeerRequest::from_response_message(&[18; 4]), None);
+
+        let mut requests = PendingPeerRequests::new();
+        requests.add(1, 1, PeerRequest::Ping, 1000);
+        requests.add(2, 1, PeerRequest::ForkId, 1000);
+        requests.add(3, 2, PeerRequest::ForkId, 2000);
+        requests.add(4, 1, PeerRequest::ForkId, 3000);
+        requests.add(
+            5,
+            2,
+            PeerRequest::BlockFetch {
+                block_hash: [1; 32],
+            },
+            3000,
+        );
+
+        // responses without an id are matched to the oldest request of the type to the peer
+        let (request_id, pending) = requests.complete_oldest(1, PeerRequest::ForkId).unwrap();
+        assert_eq!(request_id, 2);
+        assert_eq!(pending.get_round_trip_time(1500), 500);
+        assert_eq!(requests.complete(1).unwrap().request, PeerRequest::Ping);
+        // a late response to a completed request is not matched
+        assert!(requests.complete(1).is_none());
+
+        assert!(requests
+            .take_timed_out(2000 + FORK_ID_REQUEST_TIMEOUT_IN_MS - 1)
+            .is_empty());
+        let timed_out = requests.take_timed_out(3000 + PING_TIMEOUT_IN_MS);
+        assert_eq!(
+            timed_out
+                .iter()
+                .map(|(request_id, _)| *request_id)
+                .collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+        assert_eq!(requests.get_pending_count(), 1);
+        assert_eq!(requests.completed_requests, 2);
+        assert_eq!(requests.timed_out_requests, 2);
+
+        requests.remove_peer(2);
+        assert_eq!(requests.get_pending_count(), 0);
+    }
+}
@@ -0,0 +1,353 @@
+use std::fmt::Write;
+
+use ahash::AHashMap;
+
+use crate::common::defs::{BlockId, Currency, SaitoPublicKey};
+use crate::core::data::block::Block;
+use crate::core::data::crypto::hash;
+use crate::core::data::golden_ticket::GoldenTicket;
+use crate::core::data::slip::SlipType;
+use crate::core::data::transaction::Transaction;
+use crate::core::data::utxo_report::UtxoReportFormat;
+
+/// Routing work and payouts attributed to a single node
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeRoutingStats {
+    pub public_key: SaitoPublicKey,
+    /// number of transactions the node is a hop in
+    pub relayed_transactions: u64,
+    /// routing work from the hops the node received the transactions in. the first hop gets all the fees of the
+    /// transaction and each following hop gets half of the previous hop
+    pub routing_work: Currency,
+    pub router_payouts: u64,
+    pub router_payout_amount: Currency,
+    pub miner_payouts: u64,
+    pub miner_payout_amount: Currency,
+}
+
+/// Routing paths of the transactions in a block range, attributed to the nodes in the paths and checked against the
+/// router payouts in the fee transactions
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoutingReport {
+    pub from_block_id: BlockId,
+    pub to_block_id: BlockId,
+    pub transaction_count: u64,
+    /// transactions with at least one hop
+    pub routed_transaction_count: u64,
+    /// paths with invalid hop signatures, broken links, or not starting from the transaction signer
+    pub invalid_path_count: u64,
+    /// fee transactions paying the router selected from the previous block's routing paths
+    pub verified_router_payouts: u64,
+    /// blocks whose fee transaction doesn't pay the router selected from the previous block's routing paths
+    pub mismatched_router_payouts: Vec<BlockId>,
+    /// ordered by the routing work, highest first
+    pub nodes: Vec<NodeRoutingStats>,
+}
+
+impl RoutingReport {
+    /// `blocks` are consecutive longest chain blocks, oldest first. Blocks should be generated before calling this,
+    /// since the fees of the transactions are needed to find the winning routers.
+    pub fn generate(blocks: &[Block]) -> RoutingReport {
+        let mut nodes: AHashMap<SaitoPublicKey, NodeRoutingStats> = Default::default();
+        let mut report = RoutingReport {
+            from_block_id: blocks.first().map(|block| block.id).unwrap_or_default(),
+            to_block_id: blocks.last().map(|block| block.id).unwrap_or_default(),
+            ..Default::default()
+        };
+
+        for (index, block) in blocks.iter().enumerate() {
+            for transaction in block.transactions.iter() {
+                if transaction.is_fee_transaction() {
+                    Self::add_payouts(&mut nodes, transaction);
+                    continue;
+                }
+                if transaction.is_golden_ticket() || transaction.is_atr_transaction() {
+                    continue;
+                }
+                report.transaction_count += 1;
+
+                let path = transaction.get_path();
+                if path.is_empty() {
+                    continue;
+                }
+                report.routed_transaction_count += 1;
+                let signer = transaction.inputs.first().map(|slip| slip.public_key);
+                if Some(path[0].from) != signer || !transaction.validate_routing_path() {
+                    report.invalid_path_count += 1;
+                    continue;
+                }
+
+                let mut routing_work_this_hop = transaction.total_fees;
+                for (hop_index, hop) in path.iter().enumerate() {
+                    if hop_index > 0 {
+                        routing_work_this_hop /= 2;
+                    }
+                    let node = nodes.entry(hop.to).or_default();
+                    node.relayed_transactions += 1;
+                    node.routing_work += routing_work_this_hop;
+                }
+            }
+
+            if index > 0 {
+                report.verify_router_payout(&blocks[index - 1], block);
+            }
+        }
+
+        report.nodes = nodes
+            .into_iter()
+            .map(|(public_key, mut node)| {
+                node.public_key = public_key;
+                node
+            })
+            .collect();
+        report.nodes.sort_by(|a, b| {
+            b.routing_work
+                .cmp(&a.routing_work)
+                .then(a.public_key.cmp(&b.public_key))
+        });
+        report
+    }
+
+    fn add_payouts(
+        nodes: &mut AHashMap<SaitoPublicKey, NodeRoutingStats>,
+        fee_transaction: &Transaction,
+    ) {
+        for output in fee_transaction.outputs.iter() {
+            let node = nodes.entry(output.public_key).or_default();
+            match output.slip_type {
+                SlipType::RouterOutput => {
+                    node.router_payouts += 1;
+                    node.router_payout_amount += output.amount;
+                }
+                SlipType::MinerOutput => {
+                    node.miner_payouts += 1;
+                    node.miner_payout_amount += output.amount;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The first router in the fee transaction is paid for the previous block, selected with the golden ticket's
+    /// random number. Staking payouts for the older blocks are not checked.
+    fn verify_router_payout(&mut self, previous_block: &Block, block: &Block) {
+        let golden_ticket = block
+            .transactions
+            .iter()
+            .find(|transaction| transaction.is_golden_ticket());
+        let fee_transaction = block
+            .transactions
+            .iter()
+            .find(|transaction| transaction.is_fee_transaction());
+        let (golden_ticket, fee_transaction) = match (golden_ticket, fee_transaction) {
+            (Some(golden_ticket), Some(fee_transaction)) => (golden_ticket, fee_transaction),
+            _ => return,
+        };
+        if previous_block.transactions.is_empty() {
+            return;
+        }
+
+        let golden_ticket = GoldenTicket::deserialize_from_net(&golden_ticket.message);
+        let expected_router =
+            previous_block.find_winning_router(hash(golden_ticket.random.as_ref()));
+        if expected_router == [0; 33] {
+            // fees of the previous block are burnt
+            return;
+        }
+        let router = fee_transaction
+            .outputs
+            .iter()
+            .find(|output| output.slip_type == SlipType::RouterOutput)
+            .map(|output| output.public_key);
+        if router == Some(expected_router) {
+            self.verified_router_payouts += 1;
+        } else {
+            self.mismatched_router_payouts.push(block.id);
+        }
+    }
+
+    pub fn serialize(&self, format: UtxoReportFormat) -> Vec<u8> {
+        match format {
+            UtxoReportFormat::Csv => self.to_csv(),
+            UtxoReportFormat::Json => self.to_json(),
+        }
+        .into_bytes()
+    }
+
+    /// summary metrics followed by a row per node
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        writeln!(csv, "metric,value").unwrap();
+        writeln!(csv, "from_block_id,{}", self.from_block_id).unwrap();
+        writeln!(csv, "to_block_id,{}", self.to_block_id).unwrap();
+        writeln!(csv, "transaction_count,{}", self.transaction_count).unwrap();
+        writeln!(
+            csv,
+            "routed_transaction_count,{}",
+            self.routed_transaction_count
+        )
+        .unwrap();
+        writeln!(csv, "invalid_path_count,{}", self.invalid_path_count).unwrap();
+        writeln!(
+            csv,
+            "verified_router_payouts,{}",
+            self.verified_router_payouts
+        )
+        .unwrap();
+        writeln!(
+            csv,
+            "mismatched_router_payouts,{}",
+            self.mismatched_router_payouts.len()
+        )
+        .unwrap();
+        writeln!(csv).unwrap();
+        writeln!(
+            csv,
+            "public_key,relayed_transactions,routing_work,router_payouts,router_payout_amount,miner_payouts,miner_payout_amount"
+        )
+        .unwrap();
+        for node in self.nodes.iter() {
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{}",
+                hex::encode(node.public_key),
+                node.relayed_transactions,
+                node.routing_work,
+                node.router_payouts,
+                node.router_payout_amount,
+                node.miner_payouts,
+                node.miner_payout_amount
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    /// amounts are written as strings since they can exceed the integer range of json parsers
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                format!(
+                    "{{\"public_key\":\"{}\",\"relayed_transactions\":{},\"routing_work\":\"{}\",\
+                    \"router_payouts\":{},\"router_payout_amount\":\"{}\",\"miner_payouts\":{},\
+                    \"miner_payout_amount\":\"{}\"}}",
+                    hex::encode(node.public_key),
+                    node.relayed_transactions,
+                    node.routing_work,
+                    node.router_payouts,
+                    node.router_payout_amount,
+                    node.miner_payouts,
+                    node.miner_payout_amount
+                )
+            })
+            .collect();
+        let mismatched: Vec<String> = self
+            .mismatched_router_payouts
+            .iter()
+            .map(|block_id| block_id.to_string())
+            .collect();
+        format!(
+            "{{\"from_block_id\":{},\"to_block_id\":{},\"transaction_count\":{},\"routed_transaction_count\":{},\
+            \"invalid_path_count\":{},\"verified_router_payouts\":{},\"mismatched_router_payouts\":[{}],\
+            \"nodes\":[{}]}}",
+            self.from_block_id,
+            self.to_block_id,
+            self.transaction_count,
+            self.routed_transaction_count,
+            self.invalid_path_count,
+            self.verified_router_payouts,
+            mismatched.join(","),
+            nodes.join(",")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::block::Block;
+    use crate::core::data::crypto::{generate_keys, hash};
+    use crate::core::data::golden_ticket::GoldenTicket;
+    use crate::core::data::routing_report::RoutingReport;
+    use crate::core::data::slip::{Slip, SlipType};
+    use crate::core::data::transaction::{Transaction, TransactionType};
+
+    #[test]
+    fn routing_report_test() {
+        let (sender, sender_private_key) = generate_keys();
+        let (router_1, router_1_private_key) = generate_keys();
+        let (router_2, router_2_private_key) = generate_keys();
+
+        let mut transaction = Transaction::default();
+        let mut input = Slip::default();
+        input.public_key = sender;
+        input.amount = 1000;
+        transaction.add_input(input);
+        transaction.sign(&sender_private_key);
+        transaction.add_hop(&sender_private_key, &sender, &router_1);
+        transaction.add_hop(&router_1_private_key, &router_1, &router_2);
+
+        // second hop doesn't continue from the first one
+        let mut invalid_transaction = Transaction::default();
+        let mut input = Slip::default();
+        input.public_key = sender;
+        input.amount = 10;
+        invalid_transaction.add_input(input);
+        invalid_transaction.sign(&sender_private_key);
+        invalid_transaction.add_hop(&sender_private_key, &sender, &router_1);
+        invalid_transaction.add_hop(&router_2_private_key, &router_2, &sender);
+
+        let mut block_1 = Block::new();
+        block_1.id = 1;
+        block_1.transactions = vec![transaction, invalid_transaction];
+        block_1.generate();
+
+        let random = [5; 32];
+        let expected_router = block_1.find_winning_router(hash(random.as_ref()));
+        let mut golden_ticket = Transaction::default();
+        golden_ticket.transaction_type = TransactionType::GoldenTicket;
+        golden_ticket.message = GoldenTicket::new([0; 32], random, sender).serialize_for_net();
+        let mut fee_transaction = Transaction::default();
+        fee_transaction.transaction_type = TransactionType::Fee;
+        let mut output = Slip::default();
+        output.public_key = expected_router;
+        output.amount = 505;
+        output.slip_type = SlipType::RouterOutput;
+        fee_transaction.add_output(output);
+
+        let mut block_2 = Block::new();
+        block_2.id = 2;
+        block_2.transactions = vec![golden_ticket, fee_transaction];
+        block_2.generate();
+
+        let report = RoutingReport::generate(&[block_1, block_2]);
+        assert_eq!(report.from_block_id, 1);
+        assert_eq!(report.to_block_id, 2);
+        assert_eq!(report.transaction_count, 2);
+        assert_eq!(report.routed_transaction_count, 2);
+        assert_eq!(report.invalid_path_count, 1);
+        assert_eq!(report.verified_router_payouts, 1);
+        assert!(report.mismatched_router_payouts.is_empty());
+
+        assert_eq!(report.nodes[0].public_key, router_1);
+        assert_eq!(report.nodes[0].relayed_transactions, 1);
+        assert_eq!(report.nodes[0].routing_work, 1000);
+        assert_eq!(report.nodes[1].public_key, router_2);
+        assert_eq!(report.nodes[1].routing_work, 500);
+        let paid_router = report
+            .nodes
+            .iter()
+            .find(|node| node.public_key == expected_router)
+            .unwrap();
+        assert_eq!(paid_router.router_payouts, 1);
+        assert_eq!(paid_router.router_payout_amount, 505);
+
+        let csv = report.to_csv();
+        assert!(csv.contains("invalid_path_count,1\n"));
+        assert!(csv.contains(&format!("{},1,1000,", hex::encode(router_1))));
+        let json = report.to_json();
+        assert!(json.starts_with("{\"from_block_id\":1,\"to_block_id\":2,"));
+        assert!(json.contains("\"mismatched_router_payouts\":[]"));
+    }
+}
@@ -113,6 +113,11 @@ impl Slip {
         res[0..66].try_into().unwrap()
     }
 
+    /// amount is stored in the last 16 bytes of the utxoset key
+    pub fn get_amount_from_utxoset_key(key: &SaitoUTXOSetKey) -> Currency {
+        Currency::from_be_bytes(key[50..66].try_into().unwrap())
+    }
+
     // #[tracing::instrument(level = "info", skip_all)]
     pub fn on_chain_reorganization(&self, utxoset: &mut UtxoSet, _lc: bool, spendable: bool) {
         if self.amount > 0 {
@@ -0,0 +1,136 @@
+use crate::common::defs::{SaitoHash, Timestamp};
+
+/// Change in the chain tip status reported by the watchdog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainTipStatus {
+    /// no new longest chain block within the threshold. node might be partitioned from the network
+    Stale { time_since_last_tip: Timestamp },
+    /// a new longest chain block was received after the chain was marked as stale
+    Recovered { stale_duration: Timestamp },
+}
+
+/// Tracks the time since the longest chain tip last changed, to detect nodes sitting on a stale tip
+#[derive(Debug)]
+pub struct StaleChainWatchdog {
+    // 0 means the watchdog is disabled
+    threshold_in_ms: Timestamp,
+    last_tip: SaitoHash,
+    last_tip_changed_at: Timestamp,
+    last_alert_at: Option<Timestamp>,
+    pub stale_alert_count: u64,
+}
+
+impl StaleChainWatchdog {
+    pub fn new(threshold_in_ms: Timestamp) -> StaleChainWatchdog {
+        StaleChainWatchdog {
+            threshold_in_ms,
+            last_tip: [0; 32],
+            last_tip_changed_at: 0,
+            last_alert_at: None,
+            stale_alert_count: 0,
+        }
+    }
+
+    pub fn set_threshold(&mut self, threshold_in_ms: Timestamp) {
+        self.threshold_in_ms = threshold_in_ms;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.threshold_in_ms != 0
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.last_alert_at.is_some()
+    }
+
+    pub fn get_last_tip(&self) -> SaitoHash {
+        self.last_tip
+    }
+
+    pub fn get_time_since_last_tip(&self, current_time: Timestamp) -> Timestamp {
+        current_time.saturating_sub(self.last_tip_changed_at)
+    }
+
+    /// Records the current longest chain tip. Returns `Recovered` if the chain was stale before.
+    pub fn on_chain_tip(
+        &mut self,
+        tip: SaitoHash,
+        current_time: Timestamp,
+    ) -> Option<ChainTipStatus> {
+        if tip == self.last_tip {
+            return None;
+        }
+        let stale_duration = self.get_time_since_last_tip(current_time);
+        self.last_tip = tip;
+        self.last_tip_changed_at = current_time;
+        if self.last_alert_at.take().is_some() {
+            return Some(ChainTipStatus::Recovered { stale_duration });
+        }
+        None
+    }
+
+    /// Returns `Stale` when the tip hasn't changed within the threshold. While the chain stays stale this
+    /// is repeated once per threshold period, so callers can retry their recovery actions.
+    pub fn check(&mut self, current_time: Timestamp) -> Option<ChainTipStatus> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let last_checkpoint = self.last_alert_at.unwrap_or(self.last_tip_changed_at);
+        if current_time.saturating_sub(last_checkpoint) < self.threshold_in_ms {
+            return None;
+        }
+        self.last_alert_at = Some(current_time);
+        self.stale_alert_count += 1;
+        Some(ChainTipStatus::Stale {
+            time_since_last_tip: self.get_time_since_last_tip(current_time),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::stale_chain_watchdog::{ChainTipStatus, StaleChainWatchdog};
+
+    #[test]
+    fn stale_chain_detection_test() {
+        let mut watchdog = StaleChainWatchdog::new(1000);
+        watchdog.on_chain_tip([1; 32], 100);
+
+        assert_eq!(watchdog.check(1099), None);
+        assert_eq!(
+            watchdog.check(1100),
+            Some(ChainTipStatus::Stale {
+                time_since_last_tip: 1000
+            })
+        );
+        assert!(watchdog.is_stale());
+        // alert is not repeated until another threshold period passes
+        assert_eq!(watchdog.check(1500), None);
+        assert_eq!(
+            watchdog.check(2100),
+            Some(ChainTipStatus::Stale {
+                time_since_last_tip: 2000
+            })
+        );
+        assert_eq!(watchdog.stale_alert_count, 2);
+
+        // same tip doesn't reset the timer
+        assert_eq!(watchdog.on_chain_tip([1; 32], 2200), None);
+        assert_eq!(
+            watchdog.on_chain_tip([2; 32], 2500),
+            Some(ChainTipStatus::Recovered {
+                stale_duration: 2400
+            })
+        );
+        assert!(!watchdog.is_stale());
+        assert_eq!(watchdog.check(3000), None);
+    }
+
+    #[test]
+    fn disabled_watchdog_test() {
+        let mut watchdog = StaleChainWatchdog::new(0);
+        watchdog.on_chain_tip([1; 32], 100);
+        assert_eq!(watchdog.check(1_000_000), None);
+        assert!(!watchdog.is_stale());
+    }
+}
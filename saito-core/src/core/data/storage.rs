@@ -1,11 +1,20 @@
-use std::sync::Arc;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
 
-use tokio::sync::RwLock;
-use tracing::{debug, error, info};
+use ahash::{AHashMap, AHashSet};
+use tracing::{debug, error, info, trace, warn};
 
-use crate::common::defs::{push_lock, BLOCK_FILE_EXTENSION, LOCK_ORDER_MEMPOOL};
+use crate::common::defs::{
+    push_lock, SaitoHash, BLOCK_FILE_EXTENSION, LOCK_ORDER_MEMPOOL, TEMP_FILE_EXTENSION,
+};
 use crate::common::interface_io::InterfaceIO;
-use crate::core::data::block::{Block, BlockType};
+use crate::common::runtime;
+use crate::common::runtime::{Notify, RwLock, Sender};
+use crate::core::data::block::Block;
+use crate::core::data::crypto::hash;
+use crate::core::data::format_version::{
+    get_format_envelope, read_format_envelope, CURRENT_FORMAT_VERSION, LEGACY_FORMAT_VERSION,
+};
 use crate::core::data::mempool::Mempool;
 use crate::core::data::slip::Slip;
 use crate::lock_for_write;
@@ -13,11 +22,107 @@ use crate::lock_for_write;
 #[derive(Debug)]
 pub struct Storage {
     pub io_interface: Box<dyn InterfaceIO + Send + Sync>,
+    block_writer: Option<BlockWriter>,
+    // blocks moved to the corrupt directory at startup, which should be fetched again from peers
+    quarantined_blocks: Vec<SaitoHash>,
+    // files of the blocks loaded at startup by the block hash. cleared after the blocks are added to the blockchain
+    loaded_block_files: AHashMap<SaitoHash, String>,
+}
+
+#[derive(Debug)]
+struct BlockWriteRequest {
+    filename: String,
+    buffer: Vec<u8>,
+}
+
+/// Handle to the background task which writes blocks to disk.
+#[derive(Debug)]
+struct BlockWriter {
+    sender: Sender<BlockWriteRequest>,
+    pending_writes: PendingBlockWrites,
+}
+
+/// Filenames of the blocks which are queued in the block writer but not yet written. Shared with the tasks serving the
+/// block files to peers, since a block is announced before its file is written
+#[derive(Debug, Clone)]
+pub struct PendingBlockWrites {
+    filenames: Arc<Mutex<AHashSet<String>>>,
+    write_completed: Arc<Notify>,
+}
+
+impl PendingBlockWrites {
+    pub fn new() -> PendingBlockWrites {
+        PendingBlockWrites {
+            filenames: Arc::new(Mutex::new(AHashSet::new())),
+            write_completed: Arc::new(Notify::new()),
+        }
+    }
+
+    fn insert(&self, filename: String) {
+        self.filenames.lock().unwrap().insert(filename);
+    }
+
+    fn remove(&self, filename: &str) {
+        self.filenames.lock().unwrap().remove(filename);
+        self.write_completed.notify_waiters();
+    }
+
+    pub fn len(&self) -> usize {
+        self.filenames.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filenames.lock().unwrap().is_empty()
+    }
+
+    /// waits until the given block file is written to disk, if it's still in the write queue
+    pub async fn wait_for_write(&self, filename: &str) {
+        loop {
+            // need to register before checking the pending list to not miss the notification
+            let notified = self.write_completed.notified();
+            if !self.filenames.lock().unwrap().contains(filename) {
+                return;
+            }
+            trace!("waiting for block : {:?} to be written to disk", filename);
+            notified.await;
+        }
+    }
+
+    /// Same as `wait_for_write`, for callers which only know the block hash
+    pub async fn wait_for_block_write(&self, block_hash: &SaitoHash) {
+        let filename = self
+            .filenames
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|filename| {
+                Storage::get_block_hash_from_filename(filename).as_ref() == Some(block_hash)
+            })
+            .cloned();
+        if let Some(filename) = filename {
+            self.wait_for_write(filename.as_str()).await;
+        }
+    }
+}
+
+impl Default for PendingBlockWrites {
+    fn default() -> Self {
+        PendingBlockWrites::new()
+    }
 }
 
 pub const ISSUANCE_FILE_PATH: &'static str = "./data/issuance/issuance";
 pub const EARLYBIRDS_FILE_PATH: &'static str = "./data/issuance/earlybirds";
 pub const DEFAULT_FILE_PATH: &'static str = "./data/issuance/default";
+pub const BLOCK_WRITE_QUEUE_SIZE: usize = 100;
+/// blocks larger than this are written to disk in chunks instead of serializing the whole block at once
+pub const BLOCK_STREAMING_THRESHOLD: usize = 16 * 1024 * 1024;
+pub const BLOCK_STREAM_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+pub const BLOCK_FILE_CHECKSUM_MAGIC: [u8; 4] = *b"SCHK";
+/// [magic - 4 bytes][hash of the serialized block - 32 bytes]
+pub const BLOCK_FILE_FOOTER_SIZE: usize = 36;
+/// block files failing verification are moved here (relative to the block directory)
+pub const CORRUPT_BLOCKS_DIR: &'static str = "corrupt/";
 
 pub struct StorageConfigurer {}
 
@@ -31,14 +136,73 @@ pub fn configure_storage() -> String {
 
 impl Storage {
     pub fn new(io_interface: Box<dyn InterfaceIO + Send + Sync>) -> Storage {
-        Storage { io_interface }
+        Storage {
+            io_interface,
+            block_writer: None,
+            quarantined_blocks: vec![],
+            loaded_block_files: Default::default(),
+        }
+    }
+
+    /// Starts a background task to write blocks to disk, so the caller doesn't have to wait on disk latency.
+    /// Queue is bounded so a slow disk will eventually apply backpressure to the block writing thread. The queued
+    /// blocks are tracked in `pending_writes`, so the files are not read before they are written
+    pub fn start_block_writer(
+        &mut self,
+        mut io_interface: Box<dyn InterfaceIO + Send + Sync>,
+        queue_size: usize,
+        pending_writes: PendingBlockWrites,
+    ) {
+        assert!(self.block_writer.is_none());
+        let (sender, mut receiver) = runtime::channel::<BlockWriteRequest>(queue_size);
+
+        let pending = pending_writes.clone();
+        runtime::spawn(async move {
+            while let Some(request) = receiver.recv().await {
+                let result = io_interface
+                    .write_value(request.filename.clone(), request.buffer)
+                    .await;
+                if result.is_err() {
+                    error!(
+                        "failed writing block : {:?} to disk : {:?}",
+                        request.filename,
+                        result.err().unwrap()
+                    );
+                } else {
+                    trace!("block : {:?} written to disk", request.filename);
+                }
+                pending.remove(&request.filename);
+            }
+            info!("block writer stopped");
+        });
+
+        self.block_writer = Some(BlockWriter {
+            sender,
+            pending_writes,
+        });
+    }
+
+    /// waits until the given block file is written to disk, if it's still in the write queue
+    pub async fn wait_for_block_write(&self, filename: &str) {
+        if let Some(writer) = self.block_writer.as_ref() {
+            writer.pending_writes.wait_for_write(filename).await;
+        }
+    }
+
+    pub fn get_pending_block_write_count(&self) -> usize {
+        match self.block_writer.as_ref() {
+            Some(writer) => writer.pending_writes.len(),
+            None => 0,
+        }
     }
     /// read from a path to a Vec<u8>
     #[tracing::instrument(level = "info", skip_all)]
     pub async fn read(&self, path: &str) -> std::io::Result<Vec<u8>> {
         let buffer = self.io_interface.read_value(path.to_string()).await;
         if buffer.is_err() {
-            todo!()
+            let error = buffer.err().unwrap();
+            warn!("failed reading file : {:?} : {:?}", path, error);
+            return Err(error);
         }
         let buffer = buffer.unwrap();
         Ok(buffer)
@@ -70,24 +234,261 @@ impl Storage {
             + hex::encode(block_hash).as_str()
             + BLOCK_FILE_EXTENSION
     }
+    /// Writes the block to disk and returns the file name. If a block writer is running the write is only queued, so
+    /// errors of queued writes are logged by the writer instead of returned
     #[tracing::instrument(level = "info", skip_all)]
-    pub async fn write_block_to_disk(&mut self, block: &Block) -> String {
-        let buffer = block.serialize_for_net(BlockType::Full);
+    pub async fn write_block_to_disk(&mut self, block: &Block) -> Result<String, Error> {
+        if block.get_serialized_size() > BLOCK_STREAMING_THRESHOLD {
+            return self.stream_block_to_disk(block).await;
+        }
+        let mut buffer = block.serialize_for_disk();
+        let footer = Self::create_block_file_footer(&hash(&buffer));
+        buffer.extend(footer);
+        let filename = self.generate_block_filename(block);
+
+        if let Some(writer) = self.block_writer.as_ref() {
+            writer.pending_writes.insert(filename.clone());
+            let result = writer
+                .sender
+                .send(BlockWriteRequest {
+                    filename: filename.clone(),
+                    buffer,
+                })
+                .await;
+            match result {
+                Ok(_) => return Ok(filename),
+                Err(error) => {
+                    // the writer task stopped, so the block is written in this task instead
+                    error!(
+                        "block writer stopped. writing block : {:?} directly",
+                        filename
+                    );
+                    writer.pending_writes.remove(filename.as_str());
+                    buffer = error.0.buffer;
+                }
+            }
+        }
+
+        self.io_interface
+            .write_value(filename.clone(), buffer)
+            .await?;
+        Ok(filename)
+    }
+
+    /// Writes the block to disk in chunks of serialized transactions, so a large block is not
+    /// kept in memory twice. The write is done in the calling task even if a block writer is running.
+    #[tracing::instrument(level = "info", skip_all)]
+    pub async fn stream_block_to_disk(&mut self, block: &Block) -> Result<String, Error> {
         let filename = self.generate_block_filename(block);
+        debug!(
+            "streaming block : {:?} to disk with size : {:?}",
+            filename,
+            block.get_serialized_size()
+        );
+        self.wait_for_block_write(filename.as_str()).await;
+        // streamed to a temporary file and renamed once complete, so a crash doesn't leave a partial block file
+        let temp_filename = filename.clone() + TEMP_FILE_EXTENSION;
+        if self
+            .io_interface
+            .is_existing_file(temp_filename.clone())
+            .await
+        {
+            self.io_interface
+                .remove_value(temp_filename.clone())
+                .await?;
+        }
 
+        // checksum is calculated while streaming since the whole block is not serialized at once
+        let mut hasher = blake3::Hasher::new();
+        let mut chunk = get_format_envelope(CURRENT_FORMAT_VERSION);
+        chunk.extend(block.serialize_header_for_full_block());
+        for tx in block.transactions.iter() {
+            chunk.extend(tx.serialize_for_net());
+            if chunk.len() >= BLOCK_STREAM_CHUNK_SIZE {
+                hasher.update(&chunk);
+                let result = self
+                    .io_interface
+                    .append_value(temp_filename.clone(), std::mem::take(&mut chunk))
+                    .await;
+                if let Err(error) = result {
+                    error!(
+                        "streaming block : {:?} to disk failed : {:?}",
+                        filename, error
+                    );
+                    let _ = self.io_interface.remove_value(temp_filename).await;
+                    return Err(error);
+                }
+            }
+        }
+        hasher.update(&chunk);
+        chunk.extend(Self::create_block_file_footer(hasher.finalize().as_bytes()));
         let result = self
             .io_interface
-            .write_value(filename.clone(), buffer)
+            .append_value(temp_filename.clone(), chunk)
+            .await;
+        if let Err(error) = result {
+            error!(
+                "writing checksum of block : {:?} to disk failed : {:?}",
+                filename, error
+            );
+            let _ = self.io_interface.remove_value(temp_filename).await;
+            return Err(error);
+        }
+        let result = self
+            .io_interface
+            .rename_value(temp_filename.clone(), filename.clone())
+            .await;
+        if let Err(error) = result {
+            error!(
+                "renaming temporary file of block : {:?} failed : {:?}",
+                filename, error
+            );
+            let _ = self.io_interface.remove_value(temp_filename).await;
+            return Err(error);
+        }
+        Ok(filename)
+    }
+
+    pub(crate) fn create_block_file_footer(block_hash: &SaitoHash) -> Vec<u8> {
+        [BLOCK_FILE_CHECKSUM_MAGIC.as_slice(), block_hash.as_slice()].concat()
+    }
+
+    /// Verifies the contents of a block file and returns the length of the serialized block without the checksum footer.
+    /// Files written before checksums were added don't have a footer, so only their structure can be verified. Files
+    /// written before the format versions were added don't have the format envelope and are read as the legacy format.
+    pub fn verify_block_file(buffer: &[u8]) -> Result<usize, Error> {
+        let mut length = buffer.len();
+        if length >= BLOCK_FILE_FOOTER_SIZE
+            && buffer[length - BLOCK_FILE_FOOTER_SIZE..length - 32] == BLOCK_FILE_CHECKSUM_MAGIC
+        {
+            length -= BLOCK_FILE_FOOTER_SIZE;
+            if hash(&buffer[..length]) != buffer[length + 4..] {
+                warn!("block file checksum doesn't match");
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+        } else {
+            trace!("block file doesn't have a checksum");
+        }
+        let (version, block_buffer) = read_format_envelope(&buffer[..length])?;
+        let result = if version == LEGACY_FORMAT_VERSION {
+            Block::validate_legacy_net_buffer(block_buffer)
+        } else {
+            Block::validate_net_buffer(block_buffer)
+        };
+        if result.is_err() {
+            warn!(
+                "block file is truncated or has invalid structure. length : {:?}",
+                length
+            );
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        Ok(length)
+    }
+
+    /// Moves a corrupted block file out of the block directory so it's not loaded again
+    async fn quarantine_block_file(
+        io_interface: &mut Box<dyn InterfaceIO + Send + Sync>,
+        file_name: &str,
+        buffer: Vec<u8>,
+    ) {
+        let block_dir = io_interface.get_block_dir();
+        let result = io_interface
+            .write_value(block_dir.clone() + CORRUPT_BLOCKS_DIR + file_name, buffer)
+            .await;
+        if result.is_err() {
+            error!(
+                "failed copying corrupted block file : {:?} : {:?}",
+                file_name,
+                result.err().unwrap()
+            );
+        }
+        let result = io_interface.remove_value(block_dir + file_name).await;
+        if result.is_err() {
+            error!(
+                "failed removing corrupted block file : {:?} : {:?}",
+                file_name,
+                result.err().unwrap()
+            );
+        }
+    }
+
+    /// Extracts the block hash from a block file name ([timestamp]-[hash].sai)
+    pub fn get_block_hash_from_filename(file_name: &str) -> Option<SaitoHash> {
+        let (_, hash) = file_name
+            .strip_suffix(BLOCK_FILE_EXTENSION)?
+            .rsplit_once('-')?;
+        hex::decode(hash).ok()?.try_into().ok()
+    }
+
+    /// Moves the file of a block loaded at startup which failed validation to the corrupt directory, so the block is
+    /// fetched again from peers instead of failing on every restart (e.g. a file partially written before a power
+    /// loss). Returns false if the block wasn't loaded from disk.
+    pub async fn quarantine_invalid_block(&mut self, block_hash: &SaitoHash) -> bool {
+        let file_name = match self.loaded_block_files.remove(block_hash) {
+            Some(file_name) => file_name,
+            None => return false,
+        };
+        warn!(
+            "block file : {:?} failed validation. moving it to : {:?}",
+            file_name, CORRUPT_BLOCKS_DIR
+        );
+        let result = self
+            .io_interface
+            .read_value(self.io_interface.get_block_dir() + file_name.as_str())
             .await;
         if result.is_err() {
-            todo!()
+            error!(
+                "failed reading invalid block file : {:?} : {:?}",
+                file_name,
+                result.err().unwrap()
+            );
+            return false;
         }
-        filename
+        Self::quarantine_block_file(&mut self.io_interface, file_name.as_str(), result.unwrap())
+            .await;
+        self.quarantined_blocks.push(*block_hash);
+        true
+    }
+
+    /// Called after the blocks loaded at startup are added to the blockchain. Blocks failing validation later are
+    /// not from the disk
+    pub fn clear_loaded_block_files(&mut self) {
+        self.loaded_block_files = Default::default();
+    }
+
+    /// Returns the hashes of the blocks quarantined at startup and clears the list
+    pub fn take_quarantined_blocks(&mut self) -> Vec<SaitoHash> {
+        std::mem::take(&mut self.quarantined_blocks)
+    }
+
+    pub fn has_quarantined_blocks(&self) -> bool {
+        !self.quarantined_blocks.is_empty()
+    }
+
+    /// Reads a chunk of a block file, so it can be served to peers without loading the whole block
+    pub async fn read_block_chunk(
+        &self,
+        filename: String,
+        offset: u64,
+        length: u64,
+    ) -> Result<Vec<u8>, std::io::Error> {
+        self.wait_for_block_write(filename.as_str()).await;
+        self.io_interface
+            .read_value_chunk(filename, offset, length)
+            .await
     }
 
     #[tracing::instrument(level = "info", skip_all)]
     pub async fn load_blocks_from_disk(&mut self, mempool: Arc<RwLock<Mempool>>) {
         info!("loading blocks from disk");
+        match self.io_interface.remove_temp_files().await {
+            Ok(0) => {}
+            Ok(count) => warn!(
+                "removed {:?} temporary files left by interrupted block writes",
+                count
+            ),
+            Err(error) => warn!("failed removing temporary block files : {:?}", error),
+        }
         let file_names = self.io_interface.load_block_file_list().await;
 
         if file_names.is_err() {
@@ -98,62 +499,152 @@ impl Storage {
         file_names.sort();
         debug!("block file names : {:?}", file_names);
 
-        let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+        let (sender, mut receiver) = runtime::channel(10);
 
-        let mut waiting_count = file_names.len();
-        let handle = tokio::spawn(async move {
+        // blocks are deserialized while the next files are being read. both run on the current task so this doesn't depend on the runtime
+        let consumer = async move {
             let (mut mempool, _mempool_) = lock_for_write!(mempool, LOCK_ORDER_MEMPOOL);
 
-            loop {
-                if waiting_count == 0 {
-                    break;
-                }
-                waiting_count -= 1;
-                // TODO : if this fails we need to make sure `waiting_count` is reduced correctly. or should terminate the node
-                let buffer = receiver.recv().await;
-                if buffer.is_none() {
-                    continue;
-                }
-                let buffer = buffer.unwrap();
-                let mut block = Block::deserialize_from_net(&buffer);
+            // sender is dropped when all the files are read
+            while let Some(buffer) = receiver.recv().await {
+                let mut block = Block::deserialize_from_disk(&buffer);
                 block.generate();
                 info!("block : {:?} loaded from disk", hex::encode(block.hash));
                 mempool.add_block(block);
             }
-        });
+        };
+
+        let io_interface = &mut self.io_interface;
+        // files are verified before deserializing, so a corrupted file doesn't stop the node from starting
+        let producer = async move {
+            let mut quarantined_blocks = vec![];
+            let mut loaded_block_files = vec![];
+            for file_name in file_names {
+                info!("loading file : {:?}", file_name);
+                let result = io_interface
+                    .read_value(io_interface.get_block_dir() + file_name.as_str())
+                    .await;
+                if result.is_err() {
+                    error!(
+                        "failed reading block file : {:?} : {:?}",
+                        file_name,
+                        result.err().unwrap()
+                    );
+                    continue;
+                }
+                let mut buffer: Vec<u8> = result.unwrap();
+                match Self::verify_block_file(&buffer) {
+                    Ok(length) => {
+                        info!("file : {:?} loaded", file_name);
+                        buffer.truncate(length);
+                        sender.send(buffer).await.unwrap();
+                        if let Some(block_hash) = Self::get_block_hash_from_filename(&file_name) {
+                            loaded_block_files.push((block_hash, file_name));
+                        }
+                    }
+                    Err(_) => {
+                        warn!(
+                            "block file : {:?} is corrupted. moving it to : {:?}",
+                            file_name, CORRUPT_BLOCKS_DIR
+                        );
+                        Self::quarantine_block_file(io_interface, file_name.as_str(), buffer).await;
+                        if let Some(block_hash) = Self::get_block_hash_from_filename(&file_name) {
+                            quarantined_blocks.push(block_hash);
+                        }
+                    }
+                }
+            }
+            (quarantined_blocks, loaded_block_files)
+        };
 
+        let ((quarantined_blocks, loaded_block_files), _) = futures::join!(producer, consumer);
+        if !quarantined_blocks.is_empty() {
+            warn!(
+                "{:?} corrupted block files quarantined. they will be fetched from peers",
+                quarantined_blocks.len()
+            );
+        }
+        self.quarantined_blocks.extend(quarantined_blocks);
+        self.loaded_block_files.extend(loaded_block_files);
+
+        info!("loading blocks to mempool completed");
+    }
+
+    /// Reads all the block files for reindexing the chain, sorted by the block id. Corrupted files are quarantined
+    /// as when loading the blocks at startup
+    pub async fn load_blocks_for_reindex(&mut self) -> Vec<Block> {
+        let mut file_names = match self.io_interface.load_block_file_list().await {
+            Ok(file_names) => file_names,
+            Err(error) => {
+                error!("loading block file list failed : {:?}", error);
+                return vec![];
+            }
+        };
+        file_names.sort();
+
+        let mut blocks = vec![];
         for file_name in file_names {
-            info!("loading file : {:?}", file_name);
             let result = self
                 .io_interface
                 .read_value(self.io_interface.get_block_dir() + file_name.as_str())
                 .await;
-            if result.is_err() {
-                todo!()
+            let mut buffer = match result {
+                Ok(buffer) => buffer,
+                Err(error) => {
+                    error!("failed reading block file : {:?} : {:?}", file_name, error);
+                    continue;
+                }
+            };
+            match Self::verify_block_file(&buffer) {
+                Ok(length) => {
+                    buffer.truncate(length);
+                    let mut block = Block::deserialize_from_disk(&buffer);
+                    block.generate();
+                    if let Some(block_hash) = Self::get_block_hash_from_filename(&file_name) {
+                        self.loaded_block_files.insert(block_hash, file_name);
+                    }
+                    blocks.push(block);
+                }
+                Err(_) => {
+                    warn!(
+                        "block file : {:?} is corrupted. moving it to : {:?}",
+                        file_name, CORRUPT_BLOCKS_DIR
+                    );
+                    Self::quarantine_block_file(&mut self.io_interface, file_name.as_str(), buffer)
+                        .await;
+                    if let Some(block_hash) = Self::get_block_hash_from_filename(&file_name) {
+                        self.quarantined_blocks.push(block_hash);
+                    }
+                }
             }
-            info!("file : {:?} loaded", file_name);
-            let buffer: Vec<u8> = result.unwrap();
-            sender.send(buffer).await.unwrap();
         }
-
-        handle.await.unwrap();
-
-        info!("loading blocks to mempool completed");
+        blocks.sort_by_key(|block| block.id);
+        blocks
     }
 
     #[tracing::instrument(level = "info", skip_all)]
     pub async fn load_block_from_disk(&self, file_name: String) -> Result<Block, std::io::Error> {
         debug!("loading block {:?} from disk", file_name);
-        let result = self.io_interface.read_value(file_name).await;
+        self.wait_for_block_write(file_name.as_str()).await;
+        let result = self.io_interface.read_value(file_name.clone()).await;
         if result.is_err() {
-            todo!()
+            let error = result.err().unwrap();
+            warn!(
+                "failed reading block file : {:?} from disk : {:?}",
+                file_name, error
+            );
+            return Err(error);
         }
-        let buffer = result.unwrap();
-        Ok(Block::deserialize_from_net(&buffer))
+        let mut buffer = result.unwrap();
+        let length = Self::verify_block_file(&buffer)?;
+        buffer.truncate(length);
+        Ok(Block::deserialize_from_disk(&buffer))
     }
 
     #[tracing::instrument(level = "info", skip_all)]
     pub async fn delete_block_from_disk(&self, filename: String) -> bool {
+        // otherwise the queued write can recreate the file after deleting
+        self.wait_for_block_write(filename.as_str()).await;
         self.io_interface.remove_value(filename).await.is_ok()
     }
 
@@ -242,10 +733,19 @@ mod test {
     use tracing::info;
 
     use crate::common::defs::SaitoHash;
-    use crate::common::test_manager::test::{create_timestamp, TestManager};
-    use crate::core::data::block::Block;
+    use crate::common::keep_time::KeepTime;
+    use crate::common::test_io_handler::test::TestIOHandler;
+    use crate::common::test_manager::test::TestManager;
+    use crate::core::data::block::{Block, BlockType, LEGACY_BLOCK_HEADER_SIZE};
     use crate::core::data::blockchain::MAX_TOKEN_SUPPLY;
     use crate::core::data::crypto::{hash, verify};
+    use crate::core::data::format_version::{CURRENT_FORMAT_VERSION, FORMAT_ENVELOPE_SIZE};
+    use crate::core::data::slip::Slip;
+    use crate::core::data::storage::{
+        PendingBlockWrites, Storage, BLOCK_FILE_FOOTER_SIZE, BLOCK_WRITE_QUEUE_SIZE,
+        CORRUPT_BLOCKS_DIR,
+    };
+    use crate::core::data::transaction::Transaction;
 
     #[ignore]
     #[tokio::test]
@@ -269,12 +769,12 @@ mod test {
         let mut t = TestManager::new();
         t.initialize(100, 100_000_000).await;
 
-        let current_timestamp = create_timestamp();
+        let current_timestamp = t.clock.get_timestamp_in_ms();
 
         let mut block = Block::new();
         block.timestamp = current_timestamp;
 
-        let filename = t.storage.write_block_to_disk(&mut block).await;
+        let filename = t.storage.write_block_to_disk(&mut block).await.unwrap();
         tracing::trace!("block written to file : {}", filename);
         let retrieved_block = t.storage.load_block_from_disk(filename).await;
         let mut actual_retrieved_block = retrieved_block.unwrap();
@@ -283,6 +783,192 @@ mod test {
         assert_eq!(block.timestamp, actual_retrieved_block.timestamp);
     }
 
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn write_block_with_block_writer_test() {
+        let mut t = TestManager::new();
+        t.initialize(100, 100_000_000).await;
+        let pending_writes = PendingBlockWrites::new();
+        t.storage.start_block_writer(
+            Box::new(TestIOHandler::new()),
+            BLOCK_WRITE_QUEUE_SIZE,
+            pending_writes.clone(),
+        );
+
+        let mut block = Block::new();
+        block.timestamp = t.clock.get_timestamp_in_ms();
+        block.generate();
+
+        let filename = t.storage.write_block_to_disk(&mut block).await.unwrap();
+        // the tasks serving the block files only know the block hash
+        pending_writes.wait_for_block_write(&block.hash).await;
+        assert_eq!(pending_writes.len(), 0);
+        assert_eq!(t.storage.get_pending_block_write_count(), 0);
+        assert!(t.storage.file_exists(filename.as_str()).await);
+
+        let retrieved_block = t.storage.load_block_from_disk(filename).await;
+        let mut actual_retrieved_block = retrieved_block.unwrap();
+        actual_retrieved_block.generate();
+
+        assert_eq!(block.timestamp, actual_retrieved_block.timestamp);
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn stream_block_to_disk_test() {
+        let mut t = TestManager::new();
+        t.initialize(100, 100_000_000).await;
+
+        let mut block = Block::new();
+        block.timestamp = t.clock.get_timestamp_in_ms();
+        for i in 0..10 {
+            let mut tx = Transaction::default();
+            tx.timestamp = i;
+            tx.message = vec![i as u8; 100];
+            tx.add_output(Slip::default());
+            block.transactions.push(tx);
+        }
+        let buffer = block.serialize_for_net(BlockType::Full);
+        assert_eq!(block.get_serialized_size(), buffer.len());
+
+        let filename = t.storage.stream_block_to_disk(&block).await.unwrap();
+        let retrieved_block = t.storage.load_block_from_disk(filename.clone()).await;
+        let retrieved_block = retrieved_block.unwrap();
+        assert_eq!(retrieved_block.serialize_for_net(BlockType::Full), buffer);
+
+        let chunk = t.storage.read_block_chunk(filename.clone(), 10, 50).await;
+        assert_eq!(chunk.unwrap(), block.serialize_for_disk()[10..60].to_vec());
+
+        // writing again should replace the file instead of appending to it
+        t.storage.stream_block_to_disk(&block).await.unwrap();
+        let retrieved_block = t.storage.load_block_from_disk(filename).await;
+        assert_eq!(retrieved_block.unwrap().transactions.len(), 10);
+    }
+
+    #[test]
+    fn verify_block_file_test() {
+        let mut block = Block::new();
+        block.timestamp = 1000;
+        let mut tx = Transaction::default();
+        tx.message = vec![1; 100];
+        tx.add_output(Slip::default());
+        block.transactions.push(tx);
+        // files written before the format envelope was added have the legacy block header
+        let serialized = block.serialize_legacy_for_net(BlockType::Full).unwrap();
+
+        // files without a checksum are accepted if the structure is valid
+        assert_eq!(
+            Storage::verify_block_file(&serialized).unwrap(),
+            serialized.len()
+        );
+        assert!(Storage::verify_block_file(&serialized[..serialized.len() - 1]).is_err());
+        assert!(Storage::verify_block_file(&serialized[..100]).is_err());
+
+        let mut buffer = serialized.clone();
+        buffer.extend(Storage::create_block_file_footer(&hash(&serialized)));
+        assert_eq!(buffer.len(), serialized.len() + BLOCK_FILE_FOOTER_SIZE);
+        assert_eq!(
+            Storage::verify_block_file(&buffer).unwrap(),
+            serialized.len()
+        );
+
+        // bit rot inside the block data
+        buffer[LEGACY_BLOCK_HEADER_SIZE + 10] ^= 1;
+        assert!(Storage::verify_block_file(&buffer).is_err());
+        assert_eq!(
+            Block::deserialize_from_disk(&serialized).serialize_for_net(BlockType::Full),
+            block.serialize_for_net(BlockType::Full)
+        );
+        // the current layout is not valid without the envelope
+        assert!(Storage::verify_block_file(&block.serialize_for_net(BlockType::Full)).is_err());
+
+        // files written with the format envelope
+        let serialized = block.serialize_for_disk();
+        let mut buffer = serialized.clone();
+        buffer.extend(Storage::create_block_file_footer(&hash(&serialized)));
+        assert_eq!(
+            Storage::verify_block_file(&buffer).unwrap(),
+            serialized.len()
+        );
+        assert_eq!(
+            Block::deserialize_from_disk(&buffer[..serialized.len()])
+                .transactions
+                .len(),
+            1
+        );
+        let mut invalid = serialized.clone();
+        invalid[FORMAT_ENVELOPE_SIZE - 1] = CURRENT_FORMAT_VERSION + 1;
+        assert!(Storage::verify_block_file(&invalid).is_err());
+
+        assert_eq!(
+            Storage::get_block_hash_from_filename(
+                ("1000-".to_string() + hex::encode([7; 32]).as_str() + ".sai").as_str()
+            ),
+            Some([7; 32])
+        );
+        assert_eq!(Storage::get_block_hash_from_filename("1000-abc.sai"), None);
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn load_corrupted_block_from_disk_test() {
+        let mut t = TestManager::new();
+        t.initialize(100, 100_000_000).await;
+
+        let mut block = Block::new();
+        block.timestamp = t.clock.get_timestamp_in_ms();
+        let filename = t.storage.write_block_to_disk(&mut block).await.unwrap();
+        assert!(t
+            .storage
+            .load_block_from_disk(filename.clone())
+            .await
+            .is_ok());
+
+        let mut buffer = t.storage.read(filename.as_str()).await.unwrap();
+        buffer[20] ^= 1;
+        t.storage.write(buffer, filename.as_str()).await;
+        assert!(t
+            .storage
+            .load_block_from_disk(filename.clone())
+            .await
+            .is_err());
+
+        assert!(t.storage.delete_block_from_disk(filename.clone()).await);
+        assert!(t.storage.read(filename.as_str()).await.is_err());
+        assert!(t.storage.load_block_from_disk(filename).await.is_err());
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn quarantine_invalid_block_test() {
+        let mut t = TestManager::new();
+
+        let mut block = Block::new();
+        block.timestamp = t.clock.get_timestamp_in_ms();
+        block.generate();
+        let filename = t.storage.write_block_to_disk(&mut block).await.unwrap();
+        let block_dir = t.storage.io_interface.get_block_dir();
+        let file_name = filename
+            .strip_prefix(block_dir.as_str())
+            .unwrap()
+            .to_string();
+
+        // not loaded from disk
+        assert!(!t.storage.quarantine_invalid_block(&block.hash).await);
+
+        t.storage
+            .loaded_block_files
+            .insert(block.hash, file_name.clone());
+        assert!(t.storage.quarantine_invalid_block(&block.hash).await);
+        assert!(!t.storage.file_exists(filename.as_str()).await);
+        assert!(
+            t.storage
+                .file_exists((block_dir + CORRUPT_BLOCKS_DIR + file_name.as_str()).as_str())
+                .await
+        );
+        assert_eq!(t.storage.take_quarantined_blocks(), vec![block.hash]);
+    }
+
     // TODO : delete this test
     #[ignore]
     #[tokio::test]
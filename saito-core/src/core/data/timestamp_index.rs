@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use crate::common::defs::{SaitoHash, Timestamp};
+
+/// size of a single bucket in the timestamp index
+pub const TIMESTAMP_INDEX_BUCKET_SIZE_IN_MS: Timestamp = 60_000;
+
+/// Maps timestamp buckets to the hashes of the blocks created within them, so blocks can be queried
+/// by time without scanning the whole chain.
+#[derive(Debug)]
+pub struct TimestampIndex {
+    bucket_size: Timestamp,
+    buckets: BTreeMap<Timestamp, Vec<(Timestamp, SaitoHash)>>,
+    entry_count: usize,
+}
+
+impl TimestampIndex {
+    pub fn new(bucket_size: Timestamp) -> TimestampIndex {
+        assert_ne!(bucket_size, 0);
+        TimestampIndex {
+            bucket_size,
+            buckets: Default::default(),
+            entry_count: 0,
+        }
+    }
+
+    fn get_bucket(&self, timestamp: Timestamp) -> Timestamp {
+        timestamp / self.bucket_size
+    }
+
+    pub fn add(&mut self, timestamp: Timestamp, block_hash: SaitoHash) {
+        let bucket = self.get_bucket(timestamp);
+        let entries = self.buckets.entry(bucket).or_default();
+        if entries.iter().any(|(_, hash)| *hash == block_hash) {
+            return;
+        }
+        entries.push((timestamp, block_hash));
+        self.entry_count += 1;
+    }
+
+    pub fn remove(&mut self, timestamp: Timestamp, block_hash: &SaitoHash) {
+        let bucket = self.get_bucket(timestamp);
+        if let Some(entries) = self.buckets.get_mut(&bucket) {
+            let count = entries.len();
+            entries.retain(|(_, hash)| hash != block_hash);
+            self.entry_count -= count - entries.len();
+            if entries.is_empty() {
+                self.buckets.remove(&bucket);
+            }
+        }
+    }
+
+    /// Returns the hashes of the blocks with timestamps within [start, end], ordered by timestamp
+    pub fn get_in_range(&self, start: Timestamp, end: Timestamp) -> Vec<SaitoHash> {
+        if start > end {
+            return vec![];
+        }
+        let mut entries: Vec<(Timestamp, SaitoHash)> = self
+            .buckets
+            .range(self.get_bucket(start)..=self.get_bucket(end))
+            .flat_map(|(_, entries)| entries.iter())
+            .filter(|(timestamp, _)| *timestamp >= start && *timestamp <= end)
+            .cloned()
+            .collect();
+        entries.sort();
+        entries.into_iter().map(|(_, hash)| hash).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entry_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entry_count == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_index_test() {
+        let mut index = TimestampIndex::new(1000);
+        index.add(500, [1; 32]);
+        index.add(1500, [2; 32]);
+        index.add(1200, [3; 32]);
+        index.add(5000, [4; 32]);
+        index.add(1200, [3; 32]);
+        assert_eq!(index.len(), 4);
+
+        assert_eq!(index.get_in_range(0, 10000).len(), 4);
+        assert_eq!(index.get_in_range(1000, 1999), vec![[3; 32], [2; 32]]);
+        assert_eq!(index.get_in_range(501, 1199), Vec::<SaitoHash>::new());
+        assert_eq!(index.get_in_range(500, 500), vec![[1; 32]]);
+        assert!(index.get_in_range(2000, 1000).is_empty());
+
+        index.remove(1200, &[3; 32]);
+        index.remove(1200, &[3; 32]);
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.get_in_range(1000, 1999), vec![[2; 32]]);
+    }
+}
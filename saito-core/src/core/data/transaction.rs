@@ -1,3 +1,5 @@
+use std::io::{Error, ErrorKind};
+
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use primitive_types::U256;
@@ -6,14 +8,33 @@ use serde::{Deserialize, Serialize};
 use tracing::{debug, error, info, trace, warn};
 
 use crate::common::defs::{
-    Currency, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature, UtxoSet,
+    Currency, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature, SaitoUTXOSetKey,
+    Timestamp, UtxoSet,
 };
+use crate::common::validation_telemetry::ValidationStage;
+use crate::core::consensus::amounts::{checked_add_amounts, checked_sum_amounts, is_valid_amount};
+use crate::core::consensus::atr::validate_rebroadcast_transaction;
+use crate::core::data::account_nonce::{read_nonce, strip_nonce, wrap_message_with_nonce};
 use crate::core::data::crypto::{hash, sign, verify, verify_hash};
+use crate::core::data::format_version::{
+    get_format_envelope, read_format_envelope, CURRENT_FORMAT_VERSION, LEGACY_FORMAT_VERSION,
+};
+use crate::core::data::golden_ticket::GOLDEN_TICKET_SIZE;
+use crate::core::data::handle_registry::{is_valid_handle, HANDLE_REGISTRATION_FEE};
 use crate::core::data::hop::{Hop, HOP_SIZE};
+use crate::core::data::multisig::{is_multisig_address, MultisigData};
 use crate::core::data::slip::{Slip, SlipType, SLIP_SIZE};
 use crate::core::data::wallet::Wallet;
 
 pub const TRANSACTION_SIZE: usize = 93;
+/// minimum fee a data transaction has to pay for each byte of its payload
+pub const DATA_TRANSACTION_FEE_PER_BYTE: Currency = 1;
+/// maximum payload size of a data transaction
+pub const DATA_TRANSACTION_MAX_PAYLOAD_SIZE: usize = 100_000;
+/// submitted transactions larger than this are not added to the mempool
+pub const SUBMITTED_TRANSACTION_MAX_SIZE: usize = 1_000_000;
+/// submitted transactions created this long before the latest block are not added to the mempool
+pub const SUBMITTED_TRANSACTION_MAX_AGE_IN_MS: Timestamp = 24 * 60 * 60 * 1000;
 
 #[derive(Serialize, Deserialize, Debug, Copy, PartialEq, Clone, FromPrimitive)]
 pub enum TransactionType {
@@ -28,6 +49,51 @@ pub enum TransactionType {
     /// Issues funds for an address at the start of the network
     Issuance = 6,
     Other = 7,
+    /// Spends slips of a m-of-n multisig address. Signed by the members of the policy
+    Multisig = 8,
+    /// Stores an application payload in the message. Doesn't need to transfer any slips, but pays a fee
+    /// proportional to the payload size
+    Data = 9,
+    /// Registers the handle in the message for the key of the first input. Handles are first-come-first-served
+    HandleRegistration = 10,
+}
+
+/// Why a transaction was rejected. The values are used in the transaction batch results and the rejections sent to
+/// peers
+#[derive(Debug, Copy, PartialEq, Eq, Clone, FromPrimitive)]
+pub enum TransactionRejectReason {
+    /// the buffer is not a valid serialized transaction
+    InvalidFormat = 1,
+    NoInputs = 2,
+    NoOutputs = 3,
+    InvalidSignature = 4,
+    MissingHashForSignature = 5,
+    InvalidMultisig = 6,
+    SpendingMultisigSlip = 7,
+    InvalidRoutingPath = 8,
+    SpendsMoreThanAvailable = 9,
+    DataPayloadTooLarge = 10,
+    InsufficientDataFee = 11,
+    /// inputs are already spent or don't exist in the utxoset
+    InvalidInputs = 12,
+    /// spends slips which are already spent by pending transactions paying equal or higher fees
+    DoubleSpend = 13,
+    /// data transaction which doesn't fit in the mempool
+    MempoolFull = 14,
+    /// nonce is already used by the sender or leaves a gap after the sender's latest nonce
+    InvalidNonce = 15,
+    /// serialized transaction is larger than `SUBMITTED_TRANSACTION_MAX_SIZE`
+    TooLarge = 16,
+    /// created more than `SUBMITTED_TRANSACTION_MAX_AGE_IN_MS` before the latest block
+    Expired = 17,
+    /// handle registration with a handle which is not valid
+    InvalidHandle = 18,
+    /// handle registration paying less than `HANDLE_REGISTRATION_FEE`
+    InsufficientRegistrationFee = 19,
+    /// input or output total overflows or is larger than the token supply
+    AmountOverflow = 20,
+    /// rebroadcast transaction which doesn't spend a single slip or doesn't deduct the rebroadcast fee
+    InvalidRebroadcast = 21,
 }
 
 #[serde_with::serde_as]
@@ -44,6 +110,8 @@ pub struct Transaction {
     #[serde_as(as = "[_; 64]")]
     pub(crate) signature: SaitoSignature,
     path: Vec<Hop>,
+    /// policy and co-signatures. only set for multisig transactions
+    pub(crate) multisig: Option<MultisigData>,
 
     // hash used for merkle_root (does not include signature)
     pub hash_for_signature: Option<SaitoHash>,
@@ -72,6 +140,7 @@ impl Default for Transaction {
             signature: [0; 64],
             hash_for_signature: None,
             path: vec![],
+            multisig: None,
             total_in: 0,
             total_out: 0,
             total_fees: 0,
@@ -304,26 +373,26 @@ impl Transaction {
     /// ```
     ///
     /// ```
+    /// Rebroadcasts the slip with the given amount. The slip is the input, so it's spent when the rebroadcast is added
+    /// to the chain and can't be spent again until the block holding it is pruned
     pub fn create_rebroadcast_transaction(
         transaction_to_rebroadcast: &Transaction,
         output_slip_to_rebroadcast: &Slip,
-        with_fee: Currency,
-        with_staking_subsidy: Currency,
+        rebroadcast_amount: Currency,
     ) -> Transaction {
         let mut transaction = Transaction::default();
-        let mut output_payment = 0;
-        if output_slip_to_rebroadcast.amount > with_fee {
-            output_payment = output_slip_to_rebroadcast.amount - with_fee + with_staking_subsidy;
-        }
 
         transaction.transaction_type = TransactionType::ATR;
 
+        transaction.add_input(output_slip_to_rebroadcast.clone());
+
         let mut output = Slip::default();
         output.public_key = output_slip_to_rebroadcast.public_key;
-        output.amount = output_payment;
+        output.amount = rebroadcast_amount;
         output.slip_type = SlipType::ATR;
         output.block_id = output_slip_to_rebroadcast.block_id;
         output.tx_ordinal = output_slip_to_rebroadcast.tx_ordinal;
+        output.slip_index = output_slip_to_rebroadcast.slip_index;
 
         //
         // if this is the FIRST time we are rebroadcasting, we copy the
@@ -379,9 +448,16 @@ impl Transaction {
     /// [input][input][input]...
     /// [output][output][output]...
     /// [message]
+    /// [multisig data - only in multisig transactions]
     /// [hop][hop][hop]...
     // #[tracing::instrument(level = "info", skip_all)]
-    pub fn deserialize_from_net(bytes: &Vec<u8>) -> Transaction {
+    ///
+    /// Returns an error if the buffer is shorter than the lengths in it or has an unknown transaction type. Other
+    /// fields are not checked, so buffers from peers are read with `try_deserialize_from_net`
+    pub fn deserialize_from_net(bytes: &[u8]) -> Result<Transaction, Error> {
+        if Transaction::try_get_serialized_len(bytes).is_none() {
+            return Err(Error::from(ErrorKind::UnexpectedEof));
+        }
         let inputs_len: u32 = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
         let outputs_len: u32 = u32::from_be_bytes(bytes[4..8].try_into().unwrap());
         let message_len: usize = u32::from_be_bytes(bytes[8..12].try_into().unwrap()) as usize;
@@ -389,11 +465,20 @@ impl Transaction {
         let signature: SaitoSignature = bytes[16..80].try_into().unwrap();
         let timestamp: u64 = u64::from_be_bytes(bytes[80..88].try_into().unwrap());
         let replaces_txs = u32::from_be_bytes(bytes[88..92].try_into().unwrap());
-        let transaction_type: TransactionType = FromPrimitive::from_u8(bytes[92]).unwrap();
+        let transaction_type: TransactionType =
+            FromPrimitive::from_u8(bytes[92]).ok_or_else(|| Error::from(ErrorKind::InvalidData))?;
         let start_of_inputs = TRANSACTION_SIZE;
         let start_of_outputs = start_of_inputs + inputs_len as usize * SLIP_SIZE;
         let start_of_message = start_of_outputs + outputs_len as usize * SLIP_SIZE;
-        let start_of_path = start_of_message + message_len;
+        let end_of_message = start_of_message + message_len;
+        let mut multisig = None;
+        let mut start_of_path = end_of_message;
+        if transaction_type == TransactionType::Multisig {
+            start_of_path += MultisigData::get_serialized_len(&bytes[end_of_message..])?;
+            multisig = Some(MultisigData::deserialize_from_net(
+                &bytes[end_of_message..],
+            )?);
+        }
         let mut inputs: Vec<Slip> = vec![];
         for n in 0..inputs_len {
             let start_of_data: usize = start_of_inputs as usize + n as usize * SLIP_SIZE;
@@ -408,9 +493,7 @@ impl Transaction {
             let output = Slip::deserialize_from_net(&bytes[start_of_data..end_of_data].to_vec());
             outputs.push(output);
         }
-        let message = bytes[start_of_message..start_of_message + message_len]
-            .try_into()
-            .unwrap();
+        let message = bytes[start_of_message..end_of_message].try_into().unwrap();
         let mut path: Vec<Hop> = vec![];
         for n in 0..path_len {
             let start_of_data: usize = start_of_path as usize + n as usize * HOP_SIZE;
@@ -428,7 +511,107 @@ impl Transaction {
         transaction.transaction_type = transaction_type;
         transaction.signature = signature;
         transaction.path = path;
-        transaction
+        transaction.multisig = multisig;
+        Ok(transaction)
+    }
+
+    /// Same as `deserialize_from_net`, but returns an error instead of panicking if the buffer is malformed
+    pub fn try_deserialize_from_net(bytes: &[u8]) -> Result<Transaction, Error> {
+        Transaction::validate_net_buffer(bytes)?;
+        Transaction::deserialize_from_net(bytes)
+    }
+
+    /// Reads a transaction serialized in any supported format version. Buffers without a format envelope are read
+    /// with the legacy layout
+    pub fn try_deserialize_versioned(bytes: &[u8]) -> Result<Transaction, Error> {
+        let (version, buffer) = read_format_envelope(bytes)?;
+        match version {
+            LEGACY_FORMAT_VERSION | CURRENT_FORMAT_VERSION => {
+                Transaction::try_deserialize_from_net(buffer)
+            }
+            _ => Err(Error::from(ErrorKind::InvalidData)),
+        }
+    }
+
+    /// Checks that the buffer is exactly one serialized transaction which `deserialize_from_net` can read. Lengths,
+    /// the transaction and slip types and the size of golden tickets are checked, but not the signatures
+    pub fn validate_net_buffer(bytes: &[u8]) -> Result<(), Error> {
+        if Transaction::try_get_serialized_len(bytes) != Some(bytes.len()) {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let transaction_type: TransactionType =
+            FromPrimitive::from_u8(bytes[92]).ok_or_else(|| Error::from(ErrorKind::InvalidData))?;
+        let inputs_len = u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        let outputs_len = u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let message_len = u32::from_be_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        let slips =
+            &bytes[TRANSACTION_SIZE..TRANSACTION_SIZE + (inputs_len + outputs_len) * SLIP_SIZE];
+        if slips
+            .chunks_exact(SLIP_SIZE)
+            .any(|slip| SlipType::from_u8(slip[SLIP_SIZE - 1]).is_none())
+        {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        // golden tickets are read from the message without checking its size
+        if transaction_type == TransactionType::GoldenTicket && message_len != GOLDEN_TICKET_SIZE {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        Ok(())
+    }
+
+    /// size of the transaction when serialized for net
+    pub fn get_serialized_size(&self) -> usize {
+        let mut size = TRANSACTION_SIZE
+            + (self.inputs.len() + self.outputs.len()) * SLIP_SIZE
+            + self.message.len()
+            + self.path.len() * HOP_SIZE;
+        if self.transaction_type == TransactionType::Multisig {
+            size += self
+                .multisig
+                .as_ref()
+                .map(|multisig| multisig.serialize_for_net().len())
+                .unwrap_or(3);
+        }
+        size
+    }
+
+    /// Returns the length of the serialized transaction at the start of the buffer
+    pub fn get_serialized_len(bytes: &[u8]) -> usize {
+        let inputs_len = u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        let outputs_len = u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let message_len = u32::from_be_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        let path_len = u32::from_be_bytes(bytes[12..16].try_into().unwrap()) as usize;
+        let mut length = TRANSACTION_SIZE + (inputs_len + outputs_len) * SLIP_SIZE + message_len;
+        if bytes[92] == TransactionType::Multisig as u8 {
+            length += MultisigData::get_serialized_len(&bytes[length..]).unwrap();
+        }
+        length + path_len * HOP_SIZE
+    }
+
+    /// Same as `get_serialized_len`, but returns None instead of panicking if the buffer is too short for the transaction
+    pub fn try_get_serialized_len(bytes: &[u8]) -> Option<usize> {
+        if bytes.len() < TRANSACTION_SIZE {
+            return None;
+        }
+        let inputs_len = u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        let outputs_len = u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let message_len = u32::from_be_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        let path_len = u32::from_be_bytes(bytes[12..16].try_into().unwrap()) as usize;
+        // lengths are checked for overflows since usize is 32 bits in wasm
+        let mut length = inputs_len
+            .checked_add(outputs_len)?
+            .checked_mul(SLIP_SIZE)?
+            .checked_add(message_len)?
+            .checked_add(TRANSACTION_SIZE)?;
+        if bytes[92] == TransactionType::Multisig as u8 {
+            let multisig_len = MultisigData::get_serialized_len(bytes.get(length..)?).ok()?;
+            length = length.checked_add(multisig_len)?;
+        }
+        length = length.checked_add(path_len.checked_mul(HOP_SIZE)?)?;
+        if length > bytes.len() {
+            return None;
+        }
+        Some(length)
     }
 
     pub fn is_fee_transaction(&self) -> bool {
@@ -447,6 +630,72 @@ impl Transaction {
         self.transaction_type == TransactionType::Issuance
     }
 
+    pub fn is_multisig_transaction(&self) -> bool {
+        self.transaction_type == TransactionType::Multisig
+    }
+
+    pub fn get_signature(&self) -> &SaitoSignature {
+        &self.signature
+    }
+
+    pub(crate) fn get_path(&self) -> &[Hop] {
+        &self.path
+    }
+
+    pub fn is_data_transaction(&self) -> bool {
+        self.transaction_type == TransactionType::Data
+    }
+
+    pub fn is_handle_registration(&self) -> bool {
+        self.transaction_type == TransactionType::HandleRegistration
+    }
+
+    /// Returns the handle and the key it's registered for. None for other transactions and invalid handles
+    pub fn get_handle_registration(&self) -> Option<(&str, SaitoPublicKey)> {
+        if !self.is_handle_registration() {
+            return None;
+        }
+        let handle = std::str::from_utf8(&self.message).ok()?;
+        if !is_valid_handle(handle) {
+            return None;
+        }
+        Some((handle, self.inputs.first()?.public_key))
+    }
+
+    /// minimum fee for storing the message of this transaction on chain
+    pub fn get_data_fee(&self) -> Currency {
+        self.message.len() as Currency * DATA_TRANSACTION_FEE_PER_BYTE
+    }
+
+    /// Sets the nonce in the message envelope, replacing the existing one. Should be called before signing since the
+    /// nonce is part of the signed message
+    pub fn set_nonce(&mut self, nonce: u64) {
+        let message = strip_nonce(&self.message);
+        self.message = wrap_message_with_nonce(nonce, message);
+    }
+
+    /// Returns the nonce of user sent transactions with a nonce envelope in the message. Mempools only accept these
+    /// in order per sender, other transactions are not ordered
+    pub fn get_nonce(&self) -> Option<u64> {
+        match self.transaction_type {
+            TransactionType::Normal | TransactionType::Data | TransactionType::Multisig => {
+                read_nonce(&self.message)
+            }
+            _ => None,
+        }
+    }
+
+    /// key the nonce is counted for. this is the key of the first input, which signs the transaction
+    pub fn get_nonce_sender(&self) -> Option<SaitoPublicKey> {
+        self.get_nonce()?;
+        self.inputs.first().map(|input| input.public_key)
+    }
+
+    /// message without the nonce envelope
+    pub fn get_application_message(&self) -> &[u8] {
+        strip_nonce(&self.message)
+    }
+
     //
     // generates all non-cumulative
     //
@@ -474,7 +723,9 @@ impl Transaction {
     // calculate cumulative fee share in block
     //
     pub fn generate_cumulative_fees(&mut self, cumulative_fees: Currency) -> Currency {
-        self.cumulative_fees = cumulative_fees + self.total_fees;
+        // an overflow can only come from an invalid block, which fails the amount checks in the validation
+        self.cumulative_fees =
+            checked_add_amounts(cumulative_fees, self.total_fees).unwrap_or(Currency::MAX);
         self.cumulative_fees
     }
 
@@ -493,29 +744,24 @@ impl Transaction {
 
         // calculate nolan in / out, fees
         // generate utxoset key for every slip
-        let nolan_in = self
-            .inputs
-            .iter_mut()
-            .map(|slip| {
-                slip.generate_utxoset_key();
-                slip.amount
-            })
-            .sum::<Currency>();
-
-        let nolan_out = self
-            .outputs
-            .iter_mut()
-            .enumerate()
-            .map(|(index, slip)| {
-                if slip.slip_type != SlipType::ATR {
-                    slip.block_id = block_id;
-                    slip.tx_ordinal = tx_index;
-                    slip.slip_index = index as u8;
-                }
-                slip.generate_utxoset_key();
-                slip.amount
-            })
-            .sum::<Currency>();
+        for slip in self.inputs.iter_mut() {
+            slip.generate_utxoset_key();
+        }
+        for (index, slip) in self.outputs.iter_mut().enumerate() {
+            if slip.slip_type != SlipType::ATR {
+                slip.block_id = block_id;
+                slip.tx_ordinal = tx_index;
+                slip.slip_index = index as u8;
+            }
+            slip.generate_utxoset_key();
+        }
+
+        // totals which overflow or exceed the token supply are set to the max value instead of wrapping around, so
+        // the transaction fails the validation
+        let nolan_in = checked_sum_amounts(self.inputs.iter().map(|slip| slip.amount))
+            .unwrap_or(Currency::MAX);
+        let nolan_out = checked_sum_amounts(self.outputs.iter().map(|slip| slip.amount))
+            .unwrap_or(Currency::MAX);
 
         self.total_in = nolan_in;
         self.total_out = nolan_out;
@@ -697,6 +943,22 @@ impl Transaction {
         });
     }
 
+    /// utxoset changes made by `on_chain_reorganization`, in the same order
+    pub fn get_utxoset_updates(
+        &self,
+        longest_chain: bool,
+    ) -> impl Iterator<Item = (SaitoUTXOSetKey, bool)> + '_ {
+        let inputs = self.inputs.iter().map(move |input| (input, !longest_chain));
+        let outputs = self
+            .outputs
+            .iter()
+            .map(move |output| (output, longest_chain));
+        inputs
+            .chain(outputs)
+            .filter(|(slip, _)| slip.amount > 0)
+            .map(|(slip, spendable)| (slip.utxoset_key, spendable))
+    }
+
     /// [len of inputs - 4 bytes - u32]
     /// [len of outputs - 4 bytes - u32]
     /// [len of message - 4 bytes - u32]
@@ -707,12 +969,20 @@ impl Transaction {
     /// [input][input][input]...
     /// [output][output][output]...
     /// [message]
+    /// [multisig data - only in multisig transactions]
     /// [hop][hop][hop]...
     #[tracing::instrument(level = "info", skip_all)]
     pub fn serialize_for_net(&self) -> Vec<u8> {
         self.serialize_for_net_with_hop(None)
     }
 
+    /// Serializes the transaction with the envelope of the given format version
+    pub fn serialize_for_net_with_version(&self, version: u8) -> Vec<u8> {
+        let mut buffer = get_format_envelope(version);
+        buffer.extend(self.serialize_for_net());
+        buffer
+    }
+
     // #[tracing::instrument(level = "info", skip_all)]
     pub(crate) fn serialize_for_net_with_hop(&self, opt_hop: Option<Hop>) -> Vec<u8> {
         let mut path_len = self.path.len();
@@ -737,6 +1007,15 @@ impl Transaction {
             .map(|hop| hop.serialize_for_net())
             .collect::<Vec<_>>()
             .concat();
+        let mut multisig = vec![];
+        if self.transaction_type == TransactionType::Multisig {
+            // an empty policy is written if missing, so the tx can still be parsed (and rejected)
+            multisig = self
+                .multisig
+                .as_ref()
+                .map(|multisig| multisig.serialize_for_net())
+                .unwrap_or_else(|| vec![0, 0, 0]);
+        }
 
         let mut buffer: Vec<u8> = [
             (self.inputs.len() as u32).to_be_bytes().as_slice(),
@@ -750,6 +1029,7 @@ impl Transaction {
             inputs.as_slice(),
             outputs.as_slice(),
             self.message.as_slice(),
+            multisig.as_slice(),
             hops.as_slice(),
         ]
         .concat();
@@ -802,8 +1082,32 @@ impl Transaction {
         self.signature = sign(&buffer, private_key);
     }
 
-    #[tracing::instrument(level = "trace", skip_all)]
+    /// Sets a signature created elsewhere (e.g. by an offline wallet) for this transaction. Returns false without
+    /// changing the transaction if the signature is not valid for the given key
+    pub fn apply_signature(
+        &mut self,
+        signature: SaitoSignature,
+        public_key: &SaitoPublicKey,
+    ) -> bool {
+        for (i, output) in self.outputs.iter_mut().enumerate() {
+            output.slip_index = i as u8;
+        }
+        let hash_for_signature = hash(&self.serialize_for_signature());
+        if !verify_hash(&hash_for_signature, &signature, public_key) {
+            return false;
+        }
+        self.hash_for_signature = Some(hash_for_signature);
+        self.signature = signature;
+        true
+    }
+
     pub fn validate(&self, utxoset: &UtxoSet) -> bool {
+        self.validate_with_reason(utxoset).is_ok()
+    }
+
+    /// Same as `validate`, but returns why the transaction is not valid
+    #[tracing::instrument(level = "trace", skip_all)]
+    pub fn validate_with_reason(&self, utxoset: &UtxoSet) -> Result<(), TransactionRejectReason> {
         // trace!(
         //     "validating transaction : {:?}",
         //     hex::encode(self.hash_for_signature.unwrap())
@@ -816,7 +1120,23 @@ impl Transaction {
         // when reversing/unwinding the chain and have been spent previously.
         //
         if self.transaction_type == TransactionType::Fee {
-            return true;
+            return Ok(());
+        }
+
+        //
+        // amounts
+        //
+        // totals are set to the max value if the slip amounts overflow, so a
+        // transaction can't wrap its outputs around to less than its inputs.
+        //
+        if !is_valid_amount(self.total_in) || !is_valid_amount(self.total_out) {
+            error!(
+                "ERROR 802395: tx : {:?} amounts overflow. total in : {:?} total out : {:?}",
+                hex::encode(self.signature),
+                self.total_in,
+                self.total_out
+            );
+            return Err(TransactionRejectReason::AmountOverflow);
         }
 
         //
@@ -848,13 +1168,37 @@ impl Transaction {
             //
             if self.inputs.is_empty() {
                 error!("ERROR 582039: less than 1 input in transaction");
-                return false;
+                return Err(TransactionRejectReason::NoInputs);
             }
 
             //
             // validate signature
             //
-            if let Some(hash_for_signature) = &self.hash_for_signature {
+            // multisig transactions are signed by the members of the policy instead of
+            // the owner of the first input. funds in a multisig address cannot be spent
+            // by any other transaction type.
+            //
+            let stage = ValidationStage::TransactionSignatures.start();
+            if transaction_type == TransactionType::Multisig {
+                if !self.validate_multisig() {
+                    error!(
+                        "ERROR 391934: multisig transaction : {:?} does not validate",
+                        hex::encode(self.signature)
+                    );
+                    return Err(TransactionRejectReason::InvalidMultisig);
+                }
+            } else if self
+                .inputs
+                .iter()
+                .any(|input| is_multisig_address(&input.public_key))
+            {
+                error!(
+                    "ERROR 391935: tx : {:?} of type : {:?} is spending a multisig slip",
+                    hex::encode(self.signature),
+                    transaction_type
+                );
+                return Err(TransactionRejectReason::SpendingMultisigSlip);
+            } else if let Some(hash_for_signature) = &self.hash_for_signature {
                 let sig: SaitoSignature = self.signature;
                 let public_key: SaitoPublicKey = self.inputs[0].public_key;
                 if !verify_hash(hash_for_signature, &sig, &public_key) {
@@ -864,7 +1208,7 @@ impl Transaction {
                         hex::encode(sig),
                         hex::encode(public_key)
                     );
-                    return false;
+                    return Err(TransactionRejectReason::InvalidSignature);
                 }
             } else {
                 //
@@ -874,7 +1218,7 @@ impl Transaction {
                 // unexpected behavior.
                 //
                 error!("ERROR 757293: there is no hash for signature in a transaction");
-                return false;
+                return Err(TransactionRejectReason::MissingHashForSignature);
             }
 
             //
@@ -886,8 +1230,9 @@ impl Transaction {
             //
             if !self.validate_routing_path() {
                 error!("ERROR 482033: routing paths do not validate, transaction invalid");
-                return false;
+                return Err(TransactionRejectReason::InvalidRoutingPath);
             }
+            drop(stage);
 
             // TODO : what happens to tokens when total_out < total_in
             // validate we're not creating tokens out of nothing
@@ -900,7 +1245,7 @@ impl Transaction {
                 //     // info!("{:?} --- ", z.amount);
                 // }
                 error!("ERROR 802394: transaction spends more than it has available");
-                return false;
+                return Err(TransactionRejectReason::SpendsMoreThanAvailable);
             }
         }
 
@@ -912,7 +1257,17 @@ impl Transaction {
         //
         // atr transactions
         //
-        if transaction_type == TransactionType::ATR {}
+        // the signature is the signature of the original transaction, so only
+        // the slips are checked here. the rebroadcasts are checked against the
+        // expected rebroadcasts with the rebroadcast hash of the block.
+        //
+        if transaction_type == TransactionType::ATR && !validate_rebroadcast_transaction(self) {
+            error!(
+                "ERROR 582044: rebroadcast transaction : {:?} is invalid",
+                hex::encode(self.signature)
+            );
+            return Err(TransactionRejectReason::InvalidRebroadcast);
+        }
 
         //
         // normal transactions
@@ -937,6 +1292,57 @@ impl Transaction {
             // public_key associated with the Saito project.
         }
 
+        //
+        // data transactions
+        //
+        // the payload is paid for by the fees, so the transaction doesn't
+        // need to transfer any slips. outputs are only used for the change.
+        //
+        if transaction_type == TransactionType::Data {
+            if self.message.len() > DATA_TRANSACTION_MAX_PAYLOAD_SIZE {
+                error!(
+                    "ERROR 582040: data transaction payload size : {:?} is larger than {:?}",
+                    self.message.len(),
+                    DATA_TRANSACTION_MAX_PAYLOAD_SIZE
+                );
+                return Err(TransactionRejectReason::DataPayloadTooLarge);
+            }
+            if self.total_fees < self.get_data_fee() {
+                error!(
+                    "ERROR 582041: data transaction fees : {:?} are lower than required : {:?}",
+                    self.total_fees,
+                    self.get_data_fee()
+                );
+                return Err(TransactionRejectReason::InsufficientDataFee);
+            }
+            return self.validate_inputs_against_utxoset(utxoset);
+        }
+
+        //
+        // handle registrations
+        //
+        // binds the handle in the message to the key of the first input. like
+        // data transactions, outputs are only used for the change. whether the
+        // handle is available is checked against the registry with the block.
+        //
+        if transaction_type == TransactionType::HandleRegistration {
+            if self.get_handle_registration().is_none() {
+                error!(
+                    "ERROR 582042: handle registration : {:?} has an invalid handle",
+                    hex::encode(self.signature)
+                );
+                return Err(TransactionRejectReason::InvalidHandle);
+            }
+            if self.total_fees < HANDLE_REGISTRATION_FEE {
+                error!(
+                    "ERROR 582043: handle registration fees : {:?} are lower than required : {:?}",
+                    self.total_fees, HANDLE_REGISTRATION_FEE
+                );
+                return Err(TransactionRejectReason::InsufficientRegistrationFee);
+            }
+            return self.validate_inputs_against_utxoset(utxoset);
+        }
+
         //
         // all Transactions
         //
@@ -948,11 +1354,106 @@ impl Transaction {
         //
         if self.outputs.is_empty() {
             error!("ERROR 582039: less than 1 output in transaction");
+            return Err(TransactionRejectReason::NoOutputs);
+        }
+
+        self.validate_inputs_against_utxoset(utxoset)
+    }
+
+    /// Validates a transaction submitted to the node. Adds the size and age limits of the mempool, which are not
+    /// applied to the transactions in blocks
+    pub fn validate_submitted(
+        &self,
+        utxoset: &UtxoSet,
+        latest_block_timestamp: Timestamp,
+    ) -> Result<(), TransactionRejectReason> {
+        if self.get_serialized_size() > SUBMITTED_TRANSACTION_MAX_SIZE {
+            return Err(TransactionRejectReason::TooLarge);
+        }
+        // rebroadcasts are not signed by the owner of the slip, so they are only accepted in blocks
+        if self.is_atr_transaction() {
+            return Err(TransactionRejectReason::InvalidRebroadcast);
+        }
+        if self
+            .timestamp
+            .saturating_add(SUBMITTED_TRANSACTION_MAX_AGE_IN_MS)
+            < latest_block_timestamp
+        {
+            return Err(TransactionRejectReason::Expired);
+        }
+        self.validate_with_reason(utxoset)
+    }
+
+    fn validate_inputs_against_utxoset(
+        &self,
+        utxoset: &UtxoSet,
+    ) -> Result<(), TransactionRejectReason> {
+        let _stage = ValidationStage::TransactionUtxo.start();
+        if !self.validate_against_utxoset(utxoset) {
+            return Err(TransactionRejectReason::InvalidInputs);
+        }
+        Ok(())
+    }
+
+    /// checks the co-signatures of a multisig transaction against its policy. all inputs
+    /// should belong to the address of the policy.
+    pub fn validate_multisig(&self) -> bool {
+        let multisig = match &self.multisig {
+            Some(multisig) => multisig,
+            None => {
+                warn!("multisig transaction doesn't have a policy");
+                return false;
+            }
+        };
+        let hash_for_signature = match &self.hash_for_signature {
+            Some(hash) => hash,
+            None => {
+                warn!("multisig transaction doesn't have a hash for signature");
+                return false;
+            }
+        };
+        let address = multisig.policy.get_address();
+        if self.inputs.iter().any(|input| input.public_key != address) {
+            warn!(
+                "multisig transaction has inputs not belonging to : {:?}",
+                hex::encode(address)
+            );
             return false;
         }
+        match multisig.signatures.first() {
+            // tx signature is used as the tx id and for routing
+            Some(first) if first.signature == self.signature => {}
+            _ => {
+                warn!("multisig transaction signature is not set");
+                return false;
+            }
+        }
+        multisig.validate(hash_for_signature)
+    }
+
+    /// adds a co-signature to the multisig transaction. the transaction signature is set to the
+    /// co-signature with the lowest key index so it doesn't depend on the signing order.
+    pub fn add_multisig_signature(&mut self, key_index: u8, signature: SaitoSignature) -> bool {
+        if self.transaction_type != TransactionType::Multisig {
+            return false;
+        }
+        match self.multisig.as_mut() {
+            Some(multisig) => {
+                if !multisig.add_signature(key_index, signature) {
+                    return false;
+                }
+                self.signature = multisig.signatures[0].signature;
+                true
+            }
+            None => false,
+        }
+    }
 
-        let inputs_validate = self.validate_against_utxoset(utxoset);
-        inputs_validate
+    pub fn is_multisig_fully_signed(&self) -> bool {
+        self.multisig
+            .as_ref()
+            .map(|multisig| multisig.is_fully_signed())
+            .unwrap_or(false)
     }
 
     pub fn validate_against_utxoset(&self, utxoset: &UtxoSet) -> bool {
@@ -1036,6 +1537,7 @@ mod tests {
     use hex::FromHex;
 
     use super::*;
+    use crate::core::data::crypto::generate_keys;
 
     #[test]
     fn transaction_new_test() {
@@ -1066,6 +1568,87 @@ mod tests {
         assert_ne!(tx.hash_for_signature, Some([0; 32]));
     }
 
+    #[test]
+    fn transaction_reject_reason_test() {
+        let wallet = Wallet::new();
+        let utxoset = UtxoSet::default();
+        let mut tx = Transaction::default();
+        assert_eq!(
+            tx.validate_with_reason(&utxoset),
+            Err(TransactionRejectReason::NoInputs)
+        );
+
+        let mut input = Slip::default();
+        input.public_key = wallet.public_key;
+        input.amount = 100;
+        tx.add_input(input);
+        assert_eq!(
+            tx.validate_with_reason(&utxoset),
+            Err(TransactionRejectReason::MissingHashForSignature)
+        );
+
+        tx.sign(&wallet.private_key);
+        assert_eq!(
+            tx.validate_with_reason(&utxoset),
+            Err(TransactionRejectReason::NoOutputs)
+        );
+
+        let mut output = Slip::default();
+        output.public_key = wallet.public_key;
+        output.amount = 100;
+        tx.add_output(output);
+        tx.sign(&wallet.private_key);
+        // the input is not in the utxoset
+        assert_eq!(
+            tx.validate_with_reason(&utxoset),
+            Err(TransactionRejectReason::InvalidInputs)
+        );
+        assert!(!tx.validate(&utxoset));
+
+        let (other_public_key, _) = generate_keys();
+        tx.inputs[0].public_key = other_public_key;
+        assert_eq!(
+            tx.validate_with_reason(&utxoset),
+            Err(TransactionRejectReason::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn validate_submitted_test() {
+        let wallet = Wallet::new();
+        let utxoset = UtxoSet::default();
+        let mut tx = Transaction::default();
+        tx.timestamp = SUBMITTED_TRANSACTION_MAX_AGE_IN_MS;
+        let mut input = Slip::default();
+        input.public_key = wallet.public_key;
+        tx.add_input(input);
+        tx.add_output(Slip::default());
+        tx.sign(&wallet.private_key);
+
+        assert_eq!(
+            tx.validate_submitted(&utxoset, 2 * SUBMITTED_TRANSACTION_MAX_AGE_IN_MS),
+            Err(TransactionRejectReason::InvalidInputs)
+        );
+        assert_eq!(
+            tx.validate_submitted(&utxoset, 2 * SUBMITTED_TRANSACTION_MAX_AGE_IN_MS + 1),
+            Err(TransactionRejectReason::Expired)
+        );
+
+        tx.message = vec![1; SUBMITTED_TRANSACTION_MAX_SIZE];
+        tx.sign(&wallet.private_key);
+        assert_eq!(
+            tx.validate_submitted(&utxoset, 0),
+            Err(TransactionRejectReason::TooLarge)
+        );
+
+        tx.message = vec![];
+        tx.transaction_type = TransactionType::ATR;
+        assert_eq!(
+            tx.validate_submitted(&utxoset, 0),
+            Err(TransactionRejectReason::InvalidRebroadcast)
+        );
+    }
+
     #[test]
     fn serialize_for_signature_test() {
         let tx = Transaction::default();
@@ -1183,6 +1766,55 @@ mod tests {
         assert_eq!(tx.cumulative_fees, 1_0000);
     }
 
+    #[test]
+    fn transaction_amount_overflow_test() {
+        use crate::core::data::blockchain::MAX_TOKEN_SUPPLY;
+
+        let create_slip = |amount: Currency| {
+            let mut slip = Slip::default();
+            slip.amount = amount;
+            slip
+        };
+        let utxoset = UtxoSet::default();
+
+        // outputs wrapping around to less than the input
+        let mut tx = Transaction::default();
+        tx.add_input(create_slip(100));
+        tx.add_output(create_slip(Currency::MAX / 2 + 1));
+        tx.add_output(create_slip(Currency::MAX / 2 + 1));
+        tx.generate_total_fees(0, 1);
+        assert_eq!(tx.total_in, 100);
+        assert_eq!(tx.total_out, Currency::MAX);
+        assert_eq!(tx.total_fees, 0);
+        assert_eq!(
+            tx.validate_with_reason(&utxoset),
+            Err(TransactionRejectReason::AmountOverflow)
+        );
+
+        // a single output larger than the token supply
+        let mut tx = Transaction::default();
+        tx.add_input(create_slip(100));
+        tx.add_output(create_slip(MAX_TOKEN_SUPPLY + 1));
+        tx.generate_total_fees(0, 1);
+        assert_eq!(
+            tx.validate_with_reason(&utxoset),
+            Err(TransactionRejectReason::AmountOverflow)
+        );
+
+        let mut tx = Transaction::default();
+        tx.add_input(create_slip(MAX_TOKEN_SUPPLY));
+        tx.add_output(create_slip(MAX_TOKEN_SUPPLY - 10));
+        tx.generate_total_fees(0, 1);
+        assert_eq!(tx.total_fees, 10);
+        assert_ne!(
+            tx.validate_with_reason(&utxoset),
+            Err(TransactionRejectReason::AmountOverflow)
+        );
+
+        tx.generate_cumulative_fees(MAX_TOKEN_SUPPLY);
+        assert_eq!(tx.cumulative_fees, Currency::MAX);
+    }
+
     #[test]
     fn serialize_for_net_and_deserialize_from_net_test() {
         let mock_input = Slip::default();
@@ -1204,7 +1836,7 @@ mod tests {
 
         let serialized_tx = mock_tx.serialize_for_net();
 
-        let deserialized_tx = Transaction::deserialize_from_net(&serialized_tx);
+        let deserialized_tx = Transaction::deserialize_from_net(&serialized_tx).unwrap();
         assert_eq!(mock_tx, deserialized_tx);
     }
 
@@ -1215,7 +1847,7 @@ mod tests {
         let tx_buffer_txt = "00000001000000010000000300000000dc9f23b0d0feb6609170abddcd5a1de249432b3e6761b8aac39b6e1b5bcb6bef73c1b8af4f394e2b3d983b81ba3e0888feaab092fa1754de8896e22dcfbeb4ec0000017d26dd628a000000010303cb14a56ddc769932baba62c22773aaf6d26d799b548c8b8f654fb92d25ce7610dcf6cceb74717f98c3f7239459bb36fdcd8f350eedbfccfbebf7c0b0161fcd8b000000000000007b0a0103cb14a56ddc769932baba62c22773aaf6d26d799b548c8b8f654fb92d25ce7610dcf6cceb74717f98c3f7239459bb36fdcd8f350eedbfccfbebf7c0b0161fcd8b00000000000001590000616263";
         let buffer = hex::decode(tx_buffer_txt).unwrap();
 
-        let mut tx = Transaction::deserialize_from_net(&buffer);
+        let mut tx = Transaction::deserialize_from_net(&buffer).unwrap();
 
         assert_eq!(tx.timestamp, 1637034582666);
         assert_eq!(tx.transaction_type, TransactionType::ATR);
@@ -0,0 +1,195 @@
+use ahash::AHashMap;
+use tracing::{debug, warn};
+
+use crate::common::defs::{BlockId, SaitoHash, SaitoSignature};
+use crate::core::data::block::{Block, BlockType};
+
+pub const TRANSACTION_INDEX_FILE_PATH: &str = "./data/tx_index";
+/// [signature - 64 bytes][block hash - 32 bytes][block id - 8 bytes][tx ordinal - 8 bytes]
+pub(crate) const TRANSACTION_INDEX_ENTRY_SIZE: usize = 112;
+
+/// Position of a transaction within the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionLocation {
+    pub block_hash: SaitoHash,
+    pub block_id: BlockId,
+    /// index of the transaction in the block
+    pub tx_ordinal: u64,
+}
+
+/// Maps transaction signatures to the blocks containing them, so transactions can be found without scanning
+/// the blocks. A transaction can be in multiple blocks if they are in different forks.
+#[derive(Debug, Default)]
+pub struct TransactionIndex {
+    entries: AHashMap<SaitoSignature, Vec<TransactionLocation>>,
+    // signatures of the indexed blocks, so the entries can be removed with the block
+    block_signatures: AHashMap<SaitoHash, Vec<SaitoSignature>>,
+}
+
+impl TransactionIndex {
+    pub fn new() -> TransactionIndex {
+        TransactionIndex {
+            entries: Default::default(),
+            block_signatures: Default::default(),
+        }
+    }
+
+    /// Indexes the transactions of a full block. Blocks which are already indexed are ignored.
+    pub fn add_block(&mut self, block: &Block) {
+        if block.block_type != BlockType::Full {
+            debug!(
+                "block : {:?} is not indexed since it's of type : {:?}",
+                hex::encode(block.hash),
+                block.block_type
+            );
+            return;
+        }
+        if self.block_signatures.contains_key(&block.hash) {
+            return;
+        }
+        let mut signatures = Vec::with_capacity(block.transactions.len());
+        for (index, tx) in block.transactions.iter().enumerate() {
+            self.add_entry(
+                tx.signature,
+                TransactionLocation {
+                    block_hash: block.hash,
+                    block_id: block.id,
+                    tx_ordinal: index as u64,
+                },
+            );
+            signatures.push(tx.signature);
+        }
+        self.block_signatures.insert(block.hash, signatures);
+    }
+
+    fn add_entry(&mut self, signature: SaitoSignature, location: TransactionLocation) {
+        let locations = self.entries.entry(signature).or_default();
+        if !locations.contains(&location) {
+            locations.push(location);
+        }
+    }
+
+    pub fn remove_block(&mut self, block_hash: &SaitoHash) {
+        let signatures = self.block_signatures.remove(block_hash);
+        if signatures.is_none() {
+            return;
+        }
+        for signature in signatures.unwrap() {
+            if let Some(locations) = self.entries.get_mut(&signature) {
+                locations.retain(|location| location.block_hash != *block_hash);
+                if locations.is_empty() {
+                    self.entries.remove(&signature);
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, signature: &SaitoSignature) -> &[TransactionLocation] {
+        self.entries
+            .get(signature)
+            .map(|locations| locations.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn contains_block(&self, block_hash: &SaitoHash) -> bool {
+        self.block_signatures.contains_key(block_hash)
+    }
+
+    /// number of indexed transactions
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn serialize_for_disk(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(self.entries.len() * TRANSACTION_INDEX_ENTRY_SIZE);
+        for (signature, locations) in self.entries.iter() {
+            for location in locations.iter() {
+                buffer.extend_from_slice(signature);
+                buffer.extend_from_slice(&location.block_hash);
+                buffer.extend_from_slice(&location.block_id.to_be_bytes());
+                buffer.extend_from_slice(&location.tx_ordinal.to_be_bytes());
+            }
+        }
+        buffer
+    }
+
+    pub fn deserialize_from_disk(bytes: &[u8]) -> TransactionIndex {
+        let mut index = TransactionIndex::new();
+        if bytes.len() % TRANSACTION_INDEX_ENTRY_SIZE != 0 {
+            warn!(
+                "transaction index file size : {:?} is invalid. ignoring the last entry",
+                bytes.len()
+            );
+        }
+        for entry in bytes.chunks_exact(TRANSACTION_INDEX_ENTRY_SIZE) {
+            let signature: SaitoSignature = entry[0..64].try_into().unwrap();
+            let location = TransactionLocation {
+                block_hash: entry[64..96].try_into().unwrap(),
+                block_id: BlockId::from_be_bytes(entry[96..104].try_into().unwrap()),
+                tx_ordinal: u64::from_be_bytes(entry[104..112].try_into().unwrap()),
+            };
+            index.add_entry(signature, location);
+            index
+                .block_signatures
+                .entry(location.block_hash)
+                .or_default()
+                .push(signature);
+        }
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::block::{Block, BlockType};
+    use crate::core::data::transaction::Transaction;
+    use crate::core::data::transaction_index::TransactionIndex;
+
+    fn create_block(hash: u8, id: u64, signatures: &[u8]) -> Block {
+        let mut block = Block::new();
+        block.hash = [hash; 32];
+        block.id = id;
+        block.block_type = BlockType::Full;
+        for signature in signatures {
+            let mut tx = Transaction::default();
+            tx.signature = [*signature; 64];
+            block.transactions.push(tx);
+        }
+        block
+    }
+
+    #[test]
+    fn transaction_index_test() {
+        let mut index = TransactionIndex::new();
+        index.add_block(&create_block(1, 1, &[1, 2]));
+        index.add_block(&create_block(2, 2, &[3]));
+        // same transaction in a fork
+        index.add_block(&create_block(3, 2, &[3, 4]));
+        let mut pruned_block = create_block(4, 3, &[5]);
+        pruned_block.block_type = BlockType::Pruned;
+        index.add_block(&pruned_block);
+
+        assert_eq!(index.len(), 4);
+        let locations = index.get(&[2; 64]);
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].block_hash, [1; 32]);
+        assert_eq!(locations[0].tx_ordinal, 1);
+        assert_eq!(index.get(&[3; 64]).len(), 2);
+        assert!(index.get(&[5; 64]).is_empty());
+
+        let loaded = TransactionIndex::deserialize_from_disk(&index.serialize_for_disk());
+        assert_eq!(loaded.len(), 4);
+        assert_eq!(loaded.get(&[4; 64]), index.get(&[4; 64]));
+        assert!(loaded.contains_block(&[3; 32]));
+
+        index.remove_block(&[3; 32]);
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.get(&[3; 64])[0].block_hash, [2; 32]);
+        assert!(index.get(&[4; 64]).is_empty());
+        assert!(!index.contains_block(&[3; 32]));
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::VecDeque;
+
+use ahash::AHashMap;
+use serde::Serialize;
+
+use crate::common::defs::{BlockId, SaitoHash, SaitoSignature, Timestamp};
+use crate::core::data::block::Block;
+use crate::core::data::mempool::{AddTransactionResult, Mempool};
+
+/// number of transactions tracked. the oldest receipts are removed first
+pub const MAX_TRANSACTION_RECEIPTS: usize = 100_000;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionStatus {
+    /// waiting in the mempool
+    Pending,
+    /// included in a block of the longest chain
+    Included,
+    /// rejected by the mempool or removed from it without being included
+    Dropped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionReceipt {
+    pub status: TransactionStatus,
+    /// block which included the transaction. only set when included
+    pub block_id: BlockId,
+    pub block_hash: SaitoHash,
+    /// why the transaction was dropped
+    pub reason: Option<String>,
+    pub updated_at: Timestamp,
+}
+
+impl TransactionReceipt {
+    fn new(status: TransactionStatus, reason: Option<String>, time: Timestamp) -> Self {
+        TransactionReceipt {
+            status,
+            block_id: 0,
+            block_hash: [0; 32],
+            reason,
+            updated_at: time,
+        }
+    }
+}
+
+/// Fate of the transactions submitted to the node, by signature. Maintained by the consensus thread as transactions
+/// are added to the mempool and blocks are added to or removed from the longest chain
+#[derive(Debug, Clone)]
+pub struct TransactionReceipts {
+    receipts: AHashMap<SaitoSignature, TransactionReceipt>,
+    // signatures in the order they were first tracked
+    order: VecDeque<SaitoSignature>,
+    latest_block_id: BlockId,
+    max_receipts: usize,
+}
+
+impl Default for TransactionReceipts {
+    fn default() -> Self {
+        TransactionReceipts::new(MAX_TRANSACTION_RECEIPTS)
+    }
+}
+
+impl TransactionReceipts {
+    pub fn new(max_receipts: usize) -> TransactionReceipts {
+        TransactionReceipts {
+            receipts: Default::default(),
+            order: Default::default(),
+            latest_block_id: 0,
+            max_receipts,
+        }
+    }
+
+    pub fn get_receipt(&self, signature: &SaitoSignature) -> Option<&TransactionReceipt> {
+        self.receipts.get(signature)
+    }
+
+    pub fn len(&self) -> usize {
+        self.receipts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.receipts.is_empty()
+    }
+
+    /// number of blocks in the longest chain from the including block to the tip. 0 if not included
+    pub fn get_confirmations(&self, receipt: &TransactionReceipt) -> u64 {
+        if receipt.status != TransactionStatus::Included {
+            return 0;
+        }
+        (self.latest_block_id + 1).saturating_sub(receipt.block_id)
+    }
+
+    fn track(&mut self, signature: SaitoSignature, receipt: TransactionReceipt) {
+        if self.receipts.insert(signature, receipt).is_some() {
+            return;
+        }
+        self.order.push_back(signature);
+        while self.order.len() > self.max_receipts {
+            if let Some(oldest) = self.order.pop_front() {
+                self.receipts.remove(&oldest);
+            }
+        }
+    }
+
+    /// Records the result of adding a submitted transaction to the mempool
+    pub fn on_transaction_added(
+        &mut self,
+        signature: SaitoSignature,
+        result: AddTransactionResult,
+        time: Timestamp,
+    ) {
+        let (status, reason) = match result {
+            AddTransactionResult::Added | AddTransactionResult::Replaced(_) => {
+                (TransactionStatus::Pending, None)
+            }
+            // resubmitting doesn't change the receipt
+            AddTransactionResult::Exists => {
+                if self.receipts.contains_key(&signature) {
+                    return;
+                }
+                (TransactionStatus::Pending, None)
+            }
+            AddTransactionResult::Conflicting => (
+                TransactionStatus::Dropped,
+                Some("conflicts with pending transactions paying higher fees".to_string()),
+            ),
+            AddTransactionResult::Rejected => (
+                TransactionStatus::Dropped,
+                Some("mempool is full".to_string()),
+            ),
+            AddTransactionResult::InvalidNonce => (
+                TransactionStatus::Dropped,
+                Some("invalid nonce".to_string()),
+            ),
+        };
+        if let Some(receipt) = self.receipts.get(&signature) {
+            // a transaction which made it into the chain stays included even if it's received again
+            if receipt.status == TransactionStatus::Included {
+                return;
+            }
+        }
+        self.track(signature, TransactionReceipt::new(status, reason, time));
+    }
+
+    /// Marks the tracked transactions of the block as included. Called when the block is added to the longest chain
+    pub fn on_block_added_to_longest_chain(&mut self, block: &Block, time: Timestamp) {
+        for transaction in block.transactions.iter() {
+            if let Some(receipt) = self.receipts.get_mut(&transaction.signature) {
+                receipt.status = TransactionStatus::Included;
+                receipt.block_id = block.id;
+                receipt.block_hash = block.hash;
+                receipt.reason = None;
+                receipt.updated_at = time;
+            }
+        }
+    }
+
+    /// Transactions of a block removed from the longest chain are pending until they are included in the new chain
+    /// or found missing from the mempool
+    pub fn on_block_removed_from_longest_chain(&mut self, block: &Block, time: Timestamp) {
+        for transaction in block.transactions.iter() {
+            if let Some(receipt) = self.receipts.get_mut(&transaction.signature) {
+                if receipt.block_hash == block.hash {
+                    *receipt = TransactionReceipt::new(TransactionStatus::Pending, None, time);
+                }
+            }
+        }
+    }
+
+    pub fn set_latest_block_id(&mut self, latest_block_id: BlockId) {
+        self.latest_block_id = latest_block_id;
+    }
+
+    /// Drops the pending transactions which are not in the mempool anymore, e.g. replaced by a transaction paying
+    /// higher fees or invalidated by a block. Returns the number of dropped transactions
+    pub fn drop_evicted_transactions(&mut self, mempool: &Mempool, time: Timestamp) -> usize {
+        let mut dropped = 0;
+        for (signature, receipt) in self.receipts.iter_mut() {
+            if receipt.status == TransactionStatus::Pending
+                && !mempool.transactions.contains_key(signature)
+            {
+                *receipt = TransactionReceipt::new(
+                    TransactionStatus::Dropped,
+                    Some("removed from mempool".to_string()),
+                    time,
+                );
+                dropped += 1;
+            }
+        }
+        dropped
+    }
+
+    pub fn to_json(&self, signature: &SaitoSignature) -> Option<String> {
+        let receipt = self.receipts.get(signature)?;
+        Some(
+            serde_json::json!({
+                "signature": hex::encode(signature),
+                "status": receipt.status,
+                "block_id": receipt.block_id,
+                "block_hash": hex::encode(receipt.block_hash),
+                "confirmations": self.get_confirmations(receipt),
+                "reason": receipt.reason,
+                "updated_at": receipt.updated_at,
+            })
+            .to_string(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::block::Block;
+    use crate::core::data::mempool::{AddTransactionResult, Mempool};
+    use crate::core::data::transaction::Transaction;
+    use crate::core::data::transaction_receipts::{TransactionReceipts, TransactionStatus};
+
+    fn create_block(id: u64, hash: u8, signatures: &[u8]) -> Block {
+        let mut block = Block::new();
+        block.id = id;
+        block.hash = [hash; 32];
+        for signature in signatures {
+            let mut transaction = Transaction::default();
+            transaction.signature = [*signature; 64];
+            block.transactions.push(transaction);
+        }
+        block
+    }
+
+    #[test]
+    fn transaction_receipts_test() {
+        let mut receipts = TransactionReceipts::new(10);
+        receipts.on_transaction_added([1; 64], AddTransactionResult::Added, 10);
+        receipts.on_transaction_added([2; 64], AddTransactionResult::Added, 10);
+        receipts.on_transaction_added([3; 64], AddTransactionResult::InvalidNonce, 10);
+        assert_eq!(
+            receipts.get_receipt(&[1; 64]).unwrap().status,
+            TransactionStatus::Pending
+        );
+        assert_eq!(
+            receipts.get_receipt(&[3; 64]).unwrap().reason,
+            Some("invalid nonce".to_string())
+        );
+        assert!(receipts.get_receipt(&[4; 64]).is_none());
+
+        let block = create_block(5, 5, &[1, 4]);
+        receipts.on_block_added_to_longest_chain(&block, 20);
+        receipts.set_latest_block_id(7);
+        let receipt = receipts.get_receipt(&[1; 64]).unwrap();
+        assert_eq!(receipt.status, TransactionStatus::Included);
+        assert_eq!(receipt.block_hash, [5; 32]);
+        assert_eq!(receipts.get_confirmations(receipt), 3);
+        // untracked transactions in blocks are ignored
+        assert!(receipts.get_receipt(&[4; 64]).is_none());
+
+        // [2] is not in the mempool
+        let mempool = Mempool::new([0; 33], [0; 32]);
+        assert_eq!(receipts.drop_evicted_transactions(&mempool, 30), 1);
+        assert_eq!(
+            receipts.get_receipt(&[2; 64]).unwrap().status,
+            TransactionStatus::Dropped
+        );
+
+        receipts.on_block_removed_from_longest_chain(&block, 40);
+        assert_eq!(
+            receipts.get_receipt(&[1; 64]).unwrap().status,
+            TransactionStatus::Pending
+        );
+        receipts.on_block_added_to_longest_chain(&create_block(5, 6, &[1]), 40);
+        let json: serde_json::Value =
+            serde_json::from_str(receipts.to_json(&[1; 64]).unwrap().as_str()).unwrap();
+        assert_eq!(json["status"], "included");
+        assert_eq!(json["block_hash"], hex::encode([6; 32]));
+        assert_eq!(json["confirmations"], 3);
+    }
+
+    #[test]
+    fn transaction_receipts_capacity_test() {
+        let mut receipts = TransactionReceipts::new(2);
+        for index in 0..3 {
+            receipts.on_transaction_added([index; 64], AddTransactionResult::Added, 10);
+        }
+        assert_eq!(receipts.len(), 2);
+        assert!(receipts.get_receipt(&[0; 64]).is_none());
+
+        // resubmitting keeps the included status
+        receipts.on_block_added_to_longest_chain(&create_block(1, 1, &[2]), 20);
+        receipts.on_transaction_added([2; 64], AddTransactionResult::Added, 30);
+        assert_eq!(
+            receipts.get_receipt(&[2; 64]).unwrap().status,
+            TransactionStatus::Included
+        );
+    }
+}
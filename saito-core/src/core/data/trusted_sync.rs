@@ -0,0 +1,428 @@
+use std::io::{Error, ErrorKind};
+
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::common::defs::{
+    BlockId, PeerIndex, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature, SaitoUTXOSetKey,
+};
+use crate::core::data::block::BlockType;
+use crate::core::data::blockchain::{Blockchain, PRUNE_AFTER_BLOCKS};
+use crate::core::data::crypto::{hash, sign, verify};
+use crate::core::data::serialize::Serialize;
+
+/// number of recent blocks sent with a snapshot. kept below the pruning depth, so the blocks are still in memory in
+/// full when the snapshot is created
+pub const TRUSTED_SYNC_BLOCK_COUNT: u64 = PRUNE_AFTER_BLOCKS - 1;
+
+const UTXO_KEY_SIZE: usize = 66;
+
+/// Syncing from a utxoset snapshot of another node run by the same operator, instead of validating the chain from the
+/// genesis block
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct TrustedSyncConfig {
+    /// hex encoded public keys of the nodes allowed to request snapshots from this node. snapshots are only accepted
+    /// from these nodes
+    pub trusted_keys: Vec<String>,
+    /// requests a snapshot from a trusted peer when the node starts with an empty chain
+    pub request_snapshot: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustedSyncError {
+    /// snapshot wasn't requested from the peer, or was already applied
+    UnexpectedSnapshot,
+    UntrustedPeer,
+    InvalidSnapshot,
+    InvalidSignature,
+    /// snapshots can only be applied to an empty chain
+    ChainNotEmpty,
+}
+
+/// Spendable slips of a node's longest chain before its recent blocks. The receiver loads the utxoset and then
+/// fetches and validates the recent blocks on top of it, so the blocks are applied to the utxoset as usual.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UtxoSnapshot {
+    pub latest_block_id: BlockId,
+    /// recent longest chain blocks, oldest first. the last one is the source's latest block
+    pub block_hashes: Vec<SaitoHash>,
+    /// spendable slips before the first of the recent blocks, sorted
+    pub utxo_keys: Vec<SaitoUTXOSetKey>,
+    /// signed by the source node over the block hashes and the utxoset hash
+    pub signature: SaitoSignature,
+}
+
+impl UtxoSnapshot {
+    /// Creates the snapshot by unwinding the recent blocks from a copy of the utxoset. Returns None if the chain is
+    /// empty or the recent blocks are not in memory in full.
+    pub fn create(blockchain: &Blockchain, block_count: u64) -> Option<UtxoSnapshot> {
+        let latest_block_id = blockchain.get_latest_block_id();
+        if latest_block_id == 0 || block_count == 0 {
+            return None;
+        }
+        let first_block_id = latest_block_id.saturating_sub(block_count - 1).max(1);
+        let mut utxoset = blockchain.utxoset.clone();
+        let mut block_hashes = vec![];
+        for block_id in (first_block_id..=latest_block_id).rev() {
+            let block_hash = blockchain
+                .blockring
+                .get_longest_chain_block_hash_by_block_id(block_id);
+            let block = blockchain.blocks.get(&block_hash)?;
+            if block.block_type != BlockType::Full {
+                warn!(
+                    "block : {:?} is not full. cannot create the utxoset snapshot",
+                    block_id
+                );
+                return None;
+            }
+            for transaction in block.transactions.iter() {
+                transaction.on_chain_reorganization(&mut utxoset, false, block_id);
+            }
+            block_hashes.push(block_hash);
+        }
+        block_hashes.reverse();
+
+        let mut utxo_keys: Vec<SaitoUTXOSetKey> = utxoset
+            .into_iter()
+            .filter(|(_, spendable)| *spendable)
+            .map(|(key, _)| key)
+            .collect();
+        utxo_keys.sort_unstable();
+
+        Some(UtxoSnapshot {
+            latest_block_id,
+            block_hashes,
+            utxo_keys,
+            signature: [0; 64],
+        })
+    }
+
+    pub fn get_first_block_id(&self) -> BlockId {
+        self.latest_block_id + 1 - self.block_hashes.len() as u64
+    }
+
+    pub fn get_utxoset_hash(&self) -> SaitoHash {
+        hash(&self.utxo_keys.concat())
+    }
+
+    fn get_hash_for_signature(&self) -> SaitoHash {
+        hash(
+            &[
+                self.latest_block_id.to_be_bytes().as_slice(),
+                self.block_hashes.concat().as_slice(),
+                self.get_utxoset_hash().as_slice(),
+            ]
+            .concat(),
+        )
+    }
+
+    pub fn sign(&mut self, private_key: &SaitoPrivateKey) {
+        self.signature = sign(&self.get_hash_for_signature(), private_key);
+    }
+
+    pub fn verify(&self, public_key: &SaitoPublicKey) -> bool {
+        verify(&self.get_hash_for_signature(), &self.signature, public_key)
+    }
+
+    /// Loads the utxoset into an empty chain. The recent blocks should be added after this
+    pub fn apply(&self, blockchain: &mut Blockchain) -> Result<(), TrustedSyncError> {
+        if !blockchain.blocks.is_empty() {
+            return Err(TrustedSyncError::ChainNotEmpty);
+        }
+        blockchain.utxoset.clear();
+        blockchain
+            .utxoset
+            .extend(self.utxo_keys.iter().map(|key| (*key, true)));
+        Ok(())
+    }
+}
+
+impl Serialize<Self> for UtxoSnapshot {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        buffer.extend(self.latest_block_id.to_be_bytes());
+        buffer.extend((self.block_hashes.len() as u32).to_be_bytes());
+        buffer.extend(self.block_hashes.concat());
+        buffer.extend((self.utxo_keys.len() as u64).to_be_bytes());
+        buffer.extend(self.utxo_keys.concat());
+        buffer.extend(self.signature);
+        buffer
+    }
+
+    fn deserialize(buffer: &Vec<u8>) -> Result<Self, Error> {
+        if buffer.len() < 12 {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let latest_block_id = u64::from_be_bytes(buffer[0..8].try_into().unwrap());
+        let block_count = u32::from_be_bytes(buffer[8..12].try_into().unwrap()) as usize;
+        let keys_start = 12 + block_count * 32;
+        if buffer.len() < keys_start + 8 {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        let key_count = u64::from_be_bytes(buffer[keys_start..keys_start + 8].try_into().unwrap());
+        let signature_start = (key_count as usize)
+            .checked_mul(UTXO_KEY_SIZE)
+            .and_then(|size| size.checked_add(keys_start + 8))
+            .ok_or_else(|| Error::from(ErrorKind::InvalidData))?;
+        if buffer.len() != signature_start + 64 {
+            return Err(Error::from(ErrorKind::InvalidData));
+        }
+        Ok(UtxoSnapshot {
+            latest_block_id,
+            block_hashes: buffer[12..keys_start]
+                .chunks_exact(32)
+                .map(|chunk| chunk.try_into().unwrap())
+                .collect(),
+            utxo_keys: buffer[keys_start + 8..signature_start]
+                .chunks_exact(UTXO_KEY_SIZE)
+                .map(|chunk| chunk.try_into().unwrap())
+                .collect(),
+            signature: buffer[signature_start..].try_into().unwrap(),
+        })
+    }
+}
+
+/// Tracks the snapshot request of a node syncing from a trusted peer
+#[derive(Debug, Default)]
+pub struct TrustedSync {
+    trusted_keys: Vec<SaitoPublicKey>,
+    request_snapshot: bool,
+    requested_from: Option<PeerIndex>,
+    snapshot_applied: bool,
+}
+
+impl TrustedSync {
+    pub fn new() -> TrustedSync {
+        TrustedSync::default()
+    }
+
+    pub fn set_config(&mut self, config: &TrustedSyncConfig) {
+        self.trusted_keys = config
+            .trusted_keys
+            .iter()
+            .filter_map(|key| {
+                let public_key: Option<SaitoPublicKey> = hex::decode(key)
+                    .ok()
+                    .and_then(|buffer| buffer.try_into().ok());
+                if public_key.is_none() {
+                    warn!("trusted sync key : {:?} is not valid", key);
+                }
+                public_key
+            })
+            .collect();
+        self.request_snapshot = config.request_snapshot;
+    }
+
+    pub fn is_trusted(&self, public_key: &SaitoPublicKey) -> bool {
+        self.trusted_keys.contains(public_key)
+    }
+
+    /// Whether the node should wait for a snapshot instead of syncing the chain from its peers
+    pub fn is_waiting_for_snapshot(&self, latest_block_id: BlockId) -> bool {
+        self.request_snapshot && !self.snapshot_applied && latest_block_id == 0
+    }
+
+    /// The snapshot is requested from the first trusted peer which completes the handshake
+    pub fn should_request_from(&self, public_key: &SaitoPublicKey) -> bool {
+        self.requested_from.is_none() && self.is_trusted(public_key)
+    }
+
+    pub fn on_snapshot_requested(&mut self, peer_index: PeerIndex) {
+        info!("requesting utxoset snapshot from peer : {:?}", peer_index);
+        self.requested_from = Some(peer_index);
+    }
+
+    pub fn on_peer_disconnected(&mut self, peer_index: PeerIndex) {
+        if self.requested_from == Some(peer_index) && !self.snapshot_applied {
+            warn!(
+                "peer : {:?} disconnected before sending the snapshot",
+                peer_index
+            );
+            self.requested_from = None;
+        }
+    }
+
+    pub fn validate_snapshot(
+        &self,
+        snapshot: &UtxoSnapshot,
+        peer_index: PeerIndex,
+        public_key: &SaitoPublicKey,
+    ) -> Result<(), TrustedSyncError> {
+        if self.snapshot_applied || self.requested_from != Some(peer_index) {
+            return Err(TrustedSyncError::UnexpectedSnapshot);
+        }
+        if !self.is_trusted(public_key) {
+            return Err(TrustedSyncError::UntrustedPeer);
+        }
+        if snapshot.block_hashes.is_empty()
+            || snapshot.block_hashes.len() as u64 > snapshot.latest_block_id
+        {
+            return Err(TrustedSyncError::InvalidSnapshot);
+        }
+        if !snapshot.verify(public_key) {
+            return Err(TrustedSyncError::InvalidSignature);
+        }
+        Ok(())
+    }
+
+    pub fn on_snapshot_applied(&mut self) {
+        self.snapshot_applied = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::defs::{push_lock, SaitoUTXOSetKey, LOCK_ORDER_BLOCKCHAIN};
+    use crate::common::test_manager::test::TestManager;
+    use crate::core::data::block::Block;
+    use crate::core::data::blockchain::Blockchain;
+    use crate::core::data::crypto::generate_keys;
+    use crate::core::data::serialize::Serialize;
+    use crate::core::data::trusted_sync::{
+        TrustedSync, TrustedSyncConfig, TrustedSyncError, UtxoSnapshot,
+    };
+    use crate::lock_for_read;
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn utxo_snapshot_test() {
+        let mut t = TestManager::new();
+        t.initialize_with_timestamp(100, 1_000_000_000, 10_000_000)
+            .await;
+        for _ in 0..3 {
+            let latest_block_hash;
+            let ts;
+            {
+                let (blockchain, _blockchain_) =
+                    lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+                let latest_block = blockchain.get_latest_block().unwrap();
+                latest_block_hash = latest_block.hash;
+                ts = latest_block.timestamp;
+            }
+            let mut block = t
+                .create_block(latest_block_hash, ts + 120000, 0, 0, 0, true)
+                .await;
+            block.generate();
+            t.add_block(block).await;
+        }
+
+        let (blockchain, _blockchain_) = lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+        assert_eq!(blockchain.get_latest_block_id(), 4);
+        let mut snapshot = UtxoSnapshot::create(&blockchain, 2).unwrap();
+        assert_eq!(snapshot.latest_block_id, 4);
+        assert_eq!(snapshot.get_first_block_id(), 3);
+        assert_eq!(
+            snapshot.block_hashes,
+            vec![
+                blockchain
+                    .blockring
+                    .get_longest_chain_block_hash_by_block_id(3),
+                blockchain
+                    .blockring
+                    .get_longest_chain_block_hash_by_block_id(4)
+            ]
+        );
+
+        // winding the recent blocks on top of the snapshot gives the current utxoset
+        let mut new_blockchain = Blockchain::new(t.wallet_lock.clone());
+        snapshot.apply(&mut new_blockchain).unwrap();
+        for block_hash in snapshot.block_hashes.iter() {
+            let mut block = blockchain.blocks.get(block_hash).unwrap().clone();
+            block.on_chain_reorganization(&mut new_blockchain.utxoset, true);
+        }
+        let get_spendable_keys = |blockchain: &Blockchain| {
+            let mut keys: Vec<SaitoUTXOSetKey> = blockchain
+                .utxoset
+                .iter()
+                .filter(|(_, spendable)| **spendable)
+                .map(|(key, _)| *key)
+                .collect();
+            keys.sort_unstable();
+            keys
+        };
+        assert_eq!(
+            get_spendable_keys(&new_blockchain),
+            get_spendable_keys(&blockchain)
+        );
+
+        let (public_key, private_key) = generate_keys();
+        snapshot.sign(&private_key);
+        assert!(snapshot.verify(&public_key));
+        let buffer = snapshot.serialize();
+        let new_snapshot = UtxoSnapshot::deserialize(&buffer).unwrap();
+        assert_eq!(new_snapshot, snapshot);
+        assert!(UtxoSnapshot::deserialize(&buffer[..buffer.len() - 1].to_vec()).is_err());
+
+        let mut tampered_snapshot = snapshot.clone();
+        tampered_snapshot.utxo_keys.pop();
+        assert!(!tampered_snapshot.verify(&public_key));
+
+        // only applied to an empty chain
+        let mut non_empty_blockchain = Blockchain::new(t.wallet_lock.clone());
+        non_empty_blockchain.blocks.insert([1; 32], Block::new());
+        assert_eq!(
+            snapshot.apply(&mut non_empty_blockchain),
+            Err(TrustedSyncError::ChainNotEmpty)
+        );
+    }
+
+    #[test]
+    fn trusted_sync_test() {
+        let (trusted_key, private_key) = generate_keys();
+        let (other_key, other_private_key) = generate_keys();
+        let mut trusted_sync = TrustedSync::new();
+        trusted_sync.set_config(&TrustedSyncConfig {
+            trusted_keys: vec![hex::encode(trusted_key), "invalid".to_string()],
+            request_snapshot: true,
+        });
+        assert!(trusted_sync.is_waiting_for_snapshot(0));
+        assert!(!trusted_sync.is_waiting_for_snapshot(10));
+        assert!(trusted_sync.should_request_from(&trusted_key));
+        assert!(!trusted_sync.should_request_from(&other_key));
+
+        let mut snapshot = UtxoSnapshot {
+            latest_block_id: 10,
+            block_hashes: vec![[1; 32], [2; 32]],
+            utxo_keys: vec![[3; 66]],
+            signature: [0; 64],
+        };
+        snapshot.sign(&private_key);
+        assert_eq!(
+            trusted_sync.validate_snapshot(&snapshot, 1, &trusted_key),
+            Err(TrustedSyncError::UnexpectedSnapshot)
+        );
+
+        trusted_sync.on_snapshot_requested(1);
+        assert!(!trusted_sync.should_request_from(&trusted_key));
+        assert_eq!(
+            trusted_sync.validate_snapshot(&snapshot, 2, &trusted_key),
+            Err(TrustedSyncError::UnexpectedSnapshot)
+        );
+        assert_eq!(
+            trusted_sync.validate_snapshot(&snapshot, 1, &other_key),
+            Err(TrustedSyncError::UntrustedPeer)
+        );
+        let mut other_snapshot = snapshot.clone();
+        other_snapshot.sign(&other_private_key);
+        assert_eq!(
+            trusted_sync.validate_snapshot(&other_snapshot, 1, &trusted_key),
+            Err(TrustedSyncError::InvalidSignature)
+        );
+        assert_eq!(
+            trusted_sync.validate_snapshot(&snapshot, 1, &trusted_key),
+            Ok(())
+        );
+
+        trusted_sync.on_peer_disconnected(1);
+        assert!(trusted_sync.should_request_from(&trusted_key));
+
+        trusted_sync.on_snapshot_requested(1);
+        trusted_sync.on_snapshot_applied();
+        assert!(!trusted_sync.is_waiting_for_snapshot(0));
+        assert_eq!(
+            trusted_sync.validate_snapshot(&snapshot, 1, &trusted_key),
+            Err(TrustedSyncError::UnexpectedSnapshot)
+        );
+    }
+}
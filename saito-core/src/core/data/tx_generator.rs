@@ -0,0 +1,203 @@
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::common::defs::{Currency, SaitoPublicKey, Timestamp};
+use crate::core::data::transaction::Transaction;
+use crate::core::data::wallet::Wallet;
+
+/// Configuration for generating test transactions inside the node
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct TxGeneratorConfig {
+    /// whether the generator starts with the node. can be started and stopped at runtime later
+    pub enabled: bool,
+    pub interval_in_ms: u64,
+    pub txs_per_interval: u64,
+    pub bytes_per_tx: u64,
+    pub payment: Currency,
+    pub fee: Currency,
+    /// hex encoded message for the generated transactions. random bytes of `bytes_per_tx` length are used if not provided
+    pub payload: Option<String>,
+    /// hex encoded public keys to send the payments to. uses the node's own key if empty
+    pub target_addresses: Vec<String>,
+}
+
+impl Default for TxGeneratorConfig {
+    fn default() -> Self {
+        TxGeneratorConfig {
+            enabled: false,
+            interval_in_ms: 1_000,
+            txs_per_interval: 10,
+            bytes_per_tx: 1024,
+            payment: 5000,
+            fee: 5000,
+            payload: None,
+            target_addresses: vec![],
+        }
+    }
+}
+
+/// Generates test transactions at a configured rate. Used by saito-rust and saito-spammer nodes instead of
+/// hard coded test transactions.
+#[derive(Debug)]
+pub struct TransactionGenerator {
+    config: TxGeneratorConfig,
+    running: bool,
+    timer: Timestamp,
+    payload: Option<Vec<u8>>,
+    target_keys: Vec<SaitoPublicKey>,
+    next_target_index: usize,
+}
+
+impl TransactionGenerator {
+    pub fn new(config: TxGeneratorConfig) -> TransactionGenerator {
+        let payload = config.payload.as_ref().and_then(|payload| {
+            let result = hex::decode(payload);
+            if result.is_err() {
+                warn!("tx generator payload : {:?} is not valid hex", payload);
+            }
+            result.ok()
+        });
+        let target_keys = config
+            .target_addresses
+            .iter()
+            .filter_map(|address| {
+                let key: Option<SaitoPublicKey> = hex::decode(address)
+                    .ok()
+                    .and_then(|buffer| buffer.try_into().ok());
+                if key.is_none() {
+                    warn!("tx generator target address : {:?} is not valid", address);
+                }
+                key
+            })
+            .collect();
+        TransactionGenerator {
+            running: config.enabled,
+            config,
+            timer: 0,
+            payload,
+            target_keys,
+            next_target_index: 0,
+        }
+    }
+
+    pub fn start(&mut self) {
+        info!("starting transaction generator");
+        self.running = true;
+        self.timer = 0;
+    }
+
+    pub fn stop(&mut self) {
+        info!("stopping transaction generator");
+        self.running = false;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Returns true when it's time to generate the next batch of transactions
+    pub fn on_timer(&mut self, duration_in_ms: Timestamp) -> bool {
+        if !self.running {
+            return false;
+        }
+        self.timer += duration_in_ms;
+        if self.timer < self.config.interval_in_ms {
+            return false;
+        }
+        self.timer = 0;
+        true
+    }
+
+    fn get_next_target(&mut self, own_public_key: &SaitoPublicKey) -> SaitoPublicKey {
+        if self.target_keys.is_empty() {
+            return *own_public_key;
+        }
+        let key = self.target_keys[self.next_target_index % self.target_keys.len()];
+        self.next_target_index = (self.next_target_index + 1) % self.target_keys.len();
+        key
+    }
+
+    fn create_payload(&self) -> Vec<u8> {
+        match self.payload.as_ref() {
+            Some(payload) => payload.clone(),
+            None => (0..self.config.bytes_per_tx)
+                .into_iter()
+                .map(|_| rand::random::<u8>())
+                .collect(),
+        }
+    }
+
+    /// Creates a batch of signed transactions from the wallet's funds. Stops early if the wallet runs out of funds.
+    pub fn generate_transactions(&mut self, wallet: &mut Wallet) -> Vec<Transaction> {
+        let public_key = wallet.public_key;
+        let private_key = wallet.private_key;
+        let mut transactions = vec![];
+
+        for _ in 0..self.config.txs_per_interval {
+            if wallet.get_available_balance() < self.config.payment + self.config.fee {
+                warn!(
+                    "not enough balance to generate transactions. generated : {:?}",
+                    transactions.len()
+                );
+                break;
+            }
+            let to_public_key = self.get_next_target(&public_key);
+            let mut transaction =
+                Transaction::create(wallet, to_public_key, self.config.payment, self.config.fee);
+            transaction.message = self.create_payload();
+            transaction.generate(&public_key, 0, 0);
+            transaction.sign(&private_key);
+            transaction.add_hop(&private_key, &public_key, &public_key);
+
+            transactions.push(transaction);
+        }
+        transactions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::data::tx_generator::{TransactionGenerator, TxGeneratorConfig};
+
+    #[test]
+    fn tx_generator_timer_test() {
+        let mut generator = TransactionGenerator::new(TxGeneratorConfig {
+            enabled: false,
+            interval_in_ms: 1000,
+            ..Default::default()
+        });
+        assert!(!generator.on_timer(2000));
+
+        generator.start();
+        assert!(generator.is_running());
+        assert!(!generator.on_timer(600));
+        assert!(generator.on_timer(600));
+        assert!(!generator.on_timer(600));
+
+        generator.stop();
+        assert!(!generator.on_timer(2000));
+    }
+
+    #[test]
+    fn tx_generator_targets_test() {
+        let own_key = [9; 33];
+        let mut generator = TransactionGenerator::new(TxGeneratorConfig {
+            target_addresses: vec![
+                hex::encode([1; 33]),
+                "invalid".to_string(),
+                hex::encode([2; 33]),
+            ],
+            payload: Some("0a0b".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(generator.get_next_target(&own_key), [1; 33]);
+        assert_eq!(generator.get_next_target(&own_key), [2; 33]);
+        assert_eq!(generator.get_next_target(&own_key), [1; 33]);
+        assert_eq!(generator.create_payload(), vec![10, 11]);
+
+        let mut generator = TransactionGenerator::new(Default::default());
+        assert_eq!(generator.get_next_target(&own_key), own_key);
+        assert_eq!(generator.create_payload().len(), 1024);
+    }
+}
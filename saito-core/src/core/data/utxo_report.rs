@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use ahash::AHashMap;
+
+use crate::common::defs::{BlockId, Currency, SaitoPublicKey, UtxoSet};
+use crate::core::data::slip::Slip;
+
+/// slips below this amount are counted as dust if no threshold is given
+pub const DEFAULT_DUST_THRESHOLD: Currency = 1_000;
+/// upper bounds (inclusive) of the slips per address buckets
+const SLIPS_PER_ADDRESS_BUCKETS: [u64; 5] = [1, 5, 10, 100, 1000];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UtxoReportFormat {
+    Csv,
+    Json,
+}
+
+impl UtxoReportFormat {
+    /// json is used for ".json" files, csv otherwise
+    pub fn from_path(path: &str) -> UtxoReportFormat {
+        if path.to_lowercase().ends_with(".json") {
+            UtxoReportFormat::Json
+        } else {
+            UtxoReportFormat::Csv
+        }
+    }
+}
+
+/// Request to write the utxo report to a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UtxoReportRequest {
+    pub path: String,
+    pub format: UtxoReportFormat,
+    pub dust_threshold: Currency,
+}
+
+impl UtxoReportRequest {
+    pub fn new(path: String, dust_threshold: Currency) -> UtxoReportRequest {
+        UtxoReportRequest {
+            format: UtxoReportFormat::from_path(path.as_str()),
+            path,
+            dust_threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistogramBucket {
+    pub label: String,
+    pub count: u64,
+    pub value: Currency,
+}
+
+/// Distribution of the spendable slips in the utxoset
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtxoReport {
+    pub block_id: BlockId,
+    pub slip_count: u64,
+    pub address_count: u64,
+    pub total_value: Currency,
+    pub dust_threshold: Currency,
+    pub dust_count: u64,
+    pub dust_value: Currency,
+    /// 0 when all addresses hold the same amount, approaching 1 when a single address holds everything
+    pub gini_coefficient: f64,
+    /// number of addresses by the number of slips they hold
+    pub slips_per_address: Vec<HistogramBucket>,
+    /// number of slips by the order of magnitude of their amount
+    pub value_buckets: Vec<HistogramBucket>,
+}
+
+impl UtxoReport {
+    pub fn generate(utxoset: &UtxoSet, block_id: BlockId, dust_threshold: Currency) -> UtxoReport {
+        let mut holdings: AHashMap<SaitoPublicKey, (u64, Currency)> = Default::default();
+        let mut value_buckets: BTreeMap<u32, (u64, Currency)> = Default::default();
+        let mut report = UtxoReport {
+            block_id,
+            slip_count: 0,
+            address_count: 0,
+            total_value: 0,
+            dust_threshold,
+            dust_count: 0,
+            dust_value: 0,
+            gini_coefficient: 0.0,
+            slips_per_address: vec![],
+            value_buckets: vec![],
+        };
+
+        for (key, spendable) in utxoset.iter() {
+            if !*spendable {
+                continue;
+            }
+            let amount = Slip::get_amount_from_utxoset_key(key);
+            let public_key: SaitoPublicKey = key[0..33].try_into().unwrap();
+
+            report.slip_count += 1;
+            report.total_value += amount;
+            if amount < dust_threshold {
+                report.dust_count += 1;
+                report.dust_value += amount;
+            }
+            let holding = holdings.entry(public_key).or_default();
+            holding.0 += 1;
+            holding.1 += amount;
+
+            let bucket = value_buckets
+                .entry(Self::get_magnitude(amount))
+                .or_default();
+            bucket.0 += 1;
+            bucket.1 += amount;
+        }
+        report.address_count = holdings.len() as u64;
+
+        let mut slips_per_address: Vec<HistogramBucket> = SLIPS_PER_ADDRESS_BUCKETS
+            .iter()
+            .enumerate()
+            .map(|(index, upper)| {
+                let lower = if index == 0 {
+                    1
+                } else {
+                    SLIPS_PER_ADDRESS_BUCKETS[index - 1] + 1
+                };
+                let label = if lower == *upper {
+                    lower.to_string()
+                } else {
+                    format!("{}-{}", lower, upper)
+                };
+                HistogramBucket {
+                    label,
+                    count: 0,
+                    value: 0,
+                }
+            })
+            .collect();
+        slips_per_address.push(HistogramBucket {
+            label: format!("{}+", SLIPS_PER_ADDRESS_BUCKETS.last().unwrap() + 1),
+            count: 0,
+            value: 0,
+        });
+        for (slip_count, value) in holdings.values() {
+            let index = SLIPS_PER_ADDRESS_BUCKETS
+                .iter()
+                .position(|upper| slip_count <= upper)
+                .unwrap_or(SLIPS_PER_ADDRESS_BUCKETS.len());
+            slips_per_address[index].count += 1;
+            slips_per_address[index].value += value;
+        }
+        report.slips_per_address = slips_per_address;
+
+        report.value_buckets = value_buckets
+            .into_iter()
+            .map(|(magnitude, (count, value))| HistogramBucket {
+                label: Self::get_magnitude_label(magnitude),
+                count,
+                value,
+            })
+            .collect();
+
+        let mut balances: Vec<Currency> = holdings.values().map(|(_, value)| *value).collect();
+        report.gini_coefficient = Self::calculate_gini_coefficient(&mut balances);
+
+        report
+    }
+
+    /// number of decimal digits in the amount. 0 for zero amounts
+    fn get_magnitude(amount: Currency) -> u32 {
+        let mut magnitude = 0;
+        let mut amount = amount;
+        while amount > 0 {
+            amount /= 10;
+            magnitude += 1;
+        }
+        magnitude
+    }
+
+    fn get_magnitude_label(magnitude: u32) -> String {
+        if magnitude == 0 {
+            return "0".to_string();
+        }
+        let base: Currency = 10;
+        let lower = base.pow(magnitude - 1);
+        match base.checked_pow(magnitude) {
+            Some(upper) => format!("{}-{}", lower, upper - 1),
+            None => format!("{}+", lower),
+        }
+    }
+
+    fn calculate_gini_coefficient(balances: &mut [Currency]) -> f64 {
+        let total: f64 = balances.iter().map(|balance| *balance as f64).sum();
+        if balances.is_empty() || total == 0.0 {
+            return 0.0;
+        }
+        balances.sort_unstable();
+        let count = balances.len() as f64;
+        let weighted_sum: f64 = balances
+            .iter()
+            .enumerate()
+            .map(|(index, balance)| (index + 1) as f64 * *balance as f64)
+            .sum();
+        (2.0 * weighted_sum) / (count * total) - (count + 1.0) / count
+    }
+
+    pub fn serialize(&self, format: UtxoReportFormat) -> Vec<u8> {
+        match format {
+            UtxoReportFormat::Csv => self.to_csv(),
+            UtxoReportFormat::Json => self.to_json(),
+        }
+        .into_bytes()
+    }
+
+    /// summary metrics followed by the histograms as "section,bucket,count,value" rows
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        writeln!(csv, "metric,value").unwrap();
+        writeln!(csv, "block_id,{}", self.block_id).unwrap();
+        writeln!(csv, "slip_count,{}", self.slip_count).unwrap();
+        writeln!(csv, "address_count,{}", self.address_count).unwrap();
+        writeln!(csv, "total_value,{}", self.total_value).unwrap();
+        writeln!(csv, "dust_threshold,{}", self.dust_threshold).unwrap();
+        writeln!(csv, "dust_count,{}", self.dust_count).unwrap();
+        writeln!(csv, "dust_value,{}", self.dust_value).unwrap();
+        writeln!(csv, "gini_coefficient,{:.6}", self.gini_coefficient).unwrap();
+        writeln!(csv).unwrap();
+        writeln!(csv, "section,bucket,count,value").unwrap();
+        for bucket in self.slips_per_address.iter() {
+            writeln!(
+                csv,
+                "slips_per_address,{},{},{}",
+                bucket.label, bucket.count, bucket.value
+            )
+            .unwrap();
+        }
+        for bucket in self.value_buckets.iter() {
+            writeln!(
+                csv,
+                "slip_value,{},{},{}",
+                bucket.label, bucket.count, bucket.value
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    fn buckets_to_json(buckets: &[HistogramBucket]) -> String {
+        let buckets: Vec<String> = buckets
+            .iter()
+            .map(|bucket| {
+                format!(
+                    "{{\"bucket\":\"{}\",\"count\":{},\"value\":\"{}\"}}",
+                    bucket.label, bucket.count, bucket.value
+                )
+            })
+            .collect();
+        format!("[{}]", buckets.join(","))
+    }
+
+    /// amounts are written as strings since they can exceed the integer range of json parsers
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"block_id\":{},\"slip_count\":{},\"address_count\":{},\"total_value\":\"{}\",\
+            \"dust_threshold\":\"{}\",\"dust_count\":{},\"dust_value\":\"{}\",\"gini_coefficient\":{:.6},\
+            \"slips_per_address\":{},\"slip_value\":{}}}",
+            self.block_id,
+            self.slip_count,
+            self.address_count,
+            self.total_value,
+            self.dust_threshold,
+            self.dust_count,
+            self.dust_value,
+            self.gini_coefficient,
+            Self::buckets_to_json(&self.slips_per_address),
+            Self::buckets_to_json(&self.value_buckets)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::defs::UtxoSet;
+    use crate::core::data::slip::Slip;
+    use crate::core::data::utxo_report::{UtxoReport, UtxoReportFormat};
+
+    fn add_slip(utxoset: &mut UtxoSet, public_key: u8, amount: u128, index: u8, spendable: bool) {
+        let mut slip = Slip::default();
+        slip.public_key = [public_key; 33];
+        slip.amount = amount;
+        slip.slip_index = index;
+        slip.generate_utxoset_key();
+        utxoset.insert(slip.utxoset_key, spendable);
+    }
+
+    #[test]
+    fn utxo_report_test() {
+        let mut utxoset = UtxoSet::new();
+        add_slip(&mut utxoset, 1, 500, 0, true);
+        add_slip(&mut utxoset, 1, 1500, 1, true);
+        add_slip(&mut utxoset, 2, 2000, 0, true);
+        add_slip(&mut utxoset, 3, 90_000, 0, false);
+
+        let report = UtxoReport::generate(&utxoset, 10, 1000);
+        assert_eq!(report.block_id, 10);
+        assert_eq!(report.slip_count, 3);
+        assert_eq!(report.address_count, 2);
+        assert_eq!(report.total_value, 4000);
+        assert_eq!(report.dust_count, 1);
+        assert_eq!(report.dust_value, 500);
+        // both addresses hold 2000
+        assert!(report.gini_coefficient.abs() < 1e-9);
+
+        assert_eq!(report.slips_per_address[0].label, "1");
+        assert_eq!(report.slips_per_address[0].count, 1);
+        assert_eq!(report.slips_per_address[1].label, "2-5");
+        assert_eq!(report.slips_per_address[1].count, 1);
+        assert_eq!(report.slips_per_address.last().unwrap().label, "1001+");
+
+        assert_eq!(report.value_buckets.len(), 2);
+        assert_eq!(report.value_buckets[0].label, "100-999");
+        assert_eq!(report.value_buckets[0].count, 1);
+        assert_eq!(report.value_buckets[1].label, "1000-9999");
+        assert_eq!(report.value_buckets[1].value, 3500);
+
+        let csv = String::from_utf8(report.serialize(UtxoReportFormat::Csv)).unwrap();
+        assert!(csv.contains("dust_count,1\n"));
+        assert!(csv.contains("slip_value,1000-9999,2,3500\n"));
+        let json = report.to_json();
+        assert!(json.starts_with("{\"block_id\":10,"));
+        assert!(
+            json.contains("\"slip_value\":[{\"bucket\":\"100-999\",\"count\":1,\"value\":\"500\"}")
+        );
+
+        assert_eq!(
+            UtxoReportFormat::from_path("report.JSON"),
+            UtxoReportFormat::Json
+        );
+        assert_eq!(
+            UtxoReportFormat::from_path("report.csv"),
+            UtxoReportFormat::Csv
+        );
+    }
+
+    #[test]
+    fn gini_coefficient_test() {
+        assert_eq!(UtxoReport::calculate_gini_coefficient(&mut []), 0.0);
+        let gini = UtxoReport::calculate_gini_coefficient(&mut [5, 5, 5]);
+        assert!(gini.abs() < 1e-9);
+        // single holder out of 4 addresses
+        let gini = UtxoReport::calculate_gini_coefficient(&mut [0, 100, 0, 0]);
+        assert!((gini - 0.75).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,310 @@
+use ahash::AHashMap;
+use primitive_types::U256;
+use rayon::prelude::*;
+
+use crate::common::defs::{SaitoHash, SaitoUTXOSetKey};
+use crate::core::data::crypto::hash;
+
+/// number of maps the utxoset is split into
+pub const UTXOSET_SHARD_COUNT: usize = 64;
+/// updates are applied to the shards in parallel only for blocks with more slips than this
+const PARALLEL_UPDATE_THRESHOLD: usize = 1_000;
+
+/// Spendability of the slips by utxoset key, split into `UTXOSET_SHARD_COUNT` maps by key prefix. Reads take a shared
+/// reference so transactions can be validated in parallel with rayon, and the updates of a block are applied to the
+/// shards in parallel when the chain is reorganized.
+///
+/// Each shard keeps the sum (mod 2^256) of the hashes of its spendable keys, so the commitment of the whole set is
+/// updated with the slips instead of hashing a snapshot of it.
+#[derive(Debug, Clone)]
+pub struct UtxoSet {
+    shards: Vec<AHashMap<SaitoUTXOSetKey, bool>>,
+    accumulators: Vec<U256>,
+}
+
+impl Default for UtxoSet {
+    fn default() -> Self {
+        UtxoSet::new()
+    }
+}
+
+impl UtxoSet {
+    pub fn new() -> UtxoSet {
+        UtxoSet::with_capacity(0)
+    }
+
+    /// capacity is the total number of slips, split evenly between the shards
+    pub fn with_capacity(capacity: usize) -> UtxoSet {
+        UtxoSet {
+            shards: (0..UTXOSET_SHARD_COUNT)
+                .map(|_| AHashMap::with_capacity(capacity / UTXOSET_SHARD_COUNT))
+                .collect(),
+            accumulators: vec![U256::zero(); UTXOSET_SHARD_COUNT],
+        }
+    }
+
+    /// The first byte of a compressed public key is only the parity, so the shard is chosen with the second byte
+    pub fn get_shard_index(key: &SaitoUTXOSetKey) -> usize {
+        key[1] as usize % UTXOSET_SHARD_COUNT
+    }
+
+    pub fn get(&self, key: &SaitoUTXOSetKey) -> Option<&bool> {
+        self.shards[Self::get_shard_index(key)].get(key)
+    }
+
+    pub fn contains_key(&self, key: &SaitoUTXOSetKey) -> bool {
+        self.shards[Self::get_shard_index(key)].contains_key(key)
+    }
+
+    fn hash_key(key: &SaitoUTXOSetKey) -> U256 {
+        U256::from_big_endian(&hash(key))
+    }
+
+    fn insert_into_shard(
+        shard: &mut AHashMap<SaitoUTXOSetKey, bool>,
+        accumulator: &mut U256,
+        key: SaitoUTXOSetKey,
+        spendable: bool,
+    ) -> Option<bool> {
+        let previous = shard.insert(key, spendable);
+        if previous.unwrap_or(false) != spendable {
+            let key_hash = Self::hash_key(&key);
+            *accumulator = if spendable {
+                accumulator.overflowing_add(key_hash).0
+            } else {
+                accumulator.overflowing_sub(key_hash).0
+            };
+        }
+        previous
+    }
+
+    pub fn insert(&mut self, key: SaitoUTXOSetKey, spendable: bool) -> Option<bool> {
+        let index = Self::get_shard_index(&key);
+        Self::insert_into_shard(
+            &mut self.shards[index],
+            &mut self.accumulators[index],
+            key,
+            spendable,
+        )
+    }
+
+    pub fn remove(&mut self, key: &SaitoUTXOSetKey) -> Option<bool> {
+        self.remove_entry(key).map(|(_, spendable)| spendable)
+    }
+
+    pub fn remove_entry(&mut self, key: &SaitoUTXOSetKey) -> Option<(SaitoUTXOSetKey, bool)> {
+        let index = Self::get_shard_index(key);
+        let entry = self.shards[index].remove_entry(key);
+        if let Some((_, true)) = entry {
+            self.accumulators[index] = self.accumulators[index]
+                .overflowing_sub(Self::hash_key(key))
+                .0;
+        }
+        entry
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| shard.is_empty())
+    }
+
+    pub fn clear(&mut self) {
+        for shard in self.shards.iter_mut() {
+            shard.clear();
+        }
+        for accumulator in self.accumulators.iter_mut() {
+            *accumulator = U256::zero();
+        }
+    }
+
+    /// Hash committing to the spendable slips. Doesn't depend on the order the slips were added in, so nodes with the
+    /// same utxoset get the same commitment
+    pub fn get_commitment(&self) -> SaitoHash {
+        let sum = self
+            .accumulators
+            .iter()
+            .fold(U256::zero(), |sum, accumulator| {
+                sum.overflowing_add(*accumulator).0
+            });
+        let mut buffer = [0; 32];
+        sum.to_big_endian(&mut buffer);
+        hash(&buffer)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&SaitoUTXOSetKey, &bool)> {
+        self.shards.iter().flat_map(|shard| shard.iter())
+    }
+
+    /// iterates the shards in parallel
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = (&SaitoUTXOSetKey, &bool)> {
+        self.shards.par_iter().flat_map_iter(|shard| shard.iter())
+    }
+
+    /// Sets the spendability of the given slips. Updates of the same key are applied in the given order, so a slip
+    /// created and spent in the same block ends up spent
+    pub fn apply_updates(&mut self, updates: Vec<(SaitoUTXOSetKey, bool)>) {
+        if updates.len() < PARALLEL_UPDATE_THRESHOLD {
+            for (key, spendable) in updates {
+                self.insert(key, spendable);
+            }
+            return;
+        }
+        let mut updates_by_shard: Vec<Vec<(SaitoUTXOSetKey, bool)>> =
+            vec![vec![]; UTXOSET_SHARD_COUNT];
+        for (key, spendable) in updates {
+            updates_by_shard[Self::get_shard_index(&key)].push((key, spendable));
+        }
+        self.shards
+            .par_iter_mut()
+            .zip(self.accumulators.par_iter_mut())
+            .zip(updates_by_shard.into_par_iter())
+            .for_each(|((shard, accumulator), updates)| {
+                for (key, spendable) in updates {
+                    Self::insert_into_shard(shard, accumulator, key, spendable);
+                }
+            });
+    }
+}
+
+impl Extend<(SaitoUTXOSetKey, bool)> for UtxoSet {
+    fn extend<T: IntoIterator<Item = (SaitoUTXOSetKey, bool)>>(&mut self, iter: T) {
+        for (key, spendable) in iter {
+            self.insert(key, spendable);
+        }
+    }
+}
+
+impl IntoIterator for UtxoSet {
+    type Item = (SaitoUTXOSetKey, bool);
+    type IntoIter = std::iter::Flatten<std::vec::IntoIter<AHashMap<SaitoUTXOSetKey, bool>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.shards.into_iter().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::prelude::*;
+
+    use crate::common::defs::SaitoUTXOSetKey;
+    use crate::core::data::utxoset::{UtxoSet, PARALLEL_UPDATE_THRESHOLD, UTXOSET_SHARD_COUNT};
+
+    fn create_key(index: u32) -> SaitoUTXOSetKey {
+        let mut key = [0; 66];
+        key[1] = (index % 256) as u8;
+        key[62..66].copy_from_slice(&index.to_be_bytes());
+        key
+    }
+
+    #[test]
+    fn utxoset_test() {
+        let mut utxoset = UtxoSet::new();
+        assert!(utxoset.is_empty());
+        assert_eq!(utxoset.get(&create_key(1)), None);
+
+        for index in 0..1000 {
+            utxoset.insert(create_key(index), index % 2 == 0);
+        }
+        assert_eq!(utxoset.len(), 1000);
+        assert_eq!(utxoset.get(&create_key(2)), Some(&true));
+        assert_eq!(utxoset.get(&create_key(3)), Some(&false));
+        assert!(utxoset.contains_key(&create_key(999)));
+        assert_eq!(utxoset.insert(create_key(3), true), Some(false));
+        assert_eq!(
+            utxoset.iter().filter(|(_, spendable)| **spendable).count(),
+            501
+        );
+        assert_eq!(
+            utxoset
+                .par_iter()
+                .filter(|(_, spendable)| **spendable)
+                .count(),
+            501
+        );
+
+        assert_eq!(utxoset.remove(&create_key(3)), Some(true));
+        assert_eq!(
+            utxoset.remove_entry(&create_key(4)),
+            Some((create_key(4), true))
+        );
+        assert_eq!(utxoset.len(), 998);
+
+        let cloned = utxoset.clone();
+        let mut keys: Vec<SaitoUTXOSetKey> = cloned.into_iter().map(|(key, _)| key).collect();
+        keys.sort_unstable();
+        assert_eq!(keys.len(), 998);
+
+        utxoset.clear();
+        assert!(utxoset.is_empty());
+        utxoset.extend(keys.into_iter().map(|key| (key, true)));
+        assert_eq!(utxoset.len(), 998);
+        assert_eq!(
+            UtxoSet::get_shard_index(&create_key(UTXOSET_SHARD_COUNT as u32 + 1)),
+            1
+        );
+    }
+
+    #[test]
+    fn utxoset_commitment_test() {
+        let mut utxoset = UtxoSet::new();
+        let empty = utxoset.get_commitment();
+        utxoset.insert(create_key(1), true);
+        utxoset.insert(create_key(2), true);
+        let commitment = utxoset.get_commitment();
+        assert_ne!(commitment, empty);
+
+        // same slips added in a different order
+        let mut other = UtxoSet::new();
+        other.insert(create_key(2), true);
+        other.insert(create_key(1), true);
+        assert_eq!(other.get_commitment(), commitment);
+
+        // unspendable slips are not committed to
+        other.insert(create_key(3), false);
+        assert_eq!(other.get_commitment(), commitment);
+        other.insert(create_key(3), true);
+        assert_ne!(other.get_commitment(), commitment);
+        other.insert(create_key(3), false);
+        assert_eq!(other.get_commitment(), commitment);
+        other.remove(&create_key(3));
+        assert_eq!(other.get_commitment(), commitment);
+
+        other.remove_entry(&create_key(1));
+        other.remove(&create_key(2));
+        assert_eq!(other.get_commitment(), empty);
+        utxoset.clear();
+        assert_eq!(utxoset.get_commitment(), empty);
+    }
+
+    #[test]
+    fn utxoset_apply_updates_test() {
+        for count in [10, PARALLEL_UPDATE_THRESHOLD as u32 * 3] {
+            let mut utxoset = UtxoSet::new();
+            let mut updates = vec![];
+            for index in 0..count {
+                updates.push((create_key(index), true));
+            }
+            // spending the slips created before in the same batch
+            for index in (0..count).step_by(3) {
+                updates.push((create_key(index), false));
+            }
+            let mut sequential = UtxoSet::new();
+            sequential.extend(updates.iter().cloned());
+            utxoset.apply_updates(updates);
+            assert_eq!(utxoset.len(), count as usize);
+            assert_eq!(utxoset.get_commitment(), sequential.get_commitment());
+            for index in 0..count {
+                assert_eq!(
+                    utxoset.get(&create_key(index)),
+                    Some(&(index % 3 != 0)),
+                    "slip : {:?}",
+                    index
+                );
+            }
+        }
+    }
+}
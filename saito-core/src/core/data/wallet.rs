@@ -1,19 +1,39 @@
-use ahash::{AHashMap, AHashSet};
-use tracing::warn;
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{Error, ErrorKind};
+use std::ops::Bound;
+use std::sync::Arc;
+
+use ahash::AHashMap;
+use tracing::{debug, info, warn};
 
 use crate::common::defs::{
-    Currency, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature, SaitoUTXOSetKey,
+    push_lock, BlockId, Currency, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature,
+    SaitoUTXOSetKey, Timestamp, UtxoSet, LOCK_ORDER_WALLET,
 };
+use crate::common::runtime;
+use crate::common::runtime::{RwLock, Sender};
+use crate::core::data::address_book::{AddressBook, PaymentRequest};
 use crate::core::data::block::Block;
+use crate::core::data::blockchain::Blockchain;
 use crate::core::data::crypto::{
     decrypt_with_password, encrypt_with_password, generate_keys, hash, sign,
 };
 use crate::core::data::golden_ticket::GoldenTicket;
+use crate::core::data::handle_registry::{is_valid_handle, HANDLE_REGISTRATION_FEE};
+use crate::core::data::key_format::{export_private_key, import_private_key, KeyFormat};
+use crate::core::data::multisig::{MultisigData, MultisigPolicy};
 use crate::core::data::slip::Slip;
 use crate::core::data::storage::Storage;
-use crate::core::data::transaction::{Transaction, TransactionType};
+use crate::core::data::transaction::{
+    Transaction, TransactionType, DATA_TRANSACTION_MAX_PAYLOAD_SIZE,
+};
+use crate::lock_for_write;
 
 pub const WALLET_SIZE: usize = 65;
+/// watch-only wallets only store the public key
+pub const WATCH_ONLY_WALLET_SIZE: usize = 33;
+/// max number of transactions kept in the wallet history. oldest entries are removed first
+pub const MAX_TRANSACTION_HISTORY_SIZE: usize = 10_000;
 
 /// The `WalletSlip` stores the essential information needed to track which
 /// slips are spendable and managing them as they move onto and off of the
@@ -37,17 +57,171 @@ pub struct WalletSlip {
     pub spent: bool,
 }
 
+/// Selects the wallet slips with the amount and the block id in the given inclusive ranges. Unset bounds match all
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SlipFilter {
+    pub min_amount: Option<Currency>,
+    pub max_amount: Option<Currency>,
+    pub min_block_id: Option<BlockId>,
+    pub max_block_id: Option<BlockId>,
+}
+
+impl SlipFilter {
+    pub fn matches(&self, slip: &WalletSlip) -> bool {
+        self.min_amount.map_or(true, |min| slip.amount >= min)
+            && self.max_amount.map_or(true, |max| slip.amount <= max)
+            && self.min_block_id.map_or(true, |min| slip.block_id >= min)
+            && self.max_block_id.map_or(true, |max| slip.block_id <= max)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransactionDirection {
+    Incoming,
+    Outgoing,
+}
+
+/// A transaction in the longest chain which changed the wallet balance. `amount` is the net change
+/// of the balance, so for outgoing transactions it includes the fees and excludes the change.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WalletTransaction {
+    pub signature: SaitoSignature,
+    pub direction: TransactionDirection,
+    pub amount: Currency,
+    pub block_id: u64,
+    pub block_hash: SaitoHash,
+    pub timestamp: Timestamp,
+    /// number of blocks in the longest chain including the transaction's block. set when reading the history
+    pub confirmations: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WalletUpdateType {
+    /// block added to the longest chain
+    AddBlock,
+    /// block removed from the longest chain in a reorg or a rollback
+    RemoveBlock,
+    /// block deleted from the blockchain when it's pruned
+    DeleteBlock,
+}
+
+/// A change of the longest chain which is applied to the wallet after the blockchain is updated. The blockchain numbers
+/// the updates in the order they happened, so the wallet can apply them in the same order and skip the updates already
+/// included by a rescan.
+#[derive(Clone, Debug)]
+pub struct WalletUpdate {
+    pub sequence: u64,
+    pub update_type: WalletUpdateType,
+    pub block: WalletBlock,
+}
+
+/// Parts of a block which are applied to the wallet. Queued updates carry this instead of a copy of the block, so the
+/// messages and paths of the transactions are not copied while the blockchain is locked
+#[derive(Clone, Debug, Default)]
+pub struct WalletBlock {
+    pub id: BlockId,
+    pub hash: SaitoHash,
+    /// transactions with at least one non-zero slip
+    pub transactions: Vec<WalletBlockTransaction>,
+}
+
+#[derive(Clone, Debug)]
+pub struct WalletBlockTransaction {
+    /// index of the transaction in the block
+    pub ordinal: u64,
+    pub signature: SaitoSignature,
+    pub timestamp: Timestamp,
+    /// non-zero slips only, since the wallet doesn't track the others
+    pub inputs: Vec<Slip>,
+    pub outputs: Vec<Slip>,
+}
+
+impl WalletBlock {
+    pub fn new(block: &Block) -> WalletBlock {
+        let transactions = block
+            .transactions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, tx)| {
+                let inputs: Vec<Slip> = tx
+                    .inputs
+                    .iter()
+                    .filter(|slip| slip.amount > 0)
+                    .cloned()
+                    .collect();
+                let outputs: Vec<Slip> = tx
+                    .outputs
+                    .iter()
+                    .filter(|slip| slip.amount > 0)
+                    .cloned()
+                    .collect();
+                if inputs.is_empty() && outputs.is_empty() {
+                    return None;
+                }
+                Some(WalletBlockTransaction {
+                    ordinal: index as u64,
+                    signature: tx.signature,
+                    timestamp: tx.timestamp,
+                    inputs,
+                    outputs,
+                })
+            })
+            .collect();
+        WalletBlock {
+            id: block.id,
+            hash: block.hash,
+            transactions,
+        }
+    }
+}
+
+/// Starts a background task which applies the queued wallet updates in the order they are received. The task only
+/// takes the wallet lock, so sending the updates doesn't wait for the threads which read the blockchain
+pub fn start_wallet_updater(
+    wallet: Arc<RwLock<Wallet>>,
+    queue_size: usize,
+) -> Sender<Vec<WalletUpdate>> {
+    let (sender, mut receiver) = runtime::channel::<Vec<WalletUpdate>>(queue_size);
+    runtime::spawn(async move {
+        while let Some(updates) = receiver.recv().await {
+            let (mut wallet, _wallet_) = lock_for_write!(wallet, LOCK_ORDER_WALLET);
+            for update in updates.iter() {
+                if !wallet.apply_update(update.sequence, update.update_type, &update.block) {
+                    debug!(
+                        "skipping wallet update : {:?} for block : {:?}",
+                        update.sequence,
+                        hex::encode(update.block.hash)
+                    );
+                }
+            }
+        }
+        info!("wallet updater stopped");
+    });
+    sender
+}
+
 /// The `Wallet` manages the public and private keypair of the node and holds the
 /// slips that are used to form transactions on the network.
+///
+/// A watch-only wallet only has the public key. It tracks the slips and the balance of the key but can't sign, so its
+/// transactions are created unsigned and signed by the wallet holding the private key on another machine.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Wallet {
     pub public_key: SaitoPublicKey,
+    /// all zeros for watch-only wallets
     pub private_key: SaitoPrivateKey,
+    watch_only: bool,
     pub slips: AHashMap<SaitoUTXOSetKey, WalletSlip>,
-    unspent_slips: AHashSet<SaitoUTXOSetKey>,
+    // ordered by the utxoset key, so the unspent slips are iterated by block id and pages stay stable between calls
+    unspent_slips: BTreeSet<SaitoUTXOSetKey>,
     pub filename: String,
     pub filepass: String,
     available_balance: Currency,
+    transaction_history: VecDeque<WalletTransaction>,
+    latest_block_id: u64,
+    /// sequence of the last blockchain update applied to the wallet
+    last_update_sequence: u64,
+    pub address_book: AddressBook,
 }
 
 impl Wallet {
@@ -57,14 +231,32 @@ impl Wallet {
         Wallet {
             public_key,
             private_key,
+            watch_only: false,
             slips: AHashMap::with_capacity(1_000_000),
-            unspent_slips: AHashSet::with_capacity(1_000_000),
+            unspent_slips: BTreeSet::new(),
             filename: "default".to_string(),
             filepass: "password".to_string(),
             available_balance: 0,
+            transaction_history: VecDeque::new(),
+            latest_block_id: 0,
+            last_update_sequence: 0,
+            address_book: AddressBook::new(),
         }
     }
 
+    /// Creates a wallet which tracks the slips of the given key without holding its private key
+    pub fn new_watch_only(public_key: SaitoPublicKey) -> Wallet {
+        let mut wallet = Wallet::new();
+        wallet.public_key = public_key;
+        wallet.private_key = [0; 32];
+        wallet.watch_only = true;
+        wallet
+    }
+
+    pub fn is_watch_only(&self) -> bool {
+        self.watch_only
+    }
+
     #[tracing::instrument(level = "info", skip_all)]
     pub async fn load(&mut self, storage: &mut Storage) {
         let mut filename = String::from("data/wallets/");
@@ -75,6 +267,9 @@ impl Wallet {
             let encoded = storage.read(&filename).await.unwrap();
             let decrypted_encoded = decrypt_with_password(encoded.as_ref(), &password);
             self.deserialize_from_disk(&decrypted_encoded);
+            self.address_book
+                .load(&self.get_address_book_filename(), storage)
+                .await;
         } else {
             //
             // new wallet, save to disk
@@ -105,15 +300,155 @@ impl Wallet {
         let encrypted_wallet = encrypt_with_password(byte_array.as_ref(), &password);
 
         storage.write(encrypted_wallet, &filename).await;
+        self.address_book
+            .save(&self.get_address_book_filename(), storage)
+            .await;
+    }
+
+    fn get_address_book_filename(&self) -> String {
+        String::from("data/wallets/") + self.filename.as_str() + ".addresses"
+    }
+
+    /// Creates a request for a payment to this wallet
+    pub fn create_payment_request(
+        &self,
+        amount: Currency,
+        memo: String,
+        expires_at: Timestamp,
+    ) -> PaymentRequest {
+        PaymentRequest::new(self.public_key, amount, memo, expires_at)
+    }
+
+    /// Creates a signed transaction paying the given request. Returns None if the request is expired
+    pub fn create_transaction_for_payment_request(
+        &mut self,
+        request: &PaymentRequest,
+        fee: Currency,
+        current_time: Timestamp,
+    ) -> Option<Transaction> {
+        if request.is_expired(current_time) {
+            warn!(
+                "payment request to : {:?} expired at : {:?}",
+                hex::encode(request.recipient),
+                request.expires_at
+            );
+            return None;
+        }
+        if self.watch_only {
+            warn!("cannot sign payment transaction with a watch-only wallet");
+            return None;
+        }
+        let mut transaction = Transaction::create(self, request.recipient, request.amount, fee);
+        transaction.message = request.memo.as_bytes().to_vec();
+        transaction.generate(&self.public_key, 0, 0);
+        transaction.sign(&self.private_key);
+        Some(transaction)
+    }
+
+    /// Creates a signed data transaction storing the given payload on chain. Returns None if the fee is not
+    /// enough for the payload size or the wallet doesn't have enough funds
+    pub fn create_data_transaction(
+        &mut self,
+        payload: Vec<u8>,
+        fee: Currency,
+    ) -> Option<Transaction> {
+        if self.watch_only {
+            warn!("cannot sign data transaction with a watch-only wallet");
+            return None;
+        }
+        if payload.len() > DATA_TRANSACTION_MAX_PAYLOAD_SIZE {
+            warn!(
+                "data transaction payload size : {:?} is larger than {:?}",
+                payload.len(),
+                DATA_TRANSACTION_MAX_PAYLOAD_SIZE
+            );
+            return None;
+        }
+        let mut transaction = Transaction::default();
+        transaction.transaction_type = TransactionType::Data;
+        transaction.message = payload;
+        if fee < transaction.get_data_fee() {
+            warn!(
+                "fee : {:?} is not enough for data transaction. required : {:?}",
+                fee,
+                transaction.get_data_fee()
+            );
+            return None;
+        }
+        if self.available_balance < fee {
+            warn!(
+                "not enough funds to create data transaction. available : {:?} fee : {:?}",
+                self.available_balance, fee
+            );
+            return None;
+        }
+        let (inputs, outputs) = self.generate_slips(fee);
+        for input in inputs {
+            transaction.add_input(input);
+        }
+        for output in outputs.into_iter().filter(|output| output.amount > 0) {
+            transaction.add_output(output);
+        }
+        transaction.generate(&self.public_key, 0, 0);
+        transaction.sign(&self.private_key);
+        Some(transaction)
+    }
+
+    /// Creates a signed transaction registering the handle for the wallet's key. Returns None if the handle or the
+    /// fee is not valid, or the wallet doesn't have enough funds
+    pub fn create_handle_registration_transaction(
+        &mut self,
+        handle: &str,
+        fee: Currency,
+    ) -> Option<Transaction> {
+        if self.watch_only {
+            warn!("cannot sign handle registration with a watch-only wallet");
+            return None;
+        }
+        if !is_valid_handle(handle) {
+            warn!("handle : {:?} is not valid", handle);
+            return None;
+        }
+        if fee < HANDLE_REGISTRATION_FEE {
+            warn!(
+                "fee : {:?} is not enough for handle registration. required : {:?}",
+                fee, HANDLE_REGISTRATION_FEE
+            );
+            return None;
+        }
+        if self.available_balance < fee {
+            warn!(
+                "not enough funds to register handle. available : {:?} fee : {:?}",
+                self.available_balance, fee
+            );
+            return None;
+        }
+        let mut transaction = Transaction::default();
+        transaction.transaction_type = TransactionType::HandleRegistration;
+        transaction.message = handle.as_bytes().to_vec();
+        let (inputs, outputs) = self.generate_slips(fee);
+        for input in inputs {
+            transaction.add_input(input);
+        }
+        for output in outputs.into_iter().filter(|output| output.amount > 0) {
+            transaction.add_output(output);
+        }
+        transaction.generate(&self.public_key, 0, 0);
+        transaction.sign(&self.private_key);
+        Some(transaction)
     }
 
     /// [private_key - 32 bytes]
     /// [public_key - 33 bytes]
+    ///
+    /// watch-only wallets only have the public key
     #[tracing::instrument(level = "info", skip_all)]
     pub fn serialize_for_disk(&self) -> Vec<u8> {
         let mut vbytes: Vec<u8> = vec![];
 
-        vbytes.extend(&self.private_key);
+        if !self.watch_only {
+            vbytes.extend(&self.private_key);
+        }
         vbytes.extend(&self.public_key);
 
         vbytes
@@ -123,14 +458,201 @@ impl Wallet {
     /// [public_key - 33 bytes]
     #[tracing::instrument(level = "trace", skip_all)]
     pub fn deserialize_from_disk(&mut self, bytes: &Vec<u8>) {
+        if bytes.len() == WATCH_ONLY_WALLET_SIZE {
+            self.private_key = [0; 32];
+            self.public_key = bytes[0..33].try_into().unwrap();
+            self.watch_only = true;
+            return;
+        }
         self.private_key = bytes[0..32].try_into().unwrap();
         self.public_key = bytes[32..65].try_into().unwrap();
+        self.watch_only = false;
+    }
+
+    /// Replaces the keys of the wallet with the given private key. Slips and history of the previous key are removed,
+    /// so the wallet needs to be rescanned to find the slips of the new key
+    pub fn import_private_key(&mut self, encoded: &str, format: KeyFormat) -> Result<(), Error> {
+        let (public_key, private_key) = import_private_key(encoded, format)?;
+        self.public_key = public_key;
+        self.private_key = private_key;
+        self.watch_only = false;
+        self.clear_slips();
+        Ok(())
+    }
+
+    fn clear_slips(&mut self) {
+        self.slips.clear();
+        self.unspent_slips.clear();
+        self.available_balance = 0;
+        self.transaction_history.clear();
+        self.latest_block_id = 0;
+    }
+
+    /// Rebuilds the slips and the transaction history by walking the longest chain from the genesis block. Pruned
+    /// blocks are loaded from disk. Slips marked as spent by transactions which never made it to a block are
+    /// available again after the rescan.
+    pub async fn rescan(
+        &mut self,
+        blockchain: &Blockchain,
+        storage: &Storage,
+    ) -> Result<(), Error> {
+        info!(
+            "rescanning wallet : {:?} from block : {:?} to : {:?}",
+            hex::encode(self.public_key),
+            blockchain.genesis_block_id,
+            blockchain.get_latest_block_id()
+        );
+        self.clear_slips();
+        // the rescanned slips include all the updates issued until now
+        self.last_update_sequence = blockchain.get_wallet_update_sequence();
+        if blockchain.get_latest_block_id() == 0 {
+            return Ok(());
+        }
+        for block in blockchain.iter_longest_chain(blockchain.genesis_block_id) {
+            let block = blockchain.get_full_block(&block.hash, storage).await?;
+            self.on_chain_reorganization(&block, true);
+        }
+        info!(
+            "wallet rescanned. balance : {:?} in {:?} slips",
+            self.available_balance,
+            self.unspent_slips.len()
+        );
+        Ok(())
+    }
+
+    /// Checks if the available balance matches the unspent slips and the unspent slips are still spendable
+    pub fn is_balance_consistent(&self, utxoset: &UtxoSet) -> bool {
+        let mut balance: Currency = 0;
+        let mut count = 0;
+        for slip in self.iter_unspent_slips() {
+            if !utxoset.get(&slip.utxokey).cloned().unwrap_or(false) {
+                warn!(
+                    "unspent wallet slip : {:?} is not spendable",
+                    hex::encode(slip.utxokey)
+                );
+                return false;
+            }
+            balance += slip.amount;
+            count += 1;
+        }
+        if count != self.unspent_slips.len() {
+            warn!(
+                "{:?} unspent slips not found in wallet",
+                self.unspent_slips.len() - count
+            );
+            return false;
+        }
+        if balance != self.available_balance {
+            warn!(
+                "wallet balance : {:?} doesn't match the unspent slips : {:?}",
+                self.available_balance, balance
+            );
+            return false;
+        }
+        true
+    }
+
+    pub fn export_private_key(&self, format: KeyFormat) -> Result<String, Error> {
+        if self.watch_only {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "watch-only wallet has no private key",
+            ));
+        }
+        export_private_key(&self.private_key, format)
+    }
+
+    /// Creates a payment transaction without signing it, so it can be signed by the wallet holding the private key
+    /// (see `sign_offline_transaction`). The spent slips are marked as spent in this wallet. Returns None if the
+    /// balance is not enough.
+    pub fn create_unsigned_transaction(
+        &mut self,
+        to_public_key: SaitoPublicKey,
+        with_payment: Currency,
+        with_fee: Currency,
+    ) -> Option<Transaction> {
+        if self.available_balance < with_payment + with_fee {
+            warn!(
+                "not enough funds to create transaction. available : {:?} payment : {:?} fee : {:?}",
+                self.available_balance, with_payment, with_fee
+            );
+            return None;
+        }
+        let mut transaction = Transaction::create(self, to_public_key, with_payment, with_fee);
+        for (i, output) in transaction.outputs.iter_mut().enumerate() {
+            output.slip_index = i as u8;
+        }
+        transaction.generate_hash_for_signature();
+        Some(transaction)
+    }
+
+    /// Signs a transaction created by a watch-only wallet of this key. Only transactions spending this wallet's
+    /// slips are signed. Returns the signature, which is imported to the watch-only wallet's transaction with
+    /// `Transaction::apply_signature`.
+    pub fn sign_offline_transaction(
+        &self,
+        transaction: &mut Transaction,
+    ) -> Result<SaitoSignature, Error> {
+        if self.watch_only {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "watch-only wallet cannot sign transactions",
+            ));
+        }
+        if transaction.inputs.is_empty()
+            || transaction
+                .inputs
+                .iter()
+                .any(|input| input.public_key != self.public_key)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "transaction spends slips of another key",
+            ));
+        }
+        transaction.sign(&self.private_key);
+        Ok(transaction.signature)
+    }
+
+    /// Applies a blockchain update to the wallet. Returns false if the update is older than the last applied one
+    pub fn apply_update(
+        &mut self,
+        sequence: u64,
+        update_type: WalletUpdateType,
+        block: &WalletBlock,
+    ) -> bool {
+        if sequence <= self.last_update_sequence {
+            return false;
+        }
+        if sequence != self.last_update_sequence + 1 {
+            warn!(
+                "wallet updates from : {:?} to : {:?} are missing",
+                self.last_update_sequence + 1,
+                sequence - 1
+            );
+        }
+        match update_type {
+            WalletUpdateType::AddBlock => self.apply_wallet_block(block, true),
+            WalletUpdateType::RemoveBlock => self.apply_wallet_block(block, false),
+            WalletUpdateType::DeleteBlock => self.delete_block(block),
+        }
+        self.last_update_sequence = sequence;
+        true
+    }
+
+    pub fn get_last_update_sequence(&self) -> u64 {
+        self.last_update_sequence
     }
 
     #[tracing::instrument(level = "trace", skip_all)]
     pub fn on_chain_reorganization(&mut self, block: &Block, lc: bool) {
+        self.apply_wallet_block(&WalletBlock::new(block), lc);
+    }
+
+    fn apply_wallet_block(&mut self, block: &WalletBlock, lc: bool) {
+        self.update_transaction_history(block, lc);
         if lc {
-            for (index, tx) in block.transactions.iter().enumerate() {
+            for tx in block.transactions.iter() {
                 for input in tx.inputs.iter() {
                     if input.amount > 0 && input.public_key == self.public_key {
                         self.delete_slip(input);
@@ -138,15 +660,15 @@ impl Wallet {
                 }
                 for output in tx.outputs.iter() {
                     if output.amount > 0 && output.public_key == self.public_key {
-                        self.add_slip(block, index as u64, output, true);
+                        self.add_slip(block.id, tx.ordinal, output, true);
                     }
                 }
             }
         } else {
-            for (index, tx) in block.transactions.iter().enumerate() {
+            for tx in block.transactions.iter() {
                 for input in tx.inputs.iter() {
                     if input.amount > 0 && input.public_key == self.public_key {
-                        self.add_slip(block, index as u64, input, true);
+                        self.add_slip(block.id, tx.ordinal, input, true);
                     }
                 }
                 for output in tx.outputs.iter() {
@@ -158,11 +680,68 @@ impl Wallet {
         }
     }
 
+    fn update_transaction_history(&mut self, block: &WalletBlock, lc: bool) {
+        if !lc {
+            self.transaction_history
+                .retain(|tx| tx.block_hash != block.hash);
+            self.latest_block_id = block.id - 1;
+            return;
+        }
+        self.latest_block_id = block.id;
+
+        for tx in block.transactions.iter() {
+            let received: Currency = tx
+                .outputs
+                .iter()
+                .filter(|slip| slip.public_key == self.public_key)
+                .map(|slip| slip.amount)
+                .sum();
+            let spent: Currency = tx
+                .inputs
+                .iter()
+                .filter(|slip| slip.public_key == self.public_key)
+                .map(|slip| slip.amount)
+                .sum();
+            if received == 0 && spent == 0 {
+                continue;
+            }
+            let (direction, amount) = if spent > received {
+                (TransactionDirection::Outgoing, spent - received)
+            } else {
+                (TransactionDirection::Incoming, received - spent)
+            };
+            self.transaction_history.push_back(WalletTransaction {
+                signature: tx.signature,
+                direction,
+                amount,
+                block_id: block.id,
+                block_hash: block.hash,
+                timestamp: tx.timestamp,
+                confirmations: 0,
+            });
+        }
+        while self.transaction_history.len() > MAX_TRANSACTION_HISTORY_SIZE {
+            self.transaction_history.pop_front();
+        }
+    }
+
+    /// Returns the wallet transactions in the longest chain, oldest first, with their current confirmation counts
+    pub fn get_transaction_history(&self) -> Vec<WalletTransaction> {
+        self.transaction_history
+            .iter()
+            .map(|tx| {
+                let mut tx = tx.clone();
+                tx.confirmations = self.latest_block_id.saturating_sub(tx.block_id) + 1;
+                tx
+            })
+            .collect()
+    }
+
     //
     // removes all slips in block when pruned / deleted
     //
     // #[tracing::instrument(level = "info", skip_all)]
-    pub fn delete_block(&mut self, block: &Block) {
+    pub fn delete_block(&mut self, block: &WalletBlock) {
         for tx in block.transactions.iter() {
             for input in tx.inputs.iter() {
                 self.delete_slip(input);
@@ -176,14 +755,14 @@ impl Wallet {
     }
 
     // #[tracing::instrument(level = "info", skip_all)]
-    pub fn add_slip(&mut self, block: &Block, tx_index: u64, slip: &Slip, lc: bool) {
+    pub fn add_slip(&mut self, block_id: BlockId, tx_index: u64, slip: &Slip, lc: bool) {
         let mut wallet_slip = WalletSlip::new();
 
-        assert_ne!(block.id, 0);
+        assert_ne!(block_id, 0);
         wallet_slip.utxokey = slip.get_utxoset_key();
         wallet_slip.amount = slip.amount;
         wallet_slip.slip_index = slip.slip_index;
-        wallet_slip.block_id = block.id;
+        wallet_slip.block_id = block_id;
         wallet_slip.tx_ordinal = tx_index;
         wallet_slip.lc = lc;
         self.unspent_slips.insert(wallet_slip.utxokey);
@@ -220,6 +799,34 @@ impl Wallet {
         self.unspent_slips.len() as u64
     }
 
+    /// Streams the unspent slips ordered by block id without copying them
+    pub fn iter_unspent_slips(&self) -> impl Iterator<Item = &WalletSlip> + '_ {
+        self.unspent_slips
+            .iter()
+            .filter_map(|key| self.slips.get(key))
+    }
+
+    /// Returns a page of the unspent slips matching the filter, ordered by block id. The next page starts after the
+    /// utxoset key of the last slip in this page
+    pub fn get_unspent_slips(
+        &self,
+        filter: &SlipFilter,
+        start_after: Option<&SaitoUTXOSetKey>,
+        limit: usize,
+    ) -> Vec<WalletSlip> {
+        let start = match start_after {
+            Some(key) => Bound::Excluded(key),
+            None => Bound::Unbounded,
+        };
+        self.unspent_slips
+            .range::<SaitoUTXOSetKey, _>((start, Bound::Unbounded))
+            .filter_map(|key| self.slips.get(key))
+            .filter(|slip| filter.matches(slip))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
     // the nolan_requested is omitted from the slips created - only the change
     // address is provided as an output. so make sure that any function calling
     // this manually creates the output for its desired payment
@@ -231,13 +838,12 @@ impl Wallet {
         let mut nolan_out: Currency = 0;
         let my_public_key = self.public_key;
 
-        // grab inputs
-        let mut keys_to_remove = Vec::with_capacity(1000);
-        for key in &self.unspent_slips {
+        // grab inputs, oldest slips first
+        let mut keys_to_remove = vec![];
+        for slip in self.iter_unspent_slips() {
             if nolan_in >= nolan_requested {
                 break;
             }
-            let slip = self.slips.get_mut(key).expect("slip should be here");
             nolan_in += slip.amount;
 
             let mut input = Slip::default();
@@ -248,14 +854,14 @@ impl Wallet {
             input.slip_index = slip.slip_index;
             inputs.push(input);
 
-            slip.spent = true;
-            self.available_balance -= slip.amount;
-
             keys_to_remove.push(slip.utxokey);
         }
 
         for key in keys_to_remove {
             self.unspent_slips.remove(&key);
+            let slip = self.slips.get_mut(&key).expect("slip should be here");
+            slip.spent = true;
+            self.available_balance -= slip.amount;
         }
 
         // create outputs
@@ -333,6 +939,102 @@ impl Wallet {
 
         transaction
     }
+
+    /// Creates a partially signed transaction spending the given slips of a multisig address.
+    /// Change is sent back to the multisig address. If this wallet is a member of the policy
+    /// the transaction is signed by it as well. Other members need to co-sign it with
+    /// `cosign_multisig_transaction` before it can be propagated.
+    pub fn create_multisig_transaction(
+        &self,
+        policy: MultisigPolicy,
+        input_slips: Vec<Slip>,
+        to_public_key: SaitoPublicKey,
+        with_payment: Currency,
+        with_fee: Currency,
+    ) -> Option<Transaction> {
+        if !policy.is_valid() {
+            warn!("cannot create multisig transaction with an invalid policy");
+            return None;
+        }
+        let address = policy.get_address();
+        let available: Currency = input_slips.iter().map(|slip| slip.amount).sum();
+        if input_slips.is_empty() || available < with_payment + with_fee {
+            warn!(
+                "not enough funds in multisig address : {:?} to create transaction. available : {:?}",
+                hex::encode(address),
+                available
+            );
+            return None;
+        }
+
+        let mut transaction = Transaction::default();
+        transaction.transaction_type = TransactionType::Multisig;
+        for mut slip in input_slips {
+            slip.public_key = address;
+            transaction.add_input(slip);
+        }
+        let mut output = Slip::default();
+        output.public_key = to_public_key;
+        output.amount = with_payment;
+        transaction.add_output(output);
+
+        let change = available - with_payment - with_fee;
+        if change > 0 {
+            transaction.add_output(policy.create_output_slip(change));
+        }
+        for (i, output) in transaction.outputs.iter_mut().enumerate() {
+            output.slip_index = i as u8;
+        }
+        transaction.multisig = Some(MultisigData::new(policy));
+        transaction.generate_hash_for_signature();
+
+        if transaction
+            .multisig
+            .as_ref()
+            .unwrap()
+            .policy
+            .get_key_index(&self.public_key)
+            .is_some()
+        {
+            self.cosign_multisig_transaction(&mut transaction);
+        }
+
+        Some(transaction)
+    }
+
+    /// Adds this wallet's signature to a multisig transaction. Returns false if the wallet is
+    /// not a member of the policy or has already signed the transaction.
+    pub fn cosign_multisig_transaction(&self, transaction: &mut Transaction) -> bool {
+        if self.watch_only {
+            warn!("cannot cosign multisig transaction with a watch-only wallet");
+            return false;
+        }
+        let key_index = match transaction.multisig.as_ref() {
+            Some(multisig) => match multisig.policy.get_key_index(&self.public_key) {
+                Some(key_index) => {
+                    if multisig.has_signature(key_index) {
+                        return false;
+                    }
+                    key_index
+                }
+                None => {
+                    warn!(
+                        "wallet : {:?} is not a member of the multisig policy",
+                        hex::encode(self.public_key)
+                    );
+                    return false;
+                }
+            },
+            None => {
+                return false;
+            }
+        };
+
+        // we sign what we see instead of trusting the provided hash
+        let buffer = transaction.serialize_for_signature();
+        transaction.hash_for_signature = Some(hash(&buffer));
+        transaction.add_multisig_signature(key_index, sign(&buffer, &self.private_key))
+    }
 }
 
 impl WalletSlip {
@@ -354,9 +1056,11 @@ impl WalletSlip {
 mod tests {
     use tracing::info;
 
+    use crate::common::defs::{push_lock, LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_WALLET};
     use crate::common::test_io_handler::test::TestIOHandler;
     use crate::common::test_manager::test::TestManager;
     use crate::core::data::wallet::Wallet;
+    use crate::{lock_for_read, lock_for_write};
 
     use super::*;
 
@@ -388,9 +1092,7 @@ mod tests {
         let public_key1 = wallet.public_key.clone();
         let private_key1 = wallet.private_key.clone();
 
-        let mut storage = Storage {
-            io_interface: Box::new(TestIOHandler::new()),
-        };
+        let mut storage = Storage::new(Box::new(TestIOHandler::new()));
         wallet.save(&mut storage).await;
 
         wallet = Wallet::new();
@@ -403,4 +1105,435 @@ mod tests {
         assert_eq!(wallet.public_key, public_key1);
         assert_eq!(wallet.private_key, private_key1);
     }
+
+    #[test]
+    fn transaction_history_test() {
+        let mut wallet = Wallet::new();
+        let other_public_key = generate_keys().0;
+
+        let mut block = Block::new();
+        block.id = 1;
+        block.hash = [1; 32];
+        let mut tx = Transaction::default();
+        tx.signature = [1; 64];
+        let mut output = Slip::default();
+        output.public_key = wallet.public_key;
+        output.amount = 100;
+        tx.add_output(output);
+        block.transactions.push(tx);
+        wallet.on_chain_reorganization(&block, true);
+
+        let mut block2 = Block::new();
+        block2.id = 2;
+        block2.hash = [2; 32];
+        let mut tx = Transaction::default();
+        tx.signature = [2; 64];
+        let mut input = Slip::default();
+        input.public_key = wallet.public_key;
+        input.amount = 100;
+        tx.add_input(input);
+        let mut output = Slip::default();
+        output.public_key = other_public_key;
+        output.amount = 60;
+        tx.add_output(output);
+        let mut change = Slip::default();
+        change.public_key = wallet.public_key;
+        change.amount = 30;
+        tx.add_output(change);
+        block2.transactions.push(tx);
+        wallet.on_chain_reorganization(&block2, true);
+
+        let history = wallet.get_transaction_history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].direction, TransactionDirection::Incoming);
+        assert_eq!(history[0].amount, 100);
+        assert_eq!(history[0].confirmations, 2);
+        assert_eq!(history[1].direction, TransactionDirection::Outgoing);
+        assert_eq!(history[1].amount, 70);
+        assert_eq!(history[1].confirmations, 1);
+
+        wallet.on_chain_reorganization(&block2, false);
+        let history = wallet.get_transaction_history();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].signature, [1; 64]);
+        assert_eq!(history[0].confirmations, 1);
+    }
+
+    #[test]
+    fn wallet_update_test() {
+        let mut wallet = Wallet::new();
+
+        let mut block = Block::new();
+        block.id = 1;
+        block.hash = [1; 32];
+        let mut tx = Transaction::default();
+        let mut output = Slip::default();
+        output.public_key = wallet.public_key;
+        output.amount = 100;
+        output.block_id = 1;
+        output.generate_utxoset_key();
+        tx.add_output(output);
+        block.transactions.push(tx);
+
+        // messages are not copied to the update
+        block.transactions[0].message = vec![1; 1000];
+        let block = WalletBlock::new(&block);
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(block.transactions[0].outputs.len(), 1);
+
+        assert!(wallet.apply_update(1, WalletUpdateType::AddBlock, &block));
+        assert_eq!(wallet.get_available_balance(), 100);
+        // already applied
+        assert!(!wallet.apply_update(1, WalletUpdateType::AddBlock, &block));
+        assert_eq!(wallet.get_available_balance(), 100);
+
+        assert!(wallet.apply_update(2, WalletUpdateType::RemoveBlock, &block));
+        assert_eq!(wallet.get_available_balance(), 0);
+        assert_eq!(wallet.get_last_update_sequence(), 2);
+
+        assert!(wallet.apply_update(3, WalletUpdateType::AddBlock, &block));
+        assert!(wallet.apply_update(4, WalletUpdateType::DeleteBlock, &block));
+        assert_eq!(wallet.get_available_balance(), 0);
+        assert!(wallet.slips.is_empty());
+    }
+
+    #[tokio::test]
+    async fn wallet_updater_test() {
+        let wallet_lock = Arc::new(RwLock::new(Wallet::new()));
+        let public_key = wallet_lock.read().await.public_key;
+        let sender = start_wallet_updater(wallet_lock.clone(), 1);
+
+        let mut block = Block::new();
+        block.id = 1;
+        let mut tx = Transaction::default();
+        let mut output = Slip::default();
+        output.public_key = public_key;
+        output.amount = 100;
+        output.block_id = 1;
+        output.generate_utxoset_key();
+        tx.add_output(output);
+        block.transactions.push(tx);
+        let block = WalletBlock::new(&block);
+
+        for (sequence, update_type) in [
+            (1, WalletUpdateType::AddBlock),
+            (2, WalletUpdateType::RemoveBlock),
+            (3, WalletUpdateType::AddBlock),
+        ] {
+            sender
+                .send(vec![WalletUpdate {
+                    sequence,
+                    update_type,
+                    block: block.clone(),
+                }])
+                .await
+                .unwrap();
+        }
+        for _ in 0..100 {
+            if wallet_lock.read().await.get_last_update_sequence() == 3 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let wallet = wallet_lock.read().await;
+        assert_eq!(wallet.get_last_update_sequence(), 3);
+        assert_eq!(wallet.get_available_balance(), 100);
+    }
+
+    #[test]
+    fn unspent_slips_pagination_test() {
+        let mut wallet = Wallet::new();
+        for id in 1..=5 {
+            let mut block = Block::new();
+            block.id = id;
+            let mut slip = Slip::default();
+            slip.public_key = wallet.public_key;
+            slip.block_id = id;
+            slip.amount = id as Currency * 10;
+            wallet.add_slip(block.id, 0, &slip, true);
+        }
+        let block_ids: Vec<u64> = wallet
+            .iter_unspent_slips()
+            .map(|slip| slip.block_id)
+            .collect();
+        assert_eq!(block_ids, vec![1, 2, 3, 4, 5]);
+
+        // walk through the pages
+        let filter = SlipFilter::default();
+        let page = wallet.get_unspent_slips(&filter, None, 2);
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[1].block_id, 2);
+        let page = wallet.get_unspent_slips(&filter, Some(&page[1].utxokey), 2);
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].block_id, 3);
+        let page = wallet.get_unspent_slips(&filter, Some(&page[1].utxokey), 2);
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].block_id, 5);
+
+        let filter = SlipFilter {
+            min_amount: Some(20),
+            max_amount: Some(40),
+            ..Default::default()
+        };
+        let page = wallet.get_unspent_slips(&filter, None, 10);
+        assert_eq!(page.len(), 3);
+        assert_eq!(page[0].amount, 20);
+        assert_eq!(page[2].amount, 40);
+
+        let filter = SlipFilter {
+            min_block_id: Some(4),
+            ..Default::default()
+        };
+        let page = wallet.get_unspent_slips(&filter, None, 10);
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].block_id, 4);
+
+        // oldest slips are spent first
+        let (inputs, outputs) = wallet.generate_slips(25);
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(inputs[0].block_id, 1);
+        assert_eq!(inputs[1].block_id, 2);
+        assert_eq!(outputs[0].amount, 5);
+        assert_eq!(wallet.get_unspent_slip_count(), 3);
+        assert_eq!(wallet.get_available_balance(), 120);
+        let page = wallet.get_unspent_slips(&SlipFilter::default(), None, 10);
+        assert_eq!(page[0].block_id, 3);
+    }
+
+    #[test]
+    fn multisig_transaction_test() {
+        let wallets: Vec<Wallet> = (0..3).map(|_| Wallet::new()).collect();
+        let outsider = Wallet::new();
+        let policy =
+            MultisigPolicy::new(2, wallets.iter().map(|wallet| wallet.public_key).collect())
+                .unwrap();
+
+        let mut input = policy.create_output_slip(100);
+        input.block_id = 1;
+        input.tx_ordinal = 1;
+        input.generate_utxoset_key();
+        let mut utxoset = UtxoSet::default();
+        utxoset.insert(input.utxoset_key, true);
+
+        assert!(wallets[0]
+            .create_multisig_transaction(policy.clone(), vec![input.clone()], [1; 33], 90, 20)
+            .is_none());
+        let mut tx = wallets[0]
+            .create_multisig_transaction(policy.clone(), vec![input], [1; 33], 60, 10)
+            .unwrap();
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.outputs[1].public_key, policy.get_address());
+        assert_eq!(tx.outputs[1].amount, 30);
+        assert!(!tx.is_multisig_fully_signed());
+        assert!(!tx.validate(&utxoset));
+
+        assert!(!outsider.cosign_multisig_transaction(&mut tx));
+        assert!(!wallets[0].cosign_multisig_transaction(&mut tx));
+        assert!(wallets[2].cosign_multisig_transaction(&mut tx));
+        assert!(tx.is_multisig_fully_signed());
+
+        let mut tx = Transaction::deserialize_from_net(&tx.serialize_for_net()).unwrap();
+        tx.generate(&wallets[0].public_key, 0, 2);
+        assert!(tx.validate(&utxoset));
+
+        // multisig slips cannot be spent by a normal transaction
+        let mut normal_tx = tx.clone();
+        normal_tx.transaction_type = TransactionType::Normal;
+        normal_tx.sign(&wallets[0].private_key);
+        assert!(!normal_tx.validate(&utxoset));
+    }
+
+    #[test]
+    fn import_private_key_test() {
+        let mut wallet = Wallet::new();
+        let other = Wallet::new();
+        wallet.available_balance = 100;
+
+        let encoded = other.export_private_key(KeyFormat::Wif).unwrap();
+        assert!(wallet
+            .import_private_key("invalid", KeyFormat::Wif)
+            .is_err());
+        wallet.import_private_key(&encoded, KeyFormat::Wif).unwrap();
+        assert_eq!(wallet.public_key, other.public_key);
+        assert_eq!(wallet.private_key, other.private_key);
+        assert_eq!(wallet.get_available_balance(), 0);
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn wallet_rescan_test() {
+        let mut t = TestManager::new();
+        t.initialize(10, 1_000_000).await;
+
+        let (blockchain, _blockchain_) = lock_for_read!(t.blockchain_lock, LOCK_ORDER_BLOCKCHAIN);
+        let (mut wallet, _wallet_) = lock_for_write!(t.wallet_lock, LOCK_ORDER_WALLET);
+        assert_eq!(wallet.get_available_balance(), 10_000_000);
+        assert!(wallet.is_balance_consistent(&blockchain.utxoset));
+
+        // slips spent by a transaction which is not in a block
+        wallet.generate_slips(1_000_000);
+        assert_eq!(wallet.get_available_balance(), 9_000_000);
+        wallet.available_balance += 100;
+        assert!(!wallet.is_balance_consistent(&blockchain.utxoset));
+
+        wallet.rescan(&blockchain, &t.storage).await.unwrap();
+        assert_eq!(wallet.get_available_balance(), 10_000_000);
+        assert_eq!(wallet.get_unspent_slip_count(), 10);
+        assert!(wallet.is_balance_consistent(&blockchain.utxoset));
+
+        // imported keys only have the slips of the new key after the rescan
+        let other = Wallet::new();
+        wallet
+            .import_private_key(&hex::encode(other.private_key), KeyFormat::Hex)
+            .unwrap();
+        wallet.rescan(&blockchain, &t.storage).await.unwrap();
+        assert_eq!(wallet.get_available_balance(), 0);
+        assert!(wallet.is_balance_consistent(&blockchain.utxoset));
+    }
+
+    #[test]
+    fn data_transaction_test() {
+        let mut wallet = Wallet::new();
+        let mut block = Block::new();
+        block.id = 1;
+        block.hash = [1; 32];
+        let mut tx = Transaction::default();
+        tx.signature = [1; 64];
+        let mut output = Slip::default();
+        output.public_key = wallet.public_key;
+        output.amount = 100;
+        output.block_id = 1;
+        output.generate_utxoset_key();
+        tx.add_output(output.clone());
+        block.transactions.push(tx);
+        wallet.on_chain_reorganization(&block, true);
+        let mut utxoset = UtxoSet::default();
+        utxoset.insert(output.utxoset_key, true);
+
+        assert!(wallet.create_data_transaction(vec![1; 20], 10).is_none());
+        assert!(wallet.create_data_transaction(vec![1; 20], 200).is_none());
+
+        let tx = wallet.create_data_transaction(vec![1; 20], 30).unwrap();
+        assert!(tx.is_data_transaction());
+        assert_eq!(tx.total_fees, 30);
+        assert_eq!(tx.outputs.len(), 1);
+        assert_eq!(tx.outputs[0].amount, 70);
+        assert!(tx.validate(&utxoset));
+
+        let mut tx = Transaction::deserialize_from_net(&tx.serialize_for_net()).unwrap();
+        tx.generate(&wallet.public_key, 0, 2);
+        assert!(tx.validate(&utxoset));
+
+        // payload is larger than what the fees pay for
+        tx.message = vec![1; 40];
+        tx.sign(&wallet.private_key);
+        tx.generate(&wallet.public_key, 0, 2);
+        assert!(!tx.validate(&utxoset));
+    }
+
+    #[test]
+    fn handle_registration_transaction_test() {
+        use crate::core::data::transaction::TransactionRejectReason;
+
+        let mut wallet = Wallet::new();
+        let mut block = Block::new();
+        block.id = 1;
+        block.hash = [1; 32];
+        let mut tx = Transaction::default();
+        tx.signature = [1; 64];
+        let mut output = Slip::default();
+        output.public_key = wallet.public_key;
+        output.amount = HANDLE_REGISTRATION_FEE * 2;
+        output.block_id = 1;
+        output.generate_utxoset_key();
+        tx.add_output(output.clone());
+        block.transactions.push(tx);
+        wallet.on_chain_reorganization(&block, true);
+        let mut utxoset = UtxoSet::default();
+        utxoset.insert(output.utxoset_key, true);
+
+        assert!(wallet
+            .create_handle_registration_transaction("Alice", HANDLE_REGISTRATION_FEE)
+            .is_none());
+        assert!(wallet
+            .create_handle_registration_transaction("alice", HANDLE_REGISTRATION_FEE - 1)
+            .is_none());
+
+        let tx = wallet
+            .create_handle_registration_transaction("alice", HANDLE_REGISTRATION_FEE)
+            .unwrap();
+        assert_eq!(
+            tx.get_handle_registration(),
+            Some(("alice", wallet.public_key))
+        );
+        assert_eq!(tx.outputs[0].amount, HANDLE_REGISTRATION_FEE);
+        assert!(tx.validate(&utxoset));
+
+        // handle is changed after signing
+        let mut tx = Transaction::deserialize_from_net(&tx.serialize_for_net()).unwrap();
+        tx.message = b"Alice".to_vec();
+        tx.sign(&wallet.private_key);
+        tx.generate(&wallet.public_key, 0, 2);
+        assert_eq!(
+            tx.validate_with_reason(&utxoset),
+            Err(TransactionRejectReason::InvalidHandle)
+        );
+    }
+
+    #[test]
+    fn watch_only_wallet_test() {
+        let wallet = Wallet::new();
+        let mut watch_only = Wallet::new_watch_only(wallet.public_key);
+        assert!(watch_only.is_watch_only());
+        let serialized = watch_only.serialize_for_disk();
+        assert_eq!(serialized.len(), WATCH_ONLY_WALLET_SIZE);
+        let mut restored = Wallet::new();
+        restored.deserialize_from_disk(&serialized);
+        assert!(restored.is_watch_only());
+        assert_eq!(restored.public_key, wallet.public_key);
+        assert!(watch_only.export_private_key(KeyFormat::Hex).is_err());
+
+        let mut block = Block::new();
+        block.id = 1;
+        block.hash = [1; 32];
+        let mut tx = Transaction::default();
+        tx.signature = [1; 64];
+        let mut output = Slip::default();
+        output.public_key = wallet.public_key;
+        output.amount = 100;
+        output.block_id = 1;
+        output.generate_utxoset_key();
+        tx.add_output(output.clone());
+        block.transactions.push(tx);
+        watch_only.on_chain_reorganization(&block, true);
+        assert_eq!(watch_only.get_available_balance(), 100);
+        let mut utxoset = UtxoSet::default();
+        utxoset.insert(output.utxoset_key, true);
+
+        assert!(watch_only
+            .create_data_transaction(vec![1; 20], 30)
+            .is_none());
+        let (recipient, _) = generate_keys();
+        assert!(watch_only
+            .create_unsigned_transaction(recipient, 100, 10)
+            .is_none());
+        let mut unsigned = watch_only
+            .create_unsigned_transaction(recipient, 60, 10)
+            .unwrap();
+        assert!(watch_only.sign_offline_transaction(&mut unsigned).is_err());
+
+        // signed on the offline machine from the exported transaction
+        let mut exported =
+            Transaction::deserialize_from_net(&unsigned.serialize_for_net()).unwrap();
+        let signature = wallet.sign_offline_transaction(&mut exported).unwrap();
+        assert!(Wallet::new()
+            .sign_offline_transaction(&mut exported.clone())
+            .is_err());
+
+        assert!(!unsigned.apply_signature([2; 64], &wallet.public_key));
+        assert!(!unsigned.apply_signature(signature, &recipient));
+        assert!(unsigned.apply_signature(signature, &wallet.public_key));
+        unsigned.generate(&wallet.public_key, 0, 2);
+        assert!(unsigned.validate(&utxoset));
+    }
 }
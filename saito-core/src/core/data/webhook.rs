@@ -0,0 +1,401 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use crate::common::defs::{BlockId, Currency, SaitoPublicKey, Timestamp};
+use crate::core::consensus::amounts::checked_sum_amounts;
+use crate::core::data::block::Block;
+use crate::core::data::transaction::TransactionType;
+
+/// Where and when the chain events are posted
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// json payloads are posted to each url. webhooks are disabled if empty
+    pub urls: Vec<String>,
+    /// posts the latest block after each change of the longest chain
+    pub notify_new_blocks: bool,
+    /// reorgs unwinding more blocks than this are posted
+    pub reorg_depth_threshold: u64,
+    /// payments of at least this amount (in nolan) to the node wallet are posted. 0 disables the payment events
+    pub payment_threshold: Currency,
+    pub timeout_in_ms: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            urls: vec![],
+            notify_new_blocks: true,
+            reorg_depth_threshold: 1,
+            payment_threshold: 0,
+            timeout_in_ms: 5_000,
+        }
+    }
+}
+
+/// A chain event posted to the webhooks. Hashes and keys are hex encoded. Amounts are strings since they can exceed
+/// the integer range of json parsers
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// latest block of the longest chain after an update. `blocks_added` is more than 1 while syncing
+    NewBlock {
+        block_id: BlockId,
+        block_hash: String,
+        timestamp: Timestamp,
+        blocks_added: u64,
+    },
+    /// the longest chain moved to a fork. `unwound_blocks` are listed from the old tip
+    Reorg {
+        block_id: BlockId,
+        block_hash: String,
+        depth: u64,
+        unwound_blocks: Vec<String>,
+    },
+    /// no new longest chain block within the stale chain threshold
+    NodeStalled {
+        latest_block_hash: String,
+        time_since_last_tip: Timestamp,
+    },
+    /// a new longest chain block was received after the node stalled
+    NodeRecovered {
+        latest_block_hash: String,
+        stale_duration: Timestamp,
+    },
+    PaymentReceived {
+        block_id: BlockId,
+        block_hash: String,
+        transaction: String,
+        public_key: String,
+        amount: String,
+    },
+}
+
+#[derive(Serialize, Debug)]
+struct WebhookPayload<'a> {
+    timestamp: Timestamp,
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+}
+
+/// Posts the payloads to the webhook urls. Implementations shouldn't wait for the response, since the events are sent
+/// from the consensus and routing threads
+pub trait WebhookClient: Debug + Send + Sync {
+    fn post(&self, url: &str, payload: Vec<u8>, timeout_in_ms: u64);
+}
+
+/// Sends the chain events to the webhook urls in the config
+#[derive(Debug, Clone)]
+pub struct Webhooks {
+    config: WebhookConfig,
+    client: Option<Arc<dyn WebhookClient>>,
+}
+
+impl Webhooks {
+    /// webhooks are disabled without a client
+    pub fn new(config: WebhookConfig, client: Option<Arc<dyn WebhookClient>>) -> Webhooks {
+        Webhooks { config, client }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.client.is_some() && !self.config.urls.is_empty()
+    }
+
+    pub fn is_payment_notification_enabled(&self) -> bool {
+        self.is_enabled() && self.config.payment_threshold > 0
+    }
+
+    pub fn set_config(&mut self, config: WebhookConfig) {
+        self.config = config;
+    }
+
+    pub fn notify(&self, timestamp: Timestamp, event: WebhookEvent) {
+        let client = match self.client.as_ref() {
+            Some(client) if !self.config.urls.is_empty() => client,
+            _ => return,
+        };
+        let payload = WebhookPayload {
+            timestamp,
+            event: &event,
+        };
+        let payload = match serde_json::to_vec(&payload) {
+            Ok(payload) => payload,
+            Err(error) => {
+                error!(
+                    "failed serializing webhook event : {:?} : {:?}",
+                    event, error
+                );
+                return;
+            }
+        };
+        debug!("posting webhook event : {:?}", event);
+        for url in self.config.urls.iter() {
+            client.post(url, payload.clone(), self.config.timeout_in_ms);
+        }
+    }
+
+    pub fn notify_all(&self, timestamp: Timestamp, events: Vec<WebhookEvent>) {
+        for event in events {
+            self.notify(timestamp, event);
+        }
+    }
+
+    /// Events of a longest chain update. `blocks` are the blocks added to (true) and removed from (false) the longest
+    /// chain in the order the chain was wound and unwound. Payments to `public_key` are only checked if it's given
+    pub fn get_chain_events(
+        &self,
+        blocks: &[(&Block, bool)],
+        public_key: Option<&SaitoPublicKey>,
+    ) -> Vec<WebhookEvent> {
+        let mut events = vec![];
+        let tip = match blocks.iter().rev().find(|(_, added)| *added) {
+            Some((tip, _)) => *tip,
+            None => return events,
+        };
+        let unwound_blocks: Vec<String> = blocks
+            .iter()
+            .filter(|(_, added)| !*added)
+            .map(|(block, _)| hex::encode(block.hash))
+            .collect();
+        if unwound_blocks.len() as u64 > self.config.reorg_depth_threshold {
+            events.push(WebhookEvent::Reorg {
+                block_id: tip.id,
+                block_hash: hex::encode(tip.hash),
+                depth: unwound_blocks.len() as u64,
+                unwound_blocks,
+            });
+        }
+        if self.config.notify_new_blocks {
+            events.push(WebhookEvent::NewBlock {
+                block_id: tip.id,
+                block_hash: hex::encode(tip.hash),
+                timestamp: tip.timestamp,
+                blocks_added: blocks.iter().filter(|(_, added)| *added).count() as u64,
+            });
+        }
+        if let Some(public_key) = public_key {
+            for (block, _) in blocks.iter().filter(|(_, added)| *added) {
+                events.extend(get_payments(
+                    block,
+                    public_key,
+                    self.config.payment_threshold,
+                ));
+            }
+        }
+        events
+    }
+}
+
+/// Transactions in the block which pay at least the threshold to the key. Transactions spending the key's slips are
+/// not payments, and neither are the block rewards and the rebroadcasts
+fn get_payments(
+    block: &Block,
+    public_key: &SaitoPublicKey,
+    threshold: Currency,
+) -> Vec<WebhookEvent> {
+    block
+        .transactions
+        .iter()
+        .filter(|tx| {
+            !matches!(
+                tx.transaction_type,
+                TransactionType::Fee | TransactionType::ATR
+            )
+        })
+        .filter(|tx| tx.inputs.iter().all(|slip| slip.public_key != *public_key))
+        .filter_map(|tx| {
+            let amount = checked_sum_amounts(
+                tx.outputs
+                    .iter()
+                    .filter(|slip| slip.public_key == *public_key)
+                    .map(|slip| slip.amount),
+            )?;
+            if amount == 0 || amount < threshold {
+                return None;
+            }
+            Some(WebhookEvent::PaymentReceived {
+                block_id: block.id,
+                block_hash: hex::encode(block.hash),
+                transaction: hex::encode(tx.signature),
+                public_key: hex::encode(public_key),
+                amount: amount.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::core::data::block::Block;
+    use crate::core::data::slip::Slip;
+    use crate::core::data::transaction::{Transaction, TransactionType};
+    use crate::core::data::webhook::{WebhookClient, WebhookConfig, WebhookEvent, Webhooks};
+
+    #[derive(Debug, Default)]
+    struct RecordingClient {
+        posts: Mutex<Vec<(String, String)>>,
+    }
+
+    impl WebhookClient for RecordingClient {
+        fn post(&self, url: &str, payload: Vec<u8>, _timeout_in_ms: u64) {
+            self.posts
+                .lock()
+                .unwrap()
+                .push((url.to_string(), String::from_utf8(payload).unwrap()));
+        }
+    }
+
+    fn create_block(id: u64, transactions: Vec<Transaction>) -> Block {
+        let mut block = Block::new();
+        block.id = id;
+        block.timestamp = id * 1000;
+        block.transactions = transactions;
+        block.generate();
+        block
+    }
+
+    fn create_transaction(from: [u8; 33], to: [u8; 33], amount: u128) -> Transaction {
+        let mut tx = Transaction::default();
+        let mut input = Slip::default();
+        input.public_key = from;
+        input.amount = amount;
+        let mut output = Slip::default();
+        output.public_key = to;
+        output.amount = amount;
+        tx.add_input(input);
+        tx.add_output(output);
+        tx.signature = [to[0]; 64];
+        tx
+    }
+
+    #[test]
+    fn webhook_chain_events_test() {
+        let node_key = [1; 33];
+        let other_key = [2; 33];
+        let webhooks = Webhooks::new(
+            WebhookConfig {
+                urls: vec!["http://localhost:9000/hook".to_string()],
+                reorg_depth_threshold: 1,
+                payment_threshold: 100,
+                ..Default::default()
+            },
+            Some(Arc::new(RecordingClient::default())),
+        );
+        assert!(webhooks.is_payment_notification_enabled());
+
+        let mut fee_tx = create_transaction(other_key, node_key, 500);
+        fee_tx.transaction_type = TransactionType::Fee;
+        let block_3 = create_block(
+            3,
+            vec![
+                create_transaction(other_key, node_key, 100),
+                // below the threshold
+                create_transaction(other_key, node_key, 99),
+                // change of the node's own transaction
+                create_transaction(node_key, node_key, 1000),
+                fee_tx,
+            ],
+        );
+        let old_2 = create_block(2, vec![]);
+        let old_3 = create_block(3, vec![create_transaction(other_key, other_key, 1)]);
+        let new_2 = create_block(2, vec![]);
+
+        // one block unwound is within the threshold
+        let events = webhooks.get_chain_events(&[(&old_3, false), (&new_2, true)], Some(&node_key));
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events[0],
+            WebhookEvent::NewBlock {
+                block_id: 2,
+                blocks_added: 1,
+                ..
+            }
+        ));
+
+        let events = webhooks.get_chain_events(
+            &[
+                (&old_3, false),
+                (&old_2, false),
+                (&new_2, true),
+                (&block_3, true),
+            ],
+            Some(&node_key),
+        );
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            events[0],
+            WebhookEvent::Reorg {
+                block_id: 3,
+                block_hash: hex::encode(block_3.hash),
+                depth: 2,
+                unwound_blocks: vec![hex::encode(old_3.hash), hex::encode(old_2.hash)],
+            }
+        );
+        assert!(matches!(
+            events[1],
+            WebhookEvent::NewBlock {
+                block_id: 3,
+                blocks_added: 2,
+                ..
+            }
+        ));
+        assert_eq!(
+            events[2],
+            WebhookEvent::PaymentReceived {
+                block_id: 3,
+                block_hash: hex::encode(block_3.hash),
+                transaction: hex::encode([1; 64]),
+                public_key: hex::encode(node_key),
+                amount: "100".to_string(),
+            }
+        );
+
+        // only unwinding doesn't change the tip
+        assert!(webhooks
+            .get_chain_events(&[(&old_3, false)], Some(&node_key))
+            .is_empty());
+    }
+
+    #[test]
+    fn webhook_notify_test() {
+        let client = Arc::new(RecordingClient::default());
+        let mut webhooks = Webhooks::new(
+            WebhookConfig {
+                urls: vec!["http://a/hook".to_string(), "http://b/hook".to_string()],
+                ..Default::default()
+            },
+            Some(client.clone()),
+        );
+        webhooks.notify(
+            5000,
+            WebhookEvent::NodeStalled {
+                latest_block_hash: "ab".to_string(),
+                time_since_last_tip: 60_000,
+            },
+        );
+        let posts = client.posts.lock().unwrap().clone();
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[1].0, "http://b/hook");
+        assert_eq!(
+            posts[0].1,
+            "{\"timestamp\":5000,\"event\":\"node_stalled\",\"latest_block_hash\":\"ab\",\"time_since_last_tip\":60000}"
+        );
+
+        // nothing is posted without urls
+        webhooks.set_config(WebhookConfig::default());
+        assert!(!webhooks.is_enabled());
+        webhooks.notify(
+            6000,
+            WebhookEvent::NodeRecovered {
+                latest_block_hash: "ab".to_string(),
+                stale_duration: 70_000,
+            },
+        );
+        assert_eq!(client.posts.lock().unwrap().len(), 2);
+    }
+}
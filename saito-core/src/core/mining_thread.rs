@@ -1,18 +1,19 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use async_trait::async_trait;
-use tokio::sync::mpsc::Sender;
-use tokio::sync::RwLock;
 use tracing::info;
 
 use crate::common::command::NetworkEvent;
 use crate::common::defs::{push_lock, SaitoHash, SaitoPublicKey, Timestamp, LOCK_ORDER_WALLET};
 use crate::common::keep_time::KeepTime;
 use crate::common::process_event::ProcessEvent;
+use crate::common::runtime::{RwLock, Sender};
 use crate::core::consensus_thread::ConsensusEvent;
+use crate::core::data::configuration::Configuration;
 use crate::core::data::crypto::{generate_random_bytes, hash};
 use crate::core::data::golden_ticket::GoldenTicket;
+use crate::core::data::mining_status::MiningStatus;
 use crate::core::data::wallet::Wallet;
 use crate::lock_for_read;
 
@@ -27,11 +28,16 @@ pub struct MiningThread {
     pub sender_to_mempool: Sender<ConsensusEvent>,
     pub time_keeper: Box<dyn KeepTime + Send + Sync>,
     pub miner_active: bool,
+    // routing only nodes don't mine golden tickets
+    pub mining_enabled: bool,
     pub target: SaitoHash,
     pub difficulty: u64,
     pub public_key: SaitoPublicKey,
     pub mined_golden_tickets: u64,
+    /// golden ticket solutions tried
+    pub hash_count: u64,
     pub stat_sender: Sender<String>,
+    pub mining_status: Arc<Mutex<MiningStatus>>,
 }
 
 impl MiningThread {
@@ -44,6 +50,7 @@ impl MiningThread {
         // The new way of validation will be wasting a GT instance if the validation fails
         // old way used a static method instead
         let gt = GoldenTicket::create(self.target, random_bytes, self.public_key);
+        self.hash_count += 1;
         if gt.validate(self.difficulty) {
             info!(
                 "golden ticket found. sending to mempool. previous block : {:?} random : {:?} key : {:?} solution : {:?} for difficulty : {:?}",
@@ -55,12 +62,27 @@ impl MiningThread {
             );
             self.miner_active = false;
             self.mined_golden_tickets += 1;
+            self.publish_status(None);
             self.sender_to_mempool
                 .send(ConsensusEvent::NewGoldenTicket { golden_ticket: gt })
                 .await
                 .expect("sending to mempool failed");
         }
     }
+
+    /// Copies the miner's state to the shared status. The hashrate is only sampled with the current time
+    fn publish_status(&self, current_time: Option<Timestamp>) {
+        let mut status = self.mining_status.lock().unwrap();
+        status.mining_enabled = self.mining_enabled;
+        status.miner_active = self.miner_active;
+        status.target = self.target;
+        status.difficulty = self.difficulty;
+        status.mined_golden_tickets = self.mined_golden_tickets;
+        match current_time {
+            Some(current_time) => status.sample_hashrate(self.hash_count, current_time),
+            None => status.total_hashes = self.hash_count,
+        }
+    }
 }
 
 #[async_trait]
@@ -81,6 +103,10 @@ impl ProcessEvent<MiningEvent> for MiningThread {
     async fn process_event(&mut self, event: MiningEvent) -> Option<()> {
         return match event {
             MiningEvent::LongestChainBlockAdded { hash, difficulty } => {
+                self.mining_status.lock().unwrap().latest_block_hash = hash;
+                if !self.mining_enabled {
+                    return None;
+                }
                 info!(
                     "Activating miner with hash : {:?} and difficulty : {:?}",
                     hex::encode(hash),
@@ -89,6 +115,7 @@ impl ProcessEvent<MiningEvent> for MiningThread {
                 self.difficulty = difficulty;
                 self.target = hash;
                 self.miner_active = true;
+                self.publish_status(None);
                 Some(())
             }
         };
@@ -100,13 +127,35 @@ impl ProcessEvent<MiningEvent> for MiningThread {
         info!("node public key = {:?}", hex::encode(self.public_key));
     }
 
-    async fn on_stat_interval(&mut self, _current_time: Timestamp) {
-        let stat = format!("{} - total : {:?}, current difficulty : {:?}, miner_active : {:?}, current target : {:?} ",
+    async fn on_stat_interval(&mut self, current_time: Timestamp) {
+        self.publish_status(Some(current_time));
+        let hashrate = self.mining_status.lock().unwrap().hashrate;
+        let stat = format!("{} - total : {:?}, current difficulty : {:?}, miner_active : {:?}, current target : {:?}, hashrate : {:?} ",
                            format!("{:width$}", "mining::golden_tickets", width = 40),
                            self.mined_golden_tickets,
                            self.difficulty,
                            self.miner_active,
-                           hex::encode(self.target));
+                           hex::encode(self.target),
+                           hashrate);
         self.stat_sender.send(stat).await.unwrap();
     }
+
+    async fn on_shutdown(&mut self) {
+        info!(
+            "stopping miner. mined golden tickets : {:?}",
+            self.mined_golden_tickets
+        );
+        self.miner_active = false;
+    }
+
+    async fn on_config_reload(&mut self, configs: &(dyn Configuration + Send + Sync)) {
+        // golden tickets are created by the consensus thread in dev mode
+        self.mining_enabled = configs.get_server_configs().node_role.is_mining_enabled()
+            && !configs.get_server_configs().dev_mode;
+        if !self.mining_enabled {
+            self.miner_active = false;
+        }
+        self.publish_status(None);
+        info!("mining enabled : {:?}", self.mining_enabled);
+    }
 }
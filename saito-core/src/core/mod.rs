@@ -1,3 +1,4 @@
+pub mod consensus;
 pub mod consensus_thread;
 pub mod data;
 pub mod mining_thread;
@@ -1,34 +1,70 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use async_trait::async_trait;
-use tokio::sync::mpsc::Sender;
-use tokio::sync::RwLock;
-use tracing::{debug, info, trace};
+use tracing::{debug, info, trace, warn};
 
 use crate::common::command::NetworkEvent;
 use crate::common::defs::{
     push_lock, PeerIndex, SaitoHash, SaitoPublicKey, StatVariable, Timestamp,
-    LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_WALLET, STAT_BIN_COUNT,
+    LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_CONFIGS, LOCK_ORDER_PEERS, LOCK_ORDER_WALLET, STAT_BIN_COUNT,
 };
 use crate::common::keep_time::KeepTime;
 use crate::common::process_event::ProcessEvent;
+use crate::common::runtime;
+use crate::common::runtime::{RwLock, Sender};
 use crate::core::consensus_thread::ConsensusEvent;
 use crate::core::data;
+use crate::core::data::balance_proof::BalanceProof;
+use crate::core::data::block_announcement_cache::BlockAnnouncementCache;
+use crate::core::data::block_fetch::{validate_fetched_block, FetchedBlockError};
+use crate::core::data::block_queue::{BlockQueueStatus, DEFAULT_BLOCK_QUEUE_LIMIT};
 use crate::core::data::blockchain::Blockchain;
-use crate::core::data::blockchain_sync_state::BlockchainSyncState;
-use crate::core::data::configuration::Configuration;
+use crate::core::data::blockchain_sync_state::{BlockchainSyncState, BLOCK_FETCH_TIMEOUT_IN_MS};
+use crate::core::data::configuration::{Configuration, Server};
+use crate::core::data::golden_ticket::GoldenTicket;
 use crate::core::data::msg::block_request::BlockchainRequest;
-use crate::core::data::msg::message::Message;
+use crate::core::data::msg::fork_id::ForkIdResponse;
+use crate::core::data::msg::message::{Message, MESSAGE_WIRE_FORMAT_VERSION};
+use crate::core::data::msg::transaction_batch::{TransactionBatchResult, TransactionRejection};
 use crate::core::data::network::Network;
+use crate::core::data::peer_events::{PeerEvent, PeerEventListeners};
+use crate::core::data::peer_message_stats::{MessageCategory, PeerMessageStats};
+use crate::core::data::peer_quorum::ConnectionStatus;
+use crate::core::data::peer_requests::{PeerRequest, RequestId};
+use crate::core::data::stale_chain_watchdog::{ChainTipStatus, StaleChainWatchdog};
+use crate::core::data::storage::Storage;
+use crate::core::data::transaction::Transaction;
+use crate::core::data::trusted_sync::{TrustedSync, UtxoSnapshot, TRUSTED_SYNC_BLOCK_COUNT};
 use crate::core::data::wallet::Wallet;
+use crate::core::data::webhook::{WebhookEvent, Webhooks};
 use crate::core::mining_thread::MiningEvent;
-use crate::core::verification_thread::VerifyRequest;
-use crate::lock_for_read;
+use crate::core::verification_thread::{
+    select_verification_queue, TransactionBatchSource, VerifyRequest,
+};
+use crate::{lock_for_read, lock_for_write};
+
+/// how often the block fetch requests are checked for timeouts
+const BLOCK_FETCH_TIMEOUT_CHECK_PERIOD_IN_MS: Timestamp = 5_000;
 
 #[derive(Debug)]
 pub enum RoutingEvent {
     BlockchainUpdated,
+    BlockchainRolledBack,
+    /// result of a transaction batch submitted by a peer
+    TransactionBatchVerified {
+        peer_index: u64,
+        result: TransactionBatchResult,
+    },
+    /// transaction sent by the peer was not added to the mempool
+    TransactionRejected {
+        peer_index: u64,
+        rejection: TransactionRejection,
+    },
+    /// block fetched from the peer was ignored since its creator is blacklisted
+    BlockFromBlacklistedCreator {
+        peer_index: u64,
+    },
 }
 
 #[derive(Debug)]
@@ -47,6 +83,9 @@ pub struct StaticPeer {
 pub struct RoutingStats {
     pub received_transactions: StatVariable,
     pub received_blocks: StatVariable,
+    pub rejected_blocks: StatVariable,
+    /// golden tickets submitted by miner nodes
+    pub received_golden_tickets: StatVariable,
     pub total_incoming_messages: StatVariable,
 }
 
@@ -63,6 +102,16 @@ impl RoutingStats {
                 STAT_BIN_COUNT,
                 sender.clone(),
             ),
+            rejected_blocks: StatVariable::new(
+                "routing::rejected_blocks".to_string(),
+                STAT_BIN_COUNT,
+                sender.clone(),
+            ),
+            received_golden_tickets: StatVariable::new(
+                "routing::received_gts".to_string(),
+                STAT_BIN_COUNT,
+                sender.clone(),
+            ),
             total_incoming_messages: StatVariable::new(
                 "routing::incoming_msgs".to_string(),
                 STAT_BIN_COUNT,
@@ -84,12 +133,28 @@ pub struct RoutingThread {
     pub wallet: Arc<RwLock<Wallet>>,
     pub network: Network,
     pub reconnection_timer: Timestamp,
+    pub fetch_timeout_timer: Timestamp,
     pub stats: RoutingStats,
     pub public_key: SaitoPublicKey,
     pub senders_to_verification: Vec<Sender<VerifyRequest>>,
     pub last_verification_thread_index: usize,
     pub stat_sender: Sender<String>,
     pub blockchain_sync_state: BlockchainSyncState,
+    pub stale_chain_watchdog: StaleChainWatchdog,
+    /// stalled and recovered chain events are posted here
+    pub webhooks: Webhooks,
+    pub reconnect_on_stale_chain: bool,
+    pub block_announcement_cache: BlockAnnouncementCache,
+    pub trusted_sync: TrustedSync,
+    pub peer_message_stats: PeerMessageStats,
+    /// used to load the pruned blocks for balance proofs
+    pub storage: Storage,
+    /// connected peers, shared with the consensus thread to check the block production quorum
+    pub connection_status: Arc<Mutex<ConnectionStatus>>,
+    /// blocks waiting to be added to the blockchain. block bodies are not fetched while the queue is full
+    pub block_queue_status: Arc<Mutex<BlockQueueStatus>>,
+    /// connected, handshake and disconnected events of the peers are sent to these listeners
+    pub peer_events: PeerEventListeners,
 }
 
 impl RoutingThread {
@@ -118,26 +183,49 @@ impl RoutingThread {
         match message {
             Message::HandshakeChallenge(challenge) => {
                 debug!("received handshake challenge");
-                self.network
+                let result = self
+                    .network
                     .handle_handshake_challenge(
                         peer_index,
                         challenge,
                         self.wallet.clone(),
                         self.configs.clone(),
+                        self.time_keeper.get_timestamp_in_ms(),
                     )
                     .await;
+                if let Err(reason) = result {
+                    self.peer_events
+                        .notify(PeerEvent::HandshakeRejected { peer_index, reason });
+                }
             }
             Message::HandshakeResponse(response) => {
                 debug!("received handshake response");
-                self.network
+                let result = self
+                    .network
                     .handle_handshake_response(
                         peer_index,
                         response,
                         self.wallet.clone(),
                         self.blockchain.clone(),
                         self.configs.clone(),
+                        self.time_keeper.get_timestamp_in_ms(),
                     )
                     .await;
+                if let Err(reason) = result {
+                    self.peer_events
+                        .notify(PeerEvent::HandshakeRejected { peer_index, reason });
+                    return;
+                }
+                if let Some(public_key) = self.get_peer_public_key(peer_index).await {
+                    self.connection_status
+                        .lock()
+                        .unwrap()
+                        .on_handshake_completed(peer_index, public_key);
+                    self.peer_events.notify(PeerEvent::HandshakeCompleted {
+                        peer_index,
+                        public_key,
+                    });
+                }
             }
             Message::ApplicationMessage(_) => {
                 debug!("received buffer");
@@ -148,8 +236,14 @@ impl RoutingThread {
             Message::Transaction(transaction) => {
                 trace!("received transaction");
                 self.stats.received_transactions.increment();
-                self.send_to_verification_thread(VerifyRequest::Transaction(transaction))
+                self.network
+                    .on_inventory_received(peer_index, &[], &[transaction.signature])
                     .await;
+                self.send_to_verification_thread(VerifyRequest::Transaction(
+                    transaction,
+                    Some(peer_index),
+                ))
+                .await;
             }
             Message::BlockchainRequest(request) => {
                 self.process_incoming_blockchain_request(request, peer_index)
@@ -166,7 +260,94 @@ impl RoutingThread {
             Message::GhostChainRequest() => {}
             Message::Result() => {}
             Message::Error() => {}
-            Message::ApplicationTransaction(_) => {}
+            Message::ApplicationTransaction(buffer) => {
+                trace!("received application transaction");
+                let transaction = match Transaction::try_deserialize_versioned(&buffer) {
+                    Ok(transaction) => transaction,
+                    Err(_) => {
+                        warn!(
+                            "invalid application transaction of size : {:?} received from peer : {:?}",
+                            buffer.len(),
+                            peer_index
+                        );
+                        return;
+                    }
+                };
+                if !transaction.is_data_transaction() {
+                    warn!(
+                        "application transaction of type : {:?} received from peer : {:?}",
+                        transaction.transaction_type, peer_index
+                    );
+                    return;
+                }
+                self.stats.received_transactions.increment();
+                self.network
+                    .on_inventory_received(peer_index, &[], &[transaction.signature])
+                    .await;
+                self.send_to_verification_thread(VerifyRequest::Transaction(
+                    transaction,
+                    Some(peer_index),
+                ))
+                .await;
+            }
+            Message::TransactionBatch(batch) => {
+                trace!(
+                    "received batch of {:?} transactions",
+                    batch.transactions.len()
+                );
+                self.stats
+                    .received_transactions
+                    .increment_by(batch.transactions.len() as u64);
+                self.send_to_verification_thread(VerifyRequest::TransactionBatch(
+                    batch,
+                    TransactionBatchSource::Peer(peer_index),
+                ))
+                .await;
+            }
+            Message::TransactionBatchResult(result) => {
+                debug!(
+                    "received results of transaction batch : {:?} from peer : {:?}",
+                    result.batch_id, peer_index
+                );
+            }
+            Message::TransactionRejected(rejection) => {
+                debug!(
+                    "transaction : {:?} rejected by peer : {:?} : {:?}",
+                    hex::encode(rejection.signature),
+                    peer_index,
+                    rejection.reason
+                );
+            }
+            Message::ForkIdRequest() => {
+                self.process_incoming_fork_id_request(peer_index).await;
+            }
+            Message::ForkIdResponse(response) => {
+                if self.is_waiting_for_snapshot().await {
+                    self.request_snapshot_from_peer(peer_index).await;
+                    return;
+                }
+                self.network
+                    .handle_fork_id_response(peer_index, response, self.blockchain.clone())
+                    .await;
+            }
+            Message::TrustedSyncRequest() => {
+                self.process_incoming_trusted_sync_request(peer_index).await;
+            }
+            Message::TrustedSyncResponse(snapshot) => {
+                self.process_incoming_utxo_snapshot(snapshot, peer_index)
+                    .await;
+            }
+            Message::BalanceProofRequest(public_key) => {
+                self.process_incoming_balance_proof_request(public_key, peer_index)
+                    .await;
+            }
+            Message::BalanceProofResponse(proof) => {
+                self.process_incoming_balance_proof(proof, peer_index).await;
+            }
+            Message::GoldenTicket(golden_ticket) => {
+                self.process_incoming_golden_ticket(golden_ticket, peer_index)
+                    .await;
+            }
         }
         trace!("incoming message processed");
     }
@@ -178,13 +359,335 @@ impl RoutingThread {
         peer_index: u64,
     ) {
         trace!("handling new peer : {:?}", peer_index);
-        self.network.handle_new_peer(peer_data, peer_index).await;
+        self.connection_status
+            .lock()
+            .unwrap()
+            .on_peer_connected(peer_index, peer_data.as_ref());
+        self.peer_events.notify(PeerEvent::Connected {
+            peer_index,
+            address: peer_data
+                .as_ref()
+                .map(|config| format!("{}:{}", config.host, config.port)),
+        });
+        self.network
+            .handle_new_peer(
+                peer_data,
+                peer_index,
+                self.configs.clone(),
+                self.time_keeper.get_timestamp_in_ms(),
+            )
+            .await;
     }
 
     #[tracing::instrument(level = "info", skip_all)]
     async fn handle_peer_disconnect(&mut self, peer_index: u64) {
         trace!("handling peer disconnect, peer_index = {}", peer_index);
+        // the peer is removed from the collection with the disconnection
+        let public_key = self.get_peer_public_key(peer_index).await;
         self.network.handle_peer_disconnect(peer_index).await;
+        self.connection_status
+            .lock()
+            .unwrap()
+            .on_peer_disconnected(peer_index);
+        self.blockchain_sync_state.remove_peer(peer_index);
+        self.block_announcement_cache.remove_peer(peer_index);
+        self.trusted_sync.on_peer_disconnected(peer_index);
+        self.peer_message_stats.remove_peer(peer_index);
+        self.peer_events.notify(PeerEvent::Disconnected {
+            peer_index,
+            public_key,
+        });
+    }
+
+    async fn get_peer_public_key(&self, peer_index: u64) -> Option<SaitoPublicKey> {
+        let (peers, _peers_) = lock_for_read!(self.network.peers, LOCK_ORDER_PEERS);
+        peers
+            .index_to_peers
+            .get(&peer_index)
+            .and_then(|peer| peer.public_key)
+    }
+
+    async fn is_waiting_for_snapshot(&self) -> bool {
+        let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+        self.trusted_sync
+            .is_waiting_for_snapshot(blockchain.get_latest_block_id())
+    }
+
+    /// Blocks are not synced from the untrusted peers until the snapshot is applied, since the chain would be
+    /// validated from the genesis block otherwise
+    async fn request_snapshot_from_peer(&mut self, peer_index: u64) {
+        let public_key = self.get_peer_public_key(peer_index).await;
+        if !public_key.map_or(false, |key| self.trusted_sync.should_request_from(&key)) {
+            debug!(
+                "waiting for the utxoset snapshot. not syncing blocks from peer : {:?}",
+                peer_index
+            );
+            return;
+        }
+        self.trusted_sync.on_snapshot_requested(peer_index);
+        let buffer = Message::TrustedSyncRequest().serialize();
+        self.network
+            .io_interface
+            .send_message(peer_index, buffer)
+            .await
+            .unwrap();
+    }
+
+    async fn process_incoming_trusted_sync_request(&self, peer_index: u64) {
+        let public_key = self.get_peer_public_key(peer_index).await;
+        if !public_key.map_or(false, |key| self.trusted_sync.is_trusted(&key)) {
+            warn!(
+                "rejecting utxoset snapshot request from untrusted peer : {:?}",
+                peer_index
+            );
+            return;
+        }
+        let snapshot;
+        {
+            let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            snapshot = UtxoSnapshot::create(&blockchain, TRUSTED_SYNC_BLOCK_COUNT);
+        }
+        let mut snapshot = match snapshot {
+            Some(snapshot) => snapshot,
+            None => {
+                warn!(
+                    "couldn't create the utxoset snapshot requested by peer : {:?}",
+                    peer_index
+                );
+                return;
+            }
+        };
+        {
+            let (wallet, _wallet_) = lock_for_read!(self.wallet, LOCK_ORDER_WALLET);
+            snapshot.sign(&wallet.private_key);
+        }
+        info!(
+            "sending utxoset snapshot with {:?} slips at block : {:?} to peer : {:?}",
+            snapshot.utxo_keys.len(),
+            snapshot.latest_block_id,
+            peer_index
+        );
+        self.network
+            .send_message_to_peer(peer_index, &Message::TrustedSyncResponse(snapshot))
+            .await
+            .unwrap();
+    }
+
+    /// Loads the snapshot and fetches the recent blocks from the same peer. The blocks are validated against the
+    /// snapshot as they are added, and the last one is the peer's latest block which the snapshot was signed for.
+    async fn process_incoming_utxo_snapshot(&mut self, snapshot: UtxoSnapshot, peer_index: u64) {
+        let public_key = match self.get_peer_public_key(peer_index).await {
+            Some(public_key) => public_key,
+            None => {
+                warn!(
+                    "received utxoset snapshot from unknown peer : {:?}",
+                    peer_index
+                );
+                return;
+            }
+        };
+        if let Err(error) = self
+            .trusted_sync
+            .validate_snapshot(&snapshot, peer_index, &public_key)
+        {
+            warn!(
+                "rejecting utxoset snapshot from peer : {:?} : {:?}",
+                peer_index, error
+            );
+            return;
+        }
+        {
+            let (mut blockchain, _blockchain_) =
+                lock_for_write!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            if let Err(error) = snapshot.apply(&mut blockchain) {
+                warn!(
+                    "couldn't apply the utxoset snapshot from peer : {:?} : {:?}",
+                    peer_index, error
+                );
+                return;
+            }
+        }
+        self.trusted_sync.on_snapshot_applied();
+        info!(
+            "applied utxoset snapshot with {:?} slips from peer : {:?}. fetching blocks from : {:?} to : {:?}",
+            snapshot.utxo_keys.len(),
+            peer_index,
+            snapshot.get_first_block_id(),
+            snapshot.latest_block_id
+        );
+        let first_block_id = snapshot.get_first_block_id();
+        for (index, block_hash) in snapshot.block_hashes.iter().enumerate() {
+            self.blockchain_sync_state.add_entry(
+                *block_hash,
+                first_block_id + index as u64,
+                peer_index,
+            );
+        }
+        self.fetch_next_blocks().await;
+    }
+
+    async fn process_incoming_balance_proof_request(
+        &self,
+        public_key: SaitoPublicKey,
+        peer_index: u64,
+    ) {
+        debug!(
+            "processing balance proof request for : {:?} from peer : {:?}",
+            hex::encode(public_key),
+            peer_index
+        );
+        let mut proof;
+        {
+            let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            proof = BalanceProof::generate(&blockchain, &self.storage, &public_key).await;
+        }
+        {
+            let (wallet, _wallet_) = lock_for_read!(self.wallet, LOCK_ORDER_WALLET);
+            proof.sign(&wallet.private_key);
+        }
+        self.network
+            .send_message_to_peer(peer_index, &Message::BalanceProofResponse(proof))
+            .await
+            .unwrap();
+    }
+
+    /// Golden tickets from miner nodes which don't produce blocks. The ticket must solve the latest block with its
+    /// difficulty and pay the submitting peer's key, so the payout goes to the miner which found it
+    async fn process_incoming_golden_ticket(
+        &mut self,
+        golden_ticket: GoldenTicket,
+        peer_index: u64,
+    ) {
+        let public_key = match self.get_peer_public_key(peer_index).await {
+            Some(public_key) => public_key,
+            None => {
+                warn!(
+                    "received golden ticket from peer : {:?} before the handshake",
+                    peer_index
+                );
+                return;
+            }
+        };
+        if golden_ticket.public_key != public_key {
+            warn!(
+                "golden ticket from peer : {:?} pays : {:?} instead of the peer's key : {:?}",
+                peer_index,
+                hex::encode(golden_ticket.public_key),
+                hex::encode(public_key)
+            );
+            return;
+        }
+        {
+            let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            let latest_block = match blockchain.get_latest_block() {
+                Some(block) => block,
+                None => {
+                    debug!(
+                        "ignoring golden ticket from peer : {:?} since the chain is empty",
+                        peer_index
+                    );
+                    return;
+                }
+            };
+            if golden_ticket.target != latest_block.hash {
+                debug!(
+                    "golden ticket from peer : {:?} is for block : {:?}, not the latest block : {:?}",
+                    peer_index,
+                    hex::encode(golden_ticket.target),
+                    hex::encode(latest_block.hash)
+                );
+                return;
+            }
+            if !golden_ticket.validate(latest_block.difficulty) {
+                warn!(
+                    "golden ticket from peer : {:?} doesn't solve the difficulty : {:?} of block : {:?}",
+                    peer_index,
+                    latest_block.difficulty,
+                    hex::encode(latest_block.hash)
+                );
+                return;
+            }
+        }
+        info!(
+            "golden ticket for block : {:?} submitted by miner : {:?} from peer : {:?}",
+            hex::encode(golden_ticket.target),
+            hex::encode(public_key),
+            peer_index
+        );
+        self.stats.received_golden_tickets.increment();
+        self.sender_to_consensus
+            .send(ConsensusEvent::NewGoldenTicket { golden_ticket })
+            .await
+            .unwrap();
+    }
+
+    /// Checks the proof from a full node against the longest chain of this node
+    async fn process_incoming_balance_proof(&self, proof: BalanceProof, peer_index: u64) {
+        let public_key = match self.get_peer_public_key(peer_index).await {
+            Some(public_key) => public_key,
+            None => {
+                warn!(
+                    "received balance proof from unknown peer : {:?}",
+                    peer_index
+                );
+                return;
+            }
+        };
+        if !proof.verify(&public_key) {
+            warn!(
+                "invalid balance proof received from peer : {:?}",
+                peer_index
+            );
+            return;
+        }
+        let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+        let unknown_blocks = proof
+            .slip_proofs
+            .iter()
+            .filter(|slip_proof| {
+                blockchain
+                    .blockring
+                    .get_longest_chain_block_hash_by_block_id(slip_proof.block.id)
+                    != slip_proof.get_block_hash()
+            })
+            .count();
+        if unknown_blocks > 0 {
+            warn!(
+                "{:?} slips of the balance proof from peer : {:?} are not in the longest chain",
+                unknown_blocks, peer_index
+            );
+            return;
+        }
+        info!(
+            "balance of : {:?} is : {:?} at block : {:?} with {:?} proven and {:?} unproven slips",
+            hex::encode(proof.public_key),
+            proof.get_balance(),
+            proof.latest_block_id,
+            proof.slip_proofs.len(),
+            proof.unproven_utxo_keys.len()
+        );
+    }
+
+    async fn process_incoming_fork_id_request(&self, peer_index: u64) {
+        debug!(
+            "processing incoming fork id request from peer : {:?}",
+            peer_index
+        );
+        let response;
+        {
+            let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+
+            response = ForkIdResponse {
+                latest_block_id: blockchain.get_latest_block_id(),
+                fork_id: *blockchain.get_fork_id(),
+            };
+        }
+        let buffer = Message::ForkIdResponse(response).serialize();
+        self.network
+            .io_interface
+            .send_message(peer_index, buffer)
+            .await
+            .unwrap();
     }
 
     #[tracing::instrument(level = "info", skip_all)]
@@ -208,15 +711,8 @@ impl RoutingThread {
             blockchain.generate_last_shared_ancestor(request.latest_block_id, request.fork_id);
         debug!("last shared ancestor = {:?}", last_shared_ancestor);
 
-        for i in last_shared_ancestor..(blockchain.blockring.get_latest_block_id() + 1) {
-            let block_hash = blockchain
-                .blockring
-                .get_longest_chain_block_hash_by_block_id(i);
-            if block_hash == [0; 32] {
-                // TODO : can the block hash not be in the ring if we are going through the longest chain ?
-                continue;
-            }
-            let buffer = Message::BlockHeaderHash(block_hash, i).serialize();
+        for block in blockchain.iter_longest_chain(last_shared_ancestor) {
+            let buffer = Message::BlockHeaderHash(block.hash, block.id).serialize();
             self.network
                 .io_interface
                 .send_message(peer_index, buffer)
@@ -237,6 +733,20 @@ impl RoutingThread {
             peer_index
         );
 
+        // the block is not propagated back to the peers which announced it
+        self.network
+            .on_inventory_received(peer_index, &[block_hash], &[])
+            .await;
+
+        // same block is announced by most of the peers. only the first announcement needs to be fetched
+        if !self.block_announcement_cache.on_announcement(
+            block_hash,
+            peer_index,
+            self.time_keeper.get_timestamp_in_ms(),
+        ) {
+            return;
+        }
+
         self.blockchain_sync_state
             .add_entry(block_hash, block_id, peer_index);
 
@@ -251,9 +761,22 @@ impl RoutingThread {
                 .set_latest_blockchain_id(blockchain.get_latest_block_id());
         }
 
+        if self.block_queue_status.lock().unwrap().is_paused() {
+            // announced blocks are still recorded in the sync state, and fetched when the queue is drained
+            debug!("block queue is full. not fetching new blocks");
+            return;
+        }
+
         self.blockchain_sync_state.build_peer_block_picture();
 
-        let map = self.blockchain_sync_state.request_blocks_from_waitlist();
+        let peer_latencies: ahash::HashMap<PeerIndex, Timestamp>;
+        {
+            let (peers, _peers_) = lock_for_read!(self.network.peers, LOCK_ORDER_PEERS);
+            peer_latencies = peers.get_peers_by_latency().into_iter().collect();
+        }
+        let map = self
+            .blockchain_sync_state
+            .request_blocks_from_waitlist(&peer_latencies);
 
         let mut fetched_blocks: Vec<(PeerIndex, SaitoHash)> = Default::default();
         for (peer_index, vec) in map {
@@ -270,30 +793,199 @@ impl RoutingThread {
                 }
             }
         }
-        self.blockchain_sync_state.mark_as_fetching(fetched_blocks);
+        self.blockchain_sync_state
+            .mark_as_fetching(fetched_blocks, self.time_keeper.get_timestamp_in_ms());
+
+        // blocks are sent for verification in block id order, even if they were received out of order
+        let blocks = self
+            .blockchain_sync_state
+            .get_blocks_ready_for_verification();
+        self.block_queue_status
+            .lock()
+            .unwrap()
+            .on_blocks_sent(blocks.len());
+        for (peer_index, buffer) in blocks {
+            self.send_to_verification_thread(VerifyRequest::Block(buffer, peer_index))
+                .await;
+        }
+    }
+
+    /// Requests the blocks which were not received within the timeout again, possibly from other peers
+    async fn requeue_stalled_fetches(&mut self) {
+        let current_time = self.time_keeper.get_timestamp_in_ms();
+        let stalled_peers = self
+            .blockchain_sync_state
+            .requeue_stalled_fetches(current_time);
+        if stalled_peers.is_empty() {
+            return;
+        }
+        debug!("{:?} block fetches timed out", stalled_peers.len());
+        {
+            // penalize the stalled peers so the blocks are fetched from faster peers
+            let (mut peers, _peers_) = lock_for_write!(self.network.peers, LOCK_ORDER_PEERS);
+            for peer_index in stalled_peers {
+                peers.record_latency(peer_index, BLOCK_FETCH_TIMEOUT_IN_MS);
+            }
+        }
+        self.fetch_next_blocks().await;
+    }
+    /// Drops a block which failed the validation, so it's fetched from the other peers which announced it. The peer is
+    /// penalized the same way as a timed out fetch
+    async fn reject_fetched_block(
+        &mut self,
+        block_hash: SaitoHash,
+        peer_index: PeerIndex,
+        error: FetchedBlockError,
+    ) {
+        warn!(
+            "rejecting block : {:?} fetched from peer : {:?}. error : {:?}",
+            hex::encode(block_hash),
+            peer_index,
+            error
+        );
+        self.stats.rejected_blocks.increment();
+        self.blockchain_sync_state
+            .remove_entry(block_hash, peer_index);
+        {
+            let (mut peers, _peers_) = lock_for_write!(self.network.peers, LOCK_ORDER_PEERS);
+            peers.record_latency(peer_index, BLOCK_FETCH_TIMEOUT_IN_MS);
+        }
+        self.fetch_next_blocks().await;
+    }
+    /// Penalizes the peer which didn't answer the request. Timed out block fetches are queued again so they can be
+    /// fetched from other peers, and fork id requests are sent again since the sync with the peer can't start without
+    /// one
+    async fn handle_peer_request_timeout(
+        &mut self,
+        request_id: RequestId,
+        peer_index: PeerIndex,
+        request: PeerRequest,
+    ) {
+        debug!(
+            "request : {:?} - {:?} to peer : {:?} timed out",
+            request_id,
+            request.get_name(),
+            peer_index
+        );
+        {
+            let (mut peers, _peers_) = lock_for_write!(self.network.peers, LOCK_ORDER_PEERS);
+            if !peers.index_to_peers.contains_key(&peer_index) {
+                return;
+            }
+            peers.record_latency(peer_index, request.get_timeout_in_ms());
+        }
+        match request {
+            PeerRequest::BlockFetch { block_hash } => {
+                if self
+                    .blockchain_sync_state
+                    .requeue_fetch(peer_index, block_hash)
+                {
+                    self.fetch_next_blocks().await;
+                }
+            }
+            PeerRequest::ForkId => {
+                self.network.request_fork_id_from_peer(peer_index).await;
+            }
+            PeerRequest::Ping => {}
+        }
+    }
+    /// applies the server configs which can be changed without restarting the thread
+    fn apply_server_configs(&mut self, server_configs: &Server) {
+        self.trusted_sync.set_config(&server_configs.trusted_sync);
+        self.stale_chain_watchdog
+            .set_threshold(server_configs.stale_chain_threshold_in_ms);
+        self.webhooks.set_config(server_configs.webhooks.clone());
+        self.reconnect_on_stale_chain = server_configs.reconnect_on_stale_chain;
+        if server_configs.block_fetch_limit_per_peer != 0 {
+            self.blockchain_sync_state
+                .set_max_in_flight_per_peer(server_configs.block_fetch_limit_per_peer as usize);
+        }
+        let block_queue_limit = match server_configs.block_queue_limit {
+            0 => DEFAULT_BLOCK_QUEUE_LIMIT,
+            limit => limit as usize,
+        };
+        self.block_queue_status
+            .lock()
+            .unwrap()
+            .set_limit(block_queue_limit);
+    }
+    async fn update_chain_tip(&mut self) {
+        let latest_block_hash;
+        {
+            let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            latest_block_hash = blockchain.get_latest_block_hash();
+        }
+        let status = self
+            .stale_chain_watchdog
+            .on_chain_tip(latest_block_hash, self.time_keeper.get_timestamp_in_ms());
+        if let Some(ChainTipStatus::Recovered { stale_duration }) = status {
+            info!(
+                "chain tip updated to : {:?} after being stale for {:?} ms",
+                hex::encode(latest_block_hash),
+                stale_duration
+            );
+            self.webhooks.notify(
+                self.time_keeper.get_timestamp_in_ms(),
+                WebhookEvent::NodeRecovered {
+                    latest_block_hash: hex::encode(latest_block_hash),
+                    stale_duration,
+                },
+            );
+        }
+    }
+
+    async fn check_stale_chain(&mut self) {
+        let status = self
+            .stale_chain_watchdog
+            .check(self.time_keeper.get_timestamp_in_ms());
+        if let Some(ChainTipStatus::Stale {
+            time_since_last_tip,
+        }) = status
+        {
+            warn!(
+                "STALE CHAIN : no new longest chain block received for {:?} ms. node might be partitioned from the network",
+                time_since_last_tip
+            );
+            self.webhooks.notify(
+                self.time_keeper.get_timestamp_in_ms(),
+                WebhookEvent::NodeStalled {
+                    latest_block_hash: hex::encode(self.stale_chain_watchdog.get_last_tip()),
+                    time_since_last_tip,
+                },
+            );
+            if self.reconnect_on_stale_chain {
+                info!("reconnecting to peers since the chain is stale");
+                self.network.connect_to_static_peers().await;
+                self.blockchain_sync_state.reset();
+                self.block_queue_status.lock().unwrap().reset_in_flight();
+                self.block_announcement_cache.clear();
+                self.network
+                    .request_blockchain_from_all_peers(self.blockchain.clone())
+                    .await;
+            }
+        }
     }
+
     async fn send_to_verification_thread(&mut self, request: VerifyRequest) {
-        // waiting till we get an acceptable sender
-        let sender_count = self.senders_to_verification.len();
-        let mut trials = 0;
+        // sent to the shortest queue, so a thread busy with a large batch or a block doesn't hold up the requests
         loop {
-            trials += 1;
-            self.last_verification_thread_index += 1;
-            let sender_index: usize = self.last_verification_thread_index % sender_count;
-            let sender = self
+            let free_slots: Vec<usize> = self
                 .senders_to_verification
-                .get(sender_index)
-                .expect("sender should be here as we are using the modulus on index");
-
-            if sender.capacity() > 0 {
-                sender.send(request).await.unwrap();
+                .iter()
+                .map(|sender| sender.capacity())
+                .collect();
+            if let Some(sender_index) =
+                select_verification_queue(&free_slots, self.last_verification_thread_index)
+            {
+                self.last_verification_thread_index = sender_index;
+                self.senders_to_verification[sender_index]
+                    .send(request)
+                    .await
+                    .unwrap();
                 return;
             }
-            if trials == sender_count {
-                // if all the channels are full, we will sleep for a bit till some space is available
-                tokio::time::sleep(Duration::from_millis(10)).await;
-                trials = 0;
-            }
+            // if all the channels are full, we will sleep for a bit till some space is available
+            runtime::sleep(Duration::from_millis(10)).await;
         }
     }
 }
@@ -312,15 +1004,22 @@ impl ProcessEvent<RoutingEvent> for RoutingThread {
             }
             NetworkEvent::IncomingNetworkMessage { peer_index, buffer } => {
                 trace!("incoming message received from peer : {:?}", peer_index);
+                let size = buffer.len();
                 let message = Message::deserialize(buffer);
                 if message.is_err() {
                     //todo!()
+                    self.peer_message_stats.on_invalid_message(peer_index);
                     return None;
                 }
+                let message = message.unwrap();
 
                 self.stats.total_incoming_messages.increment();
-                self.process_incoming_message(peer_index, message.unwrap())
-                    .await;
+                self.peer_message_stats.on_message_received(
+                    peer_index,
+                    MessageCategory::from_message(&message),
+                    size,
+                );
+                self.process_incoming_message(peer_index, message).await;
                 return Some(());
             }
             NetworkEvent::PeerConnectionResult {
@@ -347,22 +1046,89 @@ impl ProcessEvent<RoutingEvent> for RoutingThread {
                 unreachable!()
             }
             NetworkEvent::BlockFetched {
+                request_id,
                 block_hash,
                 peer_index,
-                buffer,
+                mut buffer,
+                signature,
             } => {
-                debug!("block received : {:?}", hex::encode(block_hash));
+                debug!(
+                    "block received : {:?} for request : {:?}",
+                    hex::encode(block_hash),
+                    request_id
+                );
+                self.peer_message_stats.on_message_received(
+                    peer_index,
+                    MessageCategory::Fetch,
+                    buffer.len(),
+                );
 
-                self.send_to_verification_thread(VerifyRequest::Block(buffer, peer_index))
-                    .await;
+                let peer_public_key;
+                {
+                    let (peers, _peers_) = lock_for_read!(self.network.peers, LOCK_ORDER_PEERS);
+                    peer_public_key = peers
+                        .index_to_peers
+                        .get(&peer_index)
+                        .and_then(|peer| peer.public_key);
+                }
+                match validate_fetched_block(
+                    &block_hash,
+                    &buffer,
+                    signature.as_ref(),
+                    peer_public_key.as_ref(),
+                ) {
+                    Ok(length) => buffer.truncate(length),
+                    Err(error) => {
+                        self.reject_fetched_block(block_hash, peer_index, error)
+                            .await;
+                        return Some(());
+                    }
+                }
 
                 self.blockchain_sync_state
-                    .mark_as_fetched(peer_index, block_hash);
+                    .add_fetched_block(peer_index, block_hash, buffer);
+
+                let fetch_time = self.blockchain_sync_state.mark_as_fetched(
+                    peer_index,
+                    block_hash,
+                    self.time_keeper.get_timestamp_in_ms(),
+                );
+                if let Some(fetch_time) = fetch_time {
+                    let (mut peers, _peers_) =
+                        lock_for_write!(self.network.peers, LOCK_ORDER_PEERS);
+                    peers.record_latency(peer_index, fetch_time);
+                }
 
                 self.fetch_next_blocks().await;
 
                 return Some(());
             }
+            NetworkEvent::PeerRequestCompleted {
+                request_id,
+                peer_index,
+                request,
+                round_trip_time,
+            } => {
+                trace!(
+                    "request : {:?} - {:?} to peer : {:?} completed in {:?}ms",
+                    request_id,
+                    request.get_name(),
+                    peer_index,
+                    round_trip_time
+                );
+                let (mut peers, _peers_) = lock_for_write!(self.network.peers, LOCK_ORDER_PEERS);
+                peers.record_latency(peer_index, round_trip_time);
+                return Some(());
+            }
+            NetworkEvent::PeerRequestTimedOut {
+                request_id,
+                peer_index,
+                request,
+            } => {
+                self.handle_peer_request_timeout(request_id, peer_index, request)
+                    .await;
+                return Some(());
+            }
         }
         None
     }
@@ -378,6 +1144,26 @@ impl ProcessEvent<RoutingEvent> for RoutingThread {
             self.reconnection_timer = 0;
         }
 
+        self.check_stale_chain().await;
+
+        self.fetch_timeout_timer += duration_value;
+        if self.fetch_timeout_timer >= BLOCK_FETCH_TIMEOUT_CHECK_PERIOD_IN_MS {
+            self.requeue_stalled_fetches().await;
+            self.block_announcement_cache
+                .remove_expired(self.time_keeper.get_timestamp_in_ms());
+            let pending_block_count = self.blockchain_sync_state.get_pending_block_count();
+            self.connection_status
+                .lock()
+                .unwrap()
+                .set_pending_block_count(pending_block_count);
+            self.fetch_timeout_timer = 0;
+            // fetching is resumed by the blockchain updates after the block queue is drained. this covers the case
+            // where the drained blocks didn't extend the chain
+            if pending_block_count > 0 {
+                self.fetch_next_blocks().await;
+            }
+        }
+
         None
     }
 
@@ -385,8 +1171,62 @@ impl ProcessEvent<RoutingEvent> for RoutingThread {
         match event {
             RoutingEvent::BlockchainUpdated => {
                 debug!("received blockchain update event");
+                self.update_chain_tip().await;
                 self.fetch_next_blocks().await;
             }
+            RoutingEvent::BlockchainRolledBack => {
+                info!("blockchain rolled back. restarting the sync with peers");
+                self.update_chain_tip().await;
+                self.blockchain_sync_state.reset();
+                self.block_queue_status.lock().unwrap().reset_in_flight();
+                self.block_announcement_cache.clear();
+                self.network
+                    .request_blockchain_from_all_peers(self.blockchain.clone())
+                    .await;
+            }
+            RoutingEvent::TransactionBatchVerified { peer_index, result } => {
+                debug!(
+                    "sending results of transaction batch : {:?} to peer : {:?}. accepted : {:?}/{:?}",
+                    result.batch_id,
+                    peer_index,
+                    result.get_accepted_count(),
+                    result.results.len()
+                );
+                if let Err(error) = self
+                    .network
+                    .send_message_to_peer(peer_index, &Message::TransactionBatchResult(result))
+                    .await
+                {
+                    warn!(
+                        "failed sending transaction batch result to peer : {:?} : {:?}",
+                        peer_index, error
+                    );
+                }
+            }
+            RoutingEvent::TransactionRejected {
+                peer_index,
+                rejection,
+            } => {
+                trace!(
+                    "sending rejection of transaction : {:?} to peer : {:?}. reason : {:?}",
+                    hex::encode(rejection.signature),
+                    peer_index,
+                    rejection.reason
+                );
+                if let Err(error) = self
+                    .network
+                    .send_message_to_peer(peer_index, &Message::TransactionRejected(rejection))
+                    .await
+                {
+                    warn!(
+                        "failed sending transaction rejection to peer : {:?} : {:?}",
+                        peer_index, error
+                    );
+                }
+            }
+            RoutingEvent::BlockFromBlacklistedCreator { peer_index } => {
+                self.peer_message_stats.on_blacklisted_block(peer_index);
+            }
         }
         None
     }
@@ -394,10 +1234,17 @@ impl ProcessEvent<RoutingEvent> for RoutingThread {
     async fn on_init(&mut self) {
         assert!(!self.senders_to_verification.is_empty());
         // connect to peers
+        self.network.load_peer_key_pins().await;
         self.network
             .initialize_static_peers(self.configs.clone())
             .await;
 
+        {
+            let (configs, _configs_) = lock_for_read!(self.configs, LOCK_ORDER_CONFIGS);
+            self.apply_server_configs(configs.get_server_configs());
+        }
+        self.update_chain_tip().await;
+
         {
             let (wallet, _wallet_) = lock_for_read!(self.wallet, LOCK_ORDER_WALLET);
             self.public_key = wallet.public_key;
@@ -412,6 +1259,14 @@ impl ProcessEvent<RoutingEvent> for RoutingThread {
             .received_blocks
             .calculate_stats(current_time)
             .await;
+        self.stats
+            .rejected_blocks
+            .calculate_stats(current_time)
+            .await;
+        self.stats
+            .received_golden_tickets
+            .calculate_stats(current_time)
+            .await;
         self.stats
             .total_incoming_messages
             .calculate_stats(current_time)
@@ -426,21 +1281,80 @@ impl ProcessEvent<RoutingEvent> for RoutingThread {
         self.stat_sender.send(stat).await.unwrap();
         for (index, sender) in self.senders_to_verification.iter().enumerate() {
             let stat = format!(
-                "{} - capacity : {:?} / {:?}",
+                "{} - depth : {:?}, capacity : {:?} / {:?}",
                 format!(
                     "{:width$}",
                     format!("verification_{:?}::queue", index),
                     width = 40
                 ),
+                sender.max_capacity() - sender.capacity(),
                 sender.capacity(),
                 sender.max_capacity()
             );
             self.stat_sender.send(stat).await.unwrap();
         }
 
+        let stat = format!(
+            "{} - stale : {:?}, time_since_last_tip : {:?}, stale_alerts : {:?}",
+            format!("{:width$}", "routing::chain_tip", width = 40),
+            self.stale_chain_watchdog.is_stale(),
+            self.stale_chain_watchdog
+                .get_time_since_last_tip(current_time),
+            self.stale_chain_watchdog.stale_alert_count
+        );
+        self.stat_sender.send(stat).await.unwrap();
+
+        let stat = format!(
+            "{} - total : {:?}, duplicates : {:?}, hit_rate : {:.2}%, cached : {:?}",
+            format!("{:width$}", "routing::block_announcements", width = 40),
+            self.block_announcement_cache.total_announcements,
+            self.block_announcement_cache.duplicate_announcements,
+            self.block_announcement_cache.get_hit_rate(),
+            self.block_announcement_cache.len()
+        );
+        self.stat_sender.send(stat).await.unwrap();
+
+        let stat = format!(
+            "{} - version : {:?}",
+            format!("{:width$}", "routing::wire_format", width = 40),
+            MESSAGE_WIRE_FORMAT_VERSION
+        );
+        self.stat_sender.send(stat).await.unwrap();
+
+        for stat in self.peer_message_stats.get_stats() {
+            self.stat_sender.send(stat).await.unwrap();
+        }
+
+        {
+            let (peers, _peers_) = lock_for_read!(self.network.peers, LOCK_ORDER_PEERS);
+            for (peer_index, latency) in peers.get_peers_by_latency() {
+                let stat = format!(
+                    "{} - peer : {:?} latency : {:?} ms",
+                    format!("{:width$}", "routing::peer_latency", width = 40),
+                    peer_index,
+                    latency
+                );
+                self.stat_sender.send(stat).await.unwrap();
+            }
+        }
+
+        let stat = self.block_queue_status.lock().unwrap().get_stats();
+        self.stat_sender.send(stat).await.unwrap();
+
         let stats = self.blockchain_sync_state.get_stats();
         for stat in stats {
             self.stat_sender.send(stat).await.unwrap();
         }
     }
+
+    async fn on_shutdown(&mut self) {
+        info!("stopping routing thread");
+        self.blockchain_sync_state.reset();
+        self.block_announcement_cache.clear();
+    }
+
+    async fn on_config_reload(&mut self, configs: &(dyn Configuration + Send + Sync)) {
+        info!("applying reloaded configs to routing thread");
+        self.apply_server_configs(configs.get_server_configs());
+    }
 }
@@ -1,36 +1,69 @@
+use std::cmp::Reverse;
 use std::collections::VecDeque;
 use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
 use rayon::prelude::*;
-use tokio::sync::mpsc::Sender;
-use tokio::sync::RwLock;
-use tracing::debug;
+use tracing::{debug, warn};
 
 use crate::common::command::NetworkEvent;
 use crate::common::defs::{
-    push_lock, SaitoPublicKey, StatVariable, Timestamp, LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_PEERS,
-    LOCK_ORDER_WALLET,
+    push_lock, SaitoPublicKey, SaitoSignature, StatVariable, Timestamp, LOCK_ORDER_BLOCKCHAIN,
+    LOCK_ORDER_PEERS, LOCK_ORDER_WALLET,
 };
 use crate::common::process_event::ProcessEvent;
+use crate::common::runtime::{RwLock, Sender};
 use crate::core::consensus_thread::ConsensusEvent;
 use crate::core::data::block::Block;
 use crate::core::data::blockchain::Blockchain;
+use crate::core::data::msg::transaction_batch::{
+    TransactionBatch, TransactionBatchResult, TransactionRejection, TransactionResult,
+};
 use crate::core::data::peer_collection::PeerCollection;
-use crate::core::data::transaction::Transaction;
+use crate::core::data::transaction::{Transaction, TransactionRejectReason};
 use crate::core::data::wallet::Wallet;
+use crate::core::routing_thread::RoutingEvent;
 use crate::lock_for_read;
 
+/// Where the result of a transaction batch is sent after the transactions are added to the mempool
+#[derive(Debug)]
+pub enum TransactionBatchSource {
+    /// sent back to the peer by the routing thread
+    Peer(u64),
+    /// sent to the given channel. used to submit batches from within the node
+    Local(Sender<TransactionBatchResult>),
+}
+
 #[derive(Debug)]
 pub enum VerifyRequest {
-    Transaction(Transaction),
+    /// transaction and the index of the peer which sent it. None for transactions submitted by the node itself
+    Transaction(Transaction, Option<u64>),
     Transactions(VecDeque<Transaction>),
     Block(Vec<u8>, u64),
+    TransactionBatch(TransactionBatch, TransactionBatchSource),
+}
+
+/// Index of the verification queue to send the next request to, given the free slots of each queue. The queue with the
+/// most free slots is picked, and the ties go to the first queue after the last used one so the threads are used in
+/// turns when they keep up. None if all the queues are full
+pub fn select_verification_queue(free_slots: &[usize], last_index: usize) -> Option<usize> {
+    let count = free_slots.len();
+    if count == 0 {
+        return None;
+    }
+    let last_index = last_index % count;
+    (1..=count)
+        .map(|offset| ((last_index + offset) % count, offset))
+        .filter(|(index, _)| free_slots[*index] > 0)
+        // the closer queues rank higher on ties
+        .max_by_key(|(index, offset)| (free_slots[*index], Reverse(*offset)))
+        .map(|(index, _)| index)
 }
 
 pub struct VerificationThread {
     pub sender_to_consensus: Sender<ConsensusEvent>,
+    pub sender_to_router: Sender<RoutingEvent>,
     pub blockchain: Arc<RwLock<Blockchain>>,
     pub peers: Arc<RwLock<PeerCollection>>,
     pub wallet: Arc<RwLock<Wallet>>,
@@ -43,68 +76,195 @@ pub struct VerificationThread {
 }
 
 impl VerificationThread {
-    pub async fn verify_tx(&mut self, mut transaction: Transaction) {
+    /// Tells the peer which sent the transaction why it was rejected
+    async fn send_rejection(
+        &self,
+        peer_index: Option<u64>,
+        signature: SaitoSignature,
+        reason: TransactionRejectReason,
+    ) {
+        if let Some(peer_index) = peer_index {
+            self.sender_to_router
+                .send(RoutingEvent::TransactionRejected {
+                    peer_index,
+                    rejection: TransactionRejection { signature, reason },
+                })
+                .await
+                .unwrap();
+        }
+    }
+    pub async fn verify_tx(&mut self, mut transaction: Transaction, peer_index: Option<u64>) {
+        let result;
         {
             transaction.generate(&self.public_key, 0, 0);
 
             let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
 
-            if !transaction.validate(&blockchain.utxoset) {
-                debug!(
-                    "transaction : {:?} not valid",
-                    hex::encode(transaction.signature)
-                );
-                self.processed_txs.increment();
-                return;
-            }
+            result = transaction
+                .validate_submitted(&blockchain.utxoset, blockchain.get_latest_block_timestamp());
         }
 
         self.processed_txs.increment();
+        if let Err(reason) = result {
+            debug!(
+                "transaction : {:?} not valid : {:?}",
+                hex::encode(transaction.signature),
+                reason
+            );
+            self.invalid_txs.increment();
+            self.send_rejection(peer_index, transaction.signature, reason)
+                .await;
+            return;
+        }
         self.processed_msgs.increment();
         self.sender_to_consensus
-            .send(ConsensusEvent::NewTransaction { transaction })
+            .send(ConsensusEvent::NewTransaction {
+                transaction,
+                peer_index,
+            })
             .await
             .unwrap();
     }
-    pub async fn verify_txs(&mut self, transactions: &mut VecDeque<Transaction>) {
+    pub async fn verify_txs(&mut self, transactions: &mut VecDeque<(Transaction, Option<u64>)>) {
         self.processed_txs.increment_by(transactions.len() as u64);
         self.processed_msgs.increment_by(transactions.len() as u64);
-        let prev_count = transactions.len();
-        let txs: Vec<Transaction>;
+        let verified: Vec<(
+            Transaction,
+            Option<u64>,
+            Result<(), TransactionRejectReason>,
+        )>;
         {
             let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            let latest_block_timestamp = blockchain.get_latest_block_timestamp();
 
-            txs = transactions
+            verified = transactions
                 .par_drain(..)
                 .with_min_len(10)
                 // .with_max_len(1000)
-                .filter_map(|mut transaction| {
+                .map(|(mut transaction, peer_index)| {
                     transaction.generate(&self.public_key, 0, 0);
 
-                    if !transaction.validate(&blockchain.utxoset) {
+                    let result =
+                        transaction.validate_submitted(&blockchain.utxoset, latest_block_timestamp);
+                    if let Err(reason) = &result {
                         debug!(
-                            "transaction : {:?} not valid",
-                            hex::encode(transaction.signature)
+                            "transaction : {:?} not valid : {:?}",
+                            hex::encode(transaction.signature),
+                            reason
                         );
+                    }
+                    (transaction, peer_index, result)
+                })
+                .collect();
+        }
 
-                        return None;
+        let mut invalid_txs = 0;
+        for (transaction, peer_index, result) in verified {
+            match result {
+                Ok(()) => {
+                    self.sender_to_consensus
+                        .send(ConsensusEvent::NewTransaction {
+                            transaction,
+                            peer_index,
+                        })
+                        .await
+                        .unwrap();
+                }
+                Err(reason) => {
+                    invalid_txs += 1;
+                    self.send_rejection(peer_index, transaction.signature, reason)
+                        .await;
+                }
+            }
+        }
+        self.invalid_txs.increment_by(invalid_txs);
+    }
+    /// Validates the batch in parallel. Returns the results of the validation and the valid transactions, which are
+    /// added to the mempool by the consensus thread. Transactions in the same batch spending the same slips are all
+    /// accepted here, and the mempool keeps only the first one.
+    pub async fn verify_transaction_batch(
+        &mut self,
+        batch: TransactionBatch,
+    ) -> (TransactionBatchResult, Vec<Transaction>) {
+        self.processed_txs
+            .increment_by(batch.transactions.len() as u64);
+        self.processed_msgs.increment();
+        let public_key = self.public_key;
+        let verified: Vec<(TransactionResult, Option<Transaction>)>;
+        {
+            let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            let latest_block_timestamp = blockchain.get_latest_block_timestamp();
+
+            verified = batch
+                .transactions
+                .into_par_iter()
+                .map(|buffer| {
+                    let mut transaction = match Transaction::try_deserialize_from_net(&buffer) {
+                        Ok(transaction) => transaction,
+                        Err(_) => {
+                            let result = TransactionResult {
+                                signature: [0; 64],
+                                rejection: Some(TransactionRejectReason::InvalidFormat),
+                            };
+                            return (result, None);
+                        }
+                    };
+                    transaction.generate(&public_key, 0, 0);
+                    match transaction
+                        .validate_submitted(&blockchain.utxoset, latest_block_timestamp)
+                    {
+                        Ok(()) => {
+                            let result = TransactionResult {
+                                signature: transaction.signature,
+                                rejection: None,
+                            };
+                            (result, Some(transaction))
+                        }
+                        Err(reason) => {
+                            debug!(
+                                "transaction : {:?} in batch not valid : {:?}",
+                                hex::encode(transaction.signature),
+                                reason
+                            );
+                            let result = TransactionResult {
+                                signature: transaction.signature,
+                                rejection: Some(reason),
+                            };
+                            (result, None)
+                        }
                     }
-                    return Some(transaction);
                 })
                 .collect();
         }
 
-        let invalid_txs = prev_count - txs.len();
-        for transaction in txs {
-            self.sender_to_consensus
-                .send(ConsensusEvent::NewTransaction { transaction })
-                .await
-                .unwrap();
+        let mut results = Vec::with_capacity(verified.len());
+        let mut transactions = vec![];
+        for (result, transaction) in verified {
+            if let Some(transaction) = transaction {
+                transactions.push(transaction);
+            }
+            results.push(result);
         }
-        self.invalid_txs.increment_by(invalid_txs as u64);
+        let result = TransactionBatchResult {
+            batch_id: batch.batch_id,
+            results,
+        };
+        self.invalid_txs
+            .increment_by((result.results.len() - result.get_accepted_count()) as u64);
+        (result, transactions)
     }
     pub async fn verify_block(&mut self, buffer: Vec<u8>, peer_index: u64) {
-        let mut block = Block::deserialize_from_net(&buffer);
+        let mut block = match Block::try_deserialize_versioned(&buffer) {
+            Ok(block) => block,
+            Err(_) => {
+                warn!(
+                    "invalid block of size : {:?} received from peer : {:?}",
+                    buffer.len(),
+                    peer_index
+                );
+                return;
+            }
+        };
         let (peers, _peers_) = lock_for_read!(self.peers, LOCK_ORDER_PEERS);
 
         let peer = peers.index_to_peers.get(&peer_index);
@@ -135,15 +295,28 @@ impl ProcessEvent<VerifyRequest> for VerificationThread {
 
     async fn process_event(&mut self, request: VerifyRequest) -> Option<()> {
         match request {
-            VerifyRequest::Transaction(transaction) => {
-                self.verify_tx(transaction).await;
+            VerifyRequest::Transaction(transaction, peer_index) => {
+                self.verify_tx(transaction, peer_index).await;
             }
             VerifyRequest::Block(block, peer_index) => {
                 self.verify_block(block, peer_index).await;
             }
-            VerifyRequest::Transactions(mut txs) => {
+            VerifyRequest::Transactions(txs) => {
+                let mut txs = txs.into_iter().map(|tx| (tx, None)).collect();
                 self.verify_txs(&mut txs).await;
             }
+            VerifyRequest::TransactionBatch(batch, source) => {
+                let (result, transactions) = self.verify_transaction_batch(batch).await;
+                // the consensus thread adds the mempool rejections to the result and sends it to the source
+                self.sender_to_consensus
+                    .send(ConsensusEvent::NewTransactionBatch {
+                        transactions,
+                        result,
+                        source,
+                    })
+                    .await
+                    .unwrap();
+            }
         }
 
         Some(())
@@ -160,4 +333,29 @@ impl ProcessEvent<VerifyRequest> for VerificationThread {
         self.processed_txs.calculate_stats(current_time).await;
         self.processed_blocks.calculate_stats(current_time).await;
     }
+
+    async fn on_shutdown(&mut self) {
+        debug!(
+            "stopping verification thread. processed txs : {:?}, blocks : {:?}",
+            self.processed_txs.total, self.processed_blocks.total
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::verification_thread::select_verification_queue;
+
+    #[test]
+    fn select_verification_queue_test() {
+        assert_eq!(select_verification_queue(&[], 0), None);
+        assert_eq!(select_verification_queue(&[0, 0, 0], 1), None);
+        // the shortest queue has the most free slots
+        assert_eq!(select_verification_queue(&[10, 30, 20], 1), Some(1));
+        assert_eq!(select_verification_queue(&[0, 5, 0], 1), Some(1));
+        // ties go to the next queue in turn
+        assert_eq!(select_verification_queue(&[10, 10, 10], 0), Some(1));
+        assert_eq!(select_verification_queue(&[10, 10, 10], 2), Some(0));
+        assert_eq!(select_verification_queue(&[10, 5, 10], 0), Some(2));
+    }
 }
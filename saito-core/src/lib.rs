@@ -1,6 +1,11 @@
+//! Consensus, networking and wallet logic of the saito node, independent of the runtime it's running on.
+//!
+//! Applications should use the types re-exported by [`prelude`]. Other module paths can change between releases.
+
 pub mod common;
 pub mod core;
-pub mod saito;
+pub mod prelude;
+mod saito;
 
 #[cfg(test)]
 mod tests {
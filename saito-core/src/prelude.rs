@@ -0,0 +1,69 @@
+//! Stable public API of saito-core.
+//!
+//! Types re-exported here keep their paths between releases, even when the modules they are defined in are moved.
+//! Applications should import from this module instead of the deep `core::data::*` paths.
+//!
+//! Signing and verifying a message :
+//!
+//! ```
+//! use saito_core::prelude::*;
+//!
+//! let (public_key, private_key) = generate_keys();
+//! let message = hash(b"hello saito");
+//! let signature = sign(&message, &private_key);
+//! assert!(verify(&message, &signature, &public_key));
+//! ```
+//!
+//! Creating a transaction and sending it over the network :
+//!
+//! ```
+//! use saito_core::prelude::*;
+//!
+//! let wallet = Wallet::new();
+//! let mut output = Slip::default();
+//! output.public_key = wallet.public_key;
+//! output.amount = 10;
+//!
+//! let mut transaction = Transaction::default();
+//! transaction.add_output(output);
+//! transaction.sign(&wallet.private_key);
+//!
+//! let buffer = transaction.serialize_for_net();
+//! let received = Transaction::deserialize_from_net(&buffer).unwrap();
+//! assert_eq!(received.get_signature(), transaction.get_signature());
+//! ```
+//!
+//! Moving a wallet key to another node :
+//!
+//! ```
+//! use saito_core::prelude::*;
+//!
+//! let wallet = Wallet::new();
+//! let encoded = wallet.export_private_key(KeyFormat::Wif).unwrap();
+//!
+//! let mut imported = Wallet::new();
+//! imported.import_private_key(&encoded, KeyFormat::Wif).unwrap();
+//! assert_eq!(imported.public_key, wallet.public_key);
+//! ```
+
+pub use crate::common::command::NetworkEvent;
+pub use crate::common::defs::{
+    BlockId, Currency, PeerIndex, SaitoHash, SaitoPrivateKey, SaitoPublicKey, SaitoSignature,
+    SaitoUTXOSetKey, Timestamp, UtxoSet,
+};
+pub use crate::common::interface_io::InterfaceIO;
+pub use crate::common::keep_time::{KeepTime, SimulatedClock};
+pub use crate::common::process_event::ProcessEvent;
+pub use crate::common::run_task::RunTask;
+pub use crate::core::data::block::{Block, BlockType};
+pub use crate::core::data::blockchain::Blockchain;
+pub use crate::core::data::configuration::{Configuration, Endpoint, PeerConfig, Server};
+pub use crate::core::data::crypto::{generate_keys, hash, sign, verify};
+pub use crate::core::data::golden_ticket::GoldenTicket;
+pub use crate::core::data::key_format::KeyFormat;
+pub use crate::core::data::mempool::Mempool;
+pub use crate::core::data::network::Network;
+pub use crate::core::data::slip::{Slip, SlipType};
+pub use crate::core::data::storage::Storage;
+pub use crate::core::data::transaction::{Transaction, TransactionType};
+pub use crate::core::data::wallet::Wallet;
@@ -5,6 +5,7 @@ use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use clap::Parser;
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
@@ -17,31 +18,62 @@ use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::Layer;
 
 use saito_core::common::command::NetworkEvent;
-use saito_core::common::defs::{push_lock, StatVariable, LOCK_ORDER_CONFIGS, STAT_BIN_COUNT};
+use saito_core::common::defs::{
+    push_lock, StatVariable, LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_CONFIGS, LOCK_ORDER_MEMPOOL,
+    LOCK_ORDER_WALLET, STAT_BIN_COUNT,
+};
 use saito_core::common::keep_time::KeepTime;
+use saito_core::common::network_lanes::{
+    network_event_lanes, NetworkEventLaneReceiver, NetworkEventLaneSender,
+};
 use saito_core::common::process_event::ProcessEvent;
 use saito_core::core::consensus_thread::{ConsensusEvent, ConsensusStats, ConsensusThread};
+use saito_core::core::data::audit_log::AuditLog;
+use saito_core::core::data::block_announcement_cache::{
+    BlockAnnouncementCache, BLOCK_ANNOUNCEMENT_CACHE_SIZE, BLOCK_ANNOUNCEMENT_CACHE_TTL_IN_MS,
+};
 use saito_core::core::data::blockchain::Blockchain;
 use saito_core::core::data::blockchain_sync_state::BlockchainSyncState;
-use saito_core::core::data::configuration::Configuration;
+use saito_core::core::data::configuration::{Configuration, VerificationConfig};
 use saito_core::core::data::context::Context;
+use saito_core::core::data::data_migration::run_data_migrations;
+use saito_core::core::data::difficulty::create_difficulty_policy;
+use saito_core::core::data::issuance::Issuance;
 use saito_core::core::data::network::Network;
 use saito_core::core::data::peer_collection::PeerCollection;
-use saito_core::core::data::storage::Storage;
-use saito_core::core::data::wallet::Wallet;
+use saito_core::core::data::peer_events::PeerEventListeners;
+use saito_core::core::data::peer_message_stats::PeerMessageStats;
+use saito_core::core::data::stale_chain_watchdog::StaleChainWatchdog;
+use saito_core::core::data::storage::{PendingBlockWrites, Storage, BLOCK_WRITE_QUEUE_SIZE};
+use saito_core::core::data::transaction_index::TransactionIndex;
+use saito_core::core::data::trusted_sync::TrustedSync;
+use saito_core::core::data::tx_generator::TransactionGenerator;
+use saito_core::core::data::utxo_report::UtxoReportRequest;
+use saito_core::core::data::wallet::{start_wallet_updater, Wallet};
+use saito_core::core::data::webhook::Webhooks;
 use saito_core::core::mining_thread::{MiningEvent, MiningThread};
 use saito_core::core::routing_thread::{
     PeerState, RoutingEvent, RoutingStats, RoutingThread, StaticPeer,
 };
 use saito_core::core::verification_thread::{VerificationThread, VerifyRequest};
-use saito_core::lock_for_read;
+use saito_core::{lock_for_read, lock_for_write};
 
+use crate::saito::admin::{
+    read_issuance_file, read_transaction_file, run_chain_command, run_config_command,
+    run_conformance_command, run_data_command, run_peer_command, run_wallet_command,
+};
+use crate::saito::archive_sink::DirectoryArchiveSink;
+use crate::saito::cli::{ChainCommand, Cli, Command, ConfigCommand, RunArgs};
 use crate::saito::config_handler::ConfigHandler;
+use crate::saito::daemon::{run_status_file_writer, spawn_daemon, NodeState, NodeStatus, PidFile};
 use crate::saito::io_event::IoEvent;
+use crate::saito::lifecycle::{LifecycleController, LifecycleListener};
 use crate::saito::network_controller::run_network_controller;
-use crate::saito::rust_io_handler::RustIOHandler;
+use crate::saito::peer_events::PeerEventBroadcaster;
+use crate::saito::rust_io_handler::{set_data_dir, RustIOHandler};
 use crate::saito::stat_thread::StatThread;
 use crate::saito::time_keeper::TimeKeeper;
+use crate::saito::webhook_client::HttpWebhookClient;
 
 mod saito;
 mod test;
@@ -52,10 +84,11 @@ const MINING_EVENT_PROCESSOR_ID: u8 = 3;
 
 async fn run_thread<T>(
     mut event_processor: Box<(dyn ProcessEvent<T> + Send + 'static)>,
-    mut network_event_receiver: Option<Receiver<NetworkEvent>>,
+    mut network_event_receiver: Option<NetworkEventLaneReceiver>,
     mut event_receiver: Option<Receiver<T>>,
     stat_timer_in_ms: u64,
     thread_sleep_time_in_ms: u64,
+    mut lifecycle_listener: LifecycleListener,
 ) -> JoinHandle<()>
 where
     T: Send + 'static,
@@ -70,12 +103,18 @@ where
         event_processor.on_init().await;
 
         loop {
+            if !lifecycle_listener
+                .process_events(event_processor.as_mut())
+                .await
+            {
+                break;
+            }
             work_done = false;
             if network_event_receiver.is_some() {
                 // TODO : update to recv().await
+                // events in the priority lane are processed first
                 let result = network_event_receiver.as_mut().unwrap().try_recv();
-                if result.is_ok() {
-                    let event = result.unwrap();
+                if let Some(event) = result {
                     if event_processor.process_network_event(event).await.is_some() {
                         work_done = true;
                     }
@@ -128,32 +167,43 @@ async fn run_verification_thread(
     mut event_receiver: Receiver<VerifyRequest>,
     stat_timer_in_ms: u64,
     thread_sleep_time_in_ms: u64,
+    batch_size: usize,
+    mut lifecycle_listener: LifecycleListener,
 ) -> JoinHandle<()> {
     tokio::spawn(async move {
         info!("verification thread started");
         let mut work_done;
         let mut stat_timer = Instant::now();
         let time_keeper = TimeKeeper {};
-        let batch_size = 10000;
 
         event_processor.on_init().await;
         let mut queued_requests = vec![];
         let mut requests = VecDeque::with_capacity(batch_size);
 
         loop {
+            if !lifecycle_listener
+                .process_events::<VerifyRequest>(event_processor.as_mut())
+                .await
+            {
+                break;
+            }
             work_done = false;
 
             loop {
                 // TODO : update to recv().await
                 let result = event_receiver.try_recv();
                 if result.is_ok() {
-                    let request = result.unwrap();
-                    if let VerifyRequest::Block(..) = &request {
-                        queued_requests.push(request);
-                        break;
-                    }
-                    if let VerifyRequest::Transaction(tx) = request {
-                        requests.push_back(tx);
+                    match result.unwrap() {
+                        VerifyRequest::Transaction(tx, peer_index) => {
+                            requests.push_back((tx, peer_index));
+                        }
+                        request @ VerifyRequest::Block(..) => {
+                            queued_requests.push(request);
+                            break;
+                        }
+                        request => {
+                            queued_requests.push(request);
+                        }
                     }
                 } else {
                     break;
@@ -200,21 +250,32 @@ async fn run_mining_event_processor(
     thread_sleep_time_in_ms: u64,
     channel_size: usize,
     sender_to_stat: Sender<String>,
-) -> (Sender<NetworkEvent>, JoinHandle<()>) {
+    lifecycle: &LifecycleController,
+) -> (NetworkEventLaneSender, JoinHandle<()>) {
+    let mining_enabled;
+    {
+        let (configs, _configs_) = lock_for_read!(context.configuration, LOCK_ORDER_CONFIGS);
+        // golden tickets are created by the consensus thread in dev mode
+        mining_enabled = configs.get_server_configs().node_role.is_mining_enabled()
+            && !configs.get_server_configs().dev_mode;
+    }
     let mining_event_processor = MiningThread {
         wallet: context.wallet.clone(),
         sender_to_mempool: sender_to_mempool.clone(),
         time_keeper: Box::new(TimeKeeper {}),
         miner_active: false,
+        mining_enabled,
         target: [0; 32],
         difficulty: 0,
         public_key: [0; 33],
         mined_golden_tickets: 0,
+        hash_count: 0,
         stat_sender: sender_to_stat.clone(),
+        mining_status: context.mining_status.clone(),
     };
 
     let (interface_sender_to_miner, interface_receiver_for_miner) =
-        tokio::sync::mpsc::channel::<NetworkEvent>(channel_size);
+        network_event_lanes(channel_size, channel_size);
 
     debug!("running miner thread");
     let miner_handle = run_thread(
@@ -223,6 +284,7 @@ async fn run_mining_event_processor(
         Some(receiver_for_miner),
         stat_timer_in_ms,
         thread_sleep_time_in_ms,
+        lifecycle.subscribe(),
     )
     .await;
     (interface_sender_to_miner, miner_handle)
@@ -239,27 +301,67 @@ async fn run_consensus_event_processor(
     thread_sleep_time_in_ms: u64,
     channel_size: usize,
     sender_to_stat: Sender<String>,
+    lifecycle: &LifecycleController,
+    issuance: Option<Issuance>,
+    pending_block_writes: PendingBlockWrites,
 ) -> (Sender<NetworkEvent>, JoinHandle<()>) {
-    let result = std::env::var("GEN_TX");
-    let mut create_test_tx = false;
-    if result.is_ok() {
-        create_test_tx = result.unwrap().eq("1");
-    }
     let generate_genesis_block: bool;
+    let dev_mode: bool;
+    let submit_golden_tickets: bool;
+    let mut tx_generator_config;
+    let audit_log_config;
+    let webhook_config;
+    let block_production_quorum;
     {
         let (configs, _configs_) = lock_for_read!(context.configuration, LOCK_ORDER_CONFIGS);
 
+        tx_generator_config = configs.get_server_configs().tx_generator.clone();
+        tx_generator_config.enabled = tx_generator_config.enabled
+            && configs
+                .get_server_configs()
+                .node_role
+                .is_tx_production_enabled();
+
+        dev_mode = configs.get_server_configs().dev_mode;
+        audit_log_config = configs.get_server_configs().audit_log.clone();
+        webhook_config = configs.get_server_configs().webhooks.clone();
+        block_production_quorum = configs.get_server_configs().block_production_quorum.clone();
+        submit_golden_tickets = configs
+            .get_server_configs()
+            .node_role
+            .is_golden_ticket_submission_enabled();
         // if we have peers defined in configs, there's already an existing network. so we don't need to generate the first block.
-        generate_genesis_block = configs.get_peer_configs().is_empty();
+        // a dev mode node always starts its own chain
+        generate_genesis_block = configs.get_peer_configs().is_empty() || dev_mode;
     }
 
+    let mut storage = Storage::new(Box::new(RustIOHandler::new(
+        sender_to_network_controller.clone(),
+        CONSENSUS_EVENT_PROCESSOR_ID,
+    )));
+    storage.start_block_writer(
+        Box::new(RustIOHandler::new(
+            sender_to_network_controller.clone(),
+            CONSENSUS_EVENT_PROCESSOR_ID,
+        )),
+        BLOCK_WRITE_QUEUE_SIZE,
+        pending_block_writes,
+    );
+
     let consensus_event_processor = ConsensusThread {
         mempool: context.mempool.clone(),
         blockchain: context.blockchain.clone(),
         wallet: context.wallet.clone(),
         generate_genesis_block,
+        issuance,
+        dev_mode,
+        submit_golden_tickets,
         sender_to_router: sender_to_routing.clone(),
         sender_to_miner: sender_to_miner.clone(),
+        sender_to_wallet_updater: Some(start_wallet_updater(
+            context.wallet.clone(),
+            channel_size,
+        )),
         // sender_global: global_sender.clone(),
         time_keeper: Box::new(TimeKeeper {}),
         network: Network::new(
@@ -270,16 +372,20 @@ async fn run_consensus_event_processor(
             peers.clone(),
             context.wallet.clone(),
         ),
-        block_producing_timer: 0,
-        tx_producing_timer: 0,
-        create_test_tx,
-        storage: Storage::new(Box::new(RustIOHandler::new(
-            sender_to_network_controller.clone(),
-            CONSENSUS_EVENT_PROCESSOR_ID,
-        ))),
+        next_block_production_time: None,
+        wallet_check_timer: 0,
+        tx_generator: TransactionGenerator::new(tx_generator_config),
+        storage,
         stats: ConsensusStats::new(sender_to_stat.clone()),
         txs_for_mempool: Vec::with_capacity(channel_size),
         stat_sender: sender_to_stat.clone(),
+        audit_log: AuditLog::new(audit_log_config),
+        connection_status: context.connection_status.clone(),
+        block_production_quorum,
+        transaction_receipts: context.transaction_receipts.clone(),
+        block_queue_status: context.block_queue_status.clone(),
+        blockchain_snapshot: context.blockchain_snapshot.clone(),
+        webhooks: Webhooks::new(webhook_config, Some(Arc::new(HttpWebhookClient::new()))),
     };
     let (interface_sender_to_blockchain, interface_receiver_for_mempool) =
         tokio::sync::mpsc::channel::<NetworkEvent>(channel_size);
@@ -290,6 +396,7 @@ async fn run_consensus_event_processor(
         Some(receiver_for_blockchain),
         stat_timer_in_ms,
         thread_sleep_time_in_ms,
+        lifecycle.subscribe(),
     )
     .await;
 
@@ -310,7 +417,11 @@ async fn run_routing_event_processor(
     channel_size: usize,
     sender_to_stat: Sender<String>,
     fetch_batch_size: usize,
-) -> (Sender<NetworkEvent>, JoinHandle<()>) {
+    lifecycle: &LifecycleController,
+    peer_events: PeerEventBroadcaster,
+) -> (NetworkEventLaneSender, JoinHandle<()>) {
+    let mut peer_event_listeners = PeerEventListeners::new();
+    peer_event_listeners.add_listener(Arc::new(peer_events));
     let mut routing_event_processor = RoutingThread {
         blockchain: context.blockchain.clone(),
         sender_to_consensus: sender_to_mempool.clone(),
@@ -328,12 +439,30 @@ async fn run_routing_event_processor(
             context.wallet.clone(),
         ),
         reconnection_timer: 0,
+        fetch_timeout_timer: 0,
         stats: RoutingStats::new(sender_to_stat.clone()),
         public_key: [0; 33],
         senders_to_verification: senders,
         last_verification_thread_index: 0,
         stat_sender: sender_to_stat.clone(),
         blockchain_sync_state: BlockchainSyncState::new(fetch_batch_size),
+        stale_chain_watchdog: StaleChainWatchdog::new(0),
+        // the config is applied when the thread starts
+        webhooks: Webhooks::new(Default::default(), Some(Arc::new(HttpWebhookClient::new()))),
+        reconnect_on_stale_chain: false,
+        block_announcement_cache: BlockAnnouncementCache::new(
+            BLOCK_ANNOUNCEMENT_CACHE_TTL_IN_MS,
+            BLOCK_ANNOUNCEMENT_CACHE_SIZE,
+        ),
+        trusted_sync: TrustedSync::new(),
+        peer_message_stats: PeerMessageStats::new(),
+        storage: Storage::new(Box::new(RustIOHandler::new(
+            sender_to_io_controller.clone(),
+            ROUTING_EVENT_PROCESSOR_ID,
+        ))),
+        connection_status: context.connection_status.clone(),
+        block_queue_status: context.block_queue_status.clone(),
+        peer_events: peer_event_listeners,
     };
 
     {
@@ -350,7 +479,7 @@ async fn run_routing_event_processor(
     }
 
     let (interface_sender_to_routing, interface_receiver_for_routing) =
-        tokio::sync::mpsc::channel::<NetworkEvent>(channel_size);
+        network_event_lanes(channel_size, channel_size);
 
     debug!("running blockchain thread");
     let routing_handle = run_thread(
@@ -359,6 +488,7 @@ async fn run_routing_event_processor(
         Some(receiver_for_routing),
         stat_timer_in_ms,
         thread_sleep_time_in_ms,
+        lifecycle.subscribe(),
     )
     .await;
 
@@ -367,22 +497,27 @@ async fn run_routing_event_processor(
 
 async fn run_verification_threads(
     sender_to_consensus: Sender<ConsensusEvent>,
+    sender_to_routing: Sender<RoutingEvent>,
     blockchain: Arc<RwLock<Blockchain>>,
     peers: Arc<RwLock<PeerCollection>>,
     wallet: Arc<RwLock<Wallet>>,
     stat_timer_in_ms: u64,
     thread_sleep_time_in_ms: u64,
     verification_thread_count: u16,
+    verification_config: VerificationConfig,
     sender_to_stat: Sender<String>,
+    lifecycle: &LifecycleController,
 ) -> (Vec<Sender<VerifyRequest>>, Vec<JoinHandle<()>>) {
     let mut senders = vec![];
     let mut thread_handles = vec![];
 
     for i in 0..verification_thread_count {
-        let (sender, receiver) = tokio::sync::mpsc::channel(1_000_000);
+        let (sender, receiver) =
+            tokio::sync::mpsc::channel(verification_config.queue_size as usize);
         senders.push(sender);
         let verification_thread = VerificationThread {
             sender_to_consensus: sender_to_consensus.clone(),
+            sender_to_router: sender_to_routing.clone(),
             blockchain: blockchain.clone(),
             peers: peers.clone(),
             wallet: wallet.clone(),
@@ -415,6 +550,8 @@ async fn run_verification_threads(
             receiver,
             stat_timer_in_ms,
             thread_sleep_time_in_ms,
+            verification_config.batch_size as usize,
+            lifecycle.subscribe(),
         )
         .await;
         thread_handles.push(thread_handle);
@@ -426,7 +563,7 @@ async fn run_verification_threads(
 // TODO : to be moved to routing event processor
 fn run_loop_thread(
     mut receiver: Receiver<IoEvent>,
-    network_event_sender_to_routing_ep: Sender<NetworkEvent>,
+    mut network_event_sender_to_routing_ep: NetworkEventLaneSender,
     stat_timer_in_ms: u64,
     thread_sleep_time_in_ms: u64,
     sender_to_stat: Sender<String>,
@@ -491,6 +628,10 @@ fn run_loop_thread(
                     incoming_msgs
                         .calculate_stats(TimeKeeper {}.get_timestamp_in_ms())
                         .await;
+                    sender_to_stat
+                        .send(network_event_sender_to_routing_ep.get_stats())
+                        .await
+                        .unwrap();
                 }
             }
             if !work_done {
@@ -504,12 +645,6 @@ fn run_loop_thread(
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    ctrlc::set_handler(move || {
-        info!("shutting down the node");
-        process::exit(0);
-    })
-    .expect("Error setting Ctrl-C handler");
-
     let orig_hook = panic::take_hook();
     panic::set_hook(Box::new(move |panic_info| {
         if let Some(location) = panic_info.location() {
@@ -545,19 +680,110 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     tracing_subscriber::registry().with(fmt_layer).init();
 
+    let cli = Cli::parse();
+    // the network is fixed for the process, since it decides the data directory of all the commands
+    if let Some(network) = cli
+        .network
+        .or_else(|| ConfigHandler::read_network(&cli.config))
+    {
+        info!("using network : {}", network);
+        ConfigHandler::select_network(network);
+        set_data_dir(network.get_params().data_dir);
+    }
+    let command = if cli.check_config {
+        Command::Config {
+            command: ConfigCommand::Validate,
+        }
+    } else {
+        cli.command
+            .unwrap_or_else(|| Command::Run(RunArgs::default()))
+    };
+    if let Command::Run(args) = &command {
+        if args.daemon {
+            match spawn_daemon(&args.log_file) {
+                Ok(pid) => {
+                    println!(
+                        "node started in the background with pid : {:?}. output is written to : {:?}",
+                        pid, args.log_file
+                    );
+                    process::exit(0);
+                }
+                Err(error) => {
+                    eprintln!("failed starting the node in the background : {:?}", error);
+                    process::exit(1);
+                }
+            }
+        }
+    }
+    let result = match command {
+        Command::Run(args) => run_node(cli.config, args).await,
+        Command::Chain {
+            command: ChainCommand::Rollback { block_id },
+        } => {
+            let args = RunArgs {
+                rollback_to: Some(block_id),
+                ..Default::default()
+            };
+            run_node(cli.config, args).await
+        }
+        Command::Chain {
+            command: ChainCommand::Reindex,
+        } => {
+            let args = RunArgs {
+                reindex: true,
+                ..Default::default()
+            };
+            run_node(cli.config, args).await
+        }
+        Command::Wallet { wallet, command } => run_wallet_command(&wallet, command).await,
+        Command::Chain { command } => run_chain_command(command).await,
+        Command::Peer { command } => run_peer_command(&cli.config, command).await,
+        Command::Config { command } => run_config_command(&cli.config, command),
+        Command::Conformance { command } => run_conformance_command(command),
+        Command::Data { command } => run_data_command(command).await,
+    };
+    if let Err(error) = result {
+        eprintln!("command failed : {:?}", error);
+        process::exit(1);
+    }
+    process::exit(0);
+}
+
+async fn run_node(config_file_path: String, args: RunArgs) -> Result<(), std::io::Error> {
+    // read before starting the node so an invalid file doesn't leave the threads running
+    let transaction_to_submit = match &args.submit_transaction {
+        Some(path) => Some(read_transaction_file(path)?),
+        None => None,
+    };
+    // removed when the node stops
+    let _pid_file = match &args.pid_file {
+        Some(path) => Some(PidFile::create(path)?),
+        None => None,
+    };
+
     info!("load config");
 
-    let configs: Arc<RwLock<Box<dyn Configuration + Send + Sync>>> =
-        Arc::new(RwLock::new(Box::new(
-            ConfigHandler::load_configs("configs/config.json".to_string())
-                .expect("loading configs failed"),
-        )));
+    let configs: Arc<RwLock<Box<dyn Configuration + Send + Sync>>> = Arc::new(RwLock::new(
+        Box::new(ConfigHandler::load_configs(config_file_path.clone())?),
+    ));
 
     let channel_size;
     let thread_sleep_time_in_ms;
     let stat_timer_in_ms;
     let verification_thread_count;
+    let verification_config;
     let fetch_batch_size;
+    let node_role;
+    let full_block_cache_size;
+    let difficulty_config;
+    let transaction_index_enabled;
+    let archive_dir;
+    let max_block_future_drift_in_ms;
+    let max_orphan_blocks;
+    let orphan_block_expiry_in_ms;
+    let max_block_size;
+    let network;
+    let issuance_file;
 
     {
         let (configs, _configs_) = lock_for_read!(configs, LOCK_ORDER_CONFIGS);
@@ -566,9 +792,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         thread_sleep_time_in_ms = configs.get_server_configs().thread_sleep_time_in_ms;
         stat_timer_in_ms = configs.get_server_configs().stat_timer_in_ms;
         verification_thread_count = configs.get_server_configs().verification_threads;
+        verification_config = configs.get_server_configs().verification.clone();
         fetch_batch_size = configs.get_server_configs().block_fetch_batch_size as usize;
         assert_ne!(fetch_batch_size, 0);
+        node_role = configs.get_server_configs().node_role;
+        full_block_cache_size = configs.get_server_configs().full_block_cache_size as usize;
+        difficulty_config = configs.get_server_configs().difficulty.clone();
+        transaction_index_enabled = configs.get_server_configs().transaction_index;
+        archive_dir = configs.get_server_configs().archive_dir.clone();
+        max_block_future_drift_in_ms = configs.get_server_configs().max_block_future_drift_in_ms;
+        max_orphan_blocks = configs.get_server_configs().max_orphan_blocks;
+        orphan_block_expiry_in_ms = configs.get_server_configs().orphan_block_expiry_in_ms;
+        max_block_size = configs.get_server_configs().max_block_size;
+        network = configs.get_server_configs().network.unwrap_or_default();
+        issuance_file = configs.get_server_configs().issuance_file.clone();
     }
+    info!("running node as : {:?}", node_role);
+    // only used if the node creates the genesis block, but an invalid file is reported before starting the threads
+    let issuance = match &issuance_file {
+        Some(path) => Some(read_issuance_file(path)?),
+        None => None,
+    };
     
     info!("start channel");
     let (event_sender_to_loop, event_receiver_in_loop) =
@@ -579,7 +823,86 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     info!("running saito controllers");
 
+    {
+        // the files are migrated before anything is read from the data directory
+        let mut io_handler = RustIOHandler::new(
+            sender_to_network_controller.clone(),
+            CONSENSUS_EVENT_PROCESSOR_ID,
+        );
+        let report = run_data_migrations(&mut io_handler, false).await?;
+        if !report.migrations.is_empty() {
+            for line in report.get_lines() {
+                info!("{}", line);
+            }
+        }
+    }
+
     let context = Context::new(configs.clone());
+    {
+        // a new wallet file is created if it doesn't exist, so the node keeps the same keys after restarting
+        let (mut wallet, _wallet_) = lock_for_write!(context.wallet, LOCK_ORDER_WALLET);
+        let mut storage = Storage::new(Box::new(RustIOHandler::new(
+            sender_to_network_controller.clone(),
+            CONSENSUS_EVENT_PROCESSOR_ID,
+        )));
+        wallet
+            .load_wallet(&args.wallet.file, Some(&args.wallet.password), &mut storage)
+            .await;
+        // the node key signs the handshakes, routing hops and blocks
+        if wallet.is_watch_only() {
+            error!(
+                "watch-only wallet : {:?} cannot be used as the node wallet",
+                args.wallet.file
+            );
+            return Err(std::io::Error::from(std::io::ErrorKind::Unsupported));
+        }
+        info!("using wallet : {:?}", hex::encode(wallet.public_key));
+    }
+
+    let lifecycle = LifecycleController::new(configs.clone());
+    {
+        let lifecycle = lifecycle.clone();
+        ctrlc::set_handler(move || {
+            info!("shutting down the node");
+            lifecycle.shutdown();
+        })
+        .expect("Error setting Ctrl-C handler");
+    }
+    #[cfg(unix)]
+    {
+        let lifecycle = lifecycle.clone();
+        tokio::spawn(async move {
+            let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                .expect("listening for SIGHUP failed");
+            while hangup.recv().await.is_some() {
+                if let Err(error) = lifecycle.reload_configs(&config_file_path).await {
+                    error!("reloading configs failed : {:?}", error);
+                }
+            }
+        });
+    }
+    {
+        let (mut blockchain, _blockchain_) =
+            lock_for_write!(context.blockchain, LOCK_ORDER_BLOCKCHAIN);
+        blockchain.wallet_tracking_enabled = node_role.is_wallet_tracking_enabled();
+        blockchain.set_full_block_cache_size(full_block_cache_size);
+        blockchain.set_difficulty_policy(create_difficulty_policy(&difficulty_config));
+        blockchain.set_max_block_future_drift(max_block_future_drift_in_ms);
+        blockchain.set_genesis_period(network.get_params().genesis_period);
+        if transaction_index_enabled {
+            blockchain.set_transaction_index(TransactionIndex::new());
+        }
+        if let Some(archive_dir) = archive_dir {
+            blockchain.set_archive_sink(Box::new(DirectoryArchiveSink::new(&archive_dir)));
+        }
+    }
+    {
+        let (mut mempool, _mempool_) = lock_for_write!(context.mempool, LOCK_ORDER_MEMPOOL);
+        mempool
+            .orphan_blocks
+            .set_limits(max_orphan_blocks, orphan_block_expiry_in_ms);
+        mempool.set_max_block_size(max_block_size);
+    }
     let peers = Arc::new(RwLock::new(PeerCollection::new()));
 
     let (sender_to_consensus, receiver_for_consensus) =
@@ -595,15 +918,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("run_verification_threads");    
     let (senders, verification_handles) = run_verification_threads(
         sender_to_consensus.clone(),
+        sender_to_routing.clone(),
         context.blockchain.clone(),
         peers.clone(),
         context.wallet.clone(),
         stat_timer_in_ms,
         thread_sleep_time_in_ms,
         verification_thread_count,
+        verification_config,
         sender_to_stat.clone(),
+        &lifecycle,
     )
     .await;
+    let sender_to_verification = senders[0].clone();
+
+    // peer connection events for the /peers/ws subscribers
+    let peer_events = PeerEventBroadcaster::new();
+    // blocks queued for writing, so the block files are not served before they are written
+    let pending_block_writes = PendingBlockWrites::new();
 
     info!("run_routing_event_processor");
     let (network_event_sender_to_routing, routing_handle) = run_routing_event_processor(
@@ -620,6 +952,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         channel_size,
         sender_to_stat.clone(),
         fetch_batch_size,
+        &lifecycle,
+        peer_events.clone(),
     )
     .await;
 
@@ -635,6 +969,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         thread_sleep_time_in_ms,
         channel_size,
         sender_to_stat.clone(),
+        &lifecycle,
+        issuance,
+        pending_block_writes.clone(),
     )
     .await;
 
@@ -647,6 +984,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         thread_sleep_time_in_ms,
         channel_size,
         sender_to_stat.clone(),
+        &lifecycle,
     )
     .await;
     let stat_handle = run_thread(
@@ -655,6 +993,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Some(receiver_for_stat),
         stat_timer_in_ms,
         thread_sleep_time_in_ms,
+        lifecycle.subscribe(),
     )
     .await;
     let loop_handle = run_loop_thread(
@@ -671,17 +1010,83 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         event_sender_to_loop.clone(),
         configs.clone(),
         context.blockchain.clone(),
+        context.blockchain_snapshot.clone(),
+        context.wallet.clone(),
+        context.mining_status.clone(),
+        context.transaction_receipts.clone(),
+        peers.clone(),
+        context.mempool.clone(),
         sender_to_stat.clone(),
+        peer_events,
+        pending_block_writes,
     ));
 
+    let status_file_handle = args.status_file.clone().map(|path| {
+        run_status_file_writer(
+            path,
+            context.blockchain_snapshot.clone(),
+            context.connection_status.clone(),
+        )
+    });
+
+    if let Some(block_id) = args.rollback_to {
+        info!("rolling back the chain to block : {:?}", block_id);
+        sender_to_consensus
+            .send(ConsensusEvent::RollbackChain { block_id })
+            .await
+            .expect("sending rollback event failed");
+    }
+
+    if args.reindex {
+        info!("reindexing the chain from the block files");
+        sender_to_consensus
+            .send(ConsensusEvent::ReindexChain)
+            .await
+            .expect("sending reindex event failed");
+    }
+
+    if let Some(path) = args.utxo_report {
+        let request = UtxoReportRequest::new(path, args.dust_threshold);
+        info!("generating utxo report : {:?}", request);
+        sender_to_consensus
+            .send(ConsensusEvent::GenerateUtxoReport { request })
+            .await
+            .expect("sending utxo report event failed");
+    }
+
+    if let Some(transaction) = transaction_to_submit {
+        info!(
+            "submitting transaction : {:?}",
+            hex::encode(transaction.signature)
+        );
+        sender_to_verification
+            .send(VerifyRequest::Transaction(transaction, None))
+            .await
+            .expect("sending transaction to verification failed");
+    }
+
+    // network controller and the io loop are not stopped by the lifecycle events, so the node exits once the
+    // event processing threads are done
     let _result = tokio::join!(
         routing_handle,
         blockchain_handle,
         miner_handle,
-        loop_handle,
-        network_handle,
         stat_handle,
         futures::future::join_all(verification_handles)
     );
+    loop_handle.abort();
+    network_handle.abort();
+    if let (Some(handle), Some(path)) = (status_file_handle, &args.status_file) {
+        handle.abort();
+        let status = NodeStatus::load(
+            NodeState::Stopped,
+            &context.blockchain_snapshot,
+            &context.connection_status,
+        );
+        if let Err(error) = status.write_to_file(path) {
+            error!("failed writing status file : {:?}", error);
+        }
+    }
+    info!("node stopped");
     Ok(())
 }
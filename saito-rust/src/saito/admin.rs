@@ -0,0 +1,747 @@
+use std::io::{Error, ErrorKind};
+
+use ahash::{AHashMap, AHashSet};
+use tracing::debug;
+
+use saito_core::common::defs::{BlockId, SaitoHash, Timestamp};
+use saito_core::common::interface_io::InterfaceIO;
+use saito_core::core::data::block::Block;
+use saito_core::core::data::chain_export::{ChainExport, CHAIN_EXPORT_SCHEMA_VERSION};
+use saito_core::core::data::configuration::Configuration;
+use saito_core::core::data::conformance::{
+    generate_test_vectors, validate_test_vectors, TestVectorSet,
+};
+use saito_core::core::data::data_migration::{
+    load_data_manifest, run_data_migrations, CURRENT_DATA_VERSION,
+};
+use saito_core::core::data::fork_graph::{ForkGraph, ForkGraphFormat};
+use saito_core::core::data::issuance::Issuance;
+use saito_core::core::data::peer_identity::KeyRotationProof;
+use saito_core::core::data::routing_report::RoutingReport;
+use saito_core::core::data::storage::Storage;
+use saito_core::core::data::transaction::Transaction;
+use saito_core::core::data::utxo_report::UtxoReportFormat;
+use saito_core::core::data::wallet::Wallet;
+
+use crate::saito::cli::{
+    ChainCommand, ConfigCommand, ConformanceCommand, DataCommand, PeerCommand, WalletArgs,
+    WalletCommand,
+};
+use crate::saito::config_handler::ConfigHandler;
+use crate::saito::rust_io_handler::RustIOHandler;
+
+const WALLET_DIR: &str = "data/wallets/";
+
+/// io handler for the commands which only use the disk. network events are not processed
+fn create_io_handler() -> RustIOHandler {
+    let (sender, _receiver) = tokio::sync::mpsc::channel(1);
+    RustIOHandler::new(sender, 0)
+}
+
+fn print_failures(failures: &[String]) -> Result<(), Error> {
+    for failure in failures.iter() {
+        eprintln!("{}", failure);
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::from(ErrorKind::InvalidData))
+    }
+}
+
+pub async fn run_wallet_command(args: &WalletArgs, command: WalletCommand) -> Result<(), Error> {
+    let mut storage = Storage::new(Box::new(create_io_handler()));
+    let exists = storage
+        .file_exists(&(WALLET_DIR.to_string() + args.file.as_str()))
+        .await;
+    let mut wallet = Wallet::new();
+
+    if let WalletCommand::Create = command {
+        if exists {
+            eprintln!("wallet : {} already exists", args.file);
+            return Err(Error::from(ErrorKind::AlreadyExists));
+        }
+        wallet
+            .load_wallet(&args.file, Some(&args.password), &mut storage)
+            .await;
+        println!("wallet : {} created", args.file);
+        println!("public key : {}", hex::encode(wallet.public_key));
+        return Ok(());
+    }
+
+    if let WalletCommand::Import {
+        key,
+        format,
+        public_key,
+    } = &command
+    {
+        if exists {
+            eprintln!("wallet : {} already exists", args.file);
+            return Err(Error::from(ErrorKind::AlreadyExists));
+        }
+        wallet.import_private_key(key, *format).map_err(|error| {
+            eprintln!("failed importing {:?} private key : {:?}", format, error);
+            error
+        })?;
+        if let Some(public_key) = public_key {
+            if hex::encode(wallet.public_key) != public_key.to_lowercase() {
+                eprintln!(
+                    "derived public key : {} doesn't match : {}",
+                    hex::encode(wallet.public_key),
+                    public_key
+                );
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+        }
+        wallet.filename = args.file.clone();
+        wallet.filepass = args.password.clone();
+        wallet.save(&mut storage).await;
+        println!("wallet : {} imported", args.file);
+        println!("public key : {}", hex::encode(wallet.public_key));
+        return Ok(());
+    }
+
+    if let WalletCommand::CreateWatchOnly { public_key } = &command {
+        if exists {
+            eprintln!("wallet : {} already exists", args.file);
+            return Err(Error::from(ErrorKind::AlreadyExists));
+        }
+        let mut wallet = Wallet::new_watch_only(decode_hex::<33>(public_key, "public key")?);
+        wallet.filename = args.file.clone();
+        wallet.filepass = args.password.clone();
+        wallet.save(&mut storage).await;
+        println!("watch-only wallet : {} created", args.file);
+        println!("public key : {}", hex::encode(wallet.public_key));
+        return Ok(());
+    }
+
+    if !exists {
+        eprintln!("wallet : {} not found", args.file);
+        return Err(Error::from(ErrorKind::NotFound));
+    }
+    wallet
+        .load_wallet(&args.file, Some(&args.password), &mut storage)
+        .await;
+    println!("public key : {}", hex::encode(wallet.public_key));
+    if wallet.is_watch_only() {
+        println!("watch-only wallet");
+    }
+    match command {
+        WalletCommand::Export { format } => {
+            let private_key = wallet.export_private_key(format).map_err(|error| {
+                eprintln!("failed exporting private key as {:?} : {:?}", format, error);
+                error
+            })?;
+            println!("private key : {}", private_key);
+        }
+        WalletCommand::Balance => {
+            let block_id = load_wallet_slips(&mut wallet, &mut storage).await?;
+            println!(
+                "balance : {} in {} slips as of block : {}",
+                wallet.get_available_balance(),
+                wallet.get_unspent_slip_count(),
+                block_id
+            );
+        }
+        WalletCommand::CreateUnsigned {
+            to,
+            amount,
+            fee,
+            output,
+        } => {
+            let to = decode_hex::<33>(&to, "recipient")?;
+            load_wallet_slips(&mut wallet, &mut storage).await?;
+            let transaction = wallet
+                .create_unsigned_transaction(to, amount, fee)
+                .ok_or_else(|| {
+                    eprintln!(
+                        "not enough funds. balance : {}",
+                        wallet.get_available_balance()
+                    );
+                    Error::from(ErrorKind::InvalidInput)
+                })?;
+            std::fs::write(&output, hex::encode(transaction.serialize_for_net()))?;
+            println!(
+                "unsigned transaction written to : {}. sign it with `wallet sign` on the machine holding the private key",
+                output
+            );
+        }
+        WalletCommand::Sign { path } => {
+            let mut transaction = read_transaction_file(&path)?;
+            let signature = wallet
+                .sign_offline_transaction(&mut transaction)
+                .map_err(|error| {
+                    eprintln!("failed signing transaction : {:?}", error);
+                    error
+                })?;
+            println!("signature : {}", hex::encode(signature));
+        }
+        WalletCommand::ImportSignature {
+            path,
+            signature,
+            output,
+        } => {
+            let mut transaction = read_transaction_file(&path)?;
+            let signature = decode_hex::<64>(&signature, "signature")?;
+            if !transaction.apply_signature(signature, &wallet.public_key) {
+                eprintln!("signature is not valid for the transaction");
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+            std::fs::write(&output, hex::encode(transaction.serialize_for_net()))?;
+            println!(
+                "signed transaction written to : {}. submit it with `run --submit-transaction`",
+                output
+            );
+        }
+        WalletCommand::RotationProof { new_public_key } => {
+            let new_public_key = decode_hex::<33>(&new_public_key, "new public key")?;
+            if wallet.is_watch_only() {
+                eprintln!("watch-only wallets can't sign a key rotation proof");
+                return Err(Error::from(ErrorKind::InvalidInput));
+            }
+            let proof =
+                KeyRotationProof::new(wallet.public_key, &wallet.private_key, new_public_key);
+            println!("key rotation proof : {}", hex::encode(proof.serialize()));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Adds the wallet's slips in the longest chain on disk. Returns the latest block id
+async fn load_wallet_slips(wallet: &mut Wallet, storage: &mut Storage) -> Result<BlockId, Error> {
+    let io_handler = create_io_handler();
+    let scan = ChainScan::load(&io_handler, false).await?;
+    let longest_chain = scan.get_longest_chain();
+    for block in longest_chain.iter() {
+        let block = storage
+            .load_block_from_disk(io_handler.get_block_dir() + block.file_name.as_str())
+            .await?;
+        wallet.on_chain_reorganization(&block, true);
+    }
+    Ok(longest_chain
+        .last()
+        .map(|block| block.id)
+        .unwrap_or_default())
+}
+
+fn decode_hex<const N: usize>(value: &str, name: &str) -> Result<[u8; N], Error> {
+    hex::decode(value.trim())
+        .ok()
+        .and_then(|buffer| buffer.try_into().ok())
+        .ok_or_else(|| {
+            eprintln!("{} : {} is not {} hex encoded bytes", name, value, N);
+            Error::from(ErrorKind::InvalidInput)
+        })
+}
+
+/// Reads a hex encoded transaction written by `wallet create-unsigned` or `wallet import-signature`
+pub fn read_transaction_file(path: &str) -> Result<Transaction, Error> {
+    let buffer = hex::decode(std::fs::read_to_string(path)?.trim())
+        .map_err(|_| Error::from(ErrorKind::InvalidData))?;
+    Transaction::try_deserialize_versioned(&buffer).map_err(|error| {
+        eprintln!("transaction file : {} is not a valid transaction", path);
+        error
+    })
+}
+
+/// Reads the issuance file of the genesis block set in the server config
+pub fn read_issuance_file(path: &str) -> Result<Issuance, Error> {
+    let content = std::fs::read_to_string(path).map_err(|error| {
+        eprintln!("failed reading issuance file : {} : {:?}", path, error);
+        error
+    })?;
+    Issuance::parse(&content).map_err(|error| {
+        eprintln!("issuance file : {} is not valid : {:?}", path, error);
+        Error::from(ErrorKind::InvalidData)
+    })
+}
+
+/// Block file details needed to find the longest chain
+#[derive(Debug, Clone)]
+struct ScannedBlock {
+    id: BlockId,
+    hash: SaitoHash,
+    previous_block_hash: SaitoHash,
+    timestamp: Timestamp,
+    file_name: String,
+}
+
+/// Blocks stored on disk. Read without the node running, so the chain is rebuilt by following the previous block
+/// hashes from the latest block
+#[derive(Debug, Default)]
+struct ChainScan {
+    blocks: AHashMap<SaitoHash, ScannedBlock>,
+    failures: Vec<String>,
+}
+
+impl ChainScan {
+    /// Reads the block files. Hashes, merkle roots and signatures are also checked if `verify` is set
+    async fn load(io_handler: &RustIOHandler, verify: bool) -> Result<ChainScan, Error> {
+        let mut scan = ChainScan::default();
+        let mut file_names = io_handler.load_block_file_list().await?;
+        file_names.sort();
+        for file_name in file_names {
+            let mut buffer = io_handler
+                .read_value(io_handler.get_block_dir() + file_name.as_str())
+                .await?;
+            match Storage::verify_block_file(&buffer) {
+                Ok(length) => buffer.truncate(length),
+                Err(_) => {
+                    scan.failures
+                        .push(format!("{} : corrupted block file", file_name));
+                    continue;
+                }
+            }
+            let mut block = Block::deserialize_from_disk(&buffer);
+            block.generate_pre_hash();
+            block.generate_hash();
+            if verify {
+                if let Err(error) = Self::verify_block(&mut block, &file_name) {
+                    scan.failures.push(format!("{} : {}", file_name, error));
+                    continue;
+                }
+            }
+            debug!("block : {:?} scanned", hex::encode(block.hash));
+            scan.blocks.insert(
+                block.hash,
+                ScannedBlock {
+                    id: block.id,
+                    hash: block.hash,
+                    previous_block_hash: block.get_previous_block_hash(),
+                    timestamp: block.get_timestamp(),
+                    file_name,
+                },
+            );
+        }
+        Ok(scan)
+    }
+
+    fn verify_block(block: &mut Block, file_name: &str) -> Result<(), String> {
+        if Storage::get_block_hash_from_filename(file_name) != Some(block.hash) {
+            return Err(format!(
+                "block hash : {} doesn't match the file name",
+                hex::encode(block.hash)
+            ));
+        }
+        if !block.has_valid_merkle_root() {
+            return Err("invalid merkle root".to_string());
+        }
+        if !block.has_valid_signature() {
+            return Err("invalid block signature".to_string());
+        }
+        Ok(())
+    }
+
+    /// Returns the longest chain, oldest block first. The chain starts from the oldest block on disk, since the
+    /// blocks before it are pruned
+    fn get_longest_chain(&self) -> Vec<&ScannedBlock> {
+        let mut chain = vec![];
+        let mut current = self
+            .blocks
+            .values()
+            .max_by_key(|block| (block.id, block.timestamp));
+        while let Some(block) = current {
+            chain.push(block);
+            current = self.blocks.get(&block.previous_block_hash);
+        }
+        chain.reverse();
+        chain
+    }
+}
+
+pub async fn run_chain_command(command: ChainCommand) -> Result<(), Error> {
+    let io_handler = create_io_handler();
+    let verify = matches!(command, ChainCommand::Verify);
+    let scan = ChainScan::load(&io_handler, verify).await?;
+    let longest_chain = scan.get_longest_chain();
+
+    let mut failures = scan.failures.clone();
+    if verify {
+        for pair in longest_chain.windows(2) {
+            if pair[1].id != pair[0].id + 1 {
+                failures.push(format!(
+                    "{} : block id : {} doesn't follow the previous block : {}",
+                    pair[1].file_name, pair[1].id, pair[0].id
+                ));
+            }
+        }
+    }
+
+    println!("blocks on disk : {}", scan.blocks.len());
+    println!("invalid block files : {}", scan.failures.len());
+    match (longest_chain.first(), longest_chain.last()) {
+        (Some(first), Some(last)) => {
+            println!(
+                "longest chain : {} - {} ({} blocks)",
+                first.id,
+                last.id,
+                longest_chain.len()
+            );
+            println!(
+                "latest block : {} - {} at {}",
+                last.id,
+                hex::encode(last.hash),
+                last.timestamp
+            );
+        }
+        _ => {
+            println!("longest chain : empty");
+        }
+    }
+    println!("fork blocks : {}", scan.blocks.len() - longest_chain.len());
+
+    match command {
+        ChainCommand::Info => Ok(()),
+        ChainCommand::Verify => print_failures(&failures),
+        ChainCommand::Rollback { .. } => unreachable!("rollback is done by running the node"),
+        ChainCommand::Reindex => unreachable!("reindex is done by running the node"),
+        ChainCommand::RoutingReport { path, from, to } => {
+            write_routing_report(&io_handler, &longest_chain, &path, from, to).await
+        }
+        ChainCommand::Export { dir, from, to } => {
+            write_chain_export(&io_handler, &longest_chain, &dir, from, to).await
+        }
+        ChainCommand::ForkGraph { path, from, to } => {
+            write_fork_graph(&io_handler, &scan, &longest_chain, &path, from, to).await
+        }
+    }
+}
+
+/// Loads and generates the longest chain blocks in the range
+async fn load_blocks_in_range(
+    io_handler: &RustIOHandler,
+    storage: &mut Storage,
+    longest_chain: &[&ScannedBlock],
+    from: Option<BlockId>,
+    to: Option<BlockId>,
+) -> Result<Vec<Block>, Error> {
+    let mut blocks = vec![];
+    for scanned_block in longest_chain.iter() {
+        if from.map_or(false, |from| scanned_block.id < from)
+            || to.map_or(false, |to| scanned_block.id > to)
+        {
+            continue;
+        }
+        let mut block = storage
+            .load_block_from_disk(io_handler.get_block_dir() + scanned_block.file_name.as_str())
+            .await?;
+        block.generate();
+        blocks.push(block);
+    }
+    if blocks.is_empty() {
+        eprintln!("no blocks in the longest chain within the given range");
+        return Err(Error::from(ErrorKind::NotFound));
+    }
+    Ok(blocks)
+}
+
+/// Loads the longest chain blocks in the range and writes the routing report of them to the file
+async fn write_routing_report(
+    io_handler: &RustIOHandler,
+    longest_chain: &[&ScannedBlock],
+    path: &str,
+    from: Option<BlockId>,
+    to: Option<BlockId>,
+) -> Result<(), Error> {
+    let mut storage = Storage::new(Box::new(create_io_handler()));
+    let blocks = load_blocks_in_range(io_handler, &mut storage, longest_chain, from, to).await?;
+
+    let report = RoutingReport::generate(&blocks);
+    storage
+        .write(report.serialize(UtxoReportFormat::from_path(path)), path)
+        .await;
+    println!(
+        "routing report : {} - {} written to : {}",
+        report.from_block_id, report.to_block_id, path
+    );
+    println!(
+        "transactions : {} routed : {} invalid paths : {} nodes : {}",
+        report.transaction_count,
+        report.routed_transaction_count,
+        report.invalid_path_count,
+        report.nodes.len()
+    );
+    println!(
+        "router payouts verified : {} mismatched : {}",
+        report.verified_router_payouts,
+        report.mismatched_router_payouts.len()
+    );
+    print_failures(
+        &report
+            .mismatched_router_payouts
+            .iter()
+            .map(|block_id| format!("block : {} doesn't pay the winning router", block_id))
+            .collect::<Vec<String>>(),
+    )
+}
+
+/// Loads the longest chain blocks in the range and writes the export tables of them to the directory
+async fn write_chain_export(
+    io_handler: &RustIOHandler,
+    longest_chain: &[&ScannedBlock],
+    dir: &str,
+    from: Option<BlockId>,
+    to: Option<BlockId>,
+) -> Result<(), Error> {
+    let mut storage = Storage::new(Box::new(create_io_handler()));
+    let blocks = load_blocks_in_range(io_handler, &mut storage, longest_chain, from, to).await?;
+
+    let export = ChainExport::generate(&blocks);
+    let dir = dir.trim_end_matches('/');
+    for (file_name, contents) in export.get_files() {
+        storage
+            .write(contents.into_bytes(), &format!("{}/{}", dir, file_name))
+            .await;
+    }
+    println!(
+        "chain export : {} - {} written to : {} (schema version : {})",
+        export.from_block_id, export.to_block_id, dir, CHAIN_EXPORT_SCHEMA_VERSION
+    );
+    println!(
+        "blocks : {} transactions : {} slips : {} hops : {}",
+        export.block_count, export.transaction_count, export.slip_count, export.hop_count
+    );
+    Ok(())
+}
+
+/// Loads the blocks on disk in the range, including the forks, and writes the fork graph of them to the file
+async fn write_fork_graph(
+    io_handler: &RustIOHandler,
+    scan: &ChainScan,
+    longest_chain: &[&ScannedBlock],
+    path: &str,
+    from: Option<BlockId>,
+    to: Option<BlockId>,
+) -> Result<(), Error> {
+    let mut storage = Storage::new(Box::new(create_io_handler()));
+    let mut scanned_blocks: Vec<&ScannedBlock> = scan
+        .blocks
+        .values()
+        .filter(|block| {
+            !from.map_or(false, |from| block.id < from) && !to.map_or(false, |to| block.id > to)
+        })
+        .collect();
+    scanned_blocks.sort_by_key(|block| (block.id, block.hash));
+    let mut blocks = vec![];
+    for scanned_block in scanned_blocks {
+        let mut block = storage
+            .load_block_from_disk(io_handler.get_block_dir() + scanned_block.file_name.as_str())
+            .await?;
+        block.generate();
+        blocks.push(block);
+    }
+    if blocks.is_empty() {
+        eprintln!("no blocks on disk within the given range");
+        return Err(Error::from(ErrorKind::NotFound));
+    }
+
+    let longest_chain: AHashSet<SaitoHash> = longest_chain.iter().map(|block| block.hash).collect();
+    let graph = ForkGraph::generate(&blocks, &longest_chain);
+    storage
+        .write(graph.serialize(ForkGraphFormat::from_path(path)), path)
+        .await;
+    println!(
+        "fork graph : {} - {} written to : {}",
+        graph.from_block_id, graph.to_block_id, path
+    );
+    println!(
+        "blocks : {} fork blocks : {}",
+        graph.blocks.len(),
+        graph.get_fork_block_count()
+    );
+    Ok(())
+}
+
+pub async fn run_peer_command(config_file_path: &str, command: PeerCommand) -> Result<(), Error> {
+    match command {
+        PeerCommand::List => {
+            let configs = ConfigHandler::load_configs(config_file_path.to_string())?;
+            for peer in configs.get_peer_configs() {
+                println!(
+                    "{}://{}:{} ({}, {})",
+                    peer.protocol,
+                    peer.host,
+                    peer.port,
+                    peer.synctype,
+                    peer.get_websocket_scheme()
+                );
+            }
+            Ok(())
+        }
+        PeerCommand::Add {
+            host,
+            port,
+            protocol,
+            synctype,
+        } => {
+            add_peer_to_config_file(config_file_path, &host, port, &protocol, &synctype)?;
+            println!("peer : {}:{} added to : {}", host, port, config_file_path);
+            Ok(())
+        }
+    }
+}
+
+/// Adds the peer to the config file. Other values in the file are kept as they are
+fn add_peer_to_config_file(
+    config_file_path: &str,
+    host: &str,
+    port: u16,
+    protocol: &str,
+    synctype: &str,
+) -> Result<(), Error> {
+    let mut configs: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(config_file_path)?)
+            .map_err(|_| Error::from(ErrorKind::InvalidData))?;
+    let peers = configs
+        .as_object_mut()
+        .ok_or_else(|| Error::from(ErrorKind::InvalidData))?
+        .entry("peers")
+        .or_insert_with(|| serde_json::Value::Array(vec![]))
+        .as_array_mut()
+        .ok_or_else(|| Error::from(ErrorKind::InvalidData))?;
+    if peers
+        .iter()
+        .any(|peer| peer["host"] == host && peer["port"] == port)
+    {
+        eprintln!("peer : {}:{} already exists", host, port);
+        return Err(Error::from(ErrorKind::AlreadyExists));
+    }
+    peers.push(serde_json::json!({
+        "host": host,
+        "port": port,
+        "protocol": protocol,
+        "synctype": synctype,
+    }));
+    let buffer =
+        serde_json::to_string_pretty(&configs).map_err(|_| Error::from(ErrorKind::InvalidData))?;
+    std::fs::write(config_file_path, buffer)
+}
+
+pub fn run_config_command(config_file_path: &str, command: ConfigCommand) -> Result<(), Error> {
+    match command {
+        ConfigCommand::Validate => {
+            let (_, report) = ConfigHandler::check_configs(config_file_path);
+            for warning in report.warnings.iter() {
+                println!("warning : {}", warning);
+            }
+            print_failures(&report.errors)?;
+            println!("configs in : {} are valid", config_file_path);
+            Ok(())
+        }
+    }
+}
+
+pub async fn run_data_command(command: DataCommand) -> Result<(), Error> {
+    let mut io_handler = create_io_handler();
+    match command {
+        DataCommand::Version => {
+            let manifest = load_data_manifest(&io_handler).await?;
+            println!(
+                "data version : {} (written by node : {})",
+                manifest.version,
+                if manifest.node_version.is_empty() {
+                    "unknown"
+                } else {
+                    manifest.node_version.as_str()
+                }
+            );
+            println!("supported data version : {}", CURRENT_DATA_VERSION);
+            // lists the pending migrations without changing the files
+            let report = run_data_migrations(&mut io_handler, true).await?;
+            for line in report.get_lines().iter().skip(1) {
+                println!("{}", line);
+            }
+            Ok(())
+        }
+        DataCommand::Migrate { dry_run } => {
+            let report = run_data_migrations(&mut io_handler, dry_run).await?;
+            for line in report.get_lines() {
+                println!("{}", line);
+            }
+            println!("changed files : {}", report.get_change_count());
+            Ok(())
+        }
+    }
+}
+
+pub fn run_conformance_command(command: ConformanceCommand) -> Result<(), Error> {
+    match command {
+        ConformanceCommand::Generate { path } => {
+            std::fs::write(&path, generate_test_vectors().to_json()?)?;
+            println!("test vectors written to : {}", path);
+            Ok(())
+        }
+        ConformanceCommand::Validate { path } => {
+            let set = TestVectorSet::from_json(&std::fs::read_to_string(&path)?)?;
+            let failures = validate_test_vectors(&set);
+            println!(
+                "{} of {} test vectors passed",
+                set.vectors.len() - failures.len(),
+                set.vectors.len()
+            );
+            print_failures(&failures)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use saito_core::core::data::configuration::Configuration;
+
+    use crate::saito::admin::{add_peer_to_config_file, ChainScan, ScannedBlock};
+    use crate::saito::config_handler::ConfigHandler;
+
+    fn create_block(id: u64, hash: u8, previous_block_hash: u8) -> ScannedBlock {
+        ScannedBlock {
+            id,
+            hash: [hash; 32],
+            previous_block_hash: [previous_block_hash; 32],
+            timestamp: id,
+            file_name: hash.to_string(),
+        }
+    }
+
+    #[test]
+    fn longest_chain_test() {
+        let mut scan = ChainScan::default();
+        // block 2 is pruned, so the chain starts from block 3
+        for block in [
+            create_block(1, 1, 0),
+            create_block(3, 3, 2),
+            create_block(4, 4, 3),
+            create_block(4, 5, 3),
+            create_block(5, 6, 5),
+        ] {
+            scan.blocks.insert(block.hash, block);
+        }
+        let chain: Vec<u8> = scan
+            .get_longest_chain()
+            .iter()
+            .map(|block| block.hash[0])
+            .collect();
+        assert_eq!(chain, vec![3, 5, 6]);
+    }
+
+    #[test]
+    fn add_peer_to_config_file_test() {
+        let path = "./data/test/admin_config.json";
+        std::fs::create_dir_all("./data/test").unwrap();
+        std::fs::copy("src/test/data/config_handler_tests.json", path).unwrap();
+        let peer_count = ConfigHandler::load_configs(path.to_string())
+            .unwrap()
+            .get_peer_configs()
+            .len();
+
+        add_peer_to_config_file(path, "10.0.0.1", 12200, "https", "lite").unwrap();
+        assert!(add_peer_to_config_file(path, "10.0.0.1", 12200, "http", "full").is_err());
+
+        let configs = ConfigHandler::load_configs(path.to_string()).unwrap();
+        assert_eq!(configs.get_peer_configs().len(), peer_count + 1);
+        let peer = configs.get_peer_configs().last().unwrap();
+        assert_eq!(peer.host, "10.0.0.1");
+        assert_eq!(peer.get_websocket_scheme(), "wss");
+        assert!(ConfigHandler::validate_configs(&configs).is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,79 @@
+use std::io::Error;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use tracing::debug;
+
+use saito_core::common::defs::{BlockId, SaitoHash, BLOCK_FILE_EXTENSION};
+use saito_core::core::data::archive_sink::ArchiveSink;
+
+/// Writes the purged blocks to a directory outside the node's block directory. The directory can be a mounted object
+/// store (S3, IPFS etc.), or synced to one separately.
+#[derive(Debug)]
+pub struct DirectoryArchiveSink {
+    dir: PathBuf,
+}
+
+impl DirectoryArchiveSink {
+    pub fn new(dir: &str) -> DirectoryArchiveSink {
+        DirectoryArchiveSink {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    fn get_file_path(&self, block_id: BlockId, block_hash: &SaitoHash) -> PathBuf {
+        self.dir.join(format!(
+            "{:0>12}-{}{}",
+            block_id,
+            hex::encode(block_hash),
+            BLOCK_FILE_EXTENSION
+        ))
+    }
+}
+
+#[async_trait]
+impl ArchiveSink for DirectoryArchiveSink {
+    async fn archive_block(
+        &self,
+        block_id: BlockId,
+        block_hash: SaitoHash,
+        buffer: Vec<u8>,
+    ) -> Result<(), Error> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.get_file_path(block_id, &block_hash);
+        debug!("archiving block : {:?} to : {:?}", block_id, path);
+        // written under a temporary name first, so a crash doesn't leave a partial block in the archive
+        let temp_path = path.with_extension("tmp");
+        tokio::fs::write(&temp_path, buffer).await?;
+        tokio::fs::rename(&temp_path, &path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use saito_core::core::data::archive_sink::ArchiveSink;
+
+    use crate::saito::archive_sink::DirectoryArchiveSink;
+
+    #[tokio::test]
+    async fn directory_archive_sink_test() {
+        let dir = std::env::temp_dir().join("saito_archive_sink_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let sink = DirectoryArchiveSink::new(dir.to_str().unwrap());
+
+        sink.archive_block(12, [1; 32], vec![1, 2, 3])
+            .await
+            .unwrap();
+
+        let path = sink.get_file_path(12, &[1; 32]);
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("000000000012-0101"));
+        assert_eq!(std::fs::read(&path).unwrap(), vec![1, 2, 3]);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
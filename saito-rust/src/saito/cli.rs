@@ -0,0 +1,490 @@
+use clap::{Args, Parser, Subcommand};
+
+use saito_core::common::defs::Currency;
+use saito_core::core::data::key_format::KeyFormat;
+use saito_core::core::data::network_preset::NetworkPreset;
+use saito_core::core::data::utxo_report::DEFAULT_DUST_THRESHOLD;
+
+pub const CONFIG_FILE_PATH: &str = "configs/config.json";
+const DEFAULT_WALLET_FILE: &str = "default";
+const DEFAULT_WALLET_PASSWORD: &str = "password";
+const DEFAULT_LOG_FILE: &str = "./data/saito.log";
+
+#[derive(Parser, Debug)]
+#[clap(name = "saito-rust", about = "Saito node")]
+pub struct Cli {
+    /// config file of the node
+    #[clap(long, global = true, value_parser, default_value = CONFIG_FILE_PATH)]
+    pub config: String,
+    /// network preset (mainnet, testnet or devnet). overrides `server.network` of the config file
+    #[clap(long, global = true, value_parser)]
+    pub network: Option<NetworkPreset>,
+    /// validates the config file and exits without starting the node. same as `config validate`
+    #[clap(long, action)]
+    pub check_config: bool,
+    /// runs the node if not given
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Runs the node
+    Run(RunArgs),
+    /// Manages the wallet files of the node
+    Wallet {
+        #[clap(flatten)]
+        wallet: WalletArgs,
+        #[clap(subcommand)]
+        command: WalletCommand,
+    },
+    /// Inspects the blocks stored on disk
+    Chain {
+        #[clap(subcommand)]
+        command: ChainCommand,
+    },
+    /// Manages the peers in the config file
+    Peer {
+        #[clap(subcommand)]
+        command: PeerCommand,
+    },
+    /// Checks the config file
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Generates or validates the consensus test vectors
+    Conformance {
+        #[clap(subcommand)]
+        command: ConformanceCommand,
+    },
+    /// Manages the layout version of the data directory
+    Data {
+        #[clap(subcommand)]
+        command: DataCommand,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct WalletArgs {
+    /// wallet file name in data/wallets
+    #[clap(long = "wallet", value_parser, default_value = DEFAULT_WALLET_FILE)]
+    pub file: String,
+    #[clap(long, value_parser, default_value = DEFAULT_WALLET_PASSWORD)]
+    pub password: String,
+}
+
+impl Default for WalletArgs {
+    fn default() -> Self {
+        WalletArgs {
+            file: DEFAULT_WALLET_FILE.to_string(),
+            password: DEFAULT_WALLET_PASSWORD.to_string(),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    #[clap(flatten)]
+    pub wallet: WalletArgs,
+    /// rolls back the chain to the given block id once the chain is loaded
+    #[clap(long, value_parser)]
+    pub rollback_to: Option<u64>,
+    /// rebuilds the blockring and the utxoset from the block files on disk before syncing
+    #[clap(long, action)]
+    pub reindex: bool,
+    /// writes a report of the utxoset distribution to the given file once the chain is loaded
+    #[clap(long, value_parser)]
+    pub utxo_report: Option<String>,
+    /// slips below this amount are counted as dust in the utxo report
+    #[clap(long, value_parser, default_value_t = DEFAULT_DUST_THRESHOLD)]
+    pub dust_threshold: Currency,
+    /// propagates the signed transaction in the given file (see `wallet import-signature`) once the node is running
+    #[clap(long, value_parser)]
+    pub submit_transaction: Option<String>,
+    /// runs the node in the background, detached from the terminal
+    #[clap(long, action)]
+    pub daemon: bool,
+    /// output of the node in daemon mode
+    #[clap(long, value_parser, default_value = DEFAULT_LOG_FILE)]
+    pub log_file: String,
+    /// writes the pid of the node to the given file. the node doesn't start if the file belongs to a running node
+    #[clap(long, value_parser)]
+    pub pid_file: Option<String>,
+    /// writes the height, peer count and sync state of the node to the given json file every few seconds
+    #[clap(long, value_parser)]
+    pub status_file: Option<String>,
+}
+
+impl Default for RunArgs {
+    fn default() -> Self {
+        RunArgs {
+            wallet: Default::default(),
+            rollback_to: None,
+            reindex: false,
+            utxo_report: None,
+            dust_threshold: DEFAULT_DUST_THRESHOLD,
+            submit_transaction: None,
+            daemon: false,
+            log_file: DEFAULT_LOG_FILE.to_string(),
+            pid_file: None,
+            status_file: None,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum WalletCommand {
+    /// Creates a new wallet file
+    Create,
+    /// Shows the public key of the wallet
+    Show,
+    /// Calculates the balance of the wallet from the longest chain on disk
+    Balance,
+    /// Creates a new wallet file with an existing private key
+    Import {
+        #[clap(value_parser)]
+        key: String,
+        /// hex, wif or mnemonic (needs the mnemonic feature)
+        #[clap(long, value_parser, default_value = "hex")]
+        format: KeyFormat,
+        /// hex encoded public key expected for the private key
+        #[clap(long, value_parser)]
+        public_key: Option<String>,
+    },
+    /// Prints the private key of the wallet
+    Export {
+        /// hex, wif or mnemonic (needs the mnemonic feature)
+        #[clap(long, value_parser, default_value = "hex")]
+        format: KeyFormat,
+    },
+    /// Creates a new wallet file with only a public key. The wallet tracks the balance but can't sign
+    CreateWatchOnly {
+        /// hex encoded public key
+        #[clap(value_parser)]
+        public_key: String,
+    },
+    /// Writes an unsigned payment transaction from the wallet's slips in the longest chain on disk
+    CreateUnsigned {
+        /// hex encoded public key of the recipient
+        #[clap(value_parser)]
+        to: String,
+        #[clap(value_parser)]
+        amount: Currency,
+        #[clap(long, value_parser, default_value_t = 0)]
+        fee: Currency,
+        /// file for the hex encoded transaction
+        #[clap(long, value_parser)]
+        output: String,
+    },
+    /// Prints the signature for an unsigned transaction file. Used on the machine holding the private key
+    Sign {
+        #[clap(value_parser)]
+        path: String,
+    },
+    /// Adds a signature to an unsigned transaction file and writes the signed transaction
+    ImportSignature {
+        #[clap(value_parser)]
+        path: String,
+        /// hex encoded signature
+        #[clap(value_parser)]
+        signature: String,
+        /// file for the hex encoded signed transaction
+        #[clap(long, value_parser)]
+        output: String,
+    },
+    /// Prints a proof of moving from the wallet's key to a new key, for the `key_rotation_proof` config of the node
+    /// using the new key
+    RotationProof {
+        /// hex encoded public key of the new wallet
+        #[clap(value_parser)]
+        new_public_key: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ChainCommand {
+    /// Shows the longest chain and fork details of the blocks on disk
+    Info,
+    /// Runs the node after rolling back the chain to the given block
+    Rollback {
+        #[clap(value_parser)]
+        block_id: u64,
+    },
+    /// Runs the node after rebuilding the chain state from the block files on disk
+    Reindex,
+    /// Verifies the hashes, merkle roots and signatures of the blocks on disk
+    Verify,
+    /// Writes the routing work and payouts per node in the longest chain to a csv or json file
+    RoutingReport {
+        #[clap(value_parser)]
+        path: String,
+        /// first block id of the range. defaults to the oldest block on disk
+        #[clap(long, value_parser)]
+        from: Option<u64>,
+        /// last block id of the range. defaults to the latest block
+        #[clap(long, value_parser)]
+        to: Option<u64>,
+    },
+    /// Writes the blocks, transactions, slips and routing hops of the longest chain to csv files in the directory
+    Export {
+        #[clap(value_parser)]
+        dir: String,
+        /// first block id of the range. defaults to the oldest block on disk
+        #[clap(long, value_parser)]
+        from: Option<u64>,
+        /// last block id of the range. defaults to the latest block
+        #[clap(long, value_parser)]
+        to: Option<u64>,
+    },
+    /// Writes the blocks on disk with the forks to a graphviz dot or json file
+    ForkGraph {
+        #[clap(value_parser)]
+        path: String,
+        /// first block id of the range. defaults to the oldest block on disk
+        #[clap(long, value_parser)]
+        from: Option<u64>,
+        /// last block id of the range. defaults to the latest block
+        #[clap(long, value_parser)]
+        to: Option<u64>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PeerCommand {
+    /// Lists the configured peers
+    List,
+    /// Adds a peer to the config file
+    Add {
+        #[clap(value_parser)]
+        host: String,
+        #[clap(value_parser)]
+        port: u16,
+        #[clap(long, value_parser, default_value = "http")]
+        protocol: String,
+        #[clap(long, value_parser, default_value = "full")]
+        synctype: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Checks if the config file can be loaded and the values are usable
+    Validate,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DataCommand {
+    /// Shows the version of the data directory and the migrations pending for this node
+    Version,
+    /// Migrates the data directory to the layout of this node. The node also does this at startup
+    Migrate {
+        /// only reports the files which would be changed
+        #[clap(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConformanceCommand {
+    /// Writes the canonical test vectors to the given json file
+    Generate {
+        #[clap(value_parser)]
+        path: String,
+    },
+    /// Validates this build against the test vectors in the given json file
+    Validate {
+        #[clap(value_parser)]
+        path: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use saito_core::core::data::key_format::KeyFormat;
+    use saito_core::core::data::network_preset::NetworkPreset;
+
+    use crate::saito::cli::{
+        ChainCommand, Cli, Command, DataCommand, WalletCommand, CONFIG_FILE_PATH,
+    };
+
+    #[test]
+    fn parse_cli_test() {
+        let cli = Cli::parse_from(["saito-rust"]);
+        assert_eq!(cli.config, CONFIG_FILE_PATH);
+        assert!(cli.command.is_none());
+        assert!(!cli.check_config);
+        assert!(cli.network.is_none());
+
+        let cli = Cli::parse_from(["saito-rust", "run", "--network", "testnet"]);
+        assert_eq!(cli.network, Some(NetworkPreset::Testnet));
+        assert!(Cli::try_parse_from(["saito-rust", "--network", "regtest"]).is_err());
+
+        let cli = Cli::parse_from(["saito-rust", "--check-config", "--config", "a.json"]);
+        assert!(cli.check_config);
+        assert_eq!(cli.config, "a.json");
+
+        let cli = Cli::parse_from([
+            "saito-rust",
+            "run",
+            "--utxo-report",
+            "report.csv",
+            "--wallet",
+            "node",
+        ]);
+        match cli.command {
+            Some(Command::Run(args)) => {
+                assert_eq!(args.utxo_report, Some("report.csv".to_string()));
+                assert_eq!(args.wallet.file, "node");
+                assert_eq!(args.wallet.password, "password");
+                assert!(args.rollback_to.is_none());
+                assert!(!args.reindex);
+                assert!(!args.daemon);
+                assert!(args.pid_file.is_none());
+            }
+            _ => panic!("unexpected command"),
+        }
+
+        let cli = Cli::parse_from([
+            "saito-rust",
+            "run",
+            "--daemon",
+            "--pid-file",
+            "saito.pid",
+            "--status-file",
+            "status.json",
+        ]);
+        match cli.command {
+            Some(Command::Run(args)) => {
+                assert!(args.daemon);
+                assert_eq!(args.pid_file, Some("saito.pid".to_string()));
+                assert_eq!(args.status_file, Some("status.json".to_string()));
+                assert_eq!(args.log_file, "./data/saito.log");
+            }
+            _ => panic!("unexpected command"),
+        }
+
+        let cli = Cli::parse_from([
+            "saito-rust",
+            "chain",
+            "rollback",
+            "10",
+            "--config",
+            "a.json",
+        ]);
+        assert_eq!(cli.config, "a.json");
+        assert!(matches!(
+            cli.command,
+            Some(Command::Chain {
+                command: ChainCommand::Rollback { block_id: 10 }
+            })
+        ));
+
+        let cli = Cli::parse_from(["saito-rust", "chain", "reindex"]);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Chain {
+                command: ChainCommand::Reindex
+            })
+        ));
+        let cli = Cli::parse_from(["saito-rust", "run", "--reindex"]);
+        match cli.command {
+            Some(Command::Run(args)) => assert!(args.reindex),
+            _ => panic!("unexpected command"),
+        }
+
+        let cli = Cli::parse_from([
+            "saito-rust",
+            "chain",
+            "routing-report",
+            "routing.json",
+            "--from",
+            "5",
+        ]);
+        match cli.command {
+            Some(Command::Chain {
+                command: ChainCommand::RoutingReport { path, from, to },
+            }) => {
+                assert_eq!(path, "routing.json");
+                assert_eq!(from, Some(5));
+                assert!(to.is_none());
+            }
+            _ => panic!("unexpected command"),
+        }
+
+        let cli = Cli::parse_from([
+            "saito-rust",
+            "chain",
+            "fork-graph",
+            "forks.dot",
+            "--to",
+            "20",
+        ]);
+        match cli.command {
+            Some(Command::Chain {
+                command: ChainCommand::ForkGraph { path, from, to },
+            }) => {
+                assert_eq!(path, "forks.dot");
+                assert!(from.is_none());
+                assert_eq!(to, Some(20));
+            }
+            _ => panic!("unexpected command"),
+        }
+
+        let cli = Cli::parse_from(["saito-rust", "data", "migrate", "--dry-run"]);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Data {
+                command: DataCommand::Migrate { dry_run: true }
+            })
+        ));
+        let cli = Cli::parse_from(["saito-rust", "data", "version"]);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Data {
+                command: DataCommand::Version
+            })
+        ));
+
+        let cli = Cli::parse_from([
+            "saito-rust",
+            "wallet",
+            "create-unsigned",
+            "02aa",
+            "100",
+            "--output",
+            "tx.hex",
+        ]);
+        match cli.command {
+            Some(Command::Wallet {
+                command:
+                    WalletCommand::CreateUnsigned {
+                        to,
+                        amount,
+                        fee,
+                        output,
+                    },
+                ..
+            }) => {
+                assert_eq!(to, "02aa");
+                assert_eq!(amount, 100);
+                assert_eq!(fee, 0);
+                assert_eq!(output, "tx.hex");
+            }
+            _ => panic!("unexpected command"),
+        }
+
+        let cli = Cli::parse_from(["saito-rust", "wallet", "export", "--format", "wif"]);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Wallet {
+                command: WalletCommand::Export {
+                    format: KeyFormat::Wif
+                },
+                ..
+            })
+        ));
+    }
+}
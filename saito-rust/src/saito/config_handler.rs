@@ -1,10 +1,18 @@
 use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::sync::Mutex;
 
 use figment::providers::{Format, Json};
 use figment::Figment;
-use saito_core::core::data::configuration::{Configuration, PeerConfig, Server};
+use lazy_static::lazy_static;
+use saito_core::core::data::configuration::{
+    Configuration, PeerConfig, Server, MAX_NETWORK_ID_LENGTH,
+};
+use saito_core::core::data::network_preset::NetworkPreset;
 use serde::Deserialize;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
+
+use crate::saito::tls::validate_server_config;
 
 #[derive(Deserialize, Debug)]
 pub struct NodeConfigurations {
@@ -34,33 +42,233 @@ impl Configuration for NodeConfigurations {
     }
 }
 
+/// Problems found in a config file. All of them are collected, so they can be fixed at once instead of one per run
+#[derive(Debug, Default)]
+pub struct ConfigReport {
+    /// the node doesn't start with these
+    pub errors: Vec<String>,
+    /// fields which are not in the schema. usually typos of optional fields, which would silently use the default
+    pub warnings: Vec<String>,
+}
+
+impl ConfigReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+lazy_static! {
+    // network the node was started with. config reloads can't move the node to another network
+    static ref SELECTED_NETWORK: Mutex<Option<NetworkPreset>> = Mutex::new(None);
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct NetworkSelection {
+    #[serde(default)]
+    server: ServerNetworkSelection,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ServerNetworkSelection {
+    #[serde(default)]
+    network: Option<NetworkPreset>,
+}
+
 pub struct ConfigHandler {}
 
 impl ConfigHandler {
+    /// Applies the preset of the network to all the configs loaded afterwards, in place of `server.network` of the
+    /// config file
+    pub fn select_network(network: NetworkPreset) {
+        *SELECTED_NETWORK.lock().unwrap() = Some(network);
+    }
+
+    /// `server.network` of the config file. None if it's not set, or if the file can't be read. Only the network is
+    /// parsed, so this works for the commands which don't need a valid config file
+    pub fn read_network(config_file_path: &str) -> Option<NetworkPreset> {
+        Figment::new()
+            .merge(Json::file(config_file_path))
+            .extract::<NetworkSelection>()
+            .ok()
+            .and_then(|selection| selection.server.network)
+    }
+
     pub fn load_configs(config_file_path: String) -> Result<NodeConfigurations, Error> {
         debug!(
             "loading configurations from path : {:?} current_dir = {:?}",
             config_file_path,
             std::env::current_dir()
         );
-        // TODO : add prompt with user friendly format
-        let configs = Figment::new()
-            .merge(Json::file(config_file_path))
-            .extract::<NodeConfigurations>();
+        let (configs, report) = ConfigHandler::check_configs(&config_file_path);
+        for warning in report.warnings.iter() {
+            warn!("{}", warning);
+        }
+        for error in report.errors.iter() {
+            error!("{}", error);
+        }
+        match configs {
+            Some(configs) if report.is_valid() => Ok(configs),
+            _ => Err(Error::from(ErrorKind::InvalidInput)),
+        }
+    }
+
+    /// Loads the config file and checks the values. Returns the configs if the file could be parsed, with every
+    /// problem found in it
+    pub fn check_configs(config_file_path: &str) -> (Option<NodeConfigurations>, ConfigReport) {
+        let mut report = ConfigReport::default();
+        let buffer = match std::fs::read(config_file_path) {
+            Ok(buffer) => buffer,
+            Err(error) => {
+                report.errors.push(format!(
+                    "config file : {} can't be read : {}",
+                    config_file_path, error
+                ));
+                return (None, report);
+            }
+        };
+        report.warnings = ConfigHandler::find_unknown_fields(&buffer);
 
-        if configs.is_err() {
-            error!("{:?}", configs.err().unwrap());
-            return Err(std::io::Error::from(ErrorKind::InvalidInput));
+        let mut configs = match Figment::new()
+            .merge(Json::file(config_file_path))
+            .extract::<NodeConfigurations>()
+        {
+            Ok(configs) => configs,
+            Err(errors) => {
+                for error in errors {
+                    report.errors.push(error.to_string());
+                }
+                return (None, report);
+            }
+        };
+        let network = SELECTED_NETWORK.lock().unwrap().or(configs.server.network);
+        if let Some(network) = network {
+            network.apply(&mut configs.server);
         }
+        report.errors = ConfigHandler::validate_configs(&configs);
+        (Some(configs), report)
+    }
 
-        Ok(configs.unwrap())
+    fn find_unknown_fields(buffer: &[u8]) -> Vec<String> {
+        let mut fields = vec![];
+        let mut deserializer = serde_json::Deserializer::from_slice(buffer);
+        // parse errors are reported by figment
+        let _: Result<NodeConfigurations, _> =
+            serde_ignored::deserialize(&mut deserializer, |path| {
+                fields.push(format!("unknown config field : {} is ignored", path))
+            });
+        fields
+    }
+
+    /// Checks the ranges of the values which can be parsed but are not usable
+    pub fn validate_configs(configs: &dyn Configuration) -> Vec<String> {
+        let mut errors = vec![];
+        let server = configs.get_server_configs();
+        if server.port == 0 || server.endpoint.port == 0 {
+            errors
+                .push("server.port and server.endpoint.port should be greater than 0".to_string());
+        }
+        if !["http", "https"].contains(&server.endpoint.protocol.as_str()) {
+            errors.push("server.endpoint.protocol should be http or https".to_string());
+        }
+        if server.network_id.is_empty() || server.network_id.len() > MAX_NETWORK_ID_LENGTH {
+            errors.push(format!(
+                "server.network_id should be between 1 and {} bytes",
+                MAX_NETWORK_ID_LENGTH
+            ));
+        }
+        if server.channel_size == 0 {
+            errors.push("server.channel_size should be greater than 0".to_string());
+        }
+        let cores = std::thread::available_parallelism()
+            .map(|cores| cores.get())
+            .unwrap_or(1);
+        if server.verification_threads == 0 || server.verification_threads as usize > cores {
+            errors.push(format!(
+                "server.verification_threads should be between 1 and the number of cores : {}",
+                cores
+            ));
+        }
+        if server.verification.batch_size == 0 || server.verification.queue_size == 0 {
+            errors.push(
+                "server.verification.batch_size and queue_size should be greater than 0"
+                    .to_string(),
+            );
+        }
+        if server.stat_timer_in_ms == 0 {
+            errors.push("server.stat_timer_in_ms should be greater than 0".to_string());
+        }
+        if server.block_fetch_batch_size == 0 {
+            errors.push("server.block_fetch_batch_size should be greater than 0".to_string());
+        }
+        if server.block_fetch_limit_per_peer > server.block_fetch_batch_size {
+            errors.push(
+                "server.block_fetch_limit_per_peer should not be greater than server.block_fetch_batch_size"
+                    .to_string(),
+            );
+        }
+        if server.peer_slots.max_outbound_peers == 0 && !configs.get_peer_configs().is_empty() {
+            errors.push(
+                "server.peer_slots.max_outbound_peers should be greater than 0 when peers are set"
+                    .to_string(),
+            );
+        }
+        if !server.dns_seeds.hosts.is_empty() && server.dns_seeds.resolve_interval_in_ms == 0 {
+            errors.push(
+                "server.dns_seeds.resolve_interval_in_ms should be greater than 0".to_string(),
+            );
+        }
+        if server.audit_log.enabled
+            && (server.audit_log.file_path.is_empty()
+                || server.audit_log.max_file_size_in_bytes == 0)
+        {
+            errors.push(
+                "server.audit_log should have a file_path and a max_file_size_in_bytes greater than 0"
+                    .to_string(),
+            );
+        }
+        for (index, url) in server.webhooks.urls.iter().enumerate() {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                errors.push(format!(
+                    "server.webhooks.urls[{}] should be an http or https url",
+                    index
+                ));
+            }
+        }
+        for (index, peer) in server
+            .block_production_quorum
+            .required_peers
+            .iter()
+            .enumerate()
+        {
+            if peer.is_empty() {
+                errors.push(format!(
+                    "server.block_production_quorum.required_peers[{}] should be a public key or host:port",
+                    index
+                ));
+            }
+        }
+        if let Some(tls) = server.tls.as_ref() {
+            if validate_server_config(tls).is_err() {
+                errors.push("server.tls certificate or key file is invalid".to_string());
+            }
+        }
+        for (index, peer) in configs.get_peer_configs().iter().enumerate() {
+            if peer.host.is_empty() || peer.port == 0 {
+                errors.push(format!("peers[{}] should have a host and a port", index));
+            }
+            if !["ws", "wss"].contains(&peer.get_websocket_scheme()) {
+                errors.push(format!("peers[{}].scheme should be ws or wss", index));
+            }
+        }
+        errors
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::ConfigHandler;
-    use saito_core::core::data::configuration::Configuration;
+    use saito_core::core::data::configuration::{Configuration, DifficultyConfig, NodeRole};
+    use saito_core::core::data::network_preset::NetworkPreset;
     use std::io::ErrorKind;
 
     #[test]
@@ -81,6 +289,13 @@ mod test {
             configs.get_server_configs().endpoint.protocol,
             String::from("http")
         );
+        assert_eq!(configs.get_server_configs().node_role, NodeRole::Full);
+        assert_eq!(
+            configs.get_server_configs().difficulty,
+            DifficultyConfig::Consensus
+        );
+        assert_eq!(configs.get_server_configs().tls, None);
+        assert_eq!(configs.get_peer_configs()[0].get_websocket_scheme(), "ws");
     }
 
     #[test]
@@ -91,6 +306,88 @@ mod test {
         assert_eq!(result.err().unwrap().kind(), ErrorKind::InvalidInput);
     }
 
+    #[test]
+    fn check_configs_test() {
+        let path = std::env::temp_dir().join("saito_check_configs_test.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "server": {
+                    "host": "localhost",
+                    "port": 12101,
+                    "protocol": "http",
+                    "endpoint": { "host": "localhost", "port": 12101, "protocol": "http" },
+                    "channel_size": 0,
+                    "verification_threads": 0,
+                    "block_fetch_batch_size": 10,
+                    "stale_chain_treshold_in_ms": 1000,
+                    "webhooks": { "urls": ["https://example.com/hook", "localhost:9000"] }
+                },
+                "peers": [{ "host": "", "port": 12102, "protocol": "http", "synctype": "full" }]
+            }"#,
+        )
+        .unwrap();
+        let (configs, report) = ConfigHandler::check_configs(path.to_str().unwrap());
+        let configs = configs.unwrap();
+        // defaults of the missing fields
+        assert_eq!(configs.get_server_configs().stat_timer_in_ms, 5000);
+        assert!(!report.is_valid());
+        assert_eq!(report.errors.len(), 4);
+        assert!(report.errors[0].contains("channel_size"));
+        assert!(report.errors[1].contains("verification_threads"));
+        assert!(report.errors[2].contains("webhooks.urls[1]"));
+        assert!(report.errors[3].contains("peers[0]"));
+        assert_eq!(
+            report.warnings,
+            vec!["unknown config field : server.stale_chain_treshold_in_ms is ignored"]
+        );
+        assert_eq!(
+            ConfigHandler::load_configs(path.to_str().unwrap().to_string())
+                .err()
+                .unwrap()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+
+        std::fs::write(&path, r#"{ "server": { "host": 1 } }"#).unwrap();
+        let (configs, report) = ConfigHandler::check_configs(path.to_str().unwrap());
+        assert!(configs.is_none());
+        assert!(!report.errors.is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn network_preset_config_test() {
+        let path = std::env::temp_dir().join("saito_network_preset_config_test.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "server": {
+                    "host": "localhost",
+                    "protocol": "http",
+                    "endpoint": { "host": "localhost", "protocol": "http" },
+                    "network": "devnet"
+                },
+                "peers": []
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ConfigHandler::read_network(path.to_str().unwrap()),
+            Some(NetworkPreset::Devnet)
+        );
+        assert_eq!(ConfigHandler::read_network("badfilename.json"), None);
+
+        // ports and the network id are filled from the preset
+        let (configs, report) = ConfigHandler::check_configs(path.to_str().unwrap());
+        assert!(report.is_valid(), "{:?}", report.errors);
+        let configs = configs.unwrap();
+        assert_eq!(configs.get_server_configs().network_id, "devnet");
+        assert_eq!(configs.get_server_configs().port, 12301);
+        assert_eq!(configs.get_server_configs().endpoint.port, 12301);
+        std::fs::remove_file(path).unwrap();
+    }
+
     #[test]
     fn load_config_from_non_existing_file() {
         let path = String::from("badfilename.json");
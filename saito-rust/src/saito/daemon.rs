@@ -0,0 +1,270 @@
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use saito_core::common::defs::{BlockId, Timestamp};
+use saito_core::common::keep_time::KeepTime;
+use saito_core::core::data::blockchain_snapshot::BlockchainSnapshot;
+use saito_core::core::data::peer_quorum::ConnectionStatus;
+
+use crate::saito::time_keeper::TimeKeeper;
+
+pub const DAEMON_FLAG: &str = "--daemon";
+const STATUS_FILE_UPDATE_PERIOD_IN_MS: u64 = 5_000;
+
+/// Starts the same command again as a background process detached from the terminal. stdout and stderr of the
+/// background process are appended to the log file. Returns the pid of the background process.
+#[cfg(unix)]
+pub fn spawn_daemon(log_file: &str) -> Result<u32, Error> {
+    use std::fs::OpenOptions;
+    use std::os::unix::process::CommandExt;
+    use std::process::Stdio;
+
+    if let Some(dir) = Path::new(log_file).parent() {
+        if !dir.as_os_str().is_empty() {
+            std::fs::create_dir_all(dir)?;
+        }
+    }
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)?;
+    let args: Vec<std::ffi::OsString> = std::env::args_os()
+        .skip(1)
+        .filter(|arg| arg != DAEMON_FLAG)
+        .collect();
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    unsafe {
+        // a new session so the node doesn't get the signals of the terminal it was started from
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = command.spawn()?;
+    Ok(child.id())
+}
+
+#[cfg(not(unix))]
+pub fn spawn_daemon(_log_file: &str) -> Result<u32, Error> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "daemon mode is only supported on unix",
+    ))
+}
+
+#[cfg(unix)]
+fn is_process_running(pid: u32) -> bool {
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_process_running(_pid: u32) -> bool {
+    false
+}
+
+/// Holds the pid of the running node in a file. The file is removed when dropped
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Writes the pid of this process to the file. Fails if the file belongs to another running process, so two
+    /// nodes are not started with the same data. A file left by a stopped process is overwritten
+    pub fn create(path: &str) -> Result<PidFile, Error> {
+        let path = PathBuf::from(path);
+        if let Ok(content) = std::fs::read_to_string(&path) {
+            if let Ok(pid) = content.trim().parse::<u32>() {
+                if pid != std::process::id() && is_process_running(pid) {
+                    return Err(Error::new(
+                        ErrorKind::AlreadyExists,
+                        format!("node is already running with pid : {:?}", pid),
+                    ));
+                }
+            }
+            warn!("overwriting stale pid file : {:?}", path);
+        }
+        if let Some(dir) = path.parent() {
+            if !dir.as_os_str().is_empty() {
+                std::fs::create_dir_all(dir)?;
+            }
+        }
+        std::fs::write(&path, format!("{}\n", std::process::id()))?;
+        info!("pid file created : {:?}", path);
+        Ok(PidFile { path })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(error) = std::fs::remove_file(&self.path) {
+            warn!("failed removing pid file : {:?}. {:?}", self.path, error);
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeState {
+    Running,
+    Stopped,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncState {
+    /// blocks announced by the peers are still being fetched or added
+    Syncing,
+    Synced,
+}
+
+/// Contents of the status file. Read by the process supervisors and monitoring scripts
+#[derive(Serialize, Debug, Clone)]
+pub struct NodeStatus {
+    pub pid: u32,
+    pub state: NodeState,
+    pub updated_at: Timestamp,
+    pub latest_block_id: BlockId,
+    pub latest_block_hash: String,
+    pub peers: u64,
+    pub sync_state: SyncState,
+    pub pending_blocks: usize,
+}
+
+impl NodeStatus {
+    /// Reads the latest block from the blockchain snapshot, so the status doesn't wait for the blockchain lock
+    pub fn load(
+        state: NodeState,
+        blockchain_snapshot: &Arc<Mutex<Arc<BlockchainSnapshot>>>,
+        connection_status: &Arc<Mutex<ConnectionStatus>>,
+    ) -> NodeStatus {
+        let (latest_block_id, latest_block_hash) = {
+            let snapshot = blockchain_snapshot.lock().unwrap();
+            (snapshot.latest_block_id, snapshot.latest_block_hash)
+        };
+        let (peers, pending_blocks) = {
+            let connection_status = connection_status.lock().unwrap();
+            (
+                connection_status.get_connected_peer_count(),
+                connection_status.get_pending_block_count(),
+            )
+        };
+        NodeStatus {
+            pid: std::process::id(),
+            state,
+            updated_at: TimeKeeper {}.get_timestamp_in_ms(),
+            latest_block_id,
+            latest_block_hash: hex::encode(latest_block_hash),
+            peers,
+            sync_state: if pending_blocks > 0 {
+                SyncState::Syncing
+            } else {
+                SyncState::Synced
+            },
+            pending_blocks,
+        }
+    }
+
+    /// Replaces the file in one step, so the readers never see a partially written status
+    pub fn write_to_file(&self, path: &str) -> Result<(), Error> {
+        let path = Path::new(path);
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&temp_path, path)
+    }
+}
+
+/// Updates the status file periodically until the task is aborted
+pub fn run_status_file_writer(
+    path: String,
+    blockchain_snapshot: Arc<Mutex<Arc<BlockchainSnapshot>>>,
+    connection_status: Arc<Mutex<ConnectionStatus>>,
+) -> JoinHandle<()> {
+    info!("writing node status to : {:?}", path);
+    tokio::spawn(async move {
+        loop {
+            let status =
+                NodeStatus::load(NodeState::Running, &blockchain_snapshot, &connection_status);
+            debug!("writing status : {:?}", status);
+            if let Err(error) = status.write_to_file(&path) {
+                warn!("failed writing status file : {:?}. {:?}", path, error);
+            }
+            tokio::time::sleep(Duration::from_millis(STATUS_FILE_UPDATE_PERIOD_IN_MS)).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::saito::daemon::{NodeState, NodeStatus, PidFile, SyncState};
+
+    #[test]
+    fn pid_file_test() {
+        let dir = std::env::temp_dir().join("saito_pid_file_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("saito.pid");
+        let path_str = path.to_str().unwrap();
+
+        let pid_file = PidFile::create(path_str).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap().trim(),
+            std::process::id().to_string()
+        );
+        drop(pid_file);
+        assert!(!path.exists());
+
+        // left by a process which is not running anymore
+        std::fs::write(&path, "999999999").unwrap();
+        let pid_file = PidFile::create(path_str).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap().trim(),
+            std::process::id().to_string()
+        );
+        drop(pid_file);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_status_file_test() {
+        let dir = std::env::temp_dir().join("saito_status_file_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("status.json");
+
+        let status = NodeStatus {
+            pid: 10,
+            state: NodeState::Running,
+            updated_at: 1000,
+            latest_block_id: 5,
+            latest_block_hash: hex::encode([1; 32]),
+            peers: 2,
+            sync_state: SyncState::Syncing,
+            pending_blocks: 3,
+        };
+        status.write_to_file(path.to_str().unwrap()).unwrap();
+
+        let value: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(value["state"], "running");
+        assert_eq!(value["sync_state"], "syncing");
+        assert_eq!(value["latest_block_id"], 5);
+        assert_eq!(value["peers"], 2);
+        assert_eq!(value["pending_blocks"], 3);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -19,14 +19,21 @@ lazy_static! {
 
 impl IoEvent {
     pub fn new(event: NetworkEvent) -> IoEvent {
-        let mut value = EVENT_COUNTER.lock().unwrap();
-        *value = *value + 1;
-        assert_ne!(*value, 0);
-        trace!("new event created : {:?}", *value);
+        let event_id = get_next_event_id();
+        trace!("new event created : {:?}", event_id);
         IoEvent {
             event_processor_id: 0,
-            event_id: value.clone(),
+            event_id,
             event,
         }
     }
 }
+
+/// Event ids are also used as the ids of the peer requests, so requests created by the network controller (pings)
+/// take their ids from the same counter
+pub fn get_next_event_id() -> u64 {
+    let mut value = EVENT_COUNTER.lock().unwrap();
+    *value = *value + 1;
+    assert_ne!(*value, 0);
+    *value
+}
@@ -0,0 +1,99 @@
+use std::io::Error;
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::TryRecvError;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use saito_core::common::defs::{push_lock, LOCK_ORDER_CONFIGS};
+use saito_core::common::process_event::{LifecycleEvent, ProcessEvent};
+use saito_core::core::data::configuration::Configuration;
+use saito_core::{lock_for_read, lock_for_write};
+
+use crate::saito::config_handler::ConfigHandler;
+
+const LIFECYCLE_CHANNEL_SIZE: usize = 10;
+
+/// Sends the lifecycle events (shutdown, config reload) to all the event processing threads
+#[derive(Clone)]
+pub struct LifecycleController {
+    sender: broadcast::Sender<LifecycleEvent>,
+    configs: Arc<RwLock<Box<dyn Configuration + Send + Sync>>>,
+}
+
+impl LifecycleController {
+    pub fn new(configs: Arc<RwLock<Box<dyn Configuration + Send + Sync>>>) -> LifecycleController {
+        let (sender, _) = broadcast::channel(LIFECYCLE_CHANNEL_SIZE);
+        LifecycleController { sender, configs }
+    }
+
+    /// Creates a listener for a thread. Should be called before the events are sent
+    pub fn subscribe(&self) -> LifecycleListener {
+        LifecycleListener {
+            receiver: self.sender.subscribe(),
+            configs: self.configs.clone(),
+        }
+    }
+
+    pub fn shutdown(&self) {
+        info!("sending shutdown event to threads");
+        if self.sender.send(LifecycleEvent::Shutdown).is_err() {
+            warn!("no threads are listening for the shutdown event");
+        }
+    }
+
+    /// Reloads the configs from the file and notifies the threads
+    pub async fn reload_configs(&self, config_file_path: &str) -> Result<(), Error> {
+        info!("reloading configs from : {:?}", config_file_path);
+        let new_configs = ConfigHandler::load_configs(config_file_path.to_string())?;
+        {
+            let (mut configs, _configs_) = lock_for_write!(self.configs, LOCK_ORDER_CONFIGS);
+            *configs = Box::new(new_configs);
+        }
+        if self.sender.send(LifecycleEvent::ConfigReload).is_err() {
+            warn!("no threads are listening for the config reload event");
+        }
+        Ok(())
+    }
+}
+
+/// Receives the lifecycle events in a thread runner and calls the hooks of the event processor
+pub struct LifecycleListener {
+    receiver: broadcast::Receiver<LifecycleEvent>,
+    configs: Arc<RwLock<Box<dyn Configuration + Send + Sync>>>,
+}
+
+impl LifecycleListener {
+    /// Calls the hooks for the received events. Returns false if the thread should stop.
+    pub async fn process_events<T>(
+        &mut self,
+        event_processor: &mut (dyn ProcessEvent<T> + Send),
+    ) -> bool
+    where
+        T: Send,
+    {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(LifecycleEvent::Shutdown) => {
+                    event_processor.on_shutdown().await;
+                    return false;
+                }
+                Ok(LifecycleEvent::ConfigReload) => {
+                    let (configs, _configs_) = lock_for_read!(self.configs, LOCK_ORDER_CONFIGS);
+                    event_processor.on_config_reload(&**configs).await;
+                }
+                Err(TryRecvError::Lagged(count)) => {
+                    warn!("missed {:?} lifecycle events", count);
+                }
+                Err(TryRecvError::Empty) => {
+                    return true;
+                }
+                Err(TryRecvError::Closed) => {
+                    // controller is dropped, so there won't be a shutdown event
+                    return true;
+                }
+            }
+        }
+    }
+}
@@ -1,9 +1,20 @@
+pub mod admin;
+pub mod archive_sink;
+pub mod cli;
 pub mod config_handler;
+pub mod daemon;
 mod io_context;
 pub mod io_event;
+pub mod lifecycle;
 mod io_future;
 pub mod network_controller;
+pub mod peer_discovery;
+pub mod peer_events;
+pub mod peer_slots;
 pub mod rust_io_handler;
 mod rust_task_runner;
 pub mod stat_thread;
+pub mod status_page;
 pub mod time_keeper;
+pub mod tls;
+pub mod webhook_client;
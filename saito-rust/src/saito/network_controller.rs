@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
 use std::net::SocketAddr;
 use std::str::FromStr;
@@ -10,37 +10,80 @@ use futures::{SinkExt, StreamExt};
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 use tokio::net::TcpStream;
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::{Mutex, RwLock};
 use tokio::task::JoinHandle;
 use tokio::time::Instant;
-use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::{connect_async_tls_with_config, MaybeTlsStream, WebSocketStream};
 use tracing::{debug, error, info, trace, warn};
 use warp::http::StatusCode;
 use warp::ws::WebSocket;
 use warp::Filter;
 
 use saito_core::common::defs::{
-    push_lock, SaitoHash, StatVariable, BLOCK_FILE_EXTENSION, LOCK_ORDER_CONFIGS,
-    LOCK_ORDER_NETWORK_CONTROLLER, STAT_BIN_COUNT,
+    push_lock, SaitoHash, SaitoSignature, StatVariable, Timestamp, BLOCK_FILE_EXTENSION,
+    LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_CONFIGS, LOCK_ORDER_NETWORK_CONTROLLER, LOCK_ORDER_WALLET,
+    STAT_BIN_COUNT,
 };
 use saito_core::common::keep_time::KeepTime;
 use saito_core::core::data;
+use saito_core::core::data::block::BlockType;
+use saito_core::core::data::block_fetch::{
+    sign_block_fetch_response, BlockRangeFrame, BlockRangeRequest, BLOCK_FETCH_MAX_SIZE_IN_BYTES,
+    BLOCK_FETCH_SIGNATURE_HEADER,
+};
 use saito_core::core::data::blockchain::Blockchain;
-use saito_core::core::data::configuration::{Configuration, PeerConfig};
+use saito_core::core::data::blockchain_snapshot::{read_block, BlockchainSnapshot};
+use saito_core::core::data::configuration::{Configuration, DnsSeedConfig, PeerConfig, TlsConfig};
+use saito_core::core::data::mempool::Mempool;
+use saito_core::core::data::mining_status::MiningStatus;
+use saito_core::core::data::peer_collection::PeerCollection;
+use saito_core::core::data::peer_events::PeerEvent;
+use saito_core::core::data::peer_requests::{
+    PeerRequest, PendingPeerRequest, PendingPeerRequests, RequestId,
+};
+use saito_core::core::data::storage::{PendingBlockWrites, BLOCK_STREAM_CHUNK_SIZE};
+use saito_core::core::data::transaction_receipts::TransactionReceipts;
+use saito_core::core::data::wallet::Wallet;
 use saito_core::lock_for_read;
 
-use crate::saito::rust_io_handler::BLOCKS_DIR_PATH;
+use crate::saito::io_event::get_next_event_id;
+use crate::saito::peer_discovery::{resolve_seeds, PeerStore, PEER_STORE_FILE_PATH};
+use crate::saito::peer_events::PeerEventBroadcaster;
+use crate::saito::peer_slots::{ConnectionDirection, PeerSlots, SlotResult};
+use crate::saito::rust_io_handler::{get_data_path, BLOCKS_DIR_PATH};
+use crate::saito::status_page::StatusPage;
+use crate::saito::tls;
 use crate::{IoEvent, NetworkEvent, TimeKeeper};
 
 type SocketSender = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, tungstenite::Message>;
 type SocketReceiver = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
 
+/// how often the peers are pinged to measure their latency
+const PEER_PING_INTERVAL_IN_MS: Timestamp = 30_000;
+/// how often the mining status and the transaction receipts are checked for changes to push to the subscribed sockets
+const STATUS_PUSH_INTERVAL_IN_MS: u64 = 1_000;
+/// how often the pending peer requests are checked for timeouts when there are no events to process
+const PEER_REQUEST_TIMEOUT_CHECK_INTERVAL_IN_MS: u64 = 1_000;
+
 pub struct NetworkController {
     sockets: Arc<Mutex<HashMap<u64, PeerSender>>>,
     peer_counter: Arc<Mutex<PeerCounter>>,
     currently_queried_urls: Arc<Mutex<HashSet<String>>>,
+    peer_slots: Arc<Mutex<PeerSlots>>,
+    // requests sent to the peers which are waiting for a response
+    pending_requests: Arc<Mutex<PendingPeerRequests>>,
+    // peers from the config file. they are always connected regardless of the slot limits
+    static_peers: Vec<PeerConfig>,
+    dns_seeds: DnsSeedConfig,
+    // peers found with the dns seeds and the peers connected before
+    peer_store: Arc<Mutex<PeerStore>>,
+    // connected outbound peers which are not static peers, by the peer index
+    discovered_peers: Arc<Mutex<HashMap<u64, PeerConfig>>>,
     pub sender_to_saito_controller: Sender<IoEvent>,
+    // used for outgoing wss connections
+    tls_client_config: Arc<rustls::ClientConfig>,
 }
 
 impl NetworkController {
@@ -90,6 +133,120 @@ impl NetworkController {
         return !send_failed;
     }
 
+    /// Sends a websocket ping with the request id as the payload. The pong echoes the payload, so it's matched to the
+    /// ping and the latency is recorded when it's received
+    pub async fn send_ping(
+        connection: &mut PeerSender,
+        peer_index: u64,
+        request_id: RequestId,
+    ) -> bool {
+        let payload = request_id.to_be_bytes().to_vec();
+        let result = match connection {
+            PeerSender::Warp(sender) => sender
+                .send(warp::ws::Message::ping(payload))
+                .await
+                .map_err(|error| error.to_string()),
+            PeerSender::Tungstenite(sender) => sender
+                .send(tokio_tungstenite::tungstenite::Message::Ping(payload))
+                .await
+                .map_err(|error| error.to_string()),
+        };
+        if let Err(error) = result {
+            warn!(
+                "failed sending ping to peer : {:?}, reason : {:?}",
+                peer_index, error
+            );
+            return false;
+        }
+        true
+    }
+
+    pub async fn ping_peers(
+        sockets: Arc<Mutex<HashMap<u64, PeerSender>>>,
+        peer_slots: Arc<Mutex<PeerSlots>>,
+        pending_requests: Arc<Mutex<PendingPeerRequests>>,
+    ) {
+        let mut sockets = sockets.lock().await;
+        let mut peer_slots = peer_slots.lock().await;
+        let mut pending_requests = pending_requests.lock().await;
+        let current_time = TimeKeeper {}.get_timestamp_in_ms();
+        let mut peers_with_errors: Vec<u64> = Default::default();
+
+        for (peer_index, socket) in sockets.iter_mut() {
+            let request_id = get_next_event_id();
+            if Self::send_ping(socket, *peer_index, request_id).await {
+                peer_slots.on_ping_sent(*peer_index, current_time);
+                pending_requests.add(request_id, *peer_index, PeerRequest::Ping, current_time);
+            } else {
+                peers_with_errors.push(*peer_index);
+            }
+        }
+
+        for peer in peers_with_errors {
+            sockets.remove(&peer);
+        }
+    }
+
+    pub async fn close_connection(mut connection: PeerSender, peer_index: u64) {
+        debug!("closing connection of peer : {:?}", peer_index);
+        let result = match &mut connection {
+            PeerSender::Warp(sender) => sender.close().await.map_err(|error| error.to_string()),
+            PeerSender::Tungstenite(sender) => {
+                sender.close().await.map_err(|error| error.to_string())
+            }
+        };
+        if let Err(error) = result {
+            debug!(
+                "failed closing connection of peer : {:?}, reason : {:?}",
+                peer_index, error
+            );
+        }
+    }
+
+    /// Assigns a connection slot to the new peer. Returns false if there are no free slots. If another peer is
+    /// evicted to free a slot, it is disconnected here
+    pub async fn assign_slot(
+        sockets: Arc<Mutex<HashMap<u64, PeerSender>>>,
+        peer_slots: Arc<Mutex<PeerSlots>>,
+        sender_to_core: Sender<IoEvent>,
+        peer_index: u64,
+        direction: ConnectionDirection,
+        is_static: bool,
+    ) -> bool {
+        let evicted;
+        {
+            let mut sockets = sockets.lock().await;
+            let mut peer_slots = peer_slots.lock().await;
+            peer_slots.retain_connected(|index| sockets.contains_key(&index));
+            match peer_slots.try_add(
+                peer_index,
+                direction,
+                is_static,
+                TimeKeeper {}.get_timestamp_in_ms(),
+            ) {
+                SlotResult::Accepted => {
+                    return true;
+                }
+                SlotResult::AcceptedAfterEvicting(index) => {
+                    evicted = sockets.remove(&index).map(|connection| (index, connection));
+                }
+                SlotResult::Rejected => {
+                    info!(
+                        "no free {:?} connection slots for peer : {:?}",
+                        direction, peer_index
+                    );
+                    return false;
+                }
+            }
+        }
+        if let Some((index, connection)) = evicted {
+            info!("evicted peer : {:?} for peer : {:?}", index, peer_index);
+            Self::close_connection(connection, index).await;
+            Self::send_peer_disconnect(sender_to_core, index).await;
+        }
+        true
+    }
+
     #[tracing::instrument(level = "info", skip_all)]
     pub async fn send_outgoing_message(
         sockets: Arc<Mutex<HashMap<u64, PeerSender>>>,
@@ -122,11 +279,8 @@ impl NetworkController {
     ) {
         // TODO : handle connecting to an already connected (via incoming connection) node.
 
-        let mut protocol: String = String::from("ws");
-        if peer.protocol == "https" {
-            protocol = String::from("wss");
-        }
-        let url = protocol
+        let scheme = peer.get_websocket_scheme();
+        let url = scheme.to_string()
             + "://"
             + peer.host.as_str()
             + ":"
@@ -134,7 +288,29 @@ impl NetworkController {
             + "/wsopen";
         debug!("connecting to peer : {:?}", url);
 
-        let result = connect_async(url.clone()).await;
+        let connector;
+        let is_static;
+        let peer_store;
+        {
+            let (network_controller, _network_controller_) =
+                lock_for_read!(io_controller, LOCK_ORDER_NETWORK_CONTROLLER);
+            is_static = network_controller.static_peers.contains(&peer);
+            peer_store = network_controller.peer_store.clone();
+            if !network_controller
+                .peer_slots
+                .lock()
+                .await
+                .has_free_slot(ConnectionDirection::Outbound, is_static)
+            {
+                info!(
+                    "not connecting to : {:?} since outbound slots are full",
+                    url
+                );
+                return;
+            }
+            connector = tls::get_connector(scheme, &network_controller.tls_client_config);
+        }
+        let result = connect_async_tls_with_config(url.clone(), None, Some(connector)).await;
         if result.is_ok() {
             let result = result.unwrap();
             let socket: WebSocketStream<MaybeTlsStream<TcpStream>> = result.0;
@@ -154,11 +330,42 @@ impl NetworkController {
                 "connected to peer : {:?} with index : {:?}",
                 url, peer_index
             );
+            if !NetworkController::assign_slot(
+                network_controller.sockets.clone(),
+                network_controller.peer_slots.clone(),
+                sender_to_controller.clone(),
+                peer_index,
+                ConnectionDirection::Outbound,
+                is_static,
+            )
+            .await
+            {
+                NetworkController::close_connection(
+                    PeerSender::Tungstenite(socket_sender),
+                    peer_index,
+                )
+                .await;
+                return;
+            }
+            if !is_static {
+                network_controller
+                    .discovered_peers
+                    .lock()
+                    .await
+                    .insert(peer_index, peer.clone());
+                let mut peer_store = peer_store.lock().await;
+                peer_store.on_connected(&peer, TimeKeeper {}.get_timestamp_in_ms());
+                if let Err(error) = peer_store.save().await {
+                    warn!("failed saving peer store : {:?}", error);
+                }
+            }
 
             NetworkController::send_new_peer(
                 event_id,
                 peer_index,
                 network_controller.sockets.clone(),
+                network_controller.peer_slots.clone(),
+                network_controller.pending_requests.clone(),
                 PeerSender::Tungstenite(socket_sender),
                 PeerReceiver::Tungstenite(socket_receiver),
                 sender_to_controller,
@@ -171,6 +378,59 @@ impl NetworkController {
                 url,
                 result.err().unwrap()
             );
+            if !is_static {
+                let mut peer_store = peer_store.lock().await;
+                peer_store.on_connection_failed(&peer);
+                if let Err(error) = peer_store.save().await {
+                    warn!("failed saving peer store : {:?}", error);
+                }
+            }
+        }
+    }
+
+    /// Resolves the dns seeds and connects to the peers in the peer store while there are free outbound slots
+    pub async fn discover_peers(io_controller: Arc<RwLock<NetworkController>>) {
+        let dns_seeds;
+        {
+            let (network_controller, _network_controller_) =
+                lock_for_read!(io_controller, LOCK_ORDER_NETWORK_CONTROLLER);
+            dns_seeds = network_controller.dns_seeds.clone();
+        }
+        let resolved_peers = resolve_seeds(&dns_seeds).await;
+
+        let candidates;
+        {
+            let (network_controller, _network_controller_) =
+                lock_for_read!(io_controller, LOCK_ORDER_NETWORK_CONTROLLER);
+            let sockets = network_controller.sockets.lock().await;
+            let free_slots;
+            {
+                let peer_slots = network_controller.peer_slots.lock().await;
+                free_slots = peer_slots
+                    .get_max_slots(ConnectionDirection::Outbound)
+                    .saturating_sub(peer_slots.get_used_slots(ConnectionDirection::Outbound));
+            }
+            let mut discovered_peers = network_controller.discovered_peers.lock().await;
+            discovered_peers.retain(|peer_index, _| sockets.contains_key(peer_index));
+
+            let mut peer_store = network_controller.peer_store.lock().await;
+            if peer_store.add_candidates(&resolved_peers) > 0 {
+                if let Err(error) = peer_store.save().await {
+                    warn!("failed saving peer store : {:?}", error);
+                }
+            }
+            let is_connected = |peer: &PeerConfig| {
+                network_controller
+                    .static_peers
+                    .iter()
+                    .chain(discovered_peers.values())
+                    .any(|connected| connected.host == peer.host && connected.port == peer.port)
+            };
+            candidates = peer_store.get_candidates(free_slots, is_connected);
+        }
+        debug!("connecting to {:?} discovered peers", candidates.len());
+        for peer in candidates {
+            NetworkController::connect_to_peer(0, io_controller.clone(), peer).await;
         }
     }
     #[tracing::instrument(level = "info", skip_all)]
@@ -179,7 +439,7 @@ impl NetworkController {
         buffer: Vec<u8>,
         exceptions: Vec<u64>,
     ) {
-        trace!("sending message : {:?} to all", buffer[0]);
+        trace!("sending message : {:?} to all", buffer[4]);
         let mut sockets = sockets.lock().await;
         let mut peers_with_errors: Vec<u64> = Default::default();
 
@@ -209,6 +469,7 @@ impl NetworkController {
         event_id: u64,
         sender_to_core: Sender<IoEvent>,
         current_queries: Arc<Mutex<HashSet<String>>>,
+        pending_requests: Arc<Mutex<PendingPeerRequests>>,
     ) {
         debug!("fetching block : {:?}", url);
 
@@ -217,55 +478,108 @@ impl NetworkController {
             let mut queries = current_queries.lock().await;
             if queries.contains(&url) {
                 debug!("url : {:?} is already being fetched", url);
+                pending_requests.lock().await.cancel(event_id);
                 return;
             }
             queries.insert(url.clone());
         }
         let result = reqwest::get(url.clone()).await;
         if result.is_err() {
-            // TODO : should we retry here?
+            // the request is left pending, so the failure is reported to the routing thread as a timeout
             warn!("failed fetching : {:?}", url);
+            current_queries.lock().await.remove(&url);
             return;
         }
         let response = result.unwrap();
-        let result = response.bytes().await;
-        if result.is_err() {
-            warn!("failed getting byte buffer from fetching block : {:?}", url);
-            return;
+        let signature = Self::get_response_signature(&response);
+        let buffer = Self::read_block_response(response, &url).await;
+        {
+            // since we have already fetched the block, we will remove it from the set.
+            let mut queries = current_queries.lock().await;
+            queries.remove(&url);
         }
-        let result = result.unwrap();
-        let buffer = result.to_vec();
+        let buffer = match buffer {
+            Some(buffer) => buffer,
+            None => return,
+        };
 
         debug!(
             "block buffer received with size : {:?} for url : {:?}",
             buffer.len(),
             url
         );
-        // RustIOHandler::set_event_response(event_id, FutureState::BlockFetched(block));
+        if pending_requests.lock().await.complete(event_id).is_none() {
+            debug!(
+                "block : {:?} received after the request : {:?} timed out",
+                hex::encode(block_hash),
+                event_id
+            );
+        }
         sender_to_core
             .send(IoEvent {
                 event_processor_id: 1,
                 event_id,
                 event: NetworkEvent::BlockFetched {
+                    request_id: event_id,
                     block_hash,
                     peer_index,
                     buffer,
+                    signature,
                 },
             })
             .await
             .unwrap();
-        {
-            // since we have already fetched the block, we will remove it from the set.
-            let mut queries = current_queries.lock().await;
-            queries.remove(&url);
-        }
         debug!("block buffer sent to blockchain controller");
     }
+
+    fn get_response_signature(response: &reqwest::Response) -> Option<SaitoSignature> {
+        let value = response
+            .headers()
+            .get(BLOCK_FETCH_SIGNATURE_HEADER)?
+            .to_str()
+            .ok()?;
+        hex::decode(value).ok()?.try_into().ok()
+    }
+
+    /// Reads the response in chunks, so responses larger than the max block size are dropped without being read
+    /// into memory
+    async fn read_block_response(mut response: reqwest::Response, url: &str) -> Option<Vec<u8>> {
+        let content_length = response.content_length().unwrap_or(0) as usize;
+        if content_length > BLOCK_FETCH_MAX_SIZE_IN_BYTES {
+            warn!(
+                "block response size : {:?} from : {:?} is too large",
+                content_length, url
+            );
+            return None;
+        }
+        let mut buffer = Vec::with_capacity(content_length);
+        loop {
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    if buffer.len() + chunk.len() > BLOCK_FETCH_MAX_SIZE_IN_BYTES {
+                        warn!("block response from : {:?} is too large", url);
+                        return None;
+                    }
+                    buffer.extend_from_slice(&chunk);
+                }
+                Ok(None) => return Some(buffer),
+                Err(error) => {
+                    warn!(
+                        "failed getting byte buffer from fetching block : {:?}. {:?}",
+                        url, error
+                    );
+                    return None;
+                }
+            }
+        }
+    }
     #[tracing::instrument(level = "info", skip_all)]
     pub async fn send_new_peer(
         event_id: u64,
         peer_index: u64,
         sockets: Arc<Mutex<HashMap<u64, PeerSender>>>,
+        peer_slots: Arc<Mutex<PeerSlots>>,
+        pending_requests: Arc<Mutex<PendingPeerRequests>>,
         sender: PeerSender,
         receiver: PeerReceiver,
         sender_to_core: Sender<IoEvent>,
@@ -294,6 +608,8 @@ impl NetworkController {
             sender_to_core.clone(),
             peer_index,
             sockets,
+            peer_slots,
+            pending_requests,
         )
         .await;
     }
@@ -318,6 +634,8 @@ impl NetworkController {
         sender: Sender<IoEvent>,
         peer_index: u64,
         sockets: Arc<Mutex<HashMap<u64, PeerSender>>>,
+        peer_slots: Arc<Mutex<PeerSlots>>,
+        pending_requests: Arc<Mutex<PendingPeerRequests>>,
     ) {
         debug!("starting new task for reading from peer : {:?}", peer_index);
         tokio::spawn(async move {
@@ -326,7 +644,10 @@ impl NetworkController {
                 PeerReceiver::Warp(mut receiver) => loop {
                     let result = receiver.next().await;
                     if result.is_none() {
-                        continue;
+                        debug!("connection closed for peer : {:?}", peer_index);
+                        NetworkController::send_peer_disconnect(sender, peer_index).await;
+                        sockets.lock().await.remove(&peer_index);
+                        break;
                     }
                     let result = result.unwrap();
                     if result.is_err() {
@@ -345,20 +666,46 @@ impl NetworkController {
                             buffer.len(),
                             peer_index
                         );
+                        let request = PeerRequest::from_response_message(&buffer);
                         let message = IoEvent {
                             event_processor_id: 1,
                             event_id: 0,
                             event: NetworkEvent::IncomingNetworkMessage { peer_index, buffer },
                         };
                         sender.send(message).await.expect("sending failed");
+                        if let Some(request) = request {
+                            let completed = pending_requests
+                                .lock()
+                                .await
+                                .complete_oldest(peer_index, request);
+                            NetworkController::send_request_completed(&sender, completed).await;
+                        }
+                    } else if result.is_pong() {
+                        NetworkController::on_pong_received(
+                            peer_index,
+                            result.as_bytes(),
+                            &sender,
+                            &peer_slots,
+                            &pending_requests,
+                        )
+                        .await;
+                    } else if result.is_close() {
+                        debug!("peer : {:?} closed the connection", peer_index);
+                        NetworkController::send_peer_disconnect(sender, peer_index).await;
+                        sockets.lock().await.remove(&peer_index);
+                        break;
                     } else {
-                        todo!("handle these scenarios 1")
+                        // pings are answered by the websocket library
+                        trace!("ignoring message from peer : {:?}", peer_index);
                     }
                 },
                 PeerReceiver::Tungstenite(mut receiver) => loop {
                     let result = receiver.next().await;
                     if result.is_none() {
-                        continue;
+                        debug!("connection closed for peer : {:?}", peer_index);
+                        NetworkController::send_peer_disconnect(sender, peer_index).await;
+                        sockets.lock().await.remove(&peer_index);
+                        break;
                     }
                     let result = result.unwrap();
                     if result.is_err() {
@@ -375,23 +722,127 @@ impl NetworkController {
                                 buffer.len(),
                                 peer_index
                             );
+                            let request = PeerRequest::from_response_message(&buffer);
                             let message = IoEvent {
                                 event_processor_id: 1,
                                 event_id: 0,
                                 event: NetworkEvent::IncomingNetworkMessage { peer_index, buffer },
                             };
                             sender.send(message).await.expect("sending failed");
+                            if let Some(request) = request {
+                                let completed = pending_requests
+                                    .lock()
+                                    .await
+                                    .complete_oldest(peer_index, request);
+                                NetworkController::send_request_completed(&sender, completed).await;
+                            }
+                        }
+                        tokio_tungstenite::tungstenite::Message::Pong(payload) => {
+                            NetworkController::on_pong_received(
+                                peer_index,
+                                &payload,
+                                &sender,
+                                &peer_slots,
+                                &pending_requests,
+                            )
+                            .await;
+                        }
+                        tokio_tungstenite::tungstenite::Message::Close(_) => {
+                            debug!("peer : {:?} closed the connection", peer_index);
+                            NetworkController::send_peer_disconnect(sender, peer_index).await;
+                            sockets.lock().await.remove(&peer_index);
+                            break;
                         }
                         _ => {
-                            // Not handling these scenarios
-                            todo!("handle these scenarios 2")
+                            // pings are answered by the websocket library
+                            trace!("ignoring message from peer : {:?}", peer_index);
                         }
                     }
                 },
             }
+            pending_requests.lock().await.remove_peer(peer_index);
             debug!("listening thread existed for peer : {:?}", peer_index);
         });
     }
+
+    /// Matches the pong to the ping by the request id in the payload
+    async fn on_pong_received(
+        peer_index: u64,
+        payload: &[u8],
+        sender: &Sender<IoEvent>,
+        peer_slots: &Mutex<PeerSlots>,
+        pending_requests: &Mutex<PendingPeerRequests>,
+    ) {
+        peer_slots
+            .lock()
+            .await
+            .on_pong_received(peer_index, TimeKeeper {}.get_timestamp_in_ms());
+        let request_id = match <[u8; 8]>::try_from(payload) {
+            Ok(request_id) => RequestId::from_be_bytes(request_id),
+            Err(_) => {
+                trace!("ignoring pong without a request id from : {:?}", peer_index);
+                return;
+            }
+        };
+        let completed = {
+            let mut pending_requests = pending_requests.lock().await;
+            match pending_requests.complete(request_id) {
+                Some(pending) if pending.peer_index == peer_index => Some((request_id, pending)),
+                _ => None,
+            }
+        };
+        Self::send_request_completed(sender, completed).await;
+    }
+
+    /// Sends the completion of a request to the routing thread which issued it
+    async fn send_request_completed(
+        sender: &Sender<IoEvent>,
+        completed: Option<(RequestId, PendingPeerRequest)>,
+    ) {
+        let (request_id, pending) = match completed {
+            Some(completed) => completed,
+            None => return,
+        };
+        sender
+            .send(IoEvent {
+                event_processor_id: 1,
+                event_id: request_id,
+                event: NetworkEvent::PeerRequestCompleted {
+                    request_id,
+                    peer_index: pending.peer_index,
+                    request: pending.request,
+                    round_trip_time: pending
+                        .get_round_trip_time(TimeKeeper {}.get_timestamp_in_ms()),
+                },
+            })
+            .await
+            .expect("sending failed");
+    }
+
+    /// Sends the requests which were not answered within their timeouts to the routing thread which issued them
+    pub async fn send_timed_out_requests(
+        sender: &Sender<IoEvent>,
+        pending_requests: &Mutex<PendingPeerRequests>,
+    ) {
+        let timed_out = pending_requests
+            .lock()
+            .await
+            .take_timed_out(TimeKeeper {}.get_timestamp_in_ms());
+        for (request_id, pending) in timed_out {
+            sender
+                .send(IoEvent {
+                    event_processor_id: 1,
+                    event_id: request_id,
+                    event: NetworkEvent::PeerRequestTimedOut {
+                        request_id,
+                        peer_index: pending.peer_index,
+                        request: pending.request,
+                    },
+                })
+                .await
+                .expect("sending failed");
+        }
+    }
 }
 
 pub struct PeerCounter {
@@ -411,7 +862,15 @@ pub async fn run_network_controller(
     sender: Sender<IoEvent>,
     configs: Arc<RwLock<Box<dyn Configuration + Send + Sync>>>,
     blockchain: Arc<RwLock<Blockchain>>,
+    blockchain_snapshot: Arc<std::sync::Mutex<Arc<BlockchainSnapshot>>>,
+    wallet: Arc<RwLock<Wallet>>,
+    mining_status: Arc<std::sync::Mutex<MiningStatus>>,
+    transaction_receipts: Arc<std::sync::Mutex<TransactionReceipts>>,
+    peers: Arc<RwLock<PeerCollection>>,
+    mempool: Arc<RwLock<Mempool>>,
     sender_to_stat: Sender<String>,
+    peer_events: PeerEventBroadcaster,
+    pending_block_writes: PendingBlockWrites,
 ) {
     info!("running network handler");
     let peer_index_counter = Arc::new(Mutex::new(PeerCounter { counter: 0 }));
@@ -419,6 +878,10 @@ pub async fn run_network_controller(
     let host;
     let url;
     let port;
+    let tls_config;
+    let peer_slots_config;
+    let static_peers;
+    let dns_seeds;
     {
         let (configs, _configs_) = lock_for_read!(configs, LOCK_ORDER_CONFIGS);
 
@@ -427,7 +890,19 @@ pub async fn run_network_controller(
             + configs.get_server_configs().port.to_string().as_str();
         port = configs.get_server_configs().port;
         host = configs.get_server_configs().host.clone();
+        tls_config = configs.get_server_configs().tls.clone();
+        peer_slots_config = configs.get_server_configs().peer_slots.clone();
+        static_peers = configs.get_peer_configs().clone();
+        dns_seeds = configs.get_server_configs().dns_seeds.clone();
+    }
+    let peer_store = PeerStore::load(&get_data_path(PEER_STORE_FILE_PATH)).await;
+    let discovery_interval = Duration::from_millis(dns_seeds.resolve_interval_in_ms);
+
+    if let Some(tls_config) = tls_config.as_ref() {
+        tls::validate_server_config(tls_config).expect("invalid tls configuration");
     }
+    let tls_client_config = tls::create_client_config(tls_config.as_ref())
+        .expect("failed creating tls client configuration");
 
     info!("starting server on : {:?}", url);
     let peer_counter_clone = peer_index_counter.clone();
@@ -438,6 +913,13 @@ pub async fn run_network_controller(
         sender_to_saito_controller: sender,
         peer_counter: peer_index_counter.clone(),
         currently_queried_urls: Arc::new(Default::default()),
+        peer_slots: Arc::new(Mutex::new(PeerSlots::new(peer_slots_config))),
+        pending_requests: Arc::new(Mutex::new(PendingPeerRequests::new())),
+        static_peers,
+        dns_seeds,
+        peer_store: Arc::new(Mutex::new(peer_store)),
+        discovered_peers: Arc::new(Default::default()),
+        tls_client_config,
     }));
 
     let network_controller_clone = network_controller.clone();
@@ -448,7 +930,16 @@ pub async fn run_network_controller(
         network_controller_clone.clone(),
         port,
         host,
+        tls_config,
         blockchain.clone(),
+        blockchain_snapshot,
+        wallet,
+        mining_status,
+        transaction_receipts,
+        peers,
+        mempool,
+        peer_events,
+        pending_block_writes,
     );
 
     let mut work_done = false;
@@ -459,13 +950,21 @@ pub async fn run_network_controller(
             sender_to_stat.clone(),
         );
         let mut last_stat_on: Instant = Instant::now();
+        let mut last_ping_on: Instant = Instant::now();
+        let mut last_discovery_on: Option<Instant> = None;
         loop {
             // let command = Command::NetworkMessage(10, [1, 2, 3].to_vec());
             //
             // sender_to_saito_controller.send(command).await;
             // info!("sending test message to saito controller");
 
-            let result = receiver.recv().await;
+            // the wait is limited, so the request timeouts are checked when there are no events
+            let result = tokio::time::timeout(
+                Duration::from_millis(PEER_REQUEST_TIMEOUT_CHECK_INTERVAL_IN_MS),
+                receiver.recv(),
+            )
+            .await
+            .unwrap_or(None);
             if result.is_some() {
                 let event = result.unwrap();
                 let event_id = event.event_id;
@@ -485,6 +984,14 @@ pub async fn run_network_controller(
                     } => {
                         let (network_controller, _network_controller_) =
                             lock_for_read!(network_controller, LOCK_ORDER_NETWORK_CONTROLLER);
+                        if let Some(request) = PeerRequest::from_request_message(&buffer) {
+                            network_controller.pending_requests.lock().await.add(
+                                event_id,
+                                index,
+                                request,
+                                TimeKeeper {}.get_timestamp_in_ms(),
+                            );
+                        }
                         let sockets = network_controller.sockets.clone();
                         NetworkController::send_outgoing_message(sockets, index, buffer).await;
                         outgoing_messages.increment();
@@ -513,13 +1020,21 @@ pub async fn run_network_controller(
                     } => {
                         let sender;
                         let current_queries;
+                        let pending_requests;
                         {
                             let (network_controller, _network_controller_) =
                                 lock_for_read!(network_controller, LOCK_ORDER_NETWORK_CONTROLLER);
 
                             sender = network_controller.sender_to_saito_controller.clone();
                             current_queries = network_controller.currently_queried_urls.clone();
+                            pending_requests = network_controller.pending_requests.clone();
                         }
+                        pending_requests.lock().await.add(
+                            event_id,
+                            peer_index,
+                            PeerRequest::BlockFetch { block_hash },
+                            TimeKeeper {}.get_timestamp_in_ms(),
+                        );
                         // starting new thread to stop io controller from getting blocked
                         tokio::spawn(async move {
                             NetworkController::fetch_block(
@@ -529,6 +1044,7 @@ pub async fn run_network_controller(
                                 event_id,
                                 sender,
                                 current_queries,
+                                pending_requests,
                             )
                             .await
                         });
@@ -536,9 +1052,50 @@ pub async fn run_network_controller(
                     NetworkEvent::BlockFetched { .. } => {
                         unreachable!()
                     }
+                    NetworkEvent::PeerRequestCompleted { .. } => {
+                        unreachable!()
+                    }
+                    NetworkEvent::PeerRequestTimedOut { .. } => {
+                        unreachable!()
+                    }
                 }
             }
 
+            {
+                let (network_controller, _network_controller_) =
+                    lock_for_read!(network_controller, LOCK_ORDER_NETWORK_CONTROLLER);
+                NetworkController::send_timed_out_requests(
+                    &network_controller.sender_to_saito_controller,
+                    &network_controller.pending_requests,
+                )
+                .await;
+            }
+
+            if Instant::now().duration_since(last_ping_on)
+                > Duration::from_millis(PEER_PING_INTERVAL_IN_MS)
+            {
+                last_ping_on = Instant::now();
+                let (network_controller, _network_controller_) =
+                    lock_for_read!(network_controller, LOCK_ORDER_NETWORK_CONTROLLER);
+                NetworkController::ping_peers(
+                    network_controller.sockets.clone(),
+                    network_controller.peer_slots.clone(),
+                    network_controller.pending_requests.clone(),
+                )
+                .await;
+            }
+
+            if last_discovery_on.map_or(true, |last_discovery_on| {
+                Instant::now().duration_since(last_discovery_on) > discovery_interval
+            }) {
+                last_discovery_on = Some(Instant::now());
+                // resolving the seeds and connecting can take a while, so the controller is not blocked
+                let network_controller = network_controller.clone();
+                tokio::spawn(async move {
+                    NetworkController::discover_peers(network_controller).await;
+                });
+            }
+
             #[cfg(feature = "with-stats")]
             {
                 let (configs, _configs_) = lock_for_read!(configs, LOCK_ORDER_CONFIGS);
@@ -560,6 +1117,20 @@ pub async fn run_network_controller(
                         network_controller.sender_to_saito_controller.max_capacity()
                     );
                     sender_to_stat.send(stat).await.unwrap();
+
+                    let peer_slots = network_controller.peer_slots.lock().await;
+                    let stat = format!(
+                        "--- stats ------ {} - inbound : {:?} / {:?}, outbound : {:?} / {:?}",
+                        format!("{:width$}", "network::peer_slots", width = 30),
+                        peer_slots.get_used_slots(ConnectionDirection::Inbound),
+                        peer_slots.get_max_slots(ConnectionDirection::Inbound),
+                        peer_slots.get_used_slots(ConnectionDirection::Outbound),
+                        peer_slots.get_max_slots(ConnectionDirection::Outbound)
+                    );
+                    sender_to_stat.send(stat).await.unwrap();
+
+                    let stat = network_controller.pending_requests.lock().await.get_stats();
+                    sender_to_stat.send(stat).await.unwrap();
                 }
             }
 
@@ -576,6 +1147,169 @@ pub async fn run_network_controller(
     let _result = tokio::join!(server_handle, controller_handle);
 }
 
+/// Reads the next chunk of a block file being served
+async fn read_file_chunk(
+    file: Option<File>,
+) -> Option<(Result<Vec<u8>, std::io::Error>, Option<File>)> {
+    let mut file = file?;
+    let mut buffer = vec![0; BLOCK_STREAM_CHUNK_SIZE];
+    match file.read(&mut buffer).await {
+        Ok(0) => None,
+        Ok(count) => {
+            buffer.truncate(count);
+            Some((Ok(buffer), Some(file)))
+        }
+        Err(error) => {
+            error!("failed reading file : {:?}", error);
+            Some((Err(error), None))
+        }
+    }
+}
+
+enum BlockRangePayload {
+    /// path of the block file
+    File(String),
+    Header(Vec<u8>),
+}
+
+/// Blocks of a range response which are not fully streamed yet
+struct BlockRangeStreamState {
+    // frame header and payload of each block
+    pending: VecDeque<(Vec<u8>, BlockRangePayload)>,
+    // file of the block being streamed
+    file: Option<File>,
+}
+
+/// Streams the next frame header or block file chunk of a range response
+async fn read_block_range_chunk(
+    mut state: BlockRangeStreamState,
+) -> Option<(Result<Vec<u8>, std::io::Error>, BlockRangeStreamState)> {
+    if let Some(file) = state.file.take() {
+        match read_file_chunk(Some(file)).await {
+            Some((Ok(chunk), file)) => {
+                state.file = file;
+                return Some((Ok(chunk), state));
+            }
+            Some((Err(error), _)) => {
+                state.pending.clear();
+                return Some((Err(error), state));
+            }
+            None => {}
+        }
+    }
+    let (frame_header, payload) = state.pending.pop_front()?;
+    match payload {
+        BlockRangePayload::Header(header) => Some((Ok([frame_header, header].concat()), state)),
+        BlockRangePayload::File(file_path) => match File::open(file_path.as_str()).await {
+            Ok(file) => {
+                state.file = Some(file);
+                Some((Ok(frame_header), state))
+            }
+            Err(error) => {
+                error!("failed opening file : {:?} : {:?}", file_path, error);
+                state.pending.clear();
+                Some((Err(error), state))
+            }
+        },
+    }
+}
+
+/// Serves the longest chain blocks in the requested range as a stream of frames (see `BlockRangeFrame`), so a syncing
+/// peer doesn't need a request per block. The range ends at the first block which is not on disk.
+async fn serve_block_range(
+    request: BlockRangeRequest,
+    blockchain: Arc<RwLock<Blockchain>>,
+    blockchain_snapshot: Arc<std::sync::Mutex<Arc<BlockchainSnapshot>>>,
+    wallet: Arc<RwLock<Wallet>>,
+    pending_block_writes: PendingBlockWrites,
+) -> Result<warp::http::Response<warp::hyper::Body>, warp::Rejection> {
+    debug!("serving block range : {:?}", request);
+    if let Err(error) = request.validate() {
+        debug!("invalid block range : {:?} : {:?}", request, error);
+        return warp::http::Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(warp::hyper::Body::empty())
+            .map_err(|_| warp::reject::not_found());
+    }
+    // (block id, block hash, file path or header)
+    let mut blocks = vec![];
+    let snapshot = blockchain_snapshot.lock().unwrap().clone();
+    for block in snapshot
+        .iter_longest_chain(request.from_id)
+        .take_while(|block| block.id <= request.to_id)
+    {
+        let payload = if request.lite {
+            // headers are copied one block at a time, so the blockchain lock isn't held for the whole range
+            match read_block(&blockchain, &block.hash, |block| {
+                block.serialize_for_net(BlockType::Header)
+            })
+            .await
+            {
+                Some(header) => BlockRangePayload::Header(header),
+                None => break,
+            }
+        } else {
+            BlockRangePayload::File(get_data_path(
+                &(BLOCKS_DIR_PATH.to_string()
+                    + block.timestamp.to_string().as_str()
+                    + "-"
+                    + hex::encode(block.hash).as_str()
+                    + BLOCK_FILE_EXTENSION),
+            ))
+        };
+        blocks.push((block.id, block.hash, payload));
+    }
+    let private_key;
+    {
+        let (wallet, _wallet_) = lock_for_read!(wallet, LOCK_ORDER_WALLET);
+        private_key = wallet.private_key;
+    }
+
+    let mut pending = VecDeque::new();
+    let mut content_length = 0;
+    for (block_id, block_hash, payload) in blocks {
+        let payload_length = match &payload {
+            BlockRangePayload::Header(header) => header.len() as u64,
+            BlockRangePayload::File(file_path) => {
+                pending_block_writes.wait_for_block_write(&block_hash).await;
+                match tokio::fs::metadata(file_path).await {
+                    Ok(metadata) => metadata.len(),
+                    Err(_) => {
+                        debug!("block file : {:?} not found. ending the range", file_path);
+                        break;
+                    }
+                }
+            }
+        };
+        let frame_header = BlockRangeFrame::serialize_frame_header(
+            block_id,
+            &block_hash,
+            payload_length,
+            &private_key,
+        );
+        content_length += frame_header.len() as u64 + payload_length;
+        pending.push_back((frame_header, payload));
+    }
+    debug!(
+        "serving {:?} blocks with : {:?} length",
+        pending.len(),
+        content_length
+    );
+    let state = BlockRangeStreamState {
+        pending,
+        file: None,
+    };
+    let stream = futures::stream::unfold(state, read_block_range_chunk);
+    warp::http::Response::builder()
+        .status(StatusCode::OK)
+        .header(warp::http::header::CONTENT_LENGTH, content_length)
+        .body(warp::hyper::Body::wrap_stream(stream))
+        .map_err(|error| {
+            error!("failed creating response : {:?}", error);
+            warp::reject::not_found()
+        })
+}
+
 pub enum PeerSender {
     Warp(SplitSink<WebSocket, warp::ws::Message>),
     Tungstenite(SocketSender),
@@ -586,23 +1320,134 @@ pub enum PeerReceiver {
     Tungstenite(SocketReceiver),
 }
 
+/// Sends the json to the socket when it connects and whenever it changes, until the socket is closed
+async fn push_json_updates<F>(socket: WebSocket, name: &str, get_json: F)
+where
+    F: Fn() -> String,
+{
+    debug!("{} subscriber connected", name);
+    let (mut sender, mut receiver) = socket.split();
+    let mut interval = tokio::time::interval(Duration::from_millis(STATUS_PUSH_INTERVAL_IN_MS));
+    let mut last_sent: Option<String> = None;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let json = get_json();
+                if last_sent.as_ref() == Some(&json) {
+                    continue;
+                }
+                if let Err(error) = sender.send(warp::ws::Message::text(json.clone())).await {
+                    debug!("failed sending {} : {:?}", name, error);
+                    break;
+                }
+                last_sent = Some(json);
+            }
+            message = receiver.next() => {
+                match message {
+                    Some(Ok(message)) if !message.is_close() => {}
+                    _ => break,
+                }
+            }
+        }
+    }
+    debug!("{} subscriber disconnected", name);
+}
+
+/// Sends the peer events to a websocket client as they happen
+async fn push_peer_events(socket: WebSocket, mut events: broadcast::Receiver<PeerEvent>) {
+    debug!("peer event subscriber connected");
+    let (mut sender, mut receiver) = socket.split();
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(count)) => {
+                        debug!("peer event subscriber missed {:?} events", count);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if let Err(error) = sender.send(warp::ws::Message::text(event.to_json())).await {
+                    debug!("failed sending peer event : {:?}", error);
+                    break;
+                }
+            }
+            message = receiver.next() => {
+                match message {
+                    Some(Ok(message)) if !message.is_close() => {}
+                    _ => break,
+                }
+            }
+        }
+    }
+    debug!("peer event subscriber disconnected");
+}
+
+/// Receipt of the transaction with the hex encoded signature. None if the signature is invalid
+fn get_transaction_receipt_json(
+    transaction_receipts: &std::sync::Mutex<TransactionReceipts>,
+    signature: &str,
+) -> Option<String> {
+    let signature = SaitoSignature::try_from(hex::decode(signature).ok()?).ok()?;
+    let json = transaction_receipts
+        .lock()
+        .unwrap()
+        .to_json(&signature)
+        .unwrap_or_else(|| {
+            serde_json::json!({
+                "signature": hex::encode(signature),
+                "status": "unknown",
+            })
+            .to_string()
+        });
+    Some(json)
+}
+
+/// Owner of the handle at the latest block. None if the handle is not registered
+async fn get_handle_json(blockchain: &RwLock<Blockchain>, handle: &str) -> Option<String> {
+    let handle = handle.to_lowercase();
+    let (blockchain, _blockchain_) = lock_for_read!(blockchain, LOCK_ORDER_BLOCKCHAIN);
+    let registry = blockchain.get_handle_registry();
+    let public_key = registry.resolve(handle.as_str())?;
+    Some(
+        serde_json::json!({
+            "handle": handle,
+            "public_key": hex::encode(public_key),
+            "expires_at_block_id": registry.get_expiry_block_id(handle.as_str()),
+        })
+        .to_string(),
+    )
+}
+
 fn run_websocket_server(
     peer_counter: Arc<Mutex<PeerCounter>>,
     sender_clone: Sender<IoEvent>,
     io_controller: Arc<RwLock<NetworkController>>,
     port: u16,
     host: String,
+    tls_config: Option<TlsConfig>,
     blockchain: Arc<RwLock<Blockchain>>,
+    blockchain_snapshot: Arc<std::sync::Mutex<Arc<BlockchainSnapshot>>>,
+    wallet: Arc<RwLock<Wallet>>,
+    mining_status: Arc<std::sync::Mutex<MiningStatus>>,
+    transaction_receipts: Arc<std::sync::Mutex<TransactionReceipts>>,
+    peers: Arc<RwLock<PeerCollection>>,
+    mempool: Arc<RwLock<Mempool>>,
+    peer_events: PeerEventBroadcaster,
+    pending_block_writes: PendingBlockWrites,
 ) -> JoinHandle<()> {
     info!("running websocket server on {:?}", port);
     tokio::spawn(async move {
         info!("starting websocket server");
+        let started_at = Instant::now();
         let io_controller = io_controller.clone();
         let sender_to_io = sender_clone.clone();
         let peer_counter = peer_counter.clone();
         let ws_route = warp::path("wsopen")
             .and(warp::ws())
-            .map(move |ws: warp::ws::Ws| {
+            .and(warp::addr::remote())
+            .map(move |ws: warp::ws::Ws, address: Option<SocketAddr>| {
                 debug!("incoming connection received");
                 let clone = io_controller.clone();
                 let _peer_counter = peer_counter.clone();
@@ -621,11 +1466,37 @@ fn run_websocket_server(
                         let mut counter = network_controller.peer_counter.lock().await;
                         peer_index = counter.get_next_index();
                     }
+                    // connections from static peers are accepted even if the slots are full
+                    let is_static = address
+                        .map(|address| {
+                            let ip = address.ip().to_string();
+                            network_controller
+                                .static_peers
+                                .iter()
+                                .any(|peer| peer.host == ip)
+                        })
+                        .unwrap_or(false);
+                    if !NetworkController::assign_slot(
+                        network_controller.sockets.clone(),
+                        network_controller.peer_slots.clone(),
+                        sender_to_io.clone(),
+                        peer_index,
+                        ConnectionDirection::Inbound,
+                        is_static,
+                    )
+                    .await
+                    {
+                        NetworkController::close_connection(PeerSender::Warp(sender), peer_index)
+                            .await;
+                        return;
+                    }
 
                     NetworkController::send_new_peer(
                         0,
                         peer_index,
                         network_controller.sockets.clone(),
+                        network_controller.peer_slots.clone(),
+                        network_controller.pending_requests.clone(),
                         PeerSender::Warp(sender),
                         PeerReceiver::Warp(receiver),
                         sender_to_io,
@@ -634,57 +1505,194 @@ fn run_websocket_server(
                     .await
                 })
             });
-        let http_route = warp::path!("block" / String).and_then(|block_hash: String| async move {
-            debug!("serving block : {:?}", block_hash);
-            let mut buffer: Vec<u8> = Default::default();
-            let result = fs::read_dir(BLOCKS_DIR_PATH.to_string());
-            if result.is_err() {
-                debug!("no blocks found");
-                return Err(warp::reject::not_found());
-            }
-            let paths: Vec<_> = result
-                .unwrap()
-                .map(|r| r.unwrap())
-                .filter(|r| {
-                    let filename = r.file_name().into_string().unwrap();
-                    if !filename.contains(BLOCK_FILE_EXTENSION) {
-                        return false;
-                    }
-                    if !filename.contains(block_hash.as_str()) {
-                        return false;
-                    }
-                    debug!("selected file : {:?}", filename);
-                    return true;
-                })
-                .collect();
+        let range_wallet = wallet.clone();
+        let range_snapshot = blockchain_snapshot.clone();
+        let status_snapshot = blockchain_snapshot.clone();
+        let handle_blockchain = blockchain.clone();
+        let range_pending_block_writes = pending_block_writes.clone();
+        let http_route = warp::path!("block" / String).and_then(move |block_hash: String| {
+            let wallet = wallet.clone();
+            let pending_block_writes = pending_block_writes.clone();
+            async move {
+                debug!("serving block : {:?}", block_hash);
+                // blocks are announced before their files are written
+                if let Some(hash) = hex::decode(block_hash.as_str())
+                    .ok()
+                    .and_then(|hash| SaitoHash::try_from(hash).ok())
+                {
+                    pending_block_writes.wait_for_block_write(&hash).await;
+                }
+                let result = fs::read_dir(get_data_path(&BLOCKS_DIR_PATH));
+                if result.is_err() {
+                    debug!("no blocks found");
+                    return Err(warp::reject::not_found());
+                }
+                let paths: Vec<_> = result
+                    .unwrap()
+                    .map(|r| r.unwrap())
+                    .filter(|r| {
+                        let filename = r.file_name().into_string().unwrap();
+                        // temporary files of blocks being streamed to disk also contain the extension
+                        if !filename.ends_with(BLOCK_FILE_EXTENSION) {
+                            return false;
+                        }
+                        if !filename.contains(block_hash.as_str()) {
+                            return false;
+                        }
+                        debug!("selected file : {:?}", filename);
+                        return true;
+                    })
+                    .collect();
+
+                if paths.is_empty() {
+                    return Err(warp::reject::not_found());
+                }
+                let path = paths.first().unwrap();
+                let file_path = get_data_path(&BLOCKS_DIR_PATH)
+                    + "/"
+                    + path.file_name().into_string().unwrap().as_str();
+                let result = File::open(file_path.as_str()).await;
+                if result.is_err() {
+                    error!("failed opening file : {:?}", result.err().unwrap());
+                    todo!()
+                }
+                let file = result.unwrap();
+                let file_size = file
+                    .metadata()
+                    .await
+                    .map(|metadata| metadata.len())
+                    .unwrap_or(0);
 
-            if paths.is_empty() {
-                return Err(warp::reject::not_found());
+                // blocks are streamed in chunks, so large blocks are not loaded into memory at once
+                let stream = futures::stream::unfold(Some(file), read_file_chunk);
+                let mut builder = warp::http::Response::builder()
+                    .status(StatusCode::OK)
+                    .header(warp::http::header::CONTENT_LENGTH, file_size);
+                // the response is signed, so the peer can prove which node served the block
+                if let Ok(hash) = hex::decode(block_hash.as_str()) {
+                    if let Ok(hash) = SaitoHash::try_from(hash) {
+                        let (wallet, _wallet_) = lock_for_read!(wallet, LOCK_ORDER_WALLET);
+                        let signature =
+                            sign_block_fetch_response(&hash, file_size, &wallet.private_key);
+                        builder =
+                            builder.header(BLOCK_FETCH_SIGNATURE_HEADER, hex::encode(signature));
+                    }
+                }
+                let result = builder.body(warp::hyper::Body::wrap_stream(stream));
+                if result.is_err() {
+                    error!("failed creating response : {:?}", result.err().unwrap());
+                    return Err(warp::reject::not_found());
+                }
+                debug!("serving block with : {:?} length", file_size);
+                return Ok(result.unwrap());
             }
-            let path = paths.first().unwrap();
-            let file_path = BLOCKS_DIR_PATH.to_string()
-                + "/"
-                + path.file_name().into_string().unwrap().as_str();
-            let result = File::open(file_path.as_str()).await;
-            if result.is_err() {
-                error!("failed opening file : {:?}", result.err().unwrap());
-                todo!()
+        });
+        let block_range_route = warp::path!("blocks")
+            .and(warp::query::<BlockRangeRequest>())
+            .and_then(move |request: BlockRangeRequest| {
+                let blockchain = blockchain.clone();
+                let blockchain_snapshot = range_snapshot.clone();
+                let wallet = range_wallet.clone();
+                let pending_block_writes = range_pending_block_writes.clone();
+                async move {
+                    serve_block_range(
+                        request,
+                        blockchain,
+                        blockchain_snapshot,
+                        wallet,
+                        pending_block_writes,
+                    )
+                    .await
+                }
+            });
+        let status = mining_status.clone();
+        let mining_route = warp::path!("mining").map(move || {
+            let json = status.lock().unwrap().to_json();
+            warp::http::Response::builder()
+                .header(warp::http::header::CONTENT_TYPE, "application/json")
+                .body(json)
+        });
+        let mining_ws_route =
+            warp::path!("mining" / "ws")
+                .and(warp::ws())
+                .map(move |ws: warp::ws::Ws| {
+                    let mining_status = mining_status.clone();
+                    ws.on_upgrade(move |socket| {
+                        push_json_updates(socket, "mining status", move || {
+                            mining_status.lock().unwrap().to_json()
+                        })
+                    })
+                });
+        let handle_route = warp::path!("handle" / String).and_then(move |handle: String| {
+            let blockchain = handle_blockchain.clone();
+            async move {
+                match get_handle_json(&blockchain, handle.as_str()).await {
+                    Some(json) => Ok(warp::http::Response::builder()
+                        .header(warp::http::header::CONTENT_TYPE, "application/json")
+                        .body(json)),
+                    None => Err(warp::reject::not_found()),
+                }
             }
-            let mut file = result.unwrap();
-
-            let result = file.read_to_end(&mut buffer).await;
-            if result.is_err() {
-                error!("failed reading file : {:?}", result.err().unwrap());
-                todo!()
+        });
+        let status_route = warp::path!("status").and_then(move || {
+            let blockchain_snapshot = status_snapshot.clone();
+            let peers = peers.clone();
+            let mempool = mempool.clone();
+            async move {
+                let page =
+                    StatusPage::load(started_at.elapsed(), &blockchain_snapshot, &peers, &mempool)
+                        .await;
+                Ok::<_, warp::Rejection>(warp::reply::html(page.to_html()))
             }
-            drop(file);
-
-            let buffer_len = buffer.len();
-            let result = Ok(warp::reply::with_status(buffer, StatusCode::OK));
-            debug!("served block with : {:?} length", buffer_len);
-            return result;
         });
-        let routes = http_route.or(ws_route);
+        let receipts = transaction_receipts.clone();
+        let receipt_route = warp::path!("tx" / String).and_then(move |signature: String| {
+            let receipts = receipts.clone();
+            async move {
+                match get_transaction_receipt_json(&receipts, signature.as_str()) {
+                    Some(json) => Ok(warp::http::Response::builder()
+                        .header(warp::http::header::CONTENT_TYPE, "application/json")
+                        .body(json)),
+                    None => Err(warp::reject::not_found()),
+                }
+            }
+        });
+        let receipt_ws_route = warp::path!("tx" / String / "ws").and(warp::ws()).and_then(
+            move |signature: String, ws: warp::ws::Ws| {
+                let transaction_receipts = transaction_receipts.clone();
+                async move {
+                    if get_transaction_receipt_json(&transaction_receipts, signature.as_str())
+                        .is_none()
+                    {
+                        return Err(warp::reject::not_found());
+                    }
+                    Ok(ws.on_upgrade(move |socket| async move {
+                        push_json_updates(socket, "transaction receipt", move || {
+                            get_transaction_receipt_json(&transaction_receipts, signature.as_str())
+                                .unwrap_or_default()
+                        })
+                        .await
+                    }))
+                }
+            },
+        );
+        let peer_events_ws_route =
+            warp::path!("peers" / "ws")
+                .and(warp::ws())
+                .map(move |ws: warp::ws::Ws| {
+                    let receiver = peer_events.subscribe();
+                    ws.on_upgrade(move |socket| push_peer_events(socket, receiver))
+                });
+        let routes = http_route
+            .or(block_range_route)
+            .or(mining_route)
+            .or(mining_ws_route)
+            .or(receipt_route)
+            .or(receipt_ws_route)
+            .or(handle_route)
+            .or(status_route)
+            .or(peer_events_ws_route)
+            .or(ws_route);
         // let (_, server) =
         //     warp::serve(ws_route).bind_with_graceful_shutdown(([127, 0, 0, 1], port), async {
         //         // tokio::signal::ctrl_c().await.ok();
@@ -692,6 +1700,19 @@ fn run_websocket_server(
         // server.await;
         let address =
             SocketAddr::from_str((host + ":" + port.to_string().as_str()).as_str()).unwrap();
-        warp::serve(routes).run(address).await;
+        match tls_config {
+            Some(tls_config) => {
+                info!("serving peer connections over tls");
+                warp::serve(routes)
+                    .tls()
+                    .cert_path(tls_config.cert_path)
+                    .key_path(tls_config.key_path)
+                    .run(address)
+                    .await;
+            }
+            None => {
+                warp::serve(routes).run(address).await;
+            }
+        }
     })
 }
@@ -0,0 +1,256 @@
+use std::io::Error;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use saito_core::common::defs::Timestamp;
+use saito_core::core::data::configuration::{DnsSeedConfig, PeerConfig};
+
+pub const PEER_STORE_FILE_PATH: &str = "./data/peers.json";
+/// stored peers are removed after failing this many connection attempts in a row
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+/// peers which were never connected are dropped first when the store is full
+const MAX_STORED_PEERS: usize = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StoredPeer {
+    pub host: String,
+    pub port: u16,
+    pub protocol: String,
+    /// 0 if the peer was never connected
+    pub last_connected_at: Timestamp,
+    pub failed_attempts: u32,
+}
+
+impl StoredPeer {
+    fn matches(&self, peer: &PeerConfig) -> bool {
+        self.host == peer.host && self.port == peer.port
+    }
+
+    pub fn to_peer_config(&self) -> PeerConfig {
+        PeerConfig {
+            host: self.host.clone(),
+            port: self.port,
+            protocol: self.protocol.clone(),
+            synctype: "full".to_string(),
+            scheme: None,
+            public_key: None,
+        }
+    }
+}
+
+/// Peers found with the dns seeds, kept on disk so the node can reconnect to them after a restart even if the seeds are
+/// not reachable. Static peers from the config file are not stored.
+#[derive(Debug)]
+pub struct PeerStore {
+    path: PathBuf,
+    peers: Vec<StoredPeer>,
+}
+
+impl PeerStore {
+    pub fn new(path: &str) -> PeerStore {
+        PeerStore {
+            path: PathBuf::from(path),
+            peers: vec![],
+        }
+    }
+
+    /// Loads the stored peers. A missing or unreadable file gives an empty store
+    pub async fn load(path: &str) -> PeerStore {
+        let mut store = PeerStore::new(path);
+        match tokio::fs::read(&store.path).await {
+            Ok(buffer) => match serde_json::from_slice(&buffer) {
+                Ok(peers) => store.peers = peers,
+                Err(error) => warn!("failed parsing peer store : {:?} : {:?}", path, error),
+            },
+            Err(error) => debug!("peer store : {:?} not loaded : {:?}", path, error),
+        }
+        info!("{:?} peers loaded from the peer store", store.peers.len());
+        store
+    }
+
+    pub async fn save(&self) -> Result<(), Error> {
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let buffer = serde_json::to_vec_pretty(&self.peers)?;
+        // written under a temporary name first, so a crash doesn't leave a partial file
+        let temp_path = self.path.with_extension("tmp");
+        tokio::fs::write(&temp_path, buffer).await?;
+        tokio::fs::rename(&temp_path, &self.path).await
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Adds the peers which are not in the store yet. Returns the number of added peers
+    pub fn add_candidates(&mut self, peers: &[PeerConfig]) -> usize {
+        let mut added = 0;
+        for peer in peers.iter() {
+            if self.peers.iter().any(|stored| stored.matches(peer)) {
+                continue;
+            }
+            self.peers.push(StoredPeer {
+                host: peer.host.clone(),
+                port: peer.port,
+                protocol: peer.protocol.clone(),
+                last_connected_at: 0,
+                failed_attempts: 0,
+            });
+            added += 1;
+        }
+        if self.peers.len() > MAX_STORED_PEERS {
+            self.sort();
+            self.peers.truncate(MAX_STORED_PEERS);
+        }
+        added
+    }
+
+    pub fn on_connected(&mut self, peer: &PeerConfig, current_time: Timestamp) {
+        match self.peers.iter_mut().find(|stored| stored.matches(peer)) {
+            Some(stored) => {
+                stored.last_connected_at = current_time;
+                stored.failed_attempts = 0;
+            }
+            None => self.peers.push(StoredPeer {
+                host: peer.host.clone(),
+                port: peer.port,
+                protocol: peer.protocol.clone(),
+                last_connected_at: current_time,
+                failed_attempts: 0,
+            }),
+        }
+    }
+
+    pub fn on_connection_failed(&mut self, peer: &PeerConfig) {
+        if let Some(stored) = self.peers.iter_mut().find(|stored| stored.matches(peer)) {
+            stored.failed_attempts += 1;
+        }
+        self.peers.retain(|stored| {
+            if stored.failed_attempts < MAX_FAILED_ATTEMPTS {
+                return true;
+            }
+            info!(
+                "removing peer : {:?}:{:?} from the peer store after {:?} failed attempts",
+                stored.host, stored.port, stored.failed_attempts
+            );
+            false
+        });
+    }
+
+    /// most recently connected first, then the peers with the least failed attempts
+    fn sort(&mut self) {
+        self.peers.sort_by(|a, b| {
+            b.last_connected_at
+                .cmp(&a.last_connected_at)
+                .then(a.failed_attempts.cmp(&b.failed_attempts))
+        });
+    }
+
+    /// Returns up to `count` peers to connect to, skipping the excluded ones
+    pub fn get_candidates<F: Fn(&PeerConfig) -> bool>(
+        &mut self,
+        count: usize,
+        is_excluded: F,
+    ) -> Vec<PeerConfig> {
+        self.sort();
+        self.peers
+            .iter()
+            .map(|stored| stored.to_peer_config())
+            .filter(|peer| !is_excluded(peer))
+            .take(count)
+            .collect()
+    }
+}
+
+/// Resolves the seed hostnames to the addresses of the nodes behind them
+pub async fn resolve_seeds(config: &DnsSeedConfig) -> Vec<PeerConfig> {
+    let mut peers: Vec<PeerConfig> = vec![];
+    for host in config.hosts.iter() {
+        match tokio::net::lookup_host((host.as_str(), config.port)).await {
+            Ok(addresses) => {
+                for address in addresses {
+                    let peer = PeerConfig {
+                        host: address.ip().to_string(),
+                        port: address.port(),
+                        protocol: config.protocol.clone(),
+                        synctype: "full".to_string(),
+                        scheme: None,
+                        public_key: None,
+                    };
+                    if !peers.contains(&peer) {
+                        peers.push(peer);
+                    }
+                }
+            }
+            Err(error) => {
+                warn!("failed resolving dns seed : {:?} : {:?}", host, error);
+            }
+        }
+    }
+    debug!("{:?} peers resolved from dns seeds", peers.len());
+    peers
+}
+
+#[cfg(test)]
+mod tests {
+    use saito_core::core::data::configuration::{DnsSeedConfig, PeerConfig};
+
+    use crate::saito::peer_discovery::{resolve_seeds, PeerStore, MAX_FAILED_ATTEMPTS};
+
+    fn create_peer(host: &str) -> PeerConfig {
+        PeerConfig {
+            host: host.to_string(),
+            port: 12101,
+            protocol: "http".to_string(),
+            synctype: "full".to_string(),
+            scheme: None,
+            public_key: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn peer_store_test() {
+        let path = std::env::temp_dir().join("saito_peer_store_test.json");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+
+        let mut store = PeerStore::load(path).await;
+        assert_eq!(store.len(), 0);
+        assert_eq!(
+            store.add_candidates(&[create_peer("10.0.0.1"), create_peer("10.0.0.2")]),
+            2
+        );
+        assert_eq!(store.add_candidates(&[create_peer("10.0.0.1")]), 0);
+
+        store.on_connected(&create_peer("10.0.0.2"), 1000);
+        let candidates = store.get_candidates(2, |_| false);
+        assert_eq!(candidates[0].host, "10.0.0.2");
+        let candidates = store.get_candidates(2, |peer| peer.host == "10.0.0.2");
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].host, "10.0.0.1");
+
+        for _ in 0..MAX_FAILED_ATTEMPTS {
+            store.on_connection_failed(&create_peer("10.0.0.1"));
+        }
+        assert_eq!(store.len(), 1);
+
+        store.save().await.unwrap();
+        let store = PeerStore::load(path).await;
+        assert_eq!(store.len(), 1);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn resolve_seeds_test() {
+        let config = DnsSeedConfig {
+            hosts: vec!["localhost".to_string()],
+            ..Default::default()
+        };
+        let peers = resolve_seeds(&config).await;
+        assert!(!peers.is_empty());
+        assert!(peers.iter().all(|peer| peer.port == config.port));
+    }
+}
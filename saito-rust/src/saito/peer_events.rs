@@ -0,0 +1,37 @@
+use tokio::sync::broadcast;
+
+use saito_core::core::data::peer_events::{PeerEvent, PeerEventListener};
+
+const PEER_EVENT_CHANNEL_SIZE: usize = 100;
+
+/// Sends the peer events to the subscribers, e.g. the websocket clients of /peers/ws. Subscribers which fall behind
+/// by more than the channel size miss the oldest events
+#[derive(Debug, Clone)]
+pub struct PeerEventBroadcaster {
+    sender: broadcast::Sender<PeerEvent>,
+}
+
+impl PeerEventBroadcaster {
+    pub fn new() -> PeerEventBroadcaster {
+        let (sender, _) = broadcast::channel(PEER_EVENT_CHANNEL_SIZE);
+        PeerEventBroadcaster { sender }
+    }
+
+    /// Receives the events sent after subscribing
+    pub fn subscribe(&self) -> broadcast::Receiver<PeerEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for PeerEventBroadcaster {
+    fn default() -> Self {
+        PeerEventBroadcaster::new()
+    }
+}
+
+impl PeerEventListener for PeerEventBroadcaster {
+    fn on_peer_event(&self, event: &PeerEvent) {
+        // fails only if there are no subscribers
+        let _ = self.sender.send(event.clone());
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+use tracing::{debug, trace};
+
+use saito_core::common::defs::Timestamp;
+use saito_core::core::data::configuration::PeerSlotsConfig;
+
+/// number of inbound peers with the lowest latency which are not evicted
+const PROTECTED_BY_LATENCY: usize = 4;
+/// number of longest connected inbound peers which are not evicted
+const PROTECTED_BY_UPTIME: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionDirection {
+    Inbound,
+    Outbound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotResult {
+    Accepted,
+    /// accepted after disconnecting the peer with the given index
+    AcceptedAfterEvicting(u64),
+    Rejected,
+}
+
+#[derive(Debug, Clone)]
+struct PeerSlot {
+    direction: ConnectionDirection,
+    is_static: bool,
+    connected_at: Timestamp,
+    latency: Option<Timestamp>,
+    ping_sent_at: Option<Timestamp>,
+}
+
+/// Keeps track of the connection slots used by the peers. Statically configured peers don't use any slots, so an
+/// attacker filling the slots cannot isolate the node from them.
+///
+/// When the inbound slots are full, a new inbound peer replaces the most recently connected inbound peer, excluding
+/// the peers with the lowest latency and the longest lived peers.
+#[derive(Debug)]
+pub struct PeerSlots {
+    slots: HashMap<u64, PeerSlot>,
+    config: PeerSlotsConfig,
+}
+
+impl PeerSlots {
+    pub fn new(config: PeerSlotsConfig) -> PeerSlots {
+        PeerSlots {
+            slots: Default::default(),
+            config,
+        }
+    }
+
+    /// number of slots used in the given direction
+    pub fn get_used_slots(&self, direction: ConnectionDirection) -> usize {
+        self.slots
+            .values()
+            .filter(|slot| slot.direction == direction && !slot.is_static)
+            .count()
+    }
+
+    pub fn get_max_slots(&self, direction: ConnectionDirection) -> usize {
+        match direction {
+            ConnectionDirection::Inbound => self.config.max_inbound_peers,
+            ConnectionDirection::Outbound => self.config.max_outbound_peers,
+        }
+    }
+
+    pub fn has_free_slot(&self, direction: ConnectionDirection, is_static: bool) -> bool {
+        is_static || self.get_used_slots(direction) < self.get_max_slots(direction)
+    }
+
+    pub fn try_add(
+        &mut self,
+        peer_index: u64,
+        direction: ConnectionDirection,
+        is_static: bool,
+        current_time: Timestamp,
+    ) -> SlotResult {
+        let mut result = SlotResult::Accepted;
+        if !self.has_free_slot(direction, is_static) {
+            if direction == ConnectionDirection::Outbound {
+                return SlotResult::Rejected;
+            }
+            match self.select_peer_to_evict() {
+                Some(evicted) => {
+                    debug!(
+                        "evicting inbound peer : {:?} for peer : {:?}",
+                        evicted, peer_index
+                    );
+                    self.slots.remove(&evicted);
+                    result = SlotResult::AcceptedAfterEvicting(evicted);
+                }
+                None => {
+                    return SlotResult::Rejected;
+                }
+            }
+        }
+        self.slots.insert(
+            peer_index,
+            PeerSlot {
+                direction,
+                is_static,
+                connected_at: current_time,
+                latency: None,
+                ping_sent_at: None,
+            },
+        );
+        result
+    }
+
+    fn select_peer_to_evict(&self) -> Option<u64> {
+        let mut candidates: Vec<(u64, &PeerSlot)> = self
+            .slots
+            .iter()
+            .filter(|(_, slot)| slot.direction == ConnectionDirection::Inbound && !slot.is_static)
+            .map(|(index, slot)| (*index, slot))
+            .collect();
+
+        // peers without a measured latency are considered the slowest
+        candidates.sort_by_key(|(index, slot)| (slot.latency.unwrap_or(Timestamp::MAX), *index));
+        candidates.drain(..PROTECTED_BY_LATENCY.min(candidates.len()));
+
+        candidates.sort_by_key(|(index, slot)| (slot.connected_at, *index));
+        candidates.drain(..PROTECTED_BY_UPTIME.min(candidates.len()));
+
+        candidates.last().map(|(index, _)| *index)
+    }
+
+    /// Releases the slots of the peers which are no longer connected
+    pub fn retain_connected<F>(&mut self, is_connected: F)
+    where
+        F: Fn(u64) -> bool,
+    {
+        self.slots.retain(|index, _| is_connected(*index));
+    }
+
+    pub fn on_ping_sent(&mut self, peer_index: u64, current_time: Timestamp) {
+        if let Some(slot) = self.slots.get_mut(&peer_index) {
+            slot.ping_sent_at = Some(current_time);
+        }
+    }
+
+    pub fn on_pong_received(&mut self, peer_index: u64, current_time: Timestamp) {
+        if let Some(slot) = self.slots.get_mut(&peer_index) {
+            if let Some(ping_sent_at) = slot.ping_sent_at.take() {
+                slot.latency = Some(current_time.saturating_sub(ping_sent_at));
+                trace!("latency of peer : {:?} is {:?}", peer_index, slot.latency);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use saito_core::core::data::configuration::PeerSlotsConfig;
+
+    use crate::saito::peer_slots::{ConnectionDirection, PeerSlots, SlotResult};
+
+    #[test]
+    fn peer_slots_test() {
+        let mut slots = PeerSlots::new(PeerSlotsConfig {
+            max_outbound_peers: 1,
+            max_inbound_peers: 14,
+        });
+
+        assert_eq!(
+            slots.try_add(1, ConnectionDirection::Outbound, false, 0),
+            SlotResult::Accepted
+        );
+        assert_eq!(
+            slots.try_add(2, ConnectionDirection::Outbound, false, 0),
+            SlotResult::Rejected
+        );
+        // static peers don't use slots
+        assert_eq!(
+            slots.try_add(3, ConnectionDirection::Outbound, true, 0),
+            SlotResult::Accepted
+        );
+        assert_eq!(slots.get_used_slots(ConnectionDirection::Outbound), 1);
+
+        for index in 10..24 {
+            assert_eq!(
+                slots.try_add(index, ConnectionDirection::Inbound, false, index * 100),
+                SlotResult::Accepted
+            );
+        }
+        // lowest latency peers are protected even if they connected recently
+        for index in 20..24 {
+            slots.on_ping_sent(index, 5000);
+            slots.on_pong_received(index, 5010);
+        }
+
+        // 4 protected by latency and 8 by uptime (10..18) leaves 18 and 19. 19 connected last
+        assert_eq!(
+            slots.try_add(30, ConnectionDirection::Inbound, false, 6000),
+            SlotResult::AcceptedAfterEvicting(19)
+        );
+        assert_eq!(
+            slots.try_add(31, ConnectionDirection::Inbound, false, 6000),
+            SlotResult::AcceptedAfterEvicting(30)
+        );
+        assert_eq!(slots.get_used_slots(ConnectionDirection::Inbound), 14);
+
+        slots.retain_connected(|index| index != 31 && index != 18);
+        assert_eq!(slots.get_used_slots(ConnectionDirection::Inbound), 12);
+
+        // no peers left to evict
+        let mut slots = PeerSlots::new(PeerSlotsConfig {
+            max_outbound_peers: 1,
+            max_inbound_peers: 2,
+        });
+        slots.try_add(1, ConnectionDirection::Inbound, false, 0);
+        slots.try_add(2, ConnectionDirection::Inbound, false, 0);
+        assert_eq!(
+            slots.try_add(3, ConnectionDirection::Inbound, false, 0),
+            SlotResult::Rejected
+        );
+        assert_eq!(
+            slots.try_add(4, ConnectionDirection::Inbound, true, 0),
+            SlotResult::Accepted
+        );
+    }
+}
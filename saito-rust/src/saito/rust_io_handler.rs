@@ -1,18 +1,18 @@
 use std::fmt::{Debug, Formatter};
 use std::fs;
-use std::io::Error;
+use std::io::{Error, SeekFrom};
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::{Mutex, RwLock};
 
 use async_trait::async_trait;
 use lazy_static::lazy_static;
-use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 use tokio::sync::mpsc::Sender;
-use tracing::{debug, trace, warn};
+use tracing::{debug, info, trace, warn};
 
 use saito_core::common::command::NetworkEvent;
-use saito_core::common::defs::{SaitoHash, BLOCK_FILE_EXTENSION};
+use saito_core::common::defs::{SaitoHash, BLOCK_FILE_EXTENSION, TEMP_FILE_EXTENSION};
 use saito_core::common::interface_io::InterfaceIO;
 use saito_core::core::data::configuration::PeerConfig;
 
@@ -22,7 +22,37 @@ use crate::IoEvent;
 lazy_static! {
     pub static ref SHARED_CONTEXT: Mutex<IoContext> = Mutex::new(IoContext::new());
     pub static ref BLOCKS_DIR_PATH: String = configure_storage();
+    static ref DATA_DIR: RwLock<String> = RwLock::new(DEFAULT_DATA_DIR.to_string());
 }
+
+/// prefix of the data paths used by saito-core. they are mapped to the data directory of the network on disk
+const DEFAULT_DATA_DIR: &str = "./data/";
+
+/// Sets the directory the data paths are mapped to. Should be called before anything is read from the data directory
+pub fn set_data_dir(data_dir: &str) {
+    info!("using data directory : {:?}", data_dir);
+    *DATA_DIR.write().unwrap() = data_dir.to_string();
+}
+
+/// Location of a data path (e.g. "./data/blocks/" or "data/wallets/default") in the data directory of the network.
+/// Other paths are returned as they are
+pub fn get_data_path(key: &str) -> String {
+    map_data_path(DATA_DIR.read().unwrap().as_str(), key)
+}
+
+fn map_data_path(data_dir: &str, key: &str) -> String {
+    if data_dir == DEFAULT_DATA_DIR {
+        return key.to_string();
+    }
+    match key
+        .strip_prefix(DEFAULT_DATA_DIR)
+        .or_else(|| key.strip_prefix("data/"))
+    {
+        Some(relative_path) => data_dir.to_string() + relative_path,
+        None => key.to_string(),
+    }
+}
+
 pub fn configure_storage() -> String {
     if cfg!(test) {
         String::from("./data/test/blocks/")
@@ -77,6 +107,15 @@ impl Debug for RustIOHandler {
     }
 }
 
+/// Makes the rename of a file durable. Not supported on all platforms, so failures are ignored
+async fn sync_parent_dir(path: &Path) {
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = File::open(parent).await {
+            let _ = dir.sync_all().await;
+        }
+    }
+}
+
 #[async_trait]
 impl InterfaceIO for RustIOHandler {
     async fn send_message(&self, peer_index: u64, buffer: Vec<u8>) -> Result<(), Error> {
@@ -147,6 +186,7 @@ impl InterfaceIO for RustIOHandler {
 
     async fn write_value(&mut self, key: String, value: Vec<u8>) -> Result<(), Error> {
         debug!("writing value to disk : {:?}", key);
+        let key = get_data_path(&key);
         let filename = key.as_str();
         let path = Path::new(filename);
         if path.parent().is_some() {
@@ -154,33 +194,86 @@ impl InterfaceIO for RustIOHandler {
                 .await
                 .expect("creating directory structure failed");
         }
-        let result = File::create(filename).await;
-        if result.is_err() {
-            return Err(result.err().unwrap());
-        }
-        let mut file = result.unwrap();
-        let result = file.write_all(&value).await;
-        if result.is_err() {
-            return Err(result.err().unwrap());
-        }
+        // written to a temporary file first, so a crash doesn't leave a partially written file with the final name
+        let temp_filename = key.clone() + TEMP_FILE_EXTENSION;
+        let mut file = File::create(&temp_filename).await?;
+        file.write_all(&value).await?;
+        file.sync_all().await?;
+        drop(file);
+        tokio::fs::rename(&temp_filename, path).await?;
+        sync_parent_dir(path).await;
 
         Ok(())
     }
 
     #[tracing::instrument(level = "info", skip_all)]
     async fn read_value(&self, key: String) -> Result<Vec<u8>, Error> {
-        let result = File::open(key).await;
-        if result.is_err() {
-            todo!()
-        }
-        let mut file = result.unwrap();
+        let mut file = File::open(get_data_path(&key)).await?;
         let mut encoded = Vec::<u8>::new();
+        file.read_to_end(&mut encoded).await?;
+        Ok(encoded)
+    }
 
-        let result = file.read_to_end(&mut encoded).await;
-        if result.is_err() {
-            todo!()
+    async fn read_value_chunk(
+        &self,
+        key: String,
+        offset: u64,
+        length: u64,
+    ) -> Result<Vec<u8>, Error> {
+        let mut file = File::open(get_data_path(&key)).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+        let mut buffer = vec![];
+        file.take(length).read_to_end(&mut buffer).await?;
+        Ok(buffer)
+    }
+
+    async fn append_value(&mut self, key: String, chunk: Vec<u8>) -> Result<(), Error> {
+        let key = get_data_path(&key);
+        let path = Path::new(key.as_str());
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
         }
-        Ok(encoded)
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(&chunk).await
+    }
+
+    async fn rename_value(&mut self, from: String, to: String) -> Result<(), Error> {
+        let (from, to) = (get_data_path(&from), get_data_path(&to));
+        File::open(&from).await?.sync_all().await?;
+        tokio::fs::rename(&from, &to).await?;
+        sync_parent_dir(Path::new(to.as_str())).await;
+        Ok(())
+    }
+
+    async fn remove_temp_files(&mut self) -> Result<usize, Error> {
+        let mut count = 0;
+        let mut entries = match tokio::fs::read_dir(get_data_path(&self.get_block_dir())).await {
+            Ok(entries) => entries,
+            Err(_) => return Ok(0),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with(TEMP_FILE_EXTENSION)
+            {
+                warn!(
+                    "removing temporary file : {:?} left by an interrupted write",
+                    entry.path()
+                );
+                tokio::fs::remove_file(entry.path()).await?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    async fn get_value_size(&self, key: String) -> Result<u64, Error> {
+        Ok(tokio::fs::metadata(get_data_path(&key)).await?.len())
     }
 
     #[tracing::instrument(level = "info", skip_all)]
@@ -189,7 +282,7 @@ impl InterfaceIO for RustIOHandler {
             "loading blocks from dir : {:?}",
             self.get_block_dir().to_string(),
         );
-        let result = fs::read_dir(self.get_block_dir());
+        let result = fs::read_dir(get_data_path(&self.get_block_dir()));
         if result.is_err() {
             debug!("no blocks found");
             return Err(result.err().unwrap());
@@ -201,7 +294,7 @@ impl InterfaceIO for RustIOHandler {
                 r.file_name()
                     .into_string()
                     .unwrap()
-                    .contains(BLOCK_FILE_EXTENSION)
+                    .ends_with(BLOCK_FILE_EXTENSION)
             })
             .collect();
         paths.sort_by(|a, b| {
@@ -222,11 +315,11 @@ impl InterfaceIO for RustIOHandler {
     }
 
     async fn is_existing_file(&self, key: String) -> bool {
-        return Path::new(&key).exists();
+        return Path::new(&get_data_path(&key)).exists();
     }
 
     async fn remove_value(&self, key: String) -> Result<(), Error> {
-        let result = tokio::fs::remove_file(key).await;
+        let result = tokio::fs::remove_file(get_data_path(&key)).await;
         return result;
     }
 
@@ -239,7 +332,7 @@ impl InterfaceIO for RustIOHandler {
 mod tests {
     use saito_core::common::interface_io::InterfaceIO;
 
-    use crate::saito::rust_io_handler::RustIOHandler;
+    use crate::saito::rust_io_handler::{map_data_path, RustIOHandler};
 
     #[tokio::test]
     async fn test_write_value() {
@@ -256,6 +349,70 @@ mod tests {
         assert_eq!(result, [1, 2, 3, 4]);
     }
 
+    #[tokio::test]
+    async fn write_value_with_temp_file_test() {
+        let (sender, mut _receiver) = tokio::sync::mpsc::channel(10);
+        let mut io_handler = RustIOHandler::new(sender, 0);
+        let dir = io_handler.get_block_dir();
+        let key = dir.clone() + "atomic_write_test.sai";
+
+        io_handler
+            .write_value(key.clone(), vec![1, 2, 3])
+            .await
+            .unwrap();
+        assert_eq!(
+            io_handler.read_value(key.clone()).await.unwrap(),
+            vec![1, 2, 3]
+        );
+        assert!(!io_handler.is_existing_file(key.clone() + ".tmp").await);
+
+        // left by a write interrupted before the rename
+        let temp_key = dir + "interrupted_write_test.sai.tmp";
+        std::fs::write(&temp_key, [1, 2]).unwrap();
+        let block_files = io_handler.load_block_file_list().await.unwrap();
+        assert!(block_files.contains(&"atomic_write_test.sai".to_string()));
+        assert!(!block_files.contains(&"interrupted_write_test.sai.tmp".to_string()));
+
+        assert!(io_handler.remove_temp_files().await.unwrap() >= 1);
+        assert!(!io_handler.is_existing_file(temp_key).await);
+        assert!(io_handler.is_existing_file(key.clone()).await);
+
+        let renamed_key = key.clone() + ".renamed";
+        io_handler
+            .rename_value(key.clone(), renamed_key.clone())
+            .await
+            .unwrap();
+        assert!(!io_handler.is_existing_file(key).await);
+        assert_eq!(
+            io_handler.read_value(renamed_key.clone()).await.unwrap(),
+            vec![1, 2, 3]
+        );
+        io_handler.remove_value(renamed_key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_and_append_value_chunks() {
+        let (sender, mut _receiver) = tokio::sync::mpsc::channel(10);
+        let mut io_handler = RustIOHandler::new(sender, 0);
+        let key = "./data/test/CHUNKED_KEY".to_string();
+        let _ = io_handler.remove_value(key.clone()).await;
+
+        let result = io_handler.append_value(key.clone(), vec![1, 2, 3]).await;
+        assert!(result.is_ok());
+        let result = io_handler.append_value(key.clone(), vec![4, 5]).await;
+        assert!(result.is_ok());
+        assert_eq!(io_handler.get_value_size(key.clone()).await.unwrap(), 5);
+
+        let result = io_handler.read_value_chunk(key.clone(), 1, 3).await;
+        assert_eq!(result.unwrap(), vec![2, 3, 4]);
+        let result = io_handler.read_value_chunk(key.clone(), 3, 10).await;
+        assert_eq!(result.unwrap(), vec![4, 5]);
+        let result = io_handler.read_value_chunk(key.clone(), 10, 10).await;
+        assert!(result.unwrap().is_empty());
+
+        io_handler.remove_value(key).await.unwrap();
+    }
+
     #[tokio::test]
     async fn file_exists_success() {
         let (sender, mut _receiver) = tokio::sync::mpsc::channel(10);
@@ -266,6 +423,27 @@ mod tests {
         assert!(result);
     }
 
+    #[test]
+    fn map_data_path_test() {
+        assert_eq!(
+            map_data_path("./data/", "./data/blocks/1.sai"),
+            "./data/blocks/1.sai"
+        );
+        assert_eq!(
+            map_data_path("./data/testnet/", "./data/blocks/1.sai"),
+            "./data/testnet/blocks/1.sai"
+        );
+        assert_eq!(
+            map_data_path("./data/testnet/", "data/wallets/default"),
+            "./data/testnet/wallets/default"
+        );
+        // paths outside the data directory are not mapped
+        assert_eq!(
+            map_data_path("./data/testnet/", "./archive/1.sai"),
+            "./archive/1.sai"
+        );
+    }
+
     #[tokio::test]
     async fn file_exists_fail() {
         let (sender, mut _receiver) = tokio::sync::mpsc::channel(10);
@@ -10,6 +10,8 @@ use saito_core::common::command::NetworkEvent;
 use saito_core::common::defs::Timestamp;
 use saito_core::common::process_event::ProcessEvent;
 
+use crate::saito::rust_io_handler::get_data_path;
+
 pub struct StatThread {
     pub file: File,
     pub stat_queue: VecDeque<String>,
@@ -17,7 +19,8 @@ pub struct StatThread {
 
 impl StatThread {
     pub async fn new() -> StatThread {
-        let path = Path::new("./data/saito.stats");
+        let path = get_data_path("./data/saito.stats");
+        let path = Path::new(path.as_str());
 
         let file = File::create(path).await.unwrap();
 
@@ -57,4 +60,9 @@ impl ProcessEvent<String> for StatThread {
     async fn on_init(&mut self) {}
 
     async fn on_stat_interval(&mut self, current_time: Timestamp) {}
+
+    async fn on_shutdown(&mut self) {
+        // write the stats queued since the last timer event
+        self.process_timer_event(Duration::default()).await;
+    }
 }
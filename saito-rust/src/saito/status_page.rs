@@ -0,0 +1,295 @@
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::RwLock;
+
+use saito_core::common::defs::{
+    push_lock, BlockId, Timestamp, LOCK_ORDER_MEMPOOL, LOCK_ORDER_PEERS,
+};
+use saito_core::core::data::blockchain_snapshot::BlockchainSnapshot;
+use saito_core::core::data::configuration::PeerConfig;
+use saito_core::core::data::mempool::Mempool;
+use saito_core::core::data::peer_collection::PeerCollection;
+use saito_core::lock_for_read;
+
+/// number of latest blocks shown with their intervals
+const RECENT_BLOCK_COUNT: u64 = 10;
+/// the page reloads itself, so it can be left open during an incident
+const STATUS_PAGE_REFRESH_IN_SECONDS: u64 = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusPeer {
+    pub index: u64,
+    /// None until the handshake is completed
+    pub public_key: Option<String>,
+    /// address of a static peer. None for incoming connections
+    pub address: Option<String>,
+    pub latency: Option<Timestamp>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusBlock {
+    pub id: BlockId,
+    pub hash: String,
+    pub timestamp: Timestamp,
+    /// time since the previous block. None for the first block shown
+    pub interval: Option<Timestamp>,
+}
+
+/// Node health shown at the /status endpoint. Only the peers and the mempool locks are taken (one after the other),
+/// the chain is read from the blockchain snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusPage {
+    pub version: String,
+    pub uptime: Duration,
+    pub latest_block_id: BlockId,
+    pub latest_block_hash: String,
+    pub mempool_transactions: usize,
+    /// ordered by the peer index
+    pub peers: Vec<StatusPeer>,
+    /// latest block first
+    pub recent_blocks: Vec<StatusBlock>,
+}
+
+impl StatusPage {
+    pub async fn load(
+        uptime: Duration,
+        blockchain_snapshot: &Arc<Mutex<Arc<BlockchainSnapshot>>>,
+        peers: &RwLock<PeerCollection>,
+        mempool: &RwLock<Mempool>,
+    ) -> StatusPage {
+        let snapshot = blockchain_snapshot.lock().unwrap().clone();
+        let mut recent_blocks: Vec<StatusBlock> = vec![];
+        let mut previous_timestamp = None;
+        for summary in snapshot.iter_longest_chain(
+            snapshot
+                .latest_block_id
+                .saturating_sub(RECENT_BLOCK_COUNT - 1),
+        ) {
+            recent_blocks.push(StatusBlock {
+                id: summary.id,
+                hash: hex::encode(summary.hash),
+                timestamp: summary.timestamp,
+                interval: previous_timestamp
+                    .map(|timestamp: Timestamp| summary.timestamp.saturating_sub(timestamp)),
+            });
+            previous_timestamp = Some(summary.timestamp);
+        }
+        recent_blocks.reverse();
+
+        let mut status_peers: Vec<StatusPeer> = {
+            let (peers, _peers_) = lock_for_read!(peers, LOCK_ORDER_PEERS);
+            peers
+                .index_to_peers
+                .values()
+                .map(|peer| StatusPeer {
+                    index: peer.index,
+                    public_key: peer.public_key.map(hex::encode),
+                    address: peer.static_peer_config.as_ref().map(get_peer_address),
+                    latency: peer.get_latency(),
+                })
+                .collect()
+        };
+        status_peers.sort_by_key(|peer| peer.index);
+
+        let mempool_transactions = {
+            let (mempool, _mempool_) = lock_for_read!(mempool, LOCK_ORDER_MEMPOOL);
+            mempool.transactions.len()
+        };
+
+        StatusPage {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime,
+            latest_block_id: snapshot.latest_block_id,
+            latest_block_hash: hex::encode(snapshot.latest_block_hash),
+            mempool_transactions,
+            peers: status_peers,
+            recent_blocks,
+        }
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        writeln!(
+            html,
+            "<meta http-equiv=\"refresh\" content=\"{}\">",
+            STATUS_PAGE_REFRESH_IN_SECONDS
+        )
+        .unwrap();
+        html.push_str("<title>saito node status</title>\n");
+        html.push_str(
+            "<style>body{font-family:monospace}table{border-collapse:collapse}\
+            td,th{border:1px solid #999;padding:2px 8px;text-align:left}</style>\n",
+        );
+        html.push_str("</head>\n<body>\n<h1>saito node status</h1>\n<table>\n");
+        write_row(&mut html, "td", &["version", self.version.as_str()]);
+        write_row(
+            &mut html,
+            "td",
+            &["uptime", format_duration(self.uptime).as_str()],
+        );
+        write_row(
+            &mut html,
+            "td",
+            &["height", self.latest_block_id.to_string().as_str()],
+        );
+        write_row(
+            &mut html,
+            "td",
+            &["latest block", self.latest_block_hash.as_str()],
+        );
+        write_row(
+            &mut html,
+            "td",
+            &["mempool", self.mempool_transactions.to_string().as_str()],
+        );
+        html.push_str("</table>\n");
+
+        writeln!(html, "<h2>peers ({})</h2>\n<table>", self.peers.len()).unwrap();
+        write_row(
+            &mut html,
+            "th",
+            &["index", "address", "public key", "latency (ms)"],
+        );
+        for peer in self.peers.iter() {
+            write_row(
+                &mut html,
+                "td",
+                &[
+                    peer.index.to_string().as_str(),
+                    peer.address.as_deref().unwrap_or("inbound"),
+                    peer.public_key.as_deref().unwrap_or("handshake pending"),
+                    peer.latency
+                        .map(|latency| latency.to_string())
+                        .unwrap_or_else(|| "-".to_string())
+                        .as_str(),
+                ],
+            );
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>recent blocks</h2>\n<table>\n");
+        write_row(
+            &mut html,
+            "th",
+            &["id", "hash", "timestamp", "interval (s)"],
+        );
+        for block in self.recent_blocks.iter() {
+            write_row(
+                &mut html,
+                "td",
+                &[
+                    block.id.to_string().as_str(),
+                    block.hash.as_str(),
+                    block.timestamp.to_string().as_str(),
+                    block
+                        .interval
+                        .map(|interval| format!("{:.1}", interval as f64 / 1000.0))
+                        .unwrap_or_else(|| "-".to_string())
+                        .as_str(),
+                ],
+            );
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+fn get_peer_address(config: &PeerConfig) -> String {
+    format!("{}:{}", config.host, config.port)
+}
+
+fn write_row(html: &mut String, tag: &str, cells: &[&str]) {
+    html.push_str("<tr>");
+    for cell in cells {
+        write!(html, "<{}>{}</{}>", tag, escape_html(cell), tag).unwrap();
+    }
+    html.push_str("</tr>\n");
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// e.g. "2d 03:04:05"
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{}d {:02}:{:02}:{:02}",
+        seconds / 86400,
+        (seconds % 86400) / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::saito::status_page::{format_duration, StatusBlock, StatusPage, StatusPeer};
+
+    #[test]
+    fn status_page_html_test() {
+        assert_eq!(
+            format_duration(Duration::from_secs(2 * 86400 + 3 * 3600 + 4 * 60 + 5)),
+            "2d 03:04:05"
+        );
+
+        let page = StatusPage {
+            version: "0.1.0".to_string(),
+            uptime: Duration::from_secs(65),
+            latest_block_id: 12,
+            latest_block_hash: "ab".repeat(32),
+            mempool_transactions: 7,
+            peers: vec![
+                StatusPeer {
+                    index: 1,
+                    public_key: Some("02".repeat(33)),
+                    address: Some("<peer>:12101".to_string()),
+                    latency: Some(120),
+                },
+                StatusPeer {
+                    index: 2,
+                    public_key: None,
+                    address: None,
+                    latency: None,
+                },
+            ],
+            recent_blocks: vec![
+                StatusBlock {
+                    id: 12,
+                    hash: "ab".repeat(32),
+                    timestamp: 32_500,
+                    interval: Some(30_500),
+                },
+                StatusBlock {
+                    id: 11,
+                    hash: "cd".repeat(32),
+                    timestamp: 2_000,
+                    interval: None,
+                },
+            ],
+        };
+        let html = page.to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<tr><td>uptime</td><td>0d 00:01:05</td></tr>"));
+        assert!(html.contains("<tr><td>height</td><td>12</td></tr>"));
+        assert!(html.contains("<tr><td>mempool</td><td>7</td></tr>"));
+        assert!(html.contains("<h2>peers (2)</h2>"));
+        // addresses are escaped
+        assert!(html.contains("<td>&lt;peer&gt;:12101</td>"));
+        assert!(html.contains("<td>inbound</td><td>handshake pending</td><td>-</td>"));
+        assert!(html.contains(&format!(
+            "<tr><td>12</td><td>{}</td><td>32500</td><td>30.5</td></tr>",
+            "ab".repeat(32)
+        )));
+        assert!(html.ends_with("</html>\n"));
+    }
+}
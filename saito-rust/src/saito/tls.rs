@@ -0,0 +1,89 @@
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio_tungstenite::Connector;
+use tracing::{debug, info, warn};
+
+use saito_core::core::data::configuration::TlsConfig;
+
+/// Loads the PEM encoded certificates in the given file
+fn load_certificates(path: &str) -> Result<Vec<rustls::Certificate>, Error> {
+    let file = File::open(path)?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))?;
+    if certs.is_empty() {
+        warn!("no certificates found in : {:?}", path);
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+    Ok(certs.into_iter().map(rustls::Certificate).collect())
+}
+
+/// Creates the client config for outgoing wss connections. System root certificates are trusted along with the
+/// certificates in `ca_cert_path` if configured.
+pub fn create_client_config(tls: Option<&TlsConfig>) -> Result<Arc<rustls::ClientConfig>, Error> {
+    let mut root_store = rustls::RootCertStore::empty();
+    for cert in rustls_native_certs::load_native_certs()? {
+        if let Err(error) = root_store.add(&rustls::Certificate(cert.0)) {
+            debug!("ignoring invalid system certificate : {:?}", error);
+        }
+    }
+    if let Some(ca_cert_path) = tls.and_then(|tls| tls.ca_cert_path.as_ref()) {
+        info!("loading trusted certificates from : {:?}", ca_cert_path);
+        for cert in load_certificates(ca_cert_path)? {
+            root_store
+                .add(&cert)
+                .map_err(|_| Error::from(ErrorKind::InvalidData))?;
+        }
+    }
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+/// Returns the connector for the given websocket scheme. Plain connections are used for "ws"
+pub fn get_connector(scheme: &str, client_config: &Arc<rustls::ClientConfig>) -> Connector {
+    if scheme == "wss" {
+        Connector::Rustls(client_config.clone())
+    } else {
+        Connector::Plain
+    }
+}
+
+/// Checks the server certificate files before starting the server, since warp panics on invalid files
+pub fn validate_server_config(tls: &TlsConfig) -> Result<(), Error> {
+    for path in [tls.cert_path.as_str(), tls.key_path.as_str()] {
+        if !Path::new(path).exists() {
+            warn!("tls file : {:?} not found", path);
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+    }
+    load_certificates(tls.cert_path.as_str())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use saito_core::core::data::configuration::TlsConfig;
+
+    use crate::saito::tls::{create_client_config, validate_server_config};
+
+    #[test]
+    fn tls_config_validation_test() {
+        let tls = TlsConfig {
+            cert_path: "non_existing_cert.pem".to_string(),
+            key_path: "non_existing_key.pem".to_string(),
+            ca_cert_path: None,
+        };
+        assert!(validate_server_config(&tls).is_err());
+        assert!(create_client_config(None).is_ok());
+
+        let tls = TlsConfig {
+            ca_cert_path: Some("non_existing_ca.pem".to_string()),
+            ..tls
+        };
+        assert!(create_client_config(Some(&tls)).is_err());
+    }
+}
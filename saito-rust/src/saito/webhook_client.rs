@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+use saito_core::core::data::webhook::WebhookClient;
+
+/// Posts the webhook payloads with a shared http client. Each post runs in its own task, so a slow endpoint doesn't
+/// hold up the thread which sent the event
+#[derive(Debug, Clone, Default)]
+pub struct HttpWebhookClient {
+    client: reqwest::Client,
+}
+
+impl HttpWebhookClient {
+    pub fn new() -> HttpWebhookClient {
+        HttpWebhookClient {
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+impl WebhookClient for HttpWebhookClient {
+    fn post(&self, url: &str, payload: Vec<u8>, timeout_in_ms: u64) {
+        let request = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .timeout(Duration::from_millis(timeout_in_ms))
+            .body(payload);
+        let url = url.to_string();
+        tokio::spawn(async move {
+            match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    debug!("webhook : {:?} posted", url);
+                }
+                Ok(response) => {
+                    warn!(
+                        "webhook : {:?} responded with status : {:?}",
+                        url,
+                        response.status()
+                    );
+                }
+                Err(error) => {
+                    warn!("failed posting to webhook : {:?} : {:?}", url, error);
+                }
+            }
+        });
+    }
+}
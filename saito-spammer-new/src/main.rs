@@ -15,22 +15,34 @@ use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::Layer;
 
-use saito_core::common::command::NetworkEvent;
 use saito_core::common::defs::{
     push_lock, SaitoPrivateKey, SaitoPublicKey, StatVariable, LOCK_ORDER_CONFIGS,
     LOCK_ORDER_WALLET, STAT_BIN_COUNT,
 };
 use saito_core::common::keep_time::KeepTime;
+use saito_core::common::network_lanes::{
+    network_event_lanes, NetworkEventLaneReceiver, NetworkEventLaneSender,
+};
 use saito_core::common::process_event::ProcessEvent;
 use saito_core::core::consensus_thread::{ConsensusEvent, ConsensusStats, ConsensusThread};
+use saito_core::core::data::audit_log::AuditLog;
+use saito_core::core::data::block_announcement_cache::{
+    BlockAnnouncementCache, BLOCK_ANNOUNCEMENT_CACHE_SIZE, BLOCK_ANNOUNCEMENT_CACHE_TTL_IN_MS,
+};
 use saito_core::core::data::blockchain::Blockchain;
 use saito_core::core::data::blockchain_sync_state::BlockchainSyncState;
 use saito_core::core::data::configuration::Configuration;
 use saito_core::core::data::context::Context;
 use saito_core::core::data::network::Network;
 use saito_core::core::data::peer_collection::PeerCollection;
+use saito_core::core::data::peer_events::PeerEventListeners;
+use saito_core::core::data::peer_message_stats::PeerMessageStats;
+use saito_core::core::data::stale_chain_watchdog::StaleChainWatchdog;
 use saito_core::core::data::storage::Storage;
+use saito_core::core::data::trusted_sync::TrustedSync;
+use saito_core::core::data::tx_generator::TransactionGenerator;
 use saito_core::core::data::wallet::Wallet;
+use saito_core::core::data::webhook::Webhooks;
 use saito_core::core::mining_thread::{MiningEvent, MiningThread};
 use saito_core::core::routing_thread::{
     PeerState, RoutingEvent, RoutingStats, RoutingThread, StaticPeer,
@@ -54,7 +66,7 @@ const MINING_EVENT_PROCESSOR_ID: u8 = 3;
 
 async fn run_thread<T>(
     mut event_processor: Box<(dyn ProcessEvent<T> + Send + 'static)>,
-    mut network_event_receiver: Option<Receiver<NetworkEvent>>,
+    mut network_event_receiver: Option<NetworkEventLaneReceiver>,
     mut event_receiver: Option<Receiver<T>>,
     stat_timer_in_ms: u64,
     thread_sleep_time_in_ms: u64,
@@ -74,9 +86,9 @@ where
         loop {
             if network_event_receiver.is_some() {
                 // TODO : update to recv().await
+                // events in the priority lane are processed first
                 let result = network_event_receiver.as_mut().unwrap().try_recv();
-                if result.is_ok() {
-                    let event = result.unwrap();
+                if let Some(event) = result {
                     if event_processor.process_network_event(event).await.is_some() {
                         work_done = true;
                     }
@@ -141,11 +153,14 @@ async fn run_mining_event_processor(
         sender_to_mempool: sender_to_mempool.clone(),
         time_keeper: Box::new(TimeKeeper {}),
         miner_active: false,
+        mining_enabled: true,
         target: [0; 32],
         difficulty: 0,
         public_key: [0; 33],
         mined_golden_tickets: 0,
+        hash_count: 0,
         stat_sender: sender_to_stat.clone(),
+        mining_status: context.mining_status.clone(),
     };
     debug!("running miner thread");
     let miner_handle = run_thread(
@@ -170,15 +185,13 @@ async fn run_consensus_event_processor(
     thread_sleep_time_in_ms: u64,
     sender_to_stat: Sender<String>,
 ) -> JoinHandle<()> {
-    let result = std::env::var("GEN_TX");
-    let mut create_test_tx = false;
-    if result.is_ok() {
-        create_test_tx = result.unwrap().eq("1");
-    }
     let generate_genesis_block: bool;
+    let tx_generator_config;
     {
         let (configs, _configs_) = lock_for_read!(context.configuration, LOCK_ORDER_CONFIGS);
 
+        tx_generator_config = configs.get_server_configs().tx_generator.clone();
+
         // if we have peers defined in configs, there's already an existing network. so we don't need to generate the first block.
         generate_genesis_block = configs.get_peer_configs().is_empty();
     }
@@ -187,8 +200,12 @@ async fn run_consensus_event_processor(
         blockchain: context.blockchain.clone(),
         wallet: context.wallet.clone(),
         generate_genesis_block,
+        issuance: None,
+        dev_mode: false,
+        submit_golden_tickets: false,
         sender_to_router: sender_to_routing.clone(),
         sender_to_miner: sender_to_miner.clone(),
+        sender_to_wallet_updater: None,
         // sender_global: global_sender.clone(),
         time_keeper: Box::new(TimeKeeper {}),
         network: Network::new(
@@ -199,9 +216,9 @@ async fn run_consensus_event_processor(
             peers.clone(),
             context.wallet.clone(),
         ),
-        block_producing_timer: 0,
-        tx_producing_timer: 0,
-        create_test_tx,
+        next_block_production_time: None,
+        wallet_check_timer: 0,
+        tx_generator: TransactionGenerator::new(tx_generator_config),
         storage: Storage::new(Box::new(RustIOHandler::new(
             sender_to_network_controller.clone(),
             CONSENSUS_EVENT_PROCESSOR_ID,
@@ -209,6 +226,13 @@ async fn run_consensus_event_processor(
         stats: ConsensusStats::new(sender_to_stat.clone()),
         txs_for_mempool: vec![],
         stat_sender: sender_to_stat.clone(),
+        audit_log: AuditLog::new(Default::default()),
+        webhooks: Webhooks::new(Default::default(), None),
+        connection_status: context.connection_status.clone(),
+        block_production_quorum: Default::default(),
+        transaction_receipts: context.transaction_receipts.clone(),
+        block_queue_status: context.block_queue_status.clone(),
+        blockchain_snapshot: context.blockchain_snapshot.clone(),
     };
 
     debug!("running mempool thread");
@@ -226,6 +250,7 @@ async fn run_consensus_event_processor(
 
 async fn run_verification_threads(
     sender_to_consensus: Sender<ConsensusEvent>,
+    sender_to_routing: Sender<RoutingEvent>,
     blockchain: Arc<RwLock<Blockchain>>,
     peers: Arc<RwLock<PeerCollection>>,
     wallet: Arc<RwLock<Wallet>>,
@@ -242,6 +267,7 @@ async fn run_verification_threads(
         senders.push(sender);
         let verification_thread = VerificationThread {
             sender_to_consensus: sender_to_consensus.clone(),
+            sender_to_router: sender_to_routing.clone(),
             blockchain: blockchain.clone(),
             peers: peers.clone(),
             wallet: wallet.clone(),
@@ -297,7 +323,7 @@ async fn run_routing_event_processor(
     channel_size: usize,
     sender_to_stat: Sender<String>,
     fetch_batch_size: usize,
-) -> (Sender<NetworkEvent>, JoinHandle<()>) {
+) -> (NetworkEventLaneSender, JoinHandle<()>) {
     let mut routing_event_processor = RoutingThread {
         blockchain: context.blockchain.clone(),
         sender_to_consensus: sender_to_mempool.clone(),
@@ -315,12 +341,29 @@ async fn run_routing_event_processor(
             context.wallet.clone(),
         ),
         reconnection_timer: 0,
+        fetch_timeout_timer: 0,
         stats: RoutingStats::new(sender_to_stat.clone()),
         public_key: [0; 33],
         senders_to_verification: senders,
         last_verification_thread_index: 0,
         stat_sender: sender_to_stat.clone(),
         blockchain_sync_state: BlockchainSyncState::new(fetch_batch_size),
+        stale_chain_watchdog: StaleChainWatchdog::new(0),
+        webhooks: Webhooks::new(Default::default(), None),
+        reconnect_on_stale_chain: false,
+        block_announcement_cache: BlockAnnouncementCache::new(
+            BLOCK_ANNOUNCEMENT_CACHE_TTL_IN_MS,
+            BLOCK_ANNOUNCEMENT_CACHE_SIZE,
+        ),
+        trusted_sync: TrustedSync::new(),
+        peer_message_stats: PeerMessageStats::new(),
+        storage: Storage::new(Box::new(RustIOHandler::new(
+            sender_to_io_controller.clone(),
+            ROUTING_EVENT_PROCESSOR_ID,
+        ))),
+        connection_status: context.connection_status.clone(),
+        block_queue_status: context.block_queue_status.clone(),
+        peer_events: PeerEventListeners::new(),
     };
     {
         let (configs, _configs_) = lock_for_read!(configs, LOCK_ORDER_CONFIGS);
@@ -336,7 +379,7 @@ async fn run_routing_event_processor(
     }
 
     let (interface_sender_to_routing, interface_receiver_for_routing) =
-        tokio::sync::mpsc::channel::<NetworkEvent>(channel_size);
+        network_event_lanes(channel_size, channel_size);
 
     debug!("running blockchain thread");
     let routing_handle = run_thread(
@@ -354,7 +397,7 @@ async fn run_routing_event_processor(
 // TODO : to be moved to routing event processor
 fn run_loop_thread(
     mut receiver: Receiver<IoEvent>,
-    network_event_sender_to_routing_ep: Sender<NetworkEvent>,
+    mut network_event_sender_to_routing_ep: NetworkEventLaneSender,
     thread_sleep_time_in_ms: u64,
 ) -> JoinHandle<()> {
     let loop_handle = tokio::spawn(async move {
@@ -526,6 +569,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let (senders, verification_handles) = run_verification_threads(
         sender_to_consensus.clone(),
+        sender_to_routing.clone(),
         context.blockchain.clone(),
         peers.clone(),
         context.wallet.clone(),
@@ -5,7 +5,12 @@ use figment::Figment;
 use serde::Deserialize;
 use tracing::{debug, error};
 
-use saito_core::core::data::configuration::{Configuration, Endpoint, PeerConfig, Server};
+use saito_core::core::data::configuration::{
+    Configuration, DifficultyConfig, Endpoint, NodeRole, PeerConfig, Server, DEFAULT_NETWORK_ID,
+};
+
+use crate::saito::fan_out::FanOutConfig;
+use crate::saito::scenario::TrafficProfile;
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Spammer {
@@ -16,6 +21,12 @@ pub struct Spammer {
     pub tx_payment: u64,
     pub tx_fee: u64,
     pub stop_after: u64,
+    /// if provided, txs are generated and sent according to this profile instead of constant bursts
+    #[serde(default)]
+    pub profile: Option<TrafficProfile>,
+    /// if provided, funds are distributed to child wallets and txs are payments between them
+    #[serde(default)]
+    pub fan_out: Option<FanOutConfig>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -37,11 +48,40 @@ impl SpammerConfigs {
                     port: 0,
                     protocol: "http".to_string(),
                 },
+                network: None,
+                network_id: DEFAULT_NETWORK_ID.to_string(),
                 verification_threads: 4,
+                verification: Default::default(),
                 channel_size: 0,
                 stat_timer_in_ms: 0,
                 thread_sleep_time_in_ms: 10,
                 block_fetch_batch_size: 0,
+                block_fetch_limit_per_peer: 0,
+                block_queue_limit: 0,
+                node_role: NodeRole::Miner,
+                full_block_cache_size: 0,
+                difficulty: DifficultyConfig::Consensus,
+                stale_chain_threshold_in_ms: 0,
+                reconnect_on_stale_chain: false,
+                tx_generator: Default::default(),
+                transaction_index: false,
+                tls: None,
+                peer_slots: Default::default(),
+                archive_dir: None,
+                dev_mode: false,
+                max_block_future_drift_in_ms: 0,
+                trusted_sync: Default::default(),
+                max_orphan_blocks: 0,
+                orphan_block_expiry_in_ms: 0,
+                max_block_size: 0,
+                message_compression: false,
+                dns_seeds: Default::default(),
+                consensus_health: Default::default(),
+                audit_log: Default::default(),
+                webhooks: Default::default(),
+                block_production_quorum: Default::default(),
+                key_rotation_proof: None,
+                issuance_file: None,
             },
             peers: vec![],
             spammer: Spammer {
@@ -52,6 +92,8 @@ impl SpammerConfigs {
                 tx_payment: 0,
                 tx_fee: 0,
                 stop_after: 0,
+                profile: None,
+                fan_out: None,
             },
         }
     }
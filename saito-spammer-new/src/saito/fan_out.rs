@@ -0,0 +1,245 @@
+use ahash::AHashMap;
+use rand::Rng;
+use serde::Deserialize;
+use tracing::debug;
+
+use saito_core::common::defs::{Currency, SaitoPrivateKey, SaitoPublicKey};
+use saito_core::common::keep_time::KeepTime;
+use saito_core::core::data::block::BlockType;
+use saito_core::core::data::blockchain::Blockchain;
+use saito_core::core::data::crypto::{generate_keypair_from_private_key, hash};
+use saito_core::core::data::slip::Slip;
+use saito_core::core::data::transaction::Transaction;
+use saito_core::core::data::wallet::Wallet;
+
+use crate::saito::time_keeper::TimeKeeper;
+
+/// max number of child wallets funded by a single distribution transaction
+const DISTRIBUTION_OUTPUTS_PER_TX: usize = 100;
+
+/// Payments between child wallets instead of sending everything from the spammer's wallet
+#[derive(Deserialize, Debug, Clone)]
+pub struct FanOutConfig {
+    /// number of child wallets derived from the spammer's key
+    pub wallet_count: u32,
+    /// max number of slips spent by each payment
+    pub fan_in: u32,
+    /// number of child wallets paid by each payment
+    pub fan_out: u32,
+}
+
+struct ChildWallet {
+    public_key: SaitoPublicKey,
+    private_key: SaitoPrivateKey,
+    /// unspent slips of the wallet found in the longest chain
+    slips: Vec<Slip>,
+}
+
+/// Wallets derived from the spammer's key. Slips are tracked by scanning the longest chain blocks, and spent slips
+/// are removed when the payment is created. Reorgs are not tracked since the spammer only needs valid payments most of
+/// the time, and payments spending slips from a dropped fork are rejected by the node.
+pub struct ChildWallets {
+    config: FanOutConfig,
+    wallets: Vec<ChildWallet>,
+    wallet_indices: AHashMap<SaitoPublicKey, usize>,
+    last_scanned_block_id: u64,
+    next_sender_index: usize,
+    time_keeper: TimeKeeper,
+}
+
+/// Derives the key of a child wallet from the parent's private key, so the same child wallets are used after restarts
+pub fn derive_child_keys(
+    parent_private_key: &SaitoPrivateKey,
+    index: u32,
+) -> (SaitoPublicKey, SaitoPrivateKey) {
+    let seed = hash(
+        &[
+            parent_private_key.as_slice(),
+            index.to_be_bytes().as_slice(),
+        ]
+        .concat(),
+    );
+    generate_keypair_from_private_key(&seed)
+}
+
+impl ChildWallets {
+    pub fn new(parent_private_key: &SaitoPrivateKey, config: FanOutConfig) -> ChildWallets {
+        assert!(
+            config.wallet_count > 1,
+            "fan out needs at least 2 child wallets"
+        );
+        assert!(
+            config.fan_in > 0 && config.fan_out > 0,
+            "fan in and fan out should be positive"
+        );
+        let wallets: Vec<ChildWallet> = (0..config.wallet_count)
+            .map(|index| {
+                let (public_key, private_key) = derive_child_keys(parent_private_key, index);
+                ChildWallet {
+                    public_key,
+                    private_key,
+                    slips: vec![],
+                }
+            })
+            .collect();
+        let wallet_indices = wallets
+            .iter()
+            .enumerate()
+            .map(|(index, wallet)| (wallet.public_key, index))
+            .collect();
+        ChildWallets {
+            config,
+            wallets,
+            wallet_indices,
+            last_scanned_block_id: 0,
+            next_sender_index: 0,
+            time_keeper: TimeKeeper {},
+        }
+    }
+
+    pub fn get_slip_count(&self) -> usize {
+        self.wallets.iter().map(|wallet| wallet.slips.len()).sum()
+    }
+
+    /// Creates the transactions paying `amount_per_wallet` to each child wallet from the spammer's wallet. Blocks
+    /// before the current latest block are not scanned for child slips afterwards.
+    pub fn create_distribution_transactions(
+        &mut self,
+        wallet: &mut Wallet,
+        blockchain: &Blockchain,
+        amount_per_wallet: Currency,
+        fee: Currency,
+    ) -> Vec<Transaction> {
+        self.last_scanned_block_id = blockchain.get_latest_block_id();
+        let public_keys: Vec<SaitoPublicKey> = self
+            .wallets
+            .iter()
+            .map(|wallet| wallet.public_key)
+            .collect();
+        public_keys
+            .chunks(DISTRIBUTION_OUTPUTS_PER_TX)
+            .map(|receivers| {
+                let mut transaction = Transaction::default();
+                let (inputs, change) =
+                    wallet.generate_slips(amount_per_wallet * receivers.len() as Currency + fee);
+                for slip in inputs {
+                    transaction.add_input(slip);
+                }
+                for public_key in receivers {
+                    let mut output = Slip::default();
+                    output.public_key = *public_key;
+                    output.amount = amount_per_wallet;
+                    transaction.add_output(output);
+                }
+                for slip in change {
+                    transaction.add_output(slip);
+                }
+                transaction.timestamp = self.time_keeper.get_timestamp_in_ms();
+                transaction.generate(&wallet.public_key, 0, 0);
+                transaction.sign(&wallet.private_key);
+                transaction
+            })
+            .collect()
+    }
+
+    /// Adds the child wallet outputs of the longest chain blocks added since the last scan
+    pub fn scan_blocks(&mut self, blockchain: &Blockchain) {
+        let latest_block_id = blockchain.get_latest_block_id();
+        for block in blockchain.iter_longest_chain(self.last_scanned_block_id + 1) {
+            if block.block_type != BlockType::Full {
+                debug!(
+                    "block : {:?} is pruned. skipping it for child wallet slips",
+                    block.id
+                );
+                continue;
+            }
+            for transaction in block.transactions.iter() {
+                for output in transaction.outputs.iter() {
+                    if output.amount == 0 {
+                        continue;
+                    }
+                    if let Some(index) = self.wallet_indices.get(&output.public_key) {
+                        self.wallets[*index].slips.push(output.clone());
+                    }
+                }
+            }
+        }
+        self.last_scanned_block_id = latest_block_id;
+    }
+
+    /// Creates up to `max_count` payments between the child wallets. Each payment spends up to `fan_in` slips of a
+    /// wallet and splits them among `fan_out` randomly picked other wallets.
+    pub fn create_payments(
+        &mut self,
+        max_count: u64,
+        fee: Currency,
+        tx_size: u64,
+    ) -> Vec<Transaction> {
+        let mut transactions = vec![];
+        let wallet_count = self.wallets.len();
+        let mut checked_wallets = 0;
+        while (transactions.len() as u64) < max_count && checked_wallets < wallet_count {
+            let sender_index = self.next_sender_index;
+            self.next_sender_index = (self.next_sender_index + 1) % wallet_count;
+            checked_wallets += 1;
+
+            if let Some(transaction) = self.create_payment(sender_index, fee, tx_size) {
+                transactions.push(transaction);
+                checked_wallets = 0;
+            }
+        }
+        transactions
+    }
+
+    fn create_payment(
+        &mut self,
+        sender_index: usize,
+        fee: Currency,
+        tx_size: u64,
+    ) -> Option<Transaction> {
+        let fan_in = self.config.fan_in as usize;
+        let fan_out = self.config.fan_out as usize;
+        let sender = &mut self.wallets[sender_index];
+        if sender.slips.is_empty() {
+            return None;
+        }
+        let input_count = fan_in.min(sender.slips.len());
+        let total: Currency = sender.slips[..input_count]
+            .iter()
+            .map(|slip| slip.amount)
+            .sum();
+        if total <= fee + fan_out as Currency {
+            return None;
+        }
+        let inputs: Vec<Slip> = sender.slips.drain(..input_count).collect();
+        let public_key = sender.public_key;
+        let private_key = sender.private_key;
+
+        let mut transaction = Transaction::default();
+        for input in inputs {
+            transaction.add_input(input);
+        }
+        let amount = (total - fee) / fan_out as Currency;
+        let remainder = (total - fee) % fan_out as Currency;
+        let mut rng = rand::thread_rng();
+        for index in 0..fan_out {
+            let mut receiver_index = rng.gen_range(0..self.wallets.len() - 1);
+            if receiver_index >= sender_index {
+                receiver_index += 1;
+            }
+            let mut output = Slip::default();
+            output.public_key = self.wallets[receiver_index].public_key;
+            output.amount = if index == 0 {
+                amount + remainder
+            } else {
+                amount
+            };
+            transaction.add_output(output);
+        }
+        transaction.message = vec![0; tx_size as usize];
+        transaction.timestamp = self.time_keeper.get_timestamp_in_ms();
+        transaction.generate(&public_key, 0, 0);
+        transaction.sign(&private_key);
+        Some(transaction)
+    }
+}
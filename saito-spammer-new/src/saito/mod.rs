@@ -1,10 +1,12 @@
 pub mod config_handler;
+pub mod fan_out;
 mod io_context;
 pub mod io_event;
 mod io_future;
 pub mod network_controller;
 pub mod rust_io_handler;
 mod rust_task_runner;
+pub mod scenario;
 pub mod spammer;
 pub mod stat_thread;
 pub mod time_keeper;
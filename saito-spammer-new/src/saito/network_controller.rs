@@ -16,12 +16,13 @@ use warp::ws::WebSocket;
 use warp::Filter;
 
 use saito_core::common::defs::{
-    push_lock, SaitoHash, StatVariable, LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_CONFIGS,
+    push_lock, SaitoHash, SaitoSignature, StatVariable, LOCK_ORDER_BLOCKCHAIN, LOCK_ORDER_CONFIGS,
     LOCK_ORDER_NETWORK_CONTROLLER, STAT_BIN_COUNT,
 };
 use saito_core::common::keep_time::KeepTime;
 use saito_core::core::data;
 use saito_core::core::data::block::BlockType;
+use saito_core::core::data::block_fetch::BLOCK_FETCH_SIGNATURE_HEADER;
 use saito_core::core::data::blockchain::Blockchain;
 use saito_core::core::data::configuration::{Configuration, PeerConfig};
 use saito_core::lock_for_read;
@@ -117,11 +118,7 @@ impl NetworkController {
     ) {
         // TODO : handle connecting to an already connected (via incoming connection) node.
 
-        let mut protocol: String = String::from("ws");
-        if peer.protocol == "https" {
-            protocol = String::from("wss");
-        }
-        let url = protocol
+        let url = peer.get_websocket_scheme().to_string()
             + "://"
             + peer.host.as_str()
             + ":"
@@ -171,7 +168,7 @@ impl NetworkController {
         buffer: Vec<u8>,
         exceptions: Vec<u64>,
     ) {
-        trace!("sending message : {:?} to all", buffer[0]);
+        trace!("sending message : {:?} to all", buffer[4]);
         let mut sockets = sockets.lock().await;
         let mut peers_with_errors: Vec<u64> = Default::default();
 
@@ -218,6 +215,12 @@ impl NetworkController {
             todo!()
         }
         let response = result.unwrap();
+        let signature: Option<SaitoSignature> = response
+            .headers()
+            .get(BLOCK_FETCH_SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| hex::decode(value).ok())
+            .and_then(|value| value.try_into().ok());
         let result = response.bytes().await;
         if result.is_err() {
             todo!()
@@ -240,9 +243,11 @@ impl NetworkController {
                 event_processor_id: 1,
                 event_id,
                 event: NetworkEvent::BlockFetched {
+                    request_id: event_id,
                     block_hash,
                     peer_index,
                     buffer,
+                    signature,
                 },
             })
             .await
@@ -340,7 +345,8 @@ impl NetworkController {
                         };
                         sender.send(message).await.expect("sending failed");
                     } else {
-                        todo!()
+                        // pings from the node are answered by the websocket library
+                        trace!("ignoring message from peer : {:?}", peer_index);
                     }
                 },
                 PeerReceiver::Tungstenite(mut receiver) => loop {
@@ -366,8 +372,8 @@ impl NetworkController {
                             sender.send(message).await.expect("sending failed");
                         }
                         _ => {
-                            // Not handling these scenarios
-                            todo!()
+                            // pings from the node are answered by the websocket library
+                            trace!("ignoring message from peer : {:?}", peer_index);
                         }
                     }
                 },
@@ -515,6 +521,12 @@ pub async fn run_network_controller(
                     NetworkEvent::BlockFetched { .. } => {
                         unreachable!()
                     }
+                    NetworkEvent::PeerRequestCompleted { .. } => {
+                        unreachable!()
+                    }
+                    NetworkEvent::PeerRequestTimedOut { .. } => {
+                        unreachable!()
+                    }
                 }
             }
             #[cfg(feature = "with-stats")]
@@ -1,18 +1,18 @@
 use std::fmt::{Debug, Formatter};
 use std::fs;
-use std::io::Error;
+use std::io::{Error, SeekFrom};
 use std::path::Path;
 use std::sync::Mutex;
 
 use async_trait::async_trait;
 use lazy_static::lazy_static;
-use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 use tokio::sync::mpsc::Sender;
 use tracing::{debug, warn};
 
 use saito_core::common::command::NetworkEvent;
-use saito_core::common::defs::{SaitoHash, BLOCK_FILE_EXTENSION};
+use saito_core::common::defs::{SaitoHash, BLOCK_FILE_EXTENSION, TEMP_FILE_EXTENSION};
 use saito_core::common::interface_io::InterfaceIO;
 
 use saito_core::core::data::configuration::PeerConfig;
@@ -79,6 +79,15 @@ impl Debug for RustIOHandler {
     }
 }
 
+/// Makes the rename of a file durable. Not supported on all platforms, so failures are ignored
+async fn sync_parent_dir(path: &Path) {
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = File::open(parent).await {
+            let _ = dir.sync_all().await;
+        }
+    }
+}
+
 #[async_trait]
 impl InterfaceIO for RustIOHandler {
     async fn send_message(&self, peer_index: u64, buffer: Vec<u8>) -> Result<(), Error> {
@@ -153,15 +162,14 @@ impl InterfaceIO for RustIOHandler {
                 .await
                 .expect("creating directory structure failed");
         }
-        let result = File::create(filename).await;
-        if result.is_err() {
-            return Err(result.err().unwrap());
-        }
-        let mut file = result.unwrap();
-        let result = file.write_all(&value).await;
-        if result.is_err() {
-            return Err(result.err().unwrap());
-        }
+        // written to a temporary file first, so a crash doesn't leave a partially written file with the final name
+        let temp_filename = key.clone() + TEMP_FILE_EXTENSION;
+        let mut file = File::create(&temp_filename).await?;
+        file.write_all(&value).await?;
+        file.sync_all().await?;
+        drop(file);
+        tokio::fs::rename(&temp_filename, path).await?;
+        sync_parent_dir(path).await;
 
         Ok(())
     }
@@ -182,6 +190,66 @@ impl InterfaceIO for RustIOHandler {
         Ok(encoded)
     }
 
+    async fn read_value_chunk(
+        &self,
+        key: String,
+        offset: u64,
+        length: u64,
+    ) -> Result<Vec<u8>, Error> {
+        let mut file = File::open(key).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+        let mut buffer = vec![];
+        file.take(length).read_to_end(&mut buffer).await?;
+        Ok(buffer)
+    }
+
+    async fn append_value(&mut self, key: String, chunk: Vec<u8>) -> Result<(), Error> {
+        let path = Path::new(key.as_str());
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(&chunk).await
+    }
+
+    async fn rename_value(&mut self, from: String, to: String) -> Result<(), Error> {
+        File::open(&from).await?.sync_all().await?;
+        tokio::fs::rename(&from, &to).await?;
+        sync_parent_dir(Path::new(to.as_str())).await;
+        Ok(())
+    }
+
+    async fn remove_temp_files(&mut self) -> Result<usize, Error> {
+        let mut count = 0;
+        let mut entries = match tokio::fs::read_dir(self.get_block_dir()).await {
+            Ok(entries) => entries,
+            Err(_) => return Ok(0),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with(TEMP_FILE_EXTENSION)
+            {
+                warn!(
+                    "removing temporary file : {:?} left by an interrupted write",
+                    entry.path()
+                );
+                tokio::fs::remove_file(entry.path()).await?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    async fn get_value_size(&self, key: String) -> Result<u64, Error> {
+        Ok(tokio::fs::metadata(key).await?.len())
+    }
+
     #[tracing::instrument(level = "info", skip_all)]
     async fn load_block_file_list(&self) -> Result<Vec<String>, Error> {
         debug!(
@@ -200,7 +268,7 @@ impl InterfaceIO for RustIOHandler {
                 r.file_name()
                     .into_string()
                     .unwrap()
-                    .contains(BLOCK_FILE_EXTENSION)
+                    .ends_with(BLOCK_FILE_EXTENSION)
             })
             .collect();
         paths.sort_by(|a, b| {
@@ -0,0 +1,283 @@
+use rand::Rng;
+use serde::Deserialize;
+
+use saito_core::common::defs::{Currency, SaitoPublicKey};
+use saito_core::core::data::crypto::generate_keys;
+
+/// A single step of the traffic profile. Phases are executed in the given order.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TrafficPhase {
+    /// linearly changes the tx rate from `from_tx_per_sec` to `to_tx_per_sec` within the duration
+    Ramp {
+        duration_in_ms: u64,
+        from_tx_per_sec: u64,
+        to_tx_per_sec: u64,
+    },
+    /// sends `tx_count` transactions at once and waits till the duration is over
+    Burst { duration_in_ms: u64, tx_count: u64 },
+    /// keeps a constant tx rate within the duration
+    Sustained {
+        duration_in_ms: u64,
+        tx_per_sec: u64,
+    },
+}
+
+impl TrafficPhase {
+    pub fn get_duration(&self) -> u64 {
+        match self {
+            TrafficPhase::Ramp { duration_in_ms, .. } => *duration_in_ms,
+            TrafficPhase::Burst { duration_in_ms, .. } => *duration_in_ms,
+            TrafficPhase::Sustained { duration_in_ms, .. } => *duration_in_ms,
+        }
+    }
+}
+
+/// Inclusive range to pick a value uniformly
+#[derive(Deserialize, Debug, Clone)]
+pub struct ValueRange {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl ValueRange {
+    pub fn sample(&self) -> u64 {
+        if self.min >= self.max {
+            return self.min;
+        }
+        rand::thread_rng().gen_range(self.min..=self.max)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TrafficProfile {
+    pub phases: Vec<TrafficPhase>,
+    /// restarts from the first phase after the last one is completed
+    #[serde(default)]
+    pub repeat: bool,
+    pub tx_size: ValueRange,
+    pub tx_fee: ValueRange,
+    /// number of distinct addresses receiving the payments
+    pub receiver_count: u32,
+}
+
+/// Picks the parameters of each generated transaction according to the traffic profile
+pub struct TxParameters {
+    tx_size: ValueRange,
+    tx_fee: ValueRange,
+    receivers: Vec<SaitoPublicKey>,
+    next_receiver_index: usize,
+}
+
+impl TxParameters {
+    pub fn new(profile: &TrafficProfile) -> TxParameters {
+        let receivers = (0..profile.receiver_count.max(1))
+            .map(|_| generate_keys().0)
+            .collect();
+        TxParameters {
+            tx_size: profile.tx_size.clone(),
+            tx_fee: profile.tx_fee.clone(),
+            receivers,
+            next_receiver_index: 0,
+        }
+    }
+
+    pub fn get_next_receiver(&mut self) -> SaitoPublicKey {
+        let receiver = self.receivers[self.next_receiver_index];
+        self.next_receiver_index = (self.next_receiver_index + 1) % self.receivers.len();
+        receiver
+    }
+
+    pub fn get_tx_size(&self) -> u64 {
+        self.tx_size.sample()
+    }
+
+    pub fn get_tx_fee(&self) -> Currency {
+        self.tx_fee.sample() as Currency
+    }
+
+    pub fn get_max_tx_fee(&self) -> Currency {
+        self.tx_fee.max.max(self.tx_fee.min) as Currency
+    }
+}
+
+/// Decides how many transactions to send over time according to the traffic profile phases
+pub struct TrafficSchedule {
+    phases: Vec<TrafficPhase>,
+    repeat: bool,
+    /// txs of the current phase which are already released for sending
+    released_tx_count: u64,
+    current_phase_index: usize,
+    current_phase_start: u64,
+}
+
+impl TrafficSchedule {
+    pub fn new(profile: &TrafficProfile) -> TrafficSchedule {
+        assert!(!profile.phases.is_empty(), "traffic profile has no phases");
+        if profile.repeat {
+            assert!(
+                profile.phases.iter().any(|phase| phase.get_duration() > 0),
+                "repeating traffic profile should have a phase with a duration"
+            );
+        }
+        TrafficSchedule {
+            phases: profile.phases.clone(),
+            repeat: profile.repeat,
+            released_tx_count: 0,
+            current_phase_index: 0,
+            current_phase_start: 0,
+        }
+    }
+
+    pub fn get_current_phase(&self) -> Option<&TrafficPhase> {
+        self.phases.get(self.current_phase_index)
+    }
+
+    /// total number of txs expected to be sent in the phase after `elapsed` ms from the phase start
+    fn get_expected_tx_count(phase: &TrafficPhase, elapsed: u64) -> u64 {
+        let elapsed = elapsed.min(phase.get_duration());
+        match phase {
+            TrafficPhase::Ramp {
+                duration_in_ms,
+                from_tx_per_sec,
+                to_tx_per_sec,
+            } => {
+                if *duration_in_ms == 0 {
+                    return 0;
+                }
+                // area under the rate line from 0 to elapsed
+                let from = *from_tx_per_sec as f64;
+                let to = *to_tx_per_sec as f64;
+                let elapsed = elapsed as f64;
+                let current_rate = from + (to - from) * elapsed / *duration_in_ms as f64;
+                ((from + current_rate) / 2.0 * elapsed / 1000.0) as u64
+            }
+            TrafficPhase::Burst { tx_count, .. } => *tx_count,
+            TrafficPhase::Sustained { tx_per_sec, .. } => tx_per_sec * elapsed / 1000,
+        }
+    }
+
+    /// Returns the number of txs which should be sent now. None if all the phases are completed.
+    pub fn get_txs_to_send(&mut self, elapsed_since_start: u64) -> Option<u64> {
+        let mut count = 0;
+        loop {
+            let phase = self.phases.get(self.current_phase_index);
+            if phase.is_none() {
+                if count > 0 {
+                    return Some(count);
+                }
+                return None;
+            }
+            let phase = phase.unwrap();
+            let elapsed_in_phase = elapsed_since_start - self.current_phase_start;
+            let expected = Self::get_expected_tx_count(phase, elapsed_in_phase);
+            count += expected.saturating_sub(self.released_tx_count);
+            self.released_tx_count = expected;
+
+            if elapsed_in_phase < phase.get_duration() {
+                return Some(count);
+            }
+
+            // current phase is over. continue with the next phase
+            self.current_phase_start += phase.get_duration();
+            self.released_tx_count = 0;
+            self.current_phase_index += 1;
+            if self.current_phase_index == self.phases.len() && self.repeat {
+                self.current_phase_index = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::saito::scenario::{TrafficPhase, TrafficProfile, TrafficSchedule, ValueRange};
+
+    fn create_profile(phases: Vec<TrafficPhase>, repeat: bool) -> TrafficProfile {
+        TrafficProfile {
+            phases,
+            repeat,
+            tx_size: ValueRange { min: 10, max: 10 },
+            tx_fee: ValueRange { min: 0, max: 0 },
+            receiver_count: 1,
+        }
+    }
+
+    fn create_phases() -> Vec<TrafficPhase> {
+        vec![
+            TrafficPhase::Sustained {
+                duration_in_ms: 1000,
+                tx_per_sec: 10,
+            },
+            TrafficPhase::Burst {
+                duration_in_ms: 500,
+                tx_count: 50,
+            },
+            TrafficPhase::Ramp {
+                duration_in_ms: 1000,
+                from_tx_per_sec: 0,
+                to_tx_per_sec: 20,
+            },
+        ]
+    }
+
+    #[test]
+    fn traffic_schedule_phase_boundaries_test() {
+        let mut schedule = TrafficSchedule::new(&create_profile(create_phases(), false));
+
+        assert_eq!(schedule.get_txs_to_send(0), Some(0));
+        assert_eq!(schedule.get_txs_to_send(500), Some(5));
+        assert_eq!(schedule.get_txs_to_send(999), Some(4));
+        // the rest of the sustained phase and the whole burst are released at the boundary
+        assert_eq!(schedule.get_txs_to_send(1000), Some(51));
+        assert!(matches!(
+            schedule.get_current_phase(),
+            Some(TrafficPhase::Burst { .. })
+        ));
+        assert_eq!(schedule.get_txs_to_send(1200), Some(0));
+        assert_eq!(schedule.get_txs_to_send(1500), Some(0));
+        assert!(matches!(
+            schedule.get_current_phase(),
+            Some(TrafficPhase::Ramp { .. })
+        ));
+        // half way through the ramp the rate is 10 tx/s, so 2.5 txs are sent
+        assert_eq!(schedule.get_txs_to_send(2000), Some(2));
+        assert_eq!(schedule.get_txs_to_send(2500), Some(8));
+    }
+
+    #[test]
+    fn traffic_schedule_end_test() {
+        let mut schedule = TrafficSchedule::new(&create_profile(create_phases(), false));
+        // the txs of all the skipped phases are sent when the schedule falls behind
+        assert_eq!(schedule.get_txs_to_send(10_000), Some(70));
+        assert!(schedule.get_current_phase().is_none());
+        assert_eq!(schedule.get_txs_to_send(10_001), None);
+
+        // zero length phases are completed without waiting
+        let mut schedule = TrafficSchedule::new(&create_profile(
+            vec![TrafficPhase::Burst {
+                duration_in_ms: 0,
+                tx_count: 5,
+            }],
+            false,
+        ));
+        assert_eq!(schedule.get_txs_to_send(0), Some(5));
+        assert_eq!(schedule.get_txs_to_send(0), None);
+
+        let mut schedule = TrafficSchedule::new(&create_profile(
+            vec![TrafficPhase::Sustained {
+                duration_in_ms: 1000,
+                tx_per_sec: 10,
+            }],
+            true,
+        ));
+        assert_eq!(schedule.get_txs_to_send(2500), Some(25));
+        assert!(schedule.get_current_phase().is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "traffic profile has no phases")]
+    fn traffic_schedule_without_phases_test() {
+        TrafficSchedule::new(&create_profile(vec![], false));
+    }
+}
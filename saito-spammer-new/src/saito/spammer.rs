@@ -1,6 +1,7 @@
+use std::cmp::min;
 use std::collections::VecDeque;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::RwLock;
@@ -17,9 +18,13 @@ use saito_core::core::data::wallet::Wallet;
 use saito_core::lock_for_read;
 
 use crate::saito::config_handler::SpammerConfigs;
+use crate::saito::scenario::TrafficSchedule;
 use crate::saito::transaction_generator::{GeneratorState, TransactionGenerator};
 use crate::IoEvent;
 
+const SCENARIO_TICK_IN_MS: u64 = 10;
+const SCENARIO_REPORT_INTERVAL_IN_MS: u64 = 5_000;
+
 pub struct Spammer {
     sender_to_network: Sender<IoEvent>,
     peers: Arc<RwLock<PeerCollection>>,
@@ -36,6 +41,7 @@ impl Spammer {
         blockchain: Arc<RwLock<Blockchain>>,
         sender_to_network: Sender<IoEvent>,
         sender: Sender<VecDeque<Transaction>>,
+        slip_sender: Sender<VecDeque<Transaction>>,
         configs: Arc<RwLock<Box<SpammerConfigs>>>,
     ) -> Spammer {
         let tx_payment;
@@ -57,6 +63,7 @@ impl Spammer {
                 blockchain.clone(),
                 configs.clone(),
                 sender,
+                slip_sender,
                 tx_payment as Currency,
                 tx_fee as Currency,
             )
@@ -64,11 +71,16 @@ impl Spammer {
         }
     }
 
-    async fn run(&mut self, mut receiver: Receiver<VecDeque<Transaction>>) {
+    async fn run(
+        &mut self,
+        mut receiver: Receiver<VecDeque<Transaction>>,
+        slip_receiver: Option<Receiver<VecDeque<Transaction>>>,
+    ) {
         let mut work_done = false;
         let timer_in_milli;
         let burst_count;
         let stop_after;
+        let profile;
 
         {
             let (configs, _configs_) = lock_for_read!(self.configs, LOCK_ORDER_CONFIGS);
@@ -76,18 +88,18 @@ impl Spammer {
             timer_in_milli = configs.get_spammer_configs().timer_in_milli;
             burst_count = configs.get_spammer_configs().burst_count;
             stop_after = configs.get_spammer_configs().stop_after;
+            profile = configs.get_spammer_configs().profile.clone();
         }
 
         let sender = self.sender_to_network.clone();
-        tokio::spawn(async move {
-            let mut total_count = 0;
-            let mut count = burst_count;
-            loop {
-                if let Some(transactions) = receiver.recv().await {
+        if let Some(profile) = profile {
+            let mut slip_receiver =
+                slip_receiver.expect("slip receiver should be provided with a traffic profile");
+            let slip_tx_sender = sender.clone();
+            tokio::spawn(async move {
+                while let Some(transactions) = slip_receiver.recv().await {
                     for tx in transactions {
-                        count -= 1;
-                        total_count += 1;
-                        sender
+                        slip_tx_sender
                             .send(IoEvent {
                                 event_processor_id: 0,
                                 event_id: 0,
@@ -98,20 +110,49 @@ impl Spammer {
                             })
                             .await
                             .unwrap();
+                    }
+                }
+            });
+            tokio::spawn(run_scenario(
+                TrafficSchedule::new(&profile),
+                receiver,
+                sender,
+            ));
+        } else {
+            tokio::spawn(async move {
+                let mut total_count = 0;
+                let mut count = burst_count;
+                loop {
+                    if let Some(transactions) = receiver.recv().await {
+                        for tx in transactions {
+                            count -= 1;
+                            total_count += 1;
+                            sender
+                                .send(IoEvent {
+                                    event_processor_id: 0,
+                                    event_id: 0,
+                                    event: NetworkEvent::OutgoingNetworkMessageForAll {
+                                        buffer: Message::Transaction(tx).serialize(),
+                                        exceptions: vec![],
+                                    },
+                                })
+                                .await
+                                .unwrap();
 
-                        if count == 0 {
-                            tokio::time::sleep(Duration::from_millis(timer_in_milli)).await;
-                            count = burst_count;
-                        }
-                        if total_count == stop_after {
-                            tokio::time::sleep(Duration::from_millis(10_000)).await;
-                            info!("terminating spammer after sending : {:?} txs", total_count);
-                            std::process::exit(0);
+                            if count == 0 {
+                                tokio::time::sleep(Duration::from_millis(timer_in_milli)).await;
+                                count = burst_count;
+                            }
+                            if total_count == stop_after {
+                                tokio::time::sleep(Duration::from_millis(10_000)).await;
+                                info!("terminating spammer after sending : {:?} txs", total_count);
+                                std::process::exit(0);
+                            }
                         }
                     }
                 }
-            }
-        });
+            });
+        }
         tokio::task::yield_now().await;
         loop {
             work_done = false;
@@ -128,6 +169,81 @@ impl Spammer {
     }
 }
 
+/// Sends the generated txs as per the traffic schedule and reports the achieved throughput
+async fn run_scenario(
+    mut schedule: TrafficSchedule,
+    mut receiver: Receiver<VecDeque<Transaction>>,
+    sender: Sender<IoEvent>,
+) {
+    info!("running spammer with traffic profile");
+    let mut buffer: VecDeque<Transaction> = Default::default();
+    // schedule starts with the first generated txs, so the bootstrapping time is not counted
+    match receiver.recv().await {
+        Some(transactions) => buffer.extend(transactions),
+        None => return,
+    }
+    let start = Instant::now();
+    let mut last_report = Instant::now();
+    let mut total_count: u64 = 0;
+    let mut count_since_report: u64 = 0;
+    let mut expected_since_report: u64 = 0;
+
+    loop {
+        let elapsed = start.elapsed().as_millis() as u64;
+        let result = schedule.get_txs_to_send(elapsed);
+        if result.is_none() {
+            info!(
+                "traffic profile completed. sent : {:?} txs in {:?} ms",
+                total_count, elapsed
+            );
+            tokio::time::sleep(Duration::from_millis(10_000)).await;
+            std::process::exit(0);
+        }
+        let tx_count = result.unwrap();
+        expected_since_report += tx_count;
+
+        while (buffer.len() as u64) < tx_count {
+            match receiver.try_recv() {
+                Ok(transactions) => buffer.extend(transactions),
+                Err(_) => break,
+            }
+        }
+        // if the generator can't keep up we send what we have and it will show in the achieved throughput
+        let send_count = min(tx_count as usize, buffer.len());
+        for tx in buffer.drain(..send_count) {
+            sender
+                .send(IoEvent {
+                    event_processor_id: 0,
+                    event_id: 0,
+                    event: NetworkEvent::OutgoingNetworkMessageForAll {
+                        buffer: Message::Transaction(tx).serialize(),
+                        exceptions: vec![],
+                    },
+                })
+                .await
+                .unwrap();
+        }
+        total_count += send_count as u64;
+        count_since_report += send_count as u64;
+
+        let duration = last_report.elapsed();
+        if duration >= Duration::from_millis(SCENARIO_REPORT_INTERVAL_IN_MS) {
+            info!(
+                "phase : {:?}, expected : {:?} tx/s, achieved : {:?} tx/s, total sent : {:?}",
+                schedule.get_current_phase(),
+                expected_since_report * 1000 / duration.as_millis() as u64,
+                count_since_report * 1000 / duration.as_millis() as u64,
+                total_count
+            );
+            last_report = Instant::now();
+            count_since_report = 0;
+            expected_since_report = 0;
+        }
+
+        tokio::time::sleep(Duration::from_millis(SCENARIO_TICK_IN_MS)).await;
+    }
+}
+
 pub async fn run_spammer(
     wallet: Arc<RwLock<Wallet>>,
     peers: Arc<RwLock<PeerCollection>>,
@@ -136,15 +252,28 @@ pub async fn run_spammer(
     configs: Arc<RwLock<Box<SpammerConfigs>>>,
 ) {
     info!("starting the spammer");
+    let has_profile;
+    {
+        let (configs, _configs_) = lock_for_read!(configs, LOCK_ORDER_CONFIGS);
+        has_profile = configs.get_spammer_configs().profile.is_some();
+    }
     let (sender, receiver) = tokio::sync::mpsc::channel::<VecDeque<Transaction>>(10);
+    let mut slip_receiver = None;
+    let mut slip_sender = sender.clone();
+    if has_profile {
+        let (sender, receiver) = tokio::sync::mpsc::channel::<VecDeque<Transaction>>(10);
+        slip_sender = sender;
+        slip_receiver = Some(receiver);
+    }
     let mut spammer = Spammer::new(
         wallet,
         peers,
         blockchain,
         sender_to_network,
         sender,
+        slip_sender,
         configs,
     )
     .await;
-    spammer.run(receiver).await;
+    spammer.run(receiver, slip_receiver).await;
 }
@@ -20,6 +20,8 @@ use saito_core::core::data::transaction::Transaction;
 use saito_core::core::data::wallet::Wallet;
 use saito_core::{lock_for_read, lock_for_write};
 
+use crate::saito::fan_out::{ChildWallets, FanOutConfig};
+use crate::saito::scenario::{TrafficProfile, TxParameters};
 use crate::saito::time_keeper::TimeKeeper;
 use crate::SpammerConfigs;
 
@@ -41,9 +43,13 @@ pub struct TransactionGenerator {
     public_key: SaitoPublicKey,
     private_key: SaitoPrivateKey,
     sender: Sender<VecDeque<Transaction>>,
+    // slip creation txs are sent through this, so they are not throttled by the traffic profile
+    slip_sender: Sender<VecDeque<Transaction>>,
     tx_payment: Currency,
     tx_fee: Currency,
     peers: Arc<RwLock<PeerCollection>>,
+    profile: Option<TrafficProfile>,
+    fan_out: Option<FanOutConfig>,
 }
 
 impl TransactionGenerator {
@@ -53,16 +59,21 @@ impl TransactionGenerator {
         blockchain: Arc<RwLock<Blockchain>>,
         configuration: Arc<RwLock<Box<SpammerConfigs>>>,
         sender: Sender<VecDeque<Transaction>>,
+        slip_sender: Sender<VecDeque<Transaction>>,
         tx_payment: Currency,
         tx_fee: Currency,
     ) -> Self {
         let mut tx_size = 10;
         let tx_count;
+        let profile;
+        let fan_out;
         {
             let (configs, _configs_) = lock_for_read!(configuration, LOCK_ORDER_CONFIGS);
 
             tx_size = configs.get_spammer_configs().tx_size;
             tx_count = configs.get_spammer_configs().tx_count;
+            profile = configs.get_spammer_configs().profile.clone();
+            fan_out = configs.get_spammer_configs().fan_out.clone();
         }
 
         let mut res = TransactionGenerator {
@@ -76,9 +87,12 @@ impl TransactionGenerator {
             public_key: [0; 33],
             private_key: [0; 32],
             sender,
+            slip_sender,
             tx_payment,
             tx_fee,
             peers,
+            profile,
+            fan_out,
         };
         {
             let (wallet, _wallet_) = lock_for_read!(wallet, LOCK_ORDER_WALLET);
@@ -98,7 +112,11 @@ impl TransactionGenerator {
             }
             GeneratorState::WaitingForBlockChainConfirmation => {
                 if self.check_blockchain_for_confirmation().await {
-                    self.create_test_transactions().await;
+                    if self.fan_out.is_some() {
+                        self.create_fan_out_transactions().await;
+                    } else {
+                        self.create_test_transactions().await;
+                    }
                     self.state = GeneratorState::Done;
                 }
             }
@@ -163,7 +181,7 @@ impl TransactionGenerator {
                     break;
                 }
             }
-            self.sender.send(txs).await.unwrap();
+            self.slip_sender.send(txs).await.unwrap();
 
             self.expected_slip_count = total_output_slips_created;
 
@@ -252,9 +270,13 @@ impl TransactionGenerator {
         let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
         let public_key = self.public_key.clone();
         let count = self.tx_count;
-        let required_balance = (self.tx_payment + self.tx_fee) * count as Currency;
         let payment = self.tx_payment;
         let fee = self.tx_fee;
+        let mut tx_parameters = self.profile.as_ref().map(TxParameters::new);
+        let max_fee = tx_parameters
+            .as_ref()
+            .map_or(fee, |parameters| parameters.get_max_tx_fee());
+        let required_balance = (payment + max_fee) * count as Currency;
         tokio::spawn(async move {
             let sender = sender.clone();
             loop {
@@ -269,8 +291,19 @@ impl TransactionGenerator {
                         assert_ne!(blockchain.utxoset.len(), 0);
                         let mut vec = VecDeque::with_capacity(count as usize);
                         for _ in 0..count {
-                            let mut transaction =
-                                Transaction::create(&mut wallet, public_key, payment, fee);
+                            let mut transaction;
+                            if let Some(parameters) = tx_parameters.as_mut() {
+                                transaction = Transaction::create(
+                                    &mut wallet,
+                                    parameters.get_next_receiver(),
+                                    payment,
+                                    parameters.get_tx_fee(),
+                                );
+                                transaction.message = vec![0; parameters.get_tx_size() as usize];
+                            } else {
+                                transaction =
+                                    Transaction::create(&mut wallet, public_key, payment, fee);
+                            }
                             transaction.generate_total_fees(0, 0);
                             if (transaction.total_in == 0 || transaction.total_out == 0)
                                 && (payment + fee != 0)
@@ -292,19 +325,9 @@ impl TransactionGenerator {
             }
         });
 
-        let mut to_public_key = [0; 33];
-
-        {
-            let (peers, _peers_) = lock_for_read!(self.peers, LOCK_ORDER_PEERS);
-
-            for peer in peers.index_to_peers.iter() {
-                to_public_key = peer.1.public_key.clone().unwrap();
-                break;
-            }
-            assert_eq!(peers.address_to_peers.len(), 1 as usize, "we have assumed connecting to a single node. move add_hop to correct place if not.");
-            assert_ne!(to_public_key, self.public_key);
-        }
+        let to_public_key = self.get_node_public_key().await;
 
+        let use_profile = self.profile.is_some();
         while let Some(mut transactions) = receiver.recv().await {
             let sender = self.sender.clone();
             let tx_size = self.tx_size;
@@ -313,7 +336,10 @@ impl TransactionGenerator {
                 .par_drain(..)
                 .with_min_len(100)
                 .map(|mut transaction| {
-                    transaction.message = vec![0; tx_size as usize]; //;generate_random_bytes(tx_size as u64);
+                    if !use_profile {
+                        // profile sets the message size per transaction
+                        transaction.message = vec![0; tx_size as usize]; //;generate_random_bytes(tx_size as u64);
+                    }
                     transaction.timestamp = time_keeper.get_timestamp_in_ms();
                     transaction.generate(&public_key, 0, 0);
                     transaction.sign(&self.private_key);
@@ -328,4 +354,85 @@ impl TransactionGenerator {
 
         // info!("Test transactions created, count : {:?}", txs.len());
     }
+
+    async fn get_node_public_key(&self) -> SaitoPublicKey {
+        let mut to_public_key = [0; 33];
+
+        let (peers, _peers_) = lock_for_read!(self.peers, LOCK_ORDER_PEERS);
+
+        for peer in peers.index_to_peers.iter() {
+            to_public_key = peer.1.public_key.clone().unwrap();
+            break;
+        }
+        assert_eq!(
+            peers.address_to_peers.len(),
+            1 as usize,
+            "we have assumed connecting to a single node. move add_hop to correct place if not."
+        );
+        assert_ne!(to_public_key, self.public_key);
+        to_public_key
+    }
+
+    /// Distributes the wallet balance to the child wallets and keeps sending payments between them. Payments are
+    /// created from the child slips found in the new blocks, so the tx rate follows the block confirmations.
+    async fn create_fan_out_transactions(&mut self) {
+        let config = self.fan_out.clone().unwrap();
+        info!(
+            "creating payments between : {:?} child wallets. fan in : {:?}, fan out : {:?}",
+            config.wallet_count, config.fan_in, config.fan_out
+        );
+        let to_public_key = self.get_node_public_key().await;
+        let mut child_wallets = ChildWallets::new(&self.private_key, config.clone());
+
+        let mut distribution_txs: VecDeque<Transaction>;
+        {
+            let (blockchain, _blockchain_) = lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+            let (mut wallet, _wallet_) = lock_for_write!(self.wallet, LOCK_ORDER_WALLET);
+
+            let amount_per_wallet = (wallet.get_available_balance()
+                / config.wallet_count as Currency)
+                .saturating_sub(self.tx_fee);
+            distribution_txs = child_wallets
+                .create_distribution_transactions(
+                    &mut wallet,
+                    &blockchain,
+                    amount_per_wallet,
+                    self.tx_fee,
+                )
+                .into();
+        }
+        for transaction in distribution_txs.iter_mut() {
+            transaction.add_hop(&self.private_key, &self.public_key, &to_public_key);
+        }
+        info!(
+            "distributing funds to child wallets with {:?} txs",
+            distribution_txs.len()
+        );
+        self.slip_sender.send(distribution_txs).await.unwrap();
+
+        let mut last_slip_count = 0;
+        loop {
+            {
+                let (blockchain, _blockchain_) =
+                    lock_for_read!(self.blockchain, LOCK_ORDER_BLOCKCHAIN);
+                child_wallets.scan_blocks(&blockchain);
+            }
+            let slip_count = child_wallets.get_slip_count();
+            if slip_count != last_slip_count {
+                debug!("child wallets have {:?} unspent slips", slip_count);
+                last_slip_count = slip_count;
+            }
+            let mut transactions: VecDeque<Transaction> = child_wallets
+                .create_payments(self.tx_count, self.tx_fee, self.tx_size)
+                .into();
+            if transactions.is_empty() {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+            for transaction in transactions.iter_mut() {
+                transaction.add_hop(&self.private_key, &self.public_key, &to_public_key);
+            }
+            self.sender.send(transactions).await.unwrap();
+        }
+    }
 }
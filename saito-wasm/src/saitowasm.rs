@@ -9,29 +9,46 @@ use std::time::Duration;
 use js_sys::{Array, BigInt, Uint8Array};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc::Receiver;
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::Mutex;
 use wasm_bindgen::prelude::*;
 
 use saito_core::common::defs::{
-    push_lock, Currency, SaitoHash, SaitoPublicKey, SaitoSignature, LOCK_ORDER_PEERS,
-    LOCK_ORDER_WALLET,
+    push_lock, Currency, SaitoHash, SaitoPublicKey, SaitoSignature, LOCK_ORDER_BLOCKCHAIN,
+    LOCK_ORDER_MEMPOOL, LOCK_ORDER_PEERS, LOCK_ORDER_WALLET,
 };
 use saito_core::common::process_event::ProcessEvent;
+use saito_core::common::runtime::{Receiver, RwLock};
 use saito_core::core::consensus_thread::{ConsensusEvent, ConsensusStats, ConsensusThread};
+use saito_core::core::data::audit_log::AuditLog;
+use saito_core::core::data::block_announcement_cache::{
+    BlockAnnouncementCache, BLOCK_ANNOUNCEMENT_CACHE_SIZE, BLOCK_ANNOUNCEMENT_CACHE_TTL_IN_MS,
+};
+use saito_core::core::data::block_queue::BlockQueueStatus;
 use saito_core::core::data::blockchain::Blockchain;
+use saito_core::core::data::blockchain_snapshot::BlockchainSnapshot;
 use saito_core::core::data::blockchain_sync_state::BlockchainSyncState;
 use saito_core::core::data::configuration::Configuration;
 use saito_core::core::data::context::Context;
+use saito_core::core::data::key_format::KeyFormat;
+use saito_core::core::data::memory_report::MemoryReport;
 use saito_core::core::data::mempool::Mempool;
+use saito_core::core::data::mining_status::MiningStatus;
 use saito_core::core::data::network::Network;
 use saito_core::core::data::peer_collection::PeerCollection;
+use saito_core::core::data::peer_events::PeerEventListeners;
+use saito_core::core::data::peer_message_stats::PeerMessageStats;
+use saito_core::core::data::peer_quorum::ConnectionStatus;
+use saito_core::core::data::stale_chain_watchdog::StaleChainWatchdog;
 use saito_core::core::data::storage::Storage;
 use saito_core::core::data::transaction::Transaction;
+use saito_core::core::data::transaction_receipts::TransactionReceipts;
+use saito_core::core::data::trusted_sync::TrustedSync;
+use saito_core::core::data::tx_generator::TransactionGenerator;
 use saito_core::core::data::wallet::Wallet;
+use saito_core::core::data::webhook::Webhooks;
 use saito_core::core::mining_thread::{MiningEvent, MiningThread};
 use saito_core::core::routing_thread::{RoutingEvent, RoutingStats, RoutingThread};
-use saito_core::lock_for_write;
+use saito_core::{lock_for_read, lock_for_write};
 
 use crate::wasm_configuration::WasmConfiguration;
 use crate::wasm_io_handler::WasmIoHandler;
@@ -96,12 +113,17 @@ pub fn new() -> SaitoWasm {
         mempool: Arc::new(RwLock::new(Mempool::new(public_key, private_key))),
         wallet: wallet.clone(),
         configuration: configuration.clone(),
+        mining_status: Arc::new(std::sync::Mutex::new(MiningStatus::new())),
+        connection_status: Arc::new(std::sync::Mutex::new(ConnectionStatus::new())),
+        transaction_receipts: Arc::new(std::sync::Mutex::new(TransactionReceipts::default())),
+        block_queue_status: Arc::new(std::sync::Mutex::new(BlockQueueStatus::default())),
+        blockchain_snapshot: Arc::new(std::sync::Mutex::new(Arc::new(BlockchainSnapshot::new()))),
     };
 
-    let (sender_to_mempool, receiver_in_mempool) = tokio::sync::mpsc::channel(100);
-    let (sender_to_blockchain, receiver_in_blockchain) = tokio::sync::mpsc::channel(100);
-    let (sender_to_miner, receiver_in_miner) = tokio::sync::mpsc::channel(100);
-    let (sender_to_stat, receiver_in_stats) = tokio::sync::mpsc::channel(100);
+    let (sender_to_mempool, receiver_in_mempool) = saito_core::common::runtime::channel(100);
+    let (sender_to_blockchain, receiver_in_blockchain) = saito_core::common::runtime::channel(100);
+    let (sender_to_miner, receiver_in_miner) = saito_core::common::runtime::channel(100);
+    let (sender_to_stat, receiver_in_stats) = saito_core::common::runtime::channel(100);
 
     SaitoWasm {
         consensus_event_processor: RoutingThread {
@@ -118,24 +140,42 @@ pub fn new() -> SaitoWasm {
                 context.wallet.clone(),
             ),
             reconnection_timer: 0,
+            fetch_timeout_timer: 0,
             stats: RoutingStats::new(sender_to_stat.clone()),
             public_key,
             senders_to_verification: vec![],
             last_verification_thread_index: 0,
             stat_sender: sender_to_stat.clone(),
             blockchain_sync_state: BlockchainSyncState::new(10),
+            stale_chain_watchdog: StaleChainWatchdog::new(0),
+            webhooks: Webhooks::new(Default::default(), None),
+            reconnect_on_stale_chain: false,
+            block_announcement_cache: BlockAnnouncementCache::new(
+                BLOCK_ANNOUNCEMENT_CACHE_TTL_IN_MS,
+                BLOCK_ANNOUNCEMENT_CACHE_SIZE,
+            ),
+            trusted_sync: TrustedSync::new(),
+            peer_message_stats: PeerMessageStats::new(),
+            storage: Storage::new(Box::new(WasmIoHandler {})),
+            connection_status: context.connection_status.clone(),
+            block_queue_status: context.block_queue_status.clone(),
+            peer_events: PeerEventListeners::new(),
         },
         routing_event_processor: ConsensusThread {
             mempool: context.mempool.clone(),
             blockchain: context.blockchain.clone(),
             wallet: context.wallet.clone(),
             generate_genesis_block: false,
+            issuance: None,
+            dev_mode: false,
+            submit_golden_tickets: false,
             sender_to_router: sender_to_blockchain.clone(),
             sender_to_miner: sender_to_miner.clone(),
+            sender_to_wallet_updater: None,
             // sender_global: (),
-            block_producing_timer: 0,
-            tx_producing_timer: 0,
-            create_test_tx: false,
+            next_block_production_time: None,
+            wallet_check_timer: 0,
+            tx_generator: TransactionGenerator::new(Default::default()),
             time_keeper: Box::new(WasmTimeKeeper {}),
             network: Network::new(
                 Box::new(WasmIoHandler {}),
@@ -146,6 +186,13 @@ pub fn new() -> SaitoWasm {
             stats: ConsensusStats::new(sender_to_stat.clone()),
             txs_for_mempool: vec![],
             stat_sender: sender_to_stat.clone(),
+            audit_log: AuditLog::new(Default::default()),
+            webhooks: Webhooks::new(Default::default(), None),
+            connection_status: context.connection_status.clone(),
+            block_production_quorum: Default::default(),
+            transaction_receipts: context.transaction_receipts.clone(),
+            block_queue_status: context.block_queue_status.clone(),
+            blockchain_snapshot: context.blockchain_snapshot.clone(),
         },
         mining_event_processor: MiningThread {
             wallet: context.wallet.clone(),
@@ -153,11 +200,14 @@ pub fn new() -> SaitoWasm {
             sender_to_mempool: sender_to_mempool.clone(),
             time_keeper: Box::new(WasmTimeKeeper {}),
             miner_active: false,
+            mining_enabled: true,
             target: [0; 32],
             difficulty: 0,
             public_key: [0; 33],
             mined_golden_tickets: 0,
+            hash_count: 0,
             stat_sender: sender_to_stat.clone(),
+            mining_status: context.mining_status.clone(),
         },
         receiver_in_blockchain,
         receiver_in_mempool,
@@ -200,6 +250,74 @@ pub async fn send_transaction(transaction: WasmTransaction) -> Result<JsValue, J
     Ok(JsValue::from("test"))
 }
 
+/// Publishes the payload on chain with a data transaction. Returns the signature of the transaction
+#[wasm_bindgen]
+pub async fn publish_data(payload: Uint8Array, fee: u64) -> Result<JsValue, JsValue> {
+    let mut saito = SAITO.lock().await;
+    let transaction;
+    {
+        let (mut wallet, _wallet_) = lock_for_write!(saito.context.wallet, LOCK_ORDER_WALLET);
+        transaction = wallet.create_data_transaction(payload.to_vec(), fee);
+    }
+    let transaction = transaction.ok_or_else(|| JsValue::from("cannot create data transaction"))?;
+    let signature = hex::encode(transaction.get_signature());
+    saito
+        .routing_event_processor
+        .process_event(ConsensusEvent::NewTransaction {
+            transaction,
+            peer_index: None,
+        })
+        .await;
+    Ok(JsValue::from(signature))
+}
+
+/// Returns the payloads of the data transactions in the longest chain between the given block ids
+#[wasm_bindgen]
+pub async fn get_data_payloads(from_block_id: u64, to_block_id: u64) -> Result<Array, JsValue> {
+    let saito = SAITO.lock().await;
+    let (blockchain, _blockchain_) =
+        lock_for_read!(saito.context.blockchain, LOCK_ORDER_BLOCKCHAIN);
+    let payloads = Array::new();
+    for (_, transaction) in blockchain.get_data_transactions(from_block_id, to_block_id) {
+        payloads.push(&Uint8Array::from(transaction.message.as_slice()));
+    }
+    Ok(payloads)
+}
+
+/// Returns the estimated memory usage of the utxoset, blocks, mempool transactions and wallet slips as json
+#[wasm_bindgen]
+pub async fn get_memory_report() -> Result<JsValue, JsValue> {
+    let saito = SAITO.lock().await;
+    let (blockchain, _blockchain_) =
+        lock_for_read!(saito.context.blockchain, LOCK_ORDER_BLOCKCHAIN);
+    let (mempool, _mempool_) = lock_for_read!(saito.context.mempool, LOCK_ORDER_MEMPOOL);
+    let (wallet, _wallet_) = lock_for_read!(saito.context.wallet, LOCK_ORDER_WALLET);
+    let report = MemoryReport::generate(&blockchain, &mempool, &wallet);
+    Ok(JsValue::from(report.to_json()))
+}
+
+/// Replaces the wallet keys with the given private key and rescans the chain for its slips. Returns the public key
+#[wasm_bindgen]
+pub async fn import_private_key(key: String, format: String) -> Result<JsValue, JsValue> {
+    let mut saito = SAITO.lock().await;
+    let format: KeyFormat = format
+        .parse()
+        .map_err(|_| JsValue::from("unknown key format"))?;
+    let public_key;
+    {
+        let (mut wallet, _wallet_) = lock_for_write!(saito.context.wallet, LOCK_ORDER_WALLET);
+        wallet
+            .import_private_key(&key, format)
+            .map_err(|error| JsValue::from(format!("failed importing key : {:?}", error)))?;
+        public_key = hex::encode(wallet.public_key);
+    }
+    saito
+        .routing_event_processor
+        .process_event(ConsensusEvent::RescanWallet)
+        .await;
+    Ok(JsValue::from(public_key))
+}
+
 #[wasm_bindgen]
 pub fn get_latest_block_hash() -> Result<JsValue, JsValue> {
     Ok(JsValue::from("latestblockhash"))
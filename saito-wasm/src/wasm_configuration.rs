@@ -1,4 +1,6 @@
-use saito_core::core::data::configuration::{Configuration, Endpoint, PeerConfig, Server};
+use saito_core::core::data::configuration::{
+    Configuration, DifficultyConfig, Endpoint, NodeRole, PeerConfig, Server, DEFAULT_NETWORK_ID,
+};
 
 pub struct WasmConfiguration {
     server: Server,
@@ -17,11 +19,40 @@ impl WasmConfiguration {
                     port: 12101,
                     protocol: "http".to_string(),
                 },
+                network: None,
+                network_id: DEFAULT_NETWORK_ID.to_string(),
                 verification_threads: 2,
+                verification: Default::default(),
                 channel_size: 1000,
                 stat_timer_in_ms: 10000,
                 thread_sleep_time_in_ms: 10,
                 block_fetch_batch_size: 0,
+                block_fetch_limit_per_peer: 0,
+                block_queue_limit: 0,
+                node_role: NodeRole::Miner,
+                full_block_cache_size: 0,
+                difficulty: DifficultyConfig::Consensus,
+                stale_chain_threshold_in_ms: 0,
+                reconnect_on_stale_chain: false,
+                tx_generator: Default::default(),
+                transaction_index: false,
+                tls: None,
+                peer_slots: Default::default(),
+                archive_dir: None,
+                dev_mode: false,
+                max_block_future_drift_in_ms: 0,
+                trusted_sync: Default::default(),
+                max_orphan_blocks: 0,
+                orphan_block_expiry_in_ms: 0,
+                max_block_size: 0,
+                message_compression: false,
+                dns_seeds: Default::default(),
+                consensus_health: Default::default(),
+                audit_log: Default::default(),
+                webhooks: Default::default(),
+                block_production_quorum: Default::default(),
+                key_rotation_proof: None,
+                issuance_file: None,
             },
             peers: vec![],
         }